@@ -24,6 +24,9 @@ pub struct SendTextParams {
     pub part_index: Option<i32>,
     #[serde(rename = "ddScan", skip_serializing_if = "Option::is_none")]
     pub dd_scan: Option<bool>,
+    /// Link preview metadata for a URL in the message.
+    #[serde(rename = "payloadData", skip_serializing_if = "Option::is_none")]
+    pub payload_data: Option<serde_json::Value>,
 }
 
 /// Parameters for sending a reaction / tapback.
@@ -371,10 +374,12 @@ mod tests {
             selected_message_guid: None,
             part_index: None,
             dd_scan: None,
+            payload_data: None,
         };
         let json = serde_json::to_value(&params).unwrap();
         assert_eq!(json["chatGuid"], "iMessage;-;+1234");
         assert!(json.get("effectId").is_none());
+        assert!(json.get("payloadData").is_none());
     }
 
     #[test]
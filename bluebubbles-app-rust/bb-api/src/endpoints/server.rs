@@ -14,6 +14,7 @@ pub struct ServerInfo {
     pub helper_connected: Option<bool>,
     pub proxy_service: Option<String>,
     pub detected_icloud: Option<String>,
+    pub detected_imessage: Option<String>,
     pub local_ipv4s: Option<Vec<String>>,
     pub local_ipv6s: Option<Vec<String>>,
 }
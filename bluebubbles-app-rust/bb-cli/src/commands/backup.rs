@@ -1,4 +1,5 @@
 //! Backup commands - export/import settings and themes from the server.
+//! Settings backups and local export files also carry the chat folders.
 
 use clap::Subcommand;
 use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
@@ -6,6 +7,8 @@ use console::style;
 
 use bb_core::config::ConfigHandle;
 use bb_core::error::BbResult;
+use bb_services::backup::BackupService;
+use bb_services::event_bus::EventBus;
 use crate::OutputFormat;
 
 #[derive(Subcommand)]
@@ -42,12 +45,12 @@ pub enum BackupAction {
         /// Name of the settings backup to delete.
         name: String,
     },
-    /// Export server backups (themes + settings) to a local JSON file.
+    /// Export server backups (themes + settings) and local chat folders to a JSON file.
     Export {
         /// Output file path for the combined backup.
         path: String,
     },
-    /// Import backups from a local JSON file to the server.
+    /// Import backups from a local JSON file to the server, restoring chat folders locally.
     Import {
         /// Input file path containing backup data.
         path: String,
@@ -229,6 +232,7 @@ pub async fn run(config: ConfigHandle, action: BackupAction, format: OutputForma
                     .map_err(|e| bb_core::error::BbError::Serialization(format!("invalid JSON: {e}")))?
             } else {
                 // Use current local config as the settings data
+                let folders = local_backup(&config).await?.export_folders()?;
                 let cfg = config.read().await;
                 serde_json::json!({
                     "server": {
@@ -251,6 +255,7 @@ pub async fn run(config: ConfigHandle, action: BackupAction, format: OutputForma
                         "use_24hr_format": cfg.display.use_24hr_format,
                         "redacted_mode": cfg.display.redacted_mode,
                     },
+                    "chat_folders": folders,
                 })
             };
 
@@ -307,10 +312,12 @@ pub async fn run(config: ConfigHandle, action: BackupAction, format: OutputForma
 
             let themes = api.get_theme_backup().await?;
             let settings = api.get_settings_backup().await?;
+            let folders = local_backup(&config).await?.export_folders()?;
 
             let combined = serde_json::json!({
                 "themes": themes,
                 "settings": settings,
+                "chat_folders": folders,
                 "exported_at": chrono::Utc::now().to_rfc3339(),
             });
 
@@ -366,12 +373,15 @@ pub async fn run(config: ConfigHandle, action: BackupAction, format: OutputForma
                 }
             }
 
+            let folder_count = local_backup(&config).await?.import_folders(&data)?;
+
             match format {
                 OutputFormat::Json => {
                     println!("{}", serde_json::json!({
                         "path": path,
                         "imported": true,
                         "count": imported_count,
+                        "chat_folders": folder_count,
                     }));
                 }
                 OutputFormat::Text => {
@@ -381,6 +391,9 @@ pub async fn run(config: ConfigHandle, action: BackupAction, format: OutputForma
                         imported_count,
                         path
                     );
+                    if folder_count > 0 {
+                        println!("  {} Restored {} chat folder(s)", style("OK").green().bold(), folder_count);
+                    }
                 }
             }
         }
@@ -388,3 +401,9 @@ pub async fn run(config: ConfigHandle, action: BackupAction, format: OutputForma
 
     Ok(())
 }
+
+/// A backup service over the local database, for chat folders.
+async fn local_backup(config: &ConfigHandle) -> BbResult<BackupService> {
+    let db = super::init_database(config).await?;
+    Ok(BackupService::new(config.clone(), EventBus::new(16)).with_database(db))
+}
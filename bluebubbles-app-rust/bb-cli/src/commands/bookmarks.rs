@@ -0,0 +1,195 @@
+//! Bookmark commands: tag and annotate messages to come back to.
+
+use clap::Subcommand;
+use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
+use console::style;
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_models::BookmarkFilter;
+use bb_services::event_bus::EventBus;
+use bb_services::message::{BookmarkedMessage, MessageService};
+use crate::OutputFormat;
+
+#[derive(Subcommand)]
+pub enum BookmarksAction {
+    /// List bookmarks, most recently changed first.
+    List {
+        /// Only bookmarks with this tag.
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Only bookmarks in this chat (GUID).
+        #[arg(short, long)]
+        chat: Option<String>,
+        /// Only bookmarks whose note, tags or text contain this.
+        #[arg(short, long)]
+        search: Option<String>,
+        /// Maximum number of bookmarks.
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: i64,
+    },
+    /// Bookmark a message, or add tags and a note to its bookmark.
+    Add {
+        /// Message GUID.
+        guid: String,
+        /// Tag to add (repeatable).
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        /// Note to attach.
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Set a bookmark's note, or clear it when omitted.
+    Note {
+        /// Message GUID.
+        guid: String,
+        /// Note text.
+        note: Option<String>,
+    },
+    /// Add tags to a bookmark.
+    Tag {
+        /// Message GUID.
+        guid: String,
+        /// Tags to add.
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a bookmark.
+    Untag {
+        /// Message GUID.
+        guid: String,
+        /// Tags to remove.
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove a bookmark with its tags and note.
+    Remove {
+        /// Message GUID.
+        guid: String,
+    },
+    /// List tags in use.
+    Tags,
+}
+
+pub async fn run(config: ConfigHandle, action: BookmarksAction, format: OutputFormat) -> BbResult<()> {
+    let db = super::init_database(&config).await?;
+    let service = MessageService::new(db.clone(), EventBus::new(16));
+
+    match action {
+        BookmarksAction::List { tag, chat, search, limit } => {
+            let chat_id = match chat {
+                Some(guid) => {
+                    let conn = db.conn()?;
+                    Some(
+                        bb_models::queries::find_chat_by_guid(&conn, &guid)?
+                            .and_then(|c| c.id)
+                            .ok_or(BbError::ChatNotFound(guid))?,
+                    )
+                }
+                None => None,
+            };
+            let bookmarks = service.list_bookmarks(&BookmarkFilter {
+                tag,
+                chat_id,
+                query: search,
+                limit,
+            })?;
+            match format {
+                OutputFormat::Json => print_json(&bookmarks),
+                OutputFormat::Text => {
+                    if bookmarks.is_empty() {
+                        println!("No bookmarks.");
+                        return Ok(());
+                    }
+                    let mut table = Table::new();
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic)
+                        .set_header(vec!["GUID", "Chat", "Message", "Tags", "Note"]);
+                    for b in &bookmarks {
+                        table.add_row(vec![
+                            b.bookmark.message_guid.clone(),
+                            super::truncate(&b.chat_title, 20),
+                            super::truncate(b.message.text.as_deref().unwrap_or(""), 40),
+                            tag_list(&b.bookmark.tags),
+                            super::truncate(b.bookmark.note.as_deref().unwrap_or(""), 40),
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        BookmarksAction::Add { guid, tags, note } => {
+            let b = service.bookmark_message(&guid, &tags, note.as_deref())?;
+            print_saved(&b, format);
+        }
+        BookmarksAction::Note { guid, note } => {
+            let b = service.set_bookmark_note(&guid, note.as_deref())?;
+            print_saved(&b, format);
+        }
+        BookmarksAction::Tag { guid, tags } => {
+            let b = service.add_bookmark_tags(&guid, &tags)?;
+            print_saved(&b, format);
+        }
+        BookmarksAction::Untag { guid, tags } => {
+            let b = service.remove_bookmark_tags(&guid, &tags)?;
+            print_saved(&b, format);
+        }
+        BookmarksAction::Remove { guid } => {
+            let removed = service.remove_bookmark(&guid)?;
+            match format {
+                OutputFormat::Json => print_json(&serde_json::json!({ "removed": removed })),
+                OutputFormat::Text if removed => {
+                    println!("  {} Bookmark removed", style("✓").green());
+                }
+                OutputFormat::Text => println!("  {guid} is not bookmarked."),
+            }
+        }
+        BookmarksAction::Tags => {
+            let tags = service.bookmark_tags()?;
+            match format {
+                OutputFormat::Json => {
+                    let json: Vec<_> = tags
+                        .iter()
+                        .map(|(tag, count)| serde_json::json!({ "tag": tag, "count": count }))
+                        .collect();
+                    print_json(&json);
+                }
+                OutputFormat::Text => {
+                    if tags.is_empty() {
+                        println!("No bookmark tags.");
+                    }
+                    for (tag, count) in &tags {
+                        println!("  {} {}", style(format!("#{tag}")).cyan(), style(count).dim());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn tag_list(tags: &[String]) -> String {
+    tags.iter().map(|t| format!("#{t}")).collect::<Vec<_>>().join(" ")
+}
+
+fn print_saved(b: &BookmarkedMessage, format: OutputFormat) {
+    match format {
+        OutputFormat::Json => print_json(b),
+        OutputFormat::Text => {
+            println!("  {} Bookmarked {}", style("✓").green(), b.bookmark.message_guid);
+            if !b.bookmark.tags.is_empty() {
+                println!("    Tags: {}", style(tag_list(&b.bookmark.tags)).cyan());
+            }
+            if let Some(ref note) = b.bookmark.note {
+                println!("    Note: {note}");
+            }
+        }
+    }
+}
+
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
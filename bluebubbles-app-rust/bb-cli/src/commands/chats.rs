@@ -5,7 +5,11 @@ use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, Cont
 use console::style;
 
 use bb_core::config::ConfigHandle;
-use bb_core::error::BbResult;
+use bb_core::error::{BbError, BbResult};
+use bb_models::FolderRule;
+use bb_services::event_bus::EventBus;
+use bb_services::folder::FolderService;
+use bb_services::outbox::{OutboxService, SubmitStatus};
 use crate::OutputFormat;
 
 /// Sort options for chat listing.
@@ -35,6 +39,14 @@ pub enum ChatsAction {
         /// Include archived chats.
         #[arg(long)]
         archived: bool,
+        /// Only chats in this folder (name or ID).
+        #[arg(short, long)]
+        folder: Option<String>,
+    },
+    /// Manage chat folders.
+    Folders {
+        #[command(subcommand)]
+        action: FoldersAction,
     },
     /// Get details for a specific chat.
     Get {
@@ -46,24 +58,99 @@ pub enum ChatsAction {
         /// Search query.
         query: String,
     },
-    /// Mark a chat as read.
+    /// Mark a chat as read. Queued if the server is unreachable.
     Read {
         /// Chat GUID.
         guid: String,
     },
+    /// List chat changes waiting to be sent to the server.
+    Outbox {
+        /// Send the queued changes now.
+        #[arg(long)]
+        replay: bool,
+    },
+    /// Export a conversation transcript with its attachments.
+    Export {
+        /// Chat GUID.
+        guid: String,
+        /// Output format: html, md, json, or txt.
+        #[arg(short, long, default_value = "html")]
+        format: String,
+        /// Output directory (attachments go in an `attachments/` folder inside it).
+        #[arg(short, long, default_value = ".")]
+        out: std::path::PathBuf,
+        /// Only include messages on or after this date (YYYY-MM-DD, ISO 8601, or epoch ms).
+        #[arg(long)]
+        after: Option<String>,
+        /// Only include messages before this date (YYYY-MM-DD, ISO 8601, or epoch ms).
+        #[arg(long)]
+        before: Option<String>,
+        /// Replace names with pseudonyms, mask phone numbers and emails, and skip attachments.
+        #[arg(long)]
+        redact: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum FoldersAction {
+    /// List folders with their chat and unread counts.
+    List,
+    /// Create a folder. With --rule it is a smart folder.
+    Create {
+        /// Folder name.
+        name: String,
+        /// Smart folder rule, e.g. "group,members>5" or "unknown".
+        /// Terms: group, direct, unread, unknown, pinned, muted, members>=N,
+        /// service=NAME, name~TEXT; prefix with ! to negate, comma for AND.
+        #[arg(short, long)]
+        rule: Option<String>,
+        /// Folder color (e.g. #4a90e2).
+        #[arg(short, long)]
+        color: Option<String>,
+    },
+    /// Rename a folder.
+    Rename {
+        /// Folder name or ID.
+        folder: String,
+        /// New name.
+        name: String,
+    },
+    /// Delete a folder. Its chats are kept.
+    Delete {
+        /// Folder name or ID.
+        folder: String,
+    },
+    /// Add a chat to a folder.
+    Add {
+        /// Folder name or ID.
+        folder: String,
+        /// Chat GUID.
+        guid: String,
+    },
+    /// Remove a chat from a folder.
+    Remove {
+        /// Folder name or ID.
+        folder: String,
+        /// Chat GUID.
+        guid: String,
+    },
 }
 
 pub async fn run(config: ConfigHandle, action: ChatsAction, format: OutputFormat) -> BbResult<()> {
     let db = super::init_database(&config).await?;
 
     match action {
-        ChatsAction::List { limit, page, sort, archived } => {
+        ChatsAction::List { limit, page, sort, archived, folder } => {
             let offset = (page.max(1) - 1) * limit;
             let conn = db.conn()?;
+            let folder = match folder {
+                Some(name) => Some(find_folder(&FolderService::new(db.clone(), EventBus::new(16)), &name)?),
+                None => None,
+            };
 
             // Use the detailed query for table output
             let details = bb_models::queries::list_chats_with_details(
-                &conn, offset, limit, archived,
+                &conn, offset, limit, archived, folder.as_ref(),
             )?;
 
             // Sort in-memory for non-date sorts
@@ -102,7 +189,12 @@ pub async fn run(config: ConfigHandle, action: ChatsAction, format: OutputFormat
                     if details.is_empty() {
                         println!("No chats found.");
                     } else {
-                        let total_count = bb_models::queries::count_chats(&conn).unwrap_or(0);
+                        let total_count = match folder {
+                            Some(ref f) => bb_models::queries::folder_summary(&conn, f.clone())
+                                .map(|s| s.chat_count)
+                                .unwrap_or(0),
+                            None => bb_models::queries::count_chats(&conn).unwrap_or(0),
+                        };
 
                         let mut table = Table::new();
                         table
@@ -145,6 +237,9 @@ pub async fn run(config: ConfigHandle, action: ChatsAction, format: OutputFormat
                 }
             }
         }
+        ChatsAction::Folders { action } => {
+            run_folders(FolderService::new(db, EventBus::new(16)), action, format)?;
+        }
         ChatsAction::Get { guid } => {
             let conn = db.conn()?;
             match bb_models::queries::find_chat_by_guid(&conn, &guid)? {
@@ -246,10 +341,186 @@ pub async fn run(config: ConfigHandle, action: ChatsAction, format: OutputFormat
         }
         ChatsAction::Read { guid } => {
             let api = super::create_api_client(&config).await?;
-            api.mark_chat_read(&guid).await?;
-            println!("{} Chat marked as read: {guid}", style("OK").green().bold());
+            let service = bb_services::chat::ChatService::new(db, EventBus::new(16));
+            match service.mark_read(&api, &guid).await? {
+                SubmitStatus::Sent => {
+                    println!("{} Chat marked as read: {guid}", style("OK").green().bold());
+                }
+                SubmitStatus::Queued => println!(
+                    "{} Chat marked as read locally; the server is unreachable, so it is queued: {guid}",
+                    style("QUEUED").yellow().bold()
+                ),
+            }
+        }
+        ChatsAction::Outbox { replay } => {
+            let service = OutboxService::new(db, EventBus::new(16));
+            if replay {
+                let api = super::create_api_client(&config).await?;
+                let report = service.replay(&api).await?;
+                match format {
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+                    }
+                    OutputFormat::Text => println!(
+                        "{} {} sent, {} rejected and rolled back, {} still queued",
+                        style("OK").green().bold(),
+                        report.applied,
+                        report.rolled_back,
+                        report.remaining
+                    ),
+                }
+                return Ok(());
+            }
+
+            let pending = service.pending()?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&pending).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if pending.is_empty() {
+                        println!("No queued chat changes.");
+                        return Ok(());
+                    }
+                    let mut table = Table::new();
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic)
+                        .set_header(vec!["ID", "Chat", "Change", "Attempts", "Last Error"]);
+                    for e in &pending {
+                        table.add_row(vec![
+                            e.id.unwrap_or_default().to_string(),
+                            super::truncate(&e.chat_guid, 30),
+                            e.mutation.describe(),
+                            e.attempts.to_string(),
+                            super::truncate(e.last_error.as_deref().unwrap_or("-"), 40),
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        ChatsAction::Export { guid, format: export_format, out, after, before, redact } => {
+            use bb_services::export::{parse_date_bound, ExportFormat, ExportOptions, ExportService};
+
+            let mut options = ExportOptions::new(export_format.parse::<ExportFormat>()?, out);
+            options.after = after.as_deref().map(parse_date_bound).transpose()?;
+            options.before = before.as_deref().map(parse_date_bound).transpose()?;
+            options.redact = redact;
+
+            let event_bus = bb_services::event_bus::EventBus::new(16);
+            let service = ExportService::new(db, event_bus, super::attachment_cache_dir());
+            let summary = service.export_chat(&guid, &options)?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&summary).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "{} Exported {} messages to {}",
+                        style("OK").green().bold(),
+                        summary.message_count,
+                        summary.transcript_path.display()
+                    );
+                    if summary.attachments_copied > 0 || summary.attachments_missing > 0 {
+                        println!(
+                            "  Attachments: {} copied, {} not available locally",
+                            summary.attachments_copied, summary.attachments_missing
+                        );
+                    }
+                }
+            }
         }
     }
 
     Ok(())
 }
+
+fn find_folder(service: &FolderService, reference: &str) -> BbResult<bb_models::ChatFolder> {
+    service
+        .find_folder(reference)?
+        .ok_or_else(|| BbError::InvalidInput(format!("no folder named {reference}")))
+}
+
+fn run_folders(service: FolderService, action: FoldersAction, format: OutputFormat) -> BbResult<()> {
+    match action {
+        FoldersAction::List => {
+            let folders = service.list_folders()?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&folders).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if folders.is_empty() {
+                        println!("No folders.");
+                        return Ok(());
+                    }
+                    let mut table = Table::new();
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic)
+                        .set_header(vec!["ID", "Folder", "Rule", "Chats", "Unread"]);
+                    for s in &folders {
+                        let unread = if s.unread_count > 0 {
+                            format!("{} in {} chats", s.unread_count, s.unread_chats)
+                        } else {
+                            "-".to_string()
+                        };
+                        table.add_row(vec![
+                            s.folder.id.unwrap_or_default().to_string(),
+                            s.folder.name.clone(),
+                            s.folder.rule.as_ref().map(|r| r.to_string()).unwrap_or_else(|| "-".into()),
+                            s.chat_count.to_string(),
+                            unread,
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        FoldersAction::Create { name, rule, color } => {
+            let folder = match rule {
+                Some(rule) => service.create_smart_folder(&name, FolderRule::parse(&rule)?, color.as_deref())?,
+                None => service.create_folder(&name, color.as_deref())?,
+            };
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&folder).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    println!("{} Created folder {}", style("OK").green().bold(), folder.name);
+                    if let Some(ref rule) = folder.rule {
+                        println!("  Rule: {rule}");
+                    }
+                }
+            }
+        }
+        FoldersAction::Rename { folder, name } => {
+            let id = find_folder(&service, &folder)?.id.unwrap_or_default();
+            let renamed = service.rename_folder(id, &name)?;
+            println!("{} Renamed folder to {}", style("OK").green().bold(), renamed.name);
+        }
+        FoldersAction::Delete { folder } => {
+            let folder = find_folder(&service, &folder)?;
+            service.delete_folder(folder.id.unwrap_or_default())?;
+            println!("{} Deleted folder {}", style("OK").green().bold(), folder.name);
+        }
+        FoldersAction::Add { folder, guid } => {
+            let folder = find_folder(&service, &folder)?;
+            service.add_chat(folder.id.unwrap_or_default(), &guid)?;
+            println!("{} Added {guid} to {}", style("OK").green().bold(), folder.name);
+        }
+        FoldersAction::Remove { folder, guid } => {
+            let folder = find_folder(&service, &folder)?;
+            if service.remove_chat(folder.id.unwrap_or_default(), &guid)? {
+                println!("{} Removed {guid} from {}", style("OK").green().bold(), folder.name);
+            } else {
+                println!("  {guid} is not in {}.", folder.name);
+            }
+        }
+    }
+    Ok(())
+}
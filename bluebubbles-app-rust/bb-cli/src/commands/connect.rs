@@ -1,13 +1,30 @@
 //! Connect command - establish a persistent connection to the server.
+//!
+//! With `--notify`, incoming messages are saved to the local database and
+//! run through the notification rules; digests are shared with the desktop
+//! app through the same database. On Linux, notifications carry reply,
+//! mark as read and mute actions, which are performed against the server.
 
 use console::style;
 use dialoguer::{Input, Password};
-use tracing::error;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{error, warn};
 
+use bb_api::ApiClient;
 use bb_core::config::ConfigHandle;
 use bb_core::error::BbResult;
 use bb_core::platform::Platform;
-use bb_socket::{EventDispatcher, SocketManager};
+use bb_models::Database;
+use bb_services::chat::ChatService;
+use bb_services::message::MessageService;
+use bb_services::notification::NotificationService;
+use bb_services::notification_actions::NotificationAction;
+use bb_services::queue::QueueService;
+use bb_services::{ActionHandler, AppEvent, EventBus, LinkPreviewService};
+use bb_socket::{EventDispatcher, SocketEvent, SocketManager};
+
+/// How often held digests are checked while listening.
+const DIGEST_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
 
 /// Run the connect command.
 pub async fn run(
@@ -15,6 +32,7 @@ pub async fn run(
     address: Option<String>,
     password: Option<String>,
     save_config: bool,
+    notify: bool,
 ) -> BbResult<()> {
     // Determine address: arg > config > interactive prompt
     let addr = if let Some(a) = address {
@@ -106,6 +124,15 @@ pub async fn run(
         println!("  Local IPs:   {}", ips.join(", "));
     }
 
+    // Mentions of any of the user's addresses count as mentions
+    let db = super::init_database(&config).await?;
+    {
+        let conn = db.conn()?;
+        for address in [&info.detected_icloud, &info.detected_imessage].into_iter().flatten() {
+            bb_models::queries::save_my_address(&conn, address)?;
+        }
+    }
+
     // Optionally save config to disk
     if save_config {
         let cfg = config.read().await;
@@ -121,6 +148,14 @@ pub async fn run(
         "{} Establishing socket connection...",
         style("[3/3]").bold().dim(),
     );
+    let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+    let mut notifier = if notify {
+        Some(Notifier::new(&config, db, api.clone(), action_tx).await?)
+    } else {
+        None
+    };
+    let mut digest_check = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+
     let dispatcher = EventDispatcher::new(256);
     let mut rx = dispatcher.subscribe();
     let manager = SocketManager::new(server_config, dispatcher, None);
@@ -143,6 +178,9 @@ pub async fn run(
                             style(format!("[{}]", ev.event_type.as_str())).cyan(),
                             ev.data
                         );
+                        if let Some(notifier) = notifier.as_mut() {
+                            notifier.handle(ev).await;
+                        }
                     }
                     Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
                         println!(
@@ -153,6 +191,16 @@ pub async fn run(
                     Err(_) => break,
                 }
             }
+            _ = digest_check.tick(), if notifier.is_some() => {
+                if let Some(notifier) = notifier.as_ref() {
+                    notifier.flush_digests();
+                }
+            }
+            Some(action) = action_rx.recv() => {
+                if let Some(notifier) = notifier.as_ref() {
+                    notifier.perform(action).await;
+                }
+            }
             _ = tokio::signal::ctrl_c() => {
                 println!("\n  Disconnecting...");
                 manager.disconnect().await;
@@ -163,3 +211,128 @@ pub async fn run(
 
     Ok(())
 }
+
+/// Saves incoming messages and shows the notifications the rules allow.
+struct Notifier {
+    handler: ActionHandler,
+    events: broadcast::Receiver<AppEvent>,
+    service: NotificationService,
+    api: ApiClient,
+    /// Replies that can't reach the server wait here for the connection.
+    queue: QueueService,
+    messages: MessageService,
+    db: Database,
+    event_bus: EventBus,
+}
+
+impl Notifier {
+    /// Actions picked on notifications are sent to `actions`.
+    async fn new(
+        config: &ConfigHandle,
+        db: Database,
+        api: ApiClient,
+        actions: mpsc::UnboundedSender<NotificationAction>,
+    ) -> BbResult<Self> {
+        let event_bus = EventBus::new(256);
+        let service = NotificationService::new(config.clone()).with_database(db.clone());
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let service = match listen_for_actions(actions).await {
+            Ok(notifier) => service.with_dbus_notifier(notifier),
+            Err(e) => {
+                warn!("notification actions unavailable: {e}");
+                service
+            }
+        };
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        drop(actions);
+
+        Ok(Self {
+            handler: ActionHandler::new(db.clone(), event_bus.clone()),
+            events: event_bus.subscribe(),
+            service,
+            api,
+            queue: QueueService::new().with_database(db.clone()),
+            messages: MessageService::new(db.clone(), event_bus.clone())
+                .with_link_previews(std::sync::Arc::new(LinkPreviewService::new(db.clone()))),
+            db,
+            event_bus,
+        })
+    }
+
+    /// Save a socket event, then notify for any messages it delivered.
+    async fn handle(&mut self, event: SocketEvent) {
+        if let Err(e) = self.handler.handle_event(event).await {
+            warn!("failed to handle socket event: {e}");
+        }
+        while let Ok(app_event) = self.events.try_recv() {
+            match self.service.process_event(&app_event).await {
+                Ok(Some((ctx, decision))) if decision.shows() => {
+                    println!(
+                        "  {} {}: {}",
+                        style(format!("[{}]", decision.action)).magenta().bold(),
+                        ctx.sender_name.as_deref().unwrap_or("Unknown"),
+                        super::truncate(&ctx.text, 60)
+                    );
+                    if let Err(e) = self.service.show_message(&ctx, &decision).await {
+                        warn!("failed to show notification: {e}");
+                    }
+                }
+                Ok(Some((_, decision))) => {
+                    println!("  {} {}", style(format!("[{}]", decision.action)).dim(), decision.reason);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("notification rules failed: {e}"),
+            }
+        }
+    }
+
+    /// Show digests whose window has passed.
+    fn flush_digests(&self) {
+        let digests = match self.service.flush_digests(chrono::Utc::now()) {
+            Ok(digests) => digests,
+            Err(e) => {
+                warn!("digest flush failed: {e}");
+                return;
+            }
+        };
+        for digest in digests {
+            println!("  {} {}", style("[digest]").magenta().bold(), digest.summary());
+            if let Err(e) = self.service.show_digest(&digest) {
+                warn!("failed to show notification: {e}");
+            }
+        }
+    }
+
+    /// Perform an action picked on a notification.
+    async fn perform(&self, action: NotificationAction) {
+        let label = match &action {
+            NotificationAction::Reply { .. } => "replied",
+            NotificationAction::MarkRead { .. } => "marked read",
+            NotificationAction::Mute { .. } => "muted for 1h",
+            NotificationAction::Open { .. } => return,
+        };
+        let chats = ChatService::new(self.db.clone(), self.event_bus.clone());
+        match action.perform(&self.api, &self.queue, &chats, &self.messages).await {
+            Ok(()) => println!("  {} {}", style(format!("[{label}]")).magenta().bold(), action.chat_guid()),
+            Err(e) => warn!("notification action failed: {e}"),
+        }
+    }
+}
+
+/// Connect to the desktop notification service and forward the actions
+/// picked on its notifications.
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn listen_for_actions(
+    tx: mpsc::UnboundedSender<NotificationAction>,
+) -> BbResult<std::sync::Arc<bb_services::notification_actions::DbusNotifier>> {
+    let notifier = bb_services::notification_actions::DbusNotifier::session().await?;
+    let mut actions = notifier.listen().await?;
+    tokio::spawn(async move {
+        while let Some(action) = actions.next().await {
+            if tx.send(action).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(std::sync::Arc::new(notifier))
+}
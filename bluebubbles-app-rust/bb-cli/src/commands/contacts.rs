@@ -6,6 +6,10 @@ use console::style;
 
 use bb_core::config::ConfigHandle;
 use bb_core::error::BbResult;
+use bb_models::VCardVersion;
+use bb_services::attachment::AttachmentService;
+use bb_services::contact::{ContactCard, ContactService};
+use bb_services::event_bus::EventBus;
 use crate::OutputFormat;
 
 #[derive(Subcommand)]
@@ -26,6 +30,28 @@ pub enum ContactsAction {
     },
     /// Sync contacts from the server.
     Sync,
+    /// Import contacts from a vCard (.vcf) file.
+    Import {
+        /// Path to the vCard file.
+        file: String,
+    },
+    /// Export all contacts as a vCard file.
+    Export {
+        /// Output file path (defaults to stdout).
+        #[arg(short, long)]
+        output: Option<String>,
+        /// vCard version to write (3.0 or 4.0).
+        #[arg(long = "vcard-version", default_value = "3.0")]
+        vcard_version: String,
+    },
+    /// Show a contact card attachment, optionally saving it to local contacts.
+    Card {
+        /// Attachment GUID of the contact card.
+        guid: String,
+        /// Save the card into local contacts.
+        #[arg(long)]
+        save: bool,
+    },
 }
 
 pub async fn run(config: ConfigHandle, action: ContactsAction, format: OutputFormat) -> BbResult<()> {
@@ -164,7 +190,7 @@ pub async fn run(config: ConfigHandle, action: ContactsAction, format: OutputFor
             println!("  {} Fetching contacts from server...", style("...").dim());
             let contacts_json = api.get_contacts(false).await?;
             let conn = db.conn()?;
-            bb_models::queries::delete_all_contacts(&conn)?;
+            bb_models::queries::delete_synced_contacts(&conn)?;
             let mut count = 0;
             for cj in &contacts_json {
                 if let Ok(mut c) = bb_models::Contact::from_server_map(cj) {
@@ -179,6 +205,86 @@ pub async fn run(config: ConfigHandle, action: ContactsAction, format: OutputFor
                 count
             );
         }
+        ContactsAction::Import { file } => {
+            let data = std::fs::read(&file)?;
+            let service = ContactService::new(db, EventBus::new(16));
+            let summary = service.import_vcards(&String::from_utf8_lossy(&data))?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&summary).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} Imported {} new, updated {} existing contact(s).",
+                        style("OK").green().bold(),
+                        summary.added,
+                        summary.updated
+                    );
+                }
+            }
+        }
+        ContactsAction::Export { output, vcard_version } => {
+            let version: VCardVersion = vcard_version.parse()?;
+            let service = ContactService::new(db, EventBus::new(16));
+            let data = service.export_vcards(version)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, &data)?;
+                    let count = data.matches("BEGIN:VCARD").count();
+                    println!(
+                        "  {} Exported {} contact(s) to {}",
+                        style("OK").green().bold(),
+                        count,
+                        path
+                    );
+                }
+                None => print!("{data}"),
+            }
+        }
+        ContactsAction::Card { guid, save } => {
+            let api = super::create_api_client(&config).await?;
+            let attachments = AttachmentService::new(
+                db.clone(),
+                EventBus::new(16),
+                super::attachment_cache_dir(),
+            );
+            let contacts = attachments.load_contact_card(&api, &guid).await?;
+            let cards: Vec<ContactCard> = contacts.iter().map(ContactCard::from).collect();
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&cards).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    for card in &cards {
+                        println!("{}", style(&card.display_name).bold().underlined());
+                        for phone in &card.phones {
+                            println!("  Phone:  {phone}");
+                        }
+                        for email in &card.emails {
+                            println!("  Email:  {email}");
+                        }
+                        if card.avatar_base64.is_some() {
+                            println!("  Photo:  {}", style("yes").dim());
+                        }
+                        println!();
+                    }
+                }
+            }
+
+            if save {
+                let service = ContactService::new(db, EventBus::new(16));
+                let summary = service.import_contacts(contacts)?;
+                if matches!(format, OutputFormat::Text) {
+                    println!(
+                        "  {} Saved {} new, updated {} existing contact(s).",
+                        style("OK").green().bold(),
+                        summary.added,
+                        summary.updated
+                    );
+                }
+            }
+        }
     }
 
     Ok(())
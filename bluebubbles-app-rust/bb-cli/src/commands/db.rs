@@ -3,10 +3,12 @@
 use clap::Subcommand;
 use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
 use console::style;
-use dialoguer::Confirm;
+use dialoguer::{Confirm, Password};
 
 use bb_core::config::ConfigHandle;
-use bb_core::error::BbResult;
+use bb_core::error::{BbError, BbResult};
+use bb_models::encryption::{self, DatabaseKey};
+use bb_models::SnapshotStore;
 use crate::OutputFormat;
 
 #[derive(Subcommand)]
@@ -19,6 +21,133 @@ pub enum DbAction {
     Reset,
     /// Show the database file path.
     Path,
+    /// Encrypt the database in place with SQLCipher.
+    Encrypt {
+        /// Read the key from this file (passphrase or 64 hex chars).
+        #[arg(long, conflicts_with = "passphrase")]
+        key_file: Option<String>,
+        /// Passphrase to encrypt with (prompted if neither option is given).
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
+    /// Decrypt an encrypted database in place.
+    Decrypt {
+        /// Read the current key from this file.
+        #[arg(long, conflicts_with = "passphrase")]
+        key_file: Option<String>,
+        /// Current passphrase (prompted if neither option is given).
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
+    /// Change the key of an encrypted database.
+    Rekey {
+        /// Read the current key from this file.
+        #[arg(long, conflicts_with = "passphrase")]
+        key_file: Option<String>,
+        /// Current passphrase (prompted if neither option is given).
+        #[arg(long)]
+        passphrase: Option<String>,
+        /// Read the new key from this file.
+        #[arg(long, conflicts_with = "new_passphrase")]
+        new_key_file: Option<String>,
+        /// New passphrase (prompted if neither option is given).
+        #[arg(long)]
+        new_passphrase: Option<String>,
+    },
+    /// Import history from an Apple Messages chat.db file.
+    #[command(name = "import-chatdb")]
+    ImportChatdb {
+        /// Path to chat.db (e.g. a copy of ~/Library/Messages/chat.db).
+        path: std::path::PathBuf,
+        /// Copy of ~/Library/Messages/Attachments to pull attachment files from.
+        #[arg(long)]
+        attachments: Option<std::path::PathBuf>,
+    },
+    /// Manage local database snapshots.
+    Snapshots {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Manage data retention policies.
+    Retention {
+        #[command(subcommand)]
+        action: RetentionAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RetentionAction {
+    /// List retention policies.
+    List,
+    /// Set the global policy, or a chat's policy with --chat.
+    Set {
+        /// Chat GUID; the policy replaces the global one for this chat.
+        #[arg(long)]
+        chat: Option<String>,
+        /// Delete messages older than this many days.
+        #[arg(long)]
+        messages: Option<u32>,
+        /// Delete cached attachment files older than this many days.
+        #[arg(long)]
+        attachments: Option<u32>,
+        /// Apply to pinned chats too.
+        #[arg(long)]
+        include_pinned: bool,
+        /// Store the policy disabled.
+        #[arg(long)]
+        disabled: bool,
+    },
+    /// Remove the global policy, or a chat's policy with --chat.
+    Clear {
+        /// Chat GUID.
+        #[arg(long)]
+        chat: Option<String>,
+    },
+    /// Apply retention policies now.
+    Run {
+        /// Only report what would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotAction {
+    /// List snapshots, newest first.
+    List,
+    /// Take a snapshot now and prune old ones.
+    Create,
+    /// Replace the database with a snapshot.
+    Restore {
+        /// Snapshot ID (from `db snapshots list`).
+        id: String,
+        /// Skip the confirmation prompt.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+/// Resolve a key from CLI options, prompting for a passphrase as a last resort.
+fn resolve_key(
+    key_file: Option<String>,
+    passphrase: Option<String>,
+    prompt: &str,
+    confirm: bool,
+) -> BbResult<DatabaseKey> {
+    if let Some(path) = key_file {
+        return DatabaseKey::from_key_file(std::path::Path::new(&path));
+    }
+    if let Some(p) = passphrase {
+        return DatabaseKey::passphrase(&p);
+    }
+    let mut input = Password::new().with_prompt(prompt);
+    if confirm {
+        input = input.with_confirmation("  Confirm passphrase", "  Passphrases do not match");
+    }
+    let value = input
+        .interact()
+        .map_err(|e| BbError::Internal(e.to_string()))?;
+    DatabaseKey::passphrase(&value)
 }
 
 pub async fn run(config: ConfigHandle, action: DbAction, format: OutputFormat) -> BbResult<()> {
@@ -242,6 +371,111 @@ pub async fn run(config: ConfigHandle, action: DbAction, format: OutputFormat) -
                 style("OK").green().bold()
             );
         }
+        DbAction::Encrypt { key_file, passphrase } => {
+            if config.read().await.database.encrypted {
+                println!("  Database is already marked as encrypted in the config.");
+                return Ok(());
+            }
+            let key = resolve_key(key_file.clone(), passphrase, "  New database passphrase", true)?;
+            encryption::encrypt_database(&db_path, &key)?;
+
+            {
+                let mut cfg = config.write().await;
+                cfg.database.encrypted = true;
+                cfg.database.key_file = key_file.unwrap_or_default();
+            }
+            config.save().await?;
+
+            println!(
+                "  {} Database encrypted.",
+                style("OK").green().bold()
+            );
+            if config.read().await.database.key_file.is_empty() {
+                println!(
+                    "  Set {} to the passphrase before running other commands.",
+                    encryption::PASSPHRASE_ENV_VAR
+                );
+            }
+        }
+        DbAction::Decrypt { key_file, passphrase } => {
+            let key = resolve_key(key_file, passphrase, "  Current database passphrase", false)?;
+            encryption::decrypt_database(&db_path, &key)?;
+
+            {
+                let mut cfg = config.write().await;
+                cfg.database.encrypted = false;
+                cfg.database.key_file = String::new();
+            }
+            config.save().await?;
+
+            println!(
+                "  {} Database decrypted. Its contents are now stored in plaintext.",
+                style("OK").green().bold()
+            );
+        }
+        DbAction::Rekey { key_file, passphrase, new_key_file, new_passphrase } => {
+            let old_key = resolve_key(key_file, passphrase, "  Current database passphrase", false)?;
+            let new_key = resolve_key(
+                new_key_file.clone(),
+                new_passphrase,
+                "  New database passphrase",
+                true,
+            )?;
+            encryption::rekey_database(&db_path, &old_key, &new_key)?;
+
+            {
+                let mut cfg = config.write().await;
+                cfg.database.encrypted = true;
+                cfg.database.key_file = new_key_file.unwrap_or_default();
+            }
+            config.save().await?;
+
+            println!(
+                "  {} Database rekeyed.",
+                style("OK").green().bold()
+            );
+        }
+        DbAction::ImportChatdb { path, attachments } => {
+            use bb_services::chatdb_import::{ChatDbImportOptions, ChatDbImporter};
+
+            let db = super::init_database(&config).await?;
+            let options = ChatDbImportOptions {
+                cache_dir: attachments.as_ref().map(|_| super::attachment_cache_dir()),
+                attachments_dir: attachments,
+            };
+
+            println!(
+                "  {} Importing {}...",
+                style("...").dim(),
+                style(path.display()).dim()
+            );
+            let report = ChatDbImporter::new(db).import(&path, &options)?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    println!("  {} Import complete.", style("OK").green().bold());
+                    println!("  Chats:       {} new, {} already present", report.chats_imported, report.chats_existing);
+                    println!("  Messages:    {} new, {} already present", report.messages_imported, report.messages_skipped);
+                    println!("  Handles:     {}", report.handles);
+                    println!("  Attachments: {}", report.attachments_imported);
+                    if options.attachments_dir.is_some() {
+                        println!(
+                            "  Files:       {} copied, {} not found",
+                            report.attachment_files_copied, report.attachment_files_missing
+                        );
+                    }
+                }
+            }
+        }
+        DbAction::Snapshots { action } => {
+            run_snapshots(&config, &db_path, action, format).await?;
+        }
+        DbAction::Retention { action } => {
+            run_retention(&config, action, format).await?;
+        }
         DbAction::Path => {
             match format {
                 OutputFormat::Json => {
@@ -256,3 +490,235 @@ pub async fn run(config: ConfigHandle, action: DbAction, format: OutputFormat) -
 
     Ok(())
 }
+
+async fn run_snapshots(
+    config: &ConfigHandle,
+    db_path: &std::path::Path,
+    action: SnapshotAction,
+    format: OutputFormat,
+) -> BbResult<()> {
+    let db_config = config.read().await.database.clone();
+    let data_dir = db_path.parent().unwrap_or(std::path::Path::new("."));
+    let store = SnapshotStore::from_config(&db_config.snapshots, data_dir);
+
+    match action {
+        SnapshotAction::List => {
+            let snapshots = store.list()?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&snapshots).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if snapshots.is_empty() {
+                        println!("  No snapshots in {}", store.dir().display());
+                        return Ok(());
+                    }
+                    let mut table = Table::new();
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic);
+                    table.set_header(vec!["ID", "Taken", "Size"]);
+                    for snapshot in &snapshots {
+                        table.add_row(vec![
+                            snapshot.id.clone(),
+                            snapshot
+                                .created_at
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string(),
+                            super::format_bytes(snapshot.size_bytes),
+                        ]);
+                    }
+                    println!("{table}");
+                    println!("  {}", style(store.dir().display()).dim());
+                }
+            }
+        }
+        SnapshotAction::Create => {
+            let db = super::init_database(config).await?;
+            let key = DatabaseKey::from_config(&db_config)?;
+            let snapshot = store.create(&db, key.as_ref())?;
+            let pruned = store.prune(&db_config.snapshots)?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::json!({
+                        "snapshot": snapshot,
+                        "pruned": pruned.iter().map(|s| &s.id).collect::<Vec<_>>(),
+                    }));
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} Snapshot {} taken ({}).",
+                        style("OK").green().bold(),
+                        snapshot.id,
+                        super::format_bytes(snapshot.size_bytes)
+                    );
+                    if !pruned.is_empty() {
+                        println!("  Pruned {} old snapshot(s).", pruned.len());
+                    }
+                }
+            }
+        }
+        SnapshotAction::Restore { id, yes } => {
+            let snapshot = store.find(&id)?;
+            if !yes {
+                println!(
+                    "  {} This replaces the current database with snapshot {}.",
+                    style("WARNING").red().bold(),
+                    snapshot.id
+                );
+                println!("  Quit the desktop app before continuing.");
+                let confirmed = Confirm::new()
+                    .with_prompt("  Restore this snapshot?")
+                    .default(false)
+                    .interact()
+                    .unwrap_or(false);
+                if !confirmed {
+                    println!("  Restore cancelled.");
+                    return Ok(());
+                }
+            }
+
+            // Nothing in this process holds the database open at this point,
+            // so the store only has to wait out other processes.
+            let key = DatabaseKey::from_config(&db_config)?;
+            store.restore(&snapshot.id, db_path, key.as_ref())?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::json!({"restored": snapshot.id}));
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} Restored snapshot {}. The previous database was kept as {}-pre-restore.",
+                        style("OK").green().bold(),
+                        snapshot.id,
+                        db_path.display()
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_retention(
+    config: &ConfigHandle,
+    action: RetentionAction,
+    format: OutputFormat,
+) -> BbResult<()> {
+    use bb_models::{Chat, RetentionPolicy};
+    use bb_services::retention::RetentionService;
+
+    let db = super::init_database(config).await?;
+    let chat_id = |guid: &str| -> BbResult<i64> {
+        let conn = db.conn()?;
+        Chat::find_by_guid(&conn, guid)?
+            .and_then(|c| c.id)
+            .ok_or_else(|| BbError::ChatNotFound(guid.to_string()))
+    };
+    let service = RetentionService::new(
+        db.clone(),
+        bb_services::event_bus::EventBus::new(16),
+        super::attachment_cache_dir(),
+    );
+
+    match action {
+        RetentionAction::List => {
+            let policies = service.policies()?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&policies).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if policies.is_empty() {
+                        println!("  No retention policies. All data is kept.");
+                        return Ok(());
+                    }
+                    let conn = db.conn()?;
+                    let days = |d: Option<u32>| d.map(|d| format!("{d} days")).unwrap_or_else(|| "keep".into());
+                    let mut table = Table::new();
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic);
+                    table.set_header(vec!["Scope", "Messages", "Attachment files", "Pinned", "Enabled"]);
+                    for policy in &policies {
+                        let scope = match policy.chat_id {
+                            None => "global".to_string(),
+                            Some(id) => Chat::find_by_id(&conn, id)?
+                                .map(|c| c.title())
+                                .unwrap_or_else(|| format!("chat {id}")),
+                        };
+                        table.add_row(vec![
+                            scope,
+                            days(policy.message_max_age_days),
+                            days(policy.attachment_max_age_days),
+                            if policy.exempt_pinned { "exempt" } else { "included" }.to_string(),
+                            if policy.enabled { "yes" } else { "no" }.to_string(),
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        RetentionAction::Set { chat, messages, attachments, include_pinned, disabled } => {
+            let scope = chat.as_deref().map(chat_id).transpose()?;
+            let mut policy = RetentionPolicy::new(scope);
+            policy.message_max_age_days = messages;
+            policy.attachment_max_age_days = attachments;
+            policy.exempt_pinned = !include_pinned;
+            policy.enabled = !disabled;
+            service.set_policy(&mut policy)?;
+            println!("  {} Retention policy saved.", style("OK").green().bold());
+            if messages.is_none() && attachments.is_none() {
+                println!("  No limits set: {} keeps all data.", chat.as_deref().unwrap_or("every chat"));
+            }
+        }
+        RetentionAction::Clear { chat } => {
+            let scope = chat.as_deref().map(chat_id).transpose()?;
+            if service.remove_policy(scope)? {
+                println!("  {} Retention policy removed.", style("OK").green().bold());
+            } else {
+                println!("  No matching retention policy.");
+            }
+        }
+        RetentionAction::Run { dry_run } => {
+            let report = service.run(dry_run)?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    let verb = if dry_run { "Would delete" } else { "Deleted" };
+                    if dry_run {
+                        println!("{}", style("Retention dry run").bold().underlined());
+                    }
+                    println!("  {verb} {} message(s), {} reaction(s), {} attachment(s).",
+                        report.messages_deleted, report.reactions_deleted, report.attachments_deleted);
+                    println!("  {verb} {} cached file(s), {}.",
+                        report.files_removed, super::format_bytes(report.bytes_freed));
+                    if report.bookmarked_kept > 0 {
+                        println!("  Kept {} bookmarked message(s).", report.bookmarked_kept);
+                    }
+                    if report.pinned_chats_skipped > 0 {
+                        println!("  Skipped {} pinned chat(s).", report.pinned_chats_skipped);
+                    }
+                    for chat in &report.chats {
+                        println!(
+                            "    {}  {} messages, {} files",
+                            style(&chat.chat_guid).dim(),
+                            chat.messages_deleted,
+                            chat.files_removed
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
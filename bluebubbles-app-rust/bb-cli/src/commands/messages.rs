@@ -54,7 +54,12 @@ pub enum MessagesAction {
         /// iMessage effect ID (e.g., slam, loud, gentle, invisible-ink).
         #[arg(short, long)]
         effect: Option<String>,
+        /// Save the message to send when the connection returns, and exit.
+        #[arg(long, conflicts_with = "effect")]
+        queue: bool,
     },
+    /// Send messages queued while offline.
+    Flush,
     /// Send a reaction / tapback to a message.
     React {
         /// Chat GUID the message belongs to.
@@ -90,6 +95,11 @@ pub enum MessagesAction {
         #[arg(short, long, default_value = "0")]
         part_index: i32,
     },
+    /// Show the edit history and unsent parts of a message.
+    History {
+        /// GUID of the message.
+        guid: String,
+    },
 }
 
 /// Map a human-readable effect name to an iMessage effect ID.
@@ -315,15 +325,36 @@ pub async fn run(config: ConfigHandle, action: MessagesAction, format: OutputFor
             let db = super::init_database(&config).await?;
             let conn = db.conn()?;
 
-            let messages = if let Some(ref chat_guid) = chat {
+            let chat_id = match chat {
+                Some(ref chat_guid) => Some(
+                    bb_models::queries::find_chat_by_guid(&conn, chat_guid)?
+                        .and_then(|c| c.id)
+                        .ok_or_else(|| bb_core::error::BbError::ChatNotFound(chat_guid.clone()))?,
+                ),
+                None => None,
+            };
+            let mut messages = match chat_id {
                 // Search within a specific chat
-                let chat_obj = bb_models::queries::find_chat_by_guid(&conn, chat_guid)?;
-                let chat_id = chat_obj
-                    .and_then(|c| c.id)
-                    .ok_or_else(|| bb_core::error::BbError::ChatNotFound(chat_guid.clone()))?;
-                bb_models::queries::search_messages_in_chat(&conn, chat_id, &query, limit)?
-            } else {
-                bb_models::queries::search_messages(&conn, &query, limit)?
+                Some(chat_id) => bb_models::queries::search_messages_in_chat(&conn, chat_id, &query, limit)?,
+                None => bb_models::queries::search_messages(&conn, &query, limit)?,
+            };
+
+            // Bookmarks whose note or tags match, after the text matches
+            let bookmarks = bb_models::Bookmark::list(&conn, &bb_models::BookmarkFilter {
+                query: Some(query.trim_start_matches('#').to_string()),
+                chat_id,
+                limit,
+                ..Default::default()
+            })?;
+            for bookmark in &bookmarks {
+                if (messages.len() as i64) < limit
+                    && !messages.iter().any(|m| m.guid.as_deref() == Some(bookmark.message_guid.as_str()))
+                {
+                    messages.extend(bb_models::queries::find_message_by_guid(&conn, &bookmark.message_guid)?);
+                }
+            }
+            let bookmark_for = |m: &bb_models::Message| {
+                bookmarks.iter().find(|b| m.guid.as_deref() == Some(b.message_guid.as_str()))
             };
 
             match format {
@@ -335,6 +366,7 @@ pub async fn run(config: ConfigHandle, action: MessagesAction, format: OutputFor
                             "chat_id": m.chat_id,
                             "date_created": m.date_created,
                             "from_me": m.is_from_me,
+                            "bookmark": bookmark_for(m),
                         })
                     }).collect();
                     println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
@@ -366,16 +398,78 @@ pub async fn run(config: ConfigHandle, action: MessagesAction, format: OutputFor
                                 style(sender).bold(),
                                 text
                             );
+                            if let Some(b) = bookmark_for(msg) {
+                                let tags: Vec<String> = b.tags.iter().map(|t| format!("#{t}")).collect();
+                                println!(
+                                    "      {} {} {}",
+                                    style("★").yellow(),
+                                    style(tags.join(" ")).cyan(),
+                                    b.note.as_deref().unwrap_or("")
+                                );
+                            }
                         }
                     }
                 }
             }
         }
-        MessagesAction::Send { chat, text, effect } => {
+        MessagesAction::Send { chat, text, queue: true, .. } => {
+            let db = super::init_database(&config).await?;
+            let service = bb_services::message::MessageService::new(
+                db.clone(),
+                bb_services::event_bus::EventBus::new(16),
+            );
+            let queue = bb_services::queue::QueueService::new().with_database(db);
+            let msg = service.queue_text(&queue, &chat, &text).await?;
+            let temp_guid = msg.guid.as_deref().unwrap_or("unknown");
+
+            match format {
+                OutputFormat::Json => {
+                    println!(
+                        "{}",
+                        serde_json::json!({ "tempGuid": temp_guid, "status": "waiting_for_connection" })
+                    );
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} Message saved; it will be sent when the connection returns (temp guid: {})",
+                        style("QUEUED").yellow().bold(),
+                        temp_guid
+                    );
+                }
+            }
+        }
+        MessagesAction::Flush => {
+            let db = super::init_database(&config).await?;
+            let api = super::create_api_client(&config).await?;
+            let service = bb_services::message::MessageService::new(
+                db.clone(),
+                bb_services::event_bus::EventBus::new(16),
+            );
+            let queue = bb_services::queue::QueueService::new().with_database(db);
+            queue.reload().await?;
+            let sent = service.flush_waiting(&api, &queue).await?;
+            let remaining = queue.waiting().await.len();
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::json!({ "sent": sent, "remaining": remaining }));
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} {} sent, {} still waiting",
+                        style("OK").green().bold(),
+                        sent,
+                        remaining
+                    );
+                }
+            }
+        }
+        MessagesAction::Send { chat, text, effect, .. } => {
             let api = super::create_api_client(&config).await?;
             let temp_guid = format!("temp-{}", uuid::Uuid::new_v4());
 
             let effect_id = effect.map(|e| resolve_effect_id(&e));
+            let previews = bb_services::LinkPreviewService::new(super::init_database(&config).await?);
 
             println!(
                 "  {} Sending message to {}...",
@@ -393,6 +487,7 @@ pub async fn run(config: ConfigHandle, action: MessagesAction, format: OutputFor
                 selected_message_guid: None,
                 part_index: None,
                 dd_scan: None,
+                payload_data: previews.payload_for_text(&text).await,
             };
             let result = api.send_text(&params).await?;
             let guid = result.get("guid").and_then(|v| v.as_str()).unwrap_or("unknown");
@@ -494,11 +589,13 @@ pub async fn run(config: ConfigHandle, action: MessagesAction, format: OutputFor
                 style(&guid).dim()
             );
 
-            let result = api.unsend_message(&guid, part_index).await?;
+            let db = super::init_database(&config).await?;
+            let service = bb_services::message::MessageService::new(db, bb_services::event_bus::EventBus::new(16));
+            service.unsend_message(&api, &guid, part_index).await?;
 
             match format {
                 OutputFormat::Json => {
-                    println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+                    println!("{}", serde_json::json!({ "guid": guid, "partIndex": part_index, "unsent": true }));
                 }
                 OutputFormat::Text => {
                     println!(
@@ -509,6 +606,59 @@ pub async fn run(config: ConfigHandle, action: MessagesAction, format: OutputFor
                 }
             }
         }
+        MessagesAction::History { guid } => {
+            let db = super::init_database(&config).await?;
+            let event_bus = bb_services::event_bus::EventBus::new(16);
+            let service = bb_services::message::MessageService::new(db, event_bus);
+            let history = service.edit_history(&guid)?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&history).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} {}",
+                        style("Current:").bold(),
+                        history.current_text.as_deref().unwrap_or("[no text]")
+                    );
+                    if let Some(edited) = &history.date_edited {
+                        println!("  {} {}", style("Edited").yellow(), style(edited).dim());
+                    }
+
+                    if !history.is_edited() && history.unsent.is_empty() {
+                        println!("\nThis message has not been edited or unsent.");
+                    }
+
+                    for part in &history.parts {
+                        println!("\n  {} (part {})", style("Earlier versions").bold(), part.part_index);
+                        for version in part.previous_versions.iter().rev() {
+                            let date = version.date.as_deref().unwrap_or("-");
+                            let date_short = if date.len() > 19 { &date[..19] } else { date };
+                            println!(
+                                "    {} {}",
+                                style(date_short).dim(),
+                                version.text.as_deref().unwrap_or("[no text]")
+                            );
+                        }
+                    }
+
+                    if !history.unsent.is_empty() {
+                        println!("\n  {}", style("Unsent").red().bold());
+                        for tombstone in &history.unsent {
+                            let date = &tombstone.date_unsent;
+                            let date_short = if date.len() > 19 { &date[..19] } else { date.as_str() };
+                            println!(
+                                "    {} part {}: {}",
+                                style(date_short).dim(),
+                                tombstone.part_index,
+                                tombstone.original_text.as_deref().unwrap_or("[text unknown]")
+                            );
+                        }
+                    }
+                }
+            }
+        }
     }
 
     Ok(())
@@ -4,7 +4,12 @@ pub mod connect;
 pub mod status;
 pub mod chats;
 pub mod messages;
+pub mod bookmarks;
+pub mod notifications;
+pub mod otp;
 pub mod contacts;
+pub mod people;
+pub mod trash;
 pub mod attachments;
 pub mod sync;
 pub mod settings;
@@ -17,6 +22,7 @@ pub mod scheduled;
 pub mod backup;
 pub mod private_api;
 pub mod diagnose;
+pub mod stats;
 
 use bb_core::config::ConfigHandle;
 use bb_core::error::BbResult;
@@ -27,8 +33,25 @@ use bb_models::Database;
 /// Helper to initialize the database from config.
 pub async fn init_database(config: &ConfigHandle) -> BbResult<Database> {
     let db_path = Platform::data_dir()?.join("bluebubbles.db");
-    let db_config = config.read().await.database.clone();
-    Database::init(&db_path, &db_config)
+    let (db_config, phone_region) = {
+        let cfg = config.read().await;
+        (cfg.database.clone(), cfg.sync.default_phone_region.clone())
+    };
+    bb_models::phone::set_default_region(&phone_region);
+    let db = Database::init(&db_path, &db_config)?;
+    {
+        let conn = db.conn()?;
+        bb_models::queries::apply_saved_phone_region(&conn)?;
+    }
+    Ok(db)
+}
+
+/// Attachment cache directory shared with the desktop app.
+pub fn attachment_cache_dir() -> std::path::PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("bluebubbles")
+        .join("cache")
 }
 
 /// Helper to create an API client from config.
@@ -0,0 +1,447 @@
+//! Notification commands - edit notification rules and quiet hours, and
+//! check how a message would notify.
+//!
+//! Rules are tried in order; the first enabled rule whose conditions all
+//! match decides the action (notify, silence, priority or digest). Digest
+//! messages are held per chat and summarized once the digest window passes.
+
+use clap::Subcommand;
+use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
+use console::style;
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_models::{NotificationRule, RuleAction, RuleCondition};
+use bb_services::AppEvent;
+use bb_services::notification::NotificationService;
+use crate::OutputFormat;
+
+#[derive(Subcommand)]
+pub enum NotificationsAction {
+    /// List notification rules in evaluation order.
+    Rules,
+    /// Add a rule at the end of the order. Given conditions must all match.
+    Add {
+        /// Rule name.
+        name: String,
+        /// Action: notify, silence, priority (notify-with-priority) or digest.
+        #[arg(short, long)]
+        action: String,
+        /// Match messages in this chat (GUID).
+        #[arg(short, long)]
+        chat: Option<String>,
+        /// Match messages from this address.
+        #[arg(short, long)]
+        from: Option<String>,
+        /// Match messages from any handle of this person (ID).
+        #[arg(short, long)]
+        person: Option<i64>,
+        /// Match message text against this regular expression.
+        #[arg(short, long)]
+        text: Option<String>,
+        /// Match attachments whose MIME type starts with this (`image/`), or `*` for any.
+        #[arg(long)]
+        attachment: Option<String>,
+        /// Match messages that @mention you.
+        #[arg(short, long)]
+        mention: bool,
+    },
+    /// Delete a rule (by name or ID).
+    Remove {
+        rule: String,
+    },
+    /// Enable a rule (by name or ID).
+    Enable {
+        rule: String,
+    },
+    /// Disable a rule (by name or ID) without deleting it.
+    Disable {
+        rule: String,
+    },
+    /// Move a rule (by name or ID) to a position in the order (1 = first).
+    Move {
+        rule: String,
+        position: usize,
+    },
+    /// Show or change quiet hours.
+    Quiet {
+        #[command(subcommand)]
+        action: QuietAction,
+    },
+    /// Show or change digest settings and held messages.
+    Digest {
+        #[command(subcommand)]
+        action: DigestAction,
+    },
+    /// Show how a received message would notify.
+    Test {
+        /// Message GUID.
+        guid: String,
+        /// Local time to evaluate quiet hours at (HH:MM, default: now).
+        #[arg(long)]
+        at: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DigestAction {
+    /// Show digest settings and the messages held for each chat.
+    Show,
+    /// Change digest settings.
+    Set {
+        /// Seconds to hold a chat's messages before summarizing them.
+        #[arg(short, long)]
+        window: Option<u64>,
+        /// Number of recent lines shown in a digest.
+        #[arg(short, long)]
+        lines: Option<usize>,
+        /// Digest group chats that no rule matches (true/false).
+        #[arg(short, long)]
+        group_chats: Option<bool>,
+    },
+    /// Send held digests now instead of waiting for the window.
+    Flush {
+        /// Only this chat (GUID).
+        #[arg(short, long)]
+        chat: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum QuietAction {
+    /// Show the quiet hours.
+    Show,
+    /// Turn on quiet hours between two local times (HH:MM).
+    Set {
+        start: String,
+        end: String,
+    },
+    /// Turn off quiet hours.
+    Off,
+    /// Let an address notify during quiet hours.
+    Allow {
+        address: String,
+    },
+    /// Remove an address from the breakthrough list.
+    Disallow {
+        address: String,
+    },
+}
+
+pub async fn run(config: ConfigHandle, action: NotificationsAction, format: OutputFormat) -> BbResult<()> {
+    let db = super::init_database(&config).await?;
+    let service = NotificationService::new(config.clone()).with_database(db.clone());
+
+    match action {
+        NotificationsAction::Rules => {
+            let rules = service.list_rules()?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&rules).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if rules.is_empty() {
+                        println!("No notification rules. Every message notifies unless muted.");
+                    } else {
+                        print_rules(&rules);
+                    }
+                }
+            }
+        }
+        NotificationsAction::Add { name, action, chat, from, person, text, attachment, mention } => {
+            let action = RuleAction::parse(&action)?;
+            let mut conditions = Vec::new();
+            if let Some(guid) = chat {
+                conditions.push(RuleCondition::Chat { guid });
+            }
+            if let Some(address) = from {
+                conditions.push(RuleCondition::Sender { address });
+            }
+            if let Some(id) = person {
+                conditions.push(RuleCondition::Person { id });
+            }
+            if let Some(pattern) = text {
+                conditions.push(RuleCondition::TextMatches { pattern });
+            }
+            if let Some(mime) = attachment {
+                conditions.push(RuleCondition::Attachment { mime });
+            }
+            if mention {
+                conditions.push(RuleCondition::MentionsMe);
+            }
+            let rule = service.add_rule(&name, conditions, action)?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&rule).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} Added rule {} (id: {}, position {}).",
+                        style("OK").green().bold(),
+                        style(&rule.name).bold(),
+                        rule.id.unwrap_or_default(),
+                        service.list_rules()?.len()
+                    );
+                }
+            }
+        }
+        NotificationsAction::Remove { rule } => {
+            let rule = find_rule(&service, &rule)?;
+            service.delete_rule(rule.id.unwrap_or_default())?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::json!({ "id": rule.id, "deleted": true }));
+                }
+                OutputFormat::Text => {
+                    println!("  {} Deleted rule {}.", style("OK").green().bold(), rule.name);
+                }
+            }
+        }
+        NotificationsAction::Enable { rule } => set_enabled(&service, &rule, true, format)?,
+        NotificationsAction::Disable { rule } => set_enabled(&service, &rule, false, format)?,
+        NotificationsAction::Move { rule, position } => {
+            let rule = find_rule(&service, &rule)?;
+            let rules = service.move_rule(rule.id.unwrap_or_default(), position.saturating_sub(1))?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&rules).unwrap_or_default());
+                }
+                OutputFormat::Text => print_rules(&rules),
+            }
+        }
+        NotificationsAction::Quiet { action } => {
+            let mut quiet = service.quiet_hours()?;
+            match action {
+                QuietAction::Show => {}
+                QuietAction::Set { start, end } => {
+                    let breakthrough = std::mem::take(&mut quiet.breakthrough);
+                    quiet = bb_models::QuietHours::new(&start, &end)?;
+                    quiet.breakthrough = breakthrough;
+                }
+                QuietAction::Off => quiet.enabled = false,
+                QuietAction::Allow { address } => {
+                    if !quiet.breakthrough.iter().any(|a| a.eq_ignore_ascii_case(&address)) {
+                        quiet.breakthrough.push(address);
+                    }
+                }
+                QuietAction::Disallow { address } => {
+                    quiet.breakthrough.retain(|a| !a.eq_ignore_ascii_case(&address));
+                }
+            }
+            service.set_quiet_hours(&quiet)?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&quiet).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if quiet.enabled {
+                        println!("  Quiet hours: {} - {}", style(&quiet.start).bold(), style(&quiet.end).bold());
+                    } else {
+                        println!("  Quiet hours: {}", style("off").dim());
+                    }
+                    if quiet.breakthrough.is_empty() {
+                        println!("  Breakthrough senders: none");
+                    } else {
+                        println!("  Breakthrough senders: {}", quiet.breakthrough.join(", "));
+                    }
+                }
+            }
+        }
+        NotificationsAction::Digest { action } => match action {
+            DigestAction::Show => {
+                let settings = service.digest_settings()?;
+                let held = service.held_messages()?;
+                match format {
+                    OutputFormat::Json => {
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&serde_json::json!({ "settings": settings, "held": held }))
+                                .unwrap_or_default()
+                        );
+                    }
+                    OutputFormat::Text => {
+                        println!("  Window:      {}s", settings.window_secs);
+                        println!("  Lines:       {}", settings.preview_lines);
+                        println!("  Group chats: {}", if settings.group_chats { "digested" } else { "by rule only" });
+                        if held.is_empty() {
+                            println!("  No messages held.");
+                        } else {
+                            let mut table = Table::new();
+                            table
+                                .load_preset(UTF8_FULL)
+                                .apply_modifier(UTF8_ROUND_CORNERS)
+                                .set_content_arrangement(ContentArrangement::Dynamic);
+                            table.set_header(vec!["Chat", "Held", "Since"]);
+                            let mut chats: Vec<(&str, usize, i64)> = Vec::new();
+                            for entry in &held {
+                                match chats.iter_mut().find(|(guid, _, _)| *guid == entry.chat_guid) {
+                                    Some(chat) => chat.1 += 1,
+                                    None => chats.push((&entry.chat_guid, 1, entry.buffered_at)),
+                                }
+                            }
+                            for (guid, count, since) in chats {
+                                let title = held
+                                    .iter()
+                                    .rev()
+                                    .find(|e| e.chat_guid == guid)
+                                    .map(|e| e.chat_title.as_str())
+                                    .unwrap_or(guid);
+                                let since = chrono::DateTime::from_timestamp_millis(since)
+                                    .map(|t| t.with_timezone(&chrono::Local).format("%H:%M:%S").to_string())
+                                    .unwrap_or_default();
+                                table.add_row(vec![super::truncate(title, 40), count.to_string(), since]);
+                            }
+                            println!("{table}");
+                        }
+                    }
+                }
+            }
+            DigestAction::Set { window, lines, group_chats } => {
+                let mut settings = service.digest_settings()?;
+                if let Some(window) = window {
+                    settings.window_secs = window;
+                }
+                if let Some(lines) = lines {
+                    settings.preview_lines = lines;
+                }
+                if let Some(group_chats) = group_chats {
+                    settings.group_chats = group_chats;
+                }
+                service.set_digest_settings(&settings)?;
+                match format {
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&settings).unwrap_or_default());
+                    }
+                    OutputFormat::Text => {
+                        println!("  {} Digest settings saved.", style("OK").green().bold());
+                    }
+                }
+            }
+            DigestAction::Flush { chat } => {
+                let digests = match chat {
+                    Some(guid) => service.flush_chat_digest(&guid)?.into_iter().collect(),
+                    None => service.flush_all_digests()?,
+                };
+                for digest in &digests {
+                    service.show_digest(digest)?;
+                }
+                match format {
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&digests).unwrap_or_default());
+                    }
+                    OutputFormat::Text => {
+                        if digests.is_empty() {
+                            println!("No messages held.");
+                        }
+                        for digest in &digests {
+                            println!("  {}", style(digest.summary()).bold());
+                            for line in digest.body().lines() {
+                                println!("    {line}");
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        NotificationsAction::Test { guid, at } => {
+            let conn = db.conn()?;
+            let message = bb_models::Message::find_by_guid(&conn, &guid)?
+                .ok_or_else(|| BbError::MessageNotFound(guid.clone()))?;
+            let chat_guid = match message.chat_id {
+                Some(id) => bb_models::Chat::find_by_id(&conn, id)?.map(|c| c.guid).unwrap_or_default(),
+                None => String::new(),
+            };
+            drop(conn);
+            let event = AppEvent::MessageReceived {
+                message_guid: guid.clone(),
+                chat_guid,
+                is_from_me: message.is_from_me,
+            };
+            let ctx = service
+                .context_for_event(&event)?
+                .ok_or_else(|| BbError::MessageNotFound(guid.clone()))?;
+            let now = match at {
+                Some(t) => chrono::NaiveTime::parse_from_str(&t, "%H:%M")
+                    .map_err(|_| BbError::InvalidInput(format!("invalid time {t:?}, expected HH:MM")))?,
+                None => chrono::Local::now().time(),
+            };
+            let decision = service.decide(&ctx, now).await?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&serde_json::json!({ "message": ctx, "decision": decision }))
+                            .unwrap_or_default()
+                    );
+                }
+                OutputFormat::Text => {
+                    let action = match decision.action {
+                        RuleAction::Priority => style(decision.action.as_str()).red().bold(),
+                        RuleAction::Silence => style(decision.action.as_str()).dim(),
+                        _ => style(decision.action.as_str()).green().bold(),
+                    };
+                    println!("  Action: {action}");
+                    println!("  Reason: {}", decision.reason);
+                    println!("  From:   {}", ctx.sender.as_deref().unwrap_or("-"));
+                    println!("  Text:   {}", super::truncate(&ctx.text, 60));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn find_rule(service: &NotificationService, reference: &str) -> BbResult<NotificationRule> {
+    service
+        .find_rule(reference)?
+        .ok_or_else(|| BbError::InvalidInput(format!("no notification rule {reference}")))
+}
+
+fn set_enabled(service: &NotificationService, reference: &str, enabled: bool, format: OutputFormat) -> BbResult<()> {
+    let rule = find_rule(service, reference)?;
+    let rule = service.set_rule_enabled(rule.id.unwrap_or_default(), enabled)?;
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&rule).unwrap_or_default());
+        }
+        OutputFormat::Text => {
+            println!(
+                "  {} Rule {} {}.",
+                style("OK").green().bold(),
+                rule.name,
+                if enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+    Ok(())
+}
+
+fn print_rules(rules: &[NotificationRule]) {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+
+    table.set_header(vec!["#", "ID", "Name", "When", "Action", "Enabled"]);
+    for (i, rule) in rules.iter().enumerate() {
+        let when = if rule.conditions.is_empty() {
+            "any message".to_string()
+        } else {
+            rule.conditions.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" and ")
+        };
+        table.add_row(vec![
+            (i + 1).to_string(),
+            rule.id.unwrap_or_default().to_string(),
+            super::truncate(&rule.name, 30),
+            super::truncate(&when, 60),
+            rule.action.to_string(),
+            if rule.enabled { "yes".to_string() } else { "no".to_string() },
+        ]);
+    }
+    println!("{table}");
+}
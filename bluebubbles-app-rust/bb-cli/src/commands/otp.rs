@@ -0,0 +1,224 @@
+//! OTP commands - watch for one-time codes, list recent ones, test text
+//! against the patterns and edit the OTP settings.
+//!
+//! Detection is shared with the desktop app: custom patterns are tried
+//! before the built-in ones, and codes are only taken from allowed senders
+//! when an allowlist is set.
+
+use clap::Subcommand;
+use console::style;
+use tracing::warn;
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_services::otp::{stated_expiry_secs, OtpCode, OtpDetector, OtpService};
+use bb_services::{ActionHandler, EventBus};
+use bb_socket::{EventDispatcher, SocketManager};
+use crate::OutputFormat;
+
+#[derive(Subcommand)]
+pub enum OtpAction {
+    /// Listen to the server and print codes as they arrive (Ctrl+C to stop).
+    Watch,
+    /// List codes from received messages that have not expired yet.
+    Codes {
+        /// Only this chat (GUID).
+        #[arg(short, long)]
+        chat: Option<String>,
+    },
+    /// Show which code, if any, would be detected in some text.
+    Test {
+        text: String,
+    },
+    /// Show the OTP settings.
+    Show,
+    /// Change the OTP settings.
+    Set {
+        /// Add a custom regex pattern; its first group is the code.
+        #[arg(long)]
+        add_pattern: Vec<String>,
+        /// Remove a custom pattern.
+        #[arg(long)]
+        remove_pattern: Vec<String>,
+        /// Only accept codes from this sender (address, short code or contact name).
+        #[arg(long)]
+        allow: Vec<String>,
+        /// Remove a sender from the allowlist.
+        #[arg(long)]
+        disallow: Vec<String>,
+        /// Seconds a code stays valid when the message doesn't say.
+        #[arg(long)]
+        expiry: Option<u64>,
+    },
+}
+
+pub async fn run(config: ConfigHandle, action: OtpAction, format: OutputFormat) -> BbResult<()> {
+    let db = super::init_database(&config).await?;
+    let event_bus = EventBus::new(256);
+    let service = OtpService::new(db.clone(), event_bus.clone());
+
+    match action {
+        OtpAction::Watch => {
+            let server_config = config.read().await.server.clone();
+            if server_config.address.is_empty() {
+                return Err(BbError::MissingConfig(
+                    "server address (use 'bluebubbles connect --save' first)".into(),
+                ));
+            }
+            if !service.is_enabled()? {
+                println!("  {} OTP detection is turned off in settings.", style("WARN").yellow());
+            }
+
+            let dispatcher = EventDispatcher::new(256);
+            let mut rx = dispatcher.subscribe();
+            let manager = SocketManager::new(server_config, dispatcher, None);
+            let handler = ActionHandler::new(db.clone(), event_bus.clone());
+            let mut events = event_bus.subscribe();
+
+            manager.connect().await?;
+            if matches!(format, OutputFormat::Text) {
+                println!("  {} Watching for codes... (Ctrl+C to stop)", style("OK").green().bold());
+            }
+
+            loop {
+                tokio::select! {
+                    event = rx.recv() => {
+                        match event {
+                            Ok(ev) => {
+                                if let Err(e) = handler.handle_event(ev).await {
+                                    warn!("failed to handle socket event: {e}");
+                                }
+                                while let Ok(app_event) = events.try_recv() {
+                                    match service.process_event(&app_event) {
+                                        Ok(Some(code)) => print_code(&code, format),
+                                        Ok(None) => {}
+                                        Err(e) => warn!("otp detection failed: {e}"),
+                                    }
+                                }
+                            }
+                            Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                                warn!("missed {n} socket events");
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    _ = tokio::signal::ctrl_c() => {
+                        manager.disconnect().await;
+                        break;
+                    }
+                }
+            }
+        }
+        OtpAction::Codes { chat } => {
+            let codes = service.recent_codes(chat.as_deref(), chrono::Utc::now().timestamp_millis())?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&codes).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if codes.is_empty() {
+                        println!("No unexpired codes.");
+                    }
+                    for code in &codes {
+                        print_code(code, format);
+                    }
+                }
+            }
+        }
+        OtpAction::Test { text } => {
+            let detector = OtpDetector::new(&service.settings()?.custom_patterns)?;
+            let detection = detector.detect(&text);
+            let expiry = stated_expiry_secs(&text);
+            match format {
+                OutputFormat::Json => {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&serde_json::json!({
+                            "detection": detection,
+                            "statedExpirySecs": expiry,
+                        }))
+                        .unwrap_or_default()
+                    );
+                }
+                OutputFormat::Text => match detection {
+                    Some(d) => {
+                        println!("  Code:    {}", style(&d.code).green().bold());
+                        println!("  Pattern: {}", d.pattern.description());
+                        if let Some(secs) = expiry {
+                            println!("  Expires: after {secs}s");
+                        }
+                    }
+                    None => println!("  No code detected."),
+                },
+            }
+        }
+        OtpAction::Show => print_settings(&service.settings()?, format),
+        OtpAction::Set { add_pattern, remove_pattern, allow, disallow, expiry } => {
+            let mut settings = service.settings()?;
+            for pattern in add_pattern {
+                if !settings.custom_patterns.contains(&pattern) {
+                    settings.custom_patterns.push(pattern);
+                }
+            }
+            settings.custom_patterns.retain(|p| !remove_pattern.contains(p));
+            for sender in allow {
+                if !settings.allowed_senders.iter().any(|s| s.eq_ignore_ascii_case(&sender)) {
+                    settings.allowed_senders.push(sender);
+                }
+            }
+            settings
+                .allowed_senders
+                .retain(|s| !disallow.iter().any(|d| d.eq_ignore_ascii_case(s)));
+            if let Some(expiry) = expiry {
+                settings.default_expiry_secs = expiry;
+            }
+            service.set_settings(&settings)?;
+            print_settings(&settings, format);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_code(code: &OtpCode, format: OutputFormat) {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string(code).unwrap_or_default());
+        }
+        OutputFormat::Text => {
+            let expires = chrono::DateTime::from_timestamp_millis(code.expires_at)
+                .map(|t| t.with_timezone(&chrono::Local).format("%H:%M:%S").to_string())
+                .unwrap_or_default();
+            println!(
+                "  {} {} from {} (expires {expires})",
+                style("[code]").magenta().bold(),
+                style(&code.code).green().bold(),
+                code.sender.as_deref().unwrap_or("Unknown"),
+            );
+        }
+    }
+}
+
+fn print_settings(settings: &bb_models::OtpSettings, format: OutputFormat) {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(settings).unwrap_or_default());
+        }
+        OutputFormat::Text => {
+            println!("  Default expiry: {}s", settings.default_expiry_secs);
+            if settings.custom_patterns.is_empty() {
+                println!("  Custom patterns: none");
+            } else {
+                println!("  Custom patterns:");
+                for pattern in &settings.custom_patterns {
+                    println!("    {pattern}");
+                }
+            }
+            if settings.allowed_senders.is_empty() {
+                println!("  Allowed senders: everyone");
+            } else {
+                println!("  Allowed senders: {}", settings.allowed_senders.join(", "));
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Person commands: one identity across a contact's handles.
+
+use clap::Subcommand;
+use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
+use console::style;
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_services::event_bus::EventBus;
+use bb_services::person::{PersonService, PersonSummary};
+use crate::OutputFormat;
+
+#[derive(Subcommand)]
+pub enum PeopleAction {
+    /// List people and the handles linked to each.
+    List {
+        /// Maximum number of people to display.
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Show one person.
+    Show {
+        /// Person ID, or any of their addresses.
+        person: String,
+    },
+    /// List one-to-one chats with a person on any of their handles.
+    Chats {
+        /// Person ID, or any of their addresses.
+        person: String,
+    },
+    /// Search messages exchanged with a person.
+    Search {
+        /// Person ID, or any of their addresses.
+        person: String,
+        /// Text to search for.
+        query: String,
+        /// Maximum number of results.
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+    /// Merge one person into another.
+    Merge {
+        /// Person to keep (ID or address).
+        target: String,
+        /// Person to merge into the target (ID or address).
+        source: String,
+    },
+    /// Move a handle out into a person of its own.
+    Split {
+        /// Handle ID to split off.
+        handle_id: i64,
+    },
+    /// Set a person's name, or clear it to use the contact name.
+    Rename {
+        /// Person ID, or any of their addresses.
+        person: String,
+        /// New name (omit to clear).
+        name: Option<String>,
+    },
+    /// Refresh automatic links from contacts and addresses.
+    Relink,
+}
+
+pub async fn run(config: ConfigHandle, action: PeopleAction, format: OutputFormat) -> BbResult<()> {
+    let db = super::init_database(&config).await?;
+    let service = PersonService::new(db, EventBus::new(16));
+
+    match action {
+        PeopleAction::List { limit } => {
+            service.relink()?;
+            let mut people = service.list_people()?;
+            if let Some(limit) = limit {
+                people.truncate(limit);
+            }
+            match format {
+                OutputFormat::Json => print_json(&people),
+                OutputFormat::Text => {
+                    if people.is_empty() {
+                        println!("No people yet. Run a sync first.");
+                        return Ok(());
+                    }
+                    let mut table = new_table(vec!["ID", "Name", "Handles"]);
+                    for p in &people {
+                        table.add_row(vec![
+                            p.id.to_string(),
+                            super::truncate(&p.display_name, 30),
+                            handle_list(p),
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        PeopleAction::Show { person } => {
+            let p = resolve(&service, &person)?;
+            match format {
+                OutputFormat::Json => print_json(&p),
+                OutputFormat::Text => print_person(&p),
+            }
+        }
+        PeopleAction::Chats { person } => {
+            let p = resolve(&service, &person)?;
+            let chats = service.chats(p.id)?;
+            match format {
+                OutputFormat::Json => {
+                    let json: Vec<_> = chats.iter().map(|c| {
+                        serde_json::json!({
+                            "guid": c.guid,
+                            "title": c.title(),
+                            "latest_message_date": c.latest_message_date,
+                        })
+                    }).collect();
+                    print_json(&json);
+                }
+                OutputFormat::Text => {
+                    if chats.is_empty() {
+                        println!("No one-to-one chats with {}.", p.display_name);
+                        return Ok(());
+                    }
+                    let mut table = new_table(vec!["Chat", "Service", "Last message"]);
+                    for c in &chats {
+                        let service = c.guid.split(';').next().unwrap_or("").to_string();
+                        table.add_row(vec![
+                            c.guid.clone(),
+                            service,
+                            c.latest_message_date.clone().unwrap_or_else(|| "-".into()),
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        PeopleAction::Search { person, query, limit } => {
+            let p = resolve(&service, &person)?;
+            let messages = service.search_messages(p.id, &query, limit)?;
+            match format {
+                OutputFormat::Json => {
+                    let json: Vec<_> = messages.iter().map(|m| {
+                        serde_json::json!({
+                            "guid": m.guid,
+                            "text": m.text,
+                            "is_from_me": m.is_from_me,
+                            "date_created": m.date_created,
+                        })
+                    }).collect();
+                    print_json(&json);
+                }
+                OutputFormat::Text => {
+                    if messages.is_empty() {
+                        println!("No messages with {} matching \"{query}\".", p.display_name);
+                        return Ok(());
+                    }
+                    let mut table = new_table(vec!["Date", "From", "Text"]);
+                    for m in &messages {
+                        table.add_row(vec![
+                            m.date_created.clone().unwrap_or_default(),
+                            if m.is_from_me { "Me".to_string() } else { p.display_name.clone() },
+                            super::truncate(m.text.as_deref().unwrap_or(""), 60),
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        PeopleAction::Merge { target, source } => {
+            let target = resolve(&service, &target)?;
+            let source = resolve(&service, &source)?;
+            let merged = service.merge(target.id, source.id)?;
+            match format {
+                OutputFormat::Json => print_json(&merged),
+                OutputFormat::Text => {
+                    println!(
+                        "  {} Merged {} into {}",
+                        style("✓").green(),
+                        source.display_name,
+                        merged.display_name
+                    );
+                    print_person(&merged);
+                }
+            }
+        }
+        PeopleAction::Split { handle_id } => {
+            let p = service.split(handle_id)?;
+            match format {
+                OutputFormat::Json => print_json(&p),
+                OutputFormat::Text => {
+                    println!("  {} Handle {handle_id} is now person {}", style("✓").green(), p.id);
+                }
+            }
+        }
+        PeopleAction::Rename { person, name } => {
+            let p = resolve(&service, &person)?;
+            let p = service.rename(p.id, name.as_deref())?;
+            match format {
+                OutputFormat::Json => print_json(&p),
+                OutputFormat::Text => {
+                    println!("  {} Person {} is now \"{}\"", style("✓").green(), p.id, p.display_name);
+                }
+            }
+        }
+        PeopleAction::Relink => {
+            let changed = service.relink()?;
+            match format {
+                OutputFormat::Json => print_json(&serde_json::json!({ "changed": changed })),
+                OutputFormat::Text => {
+                    println!("  {} Updated {changed} handle link(s)", style("✓").green());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Look a person up by ID, falling back to an address on any service.
+fn resolve(service: &PersonService, reference: &str) -> BbResult<PersonSummary> {
+    let found = match reference.parse::<i64>() {
+        Ok(id) if !reference.starts_with('+') => service.get_person(id)?,
+        _ => None,
+    };
+    match found {
+        Some(p) => Ok(p),
+        None => service
+            .find_by_address(reference)?
+            .ok_or_else(|| BbError::InvalidInput(format!("no person matching {reference}"))),
+    }
+}
+
+fn handle_list(p: &PersonSummary) -> String {
+    p.handles
+        .iter()
+        .map(|h| format!("{} ({})", h.address, h.service))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn print_person(p: &PersonSummary) {
+    println!("{} {}", style(&p.display_name).bold(), style(format!("#{}", p.id)).dim());
+    let mut table = new_table(vec!["Handle", "Address", "Service", "Normalized"]);
+    for h in &p.handles {
+        table.add_row(vec![
+            h.id.map(|id| id.to_string()).unwrap_or_default(),
+            h.address.clone(),
+            h.service.clone(),
+            h.normalized_address.clone().unwrap_or_else(|| "-".into()),
+        ]);
+    }
+    println!("{table}");
+}
+
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+fn new_table(header: Vec<&str>) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(header);
+    table
+}
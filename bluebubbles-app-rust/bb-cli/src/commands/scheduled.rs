@@ -1,4 +1,12 @@
 //! Scheduled message commands - list, create, update, and delete scheduled messages.
+//!
+//! `--local` schedules are kept in the local database and sent by the
+//! client's own scheduler (the desktop app, or `scheduled run`) instead of
+//! the server's private-API scheduler.
+//!
+//! `--recurrence` takes a cron expression (`0 9 * * MON-FRI`) or an RRULE
+//! (`FREQ=WEEKLY;BYDAY=MO,WE`), evaluated in `--tz` (default: the system
+//! time zone). `preview` lists the upcoming occurrences of either.
 
 use clap::Subcommand;
 use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
@@ -11,7 +19,11 @@ use crate::OutputFormat;
 #[derive(Subcommand)]
 pub enum ScheduledAction {
     /// List all scheduled messages.
-    List,
+    List {
+        /// List pending local schedules instead of the server's.
+        #[arg(long)]
+        local: bool,
+    },
     /// Create a new scheduled message.
     Create {
         /// Chat GUID to send the message to.
@@ -26,6 +38,15 @@ pub enum ScheduledAction {
         /// Schedule type (send-message, remind).
         #[arg(short = 't', long, default_value = "send-message")]
         schedule_type: String,
+        /// Send from this client's scheduler rather than the server's.
+        #[arg(long)]
+        local: bool,
+        /// Repeat per a cron expression or RRULE, starting at --scheduled-for.
+        #[arg(short, long)]
+        recurrence: Option<String>,
+        /// IANA time zone for --recurrence (default: the system time zone).
+        #[arg(long)]
+        tz: Option<String>,
     },
     /// Update an existing scheduled message.
     Update {
@@ -46,16 +67,214 @@ pub enum ScheduledAction {
     },
     /// Delete a scheduled message.
     Delete {
-        /// ID of the scheduled message to delete.
+        /// ID of the scheduled message to delete (negative for local ones).
+        #[arg(allow_negative_numbers = true)]
         id: i64,
     },
+    /// Run the local scheduler in the foreground until Ctrl-C.
+    Run,
+    /// Show the upcoming occurrences of a cron expression or RRULE.
+    Preview {
+        /// Cron expression or RRULE.
+        expr: String,
+        /// IANA time zone (default: the system time zone).
+        #[arg(long)]
+        tz: Option<String>,
+        /// First occurrence / earliest time (epoch timestamp in milliseconds; default: now).
+        #[arg(short, long)]
+        start: Option<i64>,
+        /// Number of occurrences to show.
+        #[arg(short = 'n', long, default_value = "10")]
+        count: usize,
+        /// Also show the server's schedule format for the recurrence.
+        #[arg(long)]
+        server: bool,
+    },
 }
 
 pub async fn run(config: ConfigHandle, action: ScheduledAction, format: OutputFormat) -> BbResult<()> {
-    let api = super::create_api_client(&config).await?;
-
     match action {
-        ScheduledAction::List => {
+        ScheduledAction::List { local: true } => {
+            let service = local_service(&config).await?;
+            let messages = service.list_local()?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&messages).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if messages.is_empty() {
+                        println!("No local scheduled messages.");
+                    } else {
+                        let mut table = Table::new();
+                        table
+                            .load_preset(UTF8_FULL)
+                            .apply_modifier(UTF8_ROUND_CORNERS)
+                            .set_content_arrangement(ContentArrangement::Dynamic);
+
+                        table.set_header(vec!["ID", "Chat", "Message", "Scheduled For", "Repeats", "Last Error"]);
+
+                        for msg in &messages {
+                            let scheduled_for = msg
+                                .scheduled_time()
+                                .map(|dt| dt.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                                .unwrap_or_else(|| msg.scheduled_for.clone());
+                            table.add_row(vec![
+                                msg.id.unwrap_or_default().to_string(),
+                                super::truncate(&msg.chat_guid, 30),
+                                super::truncate(&msg.message, 40),
+                                scheduled_for,
+                                match (&msg.recurrence, &msg.time_zone) {
+                                    (Some(r), Some(tz)) => format!("{r} ({tz})"),
+                                    (Some(r), None) => r.clone(),
+                                    _ => "-".to_string(),
+                                },
+                                super::truncate(msg.error.as_deref().unwrap_or("-"), 40),
+                            ]);
+                        }
+
+                        println!("{table}");
+                        println!("\n{} local scheduled message(s).", messages.len());
+                    }
+                }
+            }
+        }
+        ScheduledAction::Create { chat, message, scheduled_for, local: true, recurrence, tz, .. } => {
+            let when = schedule_time(scheduled_for)?;
+            let service = local_service(&config).await?;
+            let msg = match recurrence {
+                Some(expr) => {
+                    let tz = time_zone(tz.as_deref())?;
+                    service.create_local_recurring(&chat, &message, when, &expr, tz.name()).await?
+                }
+                None => service.create_local(&chat, &message, when).await?,
+            };
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&msg).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    println!(
+                        "  {} Local scheduled message created (id: {}). It is sent by the desktop app or `scheduled run`.",
+                        style("OK").green().bold(),
+                        msg.id.unwrap_or_default()
+                    );
+                }
+            }
+        }
+        ScheduledAction::Delete { id } if id < 0 => {
+            let service = local_service(&config).await?;
+            let deleted = service.delete_local(id).await?;
+
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::json!({ "id": id, "deleted": deleted }));
+                }
+                OutputFormat::Text if deleted => {
+                    println!("  {} Local scheduled message {} deleted.", style("OK").green().bold(), id);
+                }
+                OutputFormat::Text => {
+                    println!("{} Local scheduled message {} not found.", style("ERROR").red().bold(), id);
+                }
+            }
+        }
+        ScheduledAction::Run => {
+            let db = super::init_database(&config).await?;
+            let api = super::create_api_client(&config).await?;
+            let event_bus = bb_services::EventBus::new(64);
+            let queue = std::sync::Arc::new(bb_services::queue::QueueService::new().with_database(db.clone()));
+            queue.reload().await?;
+            let messages = std::sync::Arc::new(
+                bb_services::message::MessageService::new(db.clone(), event_bus.clone())
+                    .with_link_previews(std::sync::Arc::new(bb_services::LinkPreviewService::new(db.clone()))),
+            );
+            let service = std::sync::Arc::new(bb_services::ScheduledMessageService::new(db, event_bus));
+            let api = std::sync::Arc::new(tokio::sync::RwLock::new(Some(api)));
+
+            let pending = service.list_local()?.len();
+            println!(
+                "  {} Running local scheduler ({} pending). Press Ctrl-C to stop.",
+                style("...").dim(),
+                pending
+            );
+            let retry = bb_services::message::MessageService::start_retry_loop(messages.clone(), queue.clone(), api.clone());
+            let scheduler = bb_services::ScheduledMessageService::start_local_scheduler(
+                service,
+                messages,
+                queue,
+                config.clone(),
+                api,
+            );
+            let _ = tokio::signal::ctrl_c().await;
+            scheduler.abort();
+            retry.abort();
+            println!("  {} Local scheduler stopped.", style("OK").green().bold());
+        }
+        ScheduledAction::Preview { expr, tz, start, count, server } => {
+            let recurrence = bb_models::Recurrence::parse(&expr)?;
+            let tz = time_zone(tz.as_deref())?;
+            let start = match start {
+                Some(ms) => schedule_time(ms)?,
+                None => chrono::Utc::now(),
+            };
+            let times = recurrence.upcoming(start, tz, start - chrono::Duration::seconds(1), count);
+            let schedule = if server { Some(recurrence.server_schedule(start, tz)) } else { None };
+
+            match format {
+                OutputFormat::Json => {
+                    let mut out = serde_json::json!({
+                        "recurrence": recurrence.to_string(),
+                        "timeZone": tz.name(),
+                        "occurrences": times.iter().map(|t| t.to_rfc3339()).collect::<Vec<_>>(),
+                    });
+                    match schedule {
+                        Some(Ok((first, schedule))) => {
+                            out["server"] = serde_json::json!({
+                                "scheduledFor": first.timestamp_millis(),
+                                "schedule": schedule,
+                            });
+                        }
+                        Some(Err(e)) => out["serverError"] = serde_json::Value::String(e.to_string()),
+                        None => {}
+                    }
+                    println!("{}", serde_json::to_string_pretty(&out).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if times.is_empty() {
+                        println!("No upcoming occurrences.");
+                    } else {
+                        let mut table = Table::new();
+                        table
+                            .load_preset(UTF8_FULL)
+                            .apply_modifier(UTF8_ROUND_CORNERS)
+                            .set_content_arrangement(ContentArrangement::Dynamic);
+
+                        table.set_header(vec!["#", &format!("{} Time", tz.name()), "UTC"]);
+                        for (i, t) in times.iter().enumerate() {
+                            table.add_row(vec![
+                                (i + 1).to_string(),
+                                t.with_timezone(&tz).format("%a %Y-%m-%d %H:%M %Z").to_string(),
+                                t.format("%Y-%m-%d %H:%M").to_string(),
+                            ]);
+                        }
+                        println!("{table}");
+                    }
+                    match schedule {
+                        Some(Ok((first, schedule))) => println!(
+                            "\n  Server schedule: first send {} (epoch ms {}), schedule {}",
+                            first.with_timezone(&tz).format("%Y-%m-%d %H:%M %Z"),
+                            first.timestamp_millis(),
+                            schedule
+                        ),
+                        Some(Err(e)) => println!("\n  {} {}", style("Server can't run this:").yellow(), e),
+                        None => {}
+                    }
+                }
+            }
+        }
+        ScheduledAction::List { .. } => {
+            let api = super::create_api_client(&config).await?;
             let messages = api.get_scheduled_messages().await?;
 
             match format {
@@ -118,22 +337,17 @@ pub async fn run(config: ConfigHandle, action: ScheduledAction, format: OutputFo
                 }
             }
         }
-        ScheduledAction::Create { chat, message, scheduled_for, schedule_type } => {
-            let temp_guid = format!("temp-{}", uuid::Uuid::new_v4());
-
-            let payload = serde_json::json!({
-                "chatGuid": chat,
-                "message": message,
-                "tempGuid": temp_guid,
-                "method": "private-api",
-            });
-
-            let params = bb_api::endpoints::messages::ScheduleMessageParams {
-                schedule_type,
-                payload,
-                scheduled_for,
-                schedule: None,
-            };
+        ScheduledAction::Create { chat, message, scheduled_for, schedule_type, recurrence, tz, .. } => {
+            let api = super::create_api_client(&config).await?;
+            let tz = time_zone(tz.as_deref())?;
+            let recurrence = recurrence.as_deref().map(|expr| (expr, tz.name()));
+            let mut params = bb_services::ScheduledMessageService::server_params(
+                &chat,
+                &message,
+                schedule_time(scheduled_for)?,
+                recurrence,
+            )?;
+            params.schedule_type = schedule_type;
 
             println!(
                 "  {} Creating scheduled message for {}...",
@@ -158,6 +372,7 @@ pub async fn run(config: ConfigHandle, action: ScheduledAction, format: OutputFo
             }
         }
         ScheduledAction::Update { id, chat, message, scheduled_for, schedule_type } => {
+            let api = super::create_api_client(&config).await?;
             // Fetch the existing scheduled message to merge changes
             let existing_messages = api.get_scheduled_messages().await?;
             let existing = existing_messages.iter()
@@ -216,6 +431,7 @@ pub async fn run(config: ConfigHandle, action: ScheduledAction, format: OutputFo
             }
         }
         ScheduledAction::Delete { id } => {
+            let api = super::create_api_client(&config).await?;
             println!(
                 "  {} Deleting scheduled message {}...",
                 style("...").dim(),
@@ -241,3 +457,23 @@ pub async fn run(config: ConfigHandle, action: ScheduledAction, format: OutputFo
 
     Ok(())
 }
+
+/// The scheduled message service over the local database.
+async fn local_service(config: &ConfigHandle) -> BbResult<bb_services::ScheduledMessageService> {
+    let db = super::init_database(config).await?;
+    Ok(bb_services::ScheduledMessageService::new(db, bb_services::EventBus::new(16)))
+}
+
+/// Parse a schedule time given as epoch milliseconds.
+fn schedule_time(ms: i64) -> BbResult<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .ok_or_else(|| bb_core::error::BbError::InvalidInput(format!("invalid schedule time: {ms}")))
+}
+
+/// The named IANA time zone, or the system's.
+fn time_zone(name: Option<&str>) -> BbResult<bb_models::models::recurrence::Tz> {
+    match name {
+        Some(name) => bb_models::models::recurrence::parse_time_zone(name),
+        None => Ok(bb_models::models::recurrence::system_time_zone()),
+    }
+}
@@ -66,6 +66,11 @@ fn get_setting_value(cfg: &bb_core::config::AppConfig, key: &str) -> Option<Stri
         "sync.messages_per_page" => Some(cfg.sync.messages_per_page.to_string()),
         "sync.skip_empty_chats" => Some(cfg.sync.skip_empty_chats.to_string()),
         "sync.sync_contacts_automatically" => Some(cfg.sync.sync_contacts_automatically.to_string()),
+        "sync.default_phone_region" => Some(cfg.sync.default_phone_region.clone()),
+        "conversation.move_to_trash" => Some(cfg.conversation.move_to_trash.to_string()),
+        "conversation.trash_retention_days" => Some(cfg.conversation.trash_retention_days.to_string()),
+        "conversation.scheduled_catch_up" => Some(cfg.conversation.scheduled_catch_up.clone()),
+        "conversation.scheduled_catch_up_minutes" => Some(cfg.conversation.scheduled_catch_up_minutes.to_string()),
         "notifications.notify_reactions" => Some(cfg.notifications.notify_reactions.to_string()),
         "notifications.notify_on_chat_list" => Some(cfg.notifications.notify_on_chat_list.to_string()),
         "notifications.filter_unknown_senders" => Some(cfg.notifications.filter_unknown_senders.to_string()),
@@ -116,6 +121,29 @@ fn set_setting_value(cfg: &mut bb_core::config::AppConfig, key: &str, value: &st
         "sync.sync_contacts_automatically" => {
             cfg.sync.sync_contacts_automatically = value.parse().map_err(|_| "expected true/false".to_string())?;
         }
+        "sync.default_phone_region" => {
+            let v = value.trim().to_uppercase();
+            if !v.is_empty() && !bb_models::phone::is_known_region(&v) {
+                return Err("expected a two-letter region code (e.g. US, GB) or empty".to_string());
+            }
+            cfg.sync.default_phone_region = v;
+        }
+        "conversation.move_to_trash" => {
+            cfg.conversation.move_to_trash = value.parse().map_err(|_| "expected true/false".to_string())?;
+        }
+        "conversation.trash_retention_days" => {
+            cfg.conversation.trash_retention_days = value.parse().map_err(|_| "invalid integer".to_string())?;
+        }
+        "conversation.scheduled_catch_up" => {
+            let v = value.to_lowercase();
+            if !["send", "skip", "recent"].contains(&v.as_str()) {
+                return Err("expected one of: send, skip, recent".to_string());
+            }
+            cfg.conversation.scheduled_catch_up = v;
+        }
+        "conversation.scheduled_catch_up_minutes" => {
+            cfg.conversation.scheduled_catch_up_minutes = value.parse().map_err(|_| "invalid integer".to_string())?;
+        }
         "notifications.notify_reactions" => {
             cfg.notifications.notify_reactions = value.parse().map_err(|_| "expected true/false".to_string())?;
         }
@@ -156,6 +184,7 @@ fn print_settings_text(cfg: &bb_core::config::AppConfig) {
     println!("  sync.messages_per_page            {}", cfg.sync.messages_per_page);
     println!("  sync.skip_empty_chats             {}", cfg.sync.skip_empty_chats);
     println!("  sync.sync_contacts_automatically  {}", cfg.sync.sync_contacts_automatically);
+    println!("  sync.default_phone_region         {}", cfg.sync.default_phone_region);
 
     println!();
     println!("{}", style("Logging").bold().underlined());
@@ -194,6 +223,7 @@ fn settings_json(cfg: &bb_core::config::AppConfig) -> serde_json::Value {
             "messages_per_page": cfg.sync.messages_per_page,
             "skip_empty_chats": cfg.sync.skip_empty_chats,
             "sync_contacts_automatically": cfg.sync.sync_contacts_automatically,
+            "default_phone_region": cfg.sync.default_phone_region,
         },
         "logging": {
             "level": cfg.logging.level,
@@ -267,9 +297,22 @@ pub async fn run(config: ConfigHandle, action: SettingsAction, format: OutputFor
                 }
             }
             // Save to disk
-            let cfg = config.read().await;
-            let path = bb_core::platform::Platform::config_dir()?.join("config.toml");
-            cfg.save_to_file(&path)?;
+            let region = {
+                let cfg = config.read().await;
+                let path = bb_core::platform::Platform::config_dir()?.join("config.toml");
+                cfg.save_to_file(&path)?;
+                cfg.sync.default_phone_region.clone()
+            };
+
+            // Stored numbers are keyed by the region they were normalized in
+            if key == "sync.default_phone_region" {
+                let db = super::init_database(&config).await?;
+                let conn = db.conn()?;
+                let (handles, contacts) = bb_models::queries::set_default_phone_region(&conn, &region)?;
+                if matches!(format, OutputFormat::Text) {
+                    println!("  Renormalized {handles} handles and {contacts} contacts.");
+                }
+            }
 
             match format {
                 OutputFormat::Json => {
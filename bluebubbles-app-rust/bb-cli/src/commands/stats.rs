@@ -0,0 +1,197 @@
+//! Messaging statistics command.
+
+use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
+use console::style;
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::BbResult;
+use bb_services::export::parse_date_bound;
+use bb_services::stats::{MessagingStats, StatsOptions, StatsService};
+use crate::OutputFormat;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Run the stats command.
+pub async fn run(
+    config: ConfigHandle,
+    since: Option<String>,
+    until: Option<String>,
+    chat: Option<String>,
+    top: usize,
+    utc: bool,
+    format: OutputFormat,
+) -> BbResult<()> {
+    let options = StatsOptions {
+        since: since.as_deref().map(parse_date_bound).transpose()?,
+        until: until.as_deref().map(parse_date_bound).transpose()?,
+        chat_guid: chat,
+        top,
+        utc_offset_secs: if utc {
+            0
+        } else {
+            i64::from(chrono::Local::now().offset().local_minus_utc())
+        },
+    };
+
+    let db = super::init_database(&config).await?;
+    let service = StatsService::new(db, bb_services::event_bus::EventBus::new(16));
+    let stats = service.compute(&options)?;
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&stats).unwrap_or_default());
+        }
+        OutputFormat::Text => print_text(&stats),
+    }
+    Ok(())
+}
+
+fn new_table(header: Vec<&str>) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(header);
+    table
+}
+
+fn format_duration(secs: Option<i64>) -> String {
+    match secs {
+        None => "-".to_string(),
+        Some(s) if s < 60 => format!("{s}s"),
+        Some(s) if s < 3600 => format!("{}m", s / 60),
+        Some(s) if s < 86_400 => format!("{}h {}m", s / 3600, (s % 3600) / 60),
+        Some(s) => format!("{}d {}h", s / 86_400, (s % 86_400) / 3600),
+    }
+}
+
+fn print_text(stats: &MessagingStats) {
+    let totals = &stats.totals;
+    println!("{}", style("Messaging Statistics").bold().underlined());
+    if stats.since.is_some() || stats.until.is_some() {
+        println!(
+            "  Range:        {} .. {}",
+            stats.since.as_deref().unwrap_or("start"),
+            stats.until.as_deref().unwrap_or("now")
+        );
+    }
+    println!("  Messages:     {} in {} chat(s)", totals.messages, totals.chats);
+    println!(
+        "  Sent/recv:    {} / {} ({:.0}% sent)",
+        totals.sent,
+        totals.received,
+        totals.sent_ratio * 100.0
+    );
+    println!(
+        "  Attachments:  {} ({})",
+        totals.attachments,
+        super::format_bytes(totals.attachment_bytes.max(0) as u64)
+    );
+    println!(
+        "  Reactions:    {} sent, {} received",
+        totals.reactions_sent, totals.reactions_received
+    );
+
+    if !stats.chats.is_empty() {
+        println!();
+        println!("{}", style("Top Chats").bold().underlined());
+        let mut table = new_table(vec!["Chat", "Messages", "Sent", "Received", "Per active day"]);
+        for chat in &stats.chats {
+            let days = chat.per_day.len().max(1) as f64;
+            table.add_row(vec![
+                super::truncate(&chat.title, 40),
+                chat.messages.to_string(),
+                chat.sent.to_string(),
+                chat.received.to_string(),
+                format!("{:.1}", chat.messages as f64 / days),
+            ]);
+        }
+        println!("{table}");
+    }
+
+    if !stats.top_contacts.is_empty() {
+        println!();
+        println!("{}", style("Top Contacts").bold().underlined());
+        let mut table = new_table(vec!["Contact", "Messages", "Sent", "Received", "My reply", "Their reply"]);
+        for contact in &stats.top_contacts {
+            table.add_row(vec![
+                super::truncate(&contact.name, 30),
+                contact.messages.to_string(),
+                contact.sent.to_string(),
+                contact.received.to_string(),
+                format_duration(contact.my_median_response_secs),
+                format_duration(contact.their_median_response_secs),
+            ]);
+        }
+        println!("{table}");
+    }
+
+    // Only worth showing when some person spans more than one handle
+    if stats.top_people.iter().any(|p| p.handles > 1) {
+        println!();
+        println!("{}", style("Top People").bold().underlined());
+        let mut table = new_table(vec!["Person", "Handles", "Messages", "Sent", "Received"]);
+        for person in &stats.top_people {
+            table.add_row(vec![
+                super::truncate(&person.name, 30),
+                person.handles.to_string(),
+                person.messages.to_string(),
+                person.sent.to_string(),
+                person.received.to_string(),
+            ]);
+        }
+        println!("{table}");
+    }
+
+    let busiest = stats
+        .hour_heatmap
+        .iter()
+        .enumerate()
+        .flat_map(|(day, hours)| hours.iter().enumerate().map(move |(hour, &count)| (day, hour, count)))
+        .filter(|(_, _, count)| *count > 0)
+        .max_by_key(|(_, _, count)| *count);
+    if let Some((day, hour, count)) = busiest {
+        println!();
+        println!("{}", style("Activity").bold().underlined());
+        println!("  Busiest hour: {} {:02}:00 ({} messages)", WEEKDAYS[day], hour, count);
+        let by_hour: Vec<i64> = (0..24)
+            .map(|h| stats.hour_heatmap.iter().map(|d| d[h]).sum())
+            .collect();
+        let max = by_hour.iter().copied().max().unwrap_or(0).max(1);
+        let bars: String = by_hour
+            .iter()
+            .map(|&c| ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'][((c * 7) / max) as usize])
+            .collect();
+        println!("  By hour:      {bars}");
+        println!("                {}", style("0     6     12    18   23").dim());
+    }
+
+    if !stats.attachments.is_empty() {
+        println!();
+        println!("{}", style("Attachments").bold().underlined());
+        let mut table = new_table(vec!["Type", "Count", "Size"]);
+        for class in &stats.attachments {
+            table.add_row(vec![
+                class.mime_class.clone(),
+                class.count.to_string(),
+                super::format_bytes(class.bytes.max(0) as u64),
+            ]);
+        }
+        println!("{table}");
+    }
+
+    if !stats.reactions.is_empty() {
+        println!();
+        println!("{}", style("Reactions").bold().underlined());
+        let mut table = new_table(vec!["Reaction", "Sent", "Received"]);
+        for reaction in &stats.reactions {
+            table.add_row(vec![
+                reaction.reaction.clone(),
+                reaction.sent.to_string(),
+                reaction.received.to_string(),
+            ]);
+        }
+        println!("{table}");
+    }
+}
@@ -0,0 +1,127 @@
+//! Trash commands: list, restore and purge deleted chats and messages.
+
+use clap::Subcommand;
+use comfy_table::{Table, presets::UTF8_FULL, modifiers::UTF8_ROUND_CORNERS, ContentArrangement};
+use console::style;
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_services::event_bus::EventBus;
+use bb_services::trash::{PurgeReport, TrashKind, TrashService};
+use crate::OutputFormat;
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List deleted chats and messages.
+    List,
+    /// Restore a chat or message from the trash.
+    Restore {
+        /// GUID of the chat or message.
+        guid: String,
+    },
+    /// Permanently delete items from the trash.
+    ///
+    /// Without arguments, purges items past the retention window.
+    Purge {
+        /// Purge everything in the trash.
+        #[arg(long, conflicts_with = "guid")]
+        all: bool,
+        /// Purge one chat or message by GUID.
+        #[arg(long)]
+        guid: Option<String>,
+    },
+}
+
+pub async fn run(config: ConfigHandle, action: TrashAction, format: OutputFormat) -> BbResult<()> {
+    let db = super::init_database(&config).await?;
+    let service = TrashService::new(config, db, EventBus::new(16), super::attachment_cache_dir());
+
+    match action {
+        TrashAction::List => {
+            let items = service.list().await?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&items).unwrap_or_default());
+                }
+                OutputFormat::Text => {
+                    if items.is_empty() {
+                        println!("Trash is empty.");
+                        return Ok(());
+                    }
+                    let mut table = Table::new();
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic)
+                        .set_header(vec!["Kind", "GUID", "Chat", "Preview", "Deleted", "Purge after"]);
+                    for item in &items {
+                        let kind = match item.kind {
+                            TrashKind::Chat => "chat",
+                            TrashKind::Message => "message",
+                        };
+                        table.add_row(vec![
+                            kind.to_string(),
+                            item.guid.clone(),
+                            super::truncate(&item.title, 25),
+                            super::truncate(item.preview.as_deref().unwrap_or(""), 40),
+                            item.date_deleted.clone(),
+                            item.purge_after.clone().unwrap_or_else(|| "-".into()),
+                        ]);
+                    }
+                    println!("{table}");
+                }
+            }
+        }
+        TrashAction::Restore { guid } => {
+            let kind = service.restore(&guid)?;
+            match format {
+                OutputFormat::Json => {
+                    println!("{}", serde_json::json!({ "guid": guid, "kind": kind }));
+                }
+                OutputFormat::Text => {
+                    let what = if kind == TrashKind::Chat { "chat" } else { "message" };
+                    println!("  {} Restored {what} {guid}", style("✓").green());
+                }
+            }
+        }
+        TrashAction::Purge { all, guid } => {
+            let report = match (all, guid) {
+                (true, _) => service.purge_all()?,
+                (false, Some(guid)) => service.purge(&guid)?,
+                (false, None) => {
+                    if service.retention_days().await.is_none() {
+                        println!(
+                            "  {} Automatic purging is off (move_to_trash is off or the window is 0 days); use --all to empty the trash.",
+                            style("!").yellow()
+                        );
+                    }
+                    service.purge_expired().await?
+                }
+            };
+            print_report(&report, format)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &PurgeReport, format: OutputFormat) -> BbResult<()> {
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(report)
+                .map_err(|e| BbError::Serialization(e.to_string()))?;
+            println!("{json}");
+        }
+        OutputFormat::Text => {
+            println!(
+                "  {} Purged {} chat(s) and {} message(s); removed {} file(s), {} freed.",
+                style("✓").green(),
+                report.chats_purged,
+                report.messages_purged,
+                report.files_removed,
+                super::format_bytes(report.bytes_freed)
+            );
+        }
+    }
+    Ok(())
+}
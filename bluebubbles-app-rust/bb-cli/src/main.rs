@@ -62,6 +62,10 @@ enum Commands {
         /// Save connection settings to config file after successful connect.
         #[arg(long)]
         save: bool,
+        /// Save incoming messages and show desktop notifications for them,
+        /// applying notification rules, quiet hours and digests.
+        #[arg(short, long)]
+        notify: bool,
     },
     /// Show the current connection and server status.
     Status,
@@ -80,6 +84,31 @@ enum Commands {
         #[command(subcommand)]
         action: commands::contacts::ContactsAction,
     },
+    /// View and manage people across their handles.
+    People {
+        #[command(subcommand)]
+        action: commands::people::PeopleAction,
+    },
+    /// List, restore and purge deleted chats and messages.
+    Trash {
+        #[command(subcommand)]
+        action: commands::trash::TrashAction,
+    },
+    /// Tag and annotate bookmarked messages.
+    Bookmarks {
+        #[command(subcommand)]
+        action: commands::bookmarks::BookmarksAction,
+    },
+    /// Edit notification rules and quiet hours.
+    Notifications {
+        #[command(subcommand)]
+        action: commands::notifications::NotificationsAction,
+    },
+    /// Watch for one-time codes and edit OTP detection settings.
+    Otp {
+        #[command(subcommand)]
+        action: commands::otp::OtpAction,
+    },
     /// Manage attachments.
     Attachments {
         #[command(subcommand)]
@@ -148,6 +177,24 @@ enum Commands {
         #[command(subcommand)]
         action: commands::private_api::PrivateApiAction,
     },
+    /// Show messaging statistics computed from the local database.
+    Stats {
+        /// Only count messages on or after this date (YYYY-MM-DD or RFC 3339).
+        #[arg(long)]
+        since: Option<String>,
+        /// Only count messages before this date.
+        #[arg(long)]
+        until: Option<String>,
+        /// Restrict to a single chat GUID.
+        #[arg(long)]
+        chat: Option<String>,
+        /// Number of chats and contacts to list.
+        #[arg(long, default_value = "10")]
+        top: usize,
+        /// Bucket days and hours in UTC instead of local time.
+        #[arg(long)]
+        utc: bool,
+    },
     /// Diagnostic commands for troubleshooting avatar sync and missing chats.
     Diagnose {
         #[command(subcommand)]
@@ -185,8 +232,8 @@ async fn main() -> BbResult<()> {
 
     // Dispatch to command handlers
     match cli.command {
-        Commands::Connect { address, password, save } => {
-            commands::connect::run(config_handle, address, password, save).await
+        Commands::Connect { address, password, save, notify } => {
+            commands::connect::run(config_handle, address, password, save, notify).await
         }
         Commands::Status => {
             commands::status::run(config_handle, cli.format).await
@@ -200,6 +247,21 @@ async fn main() -> BbResult<()> {
         Commands::Contacts { action } => {
             commands::contacts::run(config_handle, action, cli.format).await
         }
+        Commands::People { action } => {
+            commands::people::run(config_handle, action, cli.format).await
+        }
+        Commands::Trash { action } => {
+            commands::trash::run(config_handle, action, cli.format).await
+        }
+        Commands::Bookmarks { action } => {
+            commands::bookmarks::run(config_handle, action, cli.format).await
+        }
+        Commands::Notifications { action } => {
+            commands::notifications::run(config_handle, action, cli.format).await
+        }
+        Commands::Otp { action } => {
+            commands::otp::run(config_handle, action, cli.format).await
+        }
         Commands::Attachments { action } => {
             commands::attachments::run(config_handle, action, cli.format).await
         }
@@ -233,6 +295,9 @@ async fn main() -> BbResult<()> {
         Commands::PrivateApi { action } => {
             commands::private_api::run(config_handle, action, cli.format).await
         }
+        Commands::Stats { since, until, chat, top, utc } => {
+            commands::stats::run(config_handle, since, until, chat, top, utc, cli.format).await
+        }
         Commands::Diagnose { action } => {
             commands::diagnose::run(config_handle, action, cli.format).await
         }
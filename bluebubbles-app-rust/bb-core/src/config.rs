@@ -94,6 +94,55 @@ pub struct DatabaseConfig {
     /// Run integrity check on startup.
     #[serde(default = "default_true")]
     pub integrity_check_on_startup: bool,
+
+    /// Open the database as a SQLCipher-encrypted file.
+    ///
+    /// Requires a build with the `sqlcipher` feature. The key is read from
+    /// `key_file` if set, otherwise from the `BLUEBUBBLES_DB_PASSPHRASE`
+    /// environment variable.
+    #[serde(default)]
+    pub encrypted: bool,
+
+    /// Path to a file containing the database passphrase or raw hex key.
+    #[serde(default)]
+    pub key_file: String,
+
+    /// Rotating local snapshot settings.
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
+}
+
+/// Local database snapshot configuration.
+///
+/// Snapshots are taken with the SQLite online backup API and pruned with a
+/// grandfather-father-son scheme: the newest snapshot in each of the last
+/// `keep_hourly` hours, `keep_daily` days and `keep_weekly` weeks is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Take snapshots on a schedule. Off by default: each snapshot is a
+    /// full copy of the database.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Minutes between scheduled snapshots.
+    #[serde(default = "default_snapshot_interval")]
+    pub interval_minutes: u64,
+
+    /// Directory for snapshot files. If empty, uses `<data dir>/snapshots`.
+    #[serde(default)]
+    pub directory: String,
+
+    /// Number of hourly snapshots to keep.
+    #[serde(default = "default_keep_hourly")]
+    pub keep_hourly: u32,
+
+    /// Number of daily snapshots to keep.
+    #[serde(default = "default_keep_daily")]
+    pub keep_daily: u32,
+
+    /// Number of weekly snapshots to keep.
+    #[serde(default = "default_keep_weekly")]
+    pub keep_weekly: u32,
 }
 
 /// Logging configuration.
@@ -146,6 +195,11 @@ pub struct SyncConfig {
     /// Automatically sync contacts.
     #[serde(default)]
     pub sync_contacts_automatically: bool,
+
+    /// Region (ISO 3166 alpha-2, e.g. "GB") used to normalize phone numbers
+    /// written without a country code. Empty uses the system locale.
+    #[serde(default)]
+    pub default_phone_region: String,
 }
 
 /// Notification configuration.
@@ -275,6 +329,22 @@ pub struct ConversationConfig {
     #[serde(default)]
     pub move_to_trash: bool,
 
+    /// Days deleted chats and messages stay in the trash before they are
+    /// purged, when `move_to_trash` is on.
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+
+    /// What the local scheduler does with schedules that came due while the
+    /// client was not running: "send" them all, "skip" them all, or send
+    /// only those at most `scheduled_catch_up_minutes` late ("recent").
+    #[serde(default = "default_scheduled_catch_up")]
+    pub scheduled_catch_up: String,
+
+    /// How late a missed local schedule may be and still be sent, under
+    /// the "recent" catch-up policy.
+    #[serde(default = "default_scheduled_catch_up_minutes")]
+    pub scheduled_catch_up_minutes: u32,
+
     /// Swipe to close conversation view.
     #[serde(default)]
     pub swipe_to_close: bool,
@@ -318,6 +388,22 @@ fn default_pool_size() -> u32 {
     4
 }
 
+fn default_snapshot_interval() -> u64 {
+    60
+}
+
+fn default_keep_hourly() -> u32 {
+    24
+}
+
+fn default_keep_daily() -> u32 {
+    7
+}
+
+fn default_keep_weekly() -> u32 {
+    4
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
@@ -334,6 +420,18 @@ fn default_messages_per_page() -> u32 {
     25
 }
 
+fn default_trash_retention_days() -> u32 {
+    30
+}
+
+fn default_scheduled_catch_up() -> String {
+    "recent".to_string()
+}
+
+fn default_scheduled_catch_up_minutes() -> u32 {
+    60
+}
+
 fn default_user_name() -> String {
     "You".to_string()
 }
@@ -385,6 +483,22 @@ impl Default for DatabaseConfig {
             wal_mode: true,
             pool_size: default_pool_size(),
             integrity_check_on_startup: true,
+            encrypted: false,
+            key_file: String::new(),
+            snapshots: SnapshotConfig::default(),
+        }
+    }
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: default_snapshot_interval(),
+            directory: String::new(),
+            keep_hourly: default_keep_hourly(),
+            keep_daily: default_keep_daily(),
+            keep_weekly: default_keep_weekly(),
         }
     }
 }
@@ -410,6 +524,7 @@ impl Default for SyncConfig {
             messages_per_page: default_messages_per_page(),
             skip_empty_chats: true,
             sync_contacts_automatically: false,
+            default_phone_region: String::new(),
         }
     }
 }
@@ -472,6 +587,9 @@ impl Default for ConversationConfig {
             swipable_conversation_tiles: true,
             smart_reply: false,
             move_to_trash: false,
+            trash_retention_days: default_trash_retention_days(),
+            scheduled_catch_up: default_scheduled_catch_up(),
+            scheduled_catch_up_minutes: default_scheduled_catch_up_minutes(),
             swipe_to_close: false,
             double_tap_for_details: false,
             auto_play_gifs: true,
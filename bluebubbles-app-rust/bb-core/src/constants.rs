@@ -30,8 +30,14 @@ pub const DEFAULT_MESSAGES_PER_PAGE: u32 = 25;
 /// Default chat page size for sync.
 pub const DEFAULT_CHAT_PAGE_SIZE: u32 = 200;
 
+/// Seconds between the Unix epoch and Apple's reference date (2001-01-01 UTC).
+pub const APPLE_EPOCH_OFFSET_SECS: i64 = 978_307_200;
+
+/// Seconds between scheduled retention policy runs.
+pub const RETENTION_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
 /// Database schema version.
-pub const DB_SCHEMA_VERSION: i32 = 1;
+pub const DB_SCHEMA_VERSION: i32 = 8;
 
 /// Reaction type string constants matching iMessage values.
 pub mod reactions {
@@ -126,6 +126,10 @@ pub enum BbError {
     Notification(String),
 
     // -- Generic --
+    /// A user-supplied value was invalid (bad argument, unparseable date, etc).
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
     /// An unexpected internal error.
     #[error("internal error: {0}")]
     Internal(String),
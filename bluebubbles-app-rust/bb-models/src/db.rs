@@ -2,7 +2,14 @@
 //!
 //! Uses SQLite in WAL mode with r2d2 connection pooling.
 //! Runs integrity checks on startup and applies versioned migrations.
+//! When `DatabaseConfig::encrypted` is set, every pooled connection is
+//! unlocked with the configured SQLCipher key before use.
+//!
+//! Every open database also holds a shared lock on a `-lock` file next to
+//! it, so a snapshot restore or an in-place re-encryption can tell whether
+//! any process still uses it.
 
+use std::fs::{File, TryLockError};
 use std::path::Path;
 use std::sync::Arc;
 
@@ -16,6 +23,7 @@ use bb_core::config::DatabaseConfig;
 
 use crate::schema;
 use crate::migrations;
+use crate::encryption::{self, DatabaseKey};
 
 /// Type alias for the SQLite connection pool.
 pub type DbPool = Pool<SqliteConnectionManager>;
@@ -24,6 +32,8 @@ pub type DbPool = Pool<SqliteConnectionManager>;
 #[derive(Clone)]
 pub struct Database {
     pool: Arc<DbPool>,
+    /// Shared lock on the database's lock file, held while any clone lives.
+    _lock: Arc<File>,
 }
 
 impl Database {
@@ -31,11 +41,12 @@ impl Database {
     ///
     /// This:
     /// 1. Creates the database file and parent directories if needed
-    /// 2. Enables WAL mode for concurrent read/write
-    /// 3. Sets up connection pooling
-    /// 4. Runs integrity checks if configured
-    /// 5. Creates the schema tables
-    /// 6. Runs pending migrations
+    /// 2. Resolves the SQLCipher key if encryption is enabled
+    /// 3. Enables WAL mode for concurrent read/write
+    /// 4. Sets up connection pooling
+    /// 5. Runs integrity checks if configured
+    /// 6. Creates the schema tables
+    /// 7. Runs pending migrations
     pub fn init(db_path: &Path, config: &DatabaseConfig) -> BbResult<Self> {
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent)?;
@@ -43,17 +54,40 @@ impl Database {
 
         info!("initializing database at {}", db_path.display());
 
+        let lock = open_lock_file(db_path)?;
+        match lock.try_lock_shared() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(BbError::Database(
+                    "database is being restored or re-encrypted by another process".into(),
+                ));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        let key = DatabaseKey::from_config(config)?;
+        if key.is_some() {
+            if !encryption::is_sqlcipher_available() {
+                return Err(BbError::Config(
+                    "database.encrypted is set but this build lacks the `sqlcipher` feature".into(),
+                ));
+            }
+            info!("opening encrypted database");
+        }
+
         let manager = SqliteConnectionManager::file(db_path);
         let pool = Pool::builder()
             .max_size(config.pool_size)
             .connection_customizer(Box::new(ConnectionCustomizer {
                 wal_mode: config.wal_mode,
+                key,
             }))
             .build(manager)
             .map_err(|e| BbError::Pool(e.to_string()))?;
 
         let db = Self {
             pool: Arc::new(pool),
+            _lock: Arc::new(lock),
         };
 
         // Run integrity check if configured
@@ -149,6 +183,30 @@ impl Database {
     }
 }
 
+/// Open (creating if needed) the `-lock` file for the database at `db_path`.
+pub(crate) fn open_lock_file(db_path: &Path) -> BbResult<File> {
+    Ok(std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(encryption::sidecar_path(db_path, "-lock"))?)
+}
+
+/// Take the database's lock file exclusively. Every open [`Database`] holds
+/// it shared, so this fails while the app or another command is running.
+/// Maintenance that replaces the database file holds it until done.
+pub(crate) fn lock_exclusive(db_path: &Path) -> BbResult<File> {
+    let lock = open_lock_file(db_path)?;
+    match lock.try_lock() {
+        Ok(()) => Ok(lock),
+        Err(TryLockError::WouldBlock) => Err(BbError::Database(
+            "database is in use by another process; close the app and retry".into(),
+        )),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
 /// Database row count statistics.
 #[derive(Debug, Clone)]
 pub struct DatabaseStats {
@@ -176,10 +234,16 @@ impl std::fmt::Display for DatabaseStats {
 #[derive(Debug)]
 struct ConnectionCustomizer {
     wal_mode: bool,
+    key: Option<DatabaseKey>,
 }
 
 impl r2d2::CustomizeConnection<Connection, rusqlite::Error> for ConnectionCustomizer {
     fn on_acquire(&self, conn: &mut Connection) -> Result<(), rusqlite::Error> {
+        // The key must be applied before any other statement touches the file
+        if let Some(ref key) = self.key {
+            encryption::apply_key(conn, key)?;
+        }
+
         // Enable WAL mode for better concurrent performance
         if self.wal_mode {
             conn.execute_batch("PRAGMA journal_mode=WAL;")?;
@@ -225,6 +289,17 @@ mod tests {
         assert!(db.run_integrity_check().is_ok());
     }
 
+    #[test]
+    fn test_encrypted_without_key_fails() {
+        let dir = TempDir::new().unwrap();
+        let config = DatabaseConfig {
+            encrypted: true,
+            key_file: dir.path().join("missing.key").display().to_string(),
+            ..DatabaseConfig::default()
+        };
+        assert!(Database::init(&dir.path().join("test.db"), &config).is_err());
+    }
+
     #[test]
     fn test_transaction() {
         let (db, _dir) = test_db();
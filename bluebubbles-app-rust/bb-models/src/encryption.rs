@@ -0,0 +1,325 @@
+//! Encrypted-at-rest database support via SQLCipher.
+//!
+//! Encryption is opt-in: the crate must be built with the `sqlcipher` feature
+//! and `DatabaseConfig::encrypted` must be set. Existing databases are
+//! converted in place with `sqlcipher_export`, which copies every table into
+//! a freshly attached database with a different key (or no key at all).
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+use tracing::{info, warn};
+
+use bb_core::config::DatabaseConfig;
+use bb_core::error::{BbError, BbResult};
+
+/// Environment variable consulted for the passphrase when no key file is configured.
+pub const PASSPHRASE_ENV_VAR: &str = "BLUEBUBBLES_DB_PASSPHRASE";
+
+/// Key material used to unlock an encrypted database.
+#[derive(Clone, PartialEq, Eq)]
+pub enum DatabaseKey {
+    /// A passphrase, run through SQLCipher's key derivation.
+    Passphrase(String),
+    /// A raw 256-bit key given as 64 hex characters (no key derivation).
+    RawHex(String),
+}
+
+impl std::fmt::Debug for DatabaseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print key material into logs
+        match self {
+            Self::Passphrase(_) => write!(f, "DatabaseKey::Passphrase(***)"),
+            Self::RawHex(_) => write!(f, "DatabaseKey::RawHex(***)"),
+        }
+    }
+}
+
+impl DatabaseKey {
+    /// Build a key from a user-supplied passphrase.
+    pub fn passphrase(value: &str) -> BbResult<Self> {
+        if value.is_empty() {
+            return Err(BbError::Config("database passphrase must not be empty".into()));
+        }
+        Ok(Self::Passphrase(value.to_string()))
+    }
+
+    /// Read a key from a key file.
+    ///
+    /// A file containing exactly 64 hex characters (after trimming) is treated
+    /// as a raw key; anything else is used as a passphrase.
+    pub fn from_key_file(path: &Path) -> BbResult<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            BbError::Config(format!("failed to read key file {}: {e}", path.display()))
+        })?;
+        let trimmed = contents.trim();
+        if trimmed.len() == 64 && trimmed.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self::RawHex(trimmed.to_ascii_lowercase()))
+        } else {
+            Self::passphrase(trimmed)
+        }
+    }
+
+    /// Resolve the key for an encrypted database from configuration.
+    ///
+    /// Returns `None` when encryption is disabled.
+    pub fn from_config(config: &DatabaseConfig) -> BbResult<Option<Self>> {
+        if !config.encrypted {
+            return Ok(None);
+        }
+        if !config.key_file.is_empty() {
+            return Self::from_key_file(&PathBuf::from(&config.key_file)).map(Some);
+        }
+        match std::env::var(PASSPHRASE_ENV_VAR) {
+            Ok(value) if !value.is_empty() => Self::passphrase(&value).map(Some),
+            _ => Err(BbError::MissingConfig(format!(
+                "database is encrypted but neither database.key_file nor {PASSPHRASE_ENV_VAR} is set"
+            ))),
+        }
+    }
+
+    /// The key as SQLCipher expects it in `PRAGMA key` or `ATTACH ... KEY`.
+    fn sql_value(&self) -> String {
+        match self {
+            Self::Passphrase(p) => p.clone(),
+            Self::RawHex(hex) => format!("x'{hex}'"),
+        }
+    }
+
+    /// The key as a quoted SQL string literal.
+    fn sql_literal(&self) -> String {
+        quote_literal(&self.sql_value())
+    }
+}
+
+/// Quote a string as a SQL literal, escaping embedded single quotes.
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Check whether the linked SQLite library is SQLCipher.
+pub fn is_sqlcipher_available() -> bool {
+    Connection::open_in_memory()
+        .and_then(|conn| conn.query_row("PRAGMA cipher_version", [], |row| row.get::<_, String>(0)))
+        .is_ok()
+}
+
+fn require_sqlcipher() -> BbResult<()> {
+    if is_sqlcipher_available() {
+        Ok(())
+    } else {
+        Err(BbError::Config(
+            "database encryption requires a build with the `sqlcipher` feature".into(),
+        ))
+    }
+}
+
+/// Apply a key to a freshly opened connection. Must be the first statement
+/// executed on the connection.
+pub fn apply_key(conn: &Connection, key: &DatabaseKey) -> rusqlite::Result<()> {
+    conn.execute_batch(&format!("PRAGMA key = {};", key.sql_literal()))
+}
+
+/// Open a connection to the database at `path`, unlocking it with `key` if given,
+/// and verify the key by reading the schema.
+fn open_verified(path: &Path, key: Option<&DatabaseKey>) -> BbResult<Connection> {
+    let conn = Connection::open(path).map_err(|e| BbError::Database(e.to_string()))?;
+    if let Some(key) = key {
+        apply_key(&conn, key).map_err(|e| BbError::Database(e.to_string()))?;
+    }
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .map_err(|e| {
+            BbError::Database(format!(
+                "cannot read {} (wrong key or not an encrypted database?): {e}",
+                path.display()
+            ))
+        })?;
+    Ok(conn)
+}
+
+/// Copy the database at `path` into a new file keyed with `new_key` (or
+/// unencrypted when `None`) and atomically swap it into place.
+///
+/// Holds the database's lock file exclusively throughout, so this fails
+/// while the app or another command has the database open.
+fn export_in_place(
+    path: &Path,
+    current_key: Option<&DatabaseKey>,
+    new_key: Option<&DatabaseKey>,
+) -> BbResult<()> {
+    require_sqlcipher()?;
+
+    if !path.exists() {
+        return Err(BbError::Database(format!("database not found: {}", path.display())));
+    }
+
+    let _lock = crate::db::lock_exclusive(path)?;
+
+    let tmp_path = sibling_path(path, "export-tmp");
+    if tmp_path.exists() {
+        std::fs::remove_file(&tmp_path)?;
+    }
+
+    {
+        let conn = open_verified(path, current_key)?;
+
+        // Fold the WAL into the main file so the export sees every committed page
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let user_version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let target_key = new_key.map(|k| k.sql_literal()).unwrap_or_else(|| "''".to_string());
+        conn.execute_batch(&format!(
+            "ATTACH DATABASE {} AS export_target KEY {target_key};
+             SELECT sqlcipher_export('export_target');
+             PRAGMA export_target.user_version = {user_version};
+             DETACH DATABASE export_target;",
+            quote_literal(&tmp_path.to_string_lossy()),
+        ))
+        .map_err(|e| BbError::Database(format!("sqlcipher_export failed: {e}")))?;
+    }
+
+    // Make sure the exported copy opens with the new key before replacing anything
+    if let Err(e) = open_verified(&tmp_path, new_key) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    for suffix in ["-wal", "-shm"] {
+        let side = sidecar_path(path, suffix);
+        if side.exists() {
+            std::fs::remove_file(&side)?;
+        }
+    }
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Encrypt an existing plaintext database in place.
+pub fn encrypt_database(path: &Path, key: &DatabaseKey) -> BbResult<()> {
+    info!("encrypting database at {}", path.display());
+    export_in_place(path, None, Some(key))?;
+    info!("database encrypted");
+    Ok(())
+}
+
+/// Decrypt an encrypted database in place, leaving a plaintext file.
+pub fn decrypt_database(path: &Path, key: &DatabaseKey) -> BbResult<()> {
+    warn!("decrypting database at {} - contents will be stored in plaintext", path.display());
+    export_in_place(path, Some(key), None)?;
+    info!("database decrypted");
+    Ok(())
+}
+
+/// Re-encrypt an encrypted database in place with a new key.
+pub fn rekey_database(path: &Path, old_key: &DatabaseKey, new_key: &DatabaseKey) -> BbResult<()> {
+    info!("rekeying database at {}", path.display());
+    export_in_place(path, Some(old_key), Some(new_key))?;
+    info!("database rekeyed");
+    Ok(())
+}
+
+/// `bluebubbles.db` -> `bluebubbles.db-<suffix>` in the same directory.
+pub(crate) fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    sidecar_path(path, &format!("-{suffix}"))
+}
+
+/// Append a raw suffix to the full file name (e.g. `-wal`).
+pub(crate) fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_key_file_raw_hex() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("db.key");
+        std::fs::write(&path, format!("{}\n", "AB".repeat(32))).unwrap();
+        let key = DatabaseKey::from_key_file(&path).unwrap();
+        assert_eq!(key, DatabaseKey::RawHex("ab".repeat(32)));
+        assert_eq!(key.sql_literal(), format!("'x''{}'''", "ab".repeat(32)));
+    }
+
+    #[test]
+    fn test_key_file_passphrase() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("db.key");
+        std::fs::write(&path, "correct horse battery staple\n").unwrap();
+        let key = DatabaseKey::from_key_file(&path).unwrap();
+        assert_eq!(key, DatabaseKey::Passphrase("correct horse battery staple".into()));
+    }
+
+    #[test]
+    fn test_passphrase_quoting() {
+        let key = DatabaseKey::passphrase("it's").unwrap();
+        assert_eq!(key.sql_literal(), "'it''s'");
+        assert!(DatabaseKey::passphrase("").is_err());
+    }
+
+    #[test]
+    fn test_debug_redacts_key() {
+        let key = DatabaseKey::passphrase("secret").unwrap();
+        assert!(!format!("{key:?}").contains("secret"));
+    }
+
+    #[test]
+    fn test_from_config_disabled() {
+        let config = DatabaseConfig::default();
+        assert!(DatabaseKey::from_config(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sidecar_paths() {
+        let path = Path::new("/data/bluebubbles.db");
+        assert_eq!(sidecar_path(path, "-wal"), PathBuf::from("/data/bluebubbles.db-wal"));
+        assert_eq!(sibling_path(path, "export-tmp"), PathBuf::from("/data/bluebubbles.db-export-tmp"));
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_encrypt_rekey_decrypt_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch("CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('hello');").unwrap();
+        }
+
+        let key = DatabaseKey::passphrase("first").unwrap();
+        encrypt_database(&path, &key).unwrap();
+        assert!(open_verified(&path, None).is_err());
+        assert!(open_verified(&path, Some(&key)).is_ok());
+
+        let new_key = DatabaseKey::passphrase("second").unwrap();
+        rekey_database(&path, &key, &new_key).unwrap();
+        assert!(open_verified(&path, Some(&key)).is_err());
+
+        decrypt_database(&path, &new_key).unwrap();
+        let conn = open_verified(&path, None).unwrap();
+        let v: String = conn.query_row("SELECT v FROM t", [], |row| row.get(0)).unwrap();
+        assert_eq!(v, "hello");
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn test_encrypt_refuses_database_in_use() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        let db = crate::db::Database::init(&path, &DatabaseConfig::default()).unwrap();
+
+        let key = DatabaseKey::passphrase("first").unwrap();
+        let err = encrypt_database(&path, &key).unwrap_err();
+        assert!(err.to_string().contains("in use"));
+        drop(db);
+        encrypt_database(&path, &key).unwrap();
+    }
+}
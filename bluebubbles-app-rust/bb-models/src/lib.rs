@@ -9,16 +9,35 @@ pub mod schema;
 pub mod models;
 pub mod queries;
 pub mod migrations;
+pub mod encryption;
+pub mod snapshot;
+pub mod vcard;
+pub mod phone;
 
 // Re-export key types
 pub use db::{Database, DbPool};
+pub use encryption::DatabaseKey;
+pub use snapshot::{SnapshotInfo, SnapshotStore};
+pub use vcard::VCardVersion;
 pub use models::chat::Chat;
+pub use models::chat_folder::{ChatFolder, FolderRule};
+pub use models::link_preview::CachedLinkPreview;
+pub use models::notification_digest::{DigestEntry, DigestSettings};
+pub use models::notification_rule::{NotificationRule, QuietHours, RuleAction, RuleCondition};
+pub use models::otp_settings::OtpSettings;
+pub use models::outbox::{ChatMutation, OutboxEntry};
 pub use models::message::Message;
+pub use models::message_summary_info::MessageSummaryInfo;
+pub use models::message_tombstone::MessageTombstone;
+pub use models::bookmark::{Bookmark, BookmarkFilter};
+pub use models::retention_policy::RetentionPolicy;
 pub use models::handle::Handle;
+pub use models::person::Person;
 pub use models::attachment::Attachment;
 pub use models::contact::Contact;
 pub use models::fcm_data::FcmData;
 pub use models::theme::ThemeStruct;
 pub use models::scheduled_message::ScheduledMessage;
+pub use models::recurrence::Recurrence;
 pub use models::settings::Settings;
 pub use models::findmy::{FindMyLocationItem, FindMyDevice, FindMyLocation, FindMyAddress};
@@ -62,6 +62,13 @@ fn run_migration(conn: &Connection, version: i32) -> BbResult<()> {
 
     match version {
         1 => migration_v1(conn),
+        2 => migration_v2(conn),
+        3 => migration_v3(conn),
+        4 => migration_v4(conn),
+        5 => migration_v5(conn),
+        6 => migration_v6(conn),
+        7 => migration_v7(conn),
+        8 => migration_v8(conn),
         _ => {
             warn!("unknown migration version {version}, skipping");
             Ok(())
@@ -96,6 +103,133 @@ fn migration_v1(conn: &Connection) -> BbResult<()> {
     Ok(())
 }
 
+/// Migration v2: E.164 phone normalization.
+///
+/// Adds `handles.normalized_address` on databases created before the column
+/// existed, indexes it, and backfills handles and the `contact_phones` index.
+fn migration_v2(conn: &Connection) -> BbResult<()> {
+    let has_column = conn
+        .prepare("SELECT 1 FROM pragma_table_info('handles') WHERE name = 'normalized_address'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    if !has_column {
+        conn.execute("ALTER TABLE handles ADD COLUMN normalized_address TEXT", [])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_handles_normalized ON handles(normalized_address)",
+        [],
+    )
+    .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let (handles, contacts) = crate::queries::renormalize_phones(conn)?;
+    info!("normalized {handles} handle and {contacts} contact phone numbers");
+    Ok(())
+}
+
+/// Migration v3: bookmark annotations.
+///
+/// The `bookmarks` table is created by the schema; this backfills a row for
+/// every message already flagged `is_bookmarked`.
+fn migration_v3(conn: &Connection) -> BbResult<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let count = conn
+        .execute(
+            "INSERT OR IGNORE INTO bookmarks (message_guid, date_created, date_modified)
+             SELECT guid, ?1, ?1 FROM messages WHERE is_bookmarked = 1 AND guid IS NOT NULL",
+            [&now],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    info!("backfilled {count} bookmarks");
+    Ok(())
+}
+
+/// Migration v4: recurring schedules.
+///
+/// Adds `scheduled_messages.recurrence`, `time_zone` and `recurrence_start`
+/// on databases created before the columns existed.
+fn migration_v4(conn: &Connection) -> BbResult<()> {
+    for column in ["recurrence", "time_zone", "recurrence_start"] {
+        let has_column = conn
+            .prepare("SELECT 1 FROM pragma_table_info('scheduled_messages') WHERE name = ?1")
+            .and_then(|mut stmt| stmt.exists([column]))
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        if !has_column {
+            conn.execute(&format!("ALTER TABLE scheduled_messages ADD COLUMN {column} TEXT"), [])
+                .map_err(|e| BbError::Database(e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Migration v5: first send attempt of queued messages.
+///
+/// Adds `send_queue.first_attempt` on databases created before the column
+/// existed. Rows already queued keep NULL and fall back to `date_created`.
+fn migration_v5(conn: &Connection) -> BbResult<()> {
+    let has_column = conn
+        .prepare("SELECT 1 FROM pragma_table_info('send_queue') WHERE name = 'first_attempt'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    if !has_column {
+        conn.execute("ALTER TABLE send_queue ADD COLUMN first_attempt INTEGER", [])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Migration v6: send claims on queued messages.
+///
+/// Adds `send_queue.claimed_by` and `claimed_at` on databases created before
+/// the columns existed. Rows already queued start unclaimed.
+fn migration_v6(conn: &Connection) -> BbResult<()> {
+    for (column, kind) in [("claimed_by", "TEXT"), ("claimed_at", "INTEGER")] {
+        let has_column = conn
+            .prepare("SELECT 1 FROM pragma_table_info('send_queue') WHERE name = ?1")
+            .and_then(|mut stmt| stmt.exists([column]))
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        if !has_column {
+            conn.execute(&format!("ALTER TABLE send_queue ADD COLUMN {column} {kind}"), [])
+                .map_err(|e| BbError::Database(e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Migration v7: claim times on scheduled messages.
+///
+/// Adds `scheduled_messages.claimed_at` on databases created before the
+/// column existed. Schedules already `sending` have none and count as
+/// abandoned.
+fn migration_v7(conn: &Connection) -> BbResult<()> {
+    let has_column = conn
+        .prepare("SELECT 1 FROM pragma_table_info('scheduled_messages') WHERE name = 'claimed_at'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    if !has_column {
+        conn.execute("ALTER TABLE scheduled_messages ADD COLUMN claimed_at INTEGER", [])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Migration v8: server sync times on bookmarks.
+///
+/// Adds `bookmarks.date_synced` on databases created before the column
+/// existed. Existing bookmarks have none and are kept until the server next
+/// reports them bookmarked.
+fn migration_v8(conn: &Connection) -> BbResult<()> {
+    let has_column = conn
+        .prepare("SELECT 1 FROM pragma_table_info('bookmarks') WHERE name = 'date_synced'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    if !has_column {
+        conn.execute("ALTER TABLE bookmarks ADD COLUMN date_synced TEXT", [])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+    }
+    Ok(())
+}
+
 const DEFAULT_DARK_THEME: &str = r#"{"colorScheme":{"brightness":0,"primary":4278221567,"onPrimary":4294967295,"background":4278190080,"onBackground":4294967295,"surface":4278190080,"onSurface":4294967295},"textTheme":{"font":"Default"}}"#;
 
 const DEFAULT_LIGHT_THEME: &str = r#"{"colorScheme":{"brightness":1,"primary":4278221567,"onPrimary":4294967295,"background":4294967295,"onBackground":4278190080,"surface":4294967295,"onSurface":4278190080},"textTheme":{"font":"Default"}}"#;
@@ -134,4 +268,157 @@ mod tests {
             .unwrap();
         assert_eq!(count, 2);
     }
+
+    #[test]
+    fn test_migration_v2_upgrades_old_handles_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        // A version-1 database whose handles table predates normalized_address
+        conn.execute_batch(
+            "CREATE TABLE handles (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                original_rowid INTEGER,
+                address TEXT NOT NULL,
+                service TEXT NOT NULL DEFAULT 'iMessage',
+                unique_address_service TEXT NOT NULL UNIQUE,
+                formatted_address TEXT,
+                country TEXT,
+                color TEXT,
+                default_phone TEXT,
+                default_email TEXT,
+                contact_id INTEGER
+            );
+            INSERT INTO handles (address, unique_address_service, country)
+                VALUES ('07700 900123', '07700 900123/SMS', 'gb');",
+        )
+        .unwrap();
+        schema::create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO schema_version (version) VALUES (1)", []).unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        let normalized: Option<String> = conn
+            .query_row("SELECT normalized_address FROM handles", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(normalized.as_deref(), Some("+447700900123"));
+        assert_eq!(get_schema_version(&conn).unwrap(), DB_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_migration_v3_backfills_bookmarks() {
+        let conn = Connection::open_in_memory().unwrap();
+        schema::create_tables(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO schema_version (version) VALUES (2);
+             INSERT INTO messages (guid, is_bookmarked) VALUES ('m1', 1), ('m2', 0);",
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        let guids: Vec<String> = conn
+            .prepare("SELECT message_guid FROM bookmarks")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(guids, vec!["m1".to_string()]);
+    }
+
+    #[test]
+    fn test_migration_v8_adds_bookmark_sync_time() {
+        let conn = Connection::open_in_memory().unwrap();
+        // A version-7 database whose bookmarks table predates date_synced
+        conn.execute_batch(
+            "CREATE TABLE bookmarks (
+                message_guid TEXT PRIMARY KEY,
+                note TEXT,
+                date_created TEXT NOT NULL,
+                date_modified TEXT NOT NULL
+            );
+            INSERT INTO bookmarks (message_guid, date_created, date_modified)
+                VALUES ('m1', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');",
+        )
+        .unwrap();
+        schema::create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO schema_version (version) VALUES (7)", []).unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        let synced: Option<String> = conn
+            .query_row("SELECT date_synced FROM bookmarks WHERE message_guid = 'm1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(synced, None);
+        assert_eq!(get_schema_version(&conn).unwrap(), DB_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_migrations_v4_v7_add_scheduled_message_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        // A version-3 database whose scheduled_messages table predates recurrence
+        conn.execute_batch(
+            "CREATE TABLE scheduled_messages (
+                id INTEGER PRIMARY KEY,
+                type TEXT NOT NULL,
+                chat_guid TEXT NOT NULL,
+                message TEXT NOT NULL,
+                send_method TEXT NOT NULL DEFAULT 'private-api',
+                scheduled_for TEXT NOT NULL,
+                schedule_type TEXT,
+                schedule_interval INTEGER,
+                schedule_interval_type TEXT,
+                status TEXT NOT NULL DEFAULT 'pending',
+                error TEXT,
+                sent_at TEXT,
+                created_at TEXT NOT NULL
+            );",
+        )
+        .unwrap();
+        schema::create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO schema_version (version) VALUES (3)", []).unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO scheduled_messages (type, chat_guid, message, scheduled_for, created_at, recurrence, time_zone, recurrence_start, claimed_at)
+             VALUES ('send-message', 'c', 'hi', 'now', 'now', '0 9 * * *', 'UTC', 'now', 0)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(get_schema_version(&conn).unwrap(), DB_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_migrations_v5_v6_add_send_queue_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        // A version-4 database whose send_queue predates first_attempt and claims
+        conn.execute_batch(
+            "CREATE TABLE send_queue (
+                id TEXT PRIMARY KEY,
+                chat_guid TEXT NOT NULL,
+                text TEXT,
+                file_path TEXT,
+                mime_type TEXT,
+                temp_guid TEXT,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                max_attempts INTEGER NOT NULL DEFAULT 5,
+                next_attempt INTEGER NOT NULL DEFAULT 0,
+                waiting INTEGER NOT NULL DEFAULT 0,
+                date_created INTEGER NOT NULL
+            );",
+        )
+        .unwrap();
+        schema::create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO schema_version (version) VALUES (4)", []).unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO send_queue (id, chat_guid, date_created, first_attempt, claimed_by, claimed_at)
+             VALUES ('q', 'c', 1, 2, 'app', 3)",
+            [],
+        )
+        .unwrap();
+        assert_eq!(get_schema_version(&conn).unwrap(), DB_SCHEMA_VERSION);
+    }
 }
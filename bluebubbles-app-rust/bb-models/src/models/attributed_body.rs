@@ -91,6 +91,34 @@ impl AttributedBody {
         Some(AttributedBody { runs })
     }
 
+    /// Extract the plain text from an Apple `typedstream`-archived
+    /// NSAttributedString, as stored in the `attributedBody` column of
+    /// `chat.db`. Newer macOS versions leave `message.text` empty and only
+    /// populate this blob.
+    ///
+    /// Only the string payload is decoded; attributes are ignored.
+    pub fn text_from_typedstream(blob: &[u8]) -> Option<String> {
+        const MARKER: &[u8] = b"NSString";
+        let start = blob.windows(MARKER.len()).position(|w| w == MARKER)? + MARKER.len();
+
+        // The string follows a '+' type tag a few bytes after the class name
+        let rest = &blob[start..];
+        let tag = rest.iter().take(16).position(|&b| b == b'+')?;
+        let rest = &rest[tag + 1..];
+
+        // Length prefix: one byte, or 0x81 + u16 LE, or 0x82 + u32 LE
+        let (len, offset) = match *rest.first()? {
+            0x81 => (u16::from_le_bytes([*rest.get(1)?, *rest.get(2)?]) as usize, 3),
+            0x82 => (
+                u32::from_le_bytes([*rest.get(1)?, *rest.get(2)?, *rest.get(3)?, *rest.get(4)?]) as usize,
+                5,
+            ),
+            b => (b as usize, 1),
+        };
+        let bytes = rest.get(offset..offset + len)?;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
     /// Get the plain text content by joining all runs.
     pub fn plain_text(&self) -> String {
         self.runs.iter().map(|r| r.text.as_str()).collect()
@@ -133,6 +161,24 @@ mod tests {
         assert_eq!(body.mentions().len(), 1);
     }
 
+    #[test]
+    fn test_text_from_typedstream() {
+        let mut blob = b"\x04\x0bstreamtyped\x81\xe8\x03\x84\x01@\x84\x84\x84\x12NSAttributedString\x00\x84\x84\x08NSObject\x00\x85\x92\x84\x84\x84\x08NSString\x01\x94\x84\x01+".to_vec();
+        blob.push(12);
+        blob.extend_from_slice("héllo world".as_bytes());
+        blob.extend_from_slice(b"\x86\x84");
+        assert_eq!(AttributedBody::text_from_typedstream(&blob).as_deref(), Some("héllo world"));
+
+        // Two-byte length prefix
+        let long = "x".repeat(300);
+        let mut blob = b"\x84\x08NSString\x01\x94\x84\x01+\x81".to_vec();
+        blob.extend_from_slice(&300u16.to_le_bytes());
+        blob.extend_from_slice(long.as_bytes());
+        assert_eq!(AttributedBody::text_from_typedstream(&blob), Some(long));
+
+        assert!(AttributedBody::text_from_typedstream(b"garbage").is_none());
+    }
+
     #[test]
     fn test_empty_attributed_body() {
         let json = serde_json::json!(null);
@@ -0,0 +1,332 @@
+//! Bookmark entity model: tags and a note attached to a message.
+
+use serde::{Deserialize, Serialize};
+use rusqlite::{params, Connection, Row};
+use bb_core::error::{BbError, BbResult};
+
+/// A bookmarked message with its tags and note.
+///
+/// The row's presence is what makes a message bookmarked; saving and
+/// deleting keep `messages.is_bookmarked` in step with it. `date_synced`
+/// records when the server last reported the message bookmarked.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Bookmark {
+    pub message_guid: String,
+    pub note: Option<String>,
+    /// Normalized tags (trimmed, lowercase), sorted.
+    pub tags: Vec<String>,
+    pub date_created: String,
+    pub date_modified: String,
+}
+
+/// Filters for listing bookmarks. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct BookmarkFilter {
+    /// Only bookmarks carrying this tag.
+    pub tag: Option<String>,
+    /// Only bookmarks on messages in this chat.
+    pub chat_id: Option<i64>,
+    /// Only bookmarks whose note, tags or message text contain this text.
+    pub query: Option<String>,
+    /// Maximum number of bookmarks (0 for no limit).
+    pub limit: i64,
+}
+
+impl Bookmark {
+    /// Create an unsaved bookmark for a message.
+    pub fn new(message_guid: &str) -> Self {
+        let now = chrono::Utc::now().to_rfc3339();
+        Self {
+            message_guid: message_guid.to_string(),
+            note: None,
+            tags: Vec::new(),
+            date_created: now.clone(),
+            date_modified: now,
+        }
+    }
+
+    /// Construct a Bookmark from a database row. Tags are loaded separately.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            message_guid: row.get("message_guid")?,
+            note: row.get("note")?,
+            tags: Vec::new(),
+            date_created: row.get("date_created")?,
+            date_modified: row.get("date_modified")?,
+        })
+    }
+
+    /// Normalize a tag: trimmed, lowercased, a leading `#` dropped.
+    /// Returns None for empty tags.
+    pub fn normalize_tag(tag: &str) -> Option<String> {
+        let tag = tag.trim().trim_start_matches('#').trim().to_lowercase();
+        (!tag.is_empty()).then_some(tag)
+    }
+
+    /// Add tags, skipping duplicates and empty ones.
+    pub fn add_tags<S: AsRef<str>>(&mut self, tags: &[S]) {
+        self.tags.extend(tags.iter().filter_map(|t| Self::normalize_tag(t.as_ref())));
+        self.tags.sort();
+        self.tags.dedup();
+    }
+
+    /// Remove tags.
+    pub fn remove_tags<S: AsRef<str>>(&mut self, tags: &[S]) {
+        let remove: Vec<String> = tags.iter().filter_map(|t| Self::normalize_tag(t.as_ref())).collect();
+        self.tags.retain(|t| !remove.contains(t));
+    }
+
+    /// Insert or update this bookmark and replace its tags.
+    pub fn save(&mut self, conn: &Connection) -> BbResult<()> {
+        self.date_modified = chrono::Utc::now().to_rfc3339();
+        self.note = self.note.as_deref().map(str::trim).filter(|n| !n.is_empty()).map(String::from);
+
+        conn.execute(
+            "INSERT INTO bookmarks (message_guid, note, date_created, date_modified)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(message_guid) DO UPDATE SET
+                note = excluded.note,
+                date_modified = excluded.date_modified",
+            params![self.message_guid, self.note, self.date_created, self.date_modified],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+        conn.execute("DELETE FROM bookmark_tags WHERE message_guid = ?1", [&self.message_guid])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        for tag in &self.tags {
+            conn.execute(
+                "INSERT OR IGNORE INTO bookmark_tags (message_guid, tag) VALUES (?1, ?2)",
+                params![self.message_guid, tag],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        }
+
+        conn.execute("UPDATE messages SET is_bookmarked = 1 WHERE guid = ?1", [&self.message_guid])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Find the bookmark on a message.
+    pub fn find(conn: &Connection, message_guid: &str) -> BbResult<Option<Self>> {
+        let bookmark = match conn.query_row(
+            "SELECT * FROM bookmarks WHERE message_guid = ?1",
+            [message_guid],
+            Self::from_row,
+        ) {
+            Ok(b) => b,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(BbError::Database(e.to_string())),
+        };
+        Ok(Some(with_tags(conn, bookmark)?))
+    }
+
+    /// Remove the bookmark on a message. Returns whether one existed.
+    pub fn delete(conn: &Connection, message_guid: &str) -> BbResult<bool> {
+        conn.execute("DELETE FROM bookmark_tags WHERE message_guid = ?1", [message_guid])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        let deleted = conn
+            .execute("DELETE FROM bookmarks WHERE message_guid = ?1", [message_guid])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        conn.execute("UPDATE messages SET is_bookmarked = 0 WHERE guid = ?1", [message_guid])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(deleted > 0)
+    }
+
+    /// Apply the bookmark flag the server sent with a message.
+    ///
+    /// A bookmark made on another device gets a row here. One the server
+    /// stopped reporting is removed, unless it was changed here since the
+    /// server last reported it; bookmarks the server never reported stay.
+    pub fn apply_server_flag(conn: &Connection, message_guid: &str, bookmarked: bool) -> BbResult<()> {
+        if bookmarked {
+            let now = chrono::Utc::now().to_rfc3339();
+            conn.execute(
+                "INSERT INTO bookmarks (message_guid, date_created, date_modified, date_synced)
+                 VALUES (?1, ?2, ?2, ?2)
+                 ON CONFLICT(message_guid) DO UPDATE SET date_synced = excluded.date_synced",
+                params![message_guid, now],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+            return Ok(());
+        }
+
+        let removed_on_server = conn
+            .prepare(
+                "SELECT 1 FROM bookmarks
+                 WHERE message_guid = ?1 AND date_synced IS NOT NULL AND date_modified <= date_synced",
+            )
+            .and_then(|mut stmt| stmt.exists([message_guid]))
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        if removed_on_server {
+            Self::delete(conn, message_guid)?;
+        } else {
+            // Kept for its newer local change; only this device has it now
+            conn.execute("UPDATE bookmarks SET date_synced = NULL WHERE message_guid = ?1", [message_guid])
+                .map_err(|e| BbError::Database(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// List bookmarks on non-deleted messages, most recently modified first.
+    pub fn list(conn: &Connection, filter: &BookmarkFilter) -> BbResult<Vec<Self>> {
+        let tag = filter.tag.as_deref().and_then(Self::normalize_tag);
+        let query = filter
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .map(|q| format!("%{q}%"));
+        let limit = if filter.limit > 0 { filter.limit } else { -1 };
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT b.* FROM bookmarks b
+                 INNER JOIN messages m ON m.guid = b.message_guid
+                 WHERE m.date_deleted IS NULL
+                   AND (?1 IS NULL OR EXISTS (
+                        SELECT 1 FROM bookmark_tags t WHERE t.message_guid = b.message_guid AND t.tag = ?1))
+                   AND (?2 IS NULL OR m.chat_id = ?2)
+                   AND (?3 IS NULL OR b.note LIKE ?3 OR m.text LIKE ?3 OR EXISTS (
+                        SELECT 1 FROM bookmark_tags t WHERE t.message_guid = b.message_guid AND t.tag LIKE ?3))
+                 ORDER BY b.date_modified DESC
+                 LIMIT ?4",
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let bookmarks: Vec<Self> = stmt
+            .query_map(params![tag, filter.chat_id, query, limit], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        bookmarks.into_iter().map(|b| with_tags(conn, b)).collect()
+    }
+
+    /// Every tag in use, with the number of bookmarks carrying it.
+    pub fn tag_counts(conn: &Connection) -> BbResult<Vec<(String, i64)>> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT t.tag, COUNT(*) FROM bookmark_tags t
+                 INNER JOIN messages m ON m.guid = t.message_guid
+                 WHERE m.date_deleted IS NULL
+                 GROUP BY t.tag ORDER BY t.tag",
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let tags = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(tags)
+    }
+}
+
+fn with_tags(conn: &Connection, mut bookmark: Bookmark) -> BbResult<Bookmark> {
+    let mut stmt = conn
+        .prepare("SELECT tag FROM bookmark_tags WHERE message_guid = ?1 ORDER BY tag")
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    bookmark.tags = stmt
+        .query_map([&bookmark.message_guid], |row| row.get(0))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(bookmark)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    #[test]
+    fn test_bookmark_tags_and_filters() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO chats (id, guid) VALUES (1, 'chat-1'), (2, 'chat-2');
+             INSERT INTO messages (guid, chat_id, text) VALUES
+                ('m1', 1, 'can you send the invoice?'),
+                ('m2', 2, 'dinner friday'),
+                ('m3', 2, 'old news');",
+        )
+        .unwrap();
+
+        let mut b = Bookmark::new("m1");
+        b.add_tags(&["#Todo", " work ", "todo", ""]);
+        b.note = Some("  reply by monday ".into());
+        b.save(&conn).unwrap();
+        assert_eq!(b.tags, vec!["todo", "work"]);
+
+        let mut b2 = Bookmark::new("m2");
+        b2.add_tags(&["todo"]);
+        b2.save(&conn).unwrap();
+
+        let found = Bookmark::find(&conn, "m1").unwrap().unwrap();
+        assert_eq!(found.note.as_deref(), Some("reply by monday"));
+        assert_eq!(found.tags, vec!["todo", "work"]);
+        let flagged: i32 = conn
+            .query_row("SELECT is_bookmarked FROM messages WHERE guid = 'm1'", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(flagged, 1);
+
+        let by_tag = |tag: &str| {
+            Bookmark::list(&conn, &BookmarkFilter { tag: Some(tag.into()), ..Default::default() }).unwrap().len()
+        };
+        assert_eq!(by_tag("TODO"), 2);
+        assert_eq!(by_tag("work"), 1);
+        let in_chat = Bookmark::list(&conn, &BookmarkFilter { chat_id: Some(2), ..Default::default() }).unwrap();
+        assert_eq!(in_chat[0].message_guid, "m2");
+        let matched = Bookmark::list(&conn, &BookmarkFilter { query: Some("monday".into()), ..Default::default() }).unwrap();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(Bookmark::tag_counts(&conn).unwrap(), vec![("todo".into(), 2), ("work".into(), 1)]);
+
+        assert!(Bookmark::delete(&conn, "m1").unwrap());
+        assert!(!Bookmark::delete(&conn, "m1").unwrap());
+        assert!(Bookmark::find(&conn, "m1").unwrap().is_none());
+        assert_eq!(by_tag("work"), 0);
+    }
+
+    #[test]
+    fn test_server_flag_wins_unless_changed_locally() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let flagged = |guid: &str| -> i32 {
+            conn.query_row("SELECT is_bookmarked FROM messages WHERE guid = ?1", [guid], |r| r.get(0))
+                .unwrap()
+        };
+        let server = |guid: &str, bookmarked: bool| {
+            let mut msg = crate::Message::from_server_map(&serde_json::json!({
+                "guid": guid, "text": "hi", "isBookmarked": bookmarked
+            }))
+            .unwrap();
+            msg.save(&conn).unwrap();
+        };
+
+        // Bookmarked on another device, then removed there
+        server("m1", true);
+        assert!(Bookmark::find(&conn, "m1").unwrap().is_some());
+        assert_eq!(flagged("m1"), 1);
+        server("m1", false);
+        assert!(Bookmark::find(&conn, "m1").unwrap().is_none());
+        assert_eq!(flagged("m1"), 0);
+
+        // Removed on the server, but noted here since the last report
+        server("m2", true);
+        conn.execute("UPDATE bookmarks SET date_synced = '2000-01-01T00:00:00+00:00'", []).unwrap();
+        let mut b = Bookmark::find(&conn, "m2").unwrap().unwrap();
+        b.note = Some("keep".into());
+        b.save(&conn).unwrap();
+        server("m2", false);
+        assert_eq!(Bookmark::find(&conn, "m2").unwrap().unwrap().note.as_deref(), Some("keep"));
+        assert_eq!(flagged("m2"), 1);
+
+        // Only ever bookmarked here
+        server("m3", false);
+        Bookmark::new("m3").save(&conn).unwrap();
+        server("m3", false);
+        assert!(Bookmark::find(&conn, "m3").unwrap().is_some());
+        assert_eq!(flagged("m3"), 1);
+    }
+}
@@ -0,0 +1,474 @@
+//! Chat folder entity model: user-defined labels and rule-based smart folders.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, Row};
+use bb_core::error::{BbError, BbResult};
+
+/// A rule selecting the chats of a smart folder.
+///
+/// Participant counts exclude the local user, so a one-to-one chat has one
+/// participant and "groups with more than 5 members" is
+/// `MinParticipants { count: 5 }`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FolderRule {
+    /// Group chats (more than one participant).
+    Group,
+    /// One-to-one chats.
+    Direct,
+    /// Chats with at least `count` participants.
+    MinParticipants { count: i64 },
+    /// Chats with a participant that is not a saved contact.
+    UnknownSenders,
+    /// Chats with unread messages.
+    Unread,
+    /// Pinned chats.
+    Pinned,
+    /// Muted chats.
+    Muted,
+    /// Chats on a service (`iMessage`, `SMS`, ...), matched on the GUID prefix.
+    Service { name: String },
+    /// Chats whose name or identifier contains text.
+    NameContains { text: String },
+    /// Chats matching every rule.
+    All { rules: Vec<FolderRule> },
+    /// Chats matching any rule.
+    Any { rules: Vec<FolderRule> },
+    /// Chats not matching the rule.
+    Not { rule: Box<FolderRule> },
+}
+
+impl FolderRule {
+    /// Parse the compact rule syntax used by the CLI.
+    ///
+    /// Comma-separated terms that must all match: `group`, `direct`,
+    /// `unread`, `unknown`, `pinned`, `muted`, `members>=N` (or `members>N`),
+    /// `service=NAME`, and `name~TEXT`. A leading `!` negates a term.
+    pub fn parse(input: &str) -> BbResult<Self> {
+        let mut rules = Vec::new();
+        for term in input.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            rules.push(Self::parse_term(term)?);
+        }
+        match rules.len() {
+            0 => Err(BbError::InvalidInput("empty folder rule".into())),
+            1 => Ok(rules.remove(0)),
+            _ => Ok(Self::All { rules }),
+        }
+    }
+
+    fn parse_term(term: &str) -> BbResult<Self> {
+        if let Some(rest) = term.strip_prefix('!') {
+            return Ok(Self::Not { rule: Box::new(Self::parse_term(rest.trim())?) });
+        }
+        let count = |s: &str| {
+            s.trim()
+                .parse::<i64>()
+                .map_err(|_| BbError::InvalidInput(format!("invalid member count in rule: {term}")))
+        };
+        let rule = match term.to_lowercase().as_str() {
+            "group" => Self::Group,
+            "direct" => Self::Direct,
+            "unread" => Self::Unread,
+            "unknown" => Self::UnknownSenders,
+            "pinned" => Self::Pinned,
+            "muted" => Self::Muted,
+            lower => {
+                if let Some(n) = lower.strip_prefix("members>=") {
+                    Self::MinParticipants { count: count(n)? }
+                } else if let Some(n) = lower.strip_prefix("members>") {
+                    Self::MinParticipants { count: count(n)? + 1 }
+                } else if let Some((_, name)) = term.split_once("service=") {
+                    Self::Service { name: name.trim().to_string() }
+                } else if let Some((_, text)) = term.split_once("name~") {
+                    Self::NameContains { text: text.trim().to_string() }
+                } else {
+                    return Err(BbError::InvalidInput(format!("unknown folder rule: {term}")));
+                }
+            }
+        };
+        Ok(rule)
+    }
+
+    /// SQL condition over the chats table aliased `c`. Values are pushed to
+    /// `values` and referenced by their 1-based position.
+    pub fn to_sql(&self, values: &mut Vec<Value>) -> String {
+        const PARTICIPANTS: &str = "(SELECT COUNT(*) FROM chat_handle_join j WHERE j.chat_id = c.id)";
+        let mut bind = |v: Value| {
+            values.push(v);
+            format!("?{}", values.len())
+        };
+        match self {
+            Self::Group => format!("{PARTICIPANTS} > 1"),
+            Self::Direct => format!("{PARTICIPANTS} = 1"),
+            Self::MinParticipants { count } => format!("{PARTICIPANTS} >= {}", bind(Value::Integer(*count))),
+            Self::UnknownSenders => "EXISTS (SELECT 1 FROM chat_handle_join j
+                 INNER JOIN handles h ON h.id = j.handle_id
+                 WHERE j.chat_id = c.id AND h.contact_id IS NULL)"
+                .to_string(),
+            Self::Unread => "EXISTS (SELECT 1 FROM messages m WHERE m.chat_id = c.id
+                 AND m.date_read IS NULL AND m.is_from_me = 0 AND m.date_deleted IS NULL)"
+                .to_string(),
+            Self::Pinned => "c.is_pinned = 1".to_string(),
+            Self::Muted => "c.mute_type IS NOT NULL".to_string(),
+            Self::Service { name } => {
+                format!("c.guid LIKE {} || ';%'", bind(Value::Text(name.clone())))
+            }
+            Self::NameContains { text } => {
+                let p = bind(Value::Text(format!("%{text}%")));
+                format!("(c.display_name LIKE {p} OR c.chat_identifier LIKE {p})")
+            }
+            Self::All { rules } if rules.is_empty() => "1".to_string(),
+            Self::Any { rules } if rules.is_empty() => "0".to_string(),
+            Self::All { rules } => join(rules, " AND ", values),
+            Self::Any { rules } => join(rules, " OR ", values),
+            Self::Not { rule } => format!("NOT ({})", rule.to_sql(values)),
+        }
+    }
+}
+
+fn join(rules: &[FolderRule], op: &str, values: &mut Vec<Value>) -> String {
+    let parts: Vec<String> = rules.iter().map(|r| format!("({})", r.to_sql(values))).collect();
+    parts.join(op)
+}
+
+impl fmt::Display for FolderRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Group => write!(f, "group"),
+            Self::Direct => write!(f, "direct"),
+            Self::MinParticipants { count } => write!(f, "members>={count}"),
+            Self::UnknownSenders => write!(f, "unknown"),
+            Self::Unread => write!(f, "unread"),
+            Self::Pinned => write!(f, "pinned"),
+            Self::Muted => write!(f, "muted"),
+            Self::Service { name } => write!(f, "service={name}"),
+            Self::NameContains { text } => write!(f, "name~{text}"),
+            Self::All { rules } => {
+                let parts: Vec<String> = rules.iter().map(|r| r.to_string()).collect();
+                write!(f, "{}", parts.join(","))
+            }
+            Self::Any { rules } => {
+                let parts: Vec<String> = rules.iter().map(|r| r.to_string()).collect();
+                write!(f, "any({})", parts.join(" | "))
+            }
+            Self::Not { rule } => write!(f, "!{rule}"),
+        }
+    }
+}
+
+/// A chat folder: either a manual label holding chosen chats, or a smart
+/// folder whose chats are selected by a rule.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatFolder {
+    pub id: Option<i64>,
+    pub name: String,
+    pub color: Option<String>,
+    /// Rule for smart folders; None for manual folders.
+    pub rule: Option<FolderRule>,
+    pub sort_index: i64,
+    pub date_created: String,
+}
+
+impl ChatFolder {
+    /// Create an unsaved manual folder.
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: None,
+            name: name.trim().to_string(),
+            color: None,
+            rule: None,
+            sort_index: 0,
+            date_created: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Create an unsaved smart folder.
+    pub fn smart(name: &str, rule: FolderRule) -> Self {
+        Self {
+            rule: Some(rule),
+            ..Self::new(name)
+        }
+    }
+
+    /// Whether chats are selected by a rule rather than added by hand.
+    pub fn is_smart(&self) -> bool {
+        self.rule.is_some()
+    }
+
+    /// Construct a ChatFolder from a database row.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let rule: Option<String> = row.get("rule")?;
+        Ok(Self {
+            id: Some(row.get("id")?),
+            name: row.get("name")?,
+            color: row.get("color")?,
+            rule: rule.and_then(|r| serde_json::from_str(&r).ok()),
+            sort_index: row.get("sort_index")?,
+            date_created: row.get("date_created")?,
+        })
+    }
+
+    /// Insert or update this folder. Returns the row ID.
+    pub fn save(&mut self, conn: &Connection) -> BbResult<i64> {
+        if self.name.trim().is_empty() {
+            return Err(BbError::InvalidInput("folder name cannot be empty".into()));
+        }
+        if let Some(existing) = Self::find_by_name(conn, &self.name)? {
+            if existing.id != self.id {
+                return Err(BbError::InvalidInput(format!("a folder named {} already exists", self.name)));
+            }
+        }
+        let rule = self
+            .rule
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| BbError::Serialization(e.to_string()))?;
+
+        let id = match self.id {
+            Some(id) => {
+                conn.execute(
+                    "UPDATE chat_folders SET name = ?1, color = ?2, rule = ?3, sort_index = ?4 WHERE id = ?5",
+                    params![self.name, self.color, rule, self.sort_index, id],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+                id
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO chat_folders (name, color, rule, sort_index, date_created)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![self.name, self.color, rule, self.sort_index, self.date_created],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+                conn.last_insert_rowid()
+            }
+        };
+        self.id = Some(id);
+        Ok(id)
+    }
+
+    /// Find a folder by ID.
+    pub fn find_by_id(conn: &Connection, id: i64) -> BbResult<Option<Self>> {
+        match conn.query_row("SELECT * FROM chat_folders WHERE id = ?1", [id], Self::from_row) {
+            Ok(f) => Ok(Some(f)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(BbError::Database(e.to_string())),
+        }
+    }
+
+    /// Find a folder by name, ignoring case.
+    pub fn find_by_name(conn: &Connection, name: &str) -> BbResult<Option<Self>> {
+        match conn.query_row(
+            "SELECT * FROM chat_folders WHERE name = ?1 COLLATE NOCASE",
+            [name.trim()],
+            Self::from_row,
+        ) {
+            Ok(f) => Ok(Some(f)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(BbError::Database(e.to_string())),
+        }
+    }
+
+    /// Load all folders in display order.
+    pub fn list(conn: &Connection) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM chat_folders ORDER BY sort_index, name COLLATE NOCASE")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let folders = stmt
+            .query_map([], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(folders)
+    }
+
+    /// Delete a folder and its memberships. Returns whether it existed.
+    pub fn delete(conn: &Connection, id: i64) -> BbResult<bool> {
+        conn.execute("DELETE FROM chat_folder_members WHERE folder_id = ?1", [id])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        let deleted = conn
+            .execute("DELETE FROM chat_folders WHERE id = ?1", [id])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(deleted > 0)
+    }
+
+    /// Add a chat to this manual folder.
+    pub fn add_chat(&self, conn: &Connection, chat_id: i64) -> BbResult<()> {
+        let id = self.manual_id()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO chat_folder_members (folder_id, chat_id) VALUES (?1, ?2)",
+            params![id, chat_id],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Remove a chat from this manual folder. Returns whether it was there.
+    pub fn remove_chat(&self, conn: &Connection, chat_id: i64) -> BbResult<bool> {
+        let id = self.manual_id()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM chat_folder_members WHERE folder_id = ?1 AND chat_id = ?2",
+                params![id, chat_id],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(removed > 0)
+    }
+
+    /// Folders a chat has been added to by hand.
+    pub fn for_chat(conn: &Connection, chat_id: i64) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT f.* FROM chat_folders f
+                 INNER JOIN chat_folder_members fm ON fm.folder_id = f.id
+                 WHERE fm.chat_id = ?1
+                 ORDER BY f.sort_index, f.name COLLATE NOCASE",
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let folders = stmt
+            .query_map([chat_id], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(folders)
+    }
+
+    /// GUIDs of the chats added to this manual folder.
+    pub fn member_guids(&self, conn: &Connection) -> BbResult<Vec<String>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        let mut stmt = conn
+            .prepare(
+                "SELECT c.guid FROM chats c
+                 INNER JOIN chat_folder_members fm ON fm.chat_id = c.id
+                 WHERE fm.folder_id = ?1
+                 ORDER BY c.guid",
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let guids = stmt
+            .query_map([id], |row| row.get(0))
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(guids)
+    }
+
+    /// SQL condition selecting this folder's chats from `chats c`. Values
+    /// are pushed to `values` and referenced by their 1-based position.
+    pub fn filter_sql(&self, values: &mut Vec<Value>) -> String {
+        match &self.rule {
+            Some(rule) => rule.to_sql(values),
+            None => {
+                values.push(Value::Integer(self.id.unwrap_or_default()));
+                format!(
+                    "c.id IN (SELECT chat_id FROM chat_folder_members WHERE folder_id = ?{})",
+                    values.len()
+                )
+            }
+        }
+    }
+
+    fn manual_id(&self) -> BbResult<i64> {
+        if self.is_smart() {
+            return Err(BbError::InvalidInput(format!(
+                "{} is a smart folder; its chats come from its rule",
+                self.name
+            )));
+        }
+        self.id
+            .ok_or_else(|| BbError::InvalidInput(format!("folder {} is not saved", self.name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    fn chat_ids(conn: &Connection, folder: &ChatFolder) -> Vec<i64> {
+        let mut values = Vec::new();
+        let sql = format!("SELECT c.id FROM chats c WHERE {} ORDER BY c.id", folder.filter_sql(&mut values));
+        let mut stmt = conn.prepare(&sql).unwrap();
+        stmt.query_map(rusqlite::params_from_iter(values), |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rule_parse_and_display() {
+        let rule = FolderRule::parse("group, members>5, !muted").unwrap();
+        assert_eq!(
+            rule,
+            FolderRule::All {
+                rules: vec![
+                    FolderRule::Group,
+                    FolderRule::MinParticipants { count: 6 },
+                    FolderRule::Not { rule: Box::new(FolderRule::Muted) },
+                ]
+            }
+        );
+        assert_eq!(rule.to_string(), "group,members>=6,!muted");
+        assert_eq!(FolderRule::parse(&rule.to_string()).unwrap(), rule);
+        assert_eq!(FolderRule::parse("service=SMS").unwrap(), FolderRule::Service { name: "SMS".into() });
+        assert!(FolderRule::parse("bogus").is_err());
+        assert!(FolderRule::parse(" , ").is_err());
+    }
+
+    #[test]
+    fn test_manual_and_smart_folders() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO contacts (id, display_name) VALUES (1, 'Alice');
+             INSERT INTO handles (id, address, unique_address_service, contact_id) VALUES
+                (1, 'alice@example.com', 'a/iMessage', 1), (2, '+15550002', 'b/iMessage', NULL),
+                (3, '+15550003', 'c/SMS', 1);
+             INSERT INTO chats (id, guid, display_name) VALUES
+                (1, 'iMessage;-;alice', NULL), (2, 'iMessage;+;family', 'Family'), (3, 'SMS;-;carol', NULL);
+             INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1), (2, 1), (2, 2), (3, 3);",
+        )
+        .unwrap();
+
+        let mut work = ChatFolder::new("Work");
+        work.save(&conn).unwrap();
+        work.add_chat(&conn, 1).unwrap();
+        work.add_chat(&conn, 3).unwrap();
+        assert_eq!(chat_ids(&conn, &work), vec![1, 3]);
+        assert!(work.remove_chat(&conn, 3).unwrap());
+        assert_eq!(work.member_guids(&conn).unwrap(), vec!["iMessage;-;alice".to_string()]);
+        assert_eq!(ChatFolder::for_chat(&conn, 1).unwrap()[0].name, "Work");
+
+        let mut groups = ChatFolder::smart("Groups", FolderRule::Group);
+        groups.save(&conn).unwrap();
+        assert!(groups.add_chat(&conn, 1).is_err());
+        assert_eq!(chat_ids(&conn, &groups), vec![2]);
+
+        let unknown = ChatFolder::smart("Unknown", FolderRule::UnknownSenders);
+        assert_eq!(chat_ids(&conn, &unknown), vec![2]);
+        let sms = ChatFolder::smart("SMS", FolderRule::parse("service=SMS,!group").unwrap());
+        assert_eq!(chat_ids(&conn, &sms), vec![3]);
+        let named = ChatFolder::smart("Fam", FolderRule::parse("name~fam").unwrap());
+        assert_eq!(chat_ids(&conn, &named), vec![2]);
+
+        // Names are unique regardless of case, and rules round-trip through the row
+        assert!(ChatFolder::new("work").save(&conn).is_err());
+        let loaded = ChatFolder::find_by_name(&conn, "GROUPS").unwrap().unwrap();
+        assert_eq!(loaded.rule, Some(FolderRule::Group));
+        assert_eq!(ChatFolder::list(&conn).unwrap().len(), 2);
+
+        assert!(ChatFolder::delete(&conn, work.id.unwrap()).unwrap());
+        assert!(ChatFolder::for_chat(&conn, 1).unwrap().is_empty());
+    }
+}
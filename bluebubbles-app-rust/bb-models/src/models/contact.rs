@@ -4,6 +4,10 @@ use serde::{Deserialize, Serialize};
 use rusqlite::{params, Connection, Row};
 use bb_core::error::{BbError, BbResult};
 
+/// External ID prefix for contacts imported locally (e.g. from a vCard)
+/// rather than synced from the server. Server syncs leave these in place.
+pub const LOCAL_ID_PREFIX: &str = "local:";
+
 /// Represents a contact in the BlueBubbles system.
 ///
 /// Contacts are synced from the macOS server's address book. They hold
@@ -214,6 +218,11 @@ impl Contact {
             .unwrap_or_else(|| "?".to_string())
     }
 
+    /// Whether this contact was imported locally rather than synced from the server.
+    pub fn is_local(&self) -> bool {
+        self.external_id.as_deref().is_some_and(|id| id.starts_with(LOCAL_ID_PREFIX))
+    }
+
     /// Whether this contact has an avatar loaded.
     pub fn has_avatar(&self) -> bool {
         self.avatar.as_ref().map_or(false, |a| !a.is_empty())
@@ -266,13 +275,37 @@ impl Contact {
                 )
                 .map_err(|e| BbError::Database(e.to_string()))?;
             self.id = Some(real_id);
+        } else {
+            self.id = Some(conn.last_insert_rowid());
+        }
+
+        if let Some(id) = self.id {
+            self.index_phones(conn, id)?;
         }
 
         Ok(self.id.unwrap_or(0))
     }
+
+    /// Rewrite this contact's rows in the `contact_phones` index with the
+    /// E.164 form of each phone number, in the default region.
+    pub fn index_phones(&self, conn: &Connection, id: i64) -> BbResult<()> {
+        conn.execute("DELETE FROM contact_phones WHERE contact_id = ?1", [id])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        for number in self.phone_list().iter().filter_map(|p| crate::phone::normalize(p, None)) {
+            conn.execute(
+                "INSERT OR IGNORE INTO contact_phones (contact_id, number) VALUES (?1, ?2)",
+                params![id, number],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        }
+        Ok(())
+    }
 }
 
-/// Normalize a phone number by stripping non-digit characters for comparison.
+/// Strip a phone number down to digits and `+` for loose comparison.
+///
+/// This does not resolve country codes or trunk prefixes; use
+/// [`crate::phone::normalize`] for an exact E.164 key.
 pub fn normalize_address(addr: &str) -> String {
     addr.chars().filter(|c| c.is_ascii_digit() || *c == '+').collect()
 }
@@ -21,6 +21,9 @@ pub struct Handle {
     pub default_phone: Option<String>,
     pub default_email: Option<String>,
     pub contact_id: Option<i64>,
+    /// E.164 form of a phone address, derived from `address` and `country`.
+    #[serde(default)]
+    pub normalized_address: Option<String>,
 
     /// Transient: linked contact data (loaded separately).
     #[serde(skip)]
@@ -61,6 +64,7 @@ impl Handle {
             default_phone: map.get("defaultPhone").and_then(|v| v.as_str()).map(String::from),
             default_email: map.get("defaultEmail").and_then(|v| v.as_str()).map(String::from),
             contact_id: None,
+            normalized_address: None,
             contact: None,
         })
     }
@@ -79,6 +83,7 @@ impl Handle {
             default_phone: row.get("default_phone")?,
             default_email: row.get("default_email")?,
             contact_id: row.get("contact_id")?,
+            normalized_address: row.get("normalized_address")?,
             contact: None,
         })
     }
@@ -169,6 +174,15 @@ impl Handle {
         !self.is_email()
     }
 
+    /// E.164 form of the address, using the handle's country or the default
+    /// region. None for email addresses and short codes.
+    pub fn compute_normalized_address(&self) -> Option<String> {
+        if self.is_email() {
+            return None;
+        }
+        crate::phone::normalize(&self.address, self.country.as_deref())
+    }
+
     /// Whether this handle uses iMessage.
     pub fn is_imessage(&self) -> bool {
         self.service == "iMessage"
@@ -178,14 +192,16 @@ impl Handle {
 
     /// Upsert this handle into the database. Returns the local database ID.
     pub fn save(&mut self, conn: &Connection) -> BbResult<i64> {
+        self.normalized_address = self.compute_normalized_address();
         conn.execute(
             "INSERT INTO handles (
                 original_rowid, address, service, unique_address_service,
                 formatted_address, country, color, default_phone,
-                default_email, contact_id
-            ) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)
+                default_email, contact_id, normalized_address
+            ) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)
             ON CONFLICT(unique_address_service) DO UPDATE SET
                 formatted_address = COALESCE(excluded.formatted_address, formatted_address),
+                normalized_address = COALESCE(excluded.normalized_address, normalized_address),
                 color = COALESCE(excluded.color, color),
                 default_phone = COALESCE(excluded.default_phone, default_phone),
                 default_email = COALESCE(excluded.default_email, default_email),
@@ -201,6 +217,7 @@ impl Handle {
                 self.default_phone,
                 self.default_email,
                 self.contact_id,
+                self.normalized_address,
             ],
         )
         .map_err(|e| BbError::Database(e.to_string()))?;
@@ -276,6 +293,18 @@ mod tests {
         assert_eq!(handle.address, "+15551234567");
         assert!(handle.is_phone());
         assert!(handle.is_imessage());
+        assert_eq!(handle.compute_normalized_address().as_deref(), Some("+15551234567"));
+    }
+
+    #[test]
+    fn test_handle_normalized_address_uses_country() {
+        let json = serde_json::json!({"address": "07700 900123", "country": "gb"});
+        let handle = Handle::from_server_map(&json).unwrap();
+        assert_eq!(handle.compute_normalized_address().as_deref(), Some("+447700900123"));
+
+        let json = serde_json::json!({"address": "test@example.com"});
+        let handle = Handle::from_server_map(&json).unwrap();
+        assert_eq!(handle.compute_normalized_address(), None);
     }
 
     #[test]
@@ -0,0 +1,61 @@
+//! Cached link previews for outgoing URLs.
+//!
+//! Pages that had no usable metadata (or could not be fetched) are cached
+//! too, with no preview, so they are not fetched again on every send.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use bb_core::error::{BbError, BbResult};
+
+use super::payload_data::UrlPreviewData;
+
+/// A link preview as stored in the cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedLinkPreview {
+    pub url: String,
+    /// None when the page had no preview.
+    pub preview: Option<UrlPreviewData>,
+    /// When the page was fetched, in epoch milliseconds.
+    pub fetched_at: i64,
+}
+
+impl CachedLinkPreview {
+    /// Construct a CachedLinkPreview from a database row.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let preview: Option<String> = row.get("preview")?;
+        Ok(Self {
+            url: row.get("url")?,
+            preview: preview.and_then(|json| serde_json::from_str(&json).ok()),
+            fetched_at: row.get("fetched_at")?,
+        })
+    }
+
+    /// Find the cached preview for a URL.
+    pub fn find(conn: &Connection, url: &str) -> BbResult<Option<Self>> {
+        conn.query_row("SELECT * FROM link_previews WHERE url = ?1", [url], Self::from_row)
+            .optional()
+            .map_err(|e| BbError::Database(e.to_string()))
+    }
+
+    /// Save this preview, replacing any earlier one for the URL.
+    pub fn save(&self, conn: &Connection) -> BbResult<()> {
+        let preview = self
+            .preview
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| BbError::Serialization(e.to_string()))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO link_previews (url, preview, fetched_at) VALUES (?1, ?2, ?3)",
+            params![self.url, preview, self.fetched_at],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Delete previews fetched before `cutoff` (epoch ms). Returns how many
+    /// were deleted.
+    pub fn delete_older_than(conn: &Connection, cutoff: i64) -> BbResult<usize> {
+        conn.execute("DELETE FROM link_previews WHERE fetched_at < ?1", [cutoff])
+            .map_err(|e| BbError::Database(e.to_string()))
+    }
+}
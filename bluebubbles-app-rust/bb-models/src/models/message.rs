@@ -4,6 +4,10 @@ use serde::{Deserialize, Serialize};
 use rusqlite::{params, Connection, Row};
 use bb_core::error::{BbError, BbResult};
 
+use super::attributed_body::AttributedBody;
+use super::bookmark::Bookmark;
+use super::message_summary_info::MessageSummaryInfo;
+
 /// Represents a single message in the BlueBubbles system.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -218,6 +222,17 @@ impl Message {
         Ok(changed > 0)
     }
 
+    /// Clear `date_deleted` on a soft-deleted message.
+    pub fn restore(conn: &Connection, guid: &str) -> BbResult<bool> {
+        let changed = conn
+            .execute(
+                "UPDATE messages SET date_deleted = NULL WHERE guid = ?1 AND date_deleted IS NOT NULL",
+                [guid],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(changed > 0)
+    }
+
     /// Replace a temp message (identified by old_guid) with server data.
     pub fn replace_temp(conn: &Connection, old_guid: &str, new_msg: &mut Message) -> BbResult<i64> {
         // Find existing temp message
@@ -307,6 +322,67 @@ impl Message {
             .map(|d| d as i32)
     }
 
+    /// Parse the stored edit/unsend summary, if any.
+    pub fn summary_info(&self) -> Option<MessageSummaryInfo> {
+        self.message_summary_info
+            .as_deref()
+            .and_then(MessageSummaryInfo::from_json_str)
+    }
+
+    /// Text of one message part: its latest edit, else its runs in the
+    /// attributed body. A message without part information is a single
+    /// part 0, whose text is the whole message.
+    pub fn part_text(&self, part: i32) -> Option<String> {
+        if let Some(edited) = self
+            .summary_info()
+            .and_then(|info| info.edited_content.get(&part).and_then(|v| v.last()).and_then(|v| v.text.clone()))
+        {
+            return Some(edited);
+        }
+        let body = self.parsed_attributed_body();
+        let has_parts = body
+            .as_ref()
+            .is_some_and(|b| b.runs.iter().any(|r| r.attributes.message_part.is_some()));
+        if has_parts {
+            let text: String = body
+                .iter()
+                .flat_map(|b| &b.runs)
+                .filter(|r| r.attributes.message_part == Some(part))
+                .map(|r| r.text.as_str())
+                .collect();
+            return Some(text).filter(|t| !t.trim().is_empty());
+        }
+        if part == 0 {
+            self.text.clone().filter(|t| !t.is_empty())
+        } else {
+            None
+        }
+    }
+
+    /// Indices of the message's parts, as marked in the attributed body. A
+    /// message without part information is the single part 0.
+    pub fn part_indices(&self) -> Vec<i32> {
+        let mut parts: Vec<i32> = self
+            .parsed_attributed_body()
+            .iter()
+            .flat_map(|b| &b.runs)
+            .filter_map(|r| r.attributes.message_part)
+            .collect();
+        parts.sort_unstable();
+        parts.dedup();
+        if parts.is_empty() {
+            parts.push(0);
+        }
+        parts
+    }
+
+    fn parsed_attributed_body(&self) -> Option<AttributedBody> {
+        self.attributed_body
+            .as_deref()
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(json).ok())
+            .and_then(|json| AttributedBody::from_server_json(&json))
+    }
+
     /// Merge non-null fields from another message into this one.
     pub fn merge(&mut self, other: &Message) {
         if other.text.is_some() {
@@ -337,6 +413,9 @@ impl Message {
 
     /// Upsert this message into the database. Returns the local database ID.
     pub fn save(&mut self, conn: &Connection) -> BbResult<i64> {
+        if let Some(ref guid) = self.guid {
+            Bookmark::apply_server_flag(conn, guid, self.is_bookmarked)?;
+        }
         conn.execute(
             "INSERT INTO messages (
                 original_rowid, guid, chat_id, handle_id, other_handle,
@@ -360,7 +439,8 @@ impl Message {
                 date_deleted = excluded.date_deleted,
                 date_edited = COALESCE(excluded.date_edited, date_edited),
                 message_summary_info = COALESCE(excluded.message_summary_info, message_summary_info),
-                is_bookmarked = excluded.is_bookmarked",
+                is_bookmarked = excluded.is_bookmarked
+                    OR EXISTS (SELECT 1 FROM bookmarks WHERE message_guid = excluded.guid)",
             params![
                 self.original_rowid, self.guid, self.chat_id, self.handle_id,
                 self.other_handle, self.text, self.subject, self.country,
@@ -444,6 +524,28 @@ mod tests {
         assert_eq!(msg.full_text(), "Re:\nHello");
     }
 
+    #[test]
+    fn test_part_text() {
+        let mut msg = Message::from_server_map(&serde_json::json!({
+            "guid": "parts",
+            "text": "first second",
+            "attributedBody": [{"runs": [
+                {"string": "first ", "attributes": {"__kIMMessagePartAttributeName": 0}},
+                {"string": "second", "attributes": {"__kIMMessagePartAttributeName": 1}}
+            ]}]
+        }))
+        .unwrap();
+        assert_eq!(msg.part_text(0).as_deref(), Some("first "));
+        assert_eq!(msg.part_text(1).as_deref(), Some("second"));
+        assert!(msg.part_text(2).is_none());
+        assert_eq!(msg.part_indices(), vec![0, 1]);
+
+        msg.attributed_body = None;
+        assert_eq!(msg.part_text(0).as_deref(), Some("first second"));
+        assert!(msg.part_text(1).is_none());
+        assert_eq!(msg.part_indices(), vec![0]);
+    }
+
     #[test]
     fn test_temp_detection() {
         let mut msg = Message::from_server_map(&serde_json::json!({"guid": "temp-abc"})).unwrap();
@@ -0,0 +1,209 @@
+//! Message summary info parsing (edit history and retracted parts).
+//!
+//! iMessage records edits and unsends in the `messageSummaryInfo` blob. The
+//! server forwards it either with Apple's short keys (`ec`, `rp`, `ep`) or
+//! with expanded names (`editedContent`, `retractedParts`, `editedParts`),
+//! sometimes wrapped in a single-element array. Each edited part carries the
+//! full list of versions, oldest first, with the current text last.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use bb_core::constants::APPLE_EPOCH_OFFSET_SECS;
+
+use super::attributed_body::AttributedBody;
+
+/// Parsed `messageSummaryInfo` for a single message.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageSummaryInfo {
+    /// Part indexes that were unsent.
+    pub retracted_parts: Vec<i32>,
+    /// Part indexes that were edited.
+    pub edited_parts: Vec<i32>,
+    /// All versions of each edited part, keyed by part index, oldest first.
+    pub edited_content: BTreeMap<i32, Vec<EditedContent>>,
+}
+
+/// One version of an edited message part.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditedContent {
+    /// Plain text of this version.
+    pub text: Option<String>,
+    /// When this version was written (RFC 3339).
+    pub date: Option<String>,
+}
+
+impl MessageSummaryInfo {
+    /// Parse from the raw string stored in `messages.message_summary_info`.
+    pub fn from_json_str(raw: &str) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_str(raw).ok()?;
+        Self::from_server_json(&value)
+    }
+
+    /// Parse from the server JSON representation.
+    pub fn from_server_json(json: &serde_json::Value) -> Option<Self> {
+        let obj = match json {
+            serde_json::Value::Array(arr) => arr.first()?,
+            serde_json::Value::Object(_) => json,
+            _ => return None,
+        };
+        let obj = obj.as_object()?;
+
+        let int_list = |short: &str, long: &str| -> Vec<i32> {
+            obj.get(short)
+                .or_else(|| obj.get(long))
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_i64()).map(|v| v as i32).collect())
+                .unwrap_or_default()
+        };
+
+        let mut edited_content = BTreeMap::new();
+        if let Some(ec) = obj
+            .get("ec")
+            .or_else(|| obj.get("editedContent"))
+            .and_then(|v| v.as_object())
+        {
+            for (part, versions) in ec {
+                let Ok(part_index) = part.parse::<i32>() else {
+                    continue;
+                };
+                let versions: Vec<EditedContent> = versions
+                    .as_array()
+                    .map(|arr| arr.iter().map(EditedContent::from_server_json).collect())
+                    .unwrap_or_default();
+                if !versions.is_empty() {
+                    edited_content.insert(part_index, versions);
+                }
+            }
+        }
+
+        Some(Self {
+            retracted_parts: int_list("rp", "retractedParts"),
+            edited_parts: int_list("ep", "editedParts"),
+            edited_content,
+        })
+    }
+
+    /// Whether any part of the message was edited.
+    pub fn is_edited(&self) -> bool {
+        !self.edited_parts.is_empty() || !self.edited_content.is_empty()
+    }
+
+    /// Whether the given part was unsent.
+    pub fn is_retracted(&self, part_index: i32) -> bool {
+        self.retracted_parts.contains(&part_index)
+    }
+
+    /// Earlier versions of a part, excluding the current text.
+    pub fn previous_versions(&self, part_index: i32) -> &[EditedContent] {
+        match self.edited_content.get(&part_index) {
+            Some(versions) if !versions.is_empty() => &versions[..versions.len() - 1],
+            _ => &[],
+        }
+    }
+}
+
+impl EditedContent {
+    fn from_server_json(json: &serde_json::Value) -> Self {
+        let text = json.get("t").or_else(|| json.get("text")).and_then(content_text);
+        let date = json
+            .get("d")
+            .or_else(|| json.get("date"))
+            .and_then(|v| v.as_f64())
+            .and_then(timestamp_to_rfc3339);
+        Self { text, date }
+    }
+}
+
+/// Extract plain text from the assorted shapes an edited version's text takes:
+/// a bare string, `{"values": [attributedBody...]}`, or an attributed body array.
+fn content_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Object(map) => {
+            if let Some(values) = map.get("values") {
+                return content_text(values);
+            }
+            map.get("string").and_then(|v| v.as_str()).map(String::from)
+        }
+        serde_json::Value::Array(arr) => {
+            if let Some(body) = AttributedBody::from_server_json(value) {
+                if !body.runs.is_empty() {
+                    return Some(body.plain_text());
+                }
+            }
+            arr.first().and_then(content_text)
+        }
+        _ => None,
+    }
+}
+
+/// Convert an edit timestamp to RFC 3339.
+///
+/// The server usually sends Unix milliseconds, but raw Apple values may be
+/// seconds or nanoseconds since 2001-01-01, so the magnitude picks the unit.
+fn timestamp_to_rfc3339(value: f64) -> Option<String> {
+    let unix_ms = if value > 1.0e15 {
+        (value / 1.0e6) as i64 + APPLE_EPOCH_OFFSET_SECS * 1000
+    } else if value > 1.0e11 {
+        value as i64
+    } else {
+        (value * 1000.0) as i64 + APPLE_EPOCH_OFFSET_SECS * 1000
+    };
+    chrono::DateTime::from_timestamp_millis(unix_ms).map(|dt| dt.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_short_keys() {
+        let json = serde_json::json!({
+            "ec": {
+                "0": [
+                    {"d": 1_700_000_000_000_i64, "t": "helo"},
+                    {"d": 1_700_000_060_000_i64, "t": "hello"}
+                ]
+            },
+            "ep": [0],
+            "rp": [1]
+        });
+        let info = MessageSummaryInfo::from_server_json(&json).unwrap();
+        assert!(info.is_edited());
+        assert!(info.is_retracted(1));
+        assert!(!info.is_retracted(0));
+        let previous = info.previous_versions(0);
+        assert_eq!(previous.len(), 1);
+        assert_eq!(previous[0].text.as_deref(), Some("helo"));
+        assert!(previous[0].date.as_deref().unwrap().starts_with("2023-11-14"));
+    }
+
+    #[test]
+    fn test_parse_long_keys_wrapped_in_array() {
+        let raw = r#"[{"editedContent":{"0":[{"date":1700000000000,"text":{"values":[{"string":"first","runs":[]}]}},{"date":1700000060000,"text":{"values":[{"string":"second"}]}}]},"editedParts":[0],"retractedParts":[]}]"#;
+        let info = MessageSummaryInfo::from_json_str(raw).unwrap();
+        let versions = &info.edited_content[&0];
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].text.as_deref(), Some("first"));
+        assert_eq!(versions[1].text.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn test_apple_epoch_seconds() {
+        // 2023-11-14T22:13:20Z expressed as seconds since 2001-01-01
+        let secs = (1_700_000_000 - APPLE_EPOCH_OFFSET_SECS) as f64;
+        assert!(timestamp_to_rfc3339(secs).unwrap().starts_with("2023-11-14T22:13:20"));
+        assert!(timestamp_to_rfc3339(secs * 1.0e9).unwrap().starts_with("2023-11-14T22:13:20"));
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(MessageSummaryInfo::from_json_str("null").is_none());
+        assert!(MessageSummaryInfo::from_json_str("not json").is_none());
+        let empty = MessageSummaryInfo::from_json_str("{}").unwrap();
+        assert!(!empty.is_edited());
+        assert!(empty.previous_versions(0).is_empty());
+    }
+}
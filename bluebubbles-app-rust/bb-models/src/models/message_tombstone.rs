@@ -0,0 +1,137 @@
+//! Message tombstone entity model.
+
+use serde::{Deserialize, Serialize};
+use rusqlite::{params, Row};
+use bb_core::error::{BbError, BbResult};
+
+/// A record of an unsent message part.
+///
+/// When a message is unsent the server clears its text, so the tombstone
+/// keeps whatever text was known locally at the time of the unsend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageTombstone {
+    pub id: Option<i64>,
+    pub message_guid: String,
+    pub chat_id: Option<i64>,
+    pub part_index: i32,
+    pub original_text: Option<String>,
+    pub date_unsent: String,
+}
+
+impl MessageTombstone {
+    /// Create a tombstone for a part unsent now.
+    pub fn new(
+        message_guid: &str,
+        chat_id: Option<i64>,
+        part_index: i32,
+        original_text: Option<String>,
+    ) -> Self {
+        Self {
+            id: None,
+            message_guid: message_guid.to_string(),
+            chat_id,
+            part_index,
+            original_text,
+            date_unsent: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Construct a MessageTombstone from a database row.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: Some(row.get("id")?),
+            message_guid: row.get("message_guid")?,
+            chat_id: row.get("chat_id")?,
+            part_index: row.get("part_index")?,
+            original_text: row.get("original_text")?,
+            date_unsent: row.get("date_unsent")?,
+        })
+    }
+
+    /// Record this tombstone.
+    ///
+    /// A part is only tombstoned once; if a record already exists the stored
+    /// original text is kept unless it was empty. Returns the row ID.
+    pub fn save(&mut self, conn: &rusqlite::Connection) -> BbResult<i64> {
+        conn.execute(
+            "INSERT INTO message_tombstones (
+                message_guid, chat_id, part_index, original_text, date_unsent
+            ) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(message_guid, part_index) DO UPDATE SET
+                original_text = COALESCE(message_tombstones.original_text, excluded.original_text),
+                chat_id = COALESCE(message_tombstones.chat_id, excluded.chat_id)",
+            params![
+                self.message_guid,
+                self.chat_id,
+                self.part_index,
+                self.original_text,
+                self.date_unsent,
+            ],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let id: i64 = conn
+            .query_row(
+                "SELECT id FROM message_tombstones WHERE message_guid = ?1 AND part_index = ?2",
+                params![self.message_guid, self.part_index],
+                |row| row.get(0),
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        self.id = Some(id);
+        Ok(id)
+    }
+
+    /// Load all tombstones for a message, ordered by part index.
+    pub fn find_for_message(conn: &rusqlite::Connection, message_guid: &str) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM message_tombstones WHERE message_guid = ?1 ORDER BY part_index ASC")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let tombstones = stmt
+            .query_map([message_guid], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(tombstones)
+    }
+
+    /// Load all tombstones for a chat, most recent first.
+    pub fn find_for_chat(conn: &rusqlite::Connection, chat_id: i64) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM message_tombstones WHERE chat_id = ?1 ORDER BY date_unsent DESC")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let tombstones = stmt
+            .query_map([chat_id], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(tombstones)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    #[test]
+    fn test_tombstone_keeps_first_text() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+
+        let mut first = MessageTombstone::new("msg-1", Some(1), 0, Some("oops".into()));
+        let id = first.save(&conn).unwrap();
+
+        // A later event with the text already cleared must not overwrite it
+        let mut second = MessageTombstone::new("msg-1", Some(1), 0, None);
+        assert_eq!(second.save(&conn).unwrap(), id);
+
+        let stored = MessageTombstone::find_for_message(&conn, "msg-1").unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].original_text.as_deref(), Some("oops"));
+        assert_eq!(MessageTombstone::find_for_chat(&conn, 1).unwrap().len(), 1);
+    }
+}
@@ -1,13 +1,25 @@
 //! Entity model definitions.
 
 pub mod chat;
+pub mod chat_folder;
+pub mod link_preview;
+pub mod notification_digest;
+pub mod notification_rule;
+pub mod otp_settings;
+pub mod outbox;
 pub mod message;
+pub mod message_summary_info;
+pub mod message_tombstone;
+pub mod bookmark;
+pub mod retention_policy;
 pub mod handle;
+pub mod person;
 pub mod attachment;
 pub mod contact;
 pub mod fcm_data;
 pub mod theme;
 pub mod scheduled_message;
+pub mod recurrence;
 pub mod attributed_body;
 pub mod payload_data;
 pub mod settings;
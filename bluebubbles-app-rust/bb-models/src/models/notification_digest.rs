@@ -0,0 +1,216 @@
+//! Notification digest entity model: incoming messages held back to be
+//! summarized per chat, and the digest settings.
+//!
+//! Entries live in the database so every process listening for messages
+//! (the CLI and the desktop app) buffers into, and flushes from, the same
+//! digest.
+
+use serde::{Deserialize, Serialize};
+use rusqlite::{params, Connection, Row};
+use bb_core::error::{BbError, BbResult};
+
+use super::settings::Settings;
+
+/// Settings key holding the digest settings as JSON.
+const DIGEST_SETTINGS_KEY: &str = "notificationDigest";
+
+/// How digests are collected and summarized.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DigestSettings {
+    /// Seconds after a chat's first held message before its digest is sent.
+    pub window_secs: u64,
+    /// Number of recent message lines shown in a digest.
+    pub preview_lines: usize,
+    /// Put group chats with no matching rule into digests.
+    #[serde(default)]
+    pub group_chats: bool,
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        Self {
+            window_secs: 300,
+            preview_lines: 3,
+            group_chats: false,
+        }
+    }
+}
+
+impl DigestSettings {
+    /// Load the saved digest settings (defaults if never set).
+    pub fn load(conn: &Connection) -> BbResult<Self> {
+        Ok(Settings::get_json(conn, DIGEST_SETTINGS_KEY)?.unwrap_or_default())
+    }
+
+    /// Save these digest settings.
+    pub fn save(&self, conn: &Connection) -> BbResult<()> {
+        if self.window_secs == 0 {
+            return Err(BbError::InvalidInput("digest window must be at least one second".into()));
+        }
+        Settings::set_json(conn, DIGEST_SETTINGS_KEY, self)
+    }
+}
+
+/// A message held back for its chat's next digest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DigestEntry {
+    pub id: Option<i64>,
+    pub chat_guid: String,
+    /// Chat title when the message arrived.
+    pub chat_title: String,
+    pub message_guid: String,
+    /// Sender's display name.
+    pub sender: Option<String>,
+    pub text: String,
+    /// Tapback type (`love`, `like`, ...) when the message is a reaction.
+    pub reaction: Option<String>,
+    /// When the message was held, in epoch milliseconds.
+    pub buffered_at: i64,
+}
+
+impl DigestEntry {
+    /// Construct a DigestEntry from a database row.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: Some(row.get("id")?),
+            chat_guid: row.get("chat_guid")?,
+            chat_title: row.get("chat_title")?,
+            message_guid: row.get("message_guid")?,
+            sender: row.get("sender")?,
+            text: row.get("text")?,
+            reaction: row.get("reaction")?,
+            buffered_at: row.get("buffered_at")?,
+        })
+    }
+
+    /// Hold this message for its chat's digest. Returns false when the
+    /// message is already held.
+    pub fn save(&mut self, conn: &Connection) -> BbResult<bool> {
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO notification_digest_entries
+                 (chat_guid, chat_title, message_guid, sender, text, reaction, buffered_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    self.chat_guid,
+                    self.chat_title,
+                    self.message_guid,
+                    self.sender,
+                    self.text,
+                    self.reaction,
+                    self.buffered_at,
+                ],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        if inserted > 0 {
+            self.id = Some(conn.last_insert_rowid());
+        }
+        Ok(inserted > 0)
+    }
+
+    /// All held messages, oldest first.
+    pub fn list(conn: &Connection) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM notification_digest_entries ORDER BY buffered_at, id")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let entries = stmt
+            .query_map([], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Chats whose oldest held message was held at or before `cutoff`
+    /// (epoch milliseconds).
+    pub fn due_chats(conn: &Connection, cutoff: i64) -> BbResult<Vec<String>> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT chat_guid FROM notification_digest_entries
+                 GROUP BY chat_guid HAVING MIN(buffered_at) <= ?1 ORDER BY MIN(buffered_at)",
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let chats = stmt
+            .query_map([cutoff], |row| row.get(0))
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(chats)
+    }
+
+    /// Remove and return a chat's held messages, oldest first. Taking is
+    /// atomic, so only one process sends each digest.
+    pub fn take_chat(conn: &mut Connection, chat_guid: &str) -> BbResult<Vec<Self>> {
+        let tx = conn.transaction().map_err(|e| BbError::Database(e.to_string()))?;
+        let entries = {
+            let mut stmt = tx
+                .prepare("SELECT * FROM notification_digest_entries WHERE chat_guid = ?1 ORDER BY buffered_at, id")
+                .map_err(|e| BbError::Database(e.to_string()))?;
+            let entries: Vec<Self> = stmt
+                .query_map([chat_guid], Self::from_row)
+                .map_err(|e| BbError::Database(e.to_string()))?
+                .filter_map(|r| r.ok())
+                .collect();
+            entries
+        };
+        tx.execute("DELETE FROM notification_digest_entries WHERE chat_guid = ?1", [chat_guid])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        tx.commit().map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    fn entry(chat: &str, guid: &str, at: i64) -> DigestEntry {
+        DigestEntry {
+            id: None,
+            chat_guid: chat.into(),
+            chat_title: "Family".into(),
+            message_guid: guid.into(),
+            sender: Some("Alice".into()),
+            text: "hi".into(),
+            reaction: None,
+            buffered_at: at,
+        }
+    }
+
+    #[test]
+    fn test_entries_are_taken_per_chat_once() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+
+        assert!(entry("family", "m1", 1_000).save(&conn).unwrap());
+        assert!(!entry("family", "m1", 1_500).save(&conn).unwrap());
+        assert!(entry("family", "m2", 2_000).save(&conn).unwrap());
+        assert!(entry("work", "m3", 5_000).save(&conn).unwrap());
+
+        assert_eq!(DigestEntry::due_chats(&conn, 999).unwrap(), Vec::<String>::new());
+        assert_eq!(DigestEntry::due_chats(&conn, 1_000).unwrap(), vec!["family".to_string()]);
+        assert_eq!(DigestEntry::due_chats(&conn, 9_000).unwrap().len(), 2);
+
+        let taken = DigestEntry::take_chat(&mut conn, "family").unwrap();
+        assert_eq!(taken.iter().map(|e| e.message_guid.as_str()).collect::<Vec<_>>(), vec!["m1", "m2"]);
+        assert!(DigestEntry::take_chat(&mut conn, "family").unwrap().is_empty());
+        assert_eq!(DigestEntry::list(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_digest_settings_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        assert_eq!(DigestSettings::load(&conn).unwrap(), DigestSettings::default());
+
+        let settings = DigestSettings { window_secs: 600, preview_lines: 5, group_chats: true };
+        settings.save(&conn).unwrap();
+        assert_eq!(DigestSettings::load(&conn).unwrap(), settings);
+        assert!(DigestSettings { window_secs: 0, ..settings }.save(&conn).is_err());
+    }
+}
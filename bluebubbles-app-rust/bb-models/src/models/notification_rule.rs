@@ -0,0 +1,372 @@
+//! Notification rule entity model: ordered rules deciding how an incoming
+//! message notifies, and the quiet hours that hold notifications back.
+
+use std::fmt;
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use rusqlite::{params, Connection, Row};
+use bb_core::error::{BbError, BbResult};
+use tracing::warn;
+
+use super::settings::Settings;
+
+/// Settings key holding the quiet hours as JSON.
+const QUIET_HOURS_KEY: &str = "notificationQuietHours";
+
+/// A condition on an incoming message. A rule matches when all of its
+/// conditions do.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// Messages in a chat.
+    Chat { guid: String },
+    /// Messages from a handle address. Phone numbers are compared in E.164.
+    Sender { address: String },
+    /// Messages from any handle of a person.
+    Person { id: i64 },
+    /// Message text matching a regular expression.
+    TextMatches { pattern: String },
+    /// Messages with an attachment whose MIME type starts with `mime`
+    /// (`image/`, `application/pdf`); `*` matches any attachment.
+    Attachment { mime: String },
+    /// Messages that @mention the user.
+    MentionsMe,
+}
+
+impl fmt::Display for RuleCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chat { guid } => write!(f, "chat={guid}"),
+            Self::Sender { address } => write!(f, "from={address}"),
+            Self::Person { id } => write!(f, "person={id}"),
+            Self::TextMatches { pattern } => write!(f, "text~/{pattern}/"),
+            Self::Attachment { mime } => write!(f, "attachment={mime}"),
+            Self::MentionsMe => write!(f, "mentions-me"),
+        }
+    }
+}
+
+/// What a matching rule does with the notification.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Show a notification.
+    Notify,
+    /// Show nothing.
+    Silence,
+    /// Show an urgent notification, even during quiet hours.
+    Priority,
+    /// Fold the message into a periodic summary for its chat.
+    Digest,
+}
+
+impl RuleAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Notify => "notify",
+            Self::Silence => "silence",
+            Self::Priority => "priority",
+            Self::Digest => "digest",
+        }
+    }
+
+    /// Parse an action name; `notify-with-priority` is accepted for `priority`.
+    pub fn parse(input: &str) -> BbResult<Self> {
+        match input.trim().to_lowercase().replace('_', "-").as_str() {
+            "notify" => Ok(Self::Notify),
+            "silence" | "silent" | "mute" => Ok(Self::Silence),
+            "priority" | "notify-with-priority" => Ok(Self::Priority),
+            "digest" => Ok(Self::Digest),
+            other => Err(BbError::InvalidInput(format!(
+                "unknown notification action: {other} (expected notify, silence, priority or digest)"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A notification rule. Rules are tried in `sort_index` order and the
+/// first enabled rule whose conditions all match decides the action.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotificationRule {
+    pub id: Option<i64>,
+    pub name: String,
+    pub conditions: Vec<RuleCondition>,
+    pub action: RuleAction,
+    pub enabled: bool,
+    pub sort_index: i64,
+    pub date_created: String,
+}
+
+impl NotificationRule {
+    /// Create an unsaved, enabled rule.
+    pub fn new(name: &str, conditions: Vec<RuleCondition>, action: RuleAction) -> Self {
+        Self {
+            id: None,
+            name: name.trim().to_string(),
+            conditions,
+            action,
+            enabled: true,
+            sort_index: 0,
+            date_created: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Construct a NotificationRule from a database row. Fails when the
+    /// conditions can't be read: read as no conditions, the rule would
+    /// match every message.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let conditions: String = row.get("conditions")?;
+        let action: String = row.get("action")?;
+        Ok(Self {
+            id: Some(row.get("id")?),
+            name: row.get("name")?,
+            conditions: serde_json::from_str(&conditions).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            action: RuleAction::parse(&action).unwrap_or(RuleAction::Notify),
+            enabled: row.get("enabled")?,
+            sort_index: row.get("sort_index")?,
+            date_created: row.get("date_created")?,
+        })
+    }
+
+    /// Insert or update this rule. New rules go to the end of the order.
+    /// Returns the row ID.
+    pub fn save(&mut self, conn: &Connection) -> BbResult<i64> {
+        if self.name.trim().is_empty() {
+            return Err(BbError::InvalidInput("rule name cannot be empty".into()));
+        }
+        if let Some(existing) = Self::find_by_name(conn, &self.name)? {
+            if existing.id != self.id {
+                return Err(BbError::InvalidInput(format!("a rule named {} already exists", self.name)));
+            }
+        }
+        let conditions = serde_json::to_string(&self.conditions)
+            .map_err(|e| BbError::Serialization(e.to_string()))?;
+
+        let id = match self.id {
+            Some(id) => {
+                conn.execute(
+                    "UPDATE notification_rules SET name = ?1, conditions = ?2, action = ?3, enabled = ?4,
+                     sort_index = ?5 WHERE id = ?6",
+                    params![self.name, conditions, self.action.as_str(), self.enabled, self.sort_index, id],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+                id
+            }
+            None => {
+                self.sort_index = conn
+                    .query_row("SELECT COALESCE(MAX(sort_index) + 1, 0) FROM notification_rules", [], |row| row.get(0))
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                conn.execute(
+                    "INSERT INTO notification_rules (name, conditions, action, enabled, sort_index, date_created)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![self.name, conditions, self.action.as_str(), self.enabled, self.sort_index, self.date_created],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+                conn.last_insert_rowid()
+            }
+        };
+        self.id = Some(id);
+        Ok(id)
+    }
+
+    /// Find a rule by ID.
+    pub fn find_by_id(conn: &Connection, id: i64) -> BbResult<Option<Self>> {
+        match conn.query_row("SELECT * FROM notification_rules WHERE id = ?1", [id], Self::from_row) {
+            Ok(r) => Ok(Some(r)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(BbError::Database(e.to_string())),
+        }
+    }
+
+    /// Find a rule by name, ignoring case.
+    pub fn find_by_name(conn: &Connection, name: &str) -> BbResult<Option<Self>> {
+        match conn.query_row(
+            "SELECT * FROM notification_rules WHERE name = ?1 COLLATE NOCASE",
+            [name.trim()],
+            Self::from_row,
+        ) {
+            Ok(r) => Ok(Some(r)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(BbError::Database(e.to_string())),
+        }
+    }
+
+    /// Load all rules in evaluation order. Rules whose conditions can't be
+    /// read are skipped.
+    pub fn list(conn: &Connection) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM notification_rules ORDER BY sort_index, id")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let rules = stmt
+            .query_map([], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.map_err(|e| warn!("skipping unreadable notification rule: {e}")).ok())
+            .collect();
+
+        Ok(rules)
+    }
+
+    /// Delete a rule. Returns whether it existed.
+    pub fn delete(conn: &Connection, id: i64) -> BbResult<bool> {
+        let deleted = conn
+            .execute("DELETE FROM notification_rules WHERE id = ?1", [id])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(deleted > 0)
+    }
+}
+
+/// A daily window, in local time, during which only priority notifications
+/// and breakthrough senders are shown. The window may wrap past midnight
+/// (`22:00`–`07:00`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct QuietHours {
+    pub enabled: bool,
+    /// Start time, `HH:MM`.
+    pub start: String,
+    /// End time, `HH:MM`.
+    pub end: String,
+    /// Handle addresses that notify even during quiet hours.
+    #[serde(default)]
+    pub breakthrough: Vec<String>,
+}
+
+impl QuietHours {
+    /// Enabled quiet hours from `start` to `end` (`HH:MM`).
+    pub fn new(start: &str, end: &str) -> BbResult<Self> {
+        let quiet = Self {
+            enabled: true,
+            start: start.trim().to_string(),
+            end: end.trim().to_string(),
+            breakthrough: Vec::new(),
+        };
+        quiet.window()?;
+        Ok(quiet)
+    }
+
+    fn window(&self) -> BbResult<(NaiveTime, NaiveTime)> {
+        let parse = |s: &str| {
+            NaiveTime::parse_from_str(s.trim(), "%H:%M")
+                .map_err(|_| BbError::InvalidInput(format!("invalid time {s:?}, expected HH:MM")))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    /// Whether `time` falls in the quiet window. Disabled or malformed
+    /// quiet hours are never active.
+    pub fn is_active(&self, time: NaiveTime) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match self.window() {
+            Ok((start, end)) if start <= end => time >= start && time < end,
+            Ok((start, end)) => time >= start || time < end,
+            Err(_) => false,
+        }
+    }
+
+    /// Load the saved quiet hours (disabled if never set).
+    pub fn load(conn: &Connection) -> BbResult<Self> {
+        Ok(Settings::get_json(conn, QUIET_HOURS_KEY)?.unwrap_or_default())
+    }
+
+    /// Save these quiet hours.
+    pub fn save(&self, conn: &Connection) -> BbResult<()> {
+        if self.enabled {
+            self.window()?;
+        }
+        Settings::set_json(conn, QUIET_HOURS_KEY, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    #[test]
+    fn test_rules_keep_order_and_round_trip() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+
+        let mut family = NotificationRule::new(
+            "Family",
+            vec![RuleCondition::Chat { guid: "iMessage;+;family".into() }],
+            RuleAction::Digest,
+        );
+        let mut boss = NotificationRule::new(
+            "Boss",
+            vec![RuleCondition::Sender { address: "+15551234567".into() }, RuleCondition::MentionsMe],
+            RuleAction::Priority,
+        );
+        family.save(&conn).unwrap();
+        boss.save(&conn).unwrap();
+        assert_eq!((family.sort_index, boss.sort_index), (0, 1));
+
+        let mut duplicate = NotificationRule::new("family", vec![], RuleAction::Notify);
+        assert!(duplicate.save(&conn).is_err());
+
+        let rules = NotificationRule::list(&conn).unwrap();
+        assert_eq!(rules, vec![family.clone(), boss.clone()]);
+        assert_eq!(rules[1].conditions[1].to_string(), "mentions-me");
+
+        assert!(NotificationRule::delete(&conn, family.id.unwrap()).unwrap());
+        assert_eq!(NotificationRule::list(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_unreadable_rule_is_skipped() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+
+        let mut rule = NotificationRule::new("Quiet", vec![RuleCondition::MentionsMe], RuleAction::Silence);
+        rule.save(&conn).unwrap();
+        conn.execute(
+            "UPDATE notification_rules SET conditions = '[{\"type\":\"from_the_future\"}]' WHERE id = ?1",
+            [rule.id.unwrap()],
+        )
+        .unwrap();
+
+        assert!(NotificationRule::list(&conn).unwrap().is_empty());
+        assert!(NotificationRule::find_by_id(&conn, rule.id.unwrap()).is_err());
+        assert!(NotificationRule::delete(&conn, rule.id.unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_action_parse() {
+        assert_eq!(RuleAction::parse("notify-with-priority").unwrap(), RuleAction::Priority);
+        assert_eq!(RuleAction::parse("Digest").unwrap(), RuleAction::Digest);
+        assert!(RuleAction::parse("shout").is_err());
+    }
+
+    #[test]
+    fn test_quiet_hours_window() {
+        let at = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        let night = QuietHours::new("22:00", "07:00").unwrap();
+        assert!(night.is_active(at("23:30")));
+        assert!(night.is_active(at("06:59")));
+        assert!(!night.is_active(at("07:00")));
+        assert!(!night.is_active(at("12:00")));
+
+        let lunch = QuietHours::new("12:00", "13:00").unwrap();
+        assert!(lunch.is_active(at("12:30")));
+        assert!(!lunch.is_active(at("13:30")));
+        assert!(QuietHours::new("25:00", "07:00").is_err());
+        assert!(!QuietHours::default().is_active(at("23:00")));
+
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        assert_eq!(QuietHours::load(&conn).unwrap(), QuietHours::default());
+        night.save(&conn).unwrap();
+        assert_eq!(QuietHours::load(&conn).unwrap(), night);
+    }
+}
@@ -0,0 +1,63 @@
+//! One-time code detection settings: user-defined patterns and the senders
+//! codes are accepted from.
+//!
+//! Whether detection runs at all is the separate `otpDetectionEnabled`
+//! setting, kept as is for existing installs.
+
+use serde::{Deserialize, Serialize};
+use rusqlite::Connection;
+use bb_core::error::{BbError, BbResult};
+
+use super::settings::Settings;
+
+/// Settings key holding the OTP settings as JSON.
+const OTP_SETTINGS_KEY: &str = "otpSettings";
+
+/// How one-time codes are recognized.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OtpSettings {
+    /// Extra regular expressions tried before the built-in patterns. The
+    /// first capture group (or the whole match) is the code.
+    #[serde(default)]
+    pub custom_patterns: Vec<String>,
+    /// Only detect codes from these senders (addresses, short codes or
+    /// contact names). Empty accepts every sender.
+    #[serde(default)]
+    pub allowed_senders: Vec<String>,
+    /// How long a code stays valid when the message doesn't say.
+    #[serde(default = "default_expiry_secs")]
+    pub default_expiry_secs: u64,
+}
+
+fn default_expiry_secs() -> u64 {
+    600
+}
+
+impl Default for OtpSettings {
+    fn default() -> Self {
+        Self {
+            custom_patterns: Vec::new(),
+            allowed_senders: Vec::new(),
+            default_expiry_secs: default_expiry_secs(),
+        }
+    }
+}
+
+impl OtpSettings {
+    /// Load the saved OTP settings (defaults if never set).
+    pub fn load(conn: &Connection) -> BbResult<Self> {
+        Ok(Settings::get_json(conn, OTP_SETTINGS_KEY)?.unwrap_or_default())
+    }
+
+    /// Save these OTP settings. Patterns are not compiled here; callers
+    /// validate them first.
+    pub fn save(&self, conn: &Connection) -> BbResult<()> {
+        if self.default_expiry_secs == 0 {
+            return Err(BbError::InvalidInput("code expiry must be at least one second".into()));
+        }
+        if self.custom_patterns.iter().any(|p| p.trim().is_empty()) {
+            return Err(BbError::InvalidInput("custom patterns cannot be empty".into()));
+        }
+        Settings::set_json(conn, OTP_SETTINGS_KEY, self)
+    }
+}
@@ -0,0 +1,387 @@
+//! Outbox entity model: chat mutations waiting to be sent to the server.
+//!
+//! A mutation is applied to the local database when it is queued, so the
+//! UI reflects it immediately. The local state it replaced is kept in
+//! `previous` so the change can be rolled back if the server rejects it.
+
+use serde::{Deserialize, Serialize};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use bb_core::error::{BbError, BbResult};
+
+/// A server-side change to a chat.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatMutation {
+    MarkRead,
+    MarkUnread,
+    Rename { name: String },
+    AddParticipant { address: String },
+    RemoveParticipant { address: String },
+    Delete,
+}
+
+impl ChatMutation {
+    /// Short human-readable description, for logs and failure notices.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::MarkRead => "mark as read".into(),
+            Self::MarkUnread => "mark as unread".into(),
+            Self::Rename { name } => format!("rename to \"{name}\""),
+            Self::AddParticipant { address } => format!("add {address}"),
+            Self::RemoveParticipant { address } => format!("remove {address}"),
+            Self::Delete => "delete".into(),
+        }
+    }
+
+    /// Whether two mutations change the same piece of chat state in the
+    /// same way, so a later one overwrites what an earlier one applied and
+    /// can take over its saved `previous` state.
+    pub fn same_target(&self, other: &Self) -> bool {
+        use ChatMutation::*;
+        match (self, other) {
+            (MarkRead | MarkUnread, MarkRead | MarkUnread)
+            | (Rename { .. }, Rename { .. })
+            | (Delete, Delete) => true,
+            (AddParticipant { address: a }, AddParticipant { address: b })
+            | (RemoveParticipant { address: a }, RemoveParticipant { address: b }) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Apply the mutation to the local database. Returns the replaced
+    /// state needed by [`ChatMutation::rollback`].
+    pub fn apply(&self, conn: &Connection, chat_guid: &str) -> BbResult<Option<String>> {
+        let chat_id = chat_id(conn, chat_guid)?;
+        let previous = match self {
+            Self::MarkRead | Self::MarkUnread => {
+                let unread: bool = conn
+                    .query_row("SELECT has_unread_message FROM chats WHERE id = ?1", [chat_id], |r| r.get(0))
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                exec(
+                    conn,
+                    "UPDATE chats SET has_unread_message = ?1 WHERE id = ?2",
+                    params![matches!(self, Self::MarkUnread), chat_id],
+                )?;
+                Some(unread.to_string())
+            }
+            Self::Rename { name } => {
+                let old: Option<String> = conn
+                    .query_row("SELECT display_name FROM chats WHERE id = ?1", [chat_id], |r| r.get(0))
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                exec(conn, "UPDATE chats SET display_name = ?1 WHERE id = ?2", params![name, chat_id])?;
+                old
+            }
+            Self::AddParticipant { address } => {
+                let unique = format!("{address}/iMessage");
+                exec(
+                    conn,
+                    "INSERT OR IGNORE INTO handles (address, service, unique_address_service) VALUES (?1, 'iMessage', ?2)",
+                    params![address, unique],
+                )?;
+                let joined = exec(
+                    conn,
+                    "INSERT OR IGNORE INTO chat_handle_join (chat_id, handle_id)
+                     SELECT ?1, id FROM handles WHERE address = ?2 ORDER BY id LIMIT 1",
+                    params![chat_id, address],
+                )?;
+                Some((joined > 0).to_string())
+            }
+            Self::RemoveParticipant { address } => {
+                let handle_ids = participant_handle_ids(conn, chat_id, address)?;
+                exec(
+                    conn,
+                    "DELETE FROM chat_handle_join WHERE chat_id = ?1
+                     AND handle_id IN (SELECT id FROM handles WHERE address = ?2)",
+                    params![chat_id, address],
+                )?;
+                Some(serde_json::to_string(&handle_ids)?)
+            }
+            Self::Delete => {
+                let old: Option<String> = conn
+                    .query_row("SELECT date_deleted FROM chats WHERE id = ?1", [chat_id], |r| r.get(0))
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                let now = chrono::Utc::now().to_rfc3339();
+                exec(conn, "UPDATE chats SET date_deleted = ?1 WHERE id = ?2", params![now, chat_id])?;
+                old
+            }
+        };
+        Ok(previous)
+    }
+
+    /// Restore the local state replaced by [`ChatMutation::apply`].
+    pub fn rollback(&self, conn: &Connection, chat_guid: &str, previous: Option<&str>) -> BbResult<()> {
+        let chat_id = chat_id(conn, chat_guid)?;
+        match self {
+            Self::MarkRead | Self::MarkUnread => {
+                let unread = previous == Some("true");
+                exec(conn, "UPDATE chats SET has_unread_message = ?1 WHERE id = ?2", params![unread, chat_id])?;
+            }
+            Self::Rename { .. } => {
+                exec(conn, "UPDATE chats SET display_name = ?1 WHERE id = ?2", params![previous, chat_id])?;
+            }
+            Self::AddParticipant { address } => {
+                if previous == Some("true") {
+                    exec(
+                        conn,
+                        "DELETE FROM chat_handle_join WHERE chat_id = ?1
+                         AND handle_id IN (SELECT id FROM handles WHERE address = ?2)",
+                        params![chat_id, address],
+                    )?;
+                }
+            }
+            Self::RemoveParticipant { .. } => {
+                let handle_ids: Vec<i64> = match previous {
+                    Some(json) => serde_json::from_str(json)?,
+                    None => Vec::new(),
+                };
+                for handle_id in handle_ids {
+                    exec(
+                        conn,
+                        "INSERT OR IGNORE INTO chat_handle_join (chat_id, handle_id) VALUES (?1, ?2)",
+                        params![chat_id, handle_id],
+                    )?;
+                }
+            }
+            Self::Delete => {
+                exec(conn, "UPDATE chats SET date_deleted = ?1 WHERE id = ?2", params![previous, chat_id])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A queued chat mutation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutboxEntry {
+    pub id: Option<i64>,
+    pub chat_guid: String,
+    pub mutation: ChatMutation,
+    /// Local state replaced when the mutation was applied.
+    pub previous: Option<String>,
+    /// Failed send attempts so far.
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub date_created: String,
+}
+
+impl OutboxEntry {
+    /// Construct an OutboxEntry from a database row.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let mutation: String = row.get("mutation")?;
+        Ok(Self {
+            id: row.get("id")?,
+            chat_guid: row.get("chat_guid")?,
+            mutation: serde_json::from_str(&mutation).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            previous: row.get("previous")?,
+            attempts: row.get("attempts")?,
+            last_error: row.get("last_error")?,
+            date_created: row.get("date_created")?,
+        })
+    }
+
+    /// Apply a mutation locally and queue it, atomically. Returns the
+    /// saved entry.
+    pub fn enqueue(conn: &mut Connection, chat_guid: &str, mutation: ChatMutation) -> BbResult<Self> {
+        let tx = conn.transaction().map_err(|e| BbError::Database(e.to_string()))?;
+        let previous = mutation.apply(&tx, chat_guid)?;
+        let mut entry = Self {
+            id: None,
+            chat_guid: chat_guid.to_string(),
+            mutation,
+            previous,
+            attempts: 0,
+            last_error: None,
+            date_created: chrono::Utc::now().to_rfc3339(),
+        };
+        exec(
+            &tx,
+            "INSERT INTO outbox (chat_guid, mutation, previous, attempts, date_created)
+             VALUES (?1, ?2, ?3, 0, ?4)",
+            params![
+                entry.chat_guid,
+                serde_json::to_string(&entry.mutation)?,
+                entry.previous,
+                entry.date_created
+            ],
+        )?;
+        entry.id = Some(tx.last_insert_rowid());
+        tx.commit().map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(entry)
+    }
+
+    /// All pending entries, oldest first.
+    pub fn pending(conn: &Connection) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM outbox ORDER BY id")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        let entries = stmt
+            .query_map([], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(entries)
+    }
+
+    /// Number of pending entries.
+    pub fn count(conn: &Connection) -> BbResult<i64> {
+        conn.query_row("SELECT COUNT(*) FROM outbox", [], |r| r.get(0))
+            .map_err(|e| BbError::Database(e.to_string()))
+    }
+
+    /// Remove an entry once the server accepted it.
+    pub fn complete(conn: &Connection, id: i64) -> BbResult<()> {
+        exec(conn, "DELETE FROM outbox WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Record a failed attempt that will be retried.
+    pub fn record_failure(conn: &Connection, id: i64, error: &str) -> BbResult<()> {
+        exec(
+            conn,
+            "UPDATE outbox SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+            params![error, id],
+        )?;
+        Ok(())
+    }
+
+    /// Undo the entry's local change and drop it, atomically.
+    ///
+    /// When a later queued mutation has since overwritten the same state,
+    /// the local state is left alone and the later entry inherits this
+    /// entry's `previous`, so rolling it back restores the original.
+    pub fn roll_back(&self, conn: &mut Connection) -> BbResult<()> {
+        let tx = conn.transaction().map_err(|e| BbError::Database(e.to_string()))?;
+        let later = Self::pending(&tx)?.into_iter().find(|e| {
+            e.id > self.id && e.chat_guid == self.chat_guid && e.mutation.same_target(&self.mutation)
+        });
+        match later {
+            Some(later) => {
+                exec(
+                    &tx,
+                    "UPDATE outbox SET previous = ?1 WHERE id = ?2",
+                    params![self.previous, later.id],
+                )?;
+            }
+            // A chat purged since has nothing left to restore
+            None => match self.mutation.rollback(&tx, &self.chat_guid, self.previous.as_deref()) {
+                Ok(()) | Err(BbError::ChatNotFound(_)) => {}
+                Err(e) => return Err(e),
+            },
+        }
+        exec(&tx, "DELETE FROM outbox WHERE id = ?1", params![self.id])?;
+        tx.commit().map_err(|e| BbError::Database(e.to_string()))
+    }
+}
+
+fn chat_id(conn: &Connection, guid: &str) -> BbResult<i64> {
+    conn.query_row("SELECT id FROM chats WHERE guid = ?1", [guid], |r| r.get(0))
+        .optional()
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .ok_or_else(|| BbError::ChatNotFound(guid.to_string()))
+}
+
+fn participant_handle_ids(conn: &Connection, chat_id: i64, address: &str) -> BbResult<Vec<i64>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT j.handle_id FROM chat_handle_join j
+             INNER JOIN handles h ON h.id = j.handle_id
+             WHERE j.chat_id = ?1 AND h.address = ?2",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let ids = stmt
+        .query_map(params![chat_id, address], |r| r.get(0))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(ids)
+}
+
+fn exec(conn: &Connection, sql: &str, params: impl rusqlite::Params) -> BbResult<usize> {
+    conn.execute(sql, params).map_err(|e| BbError::Database(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO chats (id, guid, display_name, has_unread_message) VALUES (1, 'chat-1', 'Old', 1);
+             INSERT INTO handles (id, address, unique_address_service) VALUES (1, 'a@example.com', 'a/iMessage');
+             INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn chat_state(conn: &Connection) -> (bool, Option<String>, Option<String>, i64) {
+        conn.query_row(
+            "SELECT has_unread_message, display_name, date_deleted,
+                    (SELECT COUNT(*) FROM chat_handle_join WHERE chat_id = 1)
+             FROM chats WHERE id = 1",
+            [],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mutations_apply_and_roll_back() {
+        let mut conn = setup();
+        let before = chat_state(&conn);
+
+        let mutations = vec![
+            ChatMutation::MarkRead,
+            ChatMutation::Rename { name: "New".into() },
+            ChatMutation::AddParticipant { address: "b@example.com".into() },
+            ChatMutation::RemoveParticipant { address: "a@example.com".into() },
+            ChatMutation::Delete,
+        ];
+        let mut entries = Vec::new();
+        for m in mutations {
+            entries.push(OutboxEntry::enqueue(&mut conn, "chat-1", m).unwrap());
+        }
+        let (unread, name, deleted, members) = chat_state(&conn);
+        assert!(!unread);
+        assert_eq!(name.as_deref(), Some("New"));
+        assert!(deleted.is_some());
+        assert_eq!(members, 1);
+
+        let pending = OutboxEntry::pending(&conn).unwrap();
+        assert_eq!(pending, entries);
+
+        // Roll back newest first, as replay would on a failed chain
+        for entry in entries.iter().rev() {
+            entry.roll_back(&mut conn).unwrap();
+        }
+        assert_eq!(chat_state(&conn), before);
+        assert_eq!(OutboxEntry::count(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_rolling_back_overwritten_mutation_keeps_later_state() {
+        let mut conn = setup();
+        let first = OutboxEntry::enqueue(&mut conn, "chat-1", ChatMutation::Rename { name: "A".into() }).unwrap();
+        let second = OutboxEntry::enqueue(&mut conn, "chat-1", ChatMutation::Rename { name: "B".into() }).unwrap();
+
+        first.roll_back(&mut conn).unwrap();
+        assert_eq!(chat_state(&conn).1.as_deref(), Some("B"));
+
+        let second = OutboxEntry::pending(&conn).unwrap().into_iter().find(|e| e.id == second.id).unwrap();
+        assert_eq!(second.previous.as_deref(), Some("Old"));
+        second.roll_back(&mut conn).unwrap();
+        assert_eq!(chat_state(&conn).1.as_deref(), Some("Old"));
+    }
+
+    #[test]
+    fn test_enqueue_unknown_chat_fails_without_queueing() {
+        let mut conn = setup();
+        assert!(OutboxEntry::enqueue(&mut conn, "missing", ChatMutation::MarkRead).is_err());
+        assert_eq!(OutboxEntry::count(&conn).unwrap(), 0);
+    }
+}
@@ -18,7 +18,7 @@ pub struct PayloadData {
 }
 
 /// URL preview metadata extracted from a link in a message.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct UrlPreviewData {
     /// The original URL.
     pub url: Option<String>,
@@ -36,6 +36,28 @@ pub struct UrlPreviewData {
     pub image: Option<String>,
 }
 
+impl UrlPreviewData {
+    /// The server JSON representation, as read by `PayloadData::from_server_json`.
+    pub fn to_server_json(&self) -> serde_json::Value {
+        let mut json = serde_json::Map::new();
+        let fields = [
+            ("url", &self.url),
+            ("originalURL", &self.original_url),
+            ("title", &self.title),
+            ("summary", &self.summary),
+            ("siteName", &self.site_name),
+            ("icon", &self.icon),
+            ("image", &self.image),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                json.insert(key.to_string(), serde_json::Value::String(value.clone()));
+            }
+        }
+        serde_json::Value::Object(json)
+    }
+}
+
 /// iMessage app-specific payload data.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppData {
@@ -147,6 +169,23 @@ mod tests {
         assert_eq!(url.title.as_deref(), Some("Example"));
     }
 
+    #[test]
+    fn test_url_preview_round_trip() {
+        let preview = UrlPreviewData {
+            url: Some("https://example.com/post".into()),
+            original_url: Some("https://exam.pl/p".into()),
+            title: Some("A post".into()),
+            site_name: Some("Example".into()),
+            icon: Some("https://example.com/favicon.ico".into()),
+            ..Default::default()
+        };
+        let json = preview.to_server_json();
+        assert!(json.get("summary").is_none());
+        let parsed = PayloadData::from_server_json(&json).unwrap();
+        assert!(!parsed.has_app_data());
+        assert_eq!(parsed.url_data.unwrap(), preview);
+    }
+
     #[test]
     fn test_app_data_parsing() {
         let json = serde_json::json!({
@@ -0,0 +1,440 @@
+//! Person entity model: one human behind one or more handles.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use rusqlite::{params, Connection, Row};
+use bb_core::error::{BbError, BbResult};
+
+use super::handle::Handle;
+
+/// A person, grouping the handles one human messages from.
+///
+/// Handles are linked automatically when they share a contact or an address
+/// (the same number on iMessage and SMS), and manually through merge and
+/// split. Manual links are never changed by automatic linking.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Person {
+    pub id: Option<i64>,
+    /// Name set by the user, overriding the contact or handle name.
+    pub name: Option<String>,
+    pub date_created: String,
+}
+
+impl Person {
+    /// Create an unsaved person.
+    pub fn new(name: Option<String>) -> Self {
+        Self {
+            id: None,
+            name,
+            date_created: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Construct a Person from a database row.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: Some(row.get("id")?),
+            name: row.get("name")?,
+            date_created: row.get("date_created")?,
+        })
+    }
+
+    /// Insert or update this person. Returns the row ID.
+    pub fn save(&mut self, conn: &Connection) -> BbResult<i64> {
+        let id = match self.id {
+            Some(id) => {
+                conn.execute("UPDATE persons SET name = ?1 WHERE id = ?2", params![self.name, id])
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                id
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO persons (name, date_created) VALUES (?1, ?2)",
+                    params![self.name, self.date_created],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+                conn.last_insert_rowid()
+            }
+        };
+        self.id = Some(id);
+        Ok(id)
+    }
+
+    /// Find a person by ID.
+    pub fn find_by_id(conn: &Connection, id: i64) -> BbResult<Option<Self>> {
+        match conn.query_row("SELECT * FROM persons WHERE id = ?1", [id], Self::from_row) {
+            Ok(p) => Ok(Some(p)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(BbError::Database(e.to_string())),
+        }
+    }
+
+    /// Find the person a handle belongs to.
+    pub fn find_by_handle(conn: &Connection, handle_id: i64) -> BbResult<Option<Self>> {
+        match conn.query_row(
+            "SELECT p.* FROM persons p
+             INNER JOIN person_handles ph ON ph.person_id = p.id
+             WHERE ph.handle_id = ?1",
+            [handle_id],
+            Self::from_row,
+        ) {
+            Ok(p) => Ok(Some(p)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(BbError::Database(e.to_string())),
+        }
+    }
+
+    /// Load all persons.
+    pub fn list(conn: &Connection) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM persons ORDER BY id")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let persons = stmt
+            .query_map([], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(persons)
+    }
+
+    /// Load the handles linked to a person.
+    pub fn handles(conn: &Connection, person_id: i64) -> BbResult<Vec<Handle>> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT h.* FROM handles h
+                 INNER JOIN person_handles ph ON ph.handle_id = h.id
+                 WHERE ph.person_id = ?1
+                 ORDER BY h.id",
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let handles = stmt
+            .query_map([person_id], Handle::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(handles)
+    }
+
+    /// Move every handle of `source` to `target` and delete `source`.
+    ///
+    /// The merged links are marked manual. `target` keeps its name, or takes
+    /// `source`'s when it has none.
+    pub fn merge(conn: &Connection, target: i64, source: i64) -> BbResult<()> {
+        if target == source {
+            return Err(BbError::InvalidInput("cannot merge a person into itself".into()));
+        }
+        let (Some(mut target_person), Some(source_person)) =
+            (Self::find_by_id(conn, target)?, Self::find_by_id(conn, source)?)
+        else {
+            return Err(BbError::InvalidInput("person not found".into()));
+        };
+
+        conn.execute(
+            "UPDATE person_handles SET person_id = ?1, manual = 1 WHERE person_id IN (?1, ?2)",
+            params![target, source],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+        conn.execute("DELETE FROM persons WHERE id = ?1", [source])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        if target_person.name.is_none() && source_person.name.is_some() {
+            target_person.name = source_person.name;
+            target_person.save(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Detach a handle into a new person of its own. Both the handle and
+    /// the handles it leaves behind are pinned as manual links, so relinking
+    /// doesn't put them back together. Returns the new person.
+    pub fn split(conn: &Connection, handle_id: i64) -> BbResult<Self> {
+        if Handle::find_by_id(conn, handle_id)?.is_none() {
+            return Err(BbError::InvalidInput(format!("handle {handle_id} not found")));
+        }
+        let previous = Self::find_by_handle(conn, handle_id)?.and_then(|p| p.id);
+        let mut person = Self::new(None);
+        let id = person.save(conn)?;
+        set_link(conn, handle_id, id, true)?;
+        if let Some(previous) = previous {
+            conn.execute("UPDATE person_handles SET manual = 1 WHERE person_id = ?1", [previous])
+                .map_err(|e| BbError::Database(e.to_string()))?;
+        }
+        delete_empty(conn)?;
+        Ok(person)
+    }
+}
+
+/// Link handles to persons automatically.
+///
+/// Handles are grouped transitively: two handles land in one group when a
+/// chain of shared keys (contact, or address key: E.164 number or lowercased
+/// email) connects them. Handles with a manual link never move; a group that
+/// contains some joins the person of the first of them. Other groups keep
+/// the person most of their handles already have, or get a new one. Returns
+/// the number of links created or changed.
+pub fn link_persons(conn: &Connection) -> BbResult<u32> {
+    let handles = crate::queries::list_handles(conn)?;
+
+    let mut links: HashMap<i64, (i64, bool)> = HashMap::new();
+    {
+        let mut stmt = conn
+            .prepare("SELECT handle_id, person_id, manual FROM person_handles")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, (row.get::<_, i64>(1)?, row.get::<_, i32>(2)? != 0)))
+            })
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        for (handle_id, link) in rows.flatten() {
+            links.insert(handle_id, link);
+        }
+    }
+
+    // Union handles that share a contact or an address key
+    let ids: Vec<i64> = handles.iter().filter_map(|h| h.id).collect();
+    let mut groups = UnionFind::new(ids.len());
+    let mut first_with_key: HashMap<String, usize> = HashMap::new();
+    for (index, handle) in handles.iter().filter(|h| h.id.is_some()).enumerate() {
+        let mut handle_keys = vec![address_key(handle)];
+        if let Some(contact_id) = handle.contact_id {
+            handle_keys.push(format!("contact:{contact_id}"));
+        }
+        for key in handle_keys {
+            match first_with_key.get(&key) {
+                Some(&other) => groups.union(index, other),
+                None => {
+                    first_with_key.insert(key, index);
+                }
+            }
+        }
+    }
+
+    let mut members: BTreeMap<usize, Vec<i64>> = BTreeMap::new();
+    for (index, id) in ids.iter().enumerate() {
+        members.entry(groups.find(index)).or_default().push(*id);
+    }
+
+    // Persons holding manual links belong to those links, not to a group
+    let manual_persons: BTreeSet<i64> =
+        links.values().filter(|(_, manual)| *manual).map(|(p, _)| *p).collect();
+    let mut used: BTreeSet<i64> = BTreeSet::new();
+
+    let mut changed = 0u32;
+    for mut group in members.into_values() {
+        group.sort_unstable();
+        let pinned = group
+            .iter()
+            .find_map(|id| links.get(id).filter(|(_, manual)| *manual).map(|(p, _)| *p));
+
+        let target = match pinned {
+            Some(person) => person,
+            None => {
+                // The person most of the group already has, if still free
+                let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+                for id in &group {
+                    if let Some((person, _)) = links.get(id) {
+                        *counts.entry(*person).or_default() += 1;
+                    }
+                }
+                let mut candidates: Vec<(i64, usize)> = counts
+                    .into_iter()
+                    .filter(|(p, _)| !manual_persons.contains(p) && !used.contains(p))
+                    .collect();
+                candidates.sort_by_key(|(p, n)| (std::cmp::Reverse(*n), *p));
+                match candidates.first() {
+                    Some(&(person, _)) => person,
+                    None => Person::new(None).save(conn)?,
+                }
+            }
+        };
+        used.insert(target);
+
+        for id in &group {
+            match links.get(id) {
+                Some((_, true)) => continue,
+                Some((person, false)) if *person == target => continue,
+                _ => {}
+            }
+            set_link(conn, *id, target, false)?;
+            links.insert(*id, (target, false));
+            changed += 1;
+        }
+    }
+
+    delete_empty(conn)?;
+    Ok(changed)
+}
+
+/// Disjoint sets over indices, with path halving and union by size.
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+/// Map of handle ID to person ID for every linked handle.
+pub fn person_ids_by_handle(conn: &Connection) -> BbResult<HashMap<i64, i64>> {
+    let mut stmt = conn
+        .prepare("SELECT handle_id, person_id FROM person_handles")
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn address_key(handle: &Handle) -> String {
+    match &handle.normalized_address {
+        Some(n) => n.clone(),
+        None => handle.address.trim().to_lowercase(),
+    }
+}
+
+fn set_link(conn: &Connection, handle_id: i64, person_id: i64, manual: bool) -> BbResult<()> {
+    conn.execute(
+        "INSERT INTO person_handles (handle_id, person_id, manual) VALUES (?1, ?2, ?3)
+         ON CONFLICT(handle_id) DO UPDATE SET person_id = excluded.person_id, manual = excluded.manual",
+        params![handle_id, person_id, manual as i32],
+    )
+    .map_err(|e| BbError::Database(e.to_string()))?;
+    Ok(())
+}
+
+fn delete_empty(conn: &Connection) -> BbResult<()> {
+    conn.execute(
+        "DELETE FROM persons WHERE id NOT IN (SELECT person_id FROM person_handles)",
+        [],
+    )
+    .map_err(|e| BbError::Database(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    fn insert_handle(conn: &Connection, address: &str, service: &str, contact_id: Option<i64>) -> i64 {
+        let mut handle = Handle::from_server_map(&serde_json::json!({
+            "address": address, "service": service, "country": "us"
+        }))
+        .unwrap();
+        handle.contact_id = contact_id;
+        handle.save(conn).unwrap()
+    }
+
+    fn person_of(conn: &Connection, handle_id: i64) -> i64 {
+        Person::find_by_handle(conn, handle_id).unwrap().unwrap().id.unwrap()
+    }
+
+    #[test]
+    fn test_link_merge_and_split() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO contacts (id, display_name) VALUES (1, 'Alice')", []).unwrap();
+
+        let imessage = insert_handle(&conn, "+16502530000", "iMessage", None);
+        let sms = insert_handle(&conn, "(650) 253-0000", "SMS", None);
+        let email = insert_handle(&conn, "alice@example.com", "iMessage", Some(1));
+        let work = insert_handle(&conn, "alice@work.example", "iMessage", Some(1));
+        let bob = insert_handle(&conn, "bob@example.com", "iMessage", None);
+
+        link_persons(&conn).unwrap();
+        // Same number on two services, and two emails on one contact
+        assert_eq!(person_of(&conn, imessage), person_of(&conn, sms));
+        assert_eq!(person_of(&conn, email), person_of(&conn, work));
+        assert_ne!(person_of(&conn, imessage), person_of(&conn, email));
+        assert_ne!(person_of(&conn, bob), person_of(&conn, email));
+        assert_eq!(Person::list(&conn).unwrap().len(), 3);
+
+        // Relinking is stable
+        let alice = person_of(&conn, email);
+        assert_eq!(link_persons(&conn).unwrap(), 0);
+        assert_eq!(person_of(&conn, email), alice);
+
+        // Manual merge survives relinking
+        Person::merge(&conn, alice, person_of(&conn, sms)).unwrap();
+        link_persons(&conn).unwrap();
+        assert_eq!(Person::handles(&conn, alice).unwrap().len(), 4);
+
+        // Split pins a handle to a new person
+        let split = Person::split(&conn, work).unwrap();
+        link_persons(&conn).unwrap();
+        assert_eq!(person_of(&conn, work), split.id.unwrap());
+        assert_eq!(Person::handles(&conn, alice).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_split_of_linked_group_survives_relinking() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO contacts (id, display_name) VALUES (1, 'Alice')", []).unwrap();
+
+        let home = insert_handle(&conn, "alice@example.com", "iMessage", Some(1));
+        let work = insert_handle(&conn, "alice@work.example", "iMessage", Some(1));
+        link_persons(&conn).unwrap();
+        let alice = person_of(&conn, home);
+        assert_eq!(person_of(&conn, work), alice);
+
+        // Split without any merge first; the contact still joins the two
+        let split = Person::split(&conn, work).unwrap().id.unwrap();
+        assert_eq!(link_persons(&conn).unwrap(), 0);
+        assert_eq!(person_of(&conn, home), alice);
+        assert_eq!(person_of(&conn, work), split);
+        assert_eq!(Person::list(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_link_is_transitive() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO contacts (id, display_name) VALUES (1, 'Alice')", []).unwrap();
+
+        // The SMS handle only shares its number with h3, which only shares
+        // its contact with h1
+        let h1 = insert_handle(&conn, "alice@example.com", "iMessage", Some(1));
+        let h2 = insert_handle(&conn, "(650) 253-0000", "SMS", None);
+        let h3 = insert_handle(&conn, "+16502530000", "iMessage", Some(1));
+
+        link_persons(&conn).unwrap();
+        assert_eq!(person_of(&conn, h1), person_of(&conn, h2));
+        assert_eq!(person_of(&conn, h1), person_of(&conn, h3));
+        assert_eq!(Person::list(&conn).unwrap().len(), 1);
+        assert_eq!(link_persons(&conn).unwrap(), 0);
+    }
+}
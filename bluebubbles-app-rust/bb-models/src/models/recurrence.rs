@@ -0,0 +1,560 @@
+//! Recurrence rules for scheduled messages.
+//!
+//! Two notations are accepted: five-field cron expressions
+//! (`minute hour day-of-month month day-of-week`, plus `@daily` and
+//! friends) and a subset of iCalendar RRULE (`FREQ`, `INTERVAL`, `BYDAY`,
+//! `COUNT`, `UNTIL`). Occurrences are computed on wall-clock time in an
+//! IANA time zone, so a 09:00 schedule stays at 09:00 across DST changes.
+//! A wall time skipped by a spring-forward gap fires at the first minute
+//! after the gap; a wall time repeated by a fall-back fires once, at its
+//! first occurrence.
+
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc,
+    Weekday,
+};
+pub use chrono_tz::Tz;
+use bb_core::error::{BbError, BbResult};
+
+/// How many days ahead a cron search looks before giving up (covers
+/// expressions like `0 0 29 2 *` that match once every four years).
+const CRON_SEARCH_DAYS: u32 = 366 * 8;
+
+/// How many periods an RRULE is expanded before giving up.
+const RULE_SEARCH_PERIODS: u32 = 100_000;
+
+/// Parse an IANA time zone name such as `Europe/London`.
+pub fn parse_time_zone(name: &str) -> BbResult<Tz> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| BbError::InvalidInput(format!("unknown time zone: {name}")))
+}
+
+/// The system's IANA time zone, or UTC when it can't be determined.
+pub fn system_time_zone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// A parsed recurrence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    Cron(CronSchedule),
+    Rule(RecurrenceRule),
+}
+
+impl Recurrence {
+    /// Parse a cron expression or an RRULE (with or without the `RRULE:`
+    /// prefix).
+    pub fn parse(expr: &str) -> BbResult<Self> {
+        let expr = expr.trim();
+        let body = expr
+            .strip_prefix("RRULE:")
+            .or_else(|| expr.strip_prefix("rrule:"))
+            .unwrap_or(expr);
+        if body.to_ascii_uppercase().contains("FREQ=") {
+            Ok(Self::Rule(RecurrenceRule::parse(body)?))
+        } else {
+            Ok(Self::Cron(CronSchedule::parse(expr)?))
+        }
+    }
+
+    /// The first occurrence strictly after `after`.
+    ///
+    /// `start` is the first occurrence of an RRULE, whose wall-clock time
+    /// every later occurrence keeps; a cron expression never fires before it.
+    pub fn next_after(&self, start: DateTime<Utc>, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Cron(cron) => cron.next_after(tz, after.max(start - Duration::seconds(1))),
+            Self::Rule(rule) => rule.occurrences(start, tz).find(|t| *t > after),
+        }
+    }
+
+    /// Up to `count` occurrences after `after`, in order.
+    pub fn upcoming(&self, start: DateTime<Utc>, tz: Tz, after: DateTime<Utc>, count: usize) -> Vec<DateTime<Utc>> {
+        let mut out = Vec::with_capacity(count);
+        let mut cursor = after;
+        while out.len() < count {
+            match self.next_after(start, tz, cursor) {
+                Some(next) => {
+                    out.push(next);
+                    cursor = next;
+                }
+                None => break,
+            }
+        }
+        out
+    }
+
+    /// The server's schedule format for this recurrence, with the first
+    /// occurrence at or after `start`.
+    ///
+    /// The server only repeats at a fixed interval of hours, days, weeks,
+    /// months or years from the first send, so anything else (several
+    /// weekdays, ranges, counts, end dates) is rejected and must be
+    /// scheduled locally. The server repeats in its own time zone.
+    pub fn server_schedule(&self, start: DateTime<Utc>, tz: Tz) -> BbResult<(DateTime<Utc>, serde_json::Value)> {
+        let (interval, interval_type) = match self {
+            Self::Rule(rule) => rule.server_interval(start, tz)?,
+            Self::Cron(cron) => cron.server_interval()?,
+        };
+        let first = self
+            .next_after(start, tz, start - Duration::seconds(1))
+            .ok_or_else(|| BbError::InvalidInput("recurrence has no occurrences".into()))?;
+        let schedule = serde_json::json!({
+            "type": "recurring",
+            "interval": interval,
+            "intervalType": interval_type,
+        });
+        Ok((first, schedule))
+    }
+}
+
+impl std::fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cron(cron) => f.write_str(&cron.source),
+            Self::Rule(rule) => f.write_str(&rule.source),
+        }
+    }
+}
+
+/// Map a wall-clock time in `tz` to an instant, moving times in a DST gap
+/// to the end of the gap and taking the first of two repeated times.
+fn resolve_local(tz: Tz, naive: NaiveDateTime) -> DateTime<Utc> {
+    let local = match tz.from_local_datetime(&naive) {
+        LocalResult::Single(t) => Some(t),
+        LocalResult::Ambiguous(first, _) => Some(first),
+        LocalResult::None => (1..=24 * 60)
+            .find_map(|m| tz.from_local_datetime(&(naive + Duration::minutes(m))).earliest()),
+    };
+    local
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&naive))
+}
+
+// ─── Cron ────────────────────────────────────────────────────────────────────
+
+/// A five-field cron expression: minute, hour, day of month, month and day
+/// of week (0 or 7 = Sunday). Fields take `*`, values, names (`MON`,
+/// `JAN`), ranges, lists and `/` steps. As in standard cron, when both day
+/// fields are restricted a day matching either one fires; only a bare `*`
+/// leaves a day field unrestricted, so `*/2` restricts it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+    source: String,
+}
+
+const MONTH_NAMES: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> BbResult<Self> {
+        let source = expr.trim().to_string();
+        let expanded = match source.to_ascii_lowercase().as_str() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            _ => source.as_str(),
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(BbError::InvalidInput(format!(
+                "cron expression needs 5 fields (minute hour day month weekday): {source}"
+            )));
+        };
+
+        let mut weekdays = parse_cron_field(weekday, 0, 7, &WEEKDAY_NAMES, 0)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_cron_field(minute, 0, 59, &[], 0)?,
+            hours: parse_cron_field(hour, 0, 23, &[], 0)?,
+            days: parse_cron_field(day, 1, 31, &[], 0)?,
+            months: parse_cron_field(month, 1, 12, &MONTH_NAMES, 1)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+            source,
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first match strictly after `after`, on wall-clock time in `tz`.
+    pub fn next_after(&self, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut date = after.with_timezone(&tz).date_naive();
+        for _ in 0..CRON_SEARCH_DAYS {
+            if self.day_matches(date) {
+                for hour in bits(self.hours) {
+                    for minute in bits(self.minutes) {
+                        let t = resolve_local(tz, date.and_hms_opt(hour, minute, 0)?);
+                        if t > after {
+                            return Some(t);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// The server interval this expression is equivalent to, if any.
+    fn server_interval(&self) -> BbResult<(u32, &'static str)> {
+        let single = |mask: u64| mask.count_ones() == 1;
+        let all_hours = self.hours == (1 << 24) - 1;
+        let all_months = self.months == ((1 << 13) - 1) & !1;
+        let kind = match (single(self.minutes), all_hours, single(self.hours)) {
+            (true, true, _) if self.any_day && self.any_weekday && all_months => Some("hourly"),
+            (true, _, true) if all_months && self.any_day && self.any_weekday => Some("daily"),
+            (true, _, true) if all_months && self.any_day && single(self.weekdays) => Some("weekly"),
+            (true, _, true) if all_months && single(self.days) && self.any_weekday => Some("monthly"),
+            (true, _, true) if single(self.months) && single(self.days) && self.any_weekday => Some("yearly"),
+            _ => None,
+        };
+        kind.map(|k| (1, k)).ok_or_else(|| {
+            BbError::InvalidInput(format!(
+                "the server can't repeat \"{}\"; schedule it locally instead",
+                self.source
+            ))
+        })
+    }
+}
+
+/// Set bits of `mask`, lowest first.
+fn bits(mask: u64) -> impl Iterator<Item = u32> {
+    (0..64).filter(move |i| mask & (1 << i) != 0)
+}
+
+/// Parse one cron field into a bit mask of allowed values. `names` map to
+/// values starting at `name_base`.
+fn parse_cron_field(field: &str, min: u32, max: u32, names: &[&str], name_base: u32) -> BbResult<u64> {
+    let invalid = || BbError::InvalidInput(format!("invalid cron field: {field}"));
+    let value = |s: &str| -> BbResult<u32> {
+        let upper = s.to_ascii_uppercase();
+        if let Some(pos) = names.iter().position(|n| *n == upper) {
+            return Ok(pos as u32 + name_base);
+        }
+        s.parse::<u32>().map_err(|_| invalid())
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (value(lo)?, value(hi)?)
+        } else {
+            let v = value(range)?;
+            (v, if part.contains('/') { max } else { v })
+        };
+        if step == 0 || lo < min || hi > max || lo > hi {
+            return Err(invalid());
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+// ─── RRULE ───────────────────────────────────────────────────────────────────
+
+/// How often an RRULE repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// An RRULE-lite: `FREQ=HOURLY|DAILY|WEEKLY|MONTHLY|YEARLY` with optional
+/// `INTERVAL`, `BYDAY` (weekly only, e.g. `MO,WE,FR`), `COUNT` and `UNTIL`
+/// (`YYYYMMDD` or `YYYYMMDDTHHMMSSZ`). Monthly and yearly rules skip
+/// months without the start's day (e.g. the 31st), as RFC 5545 does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub count: Option<u32>,
+    pub until: Option<DateTime<Utc>>,
+    source: String,
+}
+
+impl RecurrenceRule {
+    pub fn parse(rule: &str) -> BbResult<Self> {
+        let invalid = |msg: String| BbError::InvalidInput(format!("invalid RRULE \"{rule}\": {msg}"));
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut count = None;
+        let mut until = None;
+
+        for part in rule.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected KEY=VALUE, got {part}")))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "HOURLY" => Frequency::Hourly,
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => return Err(invalid(format!("unsupported FREQ {other}"))),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value.parse().ok().filter(|n| *n > 0).ok_or_else(|| invalid("INTERVAL must be a positive number".into()))?
+                }
+                "COUNT" => {
+                    count = Some(value.parse().ok().filter(|n| *n > 0).ok_or_else(|| invalid("COUNT must be a positive number".into()))?)
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        by_day.push(parse_rule_weekday(day).ok_or_else(|| invalid(format!("unsupported BYDAY value {day}")))?);
+                    }
+                }
+                "UNTIL" => until = Some(parse_until(value).ok_or_else(|| invalid(format!("bad UNTIL {value}")))?),
+                other => return Err(invalid(format!("unsupported part {other}"))),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| invalid("FREQ is required".into()))?;
+        if !by_day.is_empty() && frequency != Frequency::Weekly {
+            return Err(invalid("BYDAY is only supported with FREQ=WEEKLY".into()));
+        }
+        by_day.sort_by_key(|d| d.num_days_from_monday());
+        by_day.dedup();
+        Ok(Self { frequency, interval, by_day, count, until, source: rule.trim().to_string() })
+    }
+
+    /// Every occurrence from `start`, in order, honouring COUNT and UNTIL.
+    pub fn occurrences(&self, start: DateTime<Utc>, tz: Tz) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let local = start.with_timezone(&tz).naive_local();
+        let interval = self.interval as i64;
+        (0..RULE_SEARCH_PERIODS as i64)
+            .flat_map(move |k| -> Vec<DateTime<Utc>> {
+                let n = k * interval;
+                match self.frequency {
+                    Frequency::Hourly => vec![start + Duration::hours(n)],
+                    Frequency::Daily => vec![resolve_local(tz, local + Duration::days(n))],
+                    Frequency::Weekly => {
+                        let days = if self.by_day.is_empty() { vec![local.weekday()] } else { self.by_day.clone() };
+                        let week_start = local.date() - Duration::days(local.weekday().num_days_from_monday() as i64);
+                        days.into_iter()
+                            .map(|d| week_start + Duration::days(7 * n + d.num_days_from_monday() as i64))
+                            .filter(|date| *date >= local.date())
+                            .map(|date| resolve_local(tz, date.and_time(local.time())))
+                            .collect()
+                    }
+                    Frequency::Monthly => {
+                        let months = local.month0() as i64 + n;
+                        let year = local.year() + (months / 12) as i32;
+                        NaiveDate::from_ymd_opt(year, (months % 12) as u32 + 1, local.day())
+                            .map(|date| resolve_local(tz, date.and_time(local.time())))
+                            .into_iter()
+                            .collect()
+                    }
+                    Frequency::Yearly => NaiveDate::from_ymd_opt(local.year() + n as i32, local.month(), local.day())
+                        .map(|date| resolve_local(tz, date.and_time(local.time())))
+                        .into_iter()
+                        .collect(),
+                }
+            })
+            .take(self.count.map_or(usize::MAX, |c| c as usize))
+            .take_while(move |t| self.until.is_none_or(|until| *t <= until))
+    }
+
+    /// The server interval this rule is equivalent to, if any.
+    fn server_interval(&self, start: DateTime<Utc>, tz: Tz) -> BbResult<(u32, &'static str)> {
+        let start_day = start.with_timezone(&tz).weekday();
+        let unsupported = if self.count.is_some() || self.until.is_some() {
+            Some("COUNT and UNTIL")
+        } else if !(self.by_day.is_empty() || self.by_day == [start_day]) {
+            Some("BYDAY other than the start's weekday")
+        } else {
+            None
+        };
+        if let Some(what) = unsupported {
+            return Err(BbError::InvalidInput(format!(
+                "the server can't repeat with {what}; schedule it locally instead"
+            )));
+        }
+        let kind = match self.frequency {
+            Frequency::Hourly => "hourly",
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Yearly => "yearly",
+        };
+        Ok((self.interval, kind))
+    }
+}
+
+fn parse_rule_weekday(day: &str) -> Option<Weekday> {
+    match day.trim().to_ascii_uppercase().as_str() {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// `YYYYMMDD` (end of that day, UTC) or `YYYYMMDDTHHMMSSZ`.
+fn parse_until(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+        return Some(Utc.from_utc_datetime(&t));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    Some(Utc.from_utc_datetime(&date.and_hms_opt(23, 59, 59)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_cron_parse() {
+        assert!(CronSchedule::parse("0 9 * * MON-FRI").is_ok());
+        assert!(CronSchedule::parse("*/15 * * * *").is_ok());
+        assert!(CronSchedule::parse("@weekly").is_ok());
+        assert!(CronSchedule::parse("0 9 * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("0 9 * * FUNDAY").is_err());
+        assert!(Recurrence::parse("FREQ=WEEKLY;BYDAY=MO,XX").is_err());
+        assert!(Recurrence::parse("FREQ=DAILY;BYDAY=MO").is_err());
+    }
+
+    #[test]
+    fn test_cron_keeps_wall_time_across_dst() {
+        let tz = parse_time_zone("America/New_York").unwrap();
+        let cron = Recurrence::parse("0 9 * * *").unwrap();
+        // DST starts 2025-03-09 in New York: 09:00 moves from 14:00 to 13:00 UTC
+        let times = cron.upcoming(utc("2025-03-07T00:00:00Z"), tz, utc("2025-03-07T00:00:00Z"), 4);
+        let expected = ["2025-03-07T14:00:00Z", "2025-03-08T14:00:00Z", "2025-03-09T13:00:00Z", "2025-03-10T13:00:00Z"];
+        assert_eq!(times, expected.map(utc));
+    }
+
+    #[test]
+    fn test_dst_gap_and_overlap() {
+        let tz = parse_time_zone("America/New_York").unwrap();
+        // 02:30 doesn't exist on 2025-03-09; it fires at 03:00 EDT
+        let gap = Recurrence::parse("30 2 * * *").unwrap();
+        let next = gap.next_after(utc("2025-03-08T12:00:00Z"), tz, utc("2025-03-09T05:00:00Z"));
+        assert_eq!(next, Some(utc("2025-03-09T07:00:00Z")));
+
+        // 01:30 happens twice on 2025-11-02; it fires once, at 01:30 EDT
+        let overlap = Recurrence::parse("30 1 * * *").unwrap();
+        let start = utc("2025-11-01T12:00:00Z");
+        let times = overlap.upcoming(start, tz, utc("2025-11-02T04:00:00Z"), 2);
+        assert_eq!(times, vec![utc("2025-11-02T05:30:00Z"), utc("2025-11-03T06:30:00Z")]);
+    }
+
+    #[test]
+    fn test_cron_day_fields_combine_with_or() {
+        let cron = Recurrence::parse("0 12 1 * FRI").unwrap();
+        let times = cron.upcoming(utc("2025-05-28T00:00:00Z"), Tz::UTC, utc("2025-05-28T00:00:00Z"), 3);
+        // Friday May 30, Sunday June 1 (the 1st), Friday June 6
+        let expected = ["2025-05-30T12:00:00Z", "2025-06-01T12:00:00Z", "2025-06-06T12:00:00Z"];
+        assert_eq!(times, expected.map(utc));
+    }
+
+    #[test]
+    fn test_cron_day_field_steps_restrict() {
+        let after = utc("2025-05-28T00:00:00Z");
+        // Odd days of the month only
+        let times = Recurrence::parse("0 12 */2 * *").unwrap().upcoming(after, Tz::UTC, after, 4);
+        let expected = ["2025-05-29T12:00:00Z", "2025-05-31T12:00:00Z", "2025-06-01T12:00:00Z", "2025-06-03T12:00:00Z"];
+        assert_eq!(times, expected.map(utc));
+
+        // Sundays, Wednesdays and Saturdays only, starting Wednesday May 28
+        let times = Recurrence::parse("0 12 * * */3").unwrap().upcoming(after, Tz::UTC, after, 4);
+        let expected = ["2025-05-28T12:00:00Z", "2025-05-31T12:00:00Z", "2025-06-01T12:00:00Z", "2025-06-04T12:00:00Z"];
+        assert_eq!(times, expected.map(utc));
+
+        // A stepped day of month still combines with a weekday by OR
+        let times = Recurrence::parse("0 12 */10 * MON").unwrap().upcoming(after, Tz::UTC, after, 4);
+        let expected = ["2025-05-31T12:00:00Z", "2025-06-01T12:00:00Z", "2025-06-02T12:00:00Z", "2025-06-09T12:00:00Z"];
+        assert_eq!(times, expected.map(utc));
+    }
+
+    #[test]
+    fn test_rrule_weekly_with_count() {
+        let tz = parse_time_zone("Europe/London").unwrap();
+        // Monday 2025-03-24 08:00 GMT; London moves to BST on 2025-03-30
+        let start = utc("2025-03-24T08:00:00Z");
+        let rule = Recurrence::parse("RRULE:FREQ=WEEKLY;BYDAY=MO,TH;COUNT=4").unwrap();
+        let times = rule.upcoming(start, tz, start - Duration::seconds(1), 10);
+        let expected = ["2025-03-24T08:00:00Z", "2025-03-27T08:00:00Z", "2025-03-31T07:00:00Z", "2025-04-03T07:00:00Z"];
+        assert_eq!(times, expected.map(utc));
+        assert_eq!(rule.next_after(start, tz, utc("2025-04-03T07:00:00Z")), None);
+    }
+
+    #[test]
+    fn test_rrule_monthly_skips_short_months_and_stops_at_until() {
+        let start = utc("2025-01-31T10:00:00Z");
+        let rule = Recurrence::parse("FREQ=MONTHLY;UNTIL=20250601").unwrap();
+        let times = rule.upcoming(start, Tz::UTC, start - Duration::seconds(1), 10);
+        let expected = ["2025-01-31T10:00:00Z", "2025-03-31T10:00:00Z", "2025-05-31T10:00:00Z"];
+        assert_eq!(times, expected.map(utc));
+    }
+
+    #[test]
+    fn test_server_schedule() {
+        let start = utc("2025-03-24T08:00:00Z");
+        let (first, schedule) = Recurrence::parse("FREQ=DAILY;INTERVAL=2").unwrap().server_schedule(start, Tz::UTC).unwrap();
+        assert_eq!(first, start);
+        assert_eq!(schedule, serde_json::json!({"type": "recurring", "interval": 2, "intervalType": "daily"}));
+
+        let (first, schedule) = Recurrence::parse("0 9 * * 1").unwrap().server_schedule(start, Tz::UTC).unwrap();
+        assert_eq!(first, utc("2025-03-24T09:00:00Z"));
+        assert_eq!(schedule["intervalType"], "weekly");
+
+        assert!(Recurrence::parse("0 9 * * 1-5").unwrap().server_schedule(start, Tz::UTC).is_err());
+        assert!(Recurrence::parse("0 9 */2 * *").unwrap().server_schedule(start, Tz::UTC).is_err());
+        assert!(Recurrence::parse("0 9 * * */2").unwrap().server_schedule(start, Tz::UTC).is_err());
+        assert!(Recurrence::parse("FREQ=DAILY;COUNT=3").unwrap().server_schedule(start, Tz::UTC).is_err());
+    }
+}
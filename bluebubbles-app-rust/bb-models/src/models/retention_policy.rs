@@ -0,0 +1,179 @@
+//! Retention policy entity model.
+
+use serde::{Deserialize, Serialize};
+use rusqlite::{params, Connection, Row};
+use bb_core::error::{BbError, BbResult};
+
+/// A rule for how long local message data is kept.
+///
+/// The policy with no `chat_id` is the global default. A per-chat policy
+/// replaces the global one for that chat entirely, so a chat policy with no
+/// limits set exempts the chat.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetentionPolicy {
+    pub id: Option<i64>,
+    /// Chat this policy applies to, or `None` for the global policy.
+    pub chat_id: Option<i64>,
+    /// Delete messages older than this many days.
+    pub message_max_age_days: Option<u32>,
+    /// Delete cached attachment files older than this many days, keeping the rows.
+    pub attachment_max_age_days: Option<u32>,
+    /// Skip pinned chats.
+    pub exempt_pinned: bool,
+    pub enabled: bool,
+    pub date_updated: String,
+}
+
+impl RetentionPolicy {
+    /// Create an enabled policy with no limits for the given scope.
+    pub fn new(chat_id: Option<i64>) -> Self {
+        Self {
+            id: None,
+            chat_id,
+            message_max_age_days: None,
+            attachment_max_age_days: None,
+            exempt_pinned: true,
+            enabled: true,
+            date_updated: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Whether this is the global policy.
+    pub fn is_global(&self) -> bool {
+        self.chat_id.is_none()
+    }
+
+    /// Construct a RetentionPolicy from a database row.
+    pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: Some(row.get("id")?),
+            chat_id: row.get("chat_id")?,
+            message_max_age_days: row.get("message_max_age_days")?,
+            attachment_max_age_days: row.get("attachment_max_age_days")?,
+            exempt_pinned: row.get::<_, i32>("exempt_pinned")? != 0,
+            enabled: row.get::<_, i32>("enabled")? != 0,
+            date_updated: row.get("date_updated")?,
+        })
+    }
+
+    /// Insert or replace the policy for this policy's scope. Returns the row ID.
+    pub fn save(&mut self, conn: &Connection) -> BbResult<i64> {
+        self.date_updated = chrono::Utc::now().to_rfc3339();
+
+        // chat_id is NULL for the global policy, which a UNIQUE constraint
+        // would not deduplicate, so look the existing row up explicitly
+        let existing = Self::find_for_scope(conn, self.chat_id)?.and_then(|p| p.id);
+        let id = match existing {
+            Some(id) => {
+                conn.execute(
+                    "UPDATE retention_policies SET
+                        message_max_age_days = ?1, attachment_max_age_days = ?2,
+                        exempt_pinned = ?3, enabled = ?4, date_updated = ?5
+                     WHERE id = ?6",
+                    params![
+                        self.message_max_age_days,
+                        self.attachment_max_age_days,
+                        self.exempt_pinned as i32,
+                        self.enabled as i32,
+                        self.date_updated,
+                        id,
+                    ],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+                id
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO retention_policies (
+                        chat_id, message_max_age_days, attachment_max_age_days,
+                        exempt_pinned, enabled, date_updated
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        self.chat_id,
+                        self.message_max_age_days,
+                        self.attachment_max_age_days,
+                        self.exempt_pinned as i32,
+                        self.enabled as i32,
+                        self.date_updated,
+                    ],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+                conn.last_insert_rowid()
+            }
+        };
+
+        self.id = Some(id);
+        Ok(id)
+    }
+
+    /// Load the policy for a scope (`None` for the global policy).
+    pub fn find_for_scope(conn: &Connection, chat_id: Option<i64>) -> BbResult<Option<Self>> {
+        match conn.query_row(
+            "SELECT * FROM retention_policies WHERE chat_id IS ?1",
+            [chat_id],
+            Self::from_row,
+        ) {
+            Ok(policy) => Ok(Some(policy)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(BbError::Database(e.to_string())),
+        }
+    }
+
+    /// Load all policies, global first.
+    pub fn list(conn: &Connection) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare("SELECT * FROM retention_policies ORDER BY chat_id IS NOT NULL, chat_id ASC")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let policies = stmt
+            .query_map([], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(policies)
+    }
+
+    /// Delete the policy for a scope. Returns true if a policy was removed.
+    pub fn delete_for_scope(conn: &Connection, chat_id: Option<i64>) -> BbResult<bool> {
+        let changed = conn
+            .execute("DELETE FROM retention_policies WHERE chat_id IS ?1", [chat_id])
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(changed > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::create_tables;
+
+    #[test]
+    fn test_save_upserts_per_scope() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+
+        let mut global = RetentionPolicy::new(None);
+        global.message_max_age_days = Some(730);
+        let id = global.save(&conn).unwrap();
+
+        // Saving another global policy replaces the first
+        let mut again = RetentionPolicy::new(None);
+        again.message_max_age_days = Some(365);
+        assert_eq!(again.save(&conn).unwrap(), id);
+
+        let mut chat = RetentionPolicy::new(Some(1));
+        chat.attachment_max_age_days = Some(90);
+        chat.save(&conn).unwrap();
+
+        let all = RetentionPolicy::list(&conn).unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all[0].is_global());
+        assert_eq!(all[0].message_max_age_days, Some(365));
+        assert_eq!(all[1].attachment_max_age_days, Some(90));
+
+        assert!(RetentionPolicy::delete_for_scope(&conn, Some(1)).unwrap());
+        assert!(RetentionPolicy::find_for_scope(&conn, Some(1)).unwrap().is_none());
+    }
+}
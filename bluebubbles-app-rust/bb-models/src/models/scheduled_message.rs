@@ -3,11 +3,16 @@
 use serde::{Deserialize, Serialize};
 use rusqlite::{params, Row};
 use bb_core::error::{BbError, BbResult};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use super::recurrence::{parse_time_zone, Recurrence};
 
 /// Represents a scheduled message cached locally.
 ///
-/// Scheduled messages are managed by the server. The client caches them
-/// for display purposes and status tracking.
+/// Scheduled messages are usually managed by the server, and the client
+/// caches them for display purposes and status tracking. Local schedules,
+/// sent by the client's own scheduler, take negative IDs so they never
+/// collide with the server's.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScheduledMessage {
     pub id: Option<i64>,
@@ -18,6 +23,13 @@ pub struct ScheduledMessage {
     pub repeat_type: Option<String>,
     pub repeat_interval: Option<i64>,
     pub repeat_interval_type: Option<String>,
+    /// Cron expression or RRULE for a recurring local schedule.
+    pub recurrence: Option<String>,
+    /// IANA time zone the recurrence is evaluated in.
+    pub time_zone: Option<String>,
+    /// First occurrence of the recurrence (its DTSTART), which
+    /// `scheduled_for` moves on from.
+    pub recurrence_start: Option<String>,
     pub status: String,
     pub error: Option<String>,
     pub sent_at: Option<String>,
@@ -30,8 +42,21 @@ pub mod status {
     pub const SENT: &str = "sent";
     pub const FAILED: &str = "failed";
     pub const CANCELLED: &str = "cancelled";
+    /// A local schedule missed while the client was not running, and not
+    /// sent under the catch-up policy.
+    pub const SKIPPED: &str = "skipped";
+    /// A local schedule that came due while the server was unreachable,
+    /// handed to the outgoing queue to send when the connection returns.
+    pub const QUEUED: &str = "queued";
+    /// A local schedule claimed by a scheduler that is sending it, so
+    /// another process running the scheduler leaves it alone.
+    pub const SENDING: &str = "sending";
 }
 
+/// How long a claim on a local schedule holds before the scheduler that
+/// took it counts as gone and the schedule is picked up again.
+pub const CLAIM_TIMEOUT_MS: i64 = 10 * 60 * 1000;
+
 impl ScheduledMessage {
     /// Create a ScheduledMessage from a server JSON map.
     pub fn from_server_map(map: &serde_json::Value) -> BbResult<Self> {
@@ -64,6 +89,9 @@ impl ScheduledMessage {
             repeat_type: map.get("schedule").and_then(|s| s.get("type")).and_then(|v| v.as_str()).map(String::from),
             repeat_interval: map.get("schedule").and_then(|s| s.get("interval")).and_then(|v| v.as_i64()),
             repeat_interval_type: map.get("schedule").and_then(|s| s.get("intervalType")).and_then(|v| v.as_str()).map(String::from),
+            recurrence: None,
+            time_zone: None,
+            recurrence_start: None,
             status: map
                 .get("status")
                 .and_then(|v| v.as_str())
@@ -90,6 +118,9 @@ impl ScheduledMessage {
             repeat_type: row.get("schedule_type")?,
             repeat_interval: row.get("schedule_interval")?,
             repeat_interval_type: row.get("schedule_interval_type")?,
+            recurrence: row.get("recurrence")?,
+            time_zone: row.get("time_zone")?,
+            recurrence_start: row.get("recurrence_start")?,
             status: row.get("status")?,
             error: row.get("error")?,
             sent_at: row.get("sent_at")?,
@@ -97,6 +128,75 @@ impl ScheduledMessage {
         })
     }
 
+    /// A new pending schedule for the client's own scheduler. The ID is
+    /// assigned on `save_local`.
+    pub fn new_local(chat_guid: &str, message: &str, scheduled_for: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            id: None,
+            schedule_type: "send-message".to_string(),
+            chat_guid: chat_guid.to_string(),
+            message: message.to_string(),
+            scheduled_for: scheduled_for.to_rfc3339(),
+            repeat_type: None,
+            repeat_interval: None,
+            repeat_interval_type: None,
+            recurrence: None,
+            time_zone: None,
+            recurrence_start: None,
+            status: status::PENDING.to_string(),
+            error: None,
+            sent_at: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Make this local schedule recur from its current `scheduled_for`,
+    /// which the scheduler moves to the next occurrence after each send.
+    pub fn with_recurrence(mut self, recurrence: &str, time_zone: &str) -> Self {
+        self.recurrence = Some(recurrence.to_string());
+        self.time_zone = Some(time_zone.to_string());
+        self.recurrence_start = Some(self.scheduled_for.clone());
+        self
+    }
+
+    /// The occurrence after the one due at `after`, or `None` when this
+    /// schedule doesn't repeat or its recurrence has ended.
+    pub fn next_occurrence(&self, after: DateTime<Utc>) -> BbResult<Option<DateTime<Utc>>> {
+        let Some(expr) = self.recurrence.as_deref() else {
+            return Ok(None);
+        };
+        let recurrence = Recurrence::parse(expr)?;
+        let tz = match self.time_zone.as_deref() {
+            Some(name) => parse_time_zone(name)?,
+            None => Tz::UTC,
+        };
+        let start = self
+            .recurrence_start
+            .as_deref()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|t| t.with_timezone(&Utc))
+            .or_else(|| self.scheduled_time())
+            .unwrap_or(after);
+        Ok(recurrence.next_after(start, tz, after))
+    }
+
+    /// Whether this schedule is run by the client rather than the server.
+    pub fn is_local(&self) -> bool {
+        self.id.is_some_and(|id| id < 0)
+    }
+
+    /// When this message is due, from an RFC 3339 or epoch-millisecond value.
+    pub fn scheduled_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match chrono::DateTime::parse_from_rfc3339(&self.scheduled_for) {
+            Ok(t) => Some(t.with_timezone(&chrono::Utc)),
+            Err(_) => self
+                .scheduled_for
+                .parse::<i64>()
+                .ok()
+                .and_then(chrono::DateTime::from_timestamp_millis),
+        }
+    }
+
     /// Whether this scheduled message is still pending.
     pub fn is_pending(&self) -> bool {
         self.status == status::PENDING
@@ -119,8 +219,8 @@ impl ScheduledMessage {
             "INSERT OR REPLACE INTO scheduled_messages (
                 id, type, chat_guid, message, scheduled_for,
                 schedule_type, schedule_interval, schedule_interval_type,
-                status, error, sent_at, created_at
-            ) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12)",
+                recurrence, time_zone, recurrence_start, status, error, sent_at, created_at
+            ) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15)",
             params![
                 row_id,
                 self.schedule_type,
@@ -130,6 +230,9 @@ impl ScheduledMessage {
                 self.repeat_type,
                 self.repeat_interval,
                 self.repeat_interval_type,
+                self.recurrence,
+                self.time_zone,
+                self.recurrence_start,
                 self.status,
                 self.error,
                 self.sent_at,
@@ -144,6 +247,69 @@ impl ScheduledMessage {
         Ok(self.id.unwrap_or(0))
     }
 
+    /// Save a local schedule, giving a new one the next free negative ID.
+    pub fn save_local(&mut self, conn: &rusqlite::Connection) -> BbResult<i64> {
+        if self.id.is_none() {
+            let lowest: i64 = conn
+                .query_row("SELECT MIN(0, COALESCE(MIN(id), 0)) FROM scheduled_messages", [], |row| row.get(0))
+                .map_err(|e| BbError::Database(e.to_string()))?;
+            self.id = Some(lowest - 1);
+        }
+        self.save(conn)
+    }
+
+    /// Load pending local schedules, soonest first. Schedules claimed
+    /// longer than `CLAIM_TIMEOUT_MS` ago, by a scheduler that crashed
+    /// mid-send, count as pending again.
+    pub fn load_pending_local(conn: &rusqlite::Connection) -> BbResult<Vec<Self>> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT * FROM scheduled_messages WHERE id < 0
+                 AND (status = ?1 OR (status = ?2 AND COALESCE(claimed_at, 0) < ?3))",
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let mut messages: Vec<Self> = stmt
+            .query_map(params![status::PENDING, status::SENDING, stale_claim_cutoff()], Self::from_row)
+            .map_err(|e| BbError::Database(e.to_string()))?
+            .filter_map(|r| r.ok())
+            .collect();
+        messages.sort_by_key(|m| m.scheduled_time());
+        Ok(messages)
+    }
+
+    /// Claim a pending schedule for sending by moving it to `sending` and
+    /// recording when. A stale claim is taken over. Returns false when it
+    /// is no longer pending, e.g. because another process claimed it first.
+    pub fn claim(conn: &rusqlite::Connection, id: i64) -> BbResult<bool> {
+        let changed = conn
+            .execute(
+                "UPDATE scheduled_messages SET status = ?1, claimed_at = ?2
+                 WHERE id = ?3 AND (status = ?4 OR (status = ?1 AND COALESCE(claimed_at, 0) < ?5))",
+                params![
+                    status::SENDING,
+                    chrono::Utc::now().timestamp_millis(),
+                    id,
+                    status::PENDING,
+                    stale_claim_cutoff(),
+                ],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(changed == 1)
+    }
+
+    /// Give up a claim, putting the schedule back to pending. Returns false
+    /// when it wasn't claimed.
+    pub fn release(conn: &rusqlite::Connection, id: i64) -> BbResult<bool> {
+        let changed = conn
+            .execute(
+                "UPDATE scheduled_messages SET status = ?1, claimed_at = NULL WHERE id = ?2 AND status = ?3",
+                params![status::PENDING, id, status::SENDING],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(changed == 1)
+    }
+
     /// Load all scheduled messages from the database.
     pub fn load_all(conn: &rusqlite::Connection) -> BbResult<Vec<Self>> {
         let mut stmt = conn
@@ -168,6 +334,11 @@ impl ScheduledMessage {
     }
 }
 
+/// Claims made before this time are stale.
+fn stale_claim_cutoff() -> i64 {
+    chrono::Utc::now().timestamp_millis() - CLAIM_TIMEOUT_MS
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,4 +371,83 @@ mod tests {
         assert!(msg.is_sent());
         assert!(!msg.is_pending());
     }
+
+    #[test]
+    fn test_local_schedules_take_negative_ids() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::schema::create_tables(&conn).unwrap();
+
+        let mut server = ScheduledMessage::from_server_map(&serde_json::json!({
+            "id": 3, "chatGuid": "c", "message": "server", "scheduledFor": "2025-01-01T12:00:00Z"
+        }))
+        .unwrap();
+        server.save(&conn).unwrap();
+
+        let later = chrono::Utc::now() + chrono::Duration::hours(2);
+        let sooner = chrono::Utc::now() + chrono::Duration::hours(1);
+        let mut a = ScheduledMessage::new_local("c", "later", later);
+        let mut b = ScheduledMessage::new_local("c", "sooner", sooner);
+        assert_eq!(a.save_local(&conn).unwrap(), -1);
+        assert_eq!(b.save_local(&conn).unwrap(), -2);
+        assert!(a.is_local() && !server.is_local());
+
+        let pending = ScheduledMessage::load_pending_local(&conn).unwrap();
+        let texts: Vec<&str> = pending.iter().map(|m| m.message.as_str()).collect();
+        assert_eq!(texts, vec!["sooner", "later"]);
+        assert_eq!(pending[0].scheduled_time().unwrap().timestamp(), sooner.timestamp());
+    }
+
+    #[test]
+    fn test_claim_once() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::schema::create_tables(&conn).unwrap();
+
+        let mut msg = ScheduledMessage::new_local("c", "hi", chrono::Utc::now());
+        let id = msg.save_local(&conn).unwrap();
+        assert!(ScheduledMessage::claim(&conn, id).unwrap());
+        assert!(!ScheduledMessage::claim(&conn, id).unwrap());
+        assert!(ScheduledMessage::load_pending_local(&conn).unwrap().is_empty());
+
+        assert!(ScheduledMessage::release(&conn, id).unwrap());
+        assert!(!ScheduledMessage::release(&conn, id).unwrap());
+        assert_eq!(ScheduledMessage::load_pending_local(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stale_claim_is_reclaimed() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::schema::create_tables(&conn).unwrap();
+
+        let mut msg = ScheduledMessage::new_local("c", "hi", chrono::Utc::now());
+        let id = msg.save_local(&conn).unwrap();
+        assert!(ScheduledMessage::claim(&conn, id).unwrap());
+
+        // The scheduler that claimed it crashed long ago
+        let long_ago = chrono::Utc::now().timestamp_millis() - CLAIM_TIMEOUT_MS - 1;
+        conn.execute("UPDATE scheduled_messages SET claimed_at = ?1 WHERE id = ?2", params![long_ago, id])
+            .unwrap();
+        let pending = ScheduledMessage::load_pending_local(&conn).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].status, status::SENDING);
+        assert!(ScheduledMessage::claim(&conn, id).unwrap());
+        assert!(!ScheduledMessage::claim(&conn, id).unwrap());
+    }
+
+    #[test]
+    fn test_recurring_schedule_round_trips() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::schema::create_tables(&conn).unwrap();
+
+        let first = DateTime::parse_from_rfc3339("2025-03-24T08:00:00Z").unwrap().with_timezone(&Utc);
+        let mut msg = ScheduledMessage::new_local("c", "standup", first)
+            .with_recurrence("FREQ=DAILY;COUNT=2", "Europe/London");
+        msg.save_local(&conn).unwrap();
+
+        let loaded = ScheduledMessage::load_pending_local(&conn).unwrap().remove(0);
+        assert_eq!(loaded.time_zone.as_deref(), Some("Europe/London"));
+        let second = loaded.next_occurrence(first).unwrap().unwrap();
+        assert_eq!(second.to_rfc3339(), "2025-03-25T08:00:00+00:00");
+        assert_eq!(loaded.next_occurrence(second).unwrap(), None);
+        assert_eq!(ScheduledMessage::new_local("c", "once", first).next_occurrence(first).unwrap(), None);
+    }
 }
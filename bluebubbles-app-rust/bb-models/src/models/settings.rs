@@ -141,6 +141,8 @@ impl Settings {
 pub mod keys {
     // Connection & Auth
     pub const ICLOUD_ACCOUNT: &str = "iCloudAccount";
+    /// JSON array of the user's own addresses, as reported by the server.
+    pub const MY_ADDRESSES: &str = "myAddresses";
     pub const GUID_AUTH_KEY: &str = "guidAuthKey";
     pub const SERVER_ADDRESS: &str = "serverAddress";
     pub const REMEMBER_PASSWORD: &str = "rememberPassword";
@@ -279,6 +281,7 @@ pub mod keys {
     pub const USE_LOCALHOST: &str = "useLocalhost";
     pub const USE_LOCAL_IPV6: &str = "useLocalIpv6";
     pub const SYNC_CONTACTS_AUTOMATICALLY: &str = "syncContactsAutomatically";
+    pub const DEFAULT_PHONE_REGION: &str = "defaultPhoneRegion";
 
     // Sound
     pub const SEND_SOUND_PATH: &str = "sendSoundPath";
@@ -304,6 +307,9 @@ pub mod keys {
     // OTP Detection
     pub const OTP_DETECTION_ENABLED: &str = "otpDetectionEnabled";
     pub const OTP_AUTO_COPY: &str = "otpAutoCopy";
+
+    // Link previews
+    pub const GENERATE_LINK_PREVIEWS: &str = "generateLinkPreviews";
 }
 
 #[cfg(test)]
@@ -0,0 +1,149 @@
+//! Phone number normalization.
+//!
+//! Addresses arrive in whatever form the server, the address book or a
+//! vCard used: `+1 (555) 123-4567`, `0171 1234567`, `5551234567`. To match
+//! them reliably they are parsed into E.164 (`+15551234567`) using the
+//! handle's country, or the configured default region for numbers written
+//! without a country code.
+
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use phonenumber::country;
+use phonenumber::Mode;
+
+/// Region used when neither the handle nor the config names one.
+const FALLBACK_REGION: &str = "US";
+
+/// Shortest national number treated as a phone number. Shorter values are
+/// SMS short codes and are left unnormalized.
+const MIN_NATIONAL_DIGITS: usize = 7;
+
+/// The configured default region, if any. Set once at startup from config.
+static DEFAULT_REGION: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the default region (ISO 3166 alpha-2, e.g. `GB`) for numbers without
+/// a country code. An empty or unknown code falls back to the system locale.
+pub fn set_default_region(region: &str) {
+    let region = parse_region(region).map(|_| region.trim().to_ascii_uppercase());
+    if let Ok(mut current) = DEFAULT_REGION.write() {
+        *current = region;
+    }
+}
+
+/// The default region: the configured one, else the system locale's, else `US`.
+pub fn default_region() -> String {
+    DEFAULT_REGION
+        .read()
+        .ok()
+        .and_then(|r| r.clone())
+        .or_else(system_region)
+        .unwrap_or_else(|| FALLBACK_REGION.to_string())
+}
+
+/// Normalize a phone number to E.164, interpreting national numbers in
+/// `region` (or the default region when `None`).
+///
+/// Returns None for email addresses, short codes and anything that does
+/// not parse as a phone number.
+pub fn normalize(raw: &str, region: Option<&str>) -> Option<String> {
+    let raw = raw.trim();
+    if raw.is_empty() || raw.contains('@') || raw.chars().any(|c| c.is_alphabetic()) {
+        return None;
+    }
+
+    let region = region
+        .and_then(parse_region)
+        .or_else(|| parse_region(&default_region()));
+    let number = phonenumber::parse(region, raw).ok()?;
+
+    if number.national().value().to_string().len() < MIN_NATIONAL_DIGITS {
+        return None;
+    }
+    Some(number.format().mode(Mode::E164).to_string())
+}
+
+/// Normalize with the given region, falling back to a digits-and-plus
+/// form when the number cannot be parsed, so callers always get a key.
+pub fn normalize_or_strip(raw: &str, region: Option<&str>) -> String {
+    normalize(raw, region).unwrap_or_else(|| {
+        raw.chars().filter(|c| c.is_ascii_digit() || *c == '+').collect()
+    })
+}
+
+/// Whether `code` names a region the phone number metadata covers, so it
+/// can be used as a default region.
+pub fn is_known_region(code: &str) -> bool {
+    let code = code.trim().to_ascii_uppercase();
+    parse_region(&code).is_some() && phonenumber::metadata::DATABASE.by_id(code.as_str()).is_some()
+}
+
+fn parse_region(code: &str) -> Option<country::Id> {
+    let code = code.trim();
+    if code.len() != 2 {
+        return None;
+    }
+    country::Id::from_str(&code.to_ascii_uppercase()).ok()
+}
+
+/// Region from the POSIX locale (`LC_ALL`, `LC_MESSAGES`, `LANG`), e.g.
+/// `en_GB.UTF-8` gives `GB`.
+fn system_region() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+        .and_then(|locale| {
+            let lang = locale.split(['.', '@']).next()?;
+            let region = lang.split(['_', '-']).nth(1)?;
+            parse_region(region).map(|_| region.to_ascii_uppercase())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_with_region() {
+        assert_eq!(normalize("+1 (650) 253-0000", None).as_deref(), Some("+16502530000"));
+        assert_eq!(normalize("(650) 253-0000", Some("US")).as_deref(), Some("+16502530000"));
+        // Trunk prefix is dropped
+        assert_eq!(normalize("020 7031 3000", Some("GB")).as_deref(), Some("+442070313000"));
+        assert_eq!(normalize("0171 1234567", Some("de")).as_deref(), Some("+491711234567"));
+        // An explicit country code wins over the region
+        assert_eq!(normalize("+44 20 7031 3000", Some("US")).as_deref(), Some("+442070313000"));
+        assert_eq!(normalize("00 44 20 7031 3000", Some("DE")).as_deref(), Some("+442070313000"));
+    }
+
+    #[test]
+    fn test_normalize_rejects_non_numbers() {
+        assert_eq!(normalize("jane@example.com", Some("US")), None);
+        assert_eq!(normalize("72975", Some("US")), None);
+        assert_eq!(normalize("Apple", Some("US")), None);
+        assert_eq!(normalize("", None), None);
+    }
+
+    #[test]
+    fn test_same_digits_differ_by_region() {
+        let us = normalize("2070313000", Some("US"));
+        let gb = normalize("02070313000", Some("GB"));
+        assert!(us.is_some() && gb.is_some());
+        assert_ne!(us, gb);
+    }
+
+    #[test]
+    fn test_is_known_region() {
+        assert!(is_known_region("US"));
+        assert!(is_known_region(" gb "));
+        assert!(!is_known_region("ZZ"));
+        assert!(!is_known_region("USA"));
+        assert!(!is_known_region(""));
+    }
+
+    #[test]
+    fn test_normalize_or_strip() {
+        assert_eq!(normalize_or_strip("72975", Some("US")), "72975");
+        assert_eq!(normalize_or_strip("(650) 253-0000", Some("US")), "+16502530000");
+    }
+}
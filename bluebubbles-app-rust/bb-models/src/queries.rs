@@ -13,10 +13,12 @@ use rusqlite::{params, Connection};
 use bb_core::error::{BbError, BbResult};
 
 use crate::models::chat::Chat;
+use crate::models::chat_folder::ChatFolder;
 use crate::models::message::Message;
 use crate::models::handle::Handle;
 use crate::models::attachment::Attachment;
 use crate::models::contact::Contact;
+use crate::models::settings::{keys, Settings};
 
 /// Sort direction for query results.
 #[derive(Debug, Clone, Copy)]
@@ -52,13 +54,27 @@ pub struct ChatWithDetails {
 /// Joins with messages to get last message info and unread count.
 /// After loading chats, batch-loads all participants (handles) with their
 /// associated contacts in a single query to avoid N+1 performance issues.
+/// With a folder, only that folder's chats are listed.
 pub fn list_chats_with_details(
     conn: &Connection,
     offset: i64,
     limit: i64,
     include_archived: bool,
+    folder: Option<&ChatFolder>,
 ) -> BbResult<Vec<ChatWithDetails>> {
-    let archive_filter = if include_archived { "" } else { "WHERE c.is_archived = 0 AND c.date_deleted IS NULL" };
+    let mut values: Vec<rusqlite::types::Value> = vec![limit.into(), offset.into()];
+    let mut conditions = Vec::new();
+    if !include_archived {
+        conditions.push("c.is_archived = 0 AND c.date_deleted IS NULL".to_string());
+    }
+    if let Some(folder) = folder {
+        conditions.push(format!("({})", folder.filter_sql(&mut values)));
+    }
+    let archive_filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
 
     let sql = format!(
         "SELECT c.*,
@@ -79,7 +95,7 @@ pub fn list_chats_with_details(
 
     let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
     let mut results: Vec<ChatWithDetails> = stmt
-        .query_map(params![limit, offset], |row| {
+        .query_map(rusqlite::params_from_iter(values), |row| {
             let chat = Chat::from_row(row)?;
             Ok(ChatWithDetails {
                 chat,
@@ -116,6 +132,52 @@ pub fn list_chats_with_details(
     Ok(results)
 }
 
+/// A chat folder with its chat and unread counts.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FolderSummary {
+    pub folder: ChatFolder,
+    /// Non-archived chats in the folder.
+    pub chat_count: i64,
+    /// Chats in the folder with unread messages.
+    pub unread_chats: i64,
+    /// Unread messages across the folder.
+    pub unread_count: i64,
+}
+
+/// Count chats and unread messages in a folder, excluding archived and
+/// deleted chats as the chat list does.
+pub fn folder_summary(conn: &Connection, folder: ChatFolder) -> BbResult<FolderSummary> {
+    let mut values = Vec::new();
+    let filter = folder.filter_sql(&mut values);
+    let sql = format!(
+        "SELECT COUNT(*), COALESCE(SUM(unread > 0), 0), COALESCE(SUM(unread), 0) FROM (
+            SELECT (SELECT COUNT(*) FROM messages m WHERE m.chat_id = c.id AND m.date_read IS NULL
+                    AND m.is_from_me = 0 AND m.date_deleted IS NULL) AS unread
+            FROM chats c
+            WHERE c.is_archived = 0 AND c.date_deleted IS NULL AND ({filter})
+        )"
+    );
+    let (chat_count, unread_chats, unread_count) = conn
+        .query_row(&sql, rusqlite::params_from_iter(values), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    Ok(FolderSummary {
+        folder,
+        chat_count,
+        unread_chats,
+        unread_count,
+    })
+}
+
+/// Summaries of every folder, in display order.
+pub fn folder_summaries(conn: &Connection) -> BbResult<Vec<FolderSummary>> {
+    ChatFolder::list(conn)?
+        .into_iter()
+        .map(|folder| folder_summary(conn, folder))
+        .collect()
+}
+
 /// Batch-load participants (handles) with resolved contacts for multiple chats.
 ///
 /// Performs a single query joining chat_handle_join -> handles -> contacts
@@ -147,6 +209,7 @@ fn batch_load_participants_with_contacts(
                 h.default_phone AS h_default_phone,
                 h.default_email AS h_default_email,
                 h.contact_id AS h_contact_id,
+                h.normalized_address AS h_normalized_address,
                 ct.id AS ct_id,
                 ct.external_id AS ct_external_id,
                 ct.display_name AS ct_display_name,
@@ -185,6 +248,7 @@ fn batch_load_participants_with_contacts(
                 default_phone: row.get("h_default_phone")?,
                 default_email: row.get("h_default_email")?,
                 contact_id: row.get("h_contact_id")?,
+                normalized_address: row.get("h_normalized_address")?,
                 contact: None,
             };
 
@@ -411,6 +475,88 @@ pub fn search_chats(conn: &Connection, query: &str, limit: i64) -> BbResult<Vec<
     Ok(chats)
 }
 
+/// Store a chat's composer draft. Returns false if no chat has this GUID.
+///
+/// `attachments_json` is a JSON array of local file paths, as stored in
+/// `text_field_attachments`.
+pub fn set_chat_draft(
+    conn: &Connection,
+    guid: &str,
+    text: Option<&str>,
+    attachments_json: &str,
+) -> BbResult<bool> {
+    let changed = conn
+        .execute(
+            "UPDATE chats SET text_field_text = ?1, text_field_attachments = ?2 WHERE guid = ?3",
+            params![text, attachments_json, guid],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    Ok(changed > 0)
+}
+
+/// Clear a chat's composer draft. Returns true if a draft was removed.
+pub fn clear_chat_draft(conn: &Connection, guid: &str) -> BbResult<bool> {
+    let changed = conn
+        .execute(
+            "UPDATE chats SET text_field_text = NULL, text_field_attachments = '[]'
+             WHERE guid = ?1 AND (text_field_text IS NOT NULL OR text_field_attachments != '[]')",
+            [guid],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    Ok(changed > 0)
+}
+
+/// Clear a chat's draft text if it is still `text`, keeping draft
+/// attachments. A draft typed after `text` was sent is left alone.
+/// Returns true if the draft text was removed.
+pub fn clear_draft_text(conn: &Connection, guid: &str, text: &str) -> BbResult<bool> {
+    let changed = conn
+        .execute(
+            "UPDATE chats SET text_field_text = NULL
+             WHERE guid = ?1 AND trim(text_field_text) = trim(?2)",
+            rusqlite::params![guid, text],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    Ok(changed > 0)
+}
+
+/// Drop one file from a chat's draft attachments, keeping the draft text.
+/// Returns true if the file was part of the draft.
+pub fn remove_draft_attachment(conn: &Connection, guid: &str, path: &str) -> BbResult<bool> {
+    let Some(chat) = find_chat_by_guid(conn, guid)? else {
+        return Ok(false);
+    };
+    let mut paths = chat.draft_attachment_paths();
+    let before = paths.len();
+    paths.retain(|p| p != path);
+    if paths.len() == before {
+        return Ok(false);
+    }
+    let json = serde_json::to_string(&paths)?;
+    set_chat_draft(conn, guid, chat.text_field_text.as_deref(), &json)
+}
+
+/// List chats that have an unsent draft, most recently active first.
+pub fn chats_with_drafts(conn: &Connection) -> BbResult<Vec<Chat>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT * FROM chats
+             WHERE date_deleted IS NULL
+               AND ((text_field_text IS NOT NULL AND text_field_text != '')
+                    OR text_field_attachments != '[]')
+             ORDER BY latest_message_date DESC",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let chats = stmt
+        .query_map([], Chat::from_row)
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(chats)
+}
+
 // ─── Message Queries ────────────────────────────────────────────────────────
 
 /// Cursor-based paginated message loading for a chat.
@@ -493,6 +639,23 @@ pub fn list_messages_for_chat(
     Ok(messages)
 }
 
+/// Load every message in a chat, oldest first, including deleted ones.
+///
+/// Used for full-conversation exports; callers decide how to treat deleted
+/// and unsent messages.
+pub fn all_messages_for_chat(conn: &Connection, chat_id: i64) -> BbResult<Vec<Message>> {
+    let mut stmt = conn
+        .prepare("SELECT * FROM messages WHERE chat_id = ?1 ORDER BY date_created ASC, id ASC")
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let messages = stmt
+        .query_map([chat_id], Message::from_row)
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(messages)
+}
+
 /// Search around a specific date (bidirectional pagination).
 ///
 /// Returns messages both before and after the given timestamp, centered on it.
@@ -561,6 +724,64 @@ pub fn latest_message_for_chat(conn: &Connection, chat_id: i64) -> BbResult<Opti
     }
 }
 
+/// Get the latest received text message (not a reaction) for a chat.
+pub fn latest_incoming_text_for_chat(conn: &Connection, chat_id: i64) -> BbResult<Option<Message>> {
+    match conn.query_row(
+        "SELECT * FROM messages
+         WHERE chat_id = ?1 AND is_from_me = 0 AND date_deleted IS NULL
+           AND associated_message_guid IS NULL AND TRIM(COALESCE(text, '')) != ''
+         ORDER BY date_created DESC LIMIT 1",
+        [chat_id],
+        Message::from_row,
+    ) {
+        Ok(msg) => Ok(Some(msg)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(BbError::Database(e.to_string())),
+    }
+}
+
+/// The most recent received text messages (not reactions), newest first,
+/// optionally in one chat.
+pub fn recent_incoming_texts(conn: &Connection, chat_id: Option<i64>, limit: i64) -> BbResult<Vec<Message>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT * FROM messages
+             WHERE is_from_me = 0 AND date_deleted IS NULL AND (?1 IS NULL OR chat_id = ?1)
+               AND associated_message_guid IS NULL AND TRIM(COALESCE(text, '')) != ''
+             ORDER BY date_created DESC LIMIT ?2",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let messages = stmt
+        .query_map(params![chat_id, limit], Message::from_row)
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(messages)
+}
+
+/// Text of the most recent messages the user sent, newest first. Reactions
+/// and attachment-only messages are skipped.
+pub fn sent_message_texts(conn: &Connection, limit: i64) -> BbResult<Vec<String>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT text FROM messages
+             WHERE is_from_me = 1 AND date_deleted IS NULL
+               AND associated_message_guid IS NULL AND TRIM(COALESCE(text, '')) != ''
+             ORDER BY date_created DESC LIMIT ?1",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let texts = stmt
+        .query_map([limit], |row| row.get(0))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(texts)
+}
+
 /// Load reactions (associated messages) for a message.
 pub fn load_reactions_for_message(conn: &Connection, message_guid: &str) -> BbResult<Vec<Message>> {
     let mut stmt = conn
@@ -722,6 +943,206 @@ pub fn search_handles(conn: &Connection, query: &str, limit: i64) -> BbResult<Ve
     Ok(handles)
 }
 
+/// E.164 form stored for a handle with this raw address, if any. Uses the
+/// handle's own country rather than the default region.
+pub fn find_handle_normalized_address(conn: &Connection, address: &str) -> BbResult<Option<String>> {
+    match conn.query_row(
+        "SELECT normalized_address FROM handles
+         WHERE address = ?1 AND normalized_address IS NOT NULL LIMIT 1",
+        [address],
+        |row| row.get(0),
+    ) {
+        Ok(n) => Ok(Some(n)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(BbError::Database(e.to_string())),
+    }
+}
+
+/// Recompute `handles.normalized_address` and the `contact_phones` index,
+/// e.g. after the default phone region changes.
+///
+/// Returns the number of handles and contacts that were processed.
+pub fn renormalize_phones(conn: &Connection) -> BbResult<(usize, usize)> {
+    let handles = list_handles(conn)?;
+    for handle in &handles {
+        conn.execute(
+            "UPDATE handles SET normalized_address = ?1 WHERE id = ?2",
+            params![handle.compute_normalized_address(), handle.id],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    }
+
+    let contacts = list_contacts(conn)?;
+    for contact in &contacts {
+        if let Some(id) = contact.id {
+            contact.index_phones(conn, id)?;
+        }
+    }
+
+    Ok((handles.len(), contacts.len()))
+}
+
+/// Apply the default phone region saved in settings, if any, to this process.
+///
+/// The saved region wins over the config file, so the CLI and the desktop
+/// app normalize numbers the same way the stored ones were.
+pub fn apply_saved_phone_region(conn: &Connection) -> BbResult<()> {
+    if let Some(region) = Settings::get(conn, keys::DEFAULT_PHONE_REGION)? {
+        crate::phone::set_default_region(&region);
+    }
+    Ok(())
+}
+
+/// Change the default phone region: save it, apply it to this process and
+/// renormalize stored numbers with it.
+///
+/// An empty region clears the setting so the system locale is used; any
+/// other value must be a region code the phone number library knows.
+///
+/// Returns the number of handles and contacts that were processed.
+pub fn set_default_phone_region(conn: &Connection, region: &str) -> BbResult<(usize, usize)> {
+    let region = region.trim().to_ascii_uppercase();
+    if !region.is_empty() && !crate::phone::is_known_region(&region) {
+        return Err(BbError::InvalidInput(format!("unknown phone region: {region}")));
+    }
+    Settings::set(conn, keys::DEFAULT_PHONE_REGION, &region)?;
+    crate::phone::set_default_region(&region);
+    renormalize_phones(conn)
+}
+
+/// The user's own addresses: the iCloud account setting and every address
+/// saved with `save_my_address`.
+pub fn my_addresses(conn: &Connection) -> BbResult<Vec<String>> {
+    let mut addresses: Vec<String> = Settings::get_json(conn, keys::MY_ADDRESSES)?.unwrap_or_default();
+    if let Some(account) = Settings::get(conn, keys::ICLOUD_ACCOUNT)?.filter(|a| !a.is_empty()) {
+        if !addresses.iter().any(|a| a.eq_ignore_ascii_case(&account)) {
+            addresses.insert(0, account);
+        }
+    }
+    Ok(addresses)
+}
+
+/// Remember an address the user sends from, such as the iCloud account or
+/// iMessage address the server detected. Returns whether it was new.
+pub fn save_my_address(conn: &Connection, address: &str) -> BbResult<bool> {
+    let address = address.trim();
+    if address.is_empty() {
+        return Ok(false);
+    }
+    let mut addresses: Vec<String> = Settings::get_json(conn, keys::MY_ADDRESSES)?.unwrap_or_default();
+    if addresses.iter().any(|a| a.eq_ignore_ascii_case(address)) {
+        return Ok(false);
+    }
+    addresses.push(address.to_string());
+    Settings::set_json(conn, keys::MY_ADDRESSES, &addresses)?;
+    Ok(true)
+}
+
+// ─── Trash Queries ──────────────────────────────────────────────────────────
+
+/// Soft-deleted chats, most recently deleted first.
+pub fn deleted_chats(conn: &Connection) -> BbResult<Vec<Chat>> {
+    let mut stmt = conn
+        .prepare("SELECT * FROM chats WHERE date_deleted IS NOT NULL ORDER BY date_deleted DESC")
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let chats = stmt
+        .query_map([], Chat::from_row)
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(chats)
+}
+
+/// Soft-deleted messages in chats that are not themselves deleted, most
+/// recently deleted first.
+///
+/// Reactions and unsent messages (which have a tombstone) are left out:
+/// the former go with their target, the latter are not restorable.
+pub fn deleted_messages(conn: &Connection) -> BbResult<Vec<Message>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT m.* FROM messages m
+             LEFT JOIN chats c ON c.id = m.chat_id
+             WHERE m.date_deleted IS NOT NULL
+               AND c.date_deleted IS NULL
+               AND m.associated_message_guid IS NULL
+               AND NOT EXISTS (SELECT 1 FROM message_tombstones t WHERE t.message_guid = m.guid)
+             ORDER BY m.date_deleted DESC",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let messages = stmt
+        .query_map([], Message::from_row)
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(messages)
+}
+
+// ─── Person Queries ─────────────────────────────────────────────────────────
+
+/// One-to-one chats with any of a person's handles, most recent first.
+pub fn person_chats(conn: &Connection, person_id: i64) -> BbResult<Vec<Chat>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT c.* FROM chats c
+             WHERE c.date_deleted IS NULL
+               AND c.id IN (
+                   SELECT chj.chat_id FROM chat_handle_join chj
+                   GROUP BY chj.chat_id
+                   HAVING COUNT(*) = 1
+                      AND MIN(chj.handle_id) IN
+                          (SELECT handle_id FROM person_handles WHERE person_id = ?1)
+               )
+             ORDER BY c.latest_message_date DESC",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let chats = stmt
+        .query_map([person_id], Chat::from_row)
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(chats)
+}
+
+/// Search messages exchanged with a person: those sent from any of their
+/// handles, plus the user's own messages in one-to-one chats with them.
+pub fn search_person_messages(
+    conn: &Connection,
+    person_id: i64,
+    query: &str,
+    limit: i64,
+) -> BbResult<Vec<Message>> {
+    let pattern = format!("%{query}%");
+    let mut stmt = conn
+        .prepare(
+            "SELECT * FROM messages
+             WHERE (text LIKE ?2 OR subject LIKE ?2) AND date_deleted IS NULL
+               AND (handle_id IN (SELECT handle_id FROM person_handles WHERE person_id = ?1)
+                    OR (is_from_me = 1 AND chat_id IN (
+                        SELECT chj.chat_id FROM chat_handle_join chj
+                        GROUP BY chj.chat_id
+                        HAVING COUNT(*) = 1
+                           AND MIN(chj.handle_id) IN
+                               (SELECT handle_id FROM person_handles WHERE person_id = ?1))))
+             ORDER BY date_created DESC LIMIT ?3",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let messages = stmt
+        .query_map(params![person_id, pattern, limit], Message::from_row)
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(messages)
+}
+
 // ─── Attachment Queries ─────────────────────────────────────────────────────
 
 /// Load attachments for a message.
@@ -872,6 +1293,23 @@ pub fn search_contacts_by_phone_suffix(
     Ok(contacts)
 }
 
+/// Find the contact owning an exact E.164 phone number, via the
+/// `contact_phones` index.
+pub fn find_contact_by_normalized_phone(conn: &Connection, number: &str) -> BbResult<Option<Contact>> {
+    match conn.query_row(
+        "SELECT c.* FROM contacts c
+         INNER JOIN contact_phones p ON p.contact_id = c.id
+         WHERE p.number = ?1
+         ORDER BY c.id LIMIT 1",
+        [number],
+        Contact::from_row,
+    ) {
+        Ok(c) => Ok(Some(c)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(BbError::Database(e.to_string())),
+    }
+}
+
 /// Search contacts by email.
 pub fn search_contacts_by_email(
     conn: &Connection,
@@ -921,6 +1359,16 @@ pub fn delete_all_contacts(conn: &Connection) -> BbResult<usize> {
         .map_err(|e| BbError::Database(e.to_string()))
 }
 
+/// Delete contacts that came from a server sync, keeping locally imported
+/// ones (external IDs starting with `local:`).
+pub fn delete_synced_contacts(conn: &Connection) -> BbResult<usize> {
+    conn.execute(
+        "DELETE FROM contacts WHERE external_id IS NULL OR external_id NOT LIKE ?1",
+        [format!("{}%", crate::models::contact::LOCAL_ID_PREFIX)],
+    )
+    .map_err(|e| BbError::Database(e.to_string()))
+}
+
 /// Link contacts to handles by matching phone numbers and emails.
 ///
 /// Sets `handle.contact_id` for every handle whose address matches a contact,
@@ -933,6 +1381,20 @@ pub fn link_contacts_to_handles(conn: &Connection) -> BbResult<u32> {
     let contacts = list_contacts(conn)?;
     let handles = list_handles(conn)?;
 
+    // Exact E.164 matches take precedence over the digit heuristics below
+    let mut e164_to_contact_id: HashMap<String, i64> = HashMap::new();
+    {
+        let mut stmt = conn
+            .prepare("SELECT number, contact_id FROM contact_phones ORDER BY contact_id DESC")
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        for (number, contact_id) in rows.flatten() {
+            e164_to_contact_id.insert(number, contact_id);
+        }
+    }
+
     // Build a lookup from normalized address -> contact local DB id
     let mut addr_to_contact_id: HashMap<String, i64> = HashMap::new();
     for contact in &contacts {
@@ -968,8 +1430,15 @@ pub fn link_contacts_to_handles(conn: &Connection) -> BbResult<u32> {
         let addr = handle.address.trim();
         let is_email = addr.contains('@');
 
+        let exact = handle
+            .normalized_address
+            .as_ref()
+            .and_then(|n| e164_to_contact_id.get(n).copied());
+
         let matched_contact_id = if is_email {
             addr_to_contact_id.get(&addr.to_lowercase()).copied()
+        } else if exact.is_some() {
+            exact
         } else {
             let normalized = crate::models::contact::normalize_address(addr);
             let digits_only: String = addr.chars().filter(|c| c.is_ascii_digit()).collect();
@@ -994,9 +1463,274 @@ pub fn link_contacts_to_handles(conn: &Connection) -> BbResult<u32> {
         }
     }
 
+    // Contact links feed the automatic person grouping
+    crate::models::person::link_persons(conn)?;
+
     Ok(linked)
 }
 
+// ─── Statistics Queries ─────────────────────────────────────────────────────
+
+/// Filter shared by the statistics queries.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatsFilter {
+    /// Only count messages at or after this time (Unix ms).
+    pub since: Option<i64>,
+    /// Only count messages before this time (Unix ms).
+    pub until: Option<i64>,
+    /// Only count messages in this chat.
+    pub chat_id: Option<i64>,
+    /// Offset added to timestamps before bucketing into days and hours.
+    pub utc_offset_secs: i64,
+}
+
+/// Period used to bucket message counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsPeriod {
+    /// `YYYY-MM-DD`
+    Day,
+    /// `YYYY-Www`, the ISO 8601 week (weeks start on Monday, and belong to
+    /// the year their Thursday falls in)
+    Week,
+}
+
+impl StatsPeriod {
+    /// SQL naming the period of the `local_secs` column.
+    fn bucket_sql(&self) -> &'static str {
+        match self {
+            StatsPeriod::Day => "strftime('%Y-%m-%d', local_secs, 'unixepoch')",
+            // Via the week's Thursday, which fixes the ISO year and week number
+            StatsPeriod::Week => {
+                "strftime('%Y', local_secs, 'unixepoch', '-3 days', 'weekday 4') || '-W' ||
+                 printf('%02d', CAST(strftime('%j', local_secs, 'unixepoch', '-3 days', 'weekday 4') AS INTEGER) / 7 + 1)"
+            }
+        }
+    }
+}
+
+/// Message counts for one chat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessageCounts {
+    pub chat_id: i64,
+    pub messages: i64,
+    pub sent: i64,
+    pub received: i64,
+    pub first_ms: i64,
+    pub last_ms: i64,
+}
+
+/// One message in a chat timeline, as used for response-time analysis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimelineEntry {
+    pub chat_id: i64,
+    pub handle_id: Option<i64>,
+    pub is_from_me: bool,
+    pub ms: i64,
+}
+
+/// `date_created` (Unix ms or RFC 3339 text) as Unix ms.
+const DATE_CREATED_MS_SQL: &str = "CASE WHEN date_created NOT GLOB '*[^0-9]*' \
+     THEN CAST(date_created AS INTEGER) \
+     ELSE CAST(ROUND((julianday(date_created) - 2440587.5) * 86400000) AS INTEGER) END";
+
+/// Wrap a statistics query over `r`: dated, non-deleted messages matching the
+/// `StatsFilter` bound as ?1..?4, with an `ms` column (Unix ms) and a
+/// `local_secs` column (Unix seconds shifted by the UTC offset).
+///
+/// Regular messages are those that are neither reactions nor group events.
+fn stats_sql(select: &str) -> String {
+    format!(
+        "WITH m AS (
+            SELECT id, chat_id, handle_id, is_from_me, item_type, associated_message_type,
+                   {DATE_CREATED_MS_SQL} AS ms
+            FROM messages
+            WHERE date_created IS NOT NULL AND date_created <> '' AND date_deleted IS NULL
+              AND (?3 IS NULL OR chat_id = ?3)
+        ),
+        r AS (
+            SELECT *, ms / 1000 + ?4 AS local_secs FROM m
+            WHERE (?1 IS NULL OR ms >= ?1) AND (?2 IS NULL OR ms < ?2)
+        )
+        {select}"
+    )
+}
+
+fn stats_params(filter: &StatsFilter) -> (Option<i64>, Option<i64>, Option<i64>, i64) {
+    (filter.since, filter.until, filter.chat_id, filter.utc_offset_secs)
+}
+
+/// Regular message counts per chat, busiest first.
+pub fn stats_chat_counts(conn: &Connection, filter: &StatsFilter) -> BbResult<Vec<ChatMessageCounts>> {
+    let sql = stats_sql(
+        "SELECT chat_id, COUNT(*), SUM(is_from_me), SUM(1 - is_from_me), MIN(ms), MAX(ms)
+         FROM r
+         WHERE associated_message_type IS NULL AND item_type = 0 AND chat_id IS NOT NULL
+         GROUP BY chat_id
+         ORDER BY COUNT(*) DESC, chat_id ASC",
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map(stats_params(filter), |row| {
+            Ok(ChatMessageCounts {
+                chat_id: row.get(0)?,
+                messages: row.get(1)?,
+                sent: row.get(2)?,
+                received: row.get(3)?,
+                first_ms: row.get(4)?,
+                last_ms: row.get(5)?,
+            })
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Regular message counts per chat and period, as (chat_id, period, count)
+/// ordered by chat and period.
+pub fn stats_period_counts(
+    conn: &Connection,
+    filter: &StatsFilter,
+    period: StatsPeriod,
+) -> BbResult<Vec<(i64, String, i64)>> {
+    let sql = stats_sql(&format!(
+        "SELECT chat_id, {} AS period, COUNT(*)
+         FROM r
+         WHERE associated_message_type IS NULL AND item_type = 0 AND chat_id IS NOT NULL
+         GROUP BY chat_id, period
+         ORDER BY chat_id ASC, period ASC",
+        period.bucket_sql()
+    ));
+    let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map(stats_params(filter), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Regular message counts by weekday (0 = Sunday) and hour of day.
+pub fn stats_hour_heatmap(conn: &Connection, filter: &StatsFilter) -> BbResult<[[i64; 24]; 7]> {
+    let sql = stats_sql(
+        "SELECT CAST(strftime('%w', local_secs, 'unixepoch') AS INTEGER) AS weekday,
+                CAST(strftime('%H', local_secs, 'unixepoch') AS INTEGER) AS hour,
+                COUNT(*)
+         FROM r
+         WHERE associated_message_type IS NULL AND item_type = 0
+         GROUP BY weekday, hour",
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let mut heatmap = [[0i64; 24]; 7];
+    let rows = stmt
+        .query_map(stats_params(filter), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    for (weekday, hour, count) in rows.filter_map(|r| r.ok()) {
+        if (0..7).contains(&weekday) && (0..24).contains(&hour) {
+            heatmap[weekday as usize][hour as usize] = count;
+        }
+    }
+    Ok(heatmap)
+}
+
+/// Regular messages received per sender handle, as (handle_id, count).
+pub fn stats_received_by_handle(conn: &Connection, filter: &StatsFilter) -> BbResult<Vec<(i64, i64)>> {
+    let sql = stats_sql(
+        "SELECT handle_id, COUNT(*)
+         FROM r
+         WHERE associated_message_type IS NULL AND item_type = 0
+           AND is_from_me = 0 AND handle_id IS NOT NULL AND handle_id > 0
+         GROUP BY handle_id",
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map(stats_params(filter), |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Chats with exactly one other participant, as chat_id -> handle_id.
+pub fn direct_chat_handles(conn: &Connection) -> BbResult<HashMap<i64, i64>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT chat_id, MIN(handle_id) FROM chat_handle_join
+             GROUP BY chat_id HAVING COUNT(*) = 1",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Regular messages ordered by chat and time, for response-time analysis.
+pub fn stats_timeline(conn: &Connection, filter: &StatsFilter) -> BbResult<Vec<TimelineEntry>> {
+    let sql = stats_sql(
+        "SELECT chat_id, handle_id, is_from_me, ms
+         FROM r
+         WHERE associated_message_type IS NULL AND item_type = 0 AND chat_id IS NOT NULL
+         ORDER BY chat_id ASC, ms ASC, id ASC",
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map(stats_params(filter), |row| {
+            Ok(TimelineEntry {
+                chat_id: row.get(0)?,
+                handle_id: row.get(1)?,
+                is_from_me: row.get::<_, i32>(2)? != 0,
+                ms: row.get(3)?,
+            })
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Attachment count and total bytes per MIME class (`image`, `video`, ...),
+/// largest first. Attachments without a usable MIME type count as `other`.
+pub fn stats_attachment_volume(conn: &Connection, filter: &StatsFilter) -> BbResult<Vec<(String, i64, i64)>> {
+    let sql = stats_sql(
+        "SELECT COALESCE(NULLIF(substr(a.mime_type, 1, instr(a.mime_type, '/') - 1), ''), 'other') AS class,
+                COUNT(*), COALESCE(SUM(a.total_bytes), 0)
+         FROM r JOIN attachments a ON a.message_id = r.id
+         GROUP BY class
+         ORDER BY 3 DESC, class ASC",
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map(stats_params(filter), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Reaction counts as (associated_message_type, is_from_me, count).
+pub fn stats_reaction_counts(conn: &Connection, filter: &StatsFilter) -> BbResult<Vec<(String, bool, i64)>> {
+    let sql = stats_sql(
+        "SELECT associated_message_type, is_from_me, COUNT(*)
+         FROM r
+         WHERE associated_message_type IS NOT NULL
+         GROUP BY associated_message_type, is_from_me",
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map(stats_params(filter), |row| {
+            Ok((row.get(0)?, row.get::<_, i32>(1)? != 0, row.get(2)?))
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1178,6 +1912,115 @@ mod tests {
         assert_eq!(results.len(), 0);
     }
 
+    #[test]
+    fn test_set_default_phone_region_saves_and_renormalizes() {
+        let conn = setup_db();
+        crate::phone::set_default_region("US");
+        let mut handle = Handle::from_server_map(&serde_json::json!({
+            "address": "(650) 253-0000", "service": "SMS"
+        })).unwrap();
+        handle.save(&conn).unwrap();
+        conn.execute("UPDATE handles SET normalized_address = '6502530000'", []).unwrap();
+
+        let (handles, _) = set_default_phone_region(&conn, "us").unwrap();
+        assert_eq!(handles, 1);
+        assert_eq!(Settings::get(&conn, keys::DEFAULT_PHONE_REGION).unwrap().as_deref(), Some("US"));
+        assert_eq!(
+            find_handle_normalized_address(&conn, "(650) 253-0000").unwrap().as_deref(),
+            Some("+16502530000")
+        );
+        apply_saved_phone_region(&conn).unwrap();
+        assert_eq!(crate::phone::default_region(), "US");
+    }
+
+    #[test]
+    fn test_set_default_phone_region_rejects_unknown_region() {
+        let conn = setup_db();
+        Settings::set(&conn, keys::DEFAULT_PHONE_REGION, "GB").unwrap();
+        for bad in ["ZZ", "USA", "1"] {
+            assert!(matches!(set_default_phone_region(&conn, bad), Err(BbError::InvalidInput(_))));
+        }
+        assert_eq!(Settings::get(&conn, keys::DEFAULT_PHONE_REGION).unwrap().as_deref(), Some("GB"));
+    }
+
+    #[test]
+    fn test_my_addresses() {
+        let conn = setup_db();
+        assert!(my_addresses(&conn).unwrap().is_empty());
+
+        assert!(save_my_address(&conn, "ann@icloud.com").unwrap());
+        assert!(save_my_address(&conn, " +15551234567 ").unwrap());
+        assert!(!save_my_address(&conn, "Ann@iCloud.com").unwrap());
+        assert!(!save_my_address(&conn, "  ").unwrap());
+        Settings::set(&conn, keys::ICLOUD_ACCOUNT, "ann@me.com").unwrap();
+
+        assert_eq!(my_addresses(&conn).unwrap(), vec!["ann@me.com", "ann@icloud.com", "+15551234567"]);
+    }
+
+    #[test]
+    fn test_find_contact_by_normalized_phone() {
+        let conn = setup_db();
+        crate::phone::set_default_region("US");
+        let mut contact = Contact::from_server_map(&serde_json::json!({
+            "id": "c1",
+            "displayName": "Test User",
+            "phoneNumbers": ["(650) 253-0000", "+44 20 7031 3000"]
+        })).unwrap();
+        contact.save(&conn).unwrap();
+
+        let found = find_contact_by_normalized_phone(&conn, "+16502530000").unwrap();
+        assert_eq!(found.unwrap().display_name, "Test User");
+        assert!(find_contact_by_normalized_phone(&conn, "+442070313000").unwrap().is_some());
+        // Same trailing digits, different country
+        assert!(find_contact_by_normalized_phone(&conn, "+446502530000").unwrap().is_none());
+
+        // Handle in GB with a trunk prefix links exactly
+        let mut handle = Handle::from_server_map(&serde_json::json!({
+            "address": "020 7031 3000", "service": "SMS", "country": "gb"
+        })).unwrap();
+        handle.save(&conn).unwrap();
+        assert_eq!(handle.normalized_address.as_deref(), Some("+442070313000"));
+        assert_eq!(link_contacts_to_handles(&conn).unwrap(), 1);
+
+        // Re-saving with fewer phones drops the stale index rows
+        contact.phones = r#"["(650) 253-0000"]"#.to_string();
+        contact.save(&conn).unwrap();
+        assert!(find_contact_by_normalized_phone(&conn, "+442070313000").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_person_chats_span_handles() {
+        let conn = setup_db();
+        let mut sms = Handle::from_server_map(&serde_json::json!({
+            "address": "+16502530000", "service": "SMS"
+        })).unwrap();
+        let sms_id = sms.save(&conn).unwrap();
+        let mut imessage = Handle::from_server_map(&serde_json::json!({
+            "address": "+16502530000", "service": "iMessage"
+        })).unwrap();
+        let imessage_id = imessage.save(&conn).unwrap();
+        crate::models::person::link_persons(&conn).unwrap();
+        let person = crate::Person::find_by_handle(&conn, sms_id).unwrap().unwrap().id.unwrap();
+
+        let sms_chat = insert_chat(&conn, "SMS;-;+16502530000");
+        let imessage_chat = insert_chat(&conn, "iMessage;-;+16502530000");
+        let group = insert_chat(&conn, "iMessage;+;group");
+        conn.execute_batch(&format!(
+            "INSERT INTO chat_handle_join (chat_id, handle_id) VALUES
+                ({sms_chat}, {sms_id}), ({imessage_chat}, {imessage_id}),
+                ({group}, {sms_id}), ({group}, {imessage_id});"
+        )).unwrap();
+        insert_message(&conn, "m1", imessage_chat, "2024-01-01T00:00:00Z", true);
+        conn.execute("UPDATE messages SET text = 'lunch?' WHERE guid = 'm1'", []).unwrap();
+
+        let chats = person_chats(&conn, person).unwrap();
+        assert_eq!(chats.len(), 2);
+        assert!(chats.iter().all(|c| c.id != Some(group)));
+
+        let found = search_person_messages(&conn, person, "lunch", 10).unwrap();
+        assert_eq!(found.len(), 1);
+    }
+
     #[test]
     fn test_contact_email_search() {
         let conn = setup_db();
@@ -1198,11 +2041,41 @@ mod tests {
         let chat_id = insert_chat(&conn, "chat-1");
         insert_message(&conn, "msg-1", chat_id, "2024-01-01T00:00:00Z", false);
 
-        let details = list_chats_with_details(&conn, 0, 10, true).unwrap();
+        let details = list_chats_with_details(&conn, 0, 10, true, None).unwrap();
         assert_eq!(details.len(), 1);
         assert_eq!(details[0].unread_count, 1);
     }
 
+    #[test]
+    fn test_chats_in_folder_with_unread_counts() {
+        let conn = setup_db();
+        let first = insert_chat(&conn, "chat-1");
+        let second = insert_chat(&conn, "chat-2");
+        insert_chat(&conn, "chat-3");
+        insert_message(&conn, "msg-1", first, "2024-01-01T00:00:00Z", false);
+        insert_message(&conn, "msg-2", first, "2024-01-02T00:00:00Z", false);
+        insert_message(&conn, "msg-3", second, "2024-01-03T00:00:00Z", true);
+
+        let mut folder = ChatFolder::new("Work");
+        folder.save(&conn).unwrap();
+        folder.add_chat(&conn, first).unwrap();
+        folder.add_chat(&conn, second).unwrap();
+
+        let details = list_chats_with_details(&conn, 0, 10, false, Some(&folder)).unwrap();
+        assert_eq!(details.len(), 2);
+        let unread = ChatFolder::smart("Unread", crate::models::chat_folder::FolderRule::Unread);
+        let details = list_chats_with_details(&conn, 0, 10, false, Some(&unread)).unwrap();
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].chat.guid, "chat-1");
+
+        let summaries = folder_summaries(&conn).unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(
+            (summaries[0].chat_count, summaries[0].unread_chats, summaries[0].unread_count),
+            (2, 1, 2)
+        );
+    }
+
     #[test]
     fn test_bookmarked_messages() {
         let conn = setup_db();
@@ -1220,4 +2093,87 @@ mod tests {
         let results = bookmarked_messages(&conn, 10).unwrap();
         assert_eq!(results.len(), 1);
     }
+
+    #[test]
+    fn test_chat_drafts() {
+        let conn = setup_db();
+        insert_chat(&conn, "chat-1");
+        insert_chat(&conn, "chat-2");
+
+        assert!(set_chat_draft(&conn, "chat-1", Some("half-typed"), "[]").unwrap());
+        assert!(set_chat_draft(&conn, "chat-2", None, r#"["/tmp/a.jpg"]"#).unwrap());
+        assert!(!set_chat_draft(&conn, "missing", Some("x"), "[]").unwrap());
+        assert_eq!(chats_with_drafts(&conn).unwrap().len(), 2);
+
+        assert!(remove_draft_attachment(&conn, "chat-2", "/tmp/a.jpg").unwrap());
+        assert!(!remove_draft_attachment(&conn, "chat-2", "/tmp/a.jpg").unwrap());
+        assert!(set_chat_draft(&conn, "chat-2", None, r#"["/tmp/a.jpg"]"#).unwrap());
+
+        // Only the sent text clears the draft text
+        assert!(!clear_draft_text(&conn, "chat-1", "typed later").unwrap());
+        assert!(clear_draft_text(&conn, "chat-1", "half-typed ").unwrap());
+        assert!(set_chat_draft(&conn, "chat-1", Some("half-typed"), "[]").unwrap());
+
+        assert!(clear_chat_draft(&conn, "chat-1").unwrap());
+        assert!(!clear_chat_draft(&conn, "chat-1").unwrap());
+        let remaining = chats_with_drafts(&conn).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].draft_attachment_paths(), vec!["/tmp/a.jpg"]);
+    }
+
+    #[test]
+    fn test_stats_queries_mixed_date_formats() {
+        let conn = setup_db();
+        let chat = insert_chat(&conn, "stats-chat");
+        // 2023-11-14 22:13:20 UTC, a Tuesday, stored as Unix ms and as RFC 3339
+        insert_message(&conn, "s1", chat, "1700000000000", true);
+        insert_message(&conn, "s2", chat, "2023-11-14T22:43:20+00:00", false);
+        insert_message(&conn, "s3", chat, "2023-11-20T08:00:00Z", false);
+
+        let filter = StatsFilter::default();
+        let counts = stats_chat_counts(&conn, &filter).unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!((counts[0].messages, counts[0].sent, counts[0].received), (3, 1, 2));
+        assert_eq!(counts[0].first_ms, 1_700_000_000_000);
+
+        let days = stats_period_counts(&conn, &filter, StatsPeriod::Day).unwrap();
+        assert_eq!(days, vec![
+            (chat, "2023-11-14".to_string(), 2),
+            (chat, "2023-11-20".to_string(), 1),
+        ]);
+
+        let heatmap = stats_hour_heatmap(&conn, &filter).unwrap();
+        assert_eq!(heatmap[2][22], 2);
+        assert_eq!(heatmap[1][8], 1);
+
+        // A +2h offset moves the evening messages into the next day
+        let shifted = StatsFilter { utc_offset_secs: 7200, ..filter };
+        let days = stats_period_counts(&conn, &shifted, StatsPeriod::Day).unwrap();
+        assert_eq!(days[0].1, "2023-11-15");
+
+        let since = StatsFilter { since: Some(1_700_000_000_001), ..filter };
+        assert_eq!(stats_timeline(&conn, &since).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_stats_iso_weeks_across_new_year() {
+        let conn = setup_db();
+        let chat = insert_chat(&conn, "weeks-chat");
+        // Sunday 2024-12-29 ends 2024-W52; Monday 2024-12-30 starts 2025-W01,
+        // which runs to Sunday 2025-01-05
+        insert_message(&conn, "w1", chat, "2024-12-29T12:00:00Z", true);
+        insert_message(&conn, "w2", chat, "2024-12-30T12:00:00Z", true);
+        insert_message(&conn, "w3", chat, "2025-01-05T12:00:00Z", false);
+        insert_message(&conn, "w4", chat, "2025-01-06T12:00:00Z", false);
+        // Friday 2021-01-01 still belongs to 2020-W53
+        insert_message(&conn, "w5", chat, "2021-01-01T12:00:00Z", false);
+
+        let weeks = stats_period_counts(&conn, &StatsFilter::default(), StatsPeriod::Week).unwrap();
+        assert_eq!(weeks, vec![
+            (chat, "2020-W53".to_string(), 1),
+            (chat, "2024-W52".to_string(), 1),
+            (chat, "2025-W01".to_string(), 2),
+            (chat, "2025-W02".to_string(), 1),
+        ]);
+    }
 }
@@ -22,14 +22,28 @@ pub fn drop_tables(conn: &Connection) -> BbResult<()> {
         "DROP TABLE IF EXISTS chat_handle_join;
          DROP TABLE IF EXISTS attachments;
          DROP TABLE IF EXISTS messages;
+         DROP TABLE IF EXISTS person_handles;
+         DROP TABLE IF EXISTS persons;
          DROP TABLE IF EXISTS handles;
          DROP TABLE IF EXISTS chats;
+         DROP TABLE IF EXISTS contact_phones;
          DROP TABLE IF EXISTS contacts;
          DROP TABLE IF EXISTS fcm_data;
          DROP TABLE IF EXISTS themes;
          DROP TABLE IF EXISTS theme_entries;
          DROP TABLE IF EXISTS scheduled_messages;
          DROP TABLE IF EXISTS settings;
+         DROP TABLE IF EXISTS message_tombstones;
+         DROP TABLE IF EXISTS retention_policies;
+         DROP TABLE IF EXISTS bookmark_tags;
+         DROP TABLE IF EXISTS bookmarks;
+         DROP TABLE IF EXISTS chat_folder_members;
+         DROP TABLE IF EXISTS chat_folders;
+         DROP TABLE IF EXISTS notification_rules;
+         DROP TABLE IF EXISTS notification_digest_entries;
+         DROP TABLE IF EXISTS outbox;
+         DROP TABLE IF EXISTS send_queue;
+         DROP TABLE IF EXISTS link_previews;
          DROP TABLE IF EXISTS schema_version;",
     )
     .map_err(|e| BbError::Database(format!("failed to drop tables: {e}")))?;
@@ -138,13 +152,29 @@ CREATE TABLE IF NOT EXISTS handles (
     color                           TEXT,
     default_phone                   TEXT,
     default_email                   TEXT,
-    contact_id                      INTEGER REFERENCES contacts(id)
+    contact_id                      INTEGER REFERENCES contacts(id),
+    normalized_address              TEXT
 );
 
 CREATE INDEX IF NOT EXISTS idx_handles_address ON handles(address);
 CREATE INDEX IF NOT EXISTS idx_handles_unique ON handles(unique_address_service);
 CREATE INDEX IF NOT EXISTS idx_handles_contact ON handles(contact_id);
 
+-- Persons grouping the handles of one human across services
+CREATE TABLE IF NOT EXISTS persons (
+    id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+    name                            TEXT,
+    date_created                    TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS person_handles (
+    handle_id                       INTEGER PRIMARY KEY REFERENCES handles(id) ON DELETE CASCADE,
+    person_id                       INTEGER NOT NULL REFERENCES persons(id) ON DELETE CASCADE,
+    manual                          INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_person_handles_person ON person_handles(person_id);
+
 -- Attachments
 CREATE TABLE IF NOT EXISTS attachments (
     id                              INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -181,6 +211,15 @@ CREATE TABLE IF NOT EXISTS contacts (
 CREATE INDEX IF NOT EXISTS idx_contacts_external_id ON contacts(external_id);
 CREATE INDEX IF NOT EXISTS idx_contacts_display_name ON contacts(display_name);
 
+-- E.164 phone numbers per contact, for exact handle matching
+CREATE TABLE IF NOT EXISTS contact_phones (
+    contact_id                      INTEGER NOT NULL REFERENCES contacts(id) ON DELETE CASCADE,
+    number                          TEXT NOT NULL,
+    PRIMARY KEY (contact_id, number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_contact_phones_number ON contact_phones(number);
+
 -- Chat-Handle join table (many-to-many for chat participants)
 CREATE TABLE IF NOT EXISTS chat_handle_join (
     chat_id                         INTEGER NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
@@ -235,10 +274,14 @@ CREATE TABLE IF NOT EXISTS scheduled_messages (
     schedule_type                   TEXT,
     schedule_interval               INTEGER,
     schedule_interval_type          TEXT,
+    recurrence                      TEXT,
+    time_zone                       TEXT,
+    recurrence_start                TEXT,
     status                          TEXT NOT NULL DEFAULT 'pending',
     error                           TEXT,
     sent_at                         TEXT,
-    created_at                      TEXT NOT NULL
+    created_at                      TEXT NOT NULL,
+    claimed_at                      INTEGER
 );
 
 -- Settings key-value store
@@ -248,6 +291,132 @@ CREATE TABLE IF NOT EXISTS settings (
 );
 
 CREATE INDEX IF NOT EXISTS idx_settings_key ON settings(key);
+
+-- Audit trail of unsent message parts (the original text is lost server-side)
+CREATE TABLE IF NOT EXISTS message_tombstones (
+    id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_guid                    TEXT NOT NULL,
+    chat_id                         INTEGER,
+    part_index                      INTEGER NOT NULL DEFAULT 0,
+    original_text                   TEXT,
+    date_unsent                     TEXT NOT NULL,
+    UNIQUE(message_guid, part_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_tombstones_guid ON message_tombstones(message_guid);
+
+-- Data retention rules (chat_id NULL = global policy)
+CREATE TABLE IF NOT EXISTS retention_policies (
+    id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id                         INTEGER UNIQUE REFERENCES chats(id) ON DELETE CASCADE,
+    message_max_age_days            INTEGER,
+    attachment_max_age_days         INTEGER,
+    exempt_pinned                   INTEGER NOT NULL DEFAULT 1,
+    enabled                         INTEGER NOT NULL DEFAULT 1,
+    date_updated                    TEXT NOT NULL
+);
+
+-- Bookmark annotations (messages.is_bookmarked mirrors row presence)
+CREATE TABLE IF NOT EXISTS bookmarks (
+    message_guid                    TEXT PRIMARY KEY,
+    note                            TEXT,
+    date_created                    TEXT NOT NULL,
+    date_modified                   TEXT NOT NULL,
+    date_synced                     TEXT
+);
+
+CREATE TABLE IF NOT EXISTS bookmark_tags (
+    message_guid                    TEXT NOT NULL REFERENCES bookmarks(message_guid) ON DELETE CASCADE,
+    tag                             TEXT NOT NULL,
+    PRIMARY KEY (message_guid, tag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_bookmark_tags_tag ON bookmark_tags(tag);
+
+-- User-defined chat folders (rule NULL = manual folder, else smart folder JSON rule)
+CREATE TABLE IF NOT EXISTS chat_folders (
+    id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+    name                            TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    color                           TEXT,
+    rule                            TEXT,
+    sort_index                      INTEGER NOT NULL DEFAULT 0,
+    date_created                    TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS chat_folder_members (
+    folder_id                       INTEGER NOT NULL REFERENCES chat_folders(id) ON DELETE CASCADE,
+    chat_id                         INTEGER NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    PRIMARY KEY (folder_id, chat_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_folder_members_chat ON chat_folder_members(chat_id);
+
+-- Notification rules, evaluated in sort_index order
+CREATE TABLE IF NOT EXISTS notification_rules (
+    id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+    name                            TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    conditions                      TEXT NOT NULL,
+    action                          TEXT NOT NULL,
+    enabled                         INTEGER NOT NULL DEFAULT 1,
+    sort_index                      INTEGER NOT NULL DEFAULT 0,
+    date_created                    TEXT NOT NULL
+);
+
+-- Incoming messages held for their chat's next notification digest
+CREATE TABLE IF NOT EXISTS notification_digest_entries (
+    id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_guid                       TEXT NOT NULL,
+    chat_title                      TEXT NOT NULL,
+    message_guid                    TEXT NOT NULL UNIQUE,
+    sender                          TEXT,
+    text                            TEXT NOT NULL DEFAULT '',
+    reaction                        TEXT,
+    buffered_at                     INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_notification_digest_entries_chat ON notification_digest_entries(chat_guid);
+
+-- Pending server mutations for chats, applied locally and replayed in id order
+CREATE TABLE IF NOT EXISTS outbox (
+    id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_guid                       TEXT NOT NULL,
+    mutation                        TEXT NOT NULL,
+    previous                        TEXT,
+    attempts                        INTEGER NOT NULL DEFAULT 0,
+    last_error                      TEXT,
+    date_created                    TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_chat ON outbox(chat_guid);
+
+-- Outgoing messages waiting to be sent or retried (next_attempt/date_created in epoch ms;
+-- waiting = composed offline, sent when the connection returns; claimed_by = the
+-- process sending the row now, since claimed_at in epoch ms)
+CREATE TABLE IF NOT EXISTS send_queue (
+    id                              TEXT PRIMARY KEY,
+    chat_guid                       TEXT NOT NULL,
+    text                            TEXT,
+    file_path                       TEXT,
+    mime_type                       TEXT,
+    temp_guid                       TEXT,
+    attempts                        INTEGER NOT NULL DEFAULT 0,
+    max_attempts                    INTEGER NOT NULL DEFAULT 5,
+    next_attempt                    INTEGER NOT NULL DEFAULT 0,
+    waiting                         INTEGER NOT NULL DEFAULT 0,
+    date_created                    INTEGER NOT NULL,
+    first_attempt                   INTEGER,
+    claimed_by                      TEXT,
+    claimed_at                      INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_send_queue_temp_guid ON send_queue(temp_guid);
+
+-- Link previews generated for outgoing URLs (preview is UrlPreviewData JSON,
+-- NULL when the page had none; fetched_at in epoch ms)
+CREATE TABLE IF NOT EXISTS link_previews (
+    url                             TEXT PRIMARY KEY,
+    preview                         TEXT,
+    fetched_at                      INTEGER NOT NULL
+);
 "#;
 
 #[cfg(test)]
@@ -261,8 +430,10 @@ mod tests {
 
         // Verify key tables exist
         let tables = ["chats", "messages", "handles", "attachments", "contacts",
-                       "fcm_data", "themes", "scheduled_messages", "settings",
-                       "chat_handle_join", "schema_version"];
+                       "contact_phones", "fcm_data", "themes", "scheduled_messages", "settings",
+                       "chat_handle_join", "message_tombstones", "retention_policies",
+                       "persons", "person_handles", "bookmarks", "bookmark_tags",
+                       "chat_folders", "chat_folder_members", "notification_rules", "notification_digest_entries", "outbox", "send_queue", "link_previews", "schema_version"];
         for table in &tables {
             let count: i64 = conn
                 .query_row(
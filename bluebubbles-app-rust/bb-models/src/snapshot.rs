@@ -0,0 +1,493 @@
+//! Rotating local database snapshots.
+//!
+//! Snapshots are full copies of the live database taken with the SQLite
+//! online backup API, so they can be made while the app keeps writing.
+//! Every snapshot is integrity-checked before it is kept, and old snapshots
+//! are pruned into hourly, daily and weekly buckets.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use bb_core::config::SnapshotConfig;
+use bb_core::error::{BbError, BbResult};
+
+use crate::db::Database;
+use crate::encryption::{self, DatabaseKey};
+
+/// Format of snapshot IDs (a UTC timestamp, e.g. `20240101T120000Z`).
+const ID_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// File name prefix and extension of snapshot files.
+const FILE_PREFIX: &str = "bluebubbles-";
+const FILE_EXT: &str = ".db";
+
+/// Pages copied per backup step. Small steps let writers interleave.
+const PAGES_PER_STEP: i32 = 256;
+
+/// A snapshot file on disk.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    /// Snapshot ID, usable with [`SnapshotStore::restore`].
+    pub id: String,
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+/// A directory of database snapshots.
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    /// Create a store rooted at `dir`. The directory is created on first snapshot.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Create a store from config, falling back to `<data_dir>/snapshots`.
+    pub fn from_config(config: &SnapshotConfig, data_dir: &Path) -> Self {
+        if config.directory.is_empty() {
+            Self::new(data_dir.join("snapshots"))
+        } else {
+            Self::new(&config.directory)
+        }
+    }
+
+    /// The snapshot directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Copy the live database into a new snapshot and verify it.
+    ///
+    /// `key` must be the key the live database is opened with; the snapshot
+    /// is encrypted with the same key.
+    pub fn create(&self, db: &Database, key: Option<&DatabaseKey>) -> BbResult<SnapshotInfo> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let created_at = Utc::now();
+        let id = created_at.format(ID_FORMAT).to_string();
+        let path = self.path_for(&id);
+        if path.exists() {
+            return Err(BbError::Database(format!("snapshot {id} already exists")));
+        }
+
+        let partial = encryption::sibling_path(&path, "partial");
+        if partial.exists() {
+            std::fs::remove_file(&partial)?;
+        }
+
+        let copy = || -> BbResult<()> {
+            let src = db.conn()?;
+            let mut dst = open(&partial, key, false)?;
+            copy_pages(&src, &mut dst).map_err(|e| BbError::Database(format!("snapshot backup failed: {e}")))
+        };
+
+        if let Err(e) = copy().and_then(|_| verify(&partial, key)) {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        std::fs::rename(&partial, &path)?;
+        let size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        info!("created database snapshot {id} ({size_bytes} bytes)");
+
+        Ok(SnapshotInfo {
+            id,
+            path,
+            created_at,
+            size_bytes,
+        })
+    }
+
+    /// List snapshots, newest first.
+    pub fn list(&self) -> BbResult<Vec<SnapshotInfo>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut snapshots: Vec<SnapshotInfo> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let id = name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_EXT)?.to_string();
+                let created_at = parse_id(&id)?;
+                let size_bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                Some(SnapshotInfo {
+                    id,
+                    path: entry.path(),
+                    created_at,
+                    size_bytes,
+                })
+            })
+            .collect();
+
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        Ok(snapshots)
+    }
+
+    /// Look up a snapshot by ID.
+    pub fn find(&self, id: &str) -> BbResult<SnapshotInfo> {
+        self.list()?
+            .into_iter()
+            .find(|s| s.id == id)
+            .ok_or_else(|| BbError::InvalidInput(format!("no snapshot with id {id}")))
+    }
+
+    /// Delete snapshots that fall outside the retention policy.
+    ///
+    /// Returns the snapshots that were removed.
+    pub fn prune(&self, config: &SnapshotConfig) -> BbResult<Vec<SnapshotInfo>> {
+        let snapshots = self.list()?;
+        let keep = retained_ids(&snapshots, config);
+
+        let mut removed = Vec::new();
+        for snapshot in snapshots {
+            if keep.contains(&snapshot.id) {
+                continue;
+            }
+            match std::fs::remove_file(&snapshot.path) {
+                Ok(()) => {
+                    debug!("pruned database snapshot {}", snapshot.id);
+                    removed.push(snapshot);
+                }
+                Err(e) => warn!("failed to prune snapshot {}: {e}", snapshot.id),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Replace the database at `db_path` with a snapshot.
+    ///
+    /// The snapshot is verified first, and the current file is kept next to
+    /// the database as `<name>-pre-restore`. Fails while any [`Database`]
+    /// for this file is open, in this process or another; callers must drop
+    /// their own pool before calling this, or use [`Self::restore_into`].
+    pub fn restore(
+        &self,
+        id: &str,
+        db_path: &Path,
+        key: Option<&DatabaseKey>,
+    ) -> BbResult<SnapshotInfo> {
+        let snapshot = self.find(id)?;
+        verify(&snapshot.path, key)?;
+
+        // Held until the new file is in place, so nothing opens the
+        // database halfway through the swap.
+        let _lock = crate::db::lock_exclusive(db_path)?;
+        if db_path.exists() {
+            ensure_not_in_use(db_path, key)?;
+        }
+
+        let tmp_path = encryption::sibling_path(db_path, "restore-tmp");
+        std::fs::copy(&snapshot.path, &tmp_path)?;
+
+        if db_path.exists() {
+            let previous = encryption::sibling_path(db_path, "pre-restore");
+            std::fs::rename(db_path, &previous)?;
+            info!("previous database kept at {}", previous.display());
+        }
+        for suffix in ["-wal", "-shm"] {
+            let side = encryption::sidecar_path(db_path, suffix);
+            if side.exists() {
+                std::fs::remove_file(&side)?;
+            }
+        }
+        std::fs::rename(&tmp_path, db_path)?;
+
+        info!("restored database from snapshot {id}");
+        Ok(snapshot)
+    }
+
+    /// Restore a snapshot into a database that stays open, for a running
+    /// app.
+    ///
+    /// The snapshot is verified, the current contents are kept next to the
+    /// database as `<name>-pre-restore`, and the snapshot's pages are then
+    /// copied over the live database with the online backup API, so `db`
+    /// and its clones keep working. The restored schema is brought up to
+    /// date afterwards. Callers must stop everything writing to the
+    /// database first.
+    pub fn restore_into(&self, id: &str, db: &Database, key: Option<&DatabaseKey>) -> BbResult<SnapshotInfo> {
+        let snapshot = self.find(id)?;
+        verify(&snapshot.path, key)?;
+
+        let mut live = db.conn()?;
+        if let Some(db_path) = live.path().filter(|p| !p.is_empty()).map(PathBuf::from) {
+            let previous = encryption::sibling_path(&db_path, "pre-restore");
+            if previous.exists() {
+                std::fs::remove_file(&previous)?;
+            }
+            let mut dst = open(&previous, key, false)?;
+            copy_pages(&live, &mut dst)
+                .map_err(|e| BbError::Database(format!("saving the current database failed: {e}")))?;
+            info!("previous database kept at {}", previous.display());
+        }
+
+        let src = open(&snapshot.path, key, true)?;
+        copy_pages(&src, &mut live).map_err(|e| BbError::Database(format!("snapshot restore failed: {e}")))?;
+        crate::schema::create_tables(&live)?;
+        crate::migrations::run_migrations(&live)?;
+
+        info!("restored database from snapshot {id} in place");
+        Ok(snapshot)
+    }
+
+    fn path_for(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{FILE_PREFIX}{id}{FILE_EXT}"))
+    }
+}
+
+/// Run `PRAGMA integrity_check` against a snapshot file.
+pub fn verify(path: &Path, key: Option<&DatabaseKey>) -> BbResult<()> {
+    let conn = open(path, key, true)?;
+    let result: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| BbError::Database(format!("cannot read snapshot {}: {e}", path.display())))?;
+
+    if result != "ok" {
+        return Err(BbError::IntegrityCheck(format!("{}: {result}", path.display())));
+    }
+    Ok(())
+}
+
+/// Pick which snapshots to keep: the newest overall, plus the newest one in
+/// each of the most recent `keep_hourly` hours, `keep_daily` days and
+/// `keep_weekly` ISO weeks that have a snapshot.
+pub fn retained_ids(snapshots: &[SnapshotInfo], config: &SnapshotConfig) -> HashSet<String> {
+    let mut sorted: Vec<&SnapshotInfo> = snapshots.iter().collect();
+    sorted.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+
+    let mut keep = HashSet::new();
+    if let Some(newest) = sorted.first() {
+        keep.insert(newest.id.clone());
+    }
+
+    let mut keep_buckets = |limit: u32, bucket: &dyn Fn(&DateTime<Utc>) -> String| {
+        let mut seen = HashSet::new();
+        for snapshot in &sorted {
+            if seen.len() >= limit as usize {
+                break;
+            }
+            if seen.insert(bucket(&snapshot.created_at)) {
+                keep.insert(snapshot.id.clone());
+            }
+        }
+    };
+
+    keep_buckets(config.keep_hourly, &|t| t.format("%Y%m%d%H").to_string());
+    keep_buckets(config.keep_daily, &|t| t.format("%Y%m%d").to_string());
+    keep_buckets(config.keep_weekly, &|t| {
+        let week = t.iso_week();
+        format!("{}-{}", week.year(), week.week())
+    });
+
+    keep
+}
+
+fn parse_id(id: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(id, ID_FORMAT)
+        .ok()
+        .map(|naive| naive.and_utc())
+}
+
+/// Copy every page of `src` into `dst` with the online backup API.
+fn copy_pages(src: &Connection, dst: &mut Connection) -> rusqlite::Result<()> {
+    Backup::new(src, dst)?.run_to_completion(PAGES_PER_STEP, Duration::from_millis(10), None)
+}
+
+fn open(path: &Path, key: Option<&DatabaseKey>, read_only: bool) -> BbResult<Connection> {
+    let conn = if read_only {
+        Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+    } else {
+        Connection::open(path)
+    }
+    .map_err(|e| BbError::Database(e.to_string()))?;
+
+    if let Some(key) = key {
+        encryption::apply_key(&conn, key).map_err(|e| BbError::Database(e.to_string()))?;
+    }
+    Ok(conn)
+}
+
+/// Fail if a connection from outside [`Database`] holds the database, by
+/// briefly taking an exclusive SQLite lock on it, then fold the WAL into the
+/// main file so the copy kept as `-pre-restore` is complete.
+fn ensure_not_in_use(db_path: &Path, key: Option<&DatabaseKey>) -> BbResult<()> {
+    let conn = open(db_path, key, false)?;
+    conn.busy_timeout(Duration::from_millis(500))
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    conn.execute_batch("BEGIN EXCLUSIVE; ROLLBACK;").map_err(|e| {
+        BbError::Database(format!(
+            "database is in use by another process; close the app and retry ({e})"
+        ))
+    })?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+        .map_err(|e| BbError::Database(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bb_core::config::DatabaseConfig;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn info_at(y: i32, m: u32, d: u32, h: u32, min: u32) -> SnapshotInfo {
+        let created_at = Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap();
+        SnapshotInfo {
+            id: created_at.format(ID_FORMAT).to_string(),
+            path: PathBuf::new(),
+            created_at,
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_create_list_restore() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+
+        let snapshot = {
+            let db = Database::init(&db_path, &DatabaseConfig::default()).unwrap();
+            db.conn().unwrap().execute(
+                "INSERT INTO handles (address, service, unique_address_service) VALUES ('a@b.c', 'iMessage', 'a@b.c/iMessage')",
+                [],
+            ).unwrap();
+            let snapshot = store.create(&db, None).unwrap();
+            db.conn().unwrap().execute(
+                "INSERT INTO handles (address, service, unique_address_service) VALUES ('d@e.f', 'iMessage', 'd@e.f/iMessage')",
+                [],
+            ).unwrap();
+            snapshot
+        };
+
+        let listed = store.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, snapshot.id);
+        assert!(listed[0].size_bytes > 0);
+        assert!(verify(&listed[0].path, None).is_ok());
+
+        store.restore(&snapshot.id, &db_path, None).unwrap();
+        assert!(encryption::sibling_path(&db_path, "pre-restore").exists());
+
+        let db = Database::init(&db_path, &DatabaseConfig::default()).unwrap();
+        assert_eq!(db.stats().unwrap().handles, 1);
+    }
+
+    #[test]
+    fn test_restore_refuses_open_database() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+
+        let db = Database::init(&db_path, &DatabaseConfig::default()).unwrap();
+        let snapshot = store.create(&db, None).unwrap();
+        let other = db.clone();
+        drop(db);
+
+        // An idle pool still counts as in use.
+        assert!(store.restore(&snapshot.id, &db_path, None).is_err());
+        assert!(!encryption::sibling_path(&db_path, "pre-restore").exists());
+
+        drop(other);
+        assert!(store.restore(&snapshot.id, &db_path, None).is_ok());
+    }
+
+    #[test]
+    fn test_restore_into_open_database() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("test.db");
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+        let insert = |db: &Database, address: &str| {
+            db.conn().unwrap().execute(
+                "INSERT INTO handles (address, service, unique_address_service) VALUES (?1, 'iMessage', ?1 || '/iMessage')",
+                [address],
+            ).unwrap();
+        };
+
+        let db = Database::init(&db_path, &DatabaseConfig::default()).unwrap();
+        insert(&db, "a@b.c");
+        let snapshot = store.create(&db, None).unwrap();
+        insert(&db, "d@e.f");
+
+        // Every clone of the pool sees the restored contents
+        let other = db.clone();
+        store.restore_into(&snapshot.id, &db, None).unwrap();
+        assert_eq!(other.stats().unwrap().handles, 1);
+        insert(&other, "g@h.i");
+        assert_eq!(db.stats().unwrap().handles, 2);
+
+        let previous = encryption::sibling_path(&db_path, "pre-restore");
+        let conn = Connection::open(&previous).unwrap();
+        let kept: i64 = conn.query_row("SELECT COUNT(*) FROM handles", [], |row| row.get(0)).unwrap();
+        assert_eq!(kept, 2);
+    }
+
+    #[test]
+    fn test_restore_unknown_id() {
+        let dir = TempDir::new().unwrap();
+        let store = SnapshotStore::new(dir.path());
+        assert!(store.restore("nope", &dir.path().join("test.db"), None).is_err());
+    }
+
+    #[test]
+    fn test_verify_rejects_garbage() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("bluebubbles-20240101T000000Z.db");
+        std::fs::write(&path, b"definitely not sqlite").unwrap();
+        assert!(verify(&path, None).is_err());
+    }
+
+    #[test]
+    fn test_retention_buckets() {
+        let config = SnapshotConfig {
+            keep_hourly: 2,
+            keep_daily: 2,
+            keep_weekly: 1,
+            ..SnapshotConfig::default()
+        };
+        let snapshots = vec![
+            info_at(2024, 3, 6, 12, 30), // newest; hour 1, day 1, week 1
+            info_at(2024, 3, 6, 12, 0),  // same hour, dropped
+            info_at(2024, 3, 6, 11, 0),  // hour 2
+            info_at(2024, 3, 6, 9, 0),   // over hourly limit, same day
+            info_at(2024, 3, 5, 22, 0),  // day 2
+            info_at(2024, 3, 4, 8, 0),   // over daily limit, same week
+        ];
+
+        let keep = retained_ids(&snapshots, &config);
+        let kept: Vec<&str> = snapshots
+            .iter()
+            .filter(|s| keep.contains(&s.id))
+            .map(|s| s.id.as_str())
+            .collect();
+        assert_eq!(kept, vec!["20240306T123000Z", "20240306T110000Z", "20240305T220000Z"]);
+    }
+
+    #[test]
+    fn test_retention_always_keeps_newest() {
+        let config = SnapshotConfig {
+            keep_hourly: 0,
+            keep_daily: 0,
+            keep_weekly: 0,
+            ..SnapshotConfig::default()
+        };
+        let snapshots = vec![info_at(2024, 1, 1, 0, 0), info_at(2024, 1, 2, 0, 0)];
+        let keep = retained_ids(&snapshots, &config);
+        assert_eq!(keep.len(), 1);
+        assert!(keep.contains("20240102T000000Z"));
+    }
+}
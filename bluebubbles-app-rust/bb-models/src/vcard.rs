@@ -0,0 +1,602 @@
+//! vCard parsing and serialization.
+//!
+//! Reads vCard 3.0 and 4.0 (plus the quoted-printable values common in 2.1
+//! exports) into [`Contact`]s and writes contacts back out. Only the
+//! properties the contact model holds are mapped: FN, N, ORG (as a display
+//! name fallback), TEL, EMAIL, PHOTO and UID. Everything else is ignored.
+
+use std::str::FromStr;
+
+use base64::Engine;
+use bb_core::error::{BbError, BbResult};
+
+use crate::models::contact::{Contact, StructuredName, LOCAL_ID_PREFIX};
+
+/// Maximum line length in octets before folding, per RFC 6350.
+const FOLD_AT: usize = 75;
+
+/// vCard version to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VCardVersion {
+    #[default]
+    V3,
+    V4,
+}
+
+impl VCardVersion {
+    fn as_str(&self) -> &'static str {
+        match self {
+            VCardVersion::V3 => "3.0",
+            VCardVersion::V4 => "4.0",
+        }
+    }
+}
+
+impl FromStr for VCardVersion {
+    type Err = BbError;
+
+    fn from_str(s: &str) -> BbResult<Self> {
+        match s {
+            "3" | "3.0" => Ok(VCardVersion::V3),
+            "4" | "4.0" => Ok(VCardVersion::V4),
+            other => Err(BbError::InvalidInput(format!(
+                "unsupported vCard version: {other} (expected 3.0 or 4.0)"
+            ))),
+        }
+    }
+}
+
+/// Parse every card in a vCard file.
+///
+/// A card's UID becomes a `local:` external ID so re-importing the same
+/// card updates the existing contact. Cards without a UID get no external ID.
+pub fn parse(input: &str) -> BbResult<Vec<Contact>> {
+    let mut contacts = Vec::new();
+    let mut current: Option<CardBuilder> = None;
+
+    for line in unfold(input) {
+        let Some(prop) = Property::parse(&line) else {
+            continue;
+        };
+        match prop.name.as_str() {
+            "BEGIN" if prop.value.eq_ignore_ascii_case("VCARD") => {
+                current = Some(CardBuilder::default());
+            }
+            "END" if prop.value.eq_ignore_ascii_case("VCARD") => {
+                if let Some(card) = current.take() {
+                    contacts.push(card.finish());
+                }
+            }
+            _ => {
+                if let Some(card) = current.as_mut() {
+                    card.apply(&prop);
+                }
+            }
+        }
+    }
+
+    // Tolerate a missing END:VCARD on the last card
+    if let Some(card) = current.take() {
+        contacts.push(card.finish());
+    }
+
+    if contacts.is_empty() {
+        return Err(BbError::InvalidInput("no vCard found".into()));
+    }
+    Ok(contacts)
+}
+
+/// Serialize one contact as a vCard.
+pub fn serialize(contact: &Contact, version: VCardVersion) -> String {
+    let mut out = String::new();
+    let mut line = |s: String| {
+        out.push_str(&fold(&s));
+        out.push_str("\r\n");
+    };
+
+    line("BEGIN:VCARD".into());
+    line(format!("VERSION:{}", version.as_str()));
+    if let Some(ref id) = contact.external_id {
+        let uid = id.strip_prefix(LOCAL_ID_PREFIX).unwrap_or(id);
+        line(format!("UID:{}", escape(uid)));
+    }
+    line(format!("FN:{}", escape(&contact.display_name)));
+
+    let name = contact.structured_name_parsed().unwrap_or_default();
+    let component = |v: &Option<String>| escape(v.as_deref().unwrap_or(""));
+    line(format!(
+        "N:{};{};{};{};{}",
+        component(&name.family_name),
+        component(&name.given_name),
+        component(&name.middle_name),
+        component(&name.prefix),
+        component(&name.suffix),
+    ));
+
+    for phone in contact.phone_list() {
+        match version {
+            VCardVersion::V3 => line(format!("TEL;TYPE=CELL:{}", escape(&phone))),
+            VCardVersion::V4 => line(format!("TEL;TYPE=cell:{}", escape(&phone))),
+        }
+    }
+    for email in contact.email_list() {
+        match version {
+            VCardVersion::V3 => line(format!("EMAIL;TYPE=INTERNET:{}", escape(&email))),
+            VCardVersion::V4 => line(format!("EMAIL:{}", escape(&email))),
+        }
+    }
+
+    if let Some(photo) = contact.avatar.as_deref().filter(|a| !a.is_empty()) {
+        let data = base64::engine::general_purpose::STANDARD.encode(photo);
+        let (kind, mime) = image_type(photo);
+        match version {
+            VCardVersion::V3 => line(format!("PHOTO;ENCODING=b;TYPE={kind}:{data}")),
+            VCardVersion::V4 => line(format!("PHOTO:data:{mime};base64,{data}")),
+        }
+    }
+
+    line("END:VCARD".into());
+    out
+}
+
+/// Serialize several contacts into one vCard file.
+pub fn serialize_all(contacts: &[Contact], version: VCardVersion) -> String {
+    contacts.iter().map(|c| serialize(c, version)).collect()
+}
+
+// ─── Parsing ─────────────────────────────────────────────────────────────────
+
+/// Accumulates the properties of one card.
+#[derive(Default)]
+struct CardBuilder {
+    formatted_name: Option<String>,
+    name: Option<StructuredName>,
+    org: Option<String>,
+    phones: Vec<String>,
+    emails: Vec<String>,
+    photo: Option<Vec<u8>>,
+    uid: Option<String>,
+}
+
+impl CardBuilder {
+    fn apply(&mut self, prop: &Property) {
+        match prop.name.as_str() {
+            "FN" => self.formatted_name = non_empty(prop.text()),
+            "N" => {
+                let mut parts = prop.components().into_iter().map(non_empty);
+                let name = StructuredName {
+                    family_name: parts.next().flatten(),
+                    given_name: parts.next().flatten(),
+                    middle_name: parts.next().flatten(),
+                    prefix: parts.next().flatten(),
+                    suffix: parts.next().flatten(),
+                };
+                if name.given_name.is_some() || name.family_name.is_some() {
+                    self.name = Some(name);
+                }
+            }
+            "ORG" => self.org = prop.components().into_iter().next().and_then(non_empty),
+            "TEL" => {
+                let text = prop.text();
+                let phone = strip_scheme(&text, "tel:").trim();
+                if !phone.is_empty() && !self.phones.iter().any(|p| p == phone) {
+                    self.phones.push(phone.to_string());
+                }
+            }
+            "EMAIL" => {
+                let text = prop.text();
+                let email = strip_scheme(&text, "mailto:").trim();
+                if !email.is_empty() && !self.emails.iter().any(|e| e.eq_ignore_ascii_case(email)) {
+                    self.emails.push(email.to_string());
+                }
+            }
+            "PHOTO" if self.photo.is_none() => self.photo = prop.binary(),
+            "UID" => self.uid = non_empty(prop.text()),
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Contact {
+        let display_name = self
+            .formatted_name
+            .or_else(|| self.name.as_ref().and_then(full_name))
+            .or_else(|| self.org.clone())
+            .or_else(|| self.phones.first().cloned())
+            .or_else(|| self.emails.first().cloned())
+            .unwrap_or_else(|| "Unknown".to_string());
+
+        Contact {
+            id: None,
+            external_id: self.uid.map(|uid| format!("{LOCAL_ID_PREFIX}{uid}")),
+            display_name,
+            phones: serde_json::to_string(&self.phones).unwrap_or_else(|_| "[]".into()),
+            emails: serde_json::to_string(&self.emails).unwrap_or_else(|_| "[]".into()),
+            avatar: self.photo,
+            structured_name: self.name.and_then(|n| serde_json::to_string(&n).ok()),
+        }
+    }
+}
+
+/// One content line: `[group.]NAME[;PARAM=VALUE...]:VALUE`.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        let colon = find_unquoted(line, ':')?;
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+        let mut parts = split_unquoted(head, ';').into_iter();
+        let name = parts.next()?;
+        let name = name.rsplit('.').next().unwrap_or(name).trim().to_ascii_uppercase();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut params = Vec::new();
+        for part in parts {
+            match part.split_once('=') {
+                Some((key, values)) => {
+                    let key = key.trim().to_ascii_uppercase();
+                    for v in split_unquoted(values, ',') {
+                        params.push((key.clone(), v.trim().trim_matches('"').to_string()));
+                    }
+                }
+                // vCard 2.1 allows bare types such as `TEL;CELL:...`
+                None => params.push(("TYPE".to_string(), part.trim().to_string())),
+            }
+        }
+
+        Some(Self {
+            name,
+            params,
+            value: value.to_string(),
+        })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// The raw value with any quoted-printable encoding removed.
+    fn raw_value(&self) -> String {
+        match self.param("ENCODING") {
+            Some(enc) if enc.eq_ignore_ascii_case("QUOTED-PRINTABLE") => {
+                String::from_utf8_lossy(&decode_quoted_printable(&self.value)).into_owned()
+            }
+            _ => self.value.clone(),
+        }
+    }
+
+    /// The value as unescaped text.
+    fn text(&self) -> String {
+        unescape(&self.raw_value())
+    }
+
+    /// The value split into `;`-separated structured components, unescaped.
+    fn components(&self) -> Vec<String> {
+        split_escaped(&self.raw_value(), ';').iter().map(|c| unescape(c)).collect()
+    }
+
+    /// Decode an inline binary value (`ENCODING=b`/`BASE64` or a `data:` URI).
+    ///
+    /// Returns None for values that only reference an external URL.
+    fn binary(&self) -> Option<Vec<u8>> {
+        let value = self.value.trim();
+        let encoded = if let Some(rest) = value.strip_prefix("data:") {
+            &rest[rest.find(";base64,")? + 8..]
+        } else if self
+            .param("ENCODING")
+            .is_some_and(|e| e.eq_ignore_ascii_case("b") || e.eq_ignore_ascii_case("BASE64"))
+        {
+            value
+        } else {
+            return None;
+        };
+
+        let cleaned: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(cleaned)
+            .ok()
+            .filter(|data| !data.is_empty())
+    }
+}
+
+/// Join folded lines back together.
+///
+/// Lines starting with a space or tab continue the previous line. Quoted-
+/// printable values from 2.1 exports use a trailing `=` as a soft break
+/// instead, so those are joined too.
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in input.split('\n') {
+        let line = raw.strip_suffix('\r').unwrap_or(raw);
+
+        if let Some(last) = lines.last_mut() {
+            if line.starts_with(' ') || line.starts_with('\t') {
+                last.push_str(&line[1..]);
+                continue;
+            }
+            if last.ends_with('=') && last.to_ascii_uppercase().contains("QUOTED-PRINTABLE") {
+                last.pop();
+                last.push_str(line);
+                continue;
+            }
+        }
+
+        if !line.trim().is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+/// Find the first occurrence of `needle` outside double quotes.
+fn find_unquoted(s: &str, needle: char) -> Option<usize> {
+    let mut in_quotes = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c == needle && !in_quotes => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split on `sep` outside double quotes.
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = s;
+    while let Some(i) = find_unquoted(rest, sep) {
+        parts.push(&rest[..i]);
+        rest = &rest[i + sep.len_utf8()..];
+    }
+    parts.push(rest);
+    parts
+}
+
+/// Split on `sep` where it is not backslash-escaped. Escapes are kept.
+fn split_escaped(s: &str, sep: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let current = parts.last_mut().expect("parts is never empty");
+        if c == '\\' {
+            current.push(c);
+            if let Some(next) = chars.next() {
+                current.push(next);
+            }
+        } else if c == sep {
+            parts.push(String::new());
+        } else {
+            current.push(c);
+        }
+    }
+    parts
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn decode_quoted_printable(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'=' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+fn strip_scheme<'a>(value: &'a str, scheme: &str) -> &'a str {
+    match value.get(..scheme.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(scheme) => &value[scheme.len()..],
+        _ => value,
+    }
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let trimmed = s.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+fn full_name(name: &StructuredName) -> Option<String> {
+    let parts: Vec<&str> = [
+        &name.prefix,
+        &name.given_name,
+        &name.middle_name,
+        &name.family_name,
+        &name.suffix,
+    ]
+    .iter()
+    .filter_map(|p| p.as_deref())
+    .collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+// ─── Serialization ───────────────────────────────────────────────────────────
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ',' => out.push_str("\\,"),
+            ';' => out.push_str("\\;"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Fold a line so no physical line exceeds 75 octets, without splitting
+/// a UTF-8 character.
+fn fold(line: &str) -> String {
+    if line.len() <= FOLD_AT {
+        return line.to_string();
+    }
+
+    let mut out = String::with_capacity(line.len() + line.len() / FOLD_AT * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > FOLD_AT {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out
+}
+
+/// vCard 3.0 photo type and MIME type for image bytes.
+fn image_type(data: &[u8]) -> (&'static str, &'static str) {
+    if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+        ("PNG", "image/png")
+    } else if data.starts_with(b"GIF8") {
+        ("GIF", "image/gif")
+    } else {
+        ("JPEG", "image/jpeg")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APPLE_V3: &str = "BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+PRODID:-//Apple Inc.//iPhone OS 17.0//EN\r\n\
+N:Appleseed;Johnny;Q.;Dr.;\r\n\
+FN:Dr. Johnny Q. Appleseed\r\n\
+ORG:Apple\\, Inc.;\r\n\
+item1.TEL;type=CELL;type=VOICE;type=pref:+1 (555) 123-4567\r\n\
+item1.X-ABLabel:mobile\r\n\
+TEL;type=HOME;type=VOICE:555-987-6543\r\n\
+EMAIL;type=INTERNET;type=HOME;type=pref:johnny@example.com\r\n\
+NOTE:Line one\\nLine two\r\n\
+PHOTO;ENCODING=b;TYPE=JPEG:/9j/4AAQ\r\n\x20SkZJRg==\r\n\
+UID:ABCD-1234\r\n\
+END:VCARD\r\n";
+
+    #[test]
+    fn test_parse_apple_v3() {
+        let contacts = parse(APPLE_V3).unwrap();
+        assert_eq!(contacts.len(), 1);
+        let c = &contacts[0];
+        assert_eq!(c.display_name, "Dr. Johnny Q. Appleseed");
+        assert_eq!(c.external_id.as_deref(), Some("local:ABCD-1234"));
+        assert_eq!(c.phone_list(), vec!["+1 (555) 123-4567", "555-987-6543"]);
+        assert_eq!(c.email_list(), vec!["johnny@example.com"]);
+
+        let name = c.structured_name_parsed().unwrap();
+        assert_eq!(name.given_name.as_deref(), Some("Johnny"));
+        assert_eq!(name.family_name.as_deref(), Some("Appleseed"));
+        assert_eq!(name.middle_name.as_deref(), Some("Q."));
+        assert_eq!(name.prefix.as_deref(), Some("Dr."));
+        assert_eq!(name.suffix, None);
+
+        // The folded base64 photo is joined before decoding
+        assert_eq!(c.avatar.as_deref(), Some(&b"\xff\xd8\xff\xe0\x00\x10JFIF"[..]));
+    }
+
+    #[test]
+    fn test_parse_v4_uris_and_multiple_cards() {
+        let input = "BEGIN:VCARD\nVERSION:4.0\nFN:Jane Roe\n\
+TEL;VALUE=uri;TYPE=\"voice,cell\":tel:+15550001111\n\
+EMAIL:mailto:jane@example.org\n\
+PHOTO:data:image/png;base64,iVBORw0KGgo=\nEND:VCARD\n\
+BEGIN:VCARD\nVERSION:4.0\nORG:Acme Corp\nEND:VCARD\n";
+        let contacts = parse(input).unwrap();
+        assert_eq!(contacts.len(), 2);
+        assert_eq!(contacts[0].phone_list(), vec!["+15550001111"]);
+        assert_eq!(contacts[0].email_list(), vec!["jane@example.org"]);
+        assert!(contacts[0].avatar.as_deref().unwrap().starts_with(&[0x89, b'P']));
+        assert!(contacts[0].external_id.is_none());
+        // Without FN or N the organization names the card
+        assert_eq!(contacts[1].display_name, "Acme Corp");
+    }
+
+    #[test]
+    fn test_parse_v21_quoted_printable() {
+        let input = "BEGIN:VCARD\nVERSION:2.1\n\
+N;CHARSET=UTF-8;ENCODING=QUOTED-PRINTABLE:M=C3=BCller;J=C3=\n=BCrgen;;;\n\
+TEL;CELL:+49301234567\nEND:VCARD\n";
+        let contacts = parse(input).unwrap();
+        assert_eq!(contacts[0].display_name, "Jürgen Müller");
+        assert_eq!(contacts[0].phone_list(), vec!["+49301234567"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_vcard() {
+        assert!(parse("hello world").is_err());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        for version in [VCardVersion::V3, VCardVersion::V4] {
+            let mut original = parse(APPLE_V3).unwrap().remove(0);
+            original.display_name = "Appleseed, Johnny; \"JQ\"".into();
+            original.avatar = Some(vec![0xff; 200]);
+
+            let text = serialize(&original, version);
+            assert!(text.contains(&format!("VERSION:{}", version.as_str())));
+            assert!(text.lines().all(|l| l.len() <= FOLD_AT));
+
+            let parsed = parse(&text).unwrap().remove(0);
+            assert_eq!(parsed.display_name, original.display_name);
+            assert_eq!(parsed.external_id, original.external_id);
+            assert_eq!(parsed.phone_list(), original.phone_list());
+            assert_eq!(parsed.email_list(), original.email_list());
+            assert_eq!(parsed.avatar, original.avatar);
+            assert_eq!(
+                parsed.structured_name_parsed().unwrap().family_name.as_deref(),
+                Some("Appleseed")
+            );
+        }
+    }
+
+    #[test]
+    fn test_fold_keeps_utf8_intact() {
+        let line = format!("FN:{}", "é".repeat(60));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|l| l.len() <= FOLD_AT));
+        assert_eq!(unfold(&folded), vec![line]);
+    }
+
+    #[test]
+    fn test_version_from_str() {
+        assert_eq!("4.0".parse::<VCardVersion>().unwrap(), VCardVersion::V4);
+        assert_eq!("3".parse::<VCardVersion>().unwrap(), VCardVersion::V3);
+        assert!("2.1".parse::<VCardVersion>().is_err());
+    }
+}
@@ -252,6 +252,7 @@ impl ActionHandler {
                 {
                     msg.id = existing.id;
                     msg.chat_id = existing.chat_id;
+                    record_unsent_parts(&conn, &guid, &existing, &msg);
                 }
 
                 if let Err(e) = msg.save(&conn) {
@@ -519,6 +520,35 @@ impl Service for ActionHandler {
     }
 }
 
+/// Record tombstones for parts that became retracted in this update.
+///
+/// The server clears the text of unsent parts, so the original is taken from
+/// that part of the previously stored message (or its latest edit).
+fn record_unsent_parts(
+    conn: &rusqlite::Connection,
+    guid: &str,
+    existing: &bb_models::Message,
+    updated: &bb_models::Message,
+) {
+    let Some(new_info) = updated.summary_info() else {
+        return;
+    };
+    let old_info = existing.summary_info().unwrap_or_default();
+
+    for &part in &new_info.retracted_parts {
+        if old_info.is_retracted(part) {
+            continue;
+        }
+        let mut tombstone =
+            bb_models::MessageTombstone::new(guid, existing.chat_id, part, existing.part_text(part));
+        if let Err(e) = tombstone.save(conn) {
+            warn!("failed to record unsend of {guid} part {part}: {e}");
+        } else {
+            debug!("recorded unsend of {guid} part {part}");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -602,4 +632,48 @@ mod tests {
             tokio::time::timeout(std::time::Duration::from_millis(50), rx.recv()).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_unsend_records_tombstone() {
+        let db = create_test_db();
+        let bus = EventBus::new(16);
+        let handler = ActionHandler::new(db.clone(), bus);
+
+        let original = serde_json::json!({
+            "guid": "msg-unsent-1",
+            "text": "wrong chat",
+            "isFromMe": true,
+            "chats": [{"guid": "chat-1"}],
+            "dateCreated": "2024-01-01T00:00:00Z",
+        });
+        handler
+            .handle_event(SocketEvent {
+                event_type: SocketEventType::NewMessage,
+                data: original,
+            })
+            .await
+            .unwrap();
+
+        let unsent = serde_json::json!({
+            "guid": "msg-unsent-1",
+            "text": "",
+            "isFromMe": true,
+            "chats": [{"guid": "chat-1"}],
+            "dateCreated": "2024-01-01T00:00:00Z",
+            "messageSummaryInfo": {"rp": [0]},
+        });
+        handler
+            .handle_event(SocketEvent {
+                event_type: SocketEventType::UpdatedMessage,
+                data: unsent,
+            })
+            .await
+            .unwrap();
+
+        let conn = db.conn().unwrap();
+        let tombstones = bb_models::MessageTombstone::find_for_message(&conn, "msg-unsent-1").unwrap();
+        assert_eq!(tombstones.len(), 1);
+        assert_eq!(tombstones[0].part_index, 0);
+        assert_eq!(tombstones[0].original_text.as_deref(), Some("wrong chat"));
+    }
 }
@@ -12,8 +12,9 @@ use tokio::sync::{Mutex, Semaphore};
 use tracing::{info, debug};
 use bb_core::constants;
 use bb_core::error::{BbError, BbResult};
-use bb_models::{Database, Attachment};
+use bb_models::{Database, Attachment, Contact};
 use bb_models::queries;
+use bb_models::vcard;
 use bb_api::ApiClient;
 
 use crate::event_bus::{AppEvent, EventBus};
@@ -78,13 +79,7 @@ impl AttachmentService {
 
     /// Get the local cache path for an attachment GUID.
     pub fn cache_path(&self, guid: &str, extension: Option<&str>) -> PathBuf {
-        let safe_name = guid.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
-        let filename = if let Some(ext) = extension {
-            format!("{safe_name}.{ext}")
-        } else {
-            safe_name
-        };
-        self.cache_dir.join(filename)
+        self.cache_dir.join(cache_file_name(guid, extension))
     }
 
     /// Whether a cached file exists for the given attachment GUID.
@@ -181,6 +176,42 @@ impl AttachmentService {
         Ok(path)
     }
 
+    /// Load a contact-card (vCard) attachment as structured contacts.
+    ///
+    /// Downloads the card first if it is not cached. Nothing is saved to
+    /// local contacts; pass the result to `ContactService::import_contacts`
+    /// for that.
+    pub async fn load_contact_card(&self, api: &ApiClient, guid: &str) -> BbResult<Vec<Contact>> {
+        let path = self.contact_card_path(guid)?;
+        let path = if path.exists() {
+            path
+        } else {
+            self.download(api, guid, true).await?
+        };
+        let data = std::fs::read(&path)?;
+        vcard::parse(&String::from_utf8_lossy(&data))
+    }
+
+    /// Cache path of a contact-card attachment, rejecting other attachment types.
+    fn contact_card_path(&self, guid: &str) -> BbResult<PathBuf> {
+        let attachment = self.find_attachment(guid)?;
+        if let Some(ref a) = attachment {
+            if a.mime_type.as_deref() == Some("text/x-vlocation") {
+                return Err(BbError::InvalidInput(format!(
+                    "attachment {guid} is a shared location, not a contact card"
+                )));
+            }
+            let is_vcf = a.file_extension().is_some_and(|e| e.eq_ignore_ascii_case("vcf"));
+            if !a.is_contact_card() && !is_vcf {
+                return Err(BbError::InvalidInput(format!(
+                    "attachment {guid} is not a contact card"
+                )));
+            }
+        }
+        let extension = attachment.as_ref().and_then(|a| a.file_extension());
+        Ok(self.cache_path(guid, extension))
+    }
+
     /// Download and cache a live photo movie component.
     ///
     /// Live photos consist of a still image + a short video. This downloads
@@ -349,6 +380,16 @@ impl AttachmentService {
     }
 }
 
+/// File name used for a cached attachment: the sanitized GUID plus extension.
+pub(crate) fn cache_file_name(guid: &str, extension: Option<&str>) -> String {
+    let safe_name = guid.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
+    if let Some(ext) = extension {
+        format!("{safe_name}.{ext}")
+    } else {
+        safe_name
+    }
+}
+
 impl Service for AttachmentService {
     fn name(&self) -> &str { "attachment" }
     fn state(&self) -> ServiceState { self.state }
@@ -386,6 +427,24 @@ mod tests {
         assert!(path.to_string_lossy().contains("att-123.jpg"));
     }
 
+    #[test]
+    fn test_contact_card_path() {
+        let (svc, _dir) = create_test_svc();
+        let conn = svc.database.conn().unwrap();
+        conn.execute_batch(
+            "INSERT INTO attachments (guid, mime_type, transfer_name) VALUES
+                ('att-card', 'text/vcard', 'Jane Roe.vcf'),
+                ('att-loc', 'text/x-vlocation', 'CL.loc.vcf'),
+                ('att-photo', 'image/jpeg', 'IMG_0001.jpeg');",
+        )
+        .unwrap();
+
+        let path = svc.contact_card_path("att-card").unwrap();
+        assert!(path.to_string_lossy().ends_with("att-card.vcf"));
+        assert!(svc.contact_card_path("att-loc").is_err());
+        assert!(svc.contact_card_path("att-photo").is_err());
+    }
+
     #[test]
     fn test_cache_path_sanitize() {
         let (svc, _dir) = create_test_svc();
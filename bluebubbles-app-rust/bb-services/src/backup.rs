@@ -1,15 +1,18 @@
 //! Backup service for settings and theme backup/restore via the server.
 //!
 //! Manages named backups of the application configuration and themes,
-//! supports export to the server and import from server backups.
+//! supports export to the server and import from server backups. When a
+//! database is attached, settings backups also carry the chat folders.
 
 use tracing::{info, warn, debug};
 
 use bb_core::config::ConfigHandle;
 use bb_core::error::{BbError, BbResult};
 use bb_api::ApiClient;
+use bb_models::Database;
 
 use crate::event_bus::EventBus;
+use crate::folder::{FolderBackup, FolderService};
 use crate::service::{Service, ServiceState};
 
 /// Service for managing settings and theme backups via the server.
@@ -21,6 +24,7 @@ pub struct BackupService {
     state: ServiceState,
     config: ConfigHandle,
     event_bus: EventBus,
+    database: Option<Database>,
 }
 
 impl BackupService {
@@ -30,9 +34,35 @@ impl BackupService {
             state: ServiceState::Created,
             config,
             event_bus,
+            database: None,
         }
     }
 
+    /// Attach the local database so chat folders are included in backups.
+    pub fn with_database(mut self, database: Database) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// Chat folders to include in a backup, if a database is attached.
+    pub fn export_folders(&self) -> BbResult<Option<Vec<FolderBackup>>> {
+        match &self.database {
+            Some(db) => FolderService::new(db.clone(), self.event_bus.clone()).export().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Restore the chat folders carried by a backup, if any. Returns the
+    /// number of folders restored.
+    pub fn import_folders(&self, backup_data: &serde_json::Value) -> BbResult<usize> {
+        let (Some(db), Some(folders)) = (&self.database, backup_data.get("chat_folders")) else {
+            return Ok(0);
+        };
+        let folders: Vec<FolderBackup> = serde_json::from_value(folders.clone())
+            .map_err(|e| BbError::Serialization(e.to_string()))?;
+        FolderService::new(db.clone(), self.event_bus.clone()).import(&folders)
+    }
+
     /// Export current application settings to the server as a named backup.
     ///
     /// Serialises the full AppConfig (minus sensitive credentials) to JSON
@@ -48,6 +78,15 @@ impl BackupService {
                 obj.remove("guid_auth_key");
             }
         }
+        drop(config);
+
+        if let Some(folders) = self.export_folders()? {
+            let folders = serde_json::to_value(folders)
+                .map_err(|e| BbError::Serialization(e.to_string()))?;
+            if let Some(obj) = data.as_object_mut() {
+                obj.insert("chat_folders".into(), folders);
+            }
+        }
 
         api.save_settings_backup(name, &data).await?;
         info!("exported settings backup: {name}");
@@ -67,6 +106,11 @@ impl BackupService {
             return Ok(());
         }
 
+        let folders = self.import_folders(&backup_data)?;
+        if folders > 0 {
+            info!("restored {folders} chat folders from server backup");
+        }
+
         if let Ok(imported) = serde_json::from_value::<bb_core::config::AppConfig>(backup_data.clone()) {
             let mut current = self.config.write().await;
 
@@ -206,4 +250,22 @@ mod tests {
         svc.shutdown().unwrap();
         assert_eq!(svc.state(), ServiceState::Stopped);
     }
+
+    #[test]
+    fn test_backup_folders_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        let config = ConfigHandle::new(AppConfig::default());
+        let svc = BackupService::new(config, EventBus::new(16)).with_database(db.clone());
+
+        let folders = FolderService::new(db.clone(), EventBus::new(16));
+        folders.create_smart_folder("Groups", bb_models::FolderRule::Group, None).unwrap();
+        let exported = svc.export_folders().unwrap().unwrap();
+        let data = serde_json::json!({ "chat_folders": exported });
+        folders.delete_folder(folders.find_folder("Groups").unwrap().unwrap().id.unwrap()).unwrap();
+
+        assert_eq!(svc.import_folders(&data).unwrap(), 1);
+        assert!(folders.find_folder("groups").unwrap().unwrap().is_smart());
+        assert_eq!(svc.import_folders(&serde_json::json!({})).unwrap(), 0);
+    }
 }
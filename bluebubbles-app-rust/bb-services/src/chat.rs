@@ -1,18 +1,47 @@
 //! Chat service for managing conversations.
 //!
 //! Handles chat CRUD operations, participant management, read/unread status,
-//! mute/unmute, soft delete, pin/archive, and chat search.
+//! mute/unmute, soft delete, pin/archive, composer drafts, and chat search.
+//! Changes that must reach the server go through the outbox, so they apply
+//! locally at once and are sent when the server is reachable.
 
+use serde::{Deserialize, Serialize};
 use tracing::{info, debug};
 use bb_core::error::{BbError, BbResult};
-use bb_models::{Database, Chat, Handle};
+use bb_models::{Database, Chat, ChatFolder, ChatMutation, Handle};
 use bb_models::queries;
 use bb_models::queries::ChatWithDetails;
 use bb_api::ApiClient;
 
 use crate::event_bus::{AppEvent, EventBus};
+use crate::outbox::{OutboxService, SubmitStatus};
 use crate::service::{Service, ServiceState};
 
+/// An unsent composer draft for a chat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Draft {
+    pub chat_guid: String,
+    pub text: Option<String>,
+    pub attachment_paths: Vec<String>,
+}
+
+impl Draft {
+    /// Read the draft stored on a chat, if it has one.
+    pub fn from_chat(chat: &Chat) -> Option<Self> {
+        let draft = Self {
+            chat_guid: chat.guid.clone(),
+            text: chat.text_field_text.clone().filter(|t| !t.is_empty()),
+            attachment_paths: chat.draft_attachment_paths(),
+        };
+        (!draft.is_empty()).then_some(draft)
+    }
+
+    /// Whether the draft has neither text nor attachments.
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none() && self.attachment_paths.is_empty()
+    }
+}
+
 /// Service for managing chat conversations.
 ///
 /// Handles chat CRUD operations, participant management, read/unread status,
@@ -39,15 +68,17 @@ impl ChatService {
         queries::list_chats(&conn, offset, limit, include_archived)
     }
 
-    /// List chats with participant details (display names, handles).
+    /// List chats with participant details (display names, handles),
+    /// optionally restricted to a folder.
     pub fn list_chats_with_details(
         &self,
         offset: i64,
         limit: i64,
         include_archived: bool,
+        folder: Option<&ChatFolder>,
     ) -> BbResult<Vec<ChatWithDetails>> {
         let conn = self.database.conn()?;
-        queries::list_chats_with_details(&conn, offset, limit, include_archived)
+        queries::list_chats_with_details(&conn, offset, limit, include_archived, folder)
     }
 
     /// Find a chat by GUID in the local database.
@@ -72,40 +103,19 @@ impl ChatService {
         }
     }
 
-    /// Mark a chat as read both locally and on the server.
-    pub async fn mark_read(&self, api: &ApiClient, guid: &str) -> BbResult<()> {
-        api.mark_chat_read(guid).await?;
-
-        let conn = self.database.conn()?;
-        conn.execute(
-            "UPDATE chats SET has_unread_message = 0 WHERE guid = ?1",
-            rusqlite::params![guid],
-        )
-        .map_err(|e| BbError::Database(e.to_string()))?;
-
-        debug!("marked chat {guid} as read");
-        self.event_bus.emit(AppEvent::ChatUpdated {
-            chat_guid: guid.to_string(),
-        });
-        Ok(())
+    /// Mark a chat as read locally and on the server. Offline, the change
+    /// is kept in the outbox and sent once the connection is back.
+    pub async fn mark_read(&self, api: &ApiClient, guid: &str) -> BbResult<SubmitStatus> {
+        let status = self.outbox().submit(api, guid, ChatMutation::MarkRead).await?;
+        debug!("marked chat {guid} as read ({status:?})");
+        Ok(status)
     }
 
-    /// Mark a chat as unread both locally and on the server.
-    pub async fn mark_unread(&self, api: &ApiClient, guid: &str) -> BbResult<()> {
-        api.mark_chat_unread(guid).await?;
-
-        let conn = self.database.conn()?;
-        conn.execute(
-            "UPDATE chats SET has_unread_message = 1 WHERE guid = ?1",
-            rusqlite::params![guid],
-        )
-        .map_err(|e| BbError::Database(e.to_string()))?;
-
-        debug!("marked chat {guid} as unread");
-        self.event_bus.emit(AppEvent::ChatUpdated {
-            chat_guid: guid.to_string(),
-        });
-        Ok(())
+    /// Mark a chat as unread locally and on the server, through the outbox.
+    pub async fn mark_unread(&self, api: &ApiClient, guid: &str) -> BbResult<SubmitStatus> {
+        let status = self.outbox().submit(api, guid, ChatMutation::MarkUnread).await?;
+        debug!("marked chat {guid} as unread ({status:?})");
+        Ok(status)
     }
 
     /// Create a new chat via the server API and save it locally.
@@ -136,23 +146,12 @@ impl ChatService {
         Ok(chat)
     }
 
-    /// Update a chat's display name (group name).
-    pub async fn rename_chat(&self, api: &ApiClient, guid: &str, name: &str) -> BbResult<()> {
-        api.update_chat(guid, name).await?;
-
-        let conn = self.database.conn()?;
-        conn.execute(
-            "UPDATE chats SET display_name = ?1 WHERE guid = ?2",
-            rusqlite::params![name, guid],
-        )
-        .map_err(|e| BbError::Database(e.to_string()))?;
-
-        info!("renamed chat {guid} to {name}");
-        self.event_bus.emit(AppEvent::GroupNameChanged {
-            chat_guid: guid.to_string(),
-            new_name: name.to_string(),
-        });
-        Ok(())
+    /// Update a chat's display name (group name), through the outbox.
+    pub async fn rename_chat(&self, api: &ApiClient, guid: &str, name: &str) -> BbResult<SubmitStatus> {
+        let mutation = ChatMutation::Rename { name: name.to_string() };
+        let status = self.outbox().submit(api, guid, mutation).await?;
+        info!("renamed chat {guid} to {name} ({status:?})");
+        Ok(status)
     }
 
     /// Get the total number of chats.
@@ -263,6 +262,61 @@ impl ChatService {
         Ok(())
     }
 
+    /// Save the composer draft for a chat.
+    ///
+    /// Saving empty text with no attachments clears the draft.
+    pub fn save_draft(
+        &self,
+        guid: &str,
+        text: Option<&str>,
+        attachment_paths: &[String],
+    ) -> BbResult<()> {
+        let text = text.filter(|t| !t.is_empty());
+        if text.is_none() && attachment_paths.is_empty() {
+            self.clear_draft(guid)?;
+            return Ok(());
+        }
+
+        let attachments_json = serde_json::to_string(attachment_paths)
+            .map_err(|e| BbError::Serialization(e.to_string()))?;
+        let conn = self.database.conn()?;
+        if !queries::set_chat_draft(&conn, guid, text, &attachments_json)? {
+            return Err(BbError::ChatNotFound(guid.to_string()));
+        }
+        debug!("saved draft for chat {guid}");
+        self.event_bus.emit(AppEvent::ChatUpdated {
+            chat_guid: guid.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Load the composer draft for a chat, if it has one.
+    pub fn load_draft(&self, guid: &str) -> BbResult<Option<Draft>> {
+        let conn = self.database.conn()?;
+        let chat = queries::find_chat_by_guid(&conn, guid)?
+            .ok_or_else(|| BbError::ChatNotFound(guid.to_string()))?;
+        Ok(Draft::from_chat(&chat))
+    }
+
+    /// Clear the composer draft for a chat. Returns true if a draft was removed.
+    pub fn clear_draft(&self, guid: &str) -> BbResult<bool> {
+        let conn = self.database.conn()?;
+        let cleared = queries::clear_chat_draft(&conn, guid)?;
+        if cleared {
+            debug!("cleared draft for chat {guid}");
+            self.event_bus.emit(AppEvent::ChatUpdated {
+                chat_guid: guid.to_string(),
+            });
+        }
+        Ok(cleared)
+    }
+
+    /// List chats that have an unsent draft.
+    pub fn chats_with_drafts(&self) -> BbResult<Vec<Chat>> {
+        let conn = self.database.conn()?;
+        queries::chats_with_drafts(&conn)
+    }
+
     /// Leave a group chat via the server API.
     pub async fn leave_chat(&self, api: &ApiClient, guid: &str) -> BbResult<()> {
         api.leave_chat(guid).await?;
@@ -279,58 +333,42 @@ impl ChatService {
         queries::load_chat_participants(&conn, chat_id)
     }
 
-    /// Add a participant to a group chat via the server API.
+    /// Add a participant to a group chat, through the outbox.
     pub async fn add_participant(
         &self,
         api: &ApiClient,
         chat_guid: &str,
         address: &str,
-    ) -> BbResult<()> {
-        api.add_participant(chat_guid, address).await?;
-
-        // Save the handle locally
-        let conn = self.database.conn()?;
-        let service = if address.contains('@') { "iMessage" } else { "iMessage" };
-        let unique = format!("{}/{}", address, service);
-        conn.execute(
-            "INSERT OR IGNORE INTO handles (address, service, unique_address_service) VALUES (?1, ?2, ?3)",
-            rusqlite::params![address, service, unique],
-        )
-        .map_err(|e| BbError::Database(e.to_string()))?;
-
-        info!("added participant {address} to chat {chat_guid}");
-        self.event_bus.emit(AppEvent::ParticipantAdded {
-            chat_guid: chat_guid.to_string(),
-            address: address.to_string(),
-        });
-        Ok(())
+    ) -> BbResult<SubmitStatus> {
+        let mutation = ChatMutation::AddParticipant { address: address.to_string() };
+        let status = self.outbox().submit(api, chat_guid, mutation).await?;
+        info!("added participant {address} to chat {chat_guid} ({status:?})");
+        Ok(status)
     }
 
-    /// Remove a participant from a group chat via the server API.
+    /// Remove a participant from a group chat, through the outbox.
     pub async fn remove_participant(
         &self,
         api: &ApiClient,
         chat_guid: &str,
         address: &str,
-    ) -> BbResult<()> {
-        api.remove_participant(chat_guid, address).await?;
-
-        info!("removed participant {address} from chat {chat_guid}");
-        self.event_bus.emit(AppEvent::ParticipantRemoved {
-            chat_guid: chat_guid.to_string(),
-            address: address.to_string(),
-        });
-        Ok(())
+    ) -> BbResult<SubmitStatus> {
+        let mutation = ChatMutation::RemoveParticipant { address: address.to_string() };
+        let status = self.outbox().submit(api, chat_guid, mutation).await?;
+        info!("removed participant {address} from chat {chat_guid} ({status:?})");
+        Ok(status)
     }
 
-    /// Delete a chat from the server.
-    pub async fn delete_chat(&self, api: &ApiClient, guid: &str) -> BbResult<()> {
-        api.delete_chat(guid).await?;
+    /// Delete a chat on the server and soft-delete it locally, through the
+    /// outbox.
+    pub async fn delete_chat(&self, api: &ApiClient, guid: &str) -> BbResult<SubmitStatus> {
+        let status = self.outbox().submit(api, guid, ChatMutation::Delete).await?;
+        info!("deleted chat: {guid} ({status:?})");
+        Ok(status)
+    }
 
-        // Also soft-delete locally
-        self.soft_delete(guid)?;
-        info!("deleted chat: {guid}");
-        Ok(())
+    fn outbox(&self) -> OutboxService {
+        OutboxService::new(self.database.clone(), self.event_bus.clone())
     }
 }
 
@@ -435,4 +473,36 @@ mod tests {
         svc.soft_delete("chat-del").unwrap();
         svc.restore_deleted("chat-del").unwrap();
     }
+
+    #[test]
+    fn test_draft_round_trip() {
+        let db = create_test_db();
+        let bus = crate::event_bus::EventBus::new(16);
+        let mut rx = bus.subscribe();
+        let svc = ChatService::new(db.clone(), bus);
+
+        let conn = db.conn().unwrap();
+        conn.execute("INSERT INTO chats (guid) VALUES ('chat-draft')", []).unwrap();
+
+        assert!(svc.load_draft("chat-draft").unwrap().is_none());
+
+        let paths = vec!["/tmp/photo.jpg".to_string()];
+        svc.save_draft("chat-draft", Some("see you at"), &paths).unwrap();
+        assert!(matches!(rx.try_recv().unwrap(), AppEvent::ChatUpdated { .. }));
+
+        let draft = svc.load_draft("chat-draft").unwrap().unwrap();
+        assert_eq!(draft.text.as_deref(), Some("see you at"));
+        assert_eq!(draft.attachment_paths, paths);
+        assert_eq!(svc.chats_with_drafts().unwrap().len(), 1);
+
+        // An empty save clears the draft
+        svc.save_draft("chat-draft", Some(""), &[]).unwrap();
+        assert!(svc.load_draft("chat-draft").unwrap().is_none());
+        assert!(!svc.clear_draft("chat-draft").unwrap());
+
+        assert!(matches!(
+            svc.save_draft("missing", Some("x"), &[]),
+            Err(BbError::ChatNotFound(_))
+        ));
+    }
 }
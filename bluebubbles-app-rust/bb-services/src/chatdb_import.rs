@@ -0,0 +1,567 @@
+//! Offline import of an Apple Messages `chat.db`.
+//!
+//! Maps the Apple `handle`, `chat`, `message`, and `attachment` tables (plus
+//! their join tables) onto the local models. Apple dates are nanoseconds (or
+//! seconds, on databases older than macOS 10.13) since 2001-01-01 and are
+//! converted to the Unix milliseconds the server sends. Rows whose GUID
+//! already exists locally are left untouched, so importing on top of synced
+//! data only fills in history the server never served.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use bb_core::constants::APPLE_EPOCH_OFFSET_SECS;
+use bb_core::error::{BbError, BbResult};
+use bb_models::models::attributed_body::AttributedBody;
+use bb_models::{Attachment, Chat, Database, Handle, Message};
+use bb_models::queries;
+
+use crate::attachment::cache_file_name;
+
+/// Options for a chat.db import.
+#[derive(Debug, Clone, Default)]
+pub struct ChatDbImportOptions {
+    /// Apple `Attachments/` directory to copy attachment files from.
+    pub attachments_dir: Option<PathBuf>,
+    /// Attachment cache directory to copy files into.
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// Counts of what an import did.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChatDbImportReport {
+    pub handles: usize,
+    pub chats_imported: usize,
+    pub chats_existing: usize,
+    pub messages_imported: usize,
+    pub messages_skipped: usize,
+    pub attachments_imported: usize,
+    pub attachment_files_copied: usize,
+    pub attachment_files_missing: usize,
+}
+
+/// Imports an Apple `chat.db` into the local database.
+pub struct ChatDbImporter {
+    database: Database,
+}
+
+impl ChatDbImporter {
+    /// Create an importer writing into `database`.
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+
+    /// Import everything from the `chat.db` at `path`.
+    pub fn import(&self, path: &Path, options: &ChatDbImportOptions) -> BbResult<ChatDbImportReport> {
+        let source = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| BbError::Database(format!("cannot open {}: {e}", path.display())))?;
+        for table in ["handle", "chat", "message", "chat_message_join"] {
+            if table_columns(&source, table)?.is_empty() {
+                return Err(BbError::InvalidInput(format!(
+                    "{} is not a Messages chat.db (missing table '{table}')",
+                    path.display()
+                )));
+            }
+        }
+
+        let conn = self.database.conn()?;
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+        let mut report = ChatDbImportReport::default();
+        let handle_ids = import_handles(&source, &tx, &mut report)?;
+        let (chat_ids, new_chats) = import_chats(&source, &tx, &handle_ids, &mut report)?;
+        let message_ids = import_messages(&source, &tx, &handle_ids, &chat_ids, &new_chats, &mut report)?;
+        import_attachments(&source, &tx, &message_ids, options, &mut report)?;
+
+        tx.commit().map_err(|e| BbError::Database(e.to_string()))?;
+        info!(
+            "imported {} chats and {} messages from {} ({} duplicates skipped)",
+            report.chats_imported,
+            report.messages_imported,
+            path.display(),
+            report.messages_skipped
+        );
+        Ok(report)
+    }
+}
+
+/// Convert an Apple timestamp to Unix milliseconds. Zero means "not set".
+pub fn apple_date_to_unix_ms(value: i64) -> Option<i64> {
+    if value == 0 {
+        return None;
+    }
+    let offset_ms = APPLE_EPOCH_OFFSET_SECS * 1000;
+    if value.abs() > 100_000_000_000 {
+        Some(value / 1_000_000 + offset_ms)
+    } else {
+        Some(value * 1000 + offset_ms)
+    }
+}
+
+/// Column names of a table in the source database (empty if the table is missing).
+fn table_columns(conn: &Connection, table: &str) -> BbResult<HashSet<String>> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({table})"))
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(columns)
+}
+
+/// Select expression for a column that only exists on some macOS versions.
+fn optional_column(columns: &HashSet<String>, name: &str) -> String {
+    if columns.contains(name) {
+        name.to_string()
+    } else {
+        format!("NULL AS {name}")
+    }
+}
+
+fn import_handles(
+    source: &Connection,
+    dest: &Connection,
+    report: &mut ChatDbImportReport,
+) -> BbResult<HashMap<i64, i64>> {
+    let columns = table_columns(source, "handle")?;
+    let sql = format!(
+        "SELECT ROWID, id, service, {} FROM handle",
+        optional_column(&columns, "country")
+    );
+    let mut stmt = source.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    let mut ids = HashMap::new();
+    for row in rows {
+        let (rowid, address, service, country) = row.map_err(|e| BbError::Database(e.to_string()))?;
+        let mut handle = Handle::from_server_map(&serde_json::json!({
+            "address": address,
+            "service": service.unwrap_or_else(|| "iMessage".to_string()),
+            "country": country,
+        }))?;
+        ids.insert(rowid, handle.save(dest)?);
+        report.handles += 1;
+    }
+    Ok(ids)
+}
+
+/// (ROWID, guid, chat_identifier, display_name, style, is_archived)
+type ChatRow = (i64, String, Option<String>, Option<String>, Option<i64>, Option<i64>);
+
+fn import_chats(
+    source: &Connection,
+    dest: &Connection,
+    handle_ids: &HashMap<i64, i64>,
+    report: &mut ChatDbImportReport,
+) -> BbResult<(HashMap<i64, i64>, HashSet<i64>)> {
+    let columns = table_columns(source, "chat")?;
+    let sql = format!(
+        "SELECT ROWID, guid, chat_identifier, display_name, style, {} FROM chat",
+        optional_column(&columns, "is_archived")
+    );
+    let mut stmt = source.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let rows: Vec<ChatRow> = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let participants = chat_participants(source)?;
+
+    let mut ids = HashMap::new();
+    let mut new_chats = HashSet::new();
+    for (rowid, guid, identifier, display_name, style, archived) in rows {
+        // Never overwrite a synced chat's state; just link messages to it
+        if let Some(existing) = queries::find_chat_by_guid(dest, &guid)? {
+            if let Some(id) = existing.id {
+                ids.insert(rowid, id);
+                report.chats_existing += 1;
+                continue;
+            }
+        }
+
+        let mut chat = Chat::from_server_map(&serde_json::json!({
+            "guid": guid,
+            "chatIdentifier": identifier,
+            "displayName": display_name.unwrap_or_default(),
+            "style": style,
+            "isArchived": archived.unwrap_or(0) != 0,
+        }))?;
+        let id = chat.save(dest)?;
+        chat.participants = participants
+            .get(&rowid)
+            .into_iter()
+            .flatten()
+            .filter_map(|h| handle_ids.get(h))
+            .filter_map(|&handle_id| queries::find_handle_by_id(dest, handle_id).ok().flatten())
+            .collect();
+        chat.save_participants(dest)?;
+
+        ids.insert(rowid, id);
+        new_chats.insert(id);
+        report.chats_imported += 1;
+    }
+    Ok((ids, new_chats))
+}
+
+/// Apple chat ROWID -> participant handle ROWIDs.
+fn chat_participants(source: &Connection) -> BbResult<HashMap<i64, Vec<i64>>> {
+    let mut map: HashMap<i64, Vec<i64>> = HashMap::new();
+    if table_columns(source, "chat_handle_join")?.is_empty() {
+        return Ok(map);
+    }
+    let mut stmt = source
+        .prepare("SELECT chat_id, handle_id FROM chat_handle_join")
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    for (chat, handle) in rows.filter_map(|r| r.ok()) {
+        map.entry(chat).or_default().push(handle);
+    }
+    Ok(map)
+}
+
+/// Imports messages and returns Apple message ROWID -> local message ID for
+/// newly imported messages.
+fn import_messages(
+    source: &Connection,
+    dest: &Connection,
+    handle_ids: &HashMap<i64, i64>,
+    chat_ids: &HashMap<i64, i64>,
+    new_chats: &HashSet<i64>,
+    report: &mut ChatDbImportReport,
+) -> BbResult<HashMap<i64, i64>> {
+    let c = table_columns(source, "message")?;
+    let optional = [
+        "attributedBody",
+        "date_read",
+        "date_delivered",
+        "date_edited",
+        "thread_originator_guid",
+        "thread_originator_part",
+        "associated_message_guid",
+        "associated_message_type",
+        "balloon_bundle_id",
+        "expressive_send_style_id",
+        "group_title",
+        "group_action_type",
+        "item_type",
+        "subject",
+        "error",
+        "cache_has_attachments",
+    ]
+    .iter()
+    .map(|name| optional_column(&c, name))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    let sql = format!(
+        "SELECT m.ROWID, cmj.chat_id, m.guid, m.text, m.handle_id, m.service, m.date,
+                m.is_from_me, m.is_delivered, {optional}
+         FROM message m
+         INNER JOIN chat_message_join cmj ON cmj.message_id = m.ROWID
+         ORDER BY m.date ASC"
+    );
+    let mut stmt = source.prepare(&sql).map_err(|e| BbError::Database(e.to_string()))?;
+    let mut rows = stmt.query([]).map_err(|e| BbError::Database(e.to_string()))?;
+
+    let mut ids = HashMap::new();
+    let mut latest: HashMap<i64, i64> = HashMap::new();
+    while let Some(row) = rows.next().map_err(|e| BbError::Database(e.to_string()))? {
+        let get_err = |e: rusqlite::Error| BbError::Database(e.to_string());
+        let rowid: i64 = row.get(0).map_err(get_err)?;
+        let apple_chat: i64 = row.get(1).map_err(get_err)?;
+        let guid: String = row.get(2).map_err(get_err)?;
+        let Some(&chat_id) = chat_ids.get(&apple_chat) else {
+            continue;
+        };
+
+        if queries::find_message_by_guid(dest, &guid)?.is_some() {
+            report.messages_skipped += 1;
+            continue;
+        }
+
+        let mut text: Option<String> = row.get("text").map_err(get_err)?;
+        // U+FFFC is the attachment placeholder; an empty or placeholder-only
+        // text means the real content lives in attributedBody
+        if text.as_deref().is_none_or(|t| t.trim_matches('\u{fffc}').trim().is_empty()) {
+            let blob: Option<Vec<u8>> = row.get("attributedBody").unwrap_or(None);
+            if let Some(decoded) = blob.as_deref().and_then(AttributedBody::text_from_typedstream) {
+                text = Some(decoded);
+            }
+        }
+
+        let date = |name: &str| -> Option<i64> {
+            row.get::<_, Option<i64>>(name).ok().flatten().and_then(apple_date_to_unix_ms)
+        };
+        let date_created = date("date");
+        let associated_type: Option<i64> = row.get("associated_message_type").unwrap_or(None);
+        let handle_rowid: Option<i64> = row.get("handle_id").unwrap_or(None);
+
+        let mut msg = Message::from_server_map(&serde_json::json!({
+            "guid": guid,
+            "text": text,
+            "subject": row.get::<_, Option<String>>("subject").unwrap_or(None),
+            "error": row.get::<_, Option<i64>>("error").unwrap_or(None).unwrap_or(0),
+            "dateCreated": date_created,
+            "dateRead": date("date_read"),
+            "dateDelivered": date("date_delivered"),
+            "dateEdited": date("date_edited").map(|ms| ms.to_string()),
+            "isFromMe": row.get::<_, Option<i64>>("is_from_me").unwrap_or(None).unwrap_or(0) != 0,
+            "isDelivered": row.get::<_, Option<i64>>("is_delivered").unwrap_or(None).unwrap_or(0) != 0,
+            "itemType": row.get::<_, Option<i64>>("item_type").unwrap_or(None).unwrap_or(0),
+            "groupTitle": row.get::<_, Option<String>>("group_title").unwrap_or(None),
+            "groupActionType": row.get::<_, Option<i64>>("group_action_type").unwrap_or(None).unwrap_or(0),
+            "balloonBundleId": row.get::<_, Option<String>>("balloon_bundle_id").unwrap_or(None),
+            "associatedMessageGuid": row.get::<_, Option<String>>("associated_message_guid").unwrap_or(None),
+            "associatedMessageType": associated_type.filter(|&t| t != 0).map(|t| t.to_string()),
+            "expressiveSendStyleId": row.get::<_, Option<String>>("expressive_send_style_id").unwrap_or(None),
+            "threadOriginatorGuid": row.get::<_, Option<String>>("thread_originator_guid").unwrap_or(None),
+            "threadOriginatorPart": row.get::<_, Option<String>>("thread_originator_part").unwrap_or(None),
+            "hasAttachments": row.get::<_, Option<i64>>("cache_has_attachments").unwrap_or(None).unwrap_or(0) != 0,
+        }))?;
+        msg.chat_id = Some(chat_id);
+        msg.handle_id = handle_rowid.and_then(|h| handle_ids.get(&h).copied());
+
+        let id = msg.save(dest)?;
+        ids.insert(rowid, id);
+        report.messages_imported += 1;
+
+        if let Some(ms) = date_created {
+            let entry = latest.entry(chat_id).or_insert(ms);
+            *entry = (*entry).max(ms);
+        }
+    }
+
+    // Give newly created chats a last-message date so they sort correctly
+    for (chat_id, ms) in latest {
+        if new_chats.contains(&chat_id) {
+            dest.execute(
+                "UPDATE chats SET latest_message_date = ?1 WHERE id = ?2",
+                rusqlite::params![ms.to_string(), chat_id],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        }
+    }
+    Ok(ids)
+}
+
+fn import_attachments(
+    source: &Connection,
+    dest: &Connection,
+    message_ids: &HashMap<i64, i64>,
+    options: &ChatDbImportOptions,
+    report: &mut ChatDbImportReport,
+) -> BbResult<()> {
+    if message_ids.is_empty()
+        || table_columns(source, "attachment")?.is_empty()
+        || table_columns(source, "message_attachment_join")?.is_empty()
+    {
+        return Ok(());
+    }
+
+    let mut stmt = source
+        .prepare(
+            "SELECT maj.message_id, a.guid, a.filename, a.mime_type, a.transfer_name,
+                    a.total_bytes, a.is_outgoing, a.uti
+             FROM attachment a
+             INNER JOIN message_attachment_join maj ON maj.attachment_id = a.ROWID",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                serde_json::json!({
+                    "guid": row.get::<_, String>(1)?,
+                    "mimeType": row.get::<_, Option<String>>(3)?,
+                    "transferName": row.get::<_, Option<String>>(4)?,
+                    "totalBytes": row.get::<_, Option<i64>>(5)?,
+                    "isOutgoing": row.get::<_, Option<i64>>(6)?.map(|v| v != 0),
+                    "uti": row.get::<_, Option<String>>(7)?,
+                }),
+            ))
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+    for row in rows {
+        let (apple_message, guid, filename, json) = row.map_err(|e| BbError::Database(e.to_string()))?;
+        // Attachments of skipped (already synced) messages stay as they are
+        let Some(&message_id) = message_ids.get(&apple_message) else {
+            continue;
+        };
+
+        let mut attachment = Attachment::from_server_map(&json)?;
+        attachment.message_id = Some(message_id);
+        attachment.save(dest)?;
+        report.attachments_imported += 1;
+
+        if let (Some(src_root), Some(cache_dir)) = (&options.attachments_dir, &options.cache_dir) {
+            match filename.as_deref().and_then(|f| resolve_attachment_file(src_root, f)) {
+                Some(src) => {
+                    let target = cache_dir.join(cache_file_name(&guid, attachment.file_extension()));
+                    std::fs::create_dir_all(cache_dir)?;
+                    match std::fs::copy(&src, &target) {
+                        Ok(_) => report.attachment_files_copied += 1,
+                        Err(e) => {
+                            warn!("failed to copy attachment {}: {e}", src.display());
+                            report.attachment_files_missing += 1;
+                        }
+                    }
+                }
+                None => {
+                    debug!("attachment file not found for {guid}");
+                    report.attachment_files_missing += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Map an Apple attachment path (`~/Library/Messages/Attachments/ab/11/...`)
+/// onto a copy of the `Attachments/` directory.
+fn resolve_attachment_file(attachments_dir: &Path, apple_path: &str) -> Option<PathBuf> {
+    let relative = match apple_path.find("Attachments/") {
+        Some(idx) => &apple_path[idx + "Attachments/".len()..],
+        None => apple_path.trim_start_matches('/'),
+    };
+    let candidate = attachments_dir.join(relative);
+    candidate.is_file().then_some(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_db() -> (Database, tempfile::TempDir) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        let config = bb_core::config::DatabaseConfig::default();
+        let db = Database::init(&path, &config).unwrap();
+        (db, dir)
+    }
+
+    /// Build a minimal chat.db with the columns the importer reads.
+    fn create_chat_db(path: &Path) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT, service TEXT, country TEXT);
+             CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, guid TEXT, chat_identifier TEXT,
+                                display_name TEXT, style INTEGER, is_archived INTEGER);
+             CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, handle_id INTEGER,
+                                   service TEXT, date INTEGER, is_from_me INTEGER, is_delivered INTEGER,
+                                   attributedBody BLOB, associated_message_guid TEXT,
+                                   associated_message_type INTEGER, cache_has_attachments INTEGER);
+             CREATE TABLE chat_handle_join (chat_id INTEGER, handle_id INTEGER);
+             CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER, message_date INTEGER);
+             CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, guid TEXT, filename TEXT, mime_type TEXT,
+                                      transfer_name TEXT, total_bytes INTEGER, is_outgoing INTEGER, uti TEXT);
+             CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+
+             INSERT INTO handle VALUES (1, '+15551234567', 'iMessage', 'us');
+             INSERT INTO chat VALUES (1, 'iMessage;-;+15551234567', '+15551234567', '', 45, 0);
+             INSERT INTO chat_handle_join VALUES (1, 1);
+             INSERT INTO message VALUES (1, 'apple-1', 'hi there', 1, 'iMessage', 721692800000000000, 0, 1, NULL, NULL, 0, 0);
+             INSERT INTO message VALUES (2, 'apple-2', NULL, 0, 'iMessage', 721692860000000000, 1, 1, NULL, NULL, 0, 1);
+             INSERT INTO message VALUES (3, 'apple-3', NULL, 1, 'iMessage', 721692920000000000, 0, 1, NULL, 'p:0/apple-2', 2000, 0);
+             INSERT INTO chat_message_join VALUES (1, 1, 0), (1, 2, 0), (1, 3, 0);
+             INSERT INTO attachment VALUES (1, 'att-1', '~/Library/Messages/Attachments/ab/01/att-1/IMG_0001.jpg',
+                                            'image/jpeg', 'IMG_0001.jpg', 4, 1, 'public.jpeg');
+             INSERT INTO message_attachment_join VALUES (2, 1);",
+        )
+        .unwrap();
+
+        let mut blob = b"\x84\x08NSString\x01\x94\x84\x01+".to_vec();
+        blob.push(9);
+        blob.extend_from_slice(b"look here");
+        conn.execute("UPDATE message SET attributedBody = ?1 WHERE ROWID = 2", [blob])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_apple_date_conversion() {
+        // 2023-11-14T22:13:20Z in nanoseconds and seconds since 2001-01-01
+        assert_eq!(apple_date_to_unix_ms(721_692_800_000_000_000), Some(1_700_000_000_000));
+        assert_eq!(apple_date_to_unix_ms(721_692_800), Some(1_700_000_000_000));
+        assert_eq!(apple_date_to_unix_ms(0), None);
+    }
+
+    #[test]
+    fn test_import_and_dedupe() {
+        let (db, dir) = create_test_db();
+        let chat_db = dir.path().join("chat.db");
+        create_chat_db(&chat_db);
+
+        let attachments = dir.path().join("Attachments");
+        std::fs::create_dir_all(attachments.join("ab/01/att-1")).unwrap();
+        std::fs::write(attachments.join("ab/01/att-1/IMG_0001.jpg"), b"jpeg").unwrap();
+        let options = ChatDbImportOptions {
+            attachments_dir: Some(attachments),
+            cache_dir: Some(dir.path().join("cache")),
+        };
+
+        let importer = ChatDbImporter::new(db.clone());
+        let report = importer.import(&chat_db, &options).unwrap();
+        assert_eq!(report.chats_imported, 1);
+        assert_eq!(report.messages_imported, 3);
+        assert_eq!(report.attachments_imported, 1);
+        assert_eq!(report.attachment_files_copied, 1);
+        assert!(dir.path().join("cache/att-1.jpg").exists());
+
+        let conn = db.conn().unwrap();
+        let decoded = queries::find_message_by_guid(&conn, "apple-2").unwrap().unwrap();
+        assert_eq!(decoded.text.as_deref(), Some("look here"));
+        assert!(decoded.is_from_me);
+        let received = queries::find_message_by_guid(&conn, "apple-1").unwrap().unwrap();
+        assert_eq!(received.date_created.as_deref(), Some("1700000000000"));
+        assert!(received.handle_id.is_some());
+        let reaction = queries::find_message_by_guid(&conn, "apple-3").unwrap().unwrap();
+        assert_eq!(reaction.associated_message_type.as_deref(), Some("2000"));
+
+        let chat = queries::find_chat_by_guid(&conn, "iMessage;-;+15551234567").unwrap().unwrap();
+        assert_eq!(queries::load_chat_participants(&conn, chat.id.unwrap()).unwrap().len(), 1);
+        drop(conn);
+
+        // A second import finds everything already present
+        let again = importer.import(&chat_db, &ChatDbImportOptions::default()).unwrap();
+        assert_eq!(again.chats_imported, 0);
+        assert_eq!(again.chats_existing, 1);
+        assert_eq!(again.messages_imported, 0);
+        assert_eq!(again.messages_skipped, 3);
+    }
+
+    #[test]
+    fn test_rejects_non_chat_db() {
+        let (db, dir) = create_test_db();
+        let other = dir.path().join("other.db");
+        Connection::open(&other).unwrap().execute_batch("CREATE TABLE t (x);").unwrap();
+        let importer = ChatDbImporter::new(db);
+        assert!(matches!(
+            importer.import(&other, &ChatDbImportOptions::default()),
+            Err(BbError::InvalidInput(_))
+        ));
+    }
+}
@@ -1,17 +1,54 @@
 //! Contact service for managing address book contacts.
 //!
 //! Handles contact sync from the server, local search, phone suffix matching
-//! (7-15 digits), two-pass network fetch, and handle-to-contact resolution.
+//! (7-15 digits), two-pass network fetch, handle-to-contact resolution, and
+//! vCard import/export.
 
+use serde::Serialize;
 use tracing::{info, warn};
 use bb_core::error::BbResult;
-use bb_models::{Database, Contact, Handle};
+use bb_models::{Database, Contact, Handle, VCardVersion};
+use bb_models::models::contact::{StructuredName, LOCAL_ID_PREFIX};
 use bb_models::queries;
+use bb_models::vcard;
 use bb_api::ApiClient;
 
 use crate::event_bus::{AppEvent, EventBus};
 use crate::service::{Service, ServiceState};
 
+/// Outcome of a vCard import.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+}
+
+/// A contact card prepared for display, with the avatar inlined as base64.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContactCard {
+    pub display_name: String,
+    pub structured_name: Option<StructuredName>,
+    pub phones: Vec<String>,
+    pub emails: Vec<String>,
+    pub avatar_base64: Option<String>,
+}
+
+impl From<&Contact> for ContactCard {
+    fn from(contact: &Contact) -> Self {
+        use base64::Engine;
+        Self {
+            display_name: contact.display_name.clone(),
+            structured_name: contact.structured_name_parsed(),
+            phones: contact.phone_list(),
+            emails: contact.email_list(),
+            avatar_base64: contact
+                .avatar
+                .as_deref()
+                .map(|a| base64::engine::general_purpose::STANDARD.encode(a)),
+        }
+    }
+}
+
 /// Service for managing contacts.
 ///
 /// Handles contact sync from the server, local search, and handle-to-contact
@@ -51,25 +88,40 @@ impl ContactService {
         queries::find_contact_by_external_id(&conn, external_id)
     }
 
-    /// Find a contact by phone number using suffix matching.
+    /// Find a contact by phone number.
     ///
-    /// Searches using the last 7-15 digits of the phone number. This handles
-    /// international formatting differences (e.g., +1-555-123-4567 matches
-    /// 555-123-4567 or 5551234567).
+    /// Tries an exact match on the E.164 form first, normalized with the
+    /// stored handle's country or the default region. Otherwise falls back to
+    /// the last 7-15 digits, skipping contacts whose matching number resolves
+    /// to a different E.164 number (e.g. the same digits in another country).
     pub fn find_contact_by_phone(&self, phone: &str) -> BbResult<Option<Contact>> {
+        let conn = self.database.conn()?;
+
+        // Exact E.164 match, using the stored handle's country when known
+        let normalized = match queries::find_handle_normalized_address(&conn, phone)? {
+            Some(n) => Some(n),
+            None => bb_models::phone::normalize(phone, None),
+        };
+        if let Some(ref number) = normalized {
+            if let Some(contact) = queries::find_contact_by_normalized_phone(&conn, number)? {
+                return Ok(Some(contact));
+            }
+        }
+
         let digits = extract_digits(phone);
         if digits.len() < 7 {
             return Ok(None);
         }
 
-        let conn = self.database.conn()?;
-
         // Try suffix lengths from 7 to min(15, digit_count) for best match
         let max_suffix = digits.len().min(15);
         for suffix_len in (7..=max_suffix).rev() {
             let suffix = &digits[digits.len() - suffix_len..];
-            let results = queries::search_contacts_by_phone_suffix(&conn, suffix, 1)?;
-            if let Some(contact) = results.into_iter().next() {
+            let results = queries::search_contacts_by_phone_suffix(&conn, suffix, 10)?;
+            let found = results
+                .into_iter()
+                .find(|c| !conflicts_with(c, suffix, normalized.as_deref()));
+            if let Some(contact) = found {
                 return Ok(Some(contact));
             }
         }
@@ -95,8 +147,8 @@ impl ContactService {
         let contacts_json = api.get_contacts(false).await?;
         let conn = self.database.conn()?;
 
-        // Clear existing contacts
-        queries::delete_all_contacts(&conn)?;
+        // Clear existing server contacts, keeping locally imported ones
+        queries::delete_synced_contacts(&conn)?;
 
         let mut count = 0;
         for contact_json in &contacts_json {
@@ -217,6 +269,79 @@ impl ContactService {
         let contacts = self.list_contacts()?;
         Ok(contacts.len() as i64)
     }
+
+    /// Import every card in a vCard file into local contacts.
+    pub fn import_vcards(&self, data: &str) -> BbResult<ImportSummary> {
+        self.import_contacts(vcard::parse(data)?)
+    }
+
+    /// Save parsed contacts as local contacts.
+    ///
+    /// A contact updates an existing local one with the same UID, or failing
+    /// that, the same name and a shared phone or email; its phones and emails
+    /// are merged in. Server-synced contacts are never modified.
+    pub fn import_contacts(&self, contacts: Vec<Contact>) -> BbResult<ImportSummary> {
+        let conn = self.database.conn()?;
+        let local: Vec<Contact> = queries::list_contacts(&conn)?
+            .into_iter()
+            .filter(Contact::is_local)
+            .collect();
+
+        let mut summary = ImportSummary::default();
+        for mut contact in contacts {
+            let existing = local.iter().find(|e| match contact.external_id {
+                Some(ref id) => e.external_id.as_deref() == Some(id.as_str()),
+                None => {
+                    e.display_name == contact.display_name
+                        && contact
+                            .phone_list()
+                            .iter()
+                            .chain(contact.email_list().iter())
+                            .any(|a| e.matches_address(a))
+                }
+            });
+
+            match existing {
+                Some(existing) => {
+                    contact.external_id = existing.external_id.clone();
+                    contact.phones = merge_addresses(existing.phone_list(), contact.phone_list());
+                    contact.emails = merge_addresses(existing.email_list(), contact.email_list());
+                    summary.updated += 1;
+                }
+                None => {
+                    if contact.external_id.is_none() {
+                        contact.external_id = Some(format!("{LOCAL_ID_PREFIX}{}", uuid::Uuid::new_v4()));
+                    }
+                    summary.added += 1;
+                }
+            }
+            contact.save(&conn)?;
+        }
+
+        queries::link_contacts_to_handles(&conn)?;
+        info!("imported contacts: {} added, {} updated", summary.added, summary.updated);
+        self.event_bus.emit(AppEvent::ContactsUpdated {
+            count: summary.added + summary.updated,
+        });
+        Ok(summary)
+    }
+
+    /// Export all contacts as a vCard file.
+    pub fn export_vcards(&self, version: VCardVersion) -> BbResult<String> {
+        let contacts = self.list_contacts()?;
+        Ok(vcard::serialize_all(&contacts, version))
+    }
+}
+
+/// Union two address lists, keeping order and dropping duplicates, as JSON.
+fn merge_addresses(existing: Vec<String>, incoming: Vec<String>) -> String {
+    let mut merged = existing;
+    for address in incoming {
+        if !merged.iter().any(|a| a.eq_ignore_ascii_case(&address)) {
+            merged.push(address);
+        }
+    }
+    serde_json::to_string(&merged).unwrap_or_else(|_| "[]".into())
 }
 
 /// Extract only digit characters from a phone string.
@@ -224,6 +349,24 @@ fn extract_digits(phone: &str) -> String {
     phone.chars().filter(|c| c.is_ascii_digit()).collect()
 }
 
+/// Whether a suffix match against `contact` is known to be a different
+/// number: one of its phones ends in `suffix` but normalizes to something
+/// other than `normalized`.
+fn conflicts_with(contact: &Contact, suffix: &str, normalized: Option<&str>) -> bool {
+    let Some(normalized) = normalized else {
+        return false;
+    };
+    let matching: Vec<String> = contact
+        .phone_list()
+        .into_iter()
+        .filter(|p| extract_digits(p).ends_with(suffix))
+        .collect();
+    !matching.is_empty()
+        && matching.iter().all(|p| {
+            bb_models::phone::normalize(p, None).is_some_and(|n| n != normalized)
+        })
+}
+
 impl Service for ContactService {
     fn name(&self) -> &str { "contact" }
     fn state(&self) -> ServiceState { self.state }
@@ -275,6 +418,46 @@ mod tests {
         assert!(svc.find_contact_by_phone("12345").unwrap().is_none());
     }
 
+    #[test]
+    fn test_resolve_prefers_exact_normalized_match() {
+        let db = create_test_db();
+        let bus = crate::event_bus::EventBus::new(16);
+        let svc = ContactService::new(db.clone(), bus);
+        bb_models::phone::set_default_region("US");
+
+        let conn = db.conn().unwrap();
+        for (id, name, phone) in [
+            ("c1", "London Office", "+44 20 7031 3000"),
+            ("c2", "Local Shop", "(207) 031-3000"),
+        ] {
+            Contact::from_server_map(&serde_json::json!({
+                "id": id, "displayName": name, "phoneNumbers": [phone]
+            }))
+            .unwrap()
+            .save(&conn)
+            .unwrap();
+        }
+
+        // A GB handle written with a trunk prefix resolves via its country
+        let mut handle = Handle::from_server_map(&serde_json::json!({
+            "address": "020 7031 3000", "service": "SMS", "country": "gb"
+        }))
+        .unwrap();
+        handle.save(&conn).unwrap();
+        drop(conn);
+
+        assert_eq!(
+            svc.resolve_display_name("020 7031 3000").unwrap().as_deref(),
+            Some("London Office")
+        );
+        assert_eq!(
+            svc.resolve_display_name("+1 207-031-3000").unwrap().as_deref(),
+            Some("Local Shop")
+        );
+        // Same trailing digits in a third country match neither contact
+        assert_eq!(svc.resolve_display_name("+61 2 0703 13000").unwrap(), None);
+    }
+
     #[test]
     fn test_empty_contacts() {
         let db = create_test_db();
@@ -283,4 +466,31 @@ mod tests {
         assert_eq!(svc.count().unwrap(), 0);
         assert!(svc.resolve_display_name("test@test.com").unwrap().is_none());
     }
+
+    #[test]
+    fn test_import_and_export_vcards() {
+        let db = create_test_db();
+        let bus = crate::event_bus::EventBus::new(16);
+        let svc = ContactService::new(db, bus);
+
+        let card = "BEGIN:VCARD\nVERSION:3.0\nFN:Jane Roe\nTEL:+15550001111\nEND:VCARD\n";
+        let summary = svc.import_vcards(card).unwrap();
+        assert_eq!((summary.added, summary.updated), (1, 0));
+
+        // Same name and number without a UID merges into the existing contact
+        let again = "BEGIN:VCARD\nVERSION:3.0\nFN:Jane Roe\nTEL:+15550001111\n\
+EMAIL:jane@example.org\nEND:VCARD\n";
+        let summary = svc.import_vcards(again).unwrap();
+        assert_eq!((summary.added, summary.updated), (0, 1));
+
+        let contacts = svc.list_contacts().unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert!(contacts[0].is_local());
+        assert_eq!(contacts[0].email_list(), vec!["jane@example.org"]);
+
+        let exported = svc.export_vcards(VCardVersion::V4).unwrap();
+        assert!(exported.contains("VERSION:4.0"));
+        assert!(exported.contains("FN:Jane Roe"));
+        assert!(exported.contains("+15550001111"));
+    }
 }
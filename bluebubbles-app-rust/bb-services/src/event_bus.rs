@@ -25,6 +25,12 @@ pub enum AppEvent {
         message_guid: String,
         chat_guid: String,
     },
+    /// An outgoing message moved between waiting, sending, sent and failed.
+    MessageStatusChanged {
+        temp_guid: String,
+        chat_guid: String,
+        status: crate::queue::OutgoingStatus,
+    },
     /// A message was successfully sent (temp GUID replaced with real GUID).
     MessageSent {
         temp_guid: String,
@@ -71,6 +77,23 @@ pub enum AppEvent {
     ContactsUpdated {
         count: usize,
     },
+    /// Persons were relinked, merged, split or renamed. Empty when the
+    /// change came from automatic relinking.
+    PersonsUpdated {
+        person_ids: Vec<i64>,
+    },
+    /// The server rejected a queued chat change, and it was rolled back
+    /// locally.
+    OutboxMutationFailed {
+        chat_guid: String,
+        description: String,
+        error: String,
+    },
+    /// Chat folders were created, changed, deleted or had chats added or
+    /// removed.
+    FoldersUpdated {
+        folder_ids: Vec<i64>,
+    },
     /// Theme was changed.
     ThemeChanged {
         theme_name: String,
@@ -115,6 +138,30 @@ pub enum AppEvent {
     AliasesRemoved {
         aliases: Vec<String>,
     },
+    /// A local database snapshot was taken.
+    SnapshotCreated {
+        id: String,
+        pruned: usize,
+    },
+    /// The database was restored from a local snapshot; anything loaded
+    /// from it before is stale.
+    SnapshotRestored {
+        id: String,
+    },
+    /// A chat's held notifications are ready to show as one summary.
+    NotificationDigestReady {
+        digest: crate::notification::NotificationDigest,
+    },
+    /// A one-time code was found in a received message.
+    OtpDetected {
+        message_guid: String,
+        chat_guid: String,
+        code: String,
+        /// Sender's contact name, or their address.
+        sender: Option<String>,
+        /// When the code stops being valid, in epoch milliseconds.
+        expires_at: i64,
+    },
 }
 
 /// Application-wide event bus backed by a tokio broadcast channel.
@@ -168,6 +215,7 @@ fn event_label(event: &AppEvent) -> &'static str {
     match event {
         AppEvent::MessageReceived { .. } => "MessageReceived",
         AppEvent::MessageUpdated { .. } => "MessageUpdated",
+        AppEvent::MessageStatusChanged { .. } => "MessageStatusChanged",
         AppEvent::MessageSent { .. } => "MessageSent",
         AppEvent::MessageFailed { .. } => "MessageFailed",
         AppEvent::ChatUpdated { .. } => "ChatUpdated",
@@ -177,6 +225,9 @@ fn event_label(event: &AppEvent) -> &'static str {
         AppEvent::SyncProgress { .. } => "SyncProgress",
         AppEvent::SyncComplete { .. } => "SyncComplete",
         AppEvent::ContactsUpdated { .. } => "ContactsUpdated",
+        AppEvent::PersonsUpdated { .. } => "PersonsUpdated",
+        AppEvent::FoldersUpdated { .. } => "FoldersUpdated",
+        AppEvent::OutboxMutationFailed { .. } => "OutboxMutationFailed",
         AppEvent::ThemeChanged { .. } => "ThemeChanged",
         AppEvent::ParticipantAdded { .. } => "ParticipantAdded",
         AppEvent::ParticipantRemoved { .. } => "ParticipantRemoved",
@@ -186,6 +237,10 @@ fn event_label(event: &AppEvent) -> &'static str {
         AppEvent::AttachmentDownloaded { .. } => "AttachmentDownloaded",
         AppEvent::AttachmentDownloadFailed { .. } => "AttachmentDownloadFailed",
         AppEvent::AliasesRemoved { .. } => "AliasesRemoved",
+        AppEvent::SnapshotCreated { .. } => "SnapshotCreated",
+        AppEvent::SnapshotRestored { .. } => "SnapshotRestored",
+        AppEvent::NotificationDigestReady { .. } => "NotificationDigestReady",
+        AppEvent::OtpDetected { .. } => "OtpDetected",
     }
 }
 
@@ -0,0 +1,875 @@
+//! Conversation export service.
+//!
+//! Writes a chat transcript as HTML, Markdown, JSON, or plain text with
+//! resolved sender names, reactions, replies, and edit history. Locally
+//! cached attachments are copied into an `attachments/` folder next to the
+//! transcript. A redacted mode replaces participant identities with
+//! pseudonyms and masks phone numbers and email addresses in message text.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use bb_core::error::{BbError, BbResult};
+use bb_models::models::message_summary_info::EditedContent;
+use bb_models::{Attachment, Database, Message, MessageTombstone};
+use bb_models::queries;
+
+use crate::attachment::cache_file_name;
+use crate::contact::ContactService;
+use crate::event_bus::EventBus;
+use crate::service::{Service, ServiceState};
+pub(crate) use crate::util::timestamp_ms;
+use crate::util::strip_part_prefix;
+
+/// Fewest digits in a run that is masked as a phone number.
+const MIN_PHONE_DIGITS: usize = 7;
+
+/// Digits and separators that may be a phone number.
+static PHONE_CANDIDATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\+?\(?\d[\d\s().-]{5,}\d").unwrap());
+
+/// Name of the folder attachments are copied into.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Output format for a conversation export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    Markdown,
+    Json,
+    Text,
+}
+
+impl ExportFormat {
+    /// File extension used for the transcript.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Text => "txt",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = BbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "html" | "htm" => Ok(Self::Html),
+            "md" | "markdown" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            "txt" | "text" => Ok(Self::Text),
+            other => Err(BbError::InvalidInput(format!(
+                "unknown export format '{other}' (expected html, md, json, or txt)"
+            ))),
+        }
+    }
+}
+
+/// Options controlling a conversation export.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Directory the transcript and `attachments/` folder are written to.
+    pub out_dir: PathBuf,
+    /// Only include messages at or after this time (Unix ms).
+    pub after: Option<i64>,
+    /// Only include messages before this time (Unix ms).
+    pub before: Option<i64>,
+    /// Replace identities with pseudonyms and mask addresses in text.
+    pub redact: bool,
+}
+
+impl ExportOptions {
+    /// Options for a full, unredacted export.
+    pub fn new(format: ExportFormat, out_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            format,
+            out_dir: out_dir.into(),
+            after: None,
+            before: None,
+            redact: false,
+        }
+    }
+}
+
+/// A conversation ready to be rendered.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transcript {
+    pub chat_guid: String,
+    pub title: String,
+    pub participants: Vec<String>,
+    pub exported_at: String,
+    pub redacted: bool,
+    pub messages: Vec<ExportedMessage>,
+}
+
+/// A single message in an export.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedMessage {
+    pub guid: String,
+    /// When the message was sent (RFC 3339), if known.
+    pub date: Option<String>,
+    pub sender: String,
+    pub is_from_me: bool,
+    pub text: String,
+    /// Earlier versions of edited parts, oldest first.
+    pub edits: Vec<EditedContent>,
+    pub unsent: bool,
+    pub reply_to: Option<ReplyRef>,
+    pub reactions: Vec<ExportedReaction>,
+    pub attachments: Vec<ExportedAttachment>,
+}
+
+/// The message a reply points at.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplyRef {
+    pub guid: String,
+    pub sender: Option<String>,
+    pub text: Option<String>,
+}
+
+/// A tapback left on a message.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedReaction {
+    pub sender: String,
+    pub reaction: String,
+}
+
+/// An attachment referenced by an exported message.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedAttachment {
+    pub name: String,
+    pub mime_type: Option<String>,
+    /// Path relative to the transcript, when the file was copied.
+    pub path: Option<String>,
+    #[serde(skip)]
+    source: Option<PathBuf>,
+}
+
+/// Result of writing an export to disk.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSummary {
+    pub transcript_path: PathBuf,
+    pub message_count: usize,
+    pub attachments_copied: usize,
+    pub attachments_missing: usize,
+}
+
+/// Service for exporting conversations from the local database.
+pub struct ExportService {
+    state: ServiceState,
+    database: Database,
+    event_bus: EventBus,
+    cache_dir: PathBuf,
+}
+
+impl ExportService {
+    /// Create a new ExportService reading attachments from `cache_dir`.
+    pub fn new(database: Database, event_bus: EventBus, cache_dir: PathBuf) -> Self {
+        Self {
+            state: ServiceState::Created,
+            database,
+            event_bus,
+            cache_dir,
+        }
+    }
+
+    /// Export a chat to `options.out_dir` and return what was written.
+    pub fn export_chat(&self, chat_guid: &str, options: &ExportOptions) -> BbResult<ExportSummary> {
+        let mut transcript = self.build_transcript(chat_guid, options)?;
+
+        std::fs::create_dir_all(&options.out_dir)?;
+        let (copied, missing) = if options.redact {
+            (0, 0)
+        } else {
+            self.copy_attachments(&mut transcript, &options.out_dir)?
+        };
+
+        let rendered = match options.format {
+            ExportFormat::Html => render_html(&transcript),
+            ExportFormat::Markdown => render_markdown(&transcript),
+            ExportFormat::Text => render_text(&transcript),
+            ExportFormat::Json => serde_json::to_string_pretty(&transcript)
+                .map_err(|e| BbError::Serialization(e.to_string()))?,
+        };
+
+        let file_name = format!(
+            "{}.{}",
+            sanitize_file_name(if options.redact { "conversation" } else { &transcript.title }),
+            options.format.extension()
+        );
+        let transcript_path = options.out_dir.join(file_name);
+        std::fs::write(&transcript_path, rendered)?;
+
+        info!(
+            "exported {} messages from {chat_guid} to {}",
+            transcript.messages.len(),
+            transcript_path.display()
+        );
+        Ok(ExportSummary {
+            transcript_path,
+            message_count: transcript.messages.len(),
+            attachments_copied: copied,
+            attachments_missing: missing,
+        })
+    }
+
+    /// Build the transcript for a chat without writing anything.
+    pub fn build_transcript(&self, chat_guid: &str, options: &ExportOptions) -> BbResult<Transcript> {
+        let conn = self.database.conn()?;
+        let chat = queries::find_chat_by_guid(&conn, chat_guid)?
+            .ok_or_else(|| BbError::ChatNotFound(chat_guid.to_string()))?;
+        let chat_id = chat
+            .id
+            .ok_or_else(|| BbError::ChatNotFound(chat_guid.to_string()))?;
+
+        let mut names = SenderNames::new(
+            ContactService::new(self.database.clone(), self.event_bus.clone()),
+            options.redact,
+        );
+
+        let participants: Vec<String> = queries::load_chat_participants(&conn, chat_id)?
+            .iter()
+            .map(|h| names.for_handle(&conn, h.id))
+            .collect();
+
+        let all = queries::all_messages_for_chat(&conn, chat_id)?;
+        let mut tombstones_by_guid: HashMap<String, Vec<MessageTombstone>> = HashMap::new();
+        for tombstone in MessageTombstone::find_for_chat(&conn, chat_id)? {
+            tombstones_by_guid.entry(tombstone.message_guid.clone()).or_default().push(tombstone);
+        }
+        for tombstones in tombstones_by_guid.values_mut() {
+            tombstones.sort_by_key(|t| t.part_index);
+        }
+
+        // Split tapbacks from regular messages and fold them onto their targets
+        let mut reactions: HashMap<String, Vec<ExportedReaction>> = HashMap::new();
+        let mut by_guid: HashMap<String, &Message> = HashMap::new();
+        for msg in &all {
+            if msg.is_reaction() {
+                let (Some(target), Some(kind)) =
+                    (msg.associated_message_guid.as_deref(), msg.associated_message_type.as_deref())
+                else {
+                    continue;
+                };
+                let Some((removed, label)) = reaction_label(kind) else {
+                    continue;
+                };
+                let sender = names.for_message(&conn, msg);
+                let entry = reactions.entry(strip_part_prefix(target).to_string()).or_default();
+                if removed {
+                    entry.retain(|r| !(r.sender == sender && r.reaction == label));
+                } else {
+                    entry.push(ExportedReaction { sender, reaction: label.to_string() });
+                }
+            } else if let Some(guid) = &msg.guid {
+                by_guid.insert(guid.clone(), msg);
+            }
+        }
+
+        let mut messages = Vec::new();
+        for msg in all.iter().filter(|m| !m.is_reaction()) {
+            let Some(guid) = msg.guid.clone() else {
+                continue;
+            };
+            let millis = msg.date_created.as_deref().and_then(timestamp_ms);
+            if !in_range(millis, options.after, options.before) {
+                continue;
+            }
+
+            let tombstones = tombstones_by_guid.get(&guid).map(Vec::as_slice).unwrap_or_default();
+            let summary = msg.summary_info();
+            let unsent = !tombstones.is_empty()
+                || summary.as_ref().is_some_and(|s| !s.retracted_parts.is_empty());
+            // Locally deleted messages are left out; unsent ones stay as a marker
+            if msg.date_deleted.is_some() && !unsent {
+                continue;
+            }
+
+            let mut text = if msg.is_group_event() {
+                msg.group_title.clone().unwrap_or_else(|| "[group event]".to_string())
+            } else {
+                msg.full_text()
+            };
+            if text.is_empty() && unsent {
+                text = tombstones
+                    .iter()
+                    .filter_map(|t| t.original_text.clone())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+            }
+
+            let edits: Vec<EditedContent> = summary
+                .as_ref()
+                .map(|s| {
+                    s.edited_content
+                        .keys()
+                        .flat_map(|&part| s.previous_versions(part).to_vec())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let reply_to = msg.thread_originator_guid.as_ref().map(|origin| {
+                let original = by_guid.get(origin);
+                ReplyRef {
+                    guid: origin.clone(),
+                    sender: original.map(|m| names.for_message(&conn, m)),
+                    text: original.map(|m| m.full_text()),
+                }
+            });
+
+            let attachments = match msg.id {
+                Some(id) if msg.has_attachments => queries::load_attachments_for_message(&conn, id)?
+                    .iter()
+                    .map(|a| self.exported_attachment(a))
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            messages.push(ExportedMessage {
+                guid,
+                date: millis.and_then(chrono::DateTime::from_timestamp_millis).map(|d| d.to_rfc3339()),
+                sender: names.for_message(&conn, msg),
+                is_from_me: msg.is_from_me,
+                text,
+                edits,
+                unsent,
+                reply_to,
+                reactions: msg
+                    .guid
+                    .as_ref()
+                    .and_then(|g| reactions.remove(g))
+                    .unwrap_or_default(),
+                attachments,
+            });
+        }
+
+        messages.sort_by_key(|m| m.date.clone());
+
+        let mut transcript = Transcript {
+            chat_guid: chat_guid.to_string(),
+            title: chat.title(),
+            participants,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            redacted: options.redact,
+            messages,
+        };
+        if options.redact {
+            redact_transcript(&mut transcript);
+        }
+        Ok(transcript)
+    }
+
+    fn exported_attachment(&self, attachment: &Attachment) -> ExportedAttachment {
+        let guid = attachment.guid.clone().unwrap_or_default();
+        let source = self.cache_dir.join(cache_file_name(&guid, attachment.file_extension()));
+        ExportedAttachment {
+            name: attachment.transfer_name.clone().unwrap_or_else(|| guid.clone()),
+            mime_type: attachment.mime_type.clone(),
+            path: None,
+            source: source.exists().then_some(source),
+        }
+    }
+
+    /// Copy cached attachment files into `out_dir/attachments` and fill in
+    /// their relative paths. Returns (copied, missing).
+    fn copy_attachments(&self, transcript: &mut Transcript, out_dir: &Path) -> BbResult<(usize, usize)> {
+        let dir = out_dir.join(ATTACHMENTS_DIR);
+        let mut copied = 0;
+        let mut missing = 0;
+        let mut used_names: HashMap<String, usize> = HashMap::new();
+
+        for attachment in transcript.messages.iter_mut().flat_map(|m| m.attachments.iter_mut()) {
+            let Some(source) = &attachment.source else {
+                missing += 1;
+                continue;
+            };
+            if copied == 0 {
+                std::fs::create_dir_all(&dir)?;
+            }
+
+            // Disambiguate repeated names like IMG_0001.jpg
+            let base = sanitize_file_name(&attachment.name);
+            let count = used_names.entry(base.clone()).or_insert(0);
+            *count += 1;
+            let name = if *count == 1 { base } else { format!("{count}_{base}") };
+
+            match std::fs::copy(source, dir.join(&name)) {
+                Ok(_) => {
+                    attachment.path = Some(format!("{ATTACHMENTS_DIR}/{name}"));
+                    copied += 1;
+                }
+                Err(e) => {
+                    warn!("failed to copy attachment {}: {e}", source.display());
+                    missing += 1;
+                }
+            }
+        }
+        Ok((copied, missing))
+    }
+}
+
+impl Service for ExportService {
+    fn name(&self) -> &str { "export" }
+    fn state(&self) -> ServiceState { self.state }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("export service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        Ok(())
+    }
+}
+
+/// Resolves and caches sender names, or hands out pseudonyms when redacting.
+struct SenderNames {
+    contacts: ContactService,
+    redact: bool,
+    cache: HashMap<i64, String>,
+}
+
+impl SenderNames {
+    fn new(contacts: ContactService, redact: bool) -> Self {
+        Self { contacts, redact, cache: HashMap::new() }
+    }
+
+    fn for_message(&mut self, conn: &rusqlite::Connection, msg: &Message) -> String {
+        if msg.is_from_me {
+            return "Me".to_string();
+        }
+        self.for_handle(conn, msg.handle_id)
+    }
+
+    fn for_handle(&mut self, conn: &rusqlite::Connection, handle_id: Option<i64>) -> String {
+        let Some(id) = handle_id else {
+            return "Unknown".to_string();
+        };
+        if let Some(name) = self.cache.get(&id) {
+            return name.clone();
+        }
+        let name = if self.redact {
+            format!("Participant {}", self.cache.len() + 1)
+        } else {
+            match queries::find_handle_by_id(conn, id) {
+                Ok(Some(handle)) => self
+                    .contacts
+                    .resolve_handle_name(&handle)
+                    .unwrap_or_else(|_| handle.address.clone()),
+                _ => "Unknown".to_string(),
+            }
+        };
+        self.cache.insert(id, name.clone());
+        name
+    }
+}
+
+/// Mask identities and addresses throughout a transcript.
+fn redact_transcript(transcript: &mut Transcript) {
+    // Chat GUIDs embed the peer's address ("iMessage;-;+15551234567")
+    transcript.chat_guid = "redacted".to_string();
+    transcript.title = "Redacted conversation".to_string();
+    for msg in &mut transcript.messages {
+        msg.text = mask_addresses(&msg.text);
+        for edit in &mut msg.edits {
+            edit.text = edit.text.as_deref().map(mask_addresses);
+        }
+        if let Some(reply) = &mut msg.reply_to {
+            reply.text = reply.text.as_deref().map(mask_addresses);
+        }
+        for attachment in &mut msg.attachments {
+            attachment.name = "[attachment]".to_string();
+            attachment.source = None;
+        }
+    }
+}
+
+/// Replace email addresses and phone-number-like digit runs with
+/// placeholders. Any run of digits and separators holding at least 7 digits
+/// is masked, so dates and order numbers can be masked too; a missed number
+/// would leak an address.
+fn mask_addresses(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(' ')
+        .map(|word| {
+            let trimmed = word.trim_matches(|c: char| c.is_ascii_punctuation() && c != '+');
+            if let Some(at) = trimmed.find('@') {
+                if at > 0 && trimmed[at + 1..].contains('.') {
+                    return word.replace(trimmed, "[email]");
+                }
+            }
+            word.to_string()
+        })
+        .collect();
+    let text = words.join(" ");
+
+    PHONE_CANDIDATE
+        .replace_all(&text, |c: &regex::Captures| {
+            if c[0].chars().filter(char::is_ascii_digit).count() >= MIN_PHONE_DIGITS {
+                "[phone]".to_string()
+            } else {
+                c[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// Map an associated message type to (is_removal, label).
+pub(crate) fn reaction_label(kind: &str) -> Option<(bool, &'static str)> {
+    let (removed, base) = match kind.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, kind),
+    };
+    let label = match base {
+        "love" | "2000" | "3000" => "loved",
+        "like" | "2001" | "3001" => "liked",
+        "dislike" | "2002" | "3002" => "disliked",
+        "laugh" | "2003" | "3003" => "laughed at",
+        "emphasize" | "2004" | "3004" => "emphasized",
+        "question" | "2005" | "3005" => "questioned",
+        _ => return None,
+    };
+    Some((removed || base.starts_with('3'), label))
+}
+
+/// Parse a date-range bound given as `YYYY-MM-DD`, RFC 3339, or Unix ms.
+///
+/// Bare dates are taken as midnight UTC.
+pub fn parse_date_bound(value: &str) -> BbResult<i64> {
+    if let Some(ms) = timestamp_ms(value) {
+        return Ok(ms);
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().timestamp_millis())
+        .ok_or_else(|| BbError::InvalidInput(format!("invalid date '{value}' (expected YYYY-MM-DD)")))
+}
+
+fn in_range(millis: Option<i64>, after: Option<i64>, before: Option<i64>) -> bool {
+    if after.is_none() && before.is_none() {
+        return true;
+    }
+    let Some(ms) = millis else {
+        return false;
+    };
+    after.is_none_or(|a| ms >= a) && before.is_none_or(|b| ms < b)
+}
+
+/// Make a string safe to use as a file name.
+fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').to_string();
+    if cleaned.is_empty() { "conversation".to_string() } else { cleaned }
+}
+
+/// Human-readable timestamp for text-based formats.
+fn display_date(date: Option<&str>) -> String {
+    date.and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+        .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn reaction_summary(reactions: &[ExportedReaction]) -> String {
+    reactions
+        .iter()
+        .map(|r| format!("{} {}", r.sender, r.reaction))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn render_text(t: &Transcript) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", t.title);
+    let _ = writeln!(out, "Participants: {}", t.participants.join(", "));
+    let _ = writeln!(out, "Exported: {}\n", display_date(Some(&t.exported_at)));
+
+    for m in &t.messages {
+        let _ = write!(out, "[{}] {}: ", display_date(m.date.as_deref()), m.sender);
+        if let Some(reply) = &m.reply_to {
+            let _ = write!(out, "(reply to {}: \"{}\") ", reply.sender.as_deref().unwrap_or("?"), reply.text.as_deref().unwrap_or(""));
+        }
+        let _ = writeln!(out, "{}{}", m.text, if m.unsent { " [unsent]" } else { "" });
+        for edit in &m.edits {
+            let _ = writeln!(out, "    edited from: {}", edit.text.as_deref().unwrap_or(""));
+        }
+        for a in &m.attachments {
+            let _ = writeln!(out, "    attachment: {}", a.path.as_deref().unwrap_or(&a.name));
+        }
+        if !m.reactions.is_empty() {
+            let _ = writeln!(out, "    reactions: {}", reaction_summary(&m.reactions));
+        }
+    }
+    out
+}
+
+fn render_markdown(t: &Transcript) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", t.title);
+    let _ = writeln!(out, "- **Participants:** {}", t.participants.join(", "));
+    let _ = writeln!(out, "- **Exported:** {}\n", display_date(Some(&t.exported_at)));
+
+    for m in &t.messages {
+        let _ = writeln!(out, "**{}** _{}_{}\n", m.sender, display_date(m.date.as_deref()), if m.unsent { " _(unsent)_" } else { "" });
+        if let Some(reply) = &m.reply_to {
+            let _ = writeln!(out, "> {}: {}\n", reply.sender.as_deref().unwrap_or("?"), reply.text.as_deref().unwrap_or("").replace('\n', " "));
+        }
+        if !m.text.is_empty() {
+            let _ = writeln!(out, "{}\n", m.text.replace('\n', "  \n"));
+        }
+        for edit in &m.edits {
+            let _ = writeln!(out, "- _Edited from:_ ~~{}~~", edit.text.as_deref().unwrap_or(""));
+        }
+        for a in &m.attachments {
+            match &a.path {
+                Some(path) if a.mime_type.as_deref().is_some_and(|m| m.starts_with("image/")) => {
+                    let _ = writeln!(out, "- ![{}]({})", a.name, path.replace(' ', "%20"));
+                }
+                Some(path) => {
+                    let _ = writeln!(out, "- [{}]({})", a.name, path.replace(' ', "%20"));
+                }
+                None => {
+                    let _ = writeln!(out, "- {} _(not available)_", a.name);
+                }
+            }
+        }
+        if !m.reactions.is_empty() {
+            let _ = writeln!(out, "- _Reactions:_ {}", reaction_summary(&m.reactions));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(t: &Transcript) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+         body {{ font-family: -apple-system, sans-serif; max-width: 720px; margin: 2em auto; color: #222; }}\n\
+         .msg {{ margin: 0.6em 0; padding: 0.5em 0.8em; border-radius: 12px; background: #e9e9eb; }}\n\
+         .me {{ background: #d7e8ff; }}\n\
+         .meta {{ font-size: 0.8em; color: #666; }}\n\
+         .reply, .edit {{ font-size: 0.85em; color: #666; border-left: 3px solid #ccc; padding-left: 0.5em; }}\n\
+         .edit {{ text-decoration: line-through; }}\n\
+         .reactions {{ font-size: 0.8em; color: #555; }}\n\
+         img {{ max-width: 100%; border-radius: 8px; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n<p class=\"meta\">Participants: {participants}<br>Exported: {exported}</p>\n",
+        title = escape_html(&t.title),
+        participants = escape_html(&t.participants.join(", ")),
+        exported = display_date(Some(&t.exported_at)),
+    );
+
+    for m in &t.messages {
+        let _ = writeln!(out, "<div class=\"msg{}\">", if m.is_from_me { " me" } else { "" });
+        let _ = writeln!(
+            out,
+            "<div class=\"meta\">{} &middot; {}{}</div>",
+            escape_html(&m.sender),
+            display_date(m.date.as_deref()),
+            if m.unsent { " &middot; unsent" } else { "" }
+        );
+        if let Some(reply) = &m.reply_to {
+            let _ = writeln!(
+                out,
+                "<div class=\"reply\">{}: {}</div>",
+                escape_html(reply.sender.as_deref().unwrap_or("?")),
+                escape_html(reply.text.as_deref().unwrap_or(""))
+            );
+        }
+        if !m.text.is_empty() {
+            let _ = writeln!(out, "<div class=\"text\">{}</div>", escape_html(&m.text).replace('\n', "<br>"));
+        }
+        for edit in &m.edits {
+            let _ = writeln!(out, "<div class=\"edit\">{}</div>", escape_html(edit.text.as_deref().unwrap_or("")));
+        }
+        for a in &m.attachments {
+            match &a.path {
+                Some(path) if a.mime_type.as_deref().is_some_and(|m| m.starts_with("image/")) => {
+                    let _ = writeln!(out, "<div><img src=\"{}\" alt=\"{}\"></div>", escape_html(path), escape_html(&a.name));
+                }
+                Some(path) => {
+                    let _ = writeln!(out, "<div><a href=\"{}\">{}</a></div>", escape_html(path), escape_html(&a.name));
+                }
+                None => {
+                    let _ = writeln!(out, "<div class=\"meta\">{} (not available)</div>", escape_html(&a.name));
+                }
+            }
+        }
+        if !m.reactions.is_empty() {
+            let _ = writeln!(out, "<div class=\"reactions\">{}</div>", escape_html(&reaction_summary(&m.reactions)));
+        }
+        out.push_str("</div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bb_models::{Chat, Handle};
+
+    fn create_test_db() -> (Database, tempfile::TempDir) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        let config = bb_core::config::DatabaseConfig::default();
+        let db = Database::init(&path, &config).unwrap();
+        (db, dir)
+    }
+
+    fn seed_chat(db: &Database) {
+        let conn = db.conn().unwrap();
+        let mut handle = Handle::from_server_map(&serde_json::json!({
+            "address": "+15551234567",
+            "service": "iMessage"
+        }))
+        .unwrap();
+        let handle_id = handle.save(&conn).unwrap();
+
+        let mut chat = Chat::from_server_map(&serde_json::json!({
+            "guid": "iMessage;-;+15551234567",
+            "chatIdentifier": "+15551234567",
+            "displayName": "Alex"
+        }))
+        .unwrap();
+        let chat_id = chat.save(&conn).unwrap();
+
+        let messages = [
+            serde_json::json!({"guid": "m1", "text": "call me at 555-123-4567", "isFromMe": false, "dateCreated": 1_700_000_000_000_i64}),
+            serde_json::json!({"guid": "m2", "text": "sure", "isFromMe": true, "dateCreated": 1_700_000_060_000_i64, "threadOriginatorGuid": "m1"}),
+            serde_json::json!({"guid": "m3", "isFromMe": false, "dateCreated": 1_700_000_120_000_i64, "associatedMessageGuid": "p:0/m2", "associatedMessageType": "love"}),
+            serde_json::json!({"guid": "m4", "text": "later", "isFromMe": false, "dateCreated": 1_800_000_000_000_i64}),
+        ];
+        for json in messages {
+            let mut msg = Message::from_server_map(&json).unwrap();
+            msg.chat_id = Some(chat_id);
+            if !msg.is_from_me {
+                msg.handle_id = Some(handle_id);
+            }
+            msg.save(&conn).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_build_transcript() {
+        let (db, _dir) = create_test_db();
+        seed_chat(&db);
+        let svc = ExportService::new(db, EventBus::new(16), PathBuf::from("/nonexistent"));
+
+        let options = ExportOptions::new(ExportFormat::Json, "/tmp/unused");
+        let t = svc.build_transcript("iMessage;-;+15551234567", &options).unwrap();
+        assert_eq!(t.messages.len(), 3);
+        assert_eq!(t.messages[0].sender, "+15551234567");
+        assert_eq!(t.messages[1].sender, "Me");
+        assert_eq!(t.messages[1].reply_to.as_ref().unwrap().guid, "m1");
+        assert_eq!(t.messages[1].reactions.len(), 1);
+        assert_eq!(t.messages[1].reactions[0].reaction, "loved");
+
+        let mut ranged = options.clone();
+        ranged.before = Some(parse_date_bound("2024-01-01").unwrap());
+        let t = svc.build_transcript("iMessage;-;+15551234567", &ranged).unwrap();
+        assert_eq!(t.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_redacted_export_writes_file() {
+        let (db, dir) = create_test_db();
+        seed_chat(&db);
+        let svc = ExportService::new(db, EventBus::new(16), dir.path().join("cache"));
+
+        let mut options = ExportOptions::new(ExportFormat::Markdown, dir.path().join("out"));
+        options.redact = true;
+        let summary = svc.export_chat("iMessage;-;+15551234567", &options).unwrap();
+        assert_eq!(summary.message_count, 3);
+
+        let written = std::fs::read_to_string(&summary.transcript_path).unwrap();
+        assert!(written.contains("Participant 1"));
+        assert!(written.contains("[phone]"));
+        assert!(!written.contains("555-123-4567"));
+        assert!(!written.contains("Alex"));
+    }
+
+    #[test]
+    fn test_redacted_json_has_no_addresses() {
+        let (db, dir) = create_test_db();
+        seed_chat(&db);
+        {
+            let conn = db.conn().unwrap();
+            conn.execute(
+                "UPDATE messages SET text = 'or mail alex@example.com' WHERE guid = 'm4'",
+                [],
+            )
+            .unwrap();
+        }
+        let svc = ExportService::new(db, EventBus::new(16), dir.path().join("cache"));
+
+        let mut options = ExportOptions::new(ExportFormat::Json, dir.path().join("out"));
+        options.redact = true;
+        let summary = svc.export_chat("iMessage;-;+15551234567", &options).unwrap();
+
+        let written = std::fs::read_to_string(&summary.transcript_path).unwrap();
+        for raw in ["5551234567", "555-123-4567", "alex@example.com", "Alex"] {
+            assert!(!written.contains(raw), "{raw} leaked into {written}");
+        }
+        let json: serde_json::Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(json["chat_guid"], "redacted");
+    }
+
+    #[test]
+    fn test_missing_chat() {
+        let (db, _dir) = create_test_db();
+        let svc = ExportService::new(db, EventBus::new(16), PathBuf::from("/nonexistent"));
+        let options = ExportOptions::new(ExportFormat::Text, "/tmp/unused");
+        assert!(matches!(
+            svc.build_transcript("nope", &options),
+            Err(BbError::ChatNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_parsing() {
+        assert_eq!("md".parse::<ExportFormat>().unwrap(), ExportFormat::Markdown);
+        assert_eq!("HTML".parse::<ExportFormat>().unwrap().extension(), "html");
+        assert!("pdf".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn test_mask_addresses() {
+        assert_eq!(mask_addresses("mail a@b.com now"), "mail [email] now");
+        assert_eq!(mask_addresses("ring +1 (555) 123-4567."), "ring [phone].");
+        assert_eq!(mask_addresses("meet at 10:30"), "meet at 10:30");
+        assert_eq!(mask_addresses("call 555-123-4567 or 5551234567"), "call [phone] or [phone]");
+        assert_eq!(mask_addresses("order 12345, room 101"), "order 12345, room 101");
+    }
+
+    #[test]
+    fn test_reaction_labels() {
+        assert_eq!(reaction_label("love"), Some((false, "loved")));
+        assert_eq!(reaction_label("-like"), Some((true, "liked")));
+        assert_eq!(reaction_label("3003"), Some((true, "laughed at")));
+        assert_eq!(reaction_label("sticker"), None);
+    }
+}
@@ -0,0 +1,342 @@
+//! Folder service: user-defined chat folders and rule-based smart folders.
+//!
+//! Manual folders hold chats added by hand (many-to-many); smart folders
+//! select chats with a `FolderRule` evaluated at query time, so they never
+//! go stale. Both report unread counts and can be exported for backups.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use tracing::{info, debug};
+
+use bb_core::error::{BbError, BbResult};
+use bb_models::{Chat, ChatFolder, Database, FolderRule};
+use bb_models::queries::{self, ChatWithDetails, FolderSummary};
+
+use crate::event_bus::{AppEvent, EventBus};
+use crate::service::{Service, ServiceState};
+
+/// A folder as stored in backups. Members are recorded by chat GUID so
+/// they survive a re-sync that renumbers chats.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FolderBackup {
+    pub name: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub rule: Option<FolderRule>,
+    #[serde(default)]
+    pub sort_index: i64,
+    #[serde(default)]
+    pub chat_guids: Vec<String>,
+}
+
+/// Service for chat folders.
+pub struct FolderService {
+    state: ServiceState,
+    database: Database,
+    event_bus: EventBus,
+}
+
+impl FolderService {
+    /// Create a new FolderService.
+    pub fn new(database: Database, event_bus: EventBus) -> Self {
+        Self {
+            state: ServiceState::Created,
+            database,
+            event_bus,
+        }
+    }
+
+    /// List folders with their chat and unread counts.
+    pub fn list_folders(&self) -> BbResult<Vec<FolderSummary>> {
+        let conn = self.database.conn()?;
+        queries::folder_summaries(&conn)
+    }
+
+    /// Find a folder by name, or by ID when `reference` is numeric and no
+    /// folder has that name.
+    pub fn find_folder(&self, reference: &str) -> BbResult<Option<ChatFolder>> {
+        let conn = self.database.conn()?;
+        if let Some(folder) = ChatFolder::find_by_name(&conn, reference)? {
+            return Ok(Some(folder));
+        }
+        match reference.trim().parse::<i64>() {
+            Ok(id) => ChatFolder::find_by_id(&conn, id),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Create a manual folder.
+    pub fn create_folder(&self, name: &str, color: Option<&str>) -> BbResult<ChatFolder> {
+        let mut folder = ChatFolder::new(name);
+        folder.color = color.map(String::from);
+        self.insert(folder)
+    }
+
+    /// Create a smart folder selecting chats by `rule`.
+    pub fn create_smart_folder(
+        &self,
+        name: &str,
+        rule: FolderRule,
+        color: Option<&str>,
+    ) -> BbResult<ChatFolder> {
+        let mut folder = ChatFolder::smart(name, rule);
+        folder.color = color.map(String::from);
+        self.insert(folder)
+    }
+
+    /// Rename a folder.
+    pub fn rename_folder(&self, id: i64, name: &str) -> BbResult<ChatFolder> {
+        self.update(id, |f| f.name = name.trim().to_string())
+    }
+
+    /// Set or clear a folder's color.
+    pub fn set_folder_color(&self, id: i64, color: Option<&str>) -> BbResult<ChatFolder> {
+        self.update(id, |f| f.color = color.map(String::from))
+    }
+
+    /// Replace the rule of a smart folder.
+    pub fn set_folder_rule(&self, id: i64, rule: FolderRule) -> BbResult<ChatFolder> {
+        let conn = self.database.conn()?;
+        if !find(&conn, id)?.is_smart() {
+            return Err(BbError::InvalidInput("only smart folders have rules".into()));
+        }
+        drop(conn);
+        self.update(id, |f| f.rule = Some(rule))
+    }
+
+    /// Set the display order of folders: each listed ID gets its position.
+    pub fn reorder_folders(&self, ids: &[i64]) -> BbResult<()> {
+        let conn = self.database.conn()?;
+        for (index, id) in ids.iter().enumerate() {
+            let mut folder = find(&conn, *id)?;
+            folder.sort_index = index as i64;
+            folder.save(&conn)?;
+        }
+        self.event_bus.emit(AppEvent::FoldersUpdated { folder_ids: ids.to_vec() });
+        Ok(())
+    }
+
+    /// Delete a folder. Its chats are not affected.
+    pub fn delete_folder(&self, id: i64) -> BbResult<bool> {
+        let conn = self.database.conn()?;
+        let deleted = ChatFolder::delete(&conn, id)?;
+        if deleted {
+            info!("deleted chat folder {id}");
+            self.event_bus.emit(AppEvent::FoldersUpdated { folder_ids: vec![id] });
+        }
+        Ok(deleted)
+    }
+
+    /// Add a chat to a manual folder.
+    pub fn add_chat(&self, folder_id: i64, chat_guid: &str) -> BbResult<()> {
+        let conn = self.database.conn()?;
+        let folder = find(&conn, folder_id)?;
+        folder.add_chat(&conn, chat_id(&conn, chat_guid)?)?;
+        debug!("added {chat_guid} to folder {}", folder.name);
+        self.event_bus.emit(AppEvent::FoldersUpdated { folder_ids: vec![folder_id] });
+        Ok(())
+    }
+
+    /// Remove a chat from a manual folder. Returns whether it was there.
+    pub fn remove_chat(&self, folder_id: i64, chat_guid: &str) -> BbResult<bool> {
+        let conn = self.database.conn()?;
+        let folder = find(&conn, folder_id)?;
+        let removed = folder.remove_chat(&conn, chat_id(&conn, chat_guid)?)?;
+        if removed {
+            self.event_bus.emit(AppEvent::FoldersUpdated { folder_ids: vec![folder_id] });
+        }
+        Ok(removed)
+    }
+
+    /// List the chats in a folder, pinned first then by latest message.
+    pub fn chats(
+        &self,
+        folder_id: i64,
+        offset: i64,
+        limit: i64,
+        include_archived: bool,
+    ) -> BbResult<Vec<ChatWithDetails>> {
+        let conn = self.database.conn()?;
+        let folder = find(&conn, folder_id)?;
+        queries::list_chats_with_details(&conn, offset, limit, include_archived, Some(&folder))
+    }
+
+    /// Folders containing a chat: manual folders it was added to and smart
+    /// folders whose rule matches it.
+    pub fn folders_for_chat(&self, chat_guid: &str) -> BbResult<Vec<ChatFolder>> {
+        let conn = self.database.conn()?;
+        let id = chat_id(&conn, chat_guid)?;
+        let mut folders = Vec::new();
+        for folder in ChatFolder::list(&conn)? {
+            let mut values = vec![id.into()];
+            let sql = format!(
+                "SELECT EXISTS (SELECT 1 FROM chats c WHERE c.id = ?1 AND ({}))",
+                folder.filter_sql(&mut values)
+            );
+            let matches: bool = conn
+                .query_row(&sql, rusqlite::params_from_iter(values), |row| row.get(0))
+                .map_err(|e| BbError::Database(e.to_string()))?;
+            if matches {
+                folders.push(folder);
+            }
+        }
+        Ok(folders)
+    }
+
+    /// Export every folder with its manual members, for backups.
+    pub fn export(&self) -> BbResult<Vec<FolderBackup>> {
+        let conn = self.database.conn()?;
+        ChatFolder::list(&conn)?
+            .into_iter()
+            .map(|f| {
+                Ok(FolderBackup {
+                    chat_guids: f.member_guids(&conn)?,
+                    name: f.name,
+                    color: f.color,
+                    rule: f.rule,
+                    sort_index: f.sort_index,
+                })
+            })
+            .collect()
+    }
+
+    /// Restore folders from a backup. Folders are matched by name and
+    /// updated in place; members whose chat is not in the local database
+    /// are skipped. Returns the number of folders restored.
+    pub fn import(&self, backups: &[FolderBackup]) -> BbResult<usize> {
+        let conn = self.database.conn()?;
+        let mut ids = Vec::new();
+        for backup in backups {
+            let mut folder = ChatFolder::find_by_name(&conn, &backup.name)?
+                .unwrap_or_else(|| ChatFolder::new(&backup.name));
+            folder.color = backup.color.clone();
+            folder.rule = backup.rule.clone();
+            folder.sort_index = backup.sort_index;
+            ids.push(folder.save(&conn)?);
+
+            if !folder.is_smart() {
+                for guid in &backup.chat_guids {
+                    if let Some(id) = Chat::find_by_guid(&conn, guid)?.and_then(|c| c.id) {
+                        folder.add_chat(&conn, id)?;
+                    }
+                }
+            }
+        }
+        if !ids.is_empty() {
+            info!("restored {} chat folders", ids.len());
+            self.event_bus.emit(AppEvent::FoldersUpdated { folder_ids: ids.clone() });
+        }
+        Ok(ids.len())
+    }
+
+    fn insert(&self, mut folder: ChatFolder) -> BbResult<ChatFolder> {
+        let conn = self.database.conn()?;
+        folder.sort_index = ChatFolder::list(&conn)?.len() as i64;
+        let id = folder.save(&conn)?;
+        info!("created chat folder {} ({id})", folder.name);
+        self.event_bus.emit(AppEvent::FoldersUpdated { folder_ids: vec![id] });
+        Ok(folder)
+    }
+
+    fn update(&self, id: i64, change: impl FnOnce(&mut ChatFolder)) -> BbResult<ChatFolder> {
+        let conn = self.database.conn()?;
+        let mut folder = find(&conn, id)?;
+        change(&mut folder);
+        folder.save(&conn)?;
+        self.event_bus.emit(AppEvent::FoldersUpdated { folder_ids: vec![id] });
+        Ok(folder)
+    }
+}
+
+fn find(conn: &Connection, id: i64) -> BbResult<ChatFolder> {
+    ChatFolder::find_by_id(conn, id)?
+        .ok_or_else(|| BbError::InvalidInput(format!("folder {id} not found")))
+}
+
+fn chat_id(conn: &Connection, guid: &str) -> BbResult<i64> {
+    Chat::find_by_guid(conn, guid)?
+        .and_then(|c| c.id)
+        .ok_or_else(|| BbError::ChatNotFound(guid.to_string()))
+}
+
+impl Service for FolderService {
+    fn name(&self) -> &str {
+        "folder"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("folder service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("folder service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (Database, FolderService) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        std::mem::forget(dir);
+        {
+            let conn = db.conn().unwrap();
+            conn.execute_batch(
+                "INSERT INTO handles (id, address, unique_address_service) VALUES
+                    (1, 'a@example.com', 'a/iMessage'), (2, 'b@example.com', 'b/iMessage');
+                 INSERT INTO chats (id, guid, display_name) VALUES
+                    (1, 'iMessage;-;a', NULL), (2, 'iMessage;+;team', 'Team');
+                 INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1), (2, 1), (2, 2);
+                 INSERT INTO messages (guid, chat_id, is_from_me) VALUES ('m1', 2, 0);",
+            )
+            .unwrap();
+        }
+        let svc = FolderService::new(db.clone(), EventBus::new(16));
+        (db, svc)
+    }
+
+    #[test]
+    fn test_folders_and_backup_round_trip() {
+        let (db, svc) = setup();
+        let work = svc.create_folder("Work", Some("#ff0000")).unwrap();
+        let work_id = work.id.unwrap();
+        svc.add_chat(work_id, "iMessage;-;a").unwrap();
+        assert!(svc.add_chat(work_id, "missing").is_err());
+        let groups = svc.create_smart_folder("Groups", FolderRule::Group, None).unwrap();
+        assert!(svc.add_chat(groups.id.unwrap(), "iMessage;-;a").is_err());
+
+        let summaries = svc.list_folders().unwrap();
+        assert_eq!(summaries.len(), 2);
+        let group_summary = summaries.iter().find(|s| s.folder.name == "Groups").unwrap();
+        assert_eq!((group_summary.chat_count, group_summary.unread_count), (1, 1));
+
+        let in_team: Vec<String> = svc
+            .folders_for_chat("iMessage;+;team")
+            .unwrap()
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        assert_eq!(in_team, vec!["Groups"]);
+        assert_eq!(svc.chats(work_id, 0, 10, false).unwrap()[0].chat.guid, "iMessage;-;a");
+        assert_eq!(svc.find_folder("work").unwrap().unwrap().id, Some(work_id));
+
+        let backup = svc.export().unwrap();
+        assert_eq!(backup[0].chat_guids, vec!["iMessage;-;a".to_string()]);
+
+        svc.delete_folder(work_id).unwrap();
+        svc.delete_folder(groups.id.unwrap()).unwrap();
+        assert_eq!(svc.import(&backup).unwrap(), 2);
+        let restored = svc.find_folder("Work").unwrap().unwrap();
+        assert_eq!(restored.color.as_deref(), Some("#ff0000"));
+        assert_eq!(svc.chats(restored.id.unwrap(), 0, 10, false).unwrap().len(), 1);
+        drop(db);
+    }
+}
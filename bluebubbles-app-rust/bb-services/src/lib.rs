@@ -8,6 +8,9 @@
 //! - Contact management (phone suffix matching, two-pass sync)
 //! - Attachment management (download queue, live photos, caching)
 //! - Notifications (grouped, filtered, FaceTime)
+//! - Notification actions (inline reply, mark as read, mute)
+//! - One-time code detection (custom patterns, sender allowlist, expiry)
+//! - Link previews for outgoing URLs (OpenGraph/Twitter/HTML, cached, size and time limits)
 //! - Settings persistence (typed accessors for all config sections)
 //! - Theme management (CRUD, presets, server backup)
 //! - Message queue and retry (exponential backoff, GUID tracking)
@@ -22,6 +25,16 @@
 //! - Cache management with LRU eviction
 //! - Scheduled message management
 //! - Handle/address management and availability checks
+//! - Conversation export (HTML, Markdown, JSON, plain text)
+//! - Offline import of Apple Messages chat.db files
+//! - Scheduled rotating local database snapshots
+//! - Data retention policies (global and per chat)
+//! - Messaging analytics (per-chat, per-contact and per-person statistics)
+//! - Person identity across handles (automatic linking, merge/split)
+//! - Trash for deleted chats and messages (restore, timed purge)
+//! - User-defined chat folders and rule-based smart folders
+//! - Offline outbox for chat changes (optimistic apply, ordered replay, rollback)
+//! - Offline smart reply suggestions (intent rules, bigram model of sent messages)
 
 pub mod service;
 pub mod registry;
@@ -34,6 +47,7 @@ pub mod message;
 pub mod contact;
 pub mod attachment;
 pub mod notification;
+pub mod notification_actions;
 pub mod settings;
 pub mod queue;
 pub mod theme;
@@ -45,6 +59,19 @@ pub mod search;
 pub mod cache;
 pub mod scheduled;
 pub mod handle;
+pub mod export;
+pub mod chatdb_import;
+pub mod snapshot;
+pub mod retention;
+pub mod stats;
+pub mod person;
+pub mod trash;
+pub mod folder;
+pub mod outbox;
+pub mod smart_reply;
+pub mod otp;
+pub mod link_preview;
+mod util;
 
 // Re-export key types
 pub use service::{Service, ServiceState};
@@ -60,3 +87,14 @@ pub use search::SearchService;
 pub use cache::CacheService;
 pub use scheduled::ScheduledMessageService;
 pub use handle::HandleService;
+pub use export::ExportService;
+pub use snapshot::SnapshotService;
+pub use retention::RetentionService;
+pub use stats::StatsService;
+pub use person::PersonService;
+pub use trash::TrashService;
+pub use folder::FolderService;
+pub use outbox::OutboxService;
+pub use smart_reply::SmartReplyService;
+pub use otp::OtpService;
+pub use link_preview::LinkPreviewService;
@@ -5,14 +5,17 @@
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use tracing::{info, warn, error, debug};
 
 use bb_core::config::ConfigHandle;
+use bb_core::constants::RETENTION_INTERVAL_SECS;
 use bb_core::error::BbResult;
 use bb_models::Database;
 use bb_api::ApiClient;
 
 use crate::event_bus::{AppEvent, EventBus};
+use crate::retention::RetentionService;
 use crate::service::{Service, ServiceState};
 
 /// Application lifecycle phase.
@@ -51,6 +54,7 @@ impl std::fmt::Display for LifecyclePhase {
 /// - Startup sequence: config validation, database init, server connectivity
 /// - Graceful shutdown: flush queues, close connections, save state
 /// - Foreground/background transitions: pause/resume sync, manage resources
+/// - Periodic maintenance: retention policy runs
 pub struct LifecycleService {
     state: ServiceState,
     config: ConfigHandle,
@@ -62,6 +66,10 @@ pub struct LifecycleService {
     setup_complete: Arc<AtomicBool>,
     /// Whether an incremental sync is currently running.
     sync_in_progress: Arc<AtomicBool>,
+    /// Retention engine run on a schedule once the service is initialized.
+    retention: Option<Arc<RetentionService>>,
+    /// Handle of the scheduled retention task.
+    retention_task: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl LifecycleService {
@@ -75,9 +83,17 @@ impl LifecycleService {
             phase: LifecyclePhase::NotStarted,
             setup_complete: Arc::new(AtomicBool::new(false)),
             sync_in_progress: Arc::new(AtomicBool::new(false)),
+            retention: None,
+            retention_task: None,
         }
     }
 
+    /// Run retention policies periodically while the service is running.
+    pub fn with_retention(mut self, retention: Arc<RetentionService>) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
     /// Get the current lifecycle phase.
     pub fn phase(&self) -> LifecyclePhase {
         self.phase
@@ -251,6 +267,30 @@ impl LifecycleService {
         Ok(())
     }
 
+    /// Spawn the periodic retention task.
+    ///
+    /// Runs are skipped while a sync is in progress so freshly synced rows
+    /// are not deleted mid-sync.
+    fn spawn_retention(&self, retention: Arc<RetentionService>) -> Option<tokio::task::JoinHandle<()>> {
+        let handle = tokio::runtime::Handle::try_current().ok()?;
+        let sync_in_progress = self.sync_in_progress.clone();
+        Some(handle.spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(RETENTION_INTERVAL_SECS)).await;
+                if sync_in_progress.load(Ordering::Relaxed) {
+                    debug!("sync in progress - skipping retention run");
+                    continue;
+                }
+                let retention = retention.clone();
+                match tokio::task::spawn_blocking(move || retention.run(false)).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => warn!("retention run failed: {e}"),
+                    Err(e) => error!("retention task panicked: {e}"),
+                }
+            }
+        }))
+    }
+
     /// Get a summary of the current application state for diagnostics.
     pub fn diagnostics(&self) -> LifecycleDiagnostics {
         LifecycleDiagnostics {
@@ -294,12 +334,18 @@ impl Service for LifecycleService {
     }
 
     fn init(&mut self) -> BbResult<()> {
+        if let Some(retention) = self.retention.clone() {
+            self.retention_task = self.spawn_retention(retention);
+        }
         self.state = ServiceState::Running;
         info!("lifecycle service initialized");
         Ok(())
     }
 
     fn shutdown(&mut self) -> BbResult<()> {
+        if let Some(task) = self.retention_task.take() {
+            task.abort();
+        }
         self.state = ServiceState::Stopped;
         self.phase = LifecyclePhase::Stopped;
         info!("lifecycle service stopped");
@@ -0,0 +1,563 @@
+//! Link previews for outgoing messages.
+//!
+//! iPhones only show a rich link card when the sender supplies its
+//! metadata, so `LinkPreviewService` fetches the first URL in an outgoing
+//! message and reads its OpenGraph, Twitter card and plain HTML tags
+//! (title, description, image, site name, favicon). Fetches are limited in
+//! size and time, and results are cached in the database, including pages
+//! that had no preview. Turning off the `generateLinkPreviews` setting
+//! stops all fetches, so linked sites never see a request from this device.
+//!
+//! A send waits at most `SEND_WAIT` for its preview. A page that takes
+//! longer goes out without one and keeps loading into the cache, so the
+//! next send of the same link has it.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use reqwest::Url;
+use tracing::{debug, info, warn};
+
+use bb_core::error::{BbError, BbResult};
+use bb_models::models::payload_data::UrlPreviewData;
+use bb_models::models::settings::keys;
+use bb_models::{CachedLinkPreview, Database, Settings};
+
+use crate::service::{Service, ServiceState};
+
+/// Most of a page that is read; metadata lives in the `<head>`.
+const MAX_PAGE_BYTES: usize = 512 * 1024;
+
+/// How long a page may take to fetch, redirects included.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a send waits for its link's preview.
+const SEND_WAIT: Duration = Duration::from_millis(1500);
+
+/// Redirects followed before giving up.
+const MAX_REDIRECTS: usize = 5;
+
+/// How long a preview stays cached.
+const CACHE_TTL_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// How long a page without a preview (or that failed to load) stays cached.
+const EMPTY_CACHE_TTL_MS: i64 = 60 * 60 * 1000;
+
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; BlueBubbles link preview)";
+
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\bhttps?://[^\s<>]+").unwrap());
+static META_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<meta\b[^>]*>").unwrap());
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<link\b[^>]*>").unwrap());
+static TITLE_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<title\b[^>]*>(.*?)</title>").unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([a-zA-Z_:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+static ENTITY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]+);").unwrap());
+
+/// Find the first web link in message text, without trailing punctuation.
+pub fn find_url(text: &str) -> Option<String> {
+    let found = URL.find(text)?.as_str();
+    let mut url = found.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+    // Keep a closing paren that belongs to the URL ("wiki/Rust_(language)")
+    while url.ends_with(')') && url.matches('(').count() < url.matches(')').count() {
+        url = url[..url.len() - 1].trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+    }
+    Url::parse(url).ok().map(|_| url.to_string())
+}
+
+/// Generates and caches link previews for outgoing messages. Clones share
+/// the HTTP client.
+#[derive(Clone)]
+pub struct LinkPreviewService {
+    state: ServiceState,
+    database: Database,
+    client: reqwest::Client,
+    max_bytes: usize,
+    send_wait: Duration,
+}
+
+impl LinkPreviewService {
+    /// Create a new LinkPreviewService with the default size and time limits.
+    pub fn new(database: Database) -> Self {
+        Self {
+            state: ServiceState::Created,
+            database,
+            client: build_client(FETCH_TIMEOUT),
+            max_bytes: MAX_PAGE_BYTES,
+            send_wait: SEND_WAIT,
+        }
+    }
+
+    /// Use other limits on how much of a page is read and how long a fetch
+    /// may take.
+    pub fn with_limits(mut self, max_bytes: usize, timeout: Duration) -> Self {
+        self.client = build_client(timeout);
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Use another limit on how long a send waits for its preview.
+    pub fn with_send_wait(mut self, wait: Duration) -> Self {
+        self.send_wait = wait;
+        self
+    }
+
+    /// Whether link previews are generated (the default).
+    pub fn is_enabled(&self) -> BbResult<bool> {
+        let conn = self.database.conn()?;
+        Ok(Settings::get_bool(&conn, keys::GENERATE_LINK_PREVIEWS)?.unwrap_or(true))
+    }
+
+    /// Preview for a URL, from the cache or fetched. None when previews are
+    /// off, or the page has no title, description or image.
+    pub async fn preview(&self, url: &str) -> BbResult<Option<UrlPreviewData>> {
+        if !self.is_enabled()? {
+            return Ok(None);
+        }
+        let parsed = Url::parse(url).map_err(|e| BbError::InvalidInput(format!("invalid url {url:?}: {e}")))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(BbError::InvalidInput(format!("not a web link: {url}")));
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        {
+            let conn = self.database.conn()?;
+            if let Some(cached) = CachedLinkPreview::find(&conn, url)? {
+                let ttl = if cached.preview.is_some() { CACHE_TTL_MS } else { EMPTY_CACHE_TTL_MS };
+                if now - cached.fetched_at < ttl {
+                    debug!("link preview cache hit for {url}");
+                    return Ok(cached.preview);
+                }
+            }
+        }
+
+        let preview = match self.fetch(&parsed).await {
+            Ok(preview) => preview,
+            Err(e) => {
+                debug!("link preview fetch failed for {url}: {e}");
+                None
+            }
+        };
+        let conn = self.database.conn()?;
+        CachedLinkPreview { url: url.to_string(), preview: preview.clone(), fetched_at: now }.save(&conn)?;
+        Ok(preview)
+    }
+
+    /// Server payload for the first link in an outgoing message, if it has
+    /// a preview ready within the send wait. Failures are logged, never
+    /// returned: a send must not fail over its preview.
+    pub async fn payload_for_text(&self, text: &str) -> Option<serde_json::Value> {
+        let url = find_url(text)?;
+        // The lookup runs on its own task so a slow page keeps loading
+        // into the cache after the send stops waiting for it
+        let lookup = {
+            let service = self.clone();
+            let url = url.clone();
+            tokio::spawn(async move { service.preview(&url).await })
+        };
+        let preview = match tokio::time::timeout(self.send_wait, lookup).await {
+            Ok(joined) => joined.map_err(|e| BbError::Internal(e.to_string())).and_then(|r| r),
+            Err(_) => {
+                debug!("link preview for {url} not ready, sending without it");
+                return None;
+            }
+        };
+        match preview {
+            Ok(preview) => preview.map(|p| p.to_server_json()),
+            Err(e) => {
+                warn!("link preview unavailable for {url}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Drop cached previews past their lifetime. Returns how many were
+    /// dropped.
+    pub fn prune_cache(&self) -> BbResult<usize> {
+        let conn = self.database.conn()?;
+        CachedLinkPreview::delete_older_than(&conn, chrono::Utc::now().timestamp_millis() - CACHE_TTL_MS)
+    }
+
+    /// Fetch a page (up to `max_bytes` of it) and parse its preview.
+    async fn fetch(&self, url: &Url) -> BbResult<Option<UrlPreviewData>> {
+        let mut response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| fetch_error(url, e))?;
+        if !response.status().is_success() {
+            debug!("link preview for {url}: server returned {}", response.status());
+            return Ok(None);
+        }
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_none_or(|ct| ct.contains("html"));
+        if !is_html {
+            return Ok(None);
+        }
+
+        let final_url = response.url().clone();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| fetch_error(url, e))? {
+            let room = self.max_bytes - body.len();
+            body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            if body.len() >= self.max_bytes {
+                debug!("link preview for {url}: read limit reached");
+                break;
+            }
+        }
+        Ok(parse_html(&String::from_utf8_lossy(&body), url, &final_url))
+    }
+}
+
+fn build_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .user_agent(USER_AGENT)
+        .build()
+        .unwrap_or_default()
+}
+
+fn fetch_error(url: &Url, e: reqwest::Error) -> BbError {
+    if e.is_timeout() {
+        BbError::Timeout(format!("fetching {url}"))
+    } else {
+        BbError::Http(e.to_string())
+    }
+}
+
+/// Read the preview from a page. `url` is the link as sent, `final_url`
+/// where it led after redirects.
+fn parse_html(html: &str, url: &Url, final_url: &Url) -> Option<UrlPreviewData> {
+    // First value wins, keyed by `property` or `name`
+    let mut meta: HashMap<String, String> = HashMap::new();
+    for tag in META_TAG.find_iter(html) {
+        let attrs = attributes(tag.as_str());
+        let Some(key) = attrs.get("property").or_else(|| attrs.get("name")) else {
+            continue;
+        };
+        if let Some(content) = attrs.get("content").map(|c| clean_text(c)).filter(|c| !c.is_empty()) {
+            meta.entry(key.to_ascii_lowercase()).or_insert(content);
+        }
+    }
+    let pick = |names: &[&str]| names.iter().find_map(|n| meta.get(*n).cloned());
+
+    let title = pick(&["og:title", "twitter:title"]).or_else(|| {
+        TITLE_TAG
+            .captures(html)
+            .map(|c| clean_text(&c[1]))
+            .filter(|t| !t.is_empty())
+    });
+    let summary = pick(&["og:description", "twitter:description", "description"]);
+    let image = pick(&["og:image", "og:image:url", "og:image:secure_url", "twitter:image", "twitter:image:src"])
+        .and_then(|src| final_url.join(&src).ok())
+        .map(String::from);
+    if title.is_none() && summary.is_none() && image.is_none() {
+        return None;
+    }
+
+    let site_name = pick(&["og:site_name", "application-name"])
+        .or_else(|| final_url.host_str().map(|h| h.trim_start_matches("www.").to_string()));
+    let canonical = pick(&["og:url"])
+        .and_then(|u| final_url.join(&u).ok())
+        .unwrap_or_else(|| final_url.clone());
+
+    Some(UrlPreviewData {
+        url: Some(url.to_string()),
+        original_url: (canonical != *url).then(|| canonical.to_string()),
+        title,
+        summary,
+        site_name,
+        icon: favicon(html, final_url),
+        image,
+    })
+}
+
+/// The page's icon: a `rel="icon"` link, then an Apple touch icon, then
+/// `/favicon.ico` at the site root.
+fn favicon(html: &str, base: &Url) -> Option<String> {
+    let links: Vec<(String, String)> = LINK_TAG
+        .find_iter(html)
+        .filter_map(|tag| {
+            let attrs = attributes(tag.as_str());
+            Some((attrs.get("rel")?.to_ascii_lowercase(), attrs.get("href")?.clone()))
+        })
+        .collect();
+    let href = ["icon", "apple-touch-icon"].iter().find_map(|wanted| {
+        links
+            .iter()
+            .find(|(rel, _)| rel.split_whitespace().any(|r| r == *wanted))
+            .map(|(_, href)| decode_entities(href.trim()))
+    });
+    base.join(href.as_deref().unwrap_or("/favicon.ico")).ok().map(String::from)
+}
+
+/// Attributes of an HTML tag, with lowercased names.
+fn attributes(tag: &str) -> HashMap<String, String> {
+    ATTRIBUTE
+        .captures_iter(tag)
+        .map(|c| {
+            let value = c.get(2).or_else(|| c.get(3)).or_else(|| c.get(4)).map_or("", |m| m.as_str());
+            (c[1].to_ascii_lowercase(), value.to_string())
+        })
+        .collect()
+}
+
+/// Decode entities and collapse whitespace.
+fn clean_text(text: &str) -> String {
+    decode_entities(text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    ENTITY
+        .replace_all(text, |c: &regex::Captures| {
+            let entity = &c[1];
+            let decoded = if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => None,
+                }
+            };
+            decoded.map_or_else(|| c[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+impl Service for LinkPreviewService {
+    fn name(&self) -> &str {
+        "link_preview"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        let pruned = self.prune_cache()?;
+        self.state = ServiceState::Running;
+        info!("link preview service initialized ({pruned} expired previews dropped)");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("link preview service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const ARTICLE: &str = r#"<!doctype html><html><head>
+        <title>Ignored &amp; replaced</title>
+        <meta property="og:title" content="Rust &amp; You">
+        <meta property="og:description" content="A  short
+            introduction">
+        <meta name="twitter:image" content="/img/card.png">
+        <meta property="og:site_name" content='The Blog'>
+        <link rel="apple-touch-icon" href="/touch.png">
+        <link rel="shortcut icon" href="/static/fav.png">
+        </head><body>Hello</body></html>"#;
+
+    fn setup() -> LinkPreviewService {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        std::mem::forget(dir);
+        LinkPreviewService::new(db)
+    }
+
+    /// A local HTTP stand-in serving fixed pages. Returns its base URL and
+    /// a count of requests served.
+    async fn serve(pages: Vec<(&'static str, String)>) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { break };
+                counter.fetch_add(1, Ordering::SeqCst);
+                let pages = pages.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let response = match pages.iter().find(|(p, _)| *p == path) {
+                        Some((_, body)) if path == "/slow" => {
+                            tokio::time::sleep(Duration::from_secs(2)).await;
+                            ok_response(body)
+                        }
+                        Some((_, body)) if path == "/old" => {
+                            format!("HTTP/1.1 301 Moved Permanently\r\nLocation: {body}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                        }
+                        Some((_, body)) => ok_response(body),
+                        None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+                    };
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (base, hits)
+    }
+
+    fn ok_response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[test]
+    fn test_find_url() {
+        assert_eq!(find_url("see https://example.com/a?b=1.").as_deref(), Some("https://example.com/a?b=1"));
+        assert_eq!(find_url("(at http://example.com/x)").as_deref(), Some("http://example.com/x"));
+        assert_eq!(
+            find_url("https://en.wikipedia.org/wiki/Rust_(language)!").as_deref(),
+            Some("https://en.wikipedia.org/wiki/Rust_(language)")
+        );
+        assert_eq!(find_url("HTTPS://EXAMPLE.COM").as_deref(), Some("HTTPS://EXAMPLE.COM"));
+        assert!(find_url("no links, just example.com and ftp://files").is_none());
+    }
+
+    #[test]
+    fn test_parse_open_graph() {
+        let url = Url::parse("https://blog.example.com/post/1").unwrap();
+        let preview = parse_html(ARTICLE, &url, &url).unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Rust & You"));
+        assert_eq!(preview.summary.as_deref(), Some("A short introduction"));
+        assert_eq!(preview.site_name.as_deref(), Some("The Blog"));
+        assert_eq!(preview.image.as_deref(), Some("https://blog.example.com/img/card.png"));
+        assert_eq!(preview.icon.as_deref(), Some("https://blog.example.com/static/fav.png"));
+        assert_eq!(preview.url.as_deref(), Some("https://blog.example.com/post/1"));
+        assert!(preview.original_url.is_none());
+    }
+
+    #[test]
+    fn test_parse_fallbacks() {
+        let url = Url::parse("http://exam.pl/p").unwrap();
+        let final_url = Url::parse("https://www.example.com/page").unwrap();
+        let html = "<html><head><TITLE>\n  Plain &#8212; page </TITLE>\
+                    <meta name=description content=Plain></head></html>";
+        let preview = parse_html(html, &url, &final_url).unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Plain \u{2014} page"));
+        assert_eq!(preview.summary.as_deref(), Some("Plain"));
+        assert_eq!(preview.site_name.as_deref(), Some("example.com"));
+        assert_eq!(preview.icon.as_deref(), Some("https://www.example.com/favicon.ico"));
+        assert_eq!(preview.original_url.as_deref(), Some("https://www.example.com/page"));
+        assert!(preview.image.is_none());
+
+        // Nothing to show
+        assert!(parse_html("<html><body><p>hi</p></body></html>", &url, &url).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_cache() {
+        let (base, hits) = serve(vec![
+            ("/post", ARTICLE.to_string()),
+            ("/old", "/post".to_string()),
+            ("/bare", "<p>no metadata</p>".to_string()),
+        ])
+        .await;
+        let service = setup();
+
+        let payload = service.payload_for_text(&format!("read this: {base}/post!")).await.unwrap();
+        assert_eq!(payload["title"], "Rust & You");
+        assert_eq!(payload["url"], format!("{base}/post"));
+        assert_eq!(payload["icon"], format!("{base}/static/fav.png"));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Cached: no second request
+        let again = service.preview(&format!("{base}/post")).await.unwrap().unwrap();
+        assert_eq!(again.title.as_deref(), Some("Rust & You"));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Redirects are followed and recorded
+        let moved = service.preview(&format!("{base}/old")).await.unwrap().unwrap();
+        assert_eq!(moved.url, Some(format!("{base}/old")));
+        assert_eq!(moved.original_url, Some(format!("{base}/post")));
+
+        // Pages without a preview and missing pages are cached as empty
+        assert!(service.preview(&format!("{base}/bare")).await.unwrap().is_none());
+        assert!(service.preview(&format!("{base}/missing")).await.unwrap().is_none());
+        let served = hits.load(Ordering::SeqCst);
+        assert!(service.preview(&format!("{base}/missing")).await.unwrap().is_none());
+        assert_eq!(hits.load(Ordering::SeqCst), served);
+        assert!(service.payload_for_text("nothing to see").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_size_and_time_limits() {
+        let padding = "x".repeat(4096);
+        let (base, _) = serve(vec![
+            ("/late", format!("<html><body>{padding}<title>Too far</title></body></html>")),
+            ("/early", format!("<html><head><title>In time</title></head><body>{padding}</body></html>")),
+            ("/slow", ARTICLE.to_string()),
+        ])
+        .await;
+        let service = setup().with_limits(1024, Duration::from_millis(300));
+
+        assert!(service.preview(&format!("{base}/late")).await.unwrap().is_none());
+        let early = service.preview(&format!("{base}/early")).await.unwrap().unwrap();
+        assert_eq!(early.title.as_deref(), Some("In time"));
+
+        let started = std::time::Instant::now();
+        assert!(service.preview(&format!("{base}/slow")).await.unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_send_wait() {
+        let (base, hits) = serve(vec![("/slow", ARTICLE.to_string())]).await;
+        let service = setup().with_send_wait(Duration::from_millis(200));
+        let url = format!("{base}/slow");
+
+        let started = std::time::Instant::now();
+        assert!(service.payload_for_text(&url).await.is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // The page finishes loading into the cache for the next send
+        let mut cached = None;
+        for _ in 0..50 {
+            cached = CachedLinkPreview::find(&service.database.conn().unwrap(), &url).unwrap();
+            if cached.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(cached.and_then(|c| c.preview).is_some());
+        assert_eq!(service.payload_for_text(&url).await.unwrap()["title"], "Rust & You");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_for_privacy() {
+        let (base, hits) = serve(vec![("/post", ARTICLE.to_string())]).await;
+        let service = setup();
+        {
+            let conn = service.database.conn().unwrap();
+            Settings::set_bool(&conn, keys::GENERATE_LINK_PREVIEWS, false).unwrap();
+        }
+
+        assert!(service.payload_for_text(&format!("{base}/post")).await.is_none());
+        assert!(service.preview(&format!("{base}/post")).await.unwrap().is_none());
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+}
@@ -1,21 +1,73 @@
 //! Message service for sending, receiving, and managing messages.
 //!
 //! Handles text/attachment/reaction sending with temp GUID management,
-//! message editing, unsending, retry via the outgoing queue, and
-//! incoming message processing.
+//! message editing, unsending, retry via the outgoing queue, sends
+//! composed offline that wait for the connection, and incoming message
+//! processing. Also exposes edit history, the
+//! tombstones recorded for unsent parts, and bookmarks with tags and notes.
+//! With a `LinkPreviewService` attached, text sends carry a preview of
+//! their first link.
 
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use serde::{Deserialize, Serialize};
 use tracing::{info, debug, warn};
 use bb_core::error::{BbError, BbResult, MessageError};
-use bb_models::{Database, Message};
+use bb_models::{Bookmark, BookmarkFilter, Chat, Database, Message, MessageTombstone};
+use bb_models::models::message_summary_info::EditedContent;
 use bb_models::queries;
 use bb_api::ApiClient;
 use bb_api::endpoints::messages::{SendTextParams, SendReactionParams, EditMessageParams};
 
 use crate::event_bus::{AppEvent, EventBus};
-use crate::queue::{QueueService, QueuedMessage};
+use crate::link_preview::LinkPreviewService;
+use crate::outbox::{is_transient, SubmitStatus};
+use crate::queue::{OutgoingStatus, QueueService, QueuedMessage};
 use crate::service::{Service, ServiceState};
 
+/// How often the retry loop checks for messages whose backoff has elapsed.
+const RETRY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How often to ping the server while messages wait for the connection.
+const PING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Edit history for a single message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditHistory {
+    pub message_guid: String,
+    pub current_text: Option<String>,
+    pub date_edited: Option<String>,
+    /// Prior versions of each edited part.
+    pub parts: Vec<PartEditHistory>,
+    /// Parts that were unsent, with the text known at the time.
+    pub unsent: Vec<MessageTombstone>,
+}
+
+impl EditHistory {
+    /// Whether the message has any earlier versions.
+    pub fn is_edited(&self) -> bool {
+        self.date_edited.is_some() || self.parts.iter().any(|p| !p.previous_versions.is_empty())
+    }
+}
+
+/// Earlier versions of one message part, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartEditHistory {
+    pub part_index: i32,
+    pub previous_versions: Vec<EditedContent>,
+}
+
+/// A bookmark with the message it annotates.
+#[derive(Debug, Clone, Serialize)]
+pub struct BookmarkedMessage {
+    pub bookmark: Bookmark,
+    pub message: Message,
+    pub chat_guid: String,
+    pub chat_title: String,
+}
+
 /// Service for managing messages.
 ///
 /// Handles message sending (text, attachment, reaction), receiving,
@@ -26,6 +78,7 @@ pub struct MessageService {
     state: ServiceState,
     database: Database,
     event_bus: EventBus,
+    link_previews: Option<Arc<LinkPreviewService>>,
 }
 
 impl MessageService {
@@ -35,9 +88,16 @@ impl MessageService {
             state: ServiceState::Created,
             database,
             event_bus,
+            link_previews: None,
         }
     }
 
+    /// Attach link previews to outgoing text messages.
+    pub fn with_link_previews(mut self, previews: Arc<LinkPreviewService>) -> Self {
+        self.link_previews = Some(previews);
+        self
+    }
+
     /// List messages for a chat from the local database.
     pub fn list_messages(
         &self,
@@ -78,6 +138,29 @@ impl MessageService {
         reply_guid: Option<String>,
     ) -> BbResult<Message> {
         let temp_guid = format!("temp-{}", uuid::Uuid::new_v4());
+        self.send_text_as(api, &temp_guid, chat_guid, text, method, effect_id, subject, reply_guid)
+            .await
+    }
+
+    /// Send a text message under a given temp GUID. Retries reuse the
+    /// GUID of the first attempt, replacing its errored placeholder.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_text_as(
+        &self,
+        api: &ApiClient,
+        temp_guid: &str,
+        chat_guid: &str,
+        text: &str,
+        method: &str,
+        effect_id: Option<String>,
+        subject: Option<String>,
+        reply_guid: Option<String>,
+    ) -> BbResult<Message> {
+        let temp_guid = temp_guid.to_string();
+        self.drop_error_placeholder(&temp_guid)?;
+
+        // Create temp message in local DB for optimistic UI
+        self.save_temp_text(&temp_guid, chat_guid, text)?;
 
         let params = SendTextParams {
             chat_guid: chat_guid.to_string(),
@@ -89,81 +172,254 @@ impl MessageService {
             selected_message_guid: reply_guid,
             part_index: None,
             dd_scan: None,
+            payload_data: self.link_preview(text).await,
         };
 
-        // Create temp message in local DB for optimistic UI
-        let mut temp_msg = Message::from_server_map(&serde_json::json!({
-            "guid": temp_guid,
-            "text": text,
-            "isFromMe": true,
-            "dateCreated": chrono::Utc::now().to_rfc3339(),
-        }))?;
+        debug!("sending text message (temp_guid: {temp_guid})");
 
-        {
-            let conn = self.database.conn()?;
-            if let Some(chat) = queries::find_chat_by_guid(&conn, chat_guid)? {
-                temp_msg.chat_id = chat.id;
+        // Send via API
+        match api.send_text(&params).await {
+            Ok(msg_json) => self.finish_sent(&temp_guid, chat_guid, text, &msg_json),
+            Err(e) => {
+                self.mark_errored(&temp_guid, chat_guid, &e)?;
+                warn!("failed to send message: {e}");
+                Err(e)
             }
-            temp_msg.save(&conn)?;
         }
+    }
 
-        debug!("sending text message (temp_guid: {temp_guid})");
+    /// Send a text message, or keep it for later if the server can't be
+    /// reached.
+    ///
+    /// With no client, or when the send fails for connection reasons, the
+    /// temp message stays in the local database and is queued as waiting
+    /// for the connection; `flush_waiting` sends it later, without the
+    /// effect. A chat that already has messages waiting queues the new one
+    /// behind them rather than letting it overtake them. Returns the sent
+    /// message, or the temp message when queued.
+    pub async fn send_text_or_queue(
+        &self,
+        api: Option<&ApiClient>,
+        queue: &QueueService,
+        chat_guid: &str,
+        text: &str,
+        method: &str,
+        effect_id: Option<String>,
+    ) -> BbResult<(Message, SubmitStatus)> {
+        let Some(api) = api else {
+            return Ok((self.queue_text(queue, chat_guid, text).await?, SubmitStatus::Queued));
+        };
+        queue.reload().await?;
+        if queue.waiting().await.iter().any(|m| m.chat_guid == chat_guid) {
+            debug!("chat {chat_guid} has messages waiting, queueing behind them");
+            return Ok((self.queue_text(queue, chat_guid, text).await?, SubmitStatus::Queued));
+        }
 
-        // Send via API
+        let mut queued = QueuedMessage::text(chat_guid, text);
+        let temp_guid = queued.temp_guid.clone().unwrap_or_default();
+        let temp_msg = self.save_temp_text(&temp_guid, chat_guid, text)?;
+        let mut params = text_params(&temp_guid, chat_guid, text, method);
+        params.effect_id = effect_id;
+        params.payload_data = self.link_preview(text).await;
+        queued.record_attempt();
         match api.send_text(&params).await {
-            Ok(msg_json) => {
-                let mut msg = Message::from_server_map(&msg_json)?;
-                let conn = self.database.conn()?;
+            Ok(msg_json) => Ok((self.finish_sent(&temp_guid, chat_guid, text, &msg_json)?, SubmitStatus::Sent)),
+            Err(e) if is_transient(&e) => {
+                info!("server unreachable, holding message until reconnect: {e}");
+                self.hold(queue, queued.waiting()).await;
+                Ok((temp_msg, SubmitStatus::Queued))
+            }
+            Err(e) => {
+                self.mark_errored(&temp_guid, chat_guid, &e)?;
+                Err(e)
+            }
+        }
+    }
 
-                // Replace the temp GUID with the real GUID
-                if let Some(real_guid) = msg.guid.as_deref() {
-                    conn.execute(
-                        "DELETE FROM messages WHERE guid = ?1",
-                        rusqlite::params![temp_guid],
-                    )
-                    .map_err(|e| BbError::Database(e.to_string()))?;
+    /// Save a text message to send once the connection returns, without
+    /// trying the server now. Returns the temp message.
+    pub async fn queue_text(&self, queue: &QueueService, chat_guid: &str, text: &str) -> BbResult<Message> {
+        let queued = QueuedMessage::text(chat_guid, text).waiting();
+        let temp_guid = queued.temp_guid.clone().unwrap_or_default();
+        let temp_msg = self.save_temp_text(&temp_guid, chat_guid, text)?;
+        self.hold(queue, queued).await;
+        Ok(temp_msg)
+    }
 
-                    // Resolve chat_id for the real message
-                    if let Some(chat) = queries::find_chat_by_guid(&conn, chat_guid)? {
-                        msg.chat_id = chat.id;
-                    }
+    /// Send messages waiting for the connection.
+    ///
+    /// The queue is re-read first, so messages other processes queued are
+    /// included, and each message is claimed before it is sent. Each chat's
+    /// messages go out oldest first; when one still can't reach the server,
+    /// or another process is sending it, the rest of that chat waits behind
+    /// it so the order is kept. Messages the server rejects, and ones with
+    /// nothing left to send, are marked failed and dropped. Returns how
+    /// many were sent.
+    pub async fn flush_waiting(&self, api: &ApiClient, queue: &QueueService) -> BbResult<usize> {
+        queue.reload().await?;
+        // A send whose response was lost may already be on the server
+        self.reconcile_queue(api, queue).await?;
 
-                    self.event_bus.emit(AppEvent::MessageSent {
-                        temp_guid: temp_guid.clone(),
-                        real_guid: real_guid.to_string(),
-                        chat_guid: chat_guid.to_string(),
-                    });
+        let mut blocked: Vec<String> = Vec::new();
+        let mut sent = 0;
+        for msg in queue.waiting().await {
+            if blocked.contains(&msg.chat_guid) {
+                continue;
+            }
+            if !queue.claim(&msg.id).await? {
+                debug!("queued message {} is being sent elsewhere", msg.id);
+                blocked.push(msg.chat_guid.clone());
+                continue;
+            }
+            let temp_guid = msg.temp_guid.clone().unwrap_or_default();
+
+            let mut persisted = msg.clone();
+            persisted.record_attempt();
+            queue.enqueue(persisted).await;
+            self.emit_status(&temp_guid, &msg.chat_guid, OutgoingStatus::Sending);
+            match self.send_queued(api, &msg, &temp_guid).await {
+                Ok(delivered) => {
+                    queue.remove(&msg.id).await;
+                    if let Some(real_guid) = delivered.guid.as_deref() {
+                        queue.mark_sent(&temp_guid, real_guid).await;
+                    }
+                    self.emit_status(&temp_guid, &msg.chat_guid, OutgoingStatus::Sent);
+                    sent += 1;
+                }
+                Err(e) if is_transient(&e) => {
+                    debug!("still offline, chat {} keeps waiting: {e}", msg.chat_guid);
+                    queue.release(&msg.id).await?;
+                    self.emit_status(&temp_guid, &msg.chat_guid, OutgoingStatus::WaitingForConnection);
+                    blocked.push(msg.chat_guid.clone());
                 }
+                Err(e) => {
+                    warn!("server rejected queued message {}: {e}", msg.id);
+                    self.mark_errored(&temp_guid, &msg.chat_guid, &e)?;
+                    queue.remove(&msg.id).await;
+                    queue.mark_failed(&temp_guid, &e.to_string(), classify_send_error(&e)).await;
+                    self.emit_status(&temp_guid, &msg.chat_guid, OutgoingStatus::Failed);
+                }
+            }
+        }
+        if sent > 0 {
+            info!("flushed {sent} messages composed offline");
+        }
+        Ok(sent)
+    }
 
-                msg.save(&conn)?;
-                info!("message sent: {:?}", msg.guid);
-                Ok(msg)
+    /// Send one queued message, text or attachment, under its temp GUID.
+    async fn send_queued(&self, api: &ApiClient, msg: &QueuedMessage, temp_guid: &str) -> BbResult<Message> {
+        match (&msg.text, &msg.file_path) {
+            (Some(text), _) => {
+                let mut params = text_params(temp_guid, &msg.chat_guid, text, "private-api");
+                params.payload_data = self.link_preview(text).await;
+                let msg_json = api.send_text(&params).await?;
+                self.finish_sent(temp_guid, &msg.chat_guid, text, &msg_json)
             }
-            Err(e) => {
-                // Mark temp message as errored
-                let conn = self.database.conn()?;
-                let error_guid = format!("error-{temp_guid}");
-                conn.execute(
-                    "UPDATE messages SET guid = ?1 WHERE guid = ?2",
-                    rusqlite::params![error_guid, temp_guid],
-                )
-                .map_err(|e2| BbError::Database(e2.to_string()))?;
+            (None, Some(file_path)) => {
+                let path = Path::new(file_path);
+                let bytes = std::fs::read(path)
+                    .map_err(|e| BbError::SendFailed(format!("queued attachment {file_path} is gone: {e}")))?;
+                let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+                let mime_type = msg.mime_type.as_deref().unwrap_or("application/octet-stream");
+                self.upload_attachment(api, temp_guid, &msg.chat_guid, path, file_name, bytes, mime_type, "private-api")
+                    .await
+            }
+            (None, None) => Err(BbError::SendFailed("queued message has nothing to send".into())),
+        }
+    }
 
-                self.event_bus.emit(AppEvent::MessageFailed {
-                    temp_guid,
-                    chat_guid: chat_guid.to_string(),
-                    error: e.to_string(),
-                });
+    /// Queue a message as waiting and announce it.
+    async fn hold(&self, queue: &QueueService, queued: QueuedMessage) {
+        let temp_guid = queued.temp_guid.clone().unwrap_or_default();
+        let chat_guid = queued.chat_guid.clone();
+        queue.enqueue(queued).await;
+        self.emit_status(&temp_guid, &chat_guid, OutgoingStatus::WaitingForConnection);
+    }
 
-                warn!("failed to send message: {e}");
-                Err(e)
+    /// Preview payload for the first link in an outgoing text, if any.
+    async fn link_preview(&self, text: &str) -> Option<serde_json::Value> {
+        self.link_previews.as_ref()?.payload_for_text(text).await
+    }
+
+    fn emit_status(&self, temp_guid: &str, chat_guid: &str, status: OutgoingStatus) {
+        self.event_bus.emit(AppEvent::MessageStatusChanged {
+            temp_guid: temp_guid.to_string(),
+            chat_guid: chat_guid.to_string(),
+            status,
+        });
+    }
+
+    /// Write the optimistic temp message for an outgoing text.
+    fn save_temp_text(&self, temp_guid: &str, chat_guid: &str, text: &str) -> BbResult<Message> {
+        let mut temp_msg = Message::from_server_map(&serde_json::json!({
+            "guid": temp_guid,
+            "text": text,
+            "isFromMe": true,
+            "dateCreated": chrono::Utc::now().to_rfc3339(),
+        }))?;
+
+        let conn = self.database.conn()?;
+        if let Some(chat) = queries::find_chat_by_guid(&conn, chat_guid)? {
+            temp_msg.chat_id = chat.id;
+        }
+        temp_msg.save(&conn)?;
+        Ok(temp_msg)
+    }
+
+    /// Replace the temp message with the server's copy and announce it.
+    fn finish_sent(&self, temp_guid: &str, chat_guid: &str, text: &str, msg_json: &serde_json::Value) -> BbResult<Message> {
+        let mut msg = Message::from_server_map(msg_json)?;
+        let conn = self.database.conn()?;
+
+        // Replace the temp GUID with the real GUID
+        if let Some(real_guid) = msg.guid.as_deref() {
+            conn.execute(
+                "DELETE FROM messages WHERE guid = ?1",
+                rusqlite::params![temp_guid],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+
+            // Resolve chat_id for the real message
+            if let Some(chat) = queries::find_chat_by_guid(&conn, chat_guid)? {
+                msg.chat_id = chat.id;
             }
+
+            self.event_bus.emit(AppEvent::MessageSent {
+                temp_guid: temp_guid.to_string(),
+                real_guid: real_guid.to_string(),
+                chat_guid: chat_guid.to_string(),
+            });
         }
+
+        msg.save(&conn)?;
+        self.clear_sent_draft(&conn, chat_guid, SentPart::Text(text));
+        info!("message sent: {:?}", msg.guid);
+        Ok(msg)
+    }
+
+    /// Rename the temp message to its errored GUID and announce the failure.
+    fn mark_errored(&self, temp_guid: &str, chat_guid: &str, error: &BbError) -> BbResult<()> {
+        let conn = self.database.conn()?;
+        let error_guid = format!("error-{temp_guid}");
+        conn.execute(
+            "UPDATE messages SET guid = ?1 WHERE guid = ?2",
+            rusqlite::params![error_guid, temp_guid],
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+
+        self.event_bus.emit(AppEvent::MessageFailed {
+            temp_guid: temp_guid.to_string(),
+            chat_guid: chat_guid.to_string(),
+            error: error.to_string(),
+        });
+        Ok(())
     }
 
     /// Send a text message with automatic retry via the queue service.
     ///
-    /// On failure, the message is enqueued for retry with exponential backoff.
+    /// On failure, the message is enqueued for retry with exponential backoff,
+    /// keeping its temp GUID so the retry can be matched to the first attempt.
     pub async fn send_text_with_retry(
         &self,
         api: &ApiClient,
@@ -172,23 +428,18 @@ impl MessageService {
         text: &str,
         method: &str,
     ) -> BbResult<Message> {
+        let mut queued = QueuedMessage::text(chat_guid, text);
+        let temp_guid = queued.temp_guid.clone().unwrap_or_default();
+        queued.record_attempt();
         match self
-            .send_text(api, chat_guid, text, method, None, None, None)
+            .send_text_as(api, &temp_guid, chat_guid, text, method, None, None, None)
             .await
         {
             Ok(msg) => Ok(msg),
             Err(e) => {
                 let error_code = classify_send_error(&e);
                 if error_code.should_retry() {
-                    let queued = QueuedMessage {
-                        id: format!("q-{}", uuid::Uuid::new_v4()),
-                        chat_guid: chat_guid.to_string(),
-                        text: Some(text.to_string()),
-                        file_path: None,
-                        attempts: 1,
-                        max_attempts: 5,
-                        last_attempt: Some(std::time::Instant::now()),
-                    };
+                    queued.last_attempt = Some(std::time::Instant::now());
                     queue.enqueue(queued).await;
                     debug!("message enqueued for retry");
                 }
@@ -215,6 +466,24 @@ impl MessageService {
 
         let file_bytes = std::fs::read(file_path)?;
         let temp_guid = format!("temp-{}", uuid::Uuid::new_v4());
+        self.upload_attachment(api, &temp_guid, chat_guid, file_path, file_name, file_bytes, mime_type, method)
+            .await
+    }
+
+    /// Upload an attachment under a given temp GUID and save the result.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_attachment(
+        &self,
+        api: &ApiClient,
+        temp_guid: &str,
+        chat_guid: &str,
+        file_path: &Path,
+        file_name: &str,
+        file_bytes: Vec<u8>,
+        mime_type: &str,
+        method: &str,
+    ) -> BbResult<Message> {
+        let temp_guid = temp_guid.to_string();
 
         debug!("sending attachment: {file_name} ({} bytes)", file_bytes.len());
 
@@ -247,11 +516,34 @@ impl MessageService {
                 chat_guid: chat_guid.to_string(),
             });
         }
+        self.clear_sent_draft(&conn, chat_guid, SentPart::File(file_path));
 
         info!("attachment sent: {:?}", msg.guid);
         Ok(msg)
     }
 
+    /// Remove what was just sent from the chat's composer draft.
+    ///
+    /// A text send clears the draft text only while it still is the sent
+    /// text, so a draft typed after a queued message was composed
+    /// survives its delivery; an attachment send only drops that file from
+    /// the draft attachments. Failures are logged rather than failing the
+    /// send.
+    fn clear_sent_draft(&self, conn: &rusqlite::Connection, chat_guid: &str, sent: SentPart<'_>) {
+        let result = match sent {
+            SentPart::Text(text) => queries::clear_draft_text(conn, chat_guid, text),
+            SentPart::File(file) => queries::remove_draft_attachment(conn, chat_guid, &file.to_string_lossy()),
+        };
+
+        match result {
+            Ok(true) => self.event_bus.emit(AppEvent::ChatUpdated {
+                chat_guid: chat_guid.to_string(),
+            }),
+            Ok(false) => {}
+            Err(e) => warn!("failed to clear draft for chat {chat_guid}: {e}"),
+        }
+    }
+
     /// Send a reaction / tapback.
     pub async fn send_reaction(
         &self,
@@ -308,7 +600,10 @@ impl MessageService {
         Ok(msg)
     }
 
-    /// Unsend a message.
+    /// Unsend a message part, keeping a tombstone with the part's text.
+    ///
+    /// The message itself is only marked deleted once every one of its
+    /// parts has been unsent; until then its other parts stay visible.
     pub async fn unsend_message(
         &self,
         api: &ApiClient,
@@ -318,60 +613,273 @@ impl MessageService {
         api.unsend_message(guid, part_index).await?;
 
         let conn = self.database.conn()?;
-        conn.execute(
-            "UPDATE messages SET date_deleted = ?1 WHERE guid = ?2",
-            rusqlite::params![chrono::Utc::now().to_rfc3339(), guid],
-        )
-        .map_err(|e| BbError::Database(e.to_string()))?;
+        let mut chat_guid = String::new();
+        if let Some(existing) = queries::find_message_by_guid(&conn, guid)? {
+            MessageTombstone::new(guid, existing.chat_id, part_index, existing.part_text(part_index))
+                .save(&conn)?;
 
-        info!("message unsent: {guid}");
+            let unsent: Vec<i32> = MessageTombstone::find_for_message(&conn, guid)?
+                .iter()
+                .map(|t| t.part_index)
+                .collect();
+            if existing.part_indices().iter().all(|part| unsent.contains(part)) {
+                conn.execute(
+                    "UPDATE messages SET date_deleted = ?1 WHERE guid = ?2",
+                    rusqlite::params![chrono::Utc::now().to_rfc3339(), guid],
+                )
+                .map_err(|e| BbError::Database(e.to_string()))?;
+            }
+            if let Some(chat_id) = existing.chat_id {
+                if let Some(chat) = queries::find_chat_by_id(&conn, chat_id)? {
+                    chat_guid = chat.guid;
+                }
+            }
+        }
+
+        info!("message part {part_index} unsent: {guid}");
         self.event_bus.emit(AppEvent::MessageUpdated {
             message_guid: guid.to_string(),
-            chat_guid: String::new(),
+            chat_guid,
         });
         Ok(())
     }
 
+    /// Get the edit history and unsend tombstones for a message.
+    pub fn edit_history(&self, guid: &str) -> BbResult<EditHistory> {
+        let conn = self.database.conn()?;
+        let msg = queries::find_message_by_guid(&conn, guid)?
+            .ok_or_else(|| BbError::MessageNotFound(guid.to_string()))?;
+
+        let parts = msg
+            .summary_info()
+            .map(|info| {
+                info.edited_content
+                    .keys()
+                    .map(|&part_index| PartEditHistory {
+                        part_index,
+                        previous_versions: info.previous_versions(part_index).to_vec(),
+                    })
+                    .filter(|p| !p.previous_versions.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(EditHistory {
+            message_guid: guid.to_string(),
+            current_text: msg.text,
+            date_edited: msg.date_edited,
+            parts,
+            unsent: MessageTombstone::find_for_message(&conn, guid)?,
+        })
+    }
+
     /// Retry sending a failed message from the queue.
     ///
-    /// Looks up the queued message details, re-sends, and either removes from
-    /// queue on success or re-enqueues with incremented attempt count.
+    /// Re-sends under the message's original temp GUID, and either removes it
+    /// from the queue on success or re-enqueues it with an incremented attempt
+    /// count. Attachments are re-read from disk and uploaded again.
     pub async fn retry_failed(
         &self,
         api: &ApiClient,
         queue: &QueueService,
         msg: QueuedMessage,
     ) -> BbResult<()> {
-        if let Some(text) = &msg.text {
-            match self
-                .send_text(api, &msg.chat_guid, text, "private-api", None, None, None)
-                .await
-            {
-                Ok(_) => {
+        let temp_guid = msg
+            .temp_guid
+            .clone()
+            .unwrap_or_else(|| format!("temp-{}", uuid::Uuid::new_v4()));
+        let started = chrono::Utc::now().timestamp_millis();
+        let result = match (&msg.text, &msg.file_path) {
+            (Some(text), _) => {
+                self.send_text_as(api, &temp_guid, &msg.chat_guid, text, "private-api", None, None, None)
+                    .await
+            }
+            (None, Some(file_path)) => {
+                let path = Path::new(file_path);
+                match std::fs::read(path) {
+                    Ok(bytes) => {
+                        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+                        let mime_type = msg.mime_type.as_deref().unwrap_or("application/octet-stream");
+                        self.upload_attachment(
+                            api, &temp_guid, &msg.chat_guid, path, file_name, bytes, mime_type, "private-api",
+                        )
+                        .await
+                    }
+                    Err(e) => {
+                        warn!("queued attachment is gone, dropping {}: {e}", msg.id);
+                        queue.remove(&msg.id).await;
+                        return Ok(());
+                    }
+                }
+            }
+            (None, None) => {
+                queue.remove(&msg.id).await;
+                return Ok(());
+            }
+        };
+
+        match result {
+            Ok(sent) => {
+                queue.remove(&msg.id).await;
+                if let Some(real_guid) = sent.guid.as_deref() {
+                    queue.mark_sent(&temp_guid, real_guid).await;
+                }
+                info!("retry succeeded for queue item: {}", msg.id);
+            }
+            Err(e) => {
+                let error_code = classify_send_error(&e);
+                if msg.should_retry() && error_code.should_retry() {
+                    let id = msg.id.clone();
+                    let retry_msg = QueuedMessage {
+                        attempts: msg.attempts + 1,
+                        last_attempt: Some(std::time::Instant::now()),
+                        first_attempt: msg.first_attempt.or(Some(started)),
+                        temp_guid: Some(temp_guid),
+                        ..msg
+                    };
+                    queue.enqueue(retry_msg).await;
+                    queue.release(&id).await?;
+                    debug!("re-enqueued failed message for retry");
+                } else {
                     queue.remove(&msg.id).await;
-                    info!("retry succeeded for queue item: {}", msg.id);
+                    queue.mark_failed(&temp_guid, &e.to_string(), error_code).await;
+                    warn!("message retry exhausted: {}", msg.id);
                 }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check queued messages against the server before resending them.
+    ///
+    /// A send that reached the server just before a crash or restart is
+    /// still queued locally; resending it would deliver it twice. Each
+    /// message that was attempted at least once is looked up among the
+    /// chat's recent messages by temp GUID (or, failing that, by sender,
+    /// text and attachment name). Delivered ones are saved locally and
+    /// dropped from the queue. Messages another process is sending are
+    /// left to it. Returns how many were reconciled.
+    pub async fn reconcile_queue(&self, api: &ApiClient, queue: &QueueService) -> BbResult<usize> {
+        let mut reconciled = 0;
+        for msg in queue.pending().await {
+            if msg.attempts == 0 || !queue.claim(&msg.id).await? {
+                continue;
+            }
+            let after = msg.first_attempt.unwrap_or(msg.date_created);
+            let recent = match api
+                .get_chat_messages(&msg.chat_guid, 0, 50, "DESC", &["attachment"], None, Some(after))
+                .await
+            {
+                Ok(recent) => recent,
                 Err(e) => {
-                    let error_code = classify_send_error(&e);
-                    if msg.should_retry() && error_code.should_retry() {
-                        let retry_msg = QueuedMessage {
-                            attempts: msg.attempts + 1,
-                            last_attempt: Some(std::time::Instant::now()),
-                            ..msg
-                        };
-                        queue.enqueue(retry_msg).await;
-                        debug!("re-enqueued failed message for retry");
-                    } else {
-                        queue.remove(&msg.id).await;
-                        warn!("message retry exhausted: {}", msg.id);
-                    }
+                    queue.release(&msg.id).await?;
+                    return Err(e);
                 }
+            };
+            let Some(found) = find_delivered(&recent, &msg) else {
+                queue.release(&msg.id).await?;
+                continue;
+            };
+
+            let mut delivered = Message::from_server_map(found)?;
+            let temp_guid = msg.temp_guid.clone().unwrap_or_default();
+            {
+                let conn = self.database.conn()?;
+                self.drop_error_placeholder(&temp_guid)?;
+                conn.execute("DELETE FROM messages WHERE guid = ?1", [&temp_guid])
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                if let Some(chat) = queries::find_chat_by_guid(&conn, &msg.chat_guid)? {
+                    delivered.chat_id = chat.id;
+                }
+                delivered.save(&conn)?;
             }
-        } else if msg.file_path.is_some() {
-            // Attachment retries would go here
-            warn!("attachment retry not yet supported for queue item: {}", msg.id);
+
             queue.remove(&msg.id).await;
+            if let Some(real_guid) = delivered.guid.clone() {
+                queue.mark_sent(&temp_guid, &real_guid).await;
+                self.event_bus.emit(AppEvent::MessageSent {
+                    temp_guid,
+                    real_guid,
+                    chat_guid: msg.chat_guid.clone(),
+                });
+            }
+            info!("queued message {} was already delivered", msg.id);
+            reconciled += 1;
         }
+        Ok(reconciled)
+    }
+
+    /// Run the queue: reconcile with the server once a client is
+    /// available, then resend ready messages as their backoff elapses.
+    /// Messages waiting for the connection are flushed when the socket
+    /// reports connected, or when a periodic ping gets through. The queue
+    /// is re-read on every tick, so messages other processes queue are
+    /// picked up. The API client is read from `api` each time since it can
+    /// be replaced.
+    pub fn start_retry_loop(
+        service: Arc<MessageService>,
+        queue: Arc<QueueService>,
+        api: Arc<RwLock<Option<ApiClient>>>,
+    ) -> tokio::task::JoinHandle<()> {
+        let mut events = service.event_bus.subscribe();
+        tokio::spawn(async move {
+            let mut reconciled = false;
+            let mut last_ping = std::time::Instant::now();
+            loop {
+                let connected = tokio::select! {
+                    event = events.recv() => matches!(
+                        event,
+                        Ok(AppEvent::ConnectionStateChanged { connected: true, .. })
+                    ),
+                    _ = tokio::time::sleep(RETRY_POLL_INTERVAL) => false,
+                };
+                let Some(client) = api.read().await.clone() else {
+                    continue;
+                };
+                if let Err(e) = queue.reload().await {
+                    warn!("failed to re-read send queue: {e}");
+                }
+                if !reconciled {
+                    match service.reconcile_queue(&client, &queue).await {
+                        Ok(n) => {
+                            reconciled = true;
+                            if n > 0 {
+                                info!("reconciled {n} queued messages with the server");
+                            }
+                        }
+                        Err(e) => {
+                            debug!("queue reconciliation deferred: {e}");
+                            continue;
+                        }
+                    }
+                }
+
+                let mut flush = connected;
+                if !flush && last_ping.elapsed() >= PING_INTERVAL && !queue.waiting().await.is_empty() {
+                    last_ping = std::time::Instant::now();
+                    flush = client.ping().await.unwrap_or(false);
+                }
+                if flush {
+                    if let Err(e) = service.flush_waiting(&client, &queue).await {
+                        debug!("flush of waiting messages deferred: {e}");
+                    }
+                }
+
+                while let Some(msg) = queue.dequeue().await {
+                    if let Err(e) = service.retry_failed(&client, &queue, msg).await {
+                        warn!("queued send failed: {e}");
+                    }
+                }
+            }
+        })
+    }
+
+    /// Delete the errored placeholder left by a failed attempt, so a retry
+    /// under the same temp GUID does not show the message twice.
+    fn drop_error_placeholder(&self, temp_guid: &str) -> BbResult<()> {
+        let conn = self.database.conn()?;
+        conn.execute("DELETE FROM messages WHERE guid = ?1", [format!("error-{temp_guid}")])
+            .map_err(|e| BbError::Database(e.to_string()))?;
         Ok(())
     }
 
@@ -389,6 +897,194 @@ impl MessageService {
         let conn = self.database.conn()?;
         queries::count_messages_for_chat(&conn, chat_id)
     }
+
+    /// Bookmark a message, or add to an existing bookmark. Tags are added to
+    /// any already present; `note` replaces the note when given.
+    pub fn bookmark_message(
+        &self,
+        guid: &str,
+        tags: &[String],
+        note: Option<&str>,
+    ) -> BbResult<BookmarkedMessage> {
+        self.update_bookmark(guid, |b| {
+            b.add_tags(tags);
+            if let Some(note) = note {
+                b.note = Some(note.to_string());
+            }
+        })
+    }
+
+    /// Set or clear the note on a bookmark, bookmarking the message if needed.
+    pub fn set_bookmark_note(&self, guid: &str, note: Option<&str>) -> BbResult<BookmarkedMessage> {
+        self.update_bookmark(guid, |b| b.note = note.map(String::from))
+    }
+
+    /// Add tags to a bookmark, bookmarking the message if needed.
+    pub fn add_bookmark_tags(&self, guid: &str, tags: &[String]) -> BbResult<BookmarkedMessage> {
+        self.update_bookmark(guid, |b| b.add_tags(tags))
+    }
+
+    /// Remove tags from a bookmark. The bookmark itself stays.
+    pub fn remove_bookmark_tags(&self, guid: &str, tags: &[String]) -> BbResult<BookmarkedMessage> {
+        self.update_bookmark(guid, |b| b.remove_tags(tags))
+    }
+
+    /// Remove a bookmark with its tags and note. Returns whether one existed.
+    pub fn remove_bookmark(&self, guid: &str) -> BbResult<bool> {
+        let conn = self.database.conn()?;
+        let removed = Bookmark::delete(&conn, guid)?;
+        if removed {
+            debug!("bookmark removed: {guid}");
+            self.emit_bookmark_change(&conn, guid)?;
+        }
+        Ok(removed)
+    }
+
+    /// Get the bookmark on a message, if any.
+    pub fn get_bookmark(&self, guid: &str) -> BbResult<Option<BookmarkedMessage>> {
+        let conn = self.database.conn()?;
+        let Some(bookmark) = Bookmark::find(&conn, guid)? else {
+            return Ok(None);
+        };
+        let mut chats = HashMap::new();
+        bookmarked_message(&conn, bookmark, &mut chats)
+    }
+
+    /// List bookmarks matching a filter, most recently changed first.
+    pub fn list_bookmarks(&self, filter: &BookmarkFilter) -> BbResult<Vec<BookmarkedMessage>> {
+        let conn = self.database.conn()?;
+        let mut chats = HashMap::new();
+        let mut results = Vec::new();
+        for bookmark in Bookmark::list(&conn, filter)? {
+            results.extend(bookmarked_message(&conn, bookmark, &mut chats)?);
+        }
+        Ok(results)
+    }
+
+    /// Every bookmark tag in use, with how many bookmarks carry it.
+    pub fn bookmark_tags(&self) -> BbResult<Vec<(String, i64)>> {
+        let conn = self.database.conn()?;
+        Bookmark::tag_counts(&conn)
+    }
+
+    fn update_bookmark(
+        &self,
+        guid: &str,
+        update: impl FnOnce(&mut Bookmark),
+    ) -> BbResult<BookmarkedMessage> {
+        let conn = self.database.conn()?;
+        queries::find_message_by_guid(&conn, guid)?
+            .ok_or_else(|| BbError::MessageNotFound(guid.to_string()))?;
+
+        let mut bookmark = Bookmark::find(&conn, guid)?.unwrap_or_else(|| Bookmark::new(guid));
+        update(&mut bookmark);
+        bookmark.save(&conn)?;
+        debug!("bookmark saved: {guid} {:?}", bookmark.tags);
+        self.emit_bookmark_change(&conn, guid)?;
+
+        let mut chats = HashMap::new();
+        bookmarked_message(&conn, bookmark, &mut chats)?
+            .ok_or_else(|| BbError::MessageNotFound(guid.to_string()))
+    }
+
+    fn emit_bookmark_change(&self, conn: &rusqlite::Connection, guid: &str) -> BbResult<()> {
+        let chat_guid = match queries::find_message_by_guid(conn, guid)?.and_then(|m| m.chat_id) {
+            Some(chat_id) => Chat::find_by_id(conn, chat_id)?.map(|c| c.guid).unwrap_or_default(),
+            None => String::new(),
+        };
+        self.event_bus.emit(AppEvent::MessageUpdated {
+            message_guid: guid.to_string(),
+            chat_guid,
+        });
+        Ok(())
+    }
+}
+
+/// Join a bookmark with its message and chat, caching chats by ID.
+fn bookmarked_message(
+    conn: &rusqlite::Connection,
+    bookmark: Bookmark,
+    chats: &mut HashMap<i64, Chat>,
+) -> BbResult<Option<BookmarkedMessage>> {
+    let Some(message) = queries::find_message_by_guid(conn, &bookmark.message_guid)? else {
+        return Ok(None);
+    };
+    let chat = match message.chat_id {
+        Some(chat_id) if !chats.contains_key(&chat_id) => {
+            if let Some(mut chat) = Chat::find_by_id(conn, chat_id)? {
+                chat.participants = queries::load_chat_participants(conn, chat_id)?;
+                chats.insert(chat_id, chat);
+            }
+            chats.get(&chat_id)
+        }
+        Some(chat_id) => chats.get(&chat_id),
+        None => None,
+    };
+    Ok(Some(BookmarkedMessage {
+        chat_guid: chat.map(|c| c.guid.clone()).unwrap_or_default(),
+        chat_title: chat.map(|c| c.title()).unwrap_or_default(),
+        bookmark,
+        message,
+    }))
+}
+
+/// What a send took out of the composer.
+enum SentPart<'a> {
+    Text(&'a str),
+    File(&'a Path),
+}
+
+/// Plain text send parameters for a given temp GUID.
+fn text_params(temp_guid: &str, chat_guid: &str, text: &str, method: &str) -> SendTextParams {
+    SendTextParams {
+        chat_guid: chat_guid.to_string(),
+        temp_guid: temp_guid.to_string(),
+        message: text.to_string(),
+        method: method.to_string(),
+        effect_id: None,
+        subject: None,
+        selected_message_guid: None,
+        part_index: None,
+        dd_scan: None,
+        payload_data: None,
+    }
+}
+
+/// Find a queued message among server messages: by temp GUID when the
+/// server echoes it, otherwise by a message from me with the same text or
+/// attachment file name, created no earlier than the first send attempt.
+/// An identical message sent before the attempt can't be this one.
+fn find_delivered<'a>(candidates: &'a [serde_json::Value], msg: &QueuedMessage) -> Option<&'a serde_json::Value> {
+    let by_temp_guid = candidates.iter().find(|c| {
+        msg.temp_guid.is_some() && c.get("tempGuid").and_then(|v| v.as_str()) == msg.temp_guid.as_deref()
+    });
+    by_temp_guid.or_else(|| {
+        let first_attempt = msg.first_attempt.unwrap_or(msg.date_created);
+        let file_name = msg
+            .file_path
+            .as_deref()
+            .and_then(|p| Path::new(p).file_name())
+            .and_then(|n| n.to_str());
+        candidates.iter().find(|c| {
+            if c.get("isFromMe").and_then(|v| v.as_bool()) != Some(true) {
+                return false;
+            }
+            if c.get("dateCreated").and_then(|v| v.as_i64()).is_none_or(|date| date < first_attempt) {
+                return false;
+            }
+            match (&msg.text, file_name) {
+                (Some(text), _) => c.get("text").and_then(|v| v.as_str()) == Some(text.as_str()),
+                (None, Some(name)) => c
+                    .get("attachments")
+                    .and_then(|a| a.as_array())
+                    .is_some_and(|atts| {
+                        atts.iter()
+                            .any(|a| a.get("transferName").and_then(|v| v.as_str()) == Some(name))
+                    }),
+                (None, None) => false,
+            }
+        })
+    })
 }
 
 /// Classify a send error into a MessageError code for retry decisions.
@@ -484,6 +1180,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_clear_sent_draft() {
+        let db = create_test_db();
+        let svc = MessageService::new(db.clone(), crate::event_bus::EventBus::new(16));
+        let conn = db.conn().unwrap();
+        conn.execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+        queries::set_chat_draft(&conn, "chat-1", Some("hi"), r#"["/tmp/a.jpg","/tmp/b.jpg"]"#).unwrap();
+
+        // Sending one attachment only drops that file
+        svc.clear_sent_draft(&conn, "chat-1", SentPart::File(Path::new("/tmp/a.jpg")));
+        let chat = queries::find_chat_by_guid(&conn, "chat-1").unwrap().unwrap();
+        assert_eq!(chat.text_field_text.as_deref(), Some("hi"));
+        assert_eq!(chat.draft_attachment_paths(), vec!["/tmp/b.jpg"]);
+
+        // Sending other text keeps the draft; sending the draft text clears it
+        svc.clear_sent_draft(&conn, "chat-1", SentPart::Text("earlier"));
+        let chat = queries::find_chat_by_guid(&conn, "chat-1").unwrap().unwrap();
+        assert_eq!(chat.text_field_text.as_deref(), Some("hi"));
+        svc.clear_sent_draft(&conn, "chat-1", SentPart::Text("hi"));
+        let chat = queries::find_chat_by_guid(&conn, "chat-1").unwrap().unwrap();
+        assert_eq!(chat.text_field_text, None);
+        assert_eq!(chat.draft_attachment_paths(), vec!["/tmp/b.jpg"]);
+    }
+
+    #[test]
+    fn test_edit_history() {
+        let db = create_test_db();
+        let svc = MessageService::new(db.clone(), crate::event_bus::EventBus::new(16));
+
+        let mut msg = Message::from_server_map(&serde_json::json!({
+            "guid": "msg-edited",
+            "text": "see you at 6",
+            "isFromMe": true,
+            "dateEdited": "2023-11-14T22:14:20Z",
+            "messageSummaryInfo": {
+                "ec": {"0": [
+                    {"d": 1_700_000_000_000_i64, "t": "see you at 5"},
+                    {"d": 1_700_000_060_000_i64, "t": "see you at 6"}
+                ]},
+                "ep": [0]
+            }
+        }))
+        .unwrap();
+        let conn = db.conn().unwrap();
+        msg.save(&conn).unwrap();
+        MessageTombstone::new("msg-edited", None, 1, Some("typo".into()))
+            .save(&conn)
+            .unwrap();
+
+        let history = svc.edit_history("msg-edited").unwrap();
+        assert!(history.is_edited());
+        assert_eq!(history.parts.len(), 1);
+        assert_eq!(history.parts[0].previous_versions[0].text.as_deref(), Some("see you at 5"));
+        assert_eq!(history.unsent.len(), 1);
+
+        assert!(matches!(
+            svc.edit_history("missing"),
+            Err(BbError::MessageNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_retry_eligibility() {
         assert!(MessageError::Timeout.should_retry());
@@ -493,4 +1250,274 @@ mod tests {
         assert!(!MessageError::NoAccessToConversation.should_retry());
         assert!(!MessageError::FailedToSend.should_retry());
     }
+
+    #[test]
+    fn test_find_delivered() {
+        let mut msg = QueuedMessage::text("chat-1", "on my way");
+        msg.temp_guid = Some("temp-1".into());
+        msg.first_attempt = Some(1_000);
+        let candidates = vec![
+            serde_json::json!({"guid": "a", "isFromMe": false, "text": "on my way", "dateCreated": 2_000}),
+            serde_json::json!({"guid": "b", "isFromMe": true, "text": "on my way", "dateCreated": 2_000}),
+            serde_json::json!({"guid": "c", "isFromMe": true, "text": "other", "tempGuid": "temp-1"}),
+        ];
+        assert_eq!(find_delivered(&candidates, &msg).unwrap()["guid"], "c");
+        assert_eq!(find_delivered(&candidates[..2], &msg).unwrap()["guid"], "b");
+        assert!(find_delivered(&candidates[..1], &msg).is_none());
+
+        // The same text sent before the first attempt is an earlier message
+        let earlier = vec![serde_json::json!({"guid": "e", "isFromMe": true, "text": "on my way", "dateCreated": 999})];
+        assert!(find_delivered(&earlier, &msg).is_none());
+
+        let mut photo = QueuedMessage::attachment("chat-1", "/tmp/photo.jpg", "image/jpeg");
+        photo.first_attempt = Some(1_000);
+        let with_attachment = vec![serde_json::json!({
+            "guid": "d", "isFromMe": true, "dateCreated": 1_000, "attachments": [{"transferName": "photo.jpg"}]
+        })];
+        assert_eq!(find_delivered(&with_attachment, &photo).unwrap()["guid"], "d");
+    }
+
+    /// A client whose every request fails to connect, without retry delays.
+    fn unreachable_client() -> ApiClient {
+        let config = bb_core::config::ServerConfig {
+            address: "http://127.0.0.1:1".into(),
+            ..Default::default()
+        };
+        ApiClient::new(&config).unwrap().with_retry_config(bb_api::RetryConfig {
+            max_retries: 0,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_send_while_offline_is_queued() {
+        let db = create_test_db();
+        let bus = crate::event_bus::EventBus::new(16);
+        let mut events = bus.subscribe();
+        let svc = MessageService::new(db.clone(), bus);
+        let queue = QueueService::new().with_database(db.clone());
+
+        let (msg, status) = svc
+            .send_text_or_queue(None, &queue, "chat-1", "hello", "private-api", None)
+            .await
+            .unwrap();
+        assert_eq!(status, SubmitStatus::Queued);
+        let temp_guid = msg.guid.clone().unwrap();
+        assert!(svc.find_message(&temp_guid).unwrap().is_some());
+
+        let waiting = queue.waiting().await;
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].temp_guid.as_deref(), Some(temp_guid.as_str()));
+        match events.recv().await.unwrap() {
+            AppEvent::MessageStatusChanged { temp_guid: guid, status, .. } => {
+                assert_eq!(guid, temp_guid);
+                assert_eq!(status, OutgoingStatus::WaitingForConnection);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_keeps_chat_order_while_offline() {
+        let db = create_test_db();
+        let bus = crate::event_bus::EventBus::new(64);
+        let svc = MessageService::new(db.clone(), bus);
+        let queue = QueueService::new().with_database(db);
+
+        svc.queue_text(&queue, "chat-1", "first").await.unwrap();
+        svc.queue_text(&queue, "chat-1", "second").await.unwrap();
+        svc.queue_text(&queue, "chat-2", "other").await.unwrap();
+
+        let sent = svc.flush_waiting(&unreachable_client(), &queue).await.unwrap();
+        assert_eq!(sent, 0);
+
+        // One attempt per chat: the second chat-1 message waits behind the first
+        let waiting = queue.waiting().await;
+        assert_eq!(waiting.len(), 3);
+        let attempts: Vec<(&str, u32)> = waiting
+            .iter()
+            .map(|m| (m.text.as_deref().unwrap(), m.attempts))
+            .collect();
+        assert!(attempts.contains(&("first", 1)));
+        assert!(attempts.contains(&("second", 0)));
+        assert!(attempts.contains(&("other", 1)));
+    }
+
+    /// A local stand-in for the server that accepts every send and echoes
+    /// back a message under a new GUID. Message lookups come back empty.
+    async fn accepting_client() -> ApiClient {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut served = 0;
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { break };
+                served += 1;
+                // Read the whole request so closing the socket doesn't reset it
+                let mut request = Vec::new();
+                let mut buf = vec![0; 4096];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    let complete = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                        let length = head
+                            .lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                            .and_then(|v| v.parse::<usize>().ok())
+                            .unwrap_or(0);
+                        body.len() >= length
+                    });
+                    if n == 0 || complete {
+                        break;
+                    }
+                }
+                // Lookups find nothing; sends come back delivered
+                let data = if request.starts_with(b"GET") {
+                    serde_json::json!([])
+                } else {
+                    serde_json::json!({"guid": format!("real-{served}"), "isFromMe": true, "text": "sent"})
+                };
+                let body = serde_json::json!({"status": 200, "message": "Success", "data": data}).to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        let config = bb_core::config::ServerConfig { address, ..Default::default() };
+        ApiClient::new(&config).unwrap().with_retry_config(bb_api::RetryConfig {
+            max_retries: 0,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_flush_keeps_newer_draft() {
+        let db = create_test_db();
+        let svc = MessageService::new(db.clone(), crate::event_bus::EventBus::new(64));
+        let queue = QueueService::new().with_database(db.clone());
+        db.conn().unwrap().execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+        let api = accepting_client().await;
+
+        // Something else was typed after the queued message was composed
+        svc.queue_text(&queue, "chat-1", "on my way").await.unwrap();
+        queries::set_chat_draft(&db.conn().unwrap(), "chat-1", Some("running late"), "[]").unwrap();
+        assert_eq!(svc.flush_waiting(&api, &queue).await.unwrap(), 1);
+        let chat = queries::find_chat_by_guid(&db.conn().unwrap(), "chat-1").unwrap().unwrap();
+        assert_eq!(chat.text_field_text.as_deref(), Some("running late"));
+
+        // The draft still holding the queued text is cleared once it's sent
+        svc.queue_text(&queue, "chat-1", "running late").await.unwrap();
+        assert_eq!(svc.flush_waiting(&api, &queue).await.unwrap(), 1);
+        let chat = queries::find_chat_by_guid(&db.conn().unwrap(), "chat-1").unwrap().unwrap();
+        assert_eq!(chat.text_field_text, None);
+    }
+
+    #[tokio::test]
+    async fn test_send_queues_behind_waiting_messages() {
+        let db = create_test_db();
+        let svc = MessageService::new(db.clone(), crate::event_bus::EventBus::new(64));
+        let queue = QueueService::new().with_database(db);
+        let api = accepting_client().await;
+
+        svc.queue_text(&queue, "chat-1", "first").await.unwrap();
+        let (_, status) = svc
+            .send_text_or_queue(Some(&api), &queue, "chat-1", "second", "private-api", None)
+            .await
+            .unwrap();
+        assert_eq!(status, SubmitStatus::Queued);
+
+        // Not tried ahead of the first message
+        let waiting = queue.waiting().await;
+        assert_eq!(waiting.len(), 2);
+        let second = waiting.iter().find(|m| m.text.as_deref() == Some("second")).unwrap();
+        assert_eq!(second.attempts, 0);
+
+        // A chat with nothing waiting sends straight away
+        let (_, status) = svc
+            .send_text_or_queue(Some(&api), &queue, "chat-2", "hello", "private-api", None)
+            .await
+            .unwrap();
+        assert_eq!(status, SubmitStatus::Sent);
+    }
+
+    #[tokio::test]
+    async fn test_flush_sends_or_fails_rows_without_text() {
+        let db = create_test_db();
+        let svc = MessageService::new(db.clone(), crate::event_bus::EventBus::new(64));
+        let queue = QueueService::new().with_database(db);
+        let dir = tempfile::TempDir::new().unwrap();
+        let photo = dir.path().join("photo.jpg");
+        std::fs::write(&photo, b"jpeg").unwrap();
+
+        queue
+            .enqueue(QueuedMessage::attachment("chat-1", &photo.to_string_lossy(), "image/jpeg").waiting())
+            .await;
+        let gone = QueuedMessage::attachment("chat-2", "/nonexistent/photo.jpg", "image/jpeg").waiting();
+        let gone_guid = gone.temp_guid.clone().unwrap();
+        queue.enqueue(gone).await;
+        let mut empty = QueuedMessage::text("chat-3", "").waiting();
+        empty.text = None;
+        let empty_guid = empty.temp_guid.clone().unwrap();
+        queue.enqueue(empty).await;
+
+        // The attachment is tried and keeps waiting while offline
+        assert_eq!(svc.flush_waiting(&unreachable_client(), &queue).await.unwrap(), 0);
+        let waiting = queue.waiting().await;
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].chat_guid, "chat-1");
+        assert_eq!(waiting[0].attempts, 1);
+
+        // Rows with nothing to send are failed rather than dropped silently
+        for guid in [gone_guid, empty_guid] {
+            assert!(matches!(queue.get_status(&guid).await, Some(crate::queue::SendStatus::Failed { .. })));
+        }
+
+        // Once the server is back the attachment goes out
+        assert_eq!(svc.flush_waiting(&accepting_client().await, &queue).await.unwrap(), 1);
+        assert!(queue.waiting().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsend_part_keeps_message_until_all_parts_unsent() {
+        let db = create_test_db();
+        let bus = crate::event_bus::EventBus::new(16);
+        let mut events = bus.subscribe();
+        let svc = MessageService::new(db.clone(), bus);
+        let api = accepting_client().await;
+
+        let conn = db.conn().unwrap();
+        conn.execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+        let mut msg = Message::from_server_map(&serde_json::json!({
+            "guid": "parts",
+            "text": "first second",
+            "isFromMe": true,
+            "attributedBody": [{"runs": [
+                {"string": "first ", "attributes": {"__kIMMessagePartAttributeName": 0}},
+                {"string": "second", "attributes": {"__kIMMessagePartAttributeName": 1}}
+            ]}]
+        }))
+        .unwrap();
+        msg.chat_id = queries::find_chat_by_guid(&conn, "chat-1").unwrap().unwrap().id;
+        msg.save(&conn).unwrap();
+
+        svc.unsend_message(&api, "parts", 1).await.unwrap();
+        let after_one = svc.find_message("parts").unwrap().unwrap();
+        assert!(after_one.date_deleted.is_none());
+        match events.recv().await.unwrap() {
+            AppEvent::MessageUpdated { message_guid, chat_guid } => {
+                assert_eq!(message_guid, "parts");
+                assert_eq!(chat_guid, "chat-1");
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        svc.unsend_message(&api, "parts", 0).await.unwrap();
+        assert!(svc.find_message("parts").unwrap().unwrap().date_deleted.is_some());
+        assert_eq!(svc.edit_history("parts").unwrap().unsent.len(), 2);
+    }
 }
@@ -3,13 +3,37 @@
 //! Manages native notifications for incoming messages, reactions, FaceTime
 //! calls, and connection errors. Supports notification grouping by chat,
 //! mute/DND filtering, and mark-as-read from notification actions.
+//!
+//! Incoming messages are run through ordered `NotificationRule`s matching
+//! chat, sender or person, text, attachment type and mentions of the user.
+//! The first matching rule decides whether to notify, stay silent, notify
+//! with priority or fold the message into a digest; quiet hours then hold
+//! back everything but priority notifications and breakthrough senders.
+//!
+//! Digest messages are held in the database per chat. Once a chat's first
+//! held message is older than the digest window, the chat gets a single
+//! summary ("12 new messages from 4 people in Family") with its last few
+//! lines and reaction counts. Messages that mention the user skip the
+//! digest and notify at once.
 
-#[allow(unused_imports)]
-use bb_core::error::BbError;
-use bb_core::error::BbResult;
+use bb_core::error::{BbError, BbResult};
 use bb_core::config::ConfigHandle;
-use tracing::{info, debug};
+use bb_models::{
+    Chat, Contact, Database, DigestEntry, DigestSettings, Handle, Message, NotificationRule, Person, QuietHours,
+    RuleAction, RuleCondition,
+};
+use bb_models::queries;
+use chrono::{DateTime, NaiveTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, debug, warn};
 
+use crate::event_bus::{AppEvent, EventBus};
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::notification_actions::DbusNotifier;
 use crate::service::{Service, ServiceState};
 
 /// Notification category for grouping and filtering.
@@ -27,6 +51,157 @@ pub enum NotificationCategory {
     SyncStatus,
 }
 
+/// How often the digest flusher checks for due digests.
+const DIGEST_FLUSH_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Longest message line shown in a digest.
+const DIGEST_LINE_LENGTH: usize = 80;
+
+/// What the rules engine needs to know about an incoming message.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NotificationContext {
+    pub message_guid: String,
+    pub chat_guid: String,
+    pub chat_title: String,
+    pub is_group: bool,
+    pub is_from_me: bool,
+    /// Whether the chat is muted in the database.
+    pub chat_muted: bool,
+    /// Sender's handle address, if known.
+    pub sender: Option<String>,
+    /// Sender's person or contact name, falling back to the address.
+    pub sender_name: Option<String>,
+    /// Person the sender's handle belongs to.
+    pub person_id: Option<i64>,
+    /// Whether the sender is a saved contact.
+    pub is_known_sender: bool,
+    pub text: String,
+    pub attachment_mime_types: Vec<String>,
+    /// Whether the message @mentions the user.
+    pub mentions_me: bool,
+    /// Tapback type (`love`, `like`, ...) when the message is a reaction.
+    pub reaction: Option<String>,
+}
+
+impl NotificationContext {
+    /// Notification title: the sender, and the chat for group chats.
+    pub fn title(&self) -> String {
+        let sender = self.sender_name.as_deref().or(self.sender.as_deref()).unwrap_or("Unknown");
+        if self.is_group {
+            format!("{sender} in {}", self.chat_title)
+        } else {
+            sender.to_string()
+        }
+    }
+
+    /// Notification body: the text, the reaction or "Attachment".
+    pub fn body(&self) -> String {
+        match &self.reaction {
+            Some(reaction) => format!("Reacted {reaction}"),
+            None if self.text.is_empty() && !self.attachment_mime_types.is_empty() => "Attachment".to_string(),
+            None => self.text.clone(),
+        }
+    }
+}
+
+/// The outcome of running a message through the notification rules.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NotificationDecision {
+    pub action: RuleAction,
+    /// The rule that decided the action, if any.
+    pub rule_id: Option<i64>,
+    pub rule_name: Option<String>,
+    /// Why the message was silenced or let through, for display.
+    pub reason: String,
+}
+
+impl NotificationDecision {
+    fn new(action: RuleAction, reason: impl Into<String>) -> Self {
+        Self { action, rule_id: None, rule_name: None, reason: reason.into() }
+    }
+
+    /// Whether a notification should be shown now.
+    pub fn shows(&self) -> bool {
+        matches!(self.action, RuleAction::Notify | RuleAction::Priority)
+    }
+}
+
+/// One summary of the messages held for a chat.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NotificationDigest {
+    pub chat_guid: String,
+    pub chat_title: String,
+    /// Held messages, not counting reactions.
+    pub message_count: usize,
+    /// Distinct senders of those messages.
+    pub sender_count: usize,
+    /// Reaction counts by tapback type.
+    pub reactions: BTreeMap<String, usize>,
+    /// The last few messages, as `sender: text`.
+    pub lines: Vec<String>,
+    /// GUIDs of everything in the digest, oldest first.
+    pub message_guids: Vec<String>,
+}
+
+impl NotificationDigest {
+    /// Summarize a chat's held messages, keeping the last `preview_lines`
+    /// lines. Returns `None` when there is nothing to summarize.
+    pub fn from_entries(entries: &[DigestEntry], preview_lines: usize) -> Option<Self> {
+        let last = entries.last()?;
+        let (reactions, messages): (Vec<&DigestEntry>, Vec<&DigestEntry>) =
+            entries.iter().partition(|e| e.reaction.is_some());
+
+        let mut reaction_counts = BTreeMap::new();
+        for reaction in reactions.iter().filter_map(|e| e.reaction.as_deref()) {
+            *reaction_counts.entry(reaction.to_string()).or_insert(0) += 1;
+        }
+        let senders: HashSet<&str> = messages.iter().filter_map(|e| e.sender.as_deref()).collect();
+        let lines = messages[messages.len().saturating_sub(preview_lines)..]
+            .iter()
+            .map(|e| {
+                let line = match &e.sender {
+                    Some(sender) => format!("{sender}: {}", e.text),
+                    None => e.text.clone(),
+                };
+                truncate_line(&line)
+            })
+            .collect();
+
+        Some(Self {
+            chat_guid: last.chat_guid.clone(),
+            chat_title: last.chat_title.clone(),
+            message_count: messages.len(),
+            sender_count: senders.len(),
+            reactions: reaction_counts,
+            lines,
+            message_guids: entries.iter().map(|e| e.message_guid.clone()).collect(),
+        })
+    }
+
+    /// One-line summary, e.g. "12 new messages from 4 people in Family".
+    pub fn summary(&self) -> String {
+        let reaction_count: usize = self.reactions.values().sum();
+        if self.message_count == 0 {
+            return format!("{} in {}", plural(reaction_count, "reaction", "reactions"), self.chat_title);
+        }
+        let messages = plural(self.message_count, "new message", "new messages");
+        match self.sender_count {
+            0 => format!("{messages} in {}", self.chat_title),
+            n => format!("{messages} from {} in {}", plural(n, "person", "people"), self.chat_title),
+        }
+    }
+
+    /// Notification body: the last few lines, then reaction counts.
+    pub fn body(&self) -> String {
+        let mut body = self.lines.clone();
+        if !self.reactions.is_empty() {
+            let counts: Vec<String> = self.reactions.iter().map(|(kind, n)| format!("{n} {kind}")).collect();
+            body.push(format!("Reactions: {}", counts.join(", ")));
+        }
+        body.join("\n")
+    }
+}
+
 /// Service for managing desktop notifications.
 ///
 /// Creates and displays native notifications for incoming messages,
@@ -39,6 +214,11 @@ pub struct NotificationService {
     enabled: bool,
     /// Set of muted chat GUIDs (notifications are suppressed for these).
     muted_chats: std::collections::HashSet<String>,
+    /// Database holding notification rules and quiet hours.
+    database: Option<Database>,
+    /// Freedesktop notifier used for message notifications with actions.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    actions: Option<Arc<DbusNotifier>>,
 }
 
 impl NotificationService {
@@ -49,9 +229,33 @@ impl NotificationService {
             config,
             enabled: true,
             muted_chats: std::collections::HashSet::new(),
+            database: None,
+            #[cfg(all(unix, not(target_os = "macos")))]
+            actions: None,
         }
     }
 
+    /// Read rules and quiet hours from `database`. Without one, every
+    /// message gets the default decision.
+    pub fn with_database(mut self, database: Database) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// Post message notifications through `notifier`, with reply, mark as
+    /// read and mute actions.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn with_dbus_notifier(mut self, notifier: Arc<DbusNotifier>) -> Self {
+        self.actions = Some(notifier);
+        self
+    }
+
+    fn db(&self) -> BbResult<&Database> {
+        self.database
+            .as_ref()
+            .ok_or_else(|| BbError::Internal("notification service has no database".into()))
+    }
+
     /// Set whether notifications are globally enabled.
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
@@ -101,6 +305,7 @@ impl NotificationService {
             text,
             NotificationCategory::Message,
             Some(chat_guid),
+            false,
         )?;
 
         debug!("message notification: {sender} - {text}");
@@ -131,6 +336,7 @@ impl NotificationService {
             reaction,
             NotificationCategory::Reaction,
             Some(chat_guid),
+            false,
         )?;
 
         debug!("reaction notification: {sender} {reaction}");
@@ -153,6 +359,7 @@ impl NotificationService {
             &format!("From: {caller}"),
             NotificationCategory::FaceTime,
             None,
+            false,
         )?;
 
         info!("FaceTime notification: {caller} ({call_type})");
@@ -170,6 +377,7 @@ impl NotificationService {
             message,
             NotificationCategory::ConnectionError,
             None,
+            false,
         )?;
 
         debug!("connection notification: {message}");
@@ -182,7 +390,7 @@ impl NotificationService {
             return Ok(());
         }
 
-        self.show_notification(title, body, NotificationCategory::SyncStatus, None)
+        self.show_notification(title, body, NotificationCategory::SyncStatus, None, false)
     }
 
     /// Check whether text detection keywords match the message.
@@ -208,6 +416,413 @@ impl NotificationService {
             .any(|keyword| !keyword.is_empty() && text_lower.contains(&keyword))
     }
 
+    // ─── Rules ───────────────────────────────────────────────────────────
+
+    /// Notification rules in evaluation order.
+    pub fn list_rules(&self) -> BbResult<Vec<NotificationRule>> {
+        let conn = self.db()?.conn()?;
+        NotificationRule::list(&conn)
+    }
+
+    /// Find a rule by name, or by ID when `reference` is numeric and no
+    /// rule has that name.
+    pub fn find_rule(&self, reference: &str) -> BbResult<Option<NotificationRule>> {
+        let conn = self.db()?.conn()?;
+        if let Some(rule) = NotificationRule::find_by_name(&conn, reference)? {
+            return Ok(Some(rule));
+        }
+        match reference.trim().parse::<i64>() {
+            Ok(id) => NotificationRule::find_by_id(&conn, id),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Add a rule at the end of the order.
+    pub fn add_rule(
+        &self,
+        name: &str,
+        conditions: Vec<RuleCondition>,
+        action: RuleAction,
+    ) -> BbResult<NotificationRule> {
+        let mut rule = NotificationRule::new(name, conditions, action);
+        self.save_rule(&mut rule)?;
+        info!("added notification rule {} ({action})", rule.name);
+        Ok(rule)
+    }
+
+    /// Save changes to a rule, checking its text patterns compile.
+    pub fn save_rule(&self, rule: &mut NotificationRule) -> BbResult<()> {
+        for condition in &rule.conditions {
+            if let RuleCondition::TextMatches { pattern } = condition {
+                Regex::new(pattern)
+                    .map_err(|e| BbError::InvalidInput(format!("invalid text pattern {pattern:?}: {e}")))?;
+            }
+        }
+        let conn = self.db()?.conn()?;
+        rule.save(&conn)?;
+        Ok(())
+    }
+
+    /// Enable or disable a rule.
+    pub fn set_rule_enabled(&self, id: i64, enabled: bool) -> BbResult<NotificationRule> {
+        let conn = self.db()?.conn()?;
+        let mut rule = NotificationRule::find_by_id(&conn, id)?
+            .ok_or_else(|| BbError::InvalidInput(format!("notification rule {id} not found")))?;
+        rule.enabled = enabled;
+        rule.save(&conn)?;
+        Ok(rule)
+    }
+
+    /// Move a rule to `position` (0-based) in the evaluation order.
+    pub fn move_rule(&self, id: i64, position: usize) -> BbResult<Vec<NotificationRule>> {
+        let conn = self.db()?.conn()?;
+        let mut rules = NotificationRule::list(&conn)?;
+        let from = rules
+            .iter()
+            .position(|r| r.id == Some(id))
+            .ok_or_else(|| BbError::InvalidInput(format!("notification rule {id} not found")))?;
+        let rule = rules.remove(from);
+        rules.insert(position.min(rules.len()), rule);
+        for (i, rule) in rules.iter_mut().enumerate() {
+            if rule.sort_index != i as i64 {
+                rule.sort_index = i as i64;
+                rule.save(&conn)?;
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Delete a rule. Returns whether it existed.
+    pub fn delete_rule(&self, id: i64) -> BbResult<bool> {
+        let conn = self.db()?.conn()?;
+        NotificationRule::delete(&conn, id)
+    }
+
+    /// The saved quiet hours.
+    pub fn quiet_hours(&self) -> BbResult<QuietHours> {
+        let conn = self.db()?.conn()?;
+        QuietHours::load(&conn)
+    }
+
+    /// Replace the quiet hours.
+    pub fn set_quiet_hours(&self, quiet: &QuietHours) -> BbResult<()> {
+        let conn = self.db()?.conn()?;
+        quiet.save(&conn)?;
+        debug!("quiet hours set: {quiet:?}");
+        Ok(())
+    }
+
+    /// Load what the rules need to know about a received message. Returns
+    /// `None` for other events and for messages not in the database.
+    pub fn context_for_event(&self, event: &AppEvent) -> BbResult<Option<NotificationContext>> {
+        let AppEvent::MessageReceived { message_guid, chat_guid, is_from_me } = event else {
+            return Ok(None);
+        };
+        let conn = self.db()?.conn()?;
+        let Some(message) = Message::find_by_guid(&conn, message_guid)? else {
+            return Ok(None);
+        };
+
+        let handle = match message.handle_id {
+            Some(id) => Handle::find_by_id(&conn, id)?,
+            None => None,
+        };
+        let person = match handle.as_ref().and_then(|h| h.id) {
+            Some(id) => Person::find_by_handle(&conn, id)?,
+            None => None,
+        };
+        let contact = match handle.as_ref().and_then(|h| h.contact_id) {
+            Some(id) => Contact::find_by_id(&conn, id)?,
+            None => None,
+        };
+        let sender_name = person
+            .as_ref()
+            .and_then(|p| p.name.clone())
+            .filter(|n| !n.is_empty())
+            .or_else(|| contact.map(|c| c.display_name))
+            .or_else(|| handle.as_ref().map(|h| h.display_name()));
+        let attachment_mime_types = match message.id {
+            Some(id) => {
+                let mut stmt = conn
+                    .prepare("SELECT mime_type FROM attachments WHERE message_id = ?1 AND mime_type IS NOT NULL")
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                let types = stmt
+                    .query_map([id], |row| row.get::<_, String>(0))
+                    .map_err(|e| BbError::Database(e.to_string()))?
+                    .filter_map(|r| r.ok())
+                    .collect();
+                types
+            }
+            None => Vec::new(),
+        };
+
+        let chat = match Chat::find_by_guid(&conn, chat_guid)? {
+            Some(mut chat) => {
+                if let Some(id) = chat.id {
+                    chat.participants = queries::load_chat_participants(&conn, id)?;
+                }
+                Some(chat)
+            }
+            None => None,
+        };
+        let mentions_me = match &message.attributed_body {
+            Some(body) => {
+                let mine = queries::my_addresses(&conn)?;
+                mentioned_addresses(body).iter().any(|a| mine.iter().any(|me| same_address(a, me)))
+            }
+            None => false,
+        };
+
+        Ok(Some(NotificationContext {
+            message_guid: message_guid.clone(),
+            chat_guid: chat_guid.clone(),
+            chat_title: chat.as_ref().map(|c| c.title()).unwrap_or_else(|| chat_guid.clone()),
+            is_group: chat.as_ref().is_some_and(|c| c.is_group()),
+            is_from_me: *is_from_me || message.is_from_me,
+            chat_muted: chat.as_ref().is_some_and(|c| is_muted(c, Utc::now().timestamp_millis())),
+            is_known_sender: handle.as_ref().is_some_and(|h| h.contact_id.is_some()),
+            sender: handle.map(|h| h.address),
+            sender_name,
+            person_id: person.and_then(|p| p.id),
+            text: message.text.unwrap_or_default(),
+            attachment_mime_types,
+            mentions_me,
+            reaction: message.associated_message_type,
+        }))
+    }
+
+    /// Decide how to notify for a message, with the local time `now` for
+    /// quiet hours.
+    ///
+    /// Your own messages, disabled notifications and muted chats or people
+    /// are always silent. Otherwise the first enabled rule whose
+    /// conditions all match decides; with no match, unknown senders follow
+    /// `filter_unknown_senders`, group chats go to the digest when the
+    /// digest settings say so, and everything else notifies. Digest
+    /// messages that mention the user notify instead. During quiet hours,
+    /// notify and digest become silent unless the sender is on the
+    /// breakthrough list.
+    pub async fn decide(&self, ctx: &NotificationContext, now: NaiveTime) -> BbResult<NotificationDecision> {
+        if ctx.is_from_me {
+            return Ok(NotificationDecision::new(RuleAction::Silence, "sent by you"));
+        }
+        if !self.enabled {
+            return Ok(NotificationDecision::new(RuleAction::Silence, "notifications are disabled"));
+        }
+        if ctx.chat_muted || self.is_chat_muted(&ctx.chat_guid) {
+            return Ok(NotificationDecision::new(RuleAction::Silence, "muted"));
+        }
+
+        let (rules, quiet, digest) = match &self.database {
+            Some(db) => {
+                let conn = db.conn()?;
+                (NotificationRule::list(&conn)?, QuietHours::load(&conn)?, DigestSettings::load(&conn)?)
+            }
+            None => (Vec::new(), QuietHours::default(), DigestSettings::default()),
+        };
+
+        let patterns = compile_patterns(&rules);
+        let matches = |r: &&NotificationRule| r.enabled && r.conditions.iter().all(|c| condition_matches(c, ctx, &patterns));
+        let mut decision = match rules.iter().find(matches) {
+            Some(rule) => NotificationDecision {
+                action: rule.action,
+                rule_id: rule.id,
+                rule_name: Some(rule.name.clone()),
+                reason: format!("matched rule {}", rule.name),
+            },
+            None if !ctx.is_known_sender && self.config.read().await.notifications.filter_unknown_senders => {
+                NotificationDecision::new(RuleAction::Silence, "unknown sender")
+            }
+            None if digest.group_chats && ctx.is_group => {
+                NotificationDecision::new(RuleAction::Digest, "group chat digest")
+            }
+            None => NotificationDecision::new(RuleAction::Notify, "no rule matched"),
+        };
+
+        if decision.action == RuleAction::Digest && ctx.mentions_me {
+            decision.action = RuleAction::Notify;
+            decision.reason = format!("{}; mentions you", decision.reason);
+        }
+
+        if matches!(decision.action, RuleAction::Notify | RuleAction::Digest) && quiet.is_active(now) {
+            let breaks_through = ctx
+                .sender
+                .as_deref()
+                .is_some_and(|s| quiet.breakthrough.iter().any(|b| same_address(b, s)));
+            if breaks_through {
+                decision.action = RuleAction::Notify;
+                decision.reason = format!("{}; breakthrough sender during quiet hours", decision.reason);
+            } else {
+                decision.action = RuleAction::Silence;
+                decision.reason = format!("{}; held by quiet hours", decision.reason);
+            }
+        }
+        Ok(decision)
+    }
+
+    /// Decide how to notify for an event at the current local time.
+    /// Returns `None` for events that are not received messages.
+    pub async fn decide_event(&self, event: &AppEvent) -> BbResult<Option<(NotificationContext, NotificationDecision)>> {
+        let Some(ctx) = self.context_for_event(event)? else {
+            return Ok(None);
+        };
+        let decision = self.decide(&ctx, chrono::Local::now().time()).await?;
+        Ok(Some((ctx, decision)))
+    }
+
+    // ─── Digests ─────────────────────────────────────────────────────────
+
+    /// The saved digest settings.
+    pub fn digest_settings(&self) -> BbResult<DigestSettings> {
+        let conn = self.db()?.conn()?;
+        DigestSettings::load(&conn)
+    }
+
+    /// Replace the digest settings.
+    pub fn set_digest_settings(&self, settings: &DigestSettings) -> BbResult<()> {
+        let conn = self.db()?.conn()?;
+        settings.save(&conn)?;
+        debug!("digest settings set: {settings:?}");
+        Ok(())
+    }
+
+    /// Messages held for digests, oldest first.
+    pub fn held_messages(&self) -> BbResult<Vec<DigestEntry>> {
+        let conn = self.db()?.conn()?;
+        DigestEntry::list(&conn)
+    }
+
+    /// Decide how to notify for an event, holding digest messages for
+    /// their chat's summary. Callers show a notification when the
+    /// decision `shows()`.
+    pub async fn process_event(&self, event: &AppEvent) -> BbResult<Option<(NotificationContext, NotificationDecision)>> {
+        let Some((ctx, decision)) = self.decide_event(event).await? else {
+            return Ok(None);
+        };
+        if decision.action == RuleAction::Digest {
+            self.hold_for_digest(&ctx, Utc::now().timestamp_millis())?;
+        }
+        Ok(Some((ctx, decision)))
+    }
+
+    /// Hold a message for its chat's digest, at `at` (epoch milliseconds).
+    /// Returns false when it was already held or is a removed reaction.
+    pub fn hold_for_digest(&self, ctx: &NotificationContext, at: i64) -> BbResult<bool> {
+        if ctx.reaction.as_deref().is_some_and(|r| r.starts_with('-')) {
+            return Ok(false);
+        }
+        let text = if ctx.text.is_empty() && !ctx.attachment_mime_types.is_empty() {
+            "Attachment".to_string()
+        } else {
+            ctx.text.clone()
+        };
+        let mut entry = DigestEntry {
+            id: None,
+            chat_guid: ctx.chat_guid.clone(),
+            chat_title: ctx.chat_title.clone(),
+            message_guid: ctx.message_guid.clone(),
+            sender: ctx.sender_name.clone().or_else(|| ctx.sender.clone()),
+            text,
+            reaction: ctx.reaction.clone(),
+            buffered_at: at,
+        };
+        let conn = self.db()?.conn()?;
+        entry.save(&conn)
+    }
+
+    /// Take the digests of chats whose first held message is at least the
+    /// digest window old at `now`.
+    pub fn flush_digests(&self, now: DateTime<Utc>) -> BbResult<Vec<NotificationDigest>> {
+        let settings = self.digest_settings()?;
+        let cutoff = now.timestamp_millis() - (settings.window_secs as i64).saturating_mul(1000);
+        self.take_digests_before(cutoff, settings.preview_lines)
+    }
+
+    /// Take every held digest now, however recent.
+    pub fn flush_all_digests(&self) -> BbResult<Vec<NotificationDigest>> {
+        let settings = self.digest_settings()?;
+        self.take_digests_before(i64::MAX, settings.preview_lines)
+    }
+
+    /// Take a chat's digest now, however recent its held messages.
+    pub fn flush_chat_digest(&self, chat_guid: &str) -> BbResult<Option<NotificationDigest>> {
+        let settings = self.digest_settings()?;
+        self.take_digest(chat_guid, settings.preview_lines)
+    }
+
+    fn take_digests_before(&self, cutoff: i64, preview_lines: usize) -> BbResult<Vec<NotificationDigest>> {
+        let due = {
+            let conn = self.db()?.conn()?;
+            DigestEntry::due_chats(&conn, cutoff)?
+        };
+
+        let mut digests = Vec::new();
+        for chat_guid in due {
+            if let Some(digest) = self.take_digest(&chat_guid, preview_lines)? {
+                digests.push(digest);
+            }
+        }
+        Ok(digests)
+    }
+
+    fn take_digest(&self, chat_guid: &str, preview_lines: usize) -> BbResult<Option<NotificationDigest>> {
+        let mut conn = self.db()?.conn()?;
+        let entries = DigestEntry::take_chat(&mut conn, chat_guid)?;
+        Ok(NotificationDigest::from_entries(&entries, preview_lines))
+    }
+
+    /// Show a desktop notification for a message whose decision shows,
+    /// marked critical when the decision is priority. With a D-Bus
+    /// notifier it carries reply, mark as read and mute actions for the
+    /// chat.
+    pub async fn show_message(&self, ctx: &NotificationContext, decision: &NotificationDecision) -> BbResult<()> {
+        let urgent = decision.action == RuleAction::Priority;
+        let (title, body) = (ctx.title(), ctx.body());
+        let category = match ctx.reaction {
+            Some(_) => NotificationCategory::Reaction,
+            None => NotificationCategory::Message,
+        };
+
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if let Some(notifier) = &self.actions {
+            notifier.show_message(&title, &body, &ctx.chat_guid, urgent).await?;
+            return Ok(());
+        }
+        self.show_notification(&title, &body, category, Some(&ctx.chat_guid), urgent)
+    }
+
+    /// Show a digest as one desktop notification.
+    pub fn show_digest(&self, digest: &NotificationDigest) -> BbResult<()> {
+        self.show_notification(
+            &digest.summary(),
+            &digest.body(),
+            NotificationCategory::Message,
+            Some(&digest.chat_guid),
+            false,
+        )?;
+        debug!("digest notification: {}", digest.summary());
+        Ok(())
+    }
+
+    /// Periodically take due digests and emit each as
+    /// `NotificationDigestReady`. Digests are taken atomically, so running
+    /// a flusher in several processes sends each digest once.
+    pub fn start_digest_flusher(service: Arc<NotificationService>, event_bus: EventBus) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match service.flush_digests(Utc::now()) {
+                    Ok(digests) => {
+                        for digest in digests {
+                            event_bus.emit(AppEvent::NotificationDigestReady { digest });
+                        }
+                    }
+                    Err(e) => warn!("digest flush failed: {e}"),
+                }
+                tokio::time::sleep(DIGEST_FLUSH_INTERVAL).await;
+            }
+        })
+    }
+
     /// Determine whether a notification should be shown.
     async fn should_notify(&self, chat_guid: &str, is_from_known_sender: bool) -> bool {
         if !self.enabled {
@@ -227,29 +842,130 @@ impl NotificationService {
         true
     }
 
-    /// Actually show the native notification.
+    /// Actually show the native notification. Urgent notifications are
+    /// marked critical where the platform supports it.
     fn show_notification(
         &self,
         title: &str,
         body: &str,
         _category: NotificationCategory,
         _chat_guid: Option<&str>,
+        urgent: bool,
     ) -> BbResult<()> {
         #[cfg(not(test))]
         {
-            notify_rust::Notification::new()
-                .summary(title)
-                .body(body)
-                .appname("BlueBubbles")
-                .show()
-                .map_err(|e| BbError::Notification(e.to_string()))?;
+            let mut notification = notify_rust::Notification::new();
+            notification.summary(title).body(body).appname("BlueBubbles");
+            #[cfg(all(unix, not(target_os = "macos")))]
+            if urgent {
+                notification.urgency(notify_rust::Urgency::Critical);
+            }
+            notification.show().map_err(|e| BbError::Notification(e.to_string()))?;
         }
 
-        let _ = (title, body);
+        let _ = (title, body, urgent);
         Ok(())
     }
 }
 
+/// Compile the text patterns of enabled rules, once per decision. A
+/// pattern that doesn't compile is logged and its rule never matches.
+fn compile_patterns(rules: &[NotificationRule]) -> HashMap<&str, Regex> {
+    let mut patterns = HashMap::new();
+    for rule in rules.iter().filter(|r| r.enabled) {
+        for condition in &rule.conditions {
+            let RuleCondition::TextMatches { pattern } = condition else { continue };
+            if patterns.contains_key(pattern.as_str()) {
+                continue;
+            }
+            match Regex::new(pattern) {
+                Ok(re) => {
+                    patterns.insert(pattern.as_str(), re);
+                }
+                Err(e) => warn!("notification rule {} has an invalid text pattern {pattern:?}: {e}", rule.name),
+            }
+        }
+    }
+    patterns
+}
+
+/// Whether a rule condition holds for a message, with text patterns
+/// looked up in `patterns`.
+fn condition_matches(condition: &RuleCondition, ctx: &NotificationContext, patterns: &HashMap<&str, Regex>) -> bool {
+    match condition {
+        RuleCondition::Chat { guid } => ctx.chat_guid == *guid,
+        RuleCondition::Sender { address } => ctx.sender.as_deref().is_some_and(|s| same_address(s, address)),
+        RuleCondition::Person { id } => ctx.person_id == Some(*id),
+        RuleCondition::TextMatches { pattern } => patterns.get(pattern.as_str()).is_some_and(|re| re.is_match(&ctx.text)),
+        RuleCondition::Attachment { mime } if mime == "*" => !ctx.attachment_mime_types.is_empty(),
+        RuleCondition::Attachment { mime } => {
+            let mime = mime.to_lowercase();
+            ctx.attachment_mime_types.iter().any(|m| m.to_lowercase().starts_with(&mime))
+        }
+        RuleCondition::MentionsMe => ctx.mentions_me,
+    }
+}
+
+/// Whether a chat is muted at `now_ms`. A mute with a timestamp in
+/// `mute_args` (from `ChatService::mute_until`) ends at that time.
+fn is_muted(chat: &Chat, now_ms: i64) -> bool {
+    if chat.mute_type.is_none() {
+        return false;
+    }
+    match chat.mute_args.as_deref().and_then(|a| a.parse::<i64>().ok()) {
+        Some(until) => now_ms < until,
+        None => true,
+    }
+}
+
+/// Shorten a digest line to `DIGEST_LINE_LENGTH` characters.
+fn truncate_line(line: &str) -> String {
+    let line = line.replace('\n', " ");
+    if line.chars().count() <= DIGEST_LINE_LENGTH {
+        return line;
+    }
+    let cut: String = line.chars().take(DIGEST_LINE_LENGTH - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+/// `count` with the singular or plural noun.
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{count} {}", if count == 1 { one } else { many })
+}
+
+/// Whether two handle addresses are the same: phone numbers compared in
+/// E.164, anything else ignoring case.
+pub(crate) fn same_address(a: &str, b: &str) -> bool {
+    if a.trim().eq_ignore_ascii_case(b.trim()) {
+        return true;
+    }
+    match (bb_models::phone::normalize(a, None), bb_models::phone::normalize(b, None)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Addresses @mentioned in a stored attributed body (the server's JSON).
+fn mentioned_addresses(attributed_body: &str) -> Vec<String> {
+    fn walk(value: &serde_json::Value, out: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(address) = map.get("__kIMMentionConfirmedMention").and_then(|v| v.as_str()) {
+                    out.push(address.to_string());
+                }
+                map.values().for_each(|v| walk(v, out));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| walk(v, out)),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(attributed_body) {
+        walk(&json, &mut out);
+    }
+    out
+}
+
 impl Service for NotificationService {
     fn name(&self) -> &str { "notification" }
     fn state(&self) -> ServiceState { self.state }
@@ -344,4 +1060,176 @@ mod tests {
         assert_eq!(NotificationCategory::Message, NotificationCategory::Message);
         assert_ne!(NotificationCategory::Message, NotificationCategory::Reaction);
     }
+
+    /// A database with one contact-linked sender (+15551230001, person 1),
+    /// one unknown sender, and four received messages in two chats.
+    fn rules_db() -> bb_models::Database {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = bb_models::Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        std::mem::forget(dir);
+        let conn = db.conn().unwrap();
+        conn.execute_batch(
+            r#"INSERT INTO contacts (id, display_name) VALUES (1, 'Alice');
+             INSERT INTO handles (id, address, unique_address_service, contact_id)
+                 VALUES (1, '+15551230001', '+15551230001/iMessage', 1),
+                        (2, 'stranger@example.com', 'stranger@example.com/iMessage', NULL);
+             INSERT INTO persons (id, name, date_created) VALUES (1, 'Alice', '2025-01-01');
+             INSERT INTO person_handles (handle_id, person_id) VALUES (1, 1);
+             INSERT INTO chats (id, guid, chat_identifier, display_name) VALUES (1, 'family', 'family', 'Family'), (2, 'work', 'work', NULL);
+             INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1), (1, 2);
+             INSERT INTO messages (id, guid, chat_id, handle_id, is_from_me, text, attributed_body) VALUES
+                 (1, 'm-hello', 1, 1, 0, 'hello', NULL),
+                 (2, 'm-photo', 1, 2, 0, '', NULL),
+                 (3, 'm-deploy', 2, 2, 0, 'Deploy FAILED on prod', NULL),
+                 (4, 'm-mention', 1, 2, 0, 'Ann, look',
+                  '[{"runs":[{"string":"Ann","attributes":{"__kIMMentionConfirmedMention":"ann@icloud.com"}}]}]');
+             INSERT INTO messages (id, guid, chat_id, handle_id, is_from_me, associated_message_guid, associated_message_type)
+                 VALUES (5, 'm-love', 1, 2, 0, 'm-hello', 'love');
+             INSERT INTO attachments (guid, message_id, mime_type) VALUES ('a1', 2, 'image/jpeg');"#,
+        )
+        .unwrap();
+        queries::save_my_address(&conn, "+15550000000").unwrap();
+        queries::save_my_address(&conn, "ann@icloud.com").unwrap();
+        db
+    }
+
+    fn received(guid: &str, chat: &str) -> AppEvent {
+        AppEvent::MessageReceived { message_guid: guid.into(), chat_guid: chat.into(), is_from_me: false }
+    }
+
+    async fn action_for(svc: &NotificationService, guid: &str, chat: &str, now: &str) -> RuleAction {
+        let ctx = svc.context_for_event(&received(guid, chat)).unwrap().unwrap();
+        let now = NaiveTime::parse_from_str(now, "%H:%M").unwrap();
+        svc.decide(&ctx, now).await.unwrap().action
+    }
+
+    #[tokio::test]
+    async fn test_context_for_message_received() {
+        let svc = NotificationService::new(make_config()).with_database(rules_db());
+        let ctx = svc.context_for_event(&received("m-hello", "family")).unwrap().unwrap();
+        assert_eq!(ctx.sender.as_deref(), Some("+15551230001"));
+        assert_eq!(ctx.person_id, Some(1));
+        assert!(ctx.is_known_sender && !ctx.mentions_me);
+
+        assert_eq!((ctx.title(), ctx.body()), ("Alice in Family".to_string(), "hello".to_string()));
+
+        let photo = svc.context_for_event(&received("m-photo", "family")).unwrap().unwrap();
+        assert_eq!(photo.attachment_mime_types, vec!["image/jpeg".to_string()]);
+        assert!(!photo.is_known_sender);
+        assert_eq!(photo.body(), "Attachment");
+        assert!(svc.context_for_event(&received("m-mention", "family")).unwrap().unwrap().mentions_me);
+
+        assert!(svc.context_for_event(&received("missing", "family")).unwrap().is_none());
+        let other = AppEvent::ChatDeleted { chat_guid: "family".into() };
+        assert!(svc.context_for_event(&other).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rules_apply_in_order() {
+        let svc = NotificationService::new(make_config()).with_database(rules_db());
+        svc.add_rule("Mentions", vec![RuleCondition::MentionsMe], RuleAction::Priority).unwrap();
+        svc.add_rule(
+            "Prod alerts",
+            vec![RuleCondition::Chat { guid: "work".into() }, RuleCondition::TextMatches { pattern: "(?i)failed".into() }],
+            RuleAction::Priority,
+        )
+        .unwrap();
+        svc.add_rule("Photos", vec![RuleCondition::Attachment { mime: "image/".into() }], RuleAction::Silence).unwrap();
+        let family = svc
+            .add_rule("Family digest", vec![RuleCondition::Chat { guid: "family".into() }], RuleAction::Digest)
+            .unwrap();
+        svc.add_rule("Alice", vec![RuleCondition::Person { id: 1 }], RuleAction::Notify).unwrap();
+        assert!(svc
+            .add_rule("Bad", vec![RuleCondition::TextMatches { pattern: "(".into() }], RuleAction::Notify)
+            .is_err());
+
+        assert_eq!(action_for(&svc, "m-mention", "family", "12:00").await, RuleAction::Priority);
+        assert_eq!(action_for(&svc, "m-deploy", "work", "12:00").await, RuleAction::Priority);
+        assert_eq!(action_for(&svc, "m-photo", "family", "12:00").await, RuleAction::Silence);
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Digest);
+
+        // Moving Alice's rule above the family digest lets her through
+        let alice = svc.find_rule("alice").unwrap().unwrap();
+        let order = svc.move_rule(alice.id.unwrap(), 0).unwrap();
+        assert_eq!(order[0].name, "Alice");
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Notify);
+
+        svc.set_rule_enabled(alice.id.unwrap(), false).unwrap();
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Digest);
+        assert!(svc.delete_rule(family.id.unwrap()).unwrap());
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Notify);
+
+        // A saved pattern that doesn't compile never matches
+        let mut broken = NotificationRule::new("Broken", vec![RuleCondition::TextMatches { pattern: "(".into() }], RuleAction::Silence);
+        broken.save(&svc.db().unwrap().conn().unwrap()).unwrap();
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Notify);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_hours_and_breakthrough() {
+        let svc = NotificationService::new(make_config()).with_database(rules_db());
+        svc.add_rule("Mentions", vec![RuleCondition::MentionsMe], RuleAction::Priority).unwrap();
+        let mut quiet = QuietHours::new("22:00", "07:00").unwrap();
+        quiet.breakthrough.push("(555) 123-0001".into());
+        svc.set_quiet_hours(&quiet).unwrap();
+
+        assert_eq!(action_for(&svc, "m-deploy", "work", "12:00").await, RuleAction::Notify);
+        assert_eq!(action_for(&svc, "m-deploy", "work", "23:00").await, RuleAction::Silence);
+        // Priority and breakthrough senders (matched in E.164) still notify
+        assert_eq!(action_for(&svc, "m-mention", "family", "23:00").await, RuleAction::Priority);
+        assert_eq!(action_for(&svc, "m-hello", "family", "06:30").await, RuleAction::Notify);
+    }
+
+    #[tokio::test]
+    async fn test_default_decision_respects_mutes_and_unknown_filter() {
+        let config = make_config();
+        config.write().await.notifications.filter_unknown_senders = true;
+        let mut svc = NotificationService::new(config).with_database(rules_db());
+        assert_eq!(action_for(&svc, "m-deploy", "work", "12:00").await, RuleAction::Silence);
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Notify);
+
+        svc.mute_chat("family");
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Silence);
+        svc.unmute_chat("family");
+        svc.db().unwrap().conn().unwrap().execute("UPDATE chats SET mute_type = 'mute', mute_args = '1000' WHERE guid = 'family'", []).unwrap();
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Notify);
+        svc.db().unwrap().conn().unwrap().execute("UPDATE chats SET mute_args = NULL WHERE guid = 'family'", []).unwrap();
+        assert_eq!(action_for(&svc, "m-hello", "family", "12:00").await, RuleAction::Silence);
+
+        let mine = AppEvent::MessageReceived { message_guid: "m-hello".into(), chat_guid: "family".into(), is_from_me: true };
+        let ctx = svc.context_for_event(&mine).unwrap().unwrap();
+        let decision = svc.decide(&ctx, NaiveTime::MIN).await.unwrap();
+        assert!(!decision.shows());
+    }
+
+    #[tokio::test]
+    async fn test_group_chat_digest() {
+        let svc = NotificationService::new(make_config()).with_database(rules_db());
+        svc.set_digest_settings(&DigestSettings { window_secs: 60, preview_lines: 3, group_chats: true }).unwrap();
+
+        let mut actions = Vec::new();
+        for (guid, chat) in [("m-hello", "family"), ("m-photo", "family"), ("m-love", "family"), ("m-mention", "family"), ("m-deploy", "work")] {
+            let (_, decision) = svc.process_event(&received(guid, chat)).await.unwrap().unwrap();
+            actions.push(decision.action);
+        }
+        // Mentions break out of the digest; one-to-one chats aren't digested
+        assert_eq!(
+            actions,
+            vec![RuleAction::Digest, RuleAction::Digest, RuleAction::Digest, RuleAction::Notify, RuleAction::Notify]
+        );
+        assert_eq!(svc.held_messages().unwrap().len(), 3);
+
+        let now = Utc::now();
+        assert!(svc.flush_digests(now).unwrap().is_empty());
+        let digests = svc.flush_digests(now + chrono::Duration::seconds(61)).unwrap();
+        assert_eq!(digests.len(), 1);
+        let digest = &digests[0];
+        assert_eq!(digest.summary(), "2 new messages from 2 people in Family");
+        assert_eq!(digest.lines, vec!["Alice: hello".to_string(), "stranger@example.com: Attachment".to_string()]);
+        assert_eq!(digest.reactions.get("love"), Some(&1));
+        assert!(digest.body().ends_with("Reactions: 1 love"));
+
+        assert!(svc.held_messages().unwrap().is_empty());
+        assert!(svc.flush_digests(now + chrono::Duration::seconds(120)).unwrap().is_empty());
+    }
 }
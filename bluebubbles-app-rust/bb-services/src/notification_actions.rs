@@ -0,0 +1,494 @@
+//! Actions on message notifications: reply, mark as read and mute.
+//!
+//! On Linux, `DbusNotifier` posts message notifications through the
+//! freedesktop notification service with "Reply", "Mark as Read" and
+//! "Mute 1h" actions. Reply gets a text field where the notification server
+//! supports inline replies (the `inline-reply` capability); elsewhere it
+//! comes back as `NotificationAction::Open` so the app can show the chat.
+//!
+//! The chat GUID goes out with each notification (as the
+//! `x-bluebubbles-chat-guid` hint) and is kept against the notification ID,
+//! so invoked actions come back as `NotificationAction`s for their chat.
+//! `NotificationAction::perform` routes them to
+//! `MessageService::send_text_or_queue`, `ChatService::mark_read` and
+//! `ChatService::mute_until`.
+
+use bb_api::ApiClient;
+use bb_core::error::BbResult;
+use serde::Serialize;
+use tracing::info;
+
+use crate::chat::ChatService;
+use crate::message::MessageService;
+use crate::outbox::SubmitStatus;
+use crate::queue::QueueService;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub use dbus::{DbusNotifier, NotificationActions};
+
+/// Action key for a reply typed into the notification.
+pub const ACTION_INLINE_REPLY: &str = "inline-reply";
+/// Action key for a reply button on servers without inline replies.
+pub const ACTION_REPLY: &str = "reply";
+/// Action key for "Mark as Read".
+pub const ACTION_MARK_READ: &str = "mark-read";
+/// Action key for "Mute 1h".
+pub const ACTION_MUTE: &str = "mute-1h";
+/// Action key the server sends when the notification itself is clicked.
+pub const ACTION_DEFAULT: &str = "default";
+
+/// How long "Mute 1h" mutes a chat, in milliseconds.
+const MUTE_DURATION_MS: i64 = 60 * 60 * 1000;
+
+/// Something the user did with a message notification.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationAction {
+    /// Send `text` to the chat.
+    Reply { chat_guid: String, text: String },
+    /// Mark the chat as read.
+    MarkRead { chat_guid: String },
+    /// Mute the chat until `until_ms` (epoch milliseconds).
+    Mute { chat_guid: String, until_ms: i64 },
+    /// Show the chat in the app.
+    Open { chat_guid: String },
+}
+
+impl NotificationAction {
+    /// Map an invoked action key to an action on `chat_guid`, at `now_ms`
+    /// for mutes. Returns `None` for unknown keys.
+    pub fn from_key(key: &str, chat_guid: &str, now_ms: i64) -> Option<Self> {
+        let chat_guid = chat_guid.to_string();
+        match key {
+            ACTION_MARK_READ => Some(Self::MarkRead { chat_guid }),
+            ACTION_MUTE => Some(Self::Mute { chat_guid, until_ms: now_ms + MUTE_DURATION_MS }),
+            ACTION_REPLY | ACTION_DEFAULT => Some(Self::Open { chat_guid }),
+            _ => None,
+        }
+    }
+
+    /// The chat the action is for.
+    pub fn chat_guid(&self) -> &str {
+        match self {
+            Self::Reply { chat_guid, .. }
+            | Self::MarkRead { chat_guid }
+            | Self::Mute { chat_guid, .. }
+            | Self::Open { chat_guid } => chat_guid,
+        }
+    }
+
+    /// Carry out the action. A reply that can't reach the server waits in
+    /// `queue` for the connection. `Open` is left to the caller, which owns
+    /// the UI, and does nothing here.
+    pub async fn perform(
+        &self,
+        api: &ApiClient,
+        queue: &QueueService,
+        chats: &ChatService,
+        messages: &MessageService,
+    ) -> BbResult<()> {
+        match self {
+            Self::Reply { chat_guid, text } => {
+                let (_, status) = messages
+                    .send_text_or_queue(Some(api), queue, chat_guid, text, "private-api", None)
+                    .await?;
+                match status {
+                    SubmitStatus::Sent => info!("replied to {chat_guid} from a notification"),
+                    SubmitStatus::Queued => info!("queued reply to {chat_guid} from a notification"),
+                }
+            }
+            Self::MarkRead { chat_guid } => {
+                chats.mark_read(api, chat_guid).await?;
+            }
+            Self::Mute { chat_guid, until_ms } => {
+                chats.mute_until(chat_guid, *until_ms)?;
+            }
+            Self::Open { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod dbus {
+    use std::collections::HashMap;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+
+    use bb_core::error::{BbError, BbResult};
+    use tracing::debug;
+    use zbus::export::futures_core::Stream;
+    use zbus::zvariant::Value;
+    use zbus::{Connection, MatchRule, MessageStream};
+
+    use super::{NotificationAction, ACTION_INLINE_REPLY, ACTION_MARK_READ, ACTION_MUTE, ACTION_REPLY};
+
+    /// Well-known bus name of the freedesktop notification service.
+    pub const NOTIFICATIONS_BUS: &str = "org.freedesktop.Notifications";
+    const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+    const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
+    /// `urgency` hint values from the notification spec.
+    const URGENCY_NORMAL: u8 = 1;
+    const URGENCY_CRITICAL: u8 = 2;
+
+    fn dbus_error(e: zbus::Error) -> BbError {
+        BbError::Notification(e.to_string())
+    }
+
+    /// Posts message notifications with actions over D-Bus.
+    pub struct DbusNotifier {
+        connection: Connection,
+        destination: String,
+        inline_reply: bool,
+        /// Chat GUID of each notification still on screen.
+        chats: Arc<Mutex<HashMap<u32, String>>>,
+    }
+
+    impl DbusNotifier {
+        /// Connect to the notification service on the session bus.
+        pub async fn session() -> BbResult<Self> {
+            let connection = Connection::session().await.map_err(dbus_error)?;
+            Self::with_connection(connection, NOTIFICATIONS_BUS).await
+        }
+
+        /// Use the notification service at `destination` on `connection`.
+        /// On a peer-to-peer connection the destination is ignored.
+        pub async fn with_connection(connection: Connection, destination: &str) -> BbResult<Self> {
+            let reply = connection
+                .call_method(
+                    Some(destination),
+                    NOTIFICATIONS_PATH,
+                    Some(NOTIFICATIONS_INTERFACE),
+                    "GetCapabilities",
+                    &(),
+                )
+                .await
+                .map_err(dbus_error)?;
+            let capabilities: Vec<String> = reply.body().deserialize().map_err(dbus_error)?;
+            debug!("notification server capabilities: {capabilities:?}");
+
+            Ok(Self {
+                connection,
+                destination: destination.to_string(),
+                inline_reply: capabilities.iter().any(|c| c == ACTION_INLINE_REPLY),
+                chats: Arc::new(Mutex::new(HashMap::new())),
+            })
+        }
+
+        /// Whether replies can be typed into the notification.
+        pub fn supports_inline_reply(&self) -> bool {
+            self.inline_reply
+        }
+
+        /// Post a message notification for `chat_guid` with reply, mark as
+        /// read and mute actions, at critical urgency when `urgent`.
+        /// Returns the notification ID.
+        pub async fn show_message(&self, title: &str, body: &str, chat_guid: &str, urgent: bool) -> BbResult<u32> {
+            let reply_key = if self.inline_reply { ACTION_INLINE_REPLY } else { ACTION_REPLY };
+            let actions = [reply_key, "Reply", ACTION_MARK_READ, "Mark as Read", ACTION_MUTE, "Mute 1h"];
+
+            let mut hints: HashMap<&str, Value<'_>> = HashMap::new();
+            hints.insert("category", Value::from("im.received"));
+            hints.insert("x-bluebubbles-chat-guid", Value::from(chat_guid));
+            hints.insert("urgency", Value::U8(if urgent { URGENCY_CRITICAL } else { URGENCY_NORMAL }));
+            if self.inline_reply {
+                hints.insert("x-kde-reply-placeholder-text", Value::from("Reply…"));
+            }
+
+            let reply = self
+                .connection
+                .call_method(
+                    Some(self.destination.as_str()),
+                    NOTIFICATIONS_PATH,
+                    Some(NOTIFICATIONS_INTERFACE),
+                    "Notify",
+                    &("BlueBubbles", 0u32, "", title, body, &actions[..], hints, -1i32),
+                )
+                .await
+                .map_err(dbus_error)?;
+            let id: u32 = reply.body().deserialize().map_err(dbus_error)?;
+
+            self.chats.lock().unwrap_or_else(|e| e.into_inner()).insert(id, chat_guid.to_string());
+            debug!("posted notification {id} for {chat_guid}");
+            Ok(id)
+        }
+
+        /// Start listening for actions on notifications from this notifier.
+        pub async fn listen(&self) -> BbResult<NotificationActions> {
+            let rule = MatchRule::builder()
+                .msg_type(zbus::message::Type::Signal)
+                .interface(NOTIFICATIONS_INTERFACE)
+                .map_err(dbus_error)?
+                .path(NOTIFICATIONS_PATH)
+                .map_err(dbus_error)?
+                .build();
+            let stream = MessageStream::for_match_rule(rule, &self.connection, None)
+                .await
+                .map_err(dbus_error)?;
+            Ok(NotificationActions { stream, chats: self.chats.clone() })
+        }
+    }
+
+    /// Actions invoked on posted notifications, in order.
+    pub struct NotificationActions {
+        stream: MessageStream,
+        chats: Arc<Mutex<HashMap<u32, String>>>,
+    }
+
+    impl NotificationActions {
+        /// Wait for the next action. Returns `None` when the connection
+        /// closes.
+        pub async fn next(&mut self) -> Option<NotificationAction> {
+            loop {
+                let msg = std::future::poll_fn(|cx| Pin::new(&mut self.stream).poll_next(cx)).await?;
+                let Ok(msg) = msg else { continue };
+                let header = msg.header();
+                let Some(member) = header.member() else { continue };
+
+                match member.as_str() {
+                    "ActionInvoked" => {
+                        let Ok((id, key)) = msg.body().deserialize::<(u32, String)>() else { continue };
+                        let Some(chat_guid) = self.chat_for(id) else { continue };
+                        let now = chrono::Utc::now().timestamp_millis();
+                        if let Some(action) = NotificationAction::from_key(&key, &chat_guid, now) {
+                            return Some(action);
+                        }
+                    }
+                    "NotificationReplied" => {
+                        let Ok((id, text)) = msg.body().deserialize::<(u32, String)>() else { continue };
+                        let Some(chat_guid) = self.chat_for(id) else { continue };
+                        if !text.trim().is_empty() {
+                            return Some(NotificationAction::Reply { chat_guid, text });
+                        }
+                    }
+                    "NotificationClosed" => {
+                        if let Ok((id, _reason)) = msg.body().deserialize::<(u32, u32)>() {
+                            self.chats.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        fn chat_for(&self, id: u32) -> Option<String> {
+            self.chats.lock().unwrap_or_else(|e| e.into_inner()).get(&id).cloned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_keys() {
+        assert_eq!(
+            NotificationAction::from_key(ACTION_MARK_READ, "chat-1", 0),
+            Some(NotificationAction::MarkRead { chat_guid: "chat-1".into() })
+        );
+        assert_eq!(
+            NotificationAction::from_key(ACTION_MUTE, "chat-1", 1_000),
+            Some(NotificationAction::Mute { chat_guid: "chat-1".into(), until_ms: 1_000 + 3_600_000 })
+        );
+        assert_eq!(
+            NotificationAction::from_key(ACTION_REPLY, "chat-1", 0).map(|a| a.chat_guid().to_string()),
+            Some("chat-1".to_string())
+        );
+        assert!(NotificationAction::from_key("snooze", "chat-1", 0).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_perform_routes_to_chat_service() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = bb_models::Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        db.conn()
+            .unwrap()
+            .execute("INSERT INTO chats (guid, has_unread_message) VALUES ('chat-1', 1)", [])
+            .unwrap();
+        let bus = crate::event_bus::EventBus::new(16);
+        let chats = ChatService::new(db.clone(), bus.clone());
+        let messages = MessageService::new(db.clone(), bus);
+        // Offline, so mark-as-read is applied locally and queued
+        let api = ApiClient::new(&bb_core::config::ServerConfig {
+            address: "http://127.0.0.1:1".into(),
+            ..Default::default()
+        })
+        .unwrap()
+        .with_retry_config(bb_api::RetryConfig { max_retries: 0, ..Default::default() });
+
+        let queue = QueueService::new().with_database(db.clone());
+
+        NotificationAction::MarkRead { chat_guid: "chat-1".into() }
+            .perform(&api, &queue, &chats, &messages)
+            .await
+            .unwrap();
+        NotificationAction::Mute { chat_guid: "chat-1".into(), until_ms: 42 }
+            .perform(&api, &queue, &chats, &messages)
+            .await
+            .unwrap();
+        // An offline reply waits for the connection instead of being lost
+        NotificationAction::Reply { chat_guid: "chat-1".into(), text: "on my way".into() }
+            .perform(&api, &queue, &chats, &messages)
+            .await
+            .unwrap();
+        assert_eq!(queue.waiting().await[0].text.as_deref(), Some("on my way"));
+
+        let (unread, mute_args): (bool, Option<String>) = db
+            .conn()
+            .unwrap()
+            .query_row("SELECT has_unread_message, mute_args FROM chats WHERE guid = 'chat-1'", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert!(!unread);
+        assert_eq!(mute_args.as_deref(), Some("42"));
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    mod stand_in {
+        use std::collections::HashMap;
+        use std::sync::{Arc, Mutex};
+
+        use zbus::object_server::SignalEmitter;
+        use zbus::zvariant::OwnedValue;
+
+        use super::super::*;
+
+        const PATH: &str = "/org/freedesktop/Notifications";
+
+        /// A notification posted to the stand-in daemon.
+        struct Posted {
+            summary: String,
+            actions: Vec<String>,
+            hints: HashMap<String, OwnedValue>,
+        }
+
+        /// Stands in for the desktop's notification daemon.
+        struct Daemon {
+            inline_reply: bool,
+            next_id: u32,
+            posted: Arc<Mutex<Vec<Posted>>>,
+        }
+
+        #[zbus::interface(name = "org.freedesktop.Notifications")]
+        impl Daemon {
+            fn get_capabilities(&self) -> Vec<String> {
+                let mut caps = vec!["actions".to_string(), "body".to_string()];
+                if self.inline_reply {
+                    caps.push("inline-reply".into());
+                }
+                caps
+            }
+
+            #[allow(clippy::too_many_arguments)]
+            fn notify(
+                &mut self,
+                _app_name: String,
+                _replaces_id: u32,
+                _app_icon: String,
+                summary: String,
+                _body: String,
+                actions: Vec<String>,
+                hints: HashMap<String, OwnedValue>,
+                _expire_timeout: i32,
+            ) -> u32 {
+                self.next_id += 1;
+                self.posted.lock().unwrap().push(Posted { summary, actions, hints });
+                self.next_id
+            }
+
+            #[zbus(signal)]
+            async fn action_invoked(emitter: &SignalEmitter<'_>, id: u32, action_key: &str) -> zbus::Result<()>;
+
+            #[zbus(signal)]
+            async fn notification_replied(emitter: &SignalEmitter<'_>, id: u32, text: &str) -> zbus::Result<()>;
+
+            #[zbus(signal)]
+            async fn notification_closed(emitter: &SignalEmitter<'_>, id: u32, reason: u32) -> zbus::Result<()>;
+        }
+
+        /// A notifier talking peer-to-peer to a stand-in daemon.
+        async fn connect(inline_reply: bool) -> (DbusNotifier, zbus::Connection, Arc<Mutex<Vec<Posted>>>) {
+            let posted = Arc::new(Mutex::new(Vec::new()));
+            let daemon = Daemon { inline_reply, next_id: 0, posted: posted.clone() };
+            let (server_end, client_end) = std::os::unix::net::UnixStream::pair().unwrap();
+            let server = zbus::connection::Builder::unix_stream(server_end)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(PATH, daemon)
+                .unwrap()
+                .build();
+            let client = zbus::connection::Builder::unix_stream(client_end).p2p().build();
+            let (server, client) = tokio::join!(server, client);
+            let notifier = DbusNotifier::with_connection(client.unwrap(), "org.freedesktop.Notifications")
+                .await
+                .unwrap();
+            (notifier, server.unwrap(), posted)
+        }
+
+        async fn emitter(server: &zbus::Connection) -> SignalEmitter<'static> {
+            SignalEmitter::new(server, PATH).unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_inline_reply_and_actions() {
+            let (notifier, server, posted) = connect(true).await;
+            assert!(notifier.supports_inline_reply());
+            let mut actions = notifier.listen().await.unwrap();
+
+            let family = notifier.show_message("Alice in Family", "dinner?", "iMessage;+;family", true).await.unwrap();
+            let work = notifier.show_message("Bob", "deploy done", "iMessage;-;bob", false).await.unwrap();
+            {
+                let posted = posted.lock().unwrap();
+                assert_eq!(posted[0].summary, "Alice in Family");
+                assert_eq!(posted[0].actions, vec!["inline-reply", "Reply", "mark-read", "Mark as Read", "mute-1h", "Mute 1h"]);
+                let guid: String = posted[0].hints["x-bluebubbles-chat-guid"].try_clone().unwrap().try_into().unwrap();
+                assert_eq!(guid, "iMessage;+;family");
+                let urgency: u8 = posted[0].hints["urgency"].try_clone().unwrap().try_into().unwrap();
+                assert_eq!(urgency, 2);
+                let urgency: u8 = posted[1].hints["urgency"].try_clone().unwrap().try_into().unwrap();
+                assert_eq!(urgency, 1);
+            }
+
+            let signals = emitter(&server).await;
+            // Signals for notifications this notifier didn't post are ignored
+            Daemon::action_invoked(&signals, 99, "mark-read").await.unwrap();
+            Daemon::notification_replied(&signals, family, "on my way").await.unwrap();
+            Daemon::action_invoked(&signals, work, "mark-read").await.unwrap();
+            Daemon::action_invoked(&signals, family, "mute-1h").await.unwrap();
+            Daemon::notification_closed(&signals, work, 2).await.unwrap();
+            Daemon::action_invoked(&signals, work, "mark-read").await.unwrap();
+            Daemon::action_invoked(&signals, family, "default").await.unwrap();
+
+            assert_eq!(
+                actions.next().await.unwrap(),
+                NotificationAction::Reply { chat_guid: "iMessage;+;family".into(), text: "on my way".into() }
+            );
+            assert_eq!(actions.next().await.unwrap(), NotificationAction::MarkRead { chat_guid: "iMessage;-;bob".into() });
+            match actions.next().await.unwrap() {
+                NotificationAction::Mute { chat_guid, until_ms } => {
+                    assert_eq!(chat_guid, "iMessage;+;family");
+                    assert!(until_ms > chrono::Utc::now().timestamp_millis());
+                }
+                other => panic!("expected mute, got {other:?}"),
+            }
+            // Closed notifications no longer map to a chat
+            assert_eq!(actions.next().await.unwrap(), NotificationAction::Open { chat_guid: "iMessage;+;family".into() });
+        }
+
+        #[tokio::test]
+        async fn test_reply_button_without_inline_reply() {
+            let (notifier, server, posted) = connect(false).await;
+            assert!(!notifier.supports_inline_reply());
+            let mut actions = notifier.listen().await.unwrap();
+
+            let id = notifier.show_message("Bob", "deploy done", "iMessage;-;bob", false).await.unwrap();
+            assert_eq!(posted.lock().unwrap()[0].actions[0], "reply");
+
+            Daemon::action_invoked(&emitter(&server).await, id, "reply").await.unwrap();
+            assert_eq!(actions.next().await.unwrap(), NotificationAction::Open { chat_guid: "iMessage;-;bob".into() });
+        }
+    }
+}
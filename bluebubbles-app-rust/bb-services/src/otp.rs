@@ -0,0 +1,670 @@
+//! One-time code (OTP) detection for received messages.
+//!
+//! `OtpDetector` finds verification codes in message text: the user's own
+//! patterns first, then built-in patterns from most to least specific.
+//! Codes are 4-8 digits, or 4-8 uppercase letters and digits with at least
+//! one digit ("K7Q2MD"), optionally split in two ("123-456"). The generic
+//! patterns skip numbers that are part of a date, phone number or amount,
+//! and a bare number must also look like a code in context: a code keyword
+//! nearby and not a year.
+//!
+//! `OtpService` runs the detector over `MessageReceived` events, honors the
+//! sender allowlist in `OtpSettings`, and emits `AppEvent::OtpDetected` with
+//! the code, sender and expiry. The expiry comes from phrases like "valid
+//! for 10 minutes", or the configured default.
+
+use std::sync::{Arc, LazyLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use bb_core::error::{BbError, BbResult};
+use bb_models::models::settings::keys;
+use bb_models::{queries, Chat, Contact, Database, Handle, Message, OtpSettings, Settings};
+
+use crate::event_bus::{AppEvent, EventBus};
+use crate::notification::same_address;
+use crate::service::{Service, ServiceState};
+use crate::util::timestamp_ms;
+
+/// Received messages searched by `OtpService::recent_codes`.
+const RECENT_MESSAGE_LIMIT: i64 = 20;
+
+/// A code: 4-8 uppercase letters/digits, or two groups of three digits.
+const CODE: &str = r"(\d{3}[- ]\d{3}|(?-i:[A-Z0-9]{4,8}))";
+
+// "Your code is 123456", "verification code: 123456", "Backup code: 123456"
+static CODE_WITH_PREFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)(?:your|the|verification|security|access|authentication|login|otp|one-time|backup)\s+(?:code|password|passcode|otp|pin)\s*(?:is|:|are)?\s*{CODE}\b"
+    ))
+    .unwrap()
+});
+
+// "123456 is your code"
+static CODE_WITH_SUFFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)\b{CODE}\s+(?:is|as)\s+(?:your|the|a|an)\s+(?:verification|security|access|authentication|login|otp|one-time)?\s*(?:code|password|passcode|otp|pin)\b"
+    ))
+    .unwrap()
+});
+
+// "Your Apple ID Code is: 123456"
+static APPLE_FORMAT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:apple|icloud)\s+(?:id\s+)?code\s*(?:is)?\s*:\s*(\d{4,8})\b").unwrap()
+});
+
+// "G-123456 is your Google verification code"
+static GOOGLE_FORMAT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\bG-(\d{6})\b").unwrap());
+
+// "Use code (123456)"
+static BRACKETED_CODE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"(?i)\b(?:use|enter|input|code|otp|pin)\s*(?:code|otp|pin)?\s*[\(\[\{{]{CODE}[\)\]\}}]")).unwrap()
+});
+
+// A bare number; only used when a code keyword is in the message.
+static STANDALONE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(\d{3}[- ]\d{3}|\d{4,8})\b").unwrap());
+
+static CODE_KEYWORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(code|codes|verification|verify|otp|pin|passcode|password|security|use|enter)\b").unwrap()
+});
+
+// Numbers that are not codes: dates, phone numbers and amounts.
+static DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b\d{1,4}[/.-]\d{1,2}[/.-]\d{1,4}\b|\b\d{1,2}/\d{4}\b|\b\d{4}/\d{1,2}\b").unwrap()
+});
+static PHONE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{3}\)|\b\d{3})[\s.-]?\d{3}[\s.-]\d{4}\b|\b\d{3}-\d{4}\b").unwrap()
+});
+static AMOUNT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)[$€£¥₹]\s?\d[\d,]*(?:\.\d+)?|\b\d[\d,]*(?:\.\d+)?\s?(?:%|usd\b|eur\b|gbp\b|dollars\b|euros\b|pounds\b|points\b)")
+        .unwrap()
+});
+
+// "valid for 10 minutes", "expires in 5 min"
+static EXPIRY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:valid|expires?|expiring|good|active)\s+(?:for|in|within)\s+(\d{1,3})\s*(seconds?|secs?|minutes?|mins?|hours?|hrs?)\b")
+        .unwrap()
+});
+
+/// Detected OTP code with metadata.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OtpDetection {
+    /// The detected code, without separators.
+    pub code: String,
+    /// The pattern that matched (for debugging/logging).
+    pub pattern: OtpPattern,
+    /// Position in the text where the code was found.
+    pub position: usize,
+}
+
+/// Type of pattern that detected the OTP.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum OtpPattern {
+    Custom,
+    CodeWithPrefix,
+    CodeWithSuffix,
+    AppleFormat,
+    GoogleFormat,
+    BracketedCode,
+    StandaloneCode,
+}
+
+impl OtpPattern {
+    /// Get a human-readable description of the pattern.
+    pub fn description(&self) -> &'static str {
+        match self {
+            OtpPattern::Custom => "Custom pattern",
+            OtpPattern::CodeWithPrefix => "Code with prefix (e.g., 'Your code is 123456')",
+            OtpPattern::CodeWithSuffix => "Code with suffix (e.g., '123456 is your code')",
+            OtpPattern::AppleFormat => "Apple format (e.g., 'Apple ID Code is: 123456')",
+            OtpPattern::GoogleFormat => "Google format (e.g., 'G-123456')",
+            OtpPattern::BracketedCode => "Bracketed code (e.g., 'Use code (123456)')",
+            OtpPattern::StandaloneCode => "Standalone numeric code",
+        }
+    }
+}
+
+/// Finds one-time codes in text, trying custom patterns before the
+/// built-in ones.
+#[derive(Debug, Clone, Default)]
+pub struct OtpDetector {
+    custom: Vec<Regex>,
+}
+
+impl OtpDetector {
+    /// Build a detector with user-defined patterns. Fails on a pattern
+    /// that is not a valid regular expression.
+    pub fn new(custom_patterns: &[String]) -> BbResult<Self> {
+        let custom = custom_patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| BbError::InvalidInput(format!("invalid pattern '{p}': {e}"))))
+            .collect::<BbResult<_>>()?;
+        Ok(Self { custom })
+    }
+
+    /// Detect the first code in `text`, preferring more specific patterns.
+    pub fn detect(&self, text: &str) -> Option<OtpDetection> {
+        self.detections(text).next()
+    }
+
+    /// Detect every distinct code in `text`, in pattern order.
+    pub fn detect_all(&self, text: &str) -> Vec<OtpDetection> {
+        let mut found: Vec<OtpDetection> = Vec::new();
+        for detection in self.detections(text) {
+            if !found.iter().any(|d| d.code == detection.code) {
+                found.push(detection);
+            }
+        }
+        found
+    }
+
+    fn detections<'a>(&'a self, text: &'a str) -> impl Iterator<Item = OtpDetection> + 'a {
+        let custom = self.custom.iter().flat_map(move |re| {
+            re.captures_iter(text).filter_map(|caps| {
+                let m = caps.get(1).or_else(|| caps.get(0))?;
+                let code: String = m.as_str().chars().filter(|c| !matches!(c, '-' | ' ')).collect();
+                let valid = (3..=12).contains(&code.len()) && code.chars().all(|c| c.is_alphanumeric());
+                valid.then_some(OtpDetection { code, pattern: OtpPattern::Custom, position: m.start() })
+            })
+        });
+
+        let excluded = if text.is_empty() { Vec::new() } else { excluded_spans(text) };
+        let has_keyword = CODE_KEYWORD.is_match(text);
+        let builtin = [
+            (&*APPLE_FORMAT, OtpPattern::AppleFormat),
+            (&*GOOGLE_FORMAT, OtpPattern::GoogleFormat),
+            (&*CODE_WITH_PREFIX, OtpPattern::CodeWithPrefix),
+            (&*CODE_WITH_SUFFIX, OtpPattern::CodeWithSuffix),
+            (&*BRACKETED_CODE, OtpPattern::BracketedCode),
+            (&*STANDALONE_CODE, OtpPattern::StandaloneCode),
+        ]
+        .into_iter()
+        .filter(move |(_, pattern)| *pattern != OtpPattern::StandaloneCode || has_keyword)
+        .flat_map(move |(re, pattern)| {
+            let excluded = excluded.clone();
+            re.captures_iter(text).filter_map(move |caps| {
+                let m = caps.get(1)?;
+                let code = clean_code(m.as_str())?;
+                let vendor = matches!(pattern, OtpPattern::AppleFormat | OtpPattern::GoogleFormat);
+                if !vendor && excluded.iter().any(|(s, e)| m.start() < *e && *s < m.end()) {
+                    return None;
+                }
+                if pattern == OtpPattern::StandaloneCode && is_year(&code) {
+                    return None;
+                }
+                Some(OtpDetection { code, pattern, position: m.start() })
+            })
+        });
+
+        custom.chain(builtin)
+    }
+}
+
+/// Strip separators and check the shape of a code: 4-8 letters and
+/// digits including at least one digit.
+fn clean_code(raw: &str) -> Option<String> {
+    let code: String = raw.chars().filter(|c| !matches!(c, '-' | ' ')).collect();
+    let valid = (4..=8).contains(&code.len())
+        && code.chars().all(|c| c.is_ascii_alphanumeric())
+        && code.chars().any(|c| c.is_ascii_digit());
+    valid.then_some(code)
+}
+
+fn is_year(code: &str) -> bool {
+    code.len() == 4 && code.parse::<u32>().is_ok_and(|y| (1900..=2099).contains(&y))
+}
+
+/// Byte ranges of dates, phone numbers and amounts in `text`.
+fn excluded_spans(text: &str) -> Vec<(usize, usize)> {
+    [&*DATE, &*PHONE, &*AMOUNT]
+        .into_iter()
+        .flat_map(|re| re.find_iter(text).map(|m| (m.start(), m.end())))
+        .collect()
+}
+
+/// Detect the first OTP code in text with the built-in patterns.
+///
+/// Performance: <1ms per message on average hardware.
+pub fn detect_otp(text: &str) -> Option<OtpDetection> {
+    OtpDetector::default().detect(text)
+}
+
+/// Detect every OTP code in text with the built-in patterns.
+pub fn detect_all_otps(text: &str) -> Vec<OtpDetection> {
+    OtpDetector::default().detect_all(text)
+}
+
+/// How long a message says its code is valid for, in seconds.
+pub fn stated_expiry_secs(text: &str) -> Option<u64> {
+    let caps = EXPIRY.captures(text)?;
+    let amount: u64 = caps[1].parse().ok()?;
+    let unit = caps[2].to_ascii_lowercase();
+    let scale = if unit.starts_with('h') {
+        3600
+    } else if unit.starts_with('m') {
+        60
+    } else {
+        1
+    };
+    Some(amount * scale)
+}
+
+/// A code found in a received message.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct OtpCode {
+    pub message_guid: String,
+    pub chat_guid: String,
+    pub code: String,
+    pub pattern: OtpPattern,
+    /// Sender's contact name, or their address.
+    pub sender: Option<String>,
+    /// When the code stops being valid, in epoch milliseconds.
+    pub expires_at: i64,
+}
+
+/// Detects one-time codes in received messages.
+pub struct OtpService {
+    state: ServiceState,
+    database: Database,
+    event_bus: EventBus,
+}
+
+impl OtpService {
+    /// Create a new OtpService.
+    pub fn new(database: Database, event_bus: EventBus) -> Self {
+        Self {
+            state: ServiceState::Created,
+            database,
+            event_bus,
+        }
+    }
+
+    /// Whether OTP detection is on (the default).
+    pub fn is_enabled(&self) -> BbResult<bool> {
+        let conn = self.database.conn()?;
+        Ok(Settings::get_bool(&conn, keys::OTP_DETECTION_ENABLED)?.unwrap_or(true))
+    }
+
+    /// Current OTP settings.
+    pub fn settings(&self) -> BbResult<OtpSettings> {
+        let conn = self.database.conn()?;
+        OtpSettings::load(&conn)
+    }
+
+    /// Save OTP settings after checking the custom patterns compile.
+    pub fn set_settings(&self, settings: &OtpSettings) -> BbResult<()> {
+        OtpDetector::new(&settings.custom_patterns)?;
+        let conn = self.database.conn()?;
+        settings.save(&conn)?;
+        info!(
+            "otp settings saved: {} custom patterns, {} allowed senders",
+            settings.custom_patterns.len(),
+            settings.allowed_senders.len()
+        );
+        Ok(())
+    }
+
+    /// Look for a code in a received message and emit `OtpDetected` when
+    /// one is found. Returns `None` when detection is off, the message is
+    /// your own, or the sender is not allowed.
+    pub fn check_message(&self, message_guid: &str) -> BbResult<Option<OtpCode>> {
+        if !self.is_enabled()? {
+            debug!("otp detection is disabled");
+            return Ok(None);
+        }
+        let conn = self.database.conn()?;
+        let Some(message) = Message::find_by_guid(&conn, message_guid)? else {
+            return Ok(None);
+        };
+        let settings = OtpSettings::load(&conn)?;
+        let detector = OtpDetector::new(&settings.custom_patterns)?;
+
+        let otp = self.code_in(&conn, &detector, &settings, &message)?;
+        if let Some(otp) = &otp {
+            info!("otp detected in message {message_guid} ({:?})", otp.pattern);
+            self.event_bus.emit(AppEvent::OtpDetected {
+                message_guid: otp.message_guid.clone(),
+                chat_guid: otp.chat_guid.clone(),
+                code: otp.code.clone(),
+                sender: otp.sender.clone(),
+                expires_at: otp.expires_at,
+            });
+        }
+        Ok(otp)
+    }
+
+    /// Check a `MessageReceived` event. Other events are ignored.
+    pub fn process_event(&self, event: &AppEvent) -> BbResult<Option<OtpCode>> {
+        match event {
+            AppEvent::MessageReceived { message_guid, is_from_me: false, .. } => self.check_message(message_guid),
+            _ => Ok(None),
+        }
+    }
+
+    /// Codes still valid at `now_ms` among the latest received messages,
+    /// newest first, optionally in one chat. Does not emit events.
+    pub fn recent_codes(&self, chat_guid: Option<&str>, now_ms: i64) -> BbResult<Vec<OtpCode>> {
+        if !self.is_enabled()? {
+            return Ok(Vec::new());
+        }
+        let conn = self.database.conn()?;
+        let chat_id = match chat_guid {
+            Some(guid) => Some(
+                Chat::find_by_guid(&conn, guid)?
+                    .and_then(|c| c.id)
+                    .ok_or_else(|| BbError::ChatNotFound(guid.to_string()))?,
+            ),
+            None => None,
+        };
+        let settings = OtpSettings::load(&conn)?;
+        let detector = OtpDetector::new(&settings.custom_patterns)?;
+
+        let mut codes = Vec::new();
+        for message in queries::recent_incoming_texts(&conn, chat_id, RECENT_MESSAGE_LIMIT)? {
+            if let Some(otp) = self.code_in(&conn, &detector, &settings, &message)? {
+                if otp.expires_at > now_ms {
+                    codes.push(otp);
+                }
+            }
+        }
+        Ok(codes)
+    }
+
+    fn code_in(
+        &self,
+        conn: &rusqlite::Connection,
+        detector: &OtpDetector,
+        settings: &OtpSettings,
+        message: &Message,
+    ) -> BbResult<Option<OtpCode>> {
+        if message.is_from_me {
+            return Ok(None);
+        }
+        let Some(text) = message.text.as_deref().filter(|t| !t.trim().is_empty()) else {
+            return Ok(None);
+        };
+
+        let handle = match message.handle_id {
+            Some(id) => Handle::find_by_id(conn, id)?,
+            None => None,
+        };
+        let contact_name = match handle.as_ref().and_then(|h| h.contact_id) {
+            Some(id) => Contact::find_by_id(conn, id)?.map(|c| c.display_name),
+            None => None,
+        };
+        if !settings.allowed_senders.is_empty() {
+            let allowed = settings.allowed_senders.iter().any(|allowed| {
+                handle.as_ref().is_some_and(|h| same_address(allowed, &h.address))
+                    || contact_name.as_ref().is_some_and(|n| n.eq_ignore_ascii_case(allowed.trim()))
+            });
+            if !allowed {
+                debug!("otp check skipped: sender not allowed");
+                return Ok(None);
+            }
+        }
+
+        let Some(detection) = detector.detect(text) else {
+            return Ok(None);
+        };
+        let chat_guid = match message.chat_id {
+            Some(id) => queries::find_chat_by_id(conn, id)?.map(|c| c.guid),
+            None => None,
+        };
+        let received_at = message.date_created.as_deref().and_then(timestamp_ms)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+        let expiry_secs = stated_expiry_secs(text).unwrap_or(settings.default_expiry_secs);
+
+        Ok(Some(OtpCode {
+            message_guid: message.guid.clone().unwrap_or_default(),
+            chat_guid: chat_guid.unwrap_or_default(),
+            code: detection.code,
+            pattern: detection.pattern,
+            sender: contact_name.or_else(|| handle.map(|h| h.address)),
+            expires_at: received_at + expiry_secs as i64 * 1000,
+        }))
+    }
+
+    /// Check every `MessageReceived` event on the bus for codes.
+    pub fn start_listener(service: Arc<OtpService>) -> tokio::task::JoinHandle<()> {
+        let mut events = service.event_bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = service.process_event(&event) {
+                            warn!("otp detection failed: {e}");
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("otp listener missed {n} events");
+                    }
+                    Err(_) => break,
+                }
+            }
+        })
+    }
+}
+
+impl Service for OtpService {
+    fn name(&self) -> &str {
+        "otp"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("otp service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("otp service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_with_prefix() {
+        let detection = detect_otp("Your verification code is 123456").unwrap();
+        assert_eq!(detection.code, "123456");
+        assert_eq!(detection.pattern, OtpPattern::CodeWithPrefix);
+    }
+
+    #[test]
+    fn test_code_with_suffix() {
+        let detection = detect_otp("123456 is your verification code").unwrap();
+        assert_eq!(detection.code, "123456");
+        assert_eq!(detection.pattern, OtpPattern::CodeWithSuffix);
+    }
+
+    #[test]
+    fn test_vendor_formats() {
+        let apple = detect_otp("Your Apple ID Code is: 654321. Don't share it with anyone.").unwrap();
+        assert_eq!((apple.code.as_str(), apple.pattern), ("654321", OtpPattern::AppleFormat));
+        let google = detect_otp("G-123456 is your Google verification code").unwrap();
+        assert_eq!((google.code.as_str(), google.pattern), ("123456", OtpPattern::GoogleFormat));
+    }
+
+    #[test]
+    fn test_bracketed_and_standalone_codes() {
+        let bracketed = detect_otp("Use code (987654) to sign in").unwrap();
+        assert_eq!((bracketed.code.as_str(), bracketed.pattern), ("987654", OtpPattern::BracketedCode));
+        assert_eq!(detect_otp("Your security code: 4567").unwrap().code, "4567");
+        assert_eq!(detect_otp("Use 987654 to verify your phone number.").unwrap().code, "987654");
+        assert_eq!(detect_otp("Your one-time password is 112233").unwrap().code, "112233");
+        assert_eq!(detect_otp("YOUR VERIFICATION CODE IS 456789").unwrap().code, "456789");
+    }
+
+    #[test]
+    fn test_alphanumeric_and_split_codes() {
+        assert_eq!(detect_otp("Your login code is K7Q2MD").unwrap().code, "K7Q2MD");
+        assert_eq!(detect_otp("Your code is 123-456").unwrap().code, "123456");
+        assert_eq!(detect_otp("Use code [AB12] to sign in").unwrap().code, "AB12");
+        // Words are not codes: letters need a digit, and lowercase never counts.
+        assert!(detect_otp("Your code is READY").is_none());
+        assert!(detect_otp("the code is ab12cd").is_none());
+    }
+
+    #[test]
+    fn test_various_lengths() {
+        assert_eq!(detect_otp("Code: 1234").unwrap().code, "1234");
+        assert_eq!(detect_otp("Code: 12345678").unwrap().code, "12345678");
+        assert!(detect_otp("Code: 123").is_none());
+        assert!(detect_otp("Code: 123456789").is_none());
+        assert!(detect_otp("").is_none());
+        assert!(detect_otp("Hey, how are you doing today?").is_none());
+    }
+
+    #[test]
+    fn test_dates_are_not_codes() {
+        assert!(detect_otp("The meeting is on 12/2024").is_none());
+        assert!(detect_otp("Enter the date 12/15/2024 on the form").is_none());
+        assert!(detect_otp("Your PIN reminder: it expires 2025-03-31").is_none());
+        assert!(detect_otp("Use the 2024 edition of the code book").is_none());
+    }
+
+    #[test]
+    fn test_amounts_are_not_codes() {
+        assert!(detect_otp("Use your card: a payment of $1250.00 was made").is_none());
+        assert!(detect_otp("Enter 1500 USD as the amount").is_none());
+        assert!(detect_otp("Use code SAVE10 for 2500 points").is_none());
+        assert!(detect_otp("Verify the charge of €3450").is_none());
+    }
+
+    #[test]
+    fn test_phone_numbers_are_not_codes() {
+        assert!(detect_otp("Call me at 555-1234 tomorrow").is_none());
+        assert!(detect_otp("Call (555) 123-4567 to verify your account").is_none());
+        assert!(detect_otp("To verify, call +1 555 123 4567").is_none());
+        assert!(detect_otp("Enter 555.123.4567 as your contact number").is_none());
+    }
+
+    #[test]
+    fn test_code_next_to_a_date() {
+        let detection = detect_otp("Your code is 482913. Requested on 03/14/2025").unwrap();
+        assert_eq!(detection.code, "482913");
+    }
+
+    #[test]
+    fn test_multiple_codes() {
+        let detections = detect_all_otps("Your code is 123456. Backup code: 789012");
+        let codes: Vec<_> = detections.iter().map(|d| d.code.as_str()).collect();
+        assert_eq!(codes, vec!["123456", "789012"]);
+    }
+
+    #[test]
+    fn test_custom_patterns() {
+        let detector = OtpDetector::new(&["(?i)ref no\\. (\\w{3}-\\w{3})".to_string()]).unwrap();
+        let detection = detector.detect("Bank ref no. x9k-2mq").unwrap();
+        assert_eq!((detection.code.as_str(), detection.pattern), ("x9k2mq", OtpPattern::Custom));
+        assert!(OtpDetector::new(&["(unclosed".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_stated_expiry() {
+        assert_eq!(stated_expiry_secs("Code 1234, valid for 10 minutes."), Some(600));
+        assert_eq!(stated_expiry_secs("It expires in 30 secs"), Some(30));
+        assert_eq!(stated_expiry_secs("Good for 1 hour"), Some(3600));
+        assert_eq!(stated_expiry_secs("Your code is 1234"), None);
+    }
+
+    #[test]
+    fn test_performance() {
+        let messages = [
+            "Your verification code is 123456",
+            "G-654321 is your Google verification code",
+            "Hey, how are you doing? Let's meet at 5pm tomorrow.",
+            "The meeting is scheduled for 12/15/2024 at 3pm",
+            "Call me at 555-1234 when you get this message",
+            "Your one-time password is 998877. Valid for 5 minutes.",
+        ];
+        let iterations = 500;
+        let start = std::time::Instant::now();
+        for _ in 0..iterations {
+            for msg in &messages {
+                let _ = detect_otp(msg);
+            }
+        }
+        let avg = start.elapsed() / (iterations * messages.len() as u32);
+        assert!(avg.as_micros() < 1000, "detection took {avg:?} per message");
+    }
+
+    fn otp_db() -> Database {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        std::mem::forget(dir);
+        db.conn()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO contacts (id, display_name) VALUES (1, 'Bank');
+                 INSERT INTO handles (id, address, unique_address_service, contact_id)
+                     VALUES (1, '22395', '22395/SMS', 1), (2, '+15551230002', '+15551230002/iMessage', NULL);
+                 INSERT INTO chats (id, guid, chat_identifier) VALUES (1, 'bank', '22395'), (2, 'friend', 'friend');
+                 INSERT INTO messages (guid, chat_id, handle_id, is_from_me, text, date_created) VALUES
+                     ('m-bank', 1, 1, 0, 'Your code is 482913. Valid for 5 minutes.', '1700000000000'),
+                     ('m-friend', 2, 2, 0, 'my code is 111222 lol', '2023-11-14T22:13:20Z'),
+                     ('m-mine', 2, NULL, 1, 'Your code is 999888', '1700000000000');",
+            )
+            .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_check_message_emits_otp_detected() {
+        let bus = EventBus::new(16);
+        let mut rx = bus.subscribe();
+        let service = OtpService::new(otp_db(), bus);
+
+        let otp = service.check_message("m-bank").unwrap().unwrap();
+        assert_eq!(otp.code, "482913");
+        assert_eq!(otp.chat_guid, "bank");
+        assert_eq!(otp.sender.as_deref(), Some("Bank"));
+        assert_eq!(otp.expires_at, 1_700_000_000_000 + 300_000);
+        match rx.recv().await.unwrap() {
+            AppEvent::OtpDetected { code, sender, expires_at, .. } => {
+                assert_eq!(code, "482913");
+                assert_eq!(sender.as_deref(), Some("Bank"));
+                assert_eq!(expires_at, otp.expires_at);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        let mine = AppEvent::MessageReceived { message_guid: "m-mine".into(), chat_guid: "friend".into(), is_from_me: true };
+        assert!(service.process_event(&mine).unwrap().is_none());
+        assert!(service.check_message("m-mine").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_allowlist_and_settings() {
+        let service = OtpService::new(otp_db(), EventBus::new(16));
+        let friend = service.check_message("m-friend").unwrap().unwrap();
+        assert_eq!(friend.sender.as_deref(), Some("+15551230002"));
+        assert_eq!(friend.expires_at, 1_700_000_000_000 + 600_000);
+
+        let settings = OtpSettings { allowed_senders: vec!["bank".into()], ..Default::default() };
+        service.set_settings(&settings).unwrap();
+        assert!(service.check_message("m-friend").unwrap().is_none());
+        assert!(service.check_message("m-bank").unwrap().is_some());
+
+        let settings = OtpSettings { allowed_senders: vec!["(555) 123-0002".into()], ..Default::default() };
+        service.set_settings(&settings).unwrap();
+        assert!(service.check_message("m-friend").unwrap().is_some());
+
+        let bad = OtpSettings { custom_patterns: vec!["[".into()], ..Default::default() };
+        assert!(service.set_settings(&bad).is_err());
+
+        let codes = service.recent_codes(None, 1_700_000_100_000).unwrap();
+        assert_eq!(codes.iter().map(|c| c.message_guid.as_str()).collect::<Vec<_>>(), vec!["m-friend"]);
+        assert!(service.recent_codes(None, 1_700_001_000_000).unwrap().is_empty());
+
+        Settings::set_bool(&service.database.conn().unwrap(), keys::OTP_DETECTION_ENABLED, false).unwrap();
+        assert!(service.check_message("m-friend").unwrap().is_none());
+    }
+}
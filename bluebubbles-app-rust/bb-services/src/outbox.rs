@@ -0,0 +1,397 @@
+//! Outbox service: chat changes that survive being offline.
+//!
+//! Chat mutations (mark read/unread, rename, participant changes, delete)
+//! are applied to the local database at once and queued in the `outbox`
+//! table. The queue is replayed in order whenever the server is reachable:
+//! right after a change is queued, and again on every
+//! `ConnectionStateChanged { connected: true }`. A network failure leaves the
+//! entry queued for the next replay; a rejection by the server rolls the
+//! local change back and emits `OutboxMutationFailed`.
+//!
+//! Pin and archive state is not sent to the server (it has no endpoint for
+//! it), so those changes stay local and never enter the outbox.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, warn, debug};
+
+use bb_core::error::{BbError, BbResult};
+use bb_api::ApiClient;
+use bb_models::{ChatMutation, Database, OutboxEntry};
+
+use crate::event_bus::{AppEvent, EventBus};
+use crate::service::{Service, ServiceState};
+
+/// Serializes replays so two triggers never send the same entry twice.
+static REPLAY_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// What a replay did.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ReplayReport {
+    /// Entries the server accepted.
+    pub applied: usize,
+    /// Entries the server rejected and that were rolled back.
+    pub rolled_back: usize,
+    /// Entries still queued (the server could not be reached).
+    pub remaining: usize,
+}
+
+/// Outcome of submitting a single mutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmitStatus {
+    /// The server accepted the change.
+    Sent,
+    /// The server could not be reached; the change is queued.
+    Queued,
+}
+
+/// Whether a failed server call is worth retrying later, as opposed to a
+/// rejection that will fail the same way every time.
+pub fn is_transient(error: &BbError) -> bool {
+    match error {
+        BbError::Http(_)
+        | BbError::Timeout(_)
+        | BbError::Socket(_)
+        | BbError::SocketDisconnected
+        | BbError::AuthFailed(_)
+        | BbError::MissingConfig(_) => true,
+        BbError::ServerError { status, .. } => *status >= 500 || *status == 408 || *status == 429,
+        _ => false,
+    }
+}
+
+/// Service owning the persistent outbox of chat mutations.
+pub struct OutboxService {
+    state: ServiceState,
+    database: Database,
+    event_bus: EventBus,
+}
+
+impl OutboxService {
+    /// Create a new OutboxService.
+    pub fn new(database: Database, event_bus: EventBus) -> Self {
+        Self {
+            state: ServiceState::Created,
+            database,
+            event_bus,
+        }
+    }
+
+    /// Apply a mutation locally and queue it for the server.
+    pub fn enqueue(&self, chat_guid: &str, mutation: ChatMutation) -> BbResult<OutboxEntry> {
+        let mut conn = self.database.conn()?;
+        let entry = OutboxEntry::enqueue(&mut conn, chat_guid, mutation)?;
+        debug!("queued chat mutation {}: {}", chat_guid, entry.mutation.describe());
+        self.emit_applied(&entry);
+        Ok(entry)
+    }
+
+    /// Queue a mutation and replay the outbox. Returns an error if the
+    /// server rejected this mutation (its local change is rolled back),
+    /// otherwise whether it was sent or is waiting for a connection.
+    pub async fn submit(
+        &self,
+        api: &ApiClient,
+        chat_guid: &str,
+        mutation: ChatMutation,
+    ) -> BbResult<SubmitStatus> {
+        let entry = self.enqueue(chat_guid, mutation)?;
+        let id = entry.id;
+        let mut rejected = None;
+        self.replay_with(
+            |e| async move { (e.id == id, send(api, &e).await) },
+            |is_ours, error| {
+                if is_ours {
+                    rejected = Some(error);
+                }
+            },
+        )
+        .await?;
+
+        if let Some(error) = rejected {
+            return Err(error);
+        }
+        let conn = self.database.conn()?;
+        let queued = OutboxEntry::pending(&conn)?.iter().any(|e| e.id == id);
+        Ok(if queued { SubmitStatus::Queued } else { SubmitStatus::Sent })
+    }
+
+    /// Pending mutations, oldest first.
+    pub fn pending(&self) -> BbResult<Vec<OutboxEntry>> {
+        let conn = self.database.conn()?;
+        OutboxEntry::pending(&conn)
+    }
+
+    /// Number of pending mutations.
+    pub fn pending_count(&self) -> BbResult<i64> {
+        let conn = self.database.conn()?;
+        OutboxEntry::count(&conn)
+    }
+
+    /// Send pending mutations to the server in order.
+    pub async fn replay(&self, api: &ApiClient) -> BbResult<ReplayReport> {
+        self.replay_with(|e| async move { ((), send(api, &e).await) }, |_, _| {}).await
+    }
+
+    /// Replay the outbox through `send`. Stops at the first transient
+    /// failure so later mutations never overtake earlier ones; entries the
+    /// server rejects are rolled back and reported through `on_reject`.
+    async fn replay_with<T, F, Fut>(
+        &self,
+        mut send: F,
+        mut on_reject: impl FnMut(T, BbError),
+    ) -> BbResult<ReplayReport>
+    where
+        F: FnMut(OutboxEntry) -> Fut,
+        Fut: Future<Output = (T, BbResult<()>)>,
+    {
+        let _guard = REPLAY_LOCK.lock().await;
+        let mut report = ReplayReport::default();
+        let entries = self.pending()?;
+
+        for (index, entry) in entries.iter().enumerate() {
+            let id = entry.id.unwrap_or_default();
+            let (tag, result) = send(entry.clone()).await;
+            match result {
+                Ok(()) => {
+                    let conn = self.database.conn()?;
+                    OutboxEntry::complete(&conn, id)?;
+                    report.applied += 1;
+                }
+                Err(e) if is_transient(&e) => {
+                    let conn = self.database.conn()?;
+                    OutboxEntry::record_failure(&conn, id, &e.to_string())?;
+                    debug!("outbox paused at {id}: {e}");
+                    report.remaining = entries.len() - index;
+                    break;
+                }
+                Err(e) => {
+                    warn!(
+                        "server rejected chat mutation {} ({}): {e}",
+                        entry.chat_guid,
+                        entry.mutation.describe()
+                    );
+                    let mut conn = self.database.conn()?;
+                    entry.roll_back(&mut conn)?;
+                    report.rolled_back += 1;
+                    self.event_bus.emit(AppEvent::ChatUpdated {
+                        chat_guid: entry.chat_guid.clone(),
+                    });
+                    self.event_bus.emit(AppEvent::OutboxMutationFailed {
+                        chat_guid: entry.chat_guid.clone(),
+                        description: entry.mutation.describe(),
+                        error: e.to_string(),
+                    });
+                    on_reject(tag, e);
+                }
+            }
+        }
+
+        if report.applied + report.rolled_back > 0 {
+            info!(
+                "outbox replay: {} applied, {} rolled back, {} remaining",
+                report.applied, report.rolled_back, report.remaining
+            );
+        }
+        Ok(report)
+    }
+
+    /// Replay the outbox every time the connection comes back. The API
+    /// client is read from `api` at replay time, since it may be replaced
+    /// when the server address changes.
+    pub fn start_replayer(
+        service: Arc<OutboxService>,
+        api: Arc<RwLock<Option<ApiClient>>>,
+    ) -> tokio::task::JoinHandle<()> {
+        let mut rx = service.event_bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(AppEvent::ConnectionStateChanged { connected: true, .. }) => {
+                        let Some(client) = api.read().await.clone() else {
+                            continue;
+                        };
+                        if let Err(e) = service.replay(&client).await {
+                            warn!("outbox replay failed: {e}");
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Emit the event for a mutation just applied locally.
+    fn emit_applied(&self, entry: &OutboxEntry) {
+        let chat_guid = entry.chat_guid.clone();
+        let event = match &entry.mutation {
+            ChatMutation::MarkRead | ChatMutation::MarkUnread => AppEvent::ChatUpdated { chat_guid },
+            ChatMutation::Rename { name } => AppEvent::GroupNameChanged {
+                chat_guid,
+                new_name: name.clone(),
+            },
+            ChatMutation::AddParticipant { address } => AppEvent::ParticipantAdded {
+                chat_guid,
+                address: address.clone(),
+            },
+            ChatMutation::RemoveParticipant { address } => AppEvent::ParticipantRemoved {
+                chat_guid,
+                address: address.clone(),
+            },
+            ChatMutation::Delete => AppEvent::ChatDeleted { chat_guid },
+        };
+        self.event_bus.emit(event);
+    }
+}
+
+/// Send one mutation to the server.
+async fn send(api: &ApiClient, entry: &OutboxEntry) -> BbResult<()> {
+    let guid = entry.chat_guid.as_str();
+    match &entry.mutation {
+        ChatMutation::MarkRead => api.mark_chat_read(guid).await,
+        ChatMutation::MarkUnread => api.mark_chat_unread(guid).await,
+        ChatMutation::Rename { name } => api.update_chat(guid, name).await.map(|_| ()),
+        ChatMutation::AddParticipant { address } => api.add_participant(guid, address).await.map(|_| ()),
+        ChatMutation::RemoveParticipant { address } => api.remove_participant(guid, address).await.map(|_| ()),
+        ChatMutation::Delete => api.delete_chat(guid).await,
+    }
+}
+
+impl Service for OutboxService {
+    fn name(&self) -> &str {
+        "outbox"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("outbox service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("outbox service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (OutboxService, EventBus) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        std::mem::forget(dir);
+        db.conn()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO chats (id, guid, display_name, has_unread_message) VALUES
+                    (1, 'chat-1', 'Old', 1), (2, 'chat-2', NULL, 1);",
+            )
+            .unwrap();
+        let bus = EventBus::new(64);
+        (OutboxService::new(db, bus.clone()), bus)
+    }
+
+    fn chat_row(svc: &OutboxService, guid: &str) -> (bool, Option<String>) {
+        svc.database
+            .conn()
+            .unwrap()
+            .query_row(
+                "SELECT has_unread_message, display_name FROM chats WHERE guid = ?1",
+                [guid],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay_stops_at_network_failure_and_keeps_order() {
+        let (svc, _bus) = setup();
+        svc.enqueue("chat-1", ChatMutation::MarkRead).unwrap();
+        svc.enqueue("chat-2", ChatMutation::MarkRead).unwrap();
+        assert!(!chat_row(&svc, "chat-1").0);
+
+        let offline = svc
+            .replay_with(
+                |_| async { ((), Err(BbError::Http("connection failed".into()))) },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(offline, ReplayReport { applied: 0, rolled_back: 0, remaining: 2 });
+        let pending = svc.pending().unwrap();
+        assert_eq!(pending[0].attempts, 1);
+        assert_eq!(pending[1].attempts, 0);
+
+        let mut sent = Vec::new();
+        let online = svc
+            .replay_with(
+                |e| {
+                    sent.push(e.chat_guid.clone());
+                    async { ((), Ok(())) }
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(online.applied, 2);
+        assert_eq!(sent, vec!["chat-1", "chat-2"]);
+        assert_eq!(svc.pending_count().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_mutation_is_rolled_back_with_event() {
+        let (svc, bus) = setup();
+        let mut rx = bus.subscribe();
+        svc.enqueue("chat-1", ChatMutation::Rename { name: "New".into() }).unwrap();
+        svc.enqueue("chat-2", ChatMutation::MarkRead).unwrap();
+        assert_eq!(chat_row(&svc, "chat-1").1.as_deref(), Some("New"));
+
+        let mut rejected = Vec::new();
+        let report = svc
+            .replay_with(
+                |e| async move {
+                    let result = match e.mutation {
+                        ChatMutation::Rename { .. } => Err(BbError::ServerError {
+                            status: 400,
+                            message: "not a group".into(),
+                        }),
+                        _ => Ok(()),
+                    };
+                    (e.chat_guid, result)
+                },
+                |guid, _| rejected.push(guid),
+            )
+            .await
+            .unwrap();
+        assert_eq!(report, ReplayReport { applied: 1, rolled_back: 1, remaining: 0 });
+        assert_eq!(rejected, vec!["chat-1"]);
+        assert_eq!(chat_row(&svc, "chat-1").1.as_deref(), Some("Old"));
+        assert!(!chat_row(&svc, "chat-2").0);
+
+        let mut failed = None;
+        while let Ok(event) = rx.try_recv() {
+            if let AppEvent::OutboxMutationFailed { chat_guid, description, .. } = event {
+                failed = Some((chat_guid, description));
+            }
+        }
+        assert_eq!(failed, Some(("chat-1".into(), "rename to \"New\"".into())));
+    }
+
+    #[test]
+    fn test_error_classification() {
+        assert!(is_transient(&BbError::Timeout("t".into())));
+        assert!(is_transient(&BbError::ServerError { status: 503, message: String::new() }));
+        assert!(!is_transient(&BbError::ServerError { status: 404, message: String::new() }));
+        assert!(!is_transient(&BbError::ChatNotFound("c".into())));
+    }
+}
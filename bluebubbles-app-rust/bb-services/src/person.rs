@@ -0,0 +1,285 @@
+//! Person service: one identity per human across their handles.
+//!
+//! A friend typically appears as several `Handle` rows (a number on
+//! iMessage, the same number on SMS, a couple of emails). Persons group
+//! them: automatically through shared contacts and normalized addresses,
+//! and manually through merge and split, which pin the links so automatic
+//! relinking leaves them alone.
+
+use rusqlite::Connection;
+use serde::Serialize;
+use tracing::{info, debug};
+
+use bb_core::error::{BbError, BbResult};
+use bb_models::{Chat, Contact, Database, Handle, Message, Person};
+use bb_models::models::person;
+use bb_models::queries;
+
+use crate::event_bus::{AppEvent, EventBus};
+use crate::service::{Service, ServiceState};
+
+/// A person with their resolved name and linked handles.
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonSummary {
+    pub id: i64,
+    /// User-set name, contact name, or the first handle's address.
+    pub display_name: String,
+    /// Name set by the user, if any.
+    pub name: Option<String>,
+    /// Contact of the first linked handle that has one.
+    pub contact_id: Option<i64>,
+    pub handles: Vec<Handle>,
+}
+
+impl PersonSummary {
+    /// Whether any handle of this person has the given address.
+    pub fn has_address(&self, address: &str) -> bool {
+        let normalized = bb_models::phone::normalize(address, None);
+        self.handles.iter().any(|h| {
+            h.address.eq_ignore_ascii_case(address.trim())
+                || (normalized.is_some() && h.normalized_address == normalized)
+        })
+    }
+}
+
+/// Service for the person identity layer.
+pub struct PersonService {
+    state: ServiceState,
+    database: Database,
+    event_bus: EventBus,
+}
+
+impl PersonService {
+    /// Create a new PersonService.
+    pub fn new(database: Database, event_bus: EventBus) -> Self {
+        Self {
+            state: ServiceState::Created,
+            database,
+            event_bus,
+        }
+    }
+
+    /// Refresh automatic links between handles and persons. Manual links
+    /// are kept. Returns the number of links created or changed.
+    pub fn relink(&self) -> BbResult<u32> {
+        let conn = self.database.conn()?;
+        let changed = person::link_persons(&conn)?;
+        if changed > 0 {
+            debug!("relinked {changed} handles to persons");
+            self.event_bus.emit(AppEvent::PersonsUpdated { person_ids: Vec::new() });
+        }
+        Ok(changed)
+    }
+
+    /// List all persons, by display name.
+    pub fn list_people(&self) -> BbResult<Vec<PersonSummary>> {
+        let conn = self.database.conn()?;
+        let mut people = Vec::new();
+        for p in Person::list(&conn)? {
+            people.push(summarize(&conn, p)?);
+        }
+        people.sort_by_key(|p| p.display_name.to_lowercase());
+        Ok(people)
+    }
+
+    /// Load one person.
+    pub fn get_person(&self, id: i64) -> BbResult<Option<PersonSummary>> {
+        let conn = self.database.conn()?;
+        match Person::find_by_id(&conn, id)? {
+            Some(p) => Ok(Some(summarize(&conn, p)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The person a handle belongs to.
+    pub fn person_for_handle(&self, handle_id: i64) -> BbResult<Option<PersonSummary>> {
+        let conn = self.database.conn()?;
+        match Person::find_by_handle(&conn, handle_id)? {
+            Some(p) => Ok(Some(summarize(&conn, p)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The person owning an address on any service. Phone numbers match
+    /// on their E.164 form.
+    pub fn find_by_address(&self, address: &str) -> BbResult<Option<PersonSummary>> {
+        Ok(self.list_people()?.into_iter().find(|p| p.has_address(address)))
+    }
+
+    /// Persons whose name or any handle address contains `query`.
+    pub fn search(&self, query: &str, limit: usize) -> BbResult<Vec<PersonSummary>> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let digits: String = query.chars().filter(|c| c.is_ascii_digit()).collect();
+        Ok(self
+            .list_people()?
+            .into_iter()
+            .filter(|p| {
+                p.display_name.to_lowercase().contains(&query)
+                    || p.handles.iter().any(|h| {
+                        h.address.to_lowercase().contains(&query)
+                            || (digits.len() >= 4
+                                && h.normalized_address.as_deref().is_some_and(|n| n.contains(&digits)))
+                    })
+            })
+            .take(limit)
+            .collect())
+    }
+
+    /// Set or clear the user-chosen name of a person.
+    pub fn rename(&self, id: i64, name: Option<&str>) -> BbResult<PersonSummary> {
+        let conn = self.database.conn()?;
+        let mut p = find(&conn, id)?;
+        p.name = name.map(str::trim).filter(|n| !n.is_empty()).map(String::from);
+        p.save(&conn)?;
+        self.event_bus.emit(AppEvent::PersonsUpdated { person_ids: vec![id] });
+        summarize(&conn, p)
+    }
+
+    /// Merge `source` into `target`. Returns the merged person.
+    pub fn merge(&self, target: i64, source: i64) -> BbResult<PersonSummary> {
+        let conn = self.database.conn()?;
+        Person::merge(&conn, target, source)?;
+        info!("merged person {source} into {target}");
+        self.event_bus.emit(AppEvent::PersonsUpdated { person_ids: vec![target, source] });
+        summarize(&conn, find(&conn, target)?)
+    }
+
+    /// Move a handle out into a person of its own. Returns the new person.
+    pub fn split(&self, handle_id: i64) -> BbResult<PersonSummary> {
+        let conn = self.database.conn()?;
+        let previous = Person::find_by_handle(&conn, handle_id)?.and_then(|p| p.id);
+        let p = Person::split(&conn, handle_id)?;
+        info!("split handle {handle_id} into person {:?}", p.id);
+        self.event_bus.emit(AppEvent::PersonsUpdated {
+            person_ids: previous.into_iter().chain(p.id).collect(),
+        });
+        summarize(&conn, p)
+    }
+
+    /// One-to-one chats with this person on any of their handles, most
+    /// recent first.
+    pub fn chats(&self, id: i64) -> BbResult<Vec<Chat>> {
+        let conn = self.database.conn()?;
+        find(&conn, id)?;
+        let mut chats = queries::person_chats(&conn, id)?;
+        for chat in &mut chats {
+            if let Some(chat_id) = chat.id {
+                chat.participants = queries::load_chat_participants(&conn, chat_id)?;
+            }
+        }
+        Ok(chats)
+    }
+
+    /// Search messages exchanged with this person.
+    pub fn search_messages(&self, id: i64, query: &str, limit: usize) -> BbResult<Vec<Message>> {
+        let conn = self.database.conn()?;
+        find(&conn, id)?;
+        queries::search_person_messages(&conn, id, query, limit as i64)
+    }
+}
+
+fn find(conn: &Connection, id: i64) -> BbResult<Person> {
+    Person::find_by_id(conn, id)?
+        .ok_or_else(|| BbError::InvalidInput(format!("person {id} not found")))
+}
+
+fn summarize(conn: &Connection, p: Person) -> BbResult<PersonSummary> {
+    let id = p.id.unwrap_or_default();
+    let handles = Person::handles(conn, id)?;
+    let contact_id = handles.iter().find_map(|h| h.contact_id);
+    let contact_name = match contact_id {
+        Some(cid) => Contact::find_by_id(conn, cid)?.map(|c| c.display_name),
+        None => None,
+    };
+    let display_name = p
+        .name
+        .clone()
+        .or(contact_name)
+        .or_else(|| handles.first().map(|h| h.display_name()))
+        .unwrap_or_default();
+    Ok(PersonSummary {
+        id,
+        display_name,
+        name: p.name,
+        contact_id,
+        handles,
+    })
+}
+
+impl Service for PersonService {
+    fn name(&self) -> &str {
+        "person"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("person service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("person service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (Database, PersonService) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        std::mem::forget(dir);
+        let svc = PersonService::new(db.clone(), EventBus::new(16));
+        (db, svc)
+    }
+
+    #[test]
+    fn test_people_across_handles() {
+        let (db, svc) = setup();
+        {
+            let conn = db.conn().unwrap();
+            conn.execute("INSERT INTO contacts (id, display_name) VALUES (1, 'Alice')", []).unwrap();
+            for (address, service, contact) in [
+                ("+16502530000", "iMessage", None),
+                ("+16502530000", "SMS", Some(1)),
+                ("alice@example.com", "iMessage", Some(1)),
+                ("bob@example.com", "iMessage", None),
+            ] {
+                let mut h = Handle::from_server_map(&serde_json::json!({
+                    "address": address, "service": service
+                }))
+                .unwrap();
+                h.contact_id = contact;
+                h.save(&conn).unwrap();
+            }
+        }
+        svc.relink().unwrap();
+
+        let people = svc.list_people().unwrap();
+        assert_eq!(people.len(), 2);
+        let alice = svc.find_by_address("(650) 253-0000").unwrap().unwrap();
+        assert_eq!(alice.display_name, "Alice");
+        assert_eq!(alice.handles.len(), 3);
+        assert_eq!(svc.search("bob", 10).unwrap().len(), 1);
+
+        let bob = svc.find_by_address("BOB@example.com").unwrap().unwrap();
+        let merged = svc.merge(alice.id, bob.id).unwrap();
+        assert_eq!(merged.handles.len(), 4);
+        assert!(svc.get_person(bob.id).unwrap().is_none());
+
+        let renamed = svc.rename(alice.id, Some("Al")).unwrap();
+        assert_eq!(renamed.display_name, "Al");
+
+        let bob_handle = merged.handles.iter().find(|h| h.address.starts_with("bob")).unwrap();
+        let split = svc.split(bob_handle.id.unwrap()).unwrap();
+        assert_eq!(split.display_name, "bob@example.com");
+        assert_eq!(svc.get_person(alice.id).unwrap().unwrap().handles.len(), 3);
+    }
+}
@@ -3,19 +3,39 @@
 //! Provides a priority queue for outgoing messages that handles retry logic
 //! with exponential backoff, temp GUID -> real GUID mutation tracking,
 //! error classification for retry decisions, and a background processing loop.
+//!
+//! With a database attached the queue is written through to the
+//! `send_queue` table, so pending sends, retry counts and next-attempt
+//! times survive a restart and are reloaded by `init`.
+//!
+//! Messages composed while disconnected are queued as waiting for the
+//! connection: they are not retried on a timer but flushed, in per-chat
+//! order, once the server is reachable again.
+//!
+//! Several processes (the app, `messages flush`, `scheduled run`) may share
+//! the table. Each re-reads it before flushing or retrying, and claims a row
+//! in SQL before sending it, so only one of them sends each message. A claim
+//! left by a process that died mid-send lapses after `CLAIM_TIMEOUT_MS`.
 
 use std::collections::{VecDeque, HashMap};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use tracing::{info, debug, warn};
 
-use bb_core::error::{BbResult, MessageError};
+use bb_core::error::{BbError, BbResult, MessageError};
+use bb_models::Database;
 use crate::service::{Service, ServiceState};
 
+/// How long a claim on a queued message holds before another process may
+/// take it over.
+const CLAIM_TIMEOUT_MS: i64 = 10 * 60 * 1000;
+
 /// A queued outgoing message awaiting send.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct QueuedMessage {
     /// Unique identifier for this queue entry.
     pub id: String,
@@ -31,9 +51,62 @@ pub struct QueuedMessage {
     pub max_attempts: u32,
     /// Timestamp of the last attempt.
     pub last_attempt: Option<std::time::Instant>,
+    /// Temp GUID sent with every attempt, so a send that reached the
+    /// server before a crash can be recognised instead of sent twice.
+    pub temp_guid: Option<String>,
+    /// MIME type of `file_path`, for attachment messages.
+    pub mime_type: Option<String>,
+    /// When the message was first queued (epoch ms).
+    pub date_created: i64,
+    /// When the first send attempt started (epoch ms). Server messages older
+    /// than this can't be this message.
+    pub first_attempt: Option<i64>,
+    /// Composed while offline; sent when the connection returns rather
+    /// than on the retry timer.
+    pub waiting_for_connection: bool,
 }
 
 impl QueuedMessage {
+    /// A text message to send, with a fresh queue ID and temp GUID.
+    pub fn text(chat_guid: &str, text: &str) -> Self {
+        Self::new(chat_guid, Some(text.to_string()), None, None)
+    }
+
+    /// An attachment to upload, with a fresh queue ID and temp GUID.
+    pub fn attachment(chat_guid: &str, file_path: &str, mime_type: &str) -> Self {
+        Self::new(chat_guid, None, Some(file_path.to_string()), Some(mime_type.to_string()))
+    }
+
+    fn new(chat_guid: &str, text: Option<String>, file_path: Option<String>, mime_type: Option<String>) -> Self {
+        Self {
+            id: format!("q-{}", uuid::Uuid::new_v4()),
+            chat_guid: chat_guid.to_string(),
+            text,
+            file_path,
+            attempts: 0,
+            max_attempts: 5,
+            last_attempt: None,
+            temp_guid: Some(format!("temp-{}", uuid::Uuid::new_v4())),
+            mime_type,
+            date_created: chrono::Utc::now().timestamp_millis(),
+            first_attempt: None,
+            waiting_for_connection: false,
+        }
+    }
+
+    /// Count a send attempt starting now.
+    pub fn record_attempt(&mut self) {
+        self.attempts += 1;
+        self.last_attempt = Some(Instant::now());
+        self.first_attempt.get_or_insert_with(|| chrono::Utc::now().timestamp_millis());
+    }
+
+    /// This message, held until the connection returns.
+    pub fn waiting(mut self) -> Self {
+        self.waiting_for_connection = true;
+        self
+    }
+
     /// Whether this message should be retried.
     pub fn should_retry(&self) -> bool {
         self.attempts < self.max_attempts
@@ -48,10 +121,12 @@ impl QueuedMessage {
     }
 
     /// Whether this message is ready for its next retry attempt.
+    /// Messages waiting for the connection never are; they are flushed.
     pub fn is_ready(&self) -> bool {
-        self.last_attempt.map_or(true, |last| {
-            last.elapsed() >= self.retry_delay()
-        })
+        !self.waiting_for_connection
+            && self.last_attempt.map_or(true, |last| {
+                last.elapsed() >= self.retry_delay()
+            })
     }
 }
 
@@ -66,6 +141,32 @@ pub enum SendStatus {
     Failed { error: String, error_code: MessageError },
 }
 
+/// Where an outgoing message is on its way to the server, as reported by
+/// `AppEvent::MessageStatusChanged`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutgoingStatus {
+    /// Saved locally; will be sent when the connection returns.
+    WaitingForConnection,
+    /// Being sent now.
+    Sending,
+    /// The server accepted it.
+    Sent,
+    /// The server rejected it; it will not be sent again.
+    Failed,
+}
+
+impl OutgoingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WaitingForConnection => "waiting_for_connection",
+            Self::Sending => "sending",
+            Self::Sent => "sent",
+            Self::Failed => "failed",
+        }
+    }
+}
+
 /// Service that manages outgoing message retry queue.
 ///
 /// Provides:
@@ -79,16 +180,64 @@ pub struct QueueService {
     queue: Arc<Mutex<VecDeque<QueuedMessage>>>,
     /// Mapping of temp GUIDs to their send status.
     guid_status: Arc<Mutex<HashMap<String, SendStatus>>>,
+    /// Backing store for the queue, when persistent.
+    database: Option<Database>,
+    /// Identifies this queue's claims on persisted rows.
+    owner: String,
 }
 
 impl QueueService {
-    /// Create a new QueueService.
+    /// Create a new in-memory QueueService.
     pub fn new() -> Self {
         Self {
             state: ServiceState::Created,
             queue: Arc::new(Mutex::new(VecDeque::new())),
             guid_status: Arc::new(Mutex::new(HashMap::new())),
+            database: None,
+            owner: format!("{}-{}", std::process::id(), uuid::Uuid::new_v4()),
+        }
+    }
+
+    /// Persist the queue in the `send_queue` table of `database`.
+    pub fn with_database(mut self, database: Database) -> Self {
+        self.database = Some(database);
+        self
+    }
+
+    /// Re-read the persisted queue, picking up messages other processes
+    /// queued and dropping ones they finished. Returns how many were added.
+    pub async fn reload(&self) -> BbResult<usize> {
+        if self.database.is_none() {
+            return Ok(0);
         }
+        let rows = self.load_rows()?;
+        let mut queue = self.queue.lock().await;
+        let added = rows.iter().filter(|r| !queue.iter().any(|m| m.id == r.id)).count();
+        *queue = rows.into();
+        Ok(added)
+    }
+
+    /// Claim a queued message for sending by this queue. Returns false when
+    /// another process holds a live claim on it or has already finished
+    /// it. Without a database every message can be claimed.
+    pub async fn claim(&self, id: &str) -> BbResult<bool> {
+        self.claim_row(id)
+    }
+
+    /// Give up this queue's claim on a message that stays queued, so any
+    /// process may send it next.
+    pub async fn release(&self, id: &str) -> BbResult<()> {
+        let Some(db) = &self.database else {
+            return Ok(());
+        };
+        db.conn()?
+            .execute(
+                "UPDATE send_queue SET claimed_by = NULL, claimed_at = NULL
+                 WHERE id = ?1 AND claimed_by = ?2",
+                params![id, self.owner],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(())
     }
 
     /// Add a message to the retry queue.
@@ -96,6 +245,10 @@ impl QueueService {
         let id = msg.id.clone();
         let mut queue = self.queue.lock().await;
         info!("queued message for retry: {} (attempt {})", msg.id, msg.attempts);
+        if let Err(e) = self.persist(&msg) {
+            warn!("failed to persist queued message {}: {e}", msg.id);
+        }
+        queue.retain(|m| m.id != msg.id);
         queue.push_back(msg);
 
         // Track status
@@ -103,20 +256,49 @@ impl QueueService {
         status.insert(id, SendStatus::Pending);
     }
 
-    /// Remove and return the next message ready for retry.
+    /// Remove, claim and return the next message ready for retry. Messages
+    /// another process is sending are dropped from memory; `reload` brings
+    /// them back if they stay queued.
     pub async fn dequeue(&self) -> Option<QueuedMessage> {
         let mut queue = self.queue.lock().await;
 
         // Find the first message whose retry delay has elapsed
-        for i in 0..queue.len() {
-            if queue[i].is_ready() {
-                return queue.remove(i);
+        let mut i = 0;
+        while i < queue.len() {
+            if !queue[i].is_ready() {
+                i += 1;
+                continue;
+            }
+            match self.claim_row(&queue[i].id) {
+                Ok(true) => return queue.remove(i),
+                Ok(false) => {
+                    debug!("queued message {} is claimed elsewhere", queue[i].id);
+                    queue.remove(i);
+                }
+                Err(e) => {
+                    warn!("failed to claim queued message {}: {e}", queue[i].id);
+                    i += 1;
+                }
             }
         }
 
         None
     }
 
+    /// Snapshot of every queued message, oldest first.
+    pub async fn pending(&self) -> Vec<QueuedMessage> {
+        self.queue.lock().await.iter().cloned().collect()
+    }
+
+    /// Messages waiting for the connection, oldest first.
+    pub async fn waiting(&self) -> Vec<QueuedMessage> {
+        let queue = self.queue.lock().await;
+        let mut waiting: Vec<QueuedMessage> =
+            queue.iter().filter(|m| m.waiting_for_connection).cloned().collect();
+        waiting.sort_by_key(|m| m.date_created);
+        waiting
+    }
+
     /// Get the current queue length.
     pub async fn len(&self) -> usize {
         self.queue.lock().await.len()
@@ -130,12 +312,13 @@ impl QueueService {
     /// Remove a message from the queue by ID.
     pub async fn remove(&self, id: &str) -> bool {
         let mut queue = self.queue.lock().await;
+        let persisted = self.delete_rows("id = ?1", id).unwrap_or(0) > 0;
         if let Some(pos) = queue.iter().position(|m| m.id == id) {
             queue.remove(pos);
             debug!("removed message from queue: {id}");
             true
         } else {
-            false
+            persisted
         }
     }
 
@@ -144,6 +327,9 @@ impl QueueService {
         let mut queue = self.queue.lock().await;
         let count = queue.len();
         queue.clear();
+        if let Err(e) = self.delete_rows("1 = ?1", "1") {
+            warn!("failed to clear persisted queue: {e}");
+        }
         if count > 0 {
             info!("cleared {count} messages from queue");
         }
@@ -153,6 +339,7 @@ impl QueueService {
     ///
     /// Maps the temp GUID to the real server GUID for UI updates.
     pub async fn mark_sent(&self, temp_guid: &str, real_guid: &str) {
+        self.forget_temp_guid(temp_guid).await;
         let mut status = self.guid_status.lock().await;
         status.insert(
             temp_guid.to_string(),
@@ -165,6 +352,7 @@ impl QueueService {
 
     /// Record that a message send failed permanently.
     pub async fn mark_failed(&self, temp_guid: &str, error: &str, error_code: MessageError) {
+        self.forget_temp_guid(temp_guid).await;
         let mut status = self.guid_status.lock().await;
         status.insert(
             temp_guid.to_string(),
@@ -215,6 +403,93 @@ impl QueueService {
         }
     }
 
+    /// Drop a queued message by its temp GUID once its fate is known.
+    async fn forget_temp_guid(&self, temp_guid: &str) {
+        let mut queue = self.queue.lock().await;
+        queue.retain(|m| m.temp_guid.as_deref() != Some(temp_guid));
+        if let Err(e) = self.delete_rows("temp_guid = ?1", temp_guid) {
+            warn!("failed to remove persisted message {temp_guid}: {e}");
+        }
+    }
+
+    /// Write a message to the backing table, if any.
+    fn persist(&self, msg: &QueuedMessage) -> BbResult<()> {
+        let Some(db) = &self.database else {
+            return Ok(());
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        let next_attempt = match msg.last_attempt {
+            Some(last) => {
+                let remaining = msg.retry_delay().saturating_sub(last.elapsed());
+                now + remaining.as_millis() as i64
+            }
+            None => now,
+        };
+        db.conn()?
+            .execute(
+                "INSERT INTO send_queue
+                    (id, chat_guid, text, file_path, mime_type, temp_guid,
+                     attempts, max_attempts, next_attempt, waiting, date_created, first_attempt)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                 ON CONFLICT(id) DO UPDATE SET
+                    attempts = excluded.attempts,
+                    next_attempt = excluded.next_attempt,
+                    waiting = excluded.waiting,
+                    first_attempt = excluded.first_attempt",
+                params![
+                    msg.id,
+                    msg.chat_guid,
+                    msg.text,
+                    msg.file_path,
+                    msg.mime_type,
+                    msg.temp_guid,
+                    msg.attempts,
+                    msg.max_attempts,
+                    next_attempt,
+                    msg.waiting_for_connection,
+                    msg.date_created,
+                    msg.first_attempt
+                ],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Take the row for this queue unless another live claim holds it.
+    fn claim_row(&self, id: &str) -> BbResult<bool> {
+        let Some(db) = &self.database else {
+            return Ok(true);
+        };
+        let now = chrono::Utc::now().timestamp_millis();
+        let claimed = db
+            .conn()?
+            .execute(
+                "UPDATE send_queue SET claimed_by = ?1, claimed_at = ?2
+                 WHERE id = ?3 AND (claimed_by IS NULL OR claimed_by = ?1 OR claimed_at < ?4)",
+                params![self.owner, now, id, now - CLAIM_TIMEOUT_MS],
+            )
+            .map_err(|e| BbError::Database(e.to_string()))?;
+        Ok(claimed == 1)
+    }
+
+    fn delete_rows(&self, condition: &str, value: &str) -> BbResult<usize> {
+        let Some(db) = &self.database else {
+            return Ok(0);
+        };
+        db.conn()?
+            .execute(&format!("DELETE FROM send_queue WHERE {condition}"), [value])
+            .map_err(|e| BbError::Database(e.to_string()))
+    }
+
+    /// Read persisted messages, oldest first.
+    fn load_rows(&self) -> BbResult<Vec<QueuedMessage>> {
+        let Some(db) = &self.database else {
+            return Ok(Vec::new());
+        };
+        let conn = db.conn()?;
+        load_queue(&conn, chrono::Utc::now().timestamp_millis())
+    }
+
     /// Get queue statistics.
     pub async fn stats(&self) -> QueueStats {
         let queue = self.queue.lock().await;
@@ -242,6 +517,47 @@ impl QueueService {
     }
 }
 
+/// Read the `send_queue` table, turning stored next-attempt times back
+/// into the in-memory retry clock relative to `now_ms`.
+fn load_queue(conn: &Connection, now_ms: i64) -> BbResult<Vec<QueuedMessage>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, chat_guid, text, file_path, mime_type, temp_guid,
+                    attempts, max_attempts, next_attempt, date_created, waiting, first_attempt
+             FROM send_queue ORDER BY date_created, rowid",
+        )
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| {
+            let mut msg = QueuedMessage {
+                id: row.get(0)?,
+                chat_guid: row.get(1)?,
+                text: row.get(2)?,
+                file_path: row.get(3)?,
+                mime_type: row.get(4)?,
+                temp_guid: row.get(5)?,
+                attempts: row.get(6)?,
+                max_attempts: row.get(7)?,
+                last_attempt: None,
+                date_created: row.get(9)?,
+                waiting_for_connection: row.get(10)?,
+                first_attempt: row.get(11)?,
+            };
+            let next_attempt: i64 = row.get(8)?;
+            let wait = Duration::from_millis((next_attempt - now_ms).max(0) as u64);
+            if !wait.is_zero() {
+                // Backdate the last attempt so is_ready() fires at next_attempt
+                let elapsed = msg.retry_delay().saturating_sub(wait);
+                msg.last_attempt = Instant::now().checked_sub(elapsed);
+            }
+            Ok(msg)
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
 /// Queue statistics for monitoring.
 #[derive(Debug, Clone)]
 pub struct QueueStats {
@@ -271,8 +587,16 @@ impl Service for QueueService {
     fn name(&self) -> &str { "queue" }
     fn state(&self) -> ServiceState { self.state }
     fn init(&mut self) -> BbResult<()> {
+        let rows = self.load_rows()?;
+        let restored = match self.queue.try_lock() {
+            Ok(mut queue) if self.database.is_some() => {
+                *queue = rows.into();
+                queue.len()
+            }
+            _ => 0,
+        };
         self.state = ServiceState::Running;
-        info!("queue service initialized");
+        info!("queue service initialized ({restored} pending sends restored)");
         Ok(())
     }
     fn shutdown(&mut self) -> BbResult<()> {
@@ -297,6 +621,7 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             last_attempt: None,
+            ..Default::default()
         };
 
         svc.enqueue(msg).await;
@@ -317,6 +642,7 @@ mod tests {
             attempts: 3,
             max_attempts: 5,
             last_attempt: None,
+            ..Default::default()
         };
         assert_eq!(msg.retry_delay(), Duration::from_secs(8));
         assert!(msg.should_retry());
@@ -332,6 +658,7 @@ mod tests {
             attempts: 5,
             max_attempts: 5,
             last_attempt: None,
+            ..Default::default()
         };
         assert!(!msg.should_retry());
     }
@@ -346,6 +673,7 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             last_attempt: None,
+            ..Default::default()
         };
         assert!(msg.is_ready());
     }
@@ -397,6 +725,7 @@ mod tests {
             attempts: 0,
             max_attempts: 3,
             last_attempt: None,
+            ..Default::default()
         })
         .await;
 
@@ -409,4 +738,103 @@ mod tests {
         assert_eq!(stats.failed_count, 1);
         assert!(stats.to_string().contains("pending=1"));
     }
+
+    #[tokio::test]
+    async fn test_queue_survives_restart() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+
+        let svc = QueueService::new().with_database(db.clone());
+        let mut first = QueuedMessage::text("chat-1", "first");
+        first.record_attempt();
+        first.record_attempt();
+        let first_attempt = first.first_attempt;
+        let second = QueuedMessage::attachment("chat-1", "/tmp/photo.jpg", "image/jpeg");
+        let temp_guid = first.temp_guid.clone();
+        svc.enqueue(first).await;
+        svc.enqueue(second).await;
+        drop(svc);
+
+        let restarted = QueueService::new().with_database(db.clone());
+        assert_eq!(restarted.reload().await.unwrap(), 2);
+        assert_eq!(restarted.reload().await.unwrap(), 0);
+
+        let pending = restarted.pending().await;
+        assert_eq!(pending[0].text.as_deref(), Some("first"));
+        assert_eq!(pending[0].attempts, 2);
+        assert_eq!(pending[0].temp_guid, temp_guid);
+        assert!(first_attempt.is_some());
+        assert_eq!(pending[0].first_attempt, first_attempt);
+        assert!(!pending[0].is_ready());
+        assert_eq!(pending[1].mime_type.as_deref(), Some("image/jpeg"));
+
+        let id = pending[1].id.clone();
+        assert!(restarted.remove(&id).await);
+        let again = QueueService::new().with_database(db);
+        assert_eq!(again.reload().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_messages_are_not_retried() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        let svc = QueueService::new().with_database(db.clone());
+
+        let mut later = QueuedMessage::text("chat-1", "second").waiting();
+        later.date_created += 10;
+        let earlier = QueuedMessage::text("chat-1", "first").waiting();
+        svc.enqueue(later).await;
+        svc.enqueue(earlier).await;
+
+        assert!(svc.dequeue().await.is_none());
+        let waiting = svc.waiting().await;
+        assert_eq!(waiting.len(), 2);
+        assert_eq!(waiting[0].text.as_deref(), Some("first"));
+
+        let restarted = QueueService::new().with_database(db);
+        restarted.reload().await.unwrap();
+        assert_eq!(restarted.waiting().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_processes_share_the_queue_and_claim_rows() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        let app = QueueService::new().with_database(db.clone());
+        let cli = QueueService::new().with_database(db.clone());
+        app.reload().await.unwrap();
+
+        // A message queued by another process shows up on the next reload
+        let queued = QueuedMessage::text("chat-1", "from the cli");
+        let id = queued.id.clone();
+        cli.enqueue(queued).await;
+        assert_eq!(app.reload().await.unwrap(), 1);
+
+        // Only one process gets to send it
+        assert!(cli.claim(&id).await.unwrap());
+        assert!(!app.claim(&id).await.unwrap());
+        assert!(app.dequeue().await.is_none());
+        assert!(app.is_empty().await);
+
+        // Released, it can be taken again
+        cli.release(&id).await.unwrap();
+        app.reload().await.unwrap();
+        assert_eq!(app.dequeue().await.unwrap().id, id);
+        assert!(!cli.claim(&id).await.unwrap());
+
+        // A claim left by a process that died mid-send lapses
+        db.conn()
+            .unwrap()
+            .execute("UPDATE send_queue SET claimed_at = claimed_at - ?1", [CLAIM_TIMEOUT_MS + 1])
+            .unwrap();
+        assert!(cli.claim(&id).await.unwrap());
+
+        // Rows another process finished are dropped on reload
+        let other = QueuedMessage::text("chat-1", "from the app").waiting();
+        let other_id = other.id.clone();
+        app.enqueue(other).await;
+        assert!(cli.remove(&other_id).await);
+        app.reload().await.unwrap();
+        assert!(app.waiting().await.is_empty());
+    }
 }
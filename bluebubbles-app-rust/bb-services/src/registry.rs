@@ -10,12 +10,12 @@ use tracing::{info, error};
 
 use bb_core::error::{BbError, BbResult};
 use bb_core::config::ConfigHandle;
-use bb_models::Database;
+use bb_models::{Database, DatabaseKey, SnapshotInfo, SnapshotStore};
 use bb_api::ApiClient;
 use bb_socket::EventDispatcher;
 
 use crate::service::{Service, ServiceState};
-use crate::event_bus::EventBus;
+use crate::event_bus::{AppEvent, EventBus};
 use crate::action_handler::ActionHandler;
 use crate::lifecycle::LifecycleService;
 use crate::sync::SyncService;
@@ -25,7 +25,6 @@ use crate::contact::ContactService;
 use crate::attachment::AttachmentService;
 use crate::notification::NotificationService;
 use crate::settings::SettingsService;
-use crate::queue::QueueService;
 use crate::theme::ThemeService;
 use crate::fcm::FcmService;
 use crate::findmy::FindMyService;
@@ -35,6 +34,10 @@ use crate::search::SearchService;
 use crate::cache::CacheService;
 use crate::scheduled::ScheduledMessageService;
 use crate::handle::HandleService;
+use crate::export::ExportService;
+use crate::retention::RetentionService;
+use crate::stats::StatsService;
+use crate::snapshot::SnapshotService;
 
 /// Central service registry that manages all application services.
 ///
@@ -52,6 +55,8 @@ pub struct ServiceRegistry {
     pub dispatcher: EventDispatcher,
     /// Application-level event bus.
     pub event_bus: EventBus,
+    /// Where local database snapshots live, when snapshots are set up.
+    snapshot_store: Option<SnapshotStore>,
     /// Registered services in initialization order.
     services: Vec<(String, Arc<RwLock<Box<dyn Service>>>)>,
 }
@@ -69,10 +74,18 @@ impl ServiceRegistry {
             api_client: Arc::new(RwLock::new(None)),
             dispatcher,
             event_bus: EventBus::new(256),
+            snapshot_store: None,
             services: Vec::new(),
         }
     }
 
+    /// Take scheduled local snapshots into `store`, and allow restoring
+    /// from it with `restore_snapshot`. Set before `register_all`.
+    pub fn with_snapshot_store(mut self, store: SnapshotStore) -> Self {
+        self.snapshot_store = Some(store);
+        self
+    }
+
     /// Register a service. Services are initialized in registration order.
     pub fn register<S: Service + 'static>(&mut self, service: S) {
         let name = service.name().to_string();
@@ -85,95 +98,110 @@ impl ServiceRegistry {
     ///
     /// Initialization order:
     /// 1. Settings (no deps)
-    /// 2. Queue (no deps)
-    /// 3. Notification (config)
-    /// 4. Contact (database, event_bus)
-    /// 5. Chat (database, event_bus)
-    /// 6. Message (database, event_bus)
-    /// 7. Attachment (database, event_bus)
-    /// 8. Theme (database, event_bus)
-    /// 9. FCM (database, event_bus)
-    /// 10. Handle (database, event_bus)
-    /// 11. FindMy (event_bus)
-    /// 12. FaceTime (event_bus)
-    /// 13. Backup (config, event_bus)
-    /// 14. Search (database, event_bus)
-    /// 15. Cache (event_bus, cache_dir)
-    /// 16. ScheduledMessages (database, event_bus)
-    /// 17. Sync (config, database, event_bus)
-    /// 18. ActionHandler (database, event_bus)
-    /// 19. Lifecycle (config, database, event_bus)
+    /// 2. Notification (config, database)
+    /// 3. Contact (database, event_bus)
+    /// 4. Chat (database, event_bus)
+    /// 5. Message (database, event_bus)
+    /// 6. Attachment (database, event_bus)
+    /// 7. Theme (database, event_bus)
+    /// 8. FCM (database, event_bus)
+    /// 9. Handle (database, event_bus)
+    /// 10. FindMy (event_bus)
+    /// 11. FaceTime (event_bus)
+    /// 12. Backup (config, event_bus)
+    /// 13. Search (database, event_bus)
+    /// 14. Cache (event_bus, cache_dir)
+    /// 15. ScheduledMessages (database, event_bus)
+    /// 16. Export (database, event_bus, cache_dir)
+    /// 17. Stats (database, event_bus)
+    /// 18. Snapshot (config, database, event_bus, snapshot store), if set
+    /// 19. Sync (config, database, event_bus)
+    /// 20. ActionHandler (database, event_bus)
+    /// 21. Lifecycle (config, database, event_bus, retention)
     pub fn register_all(&mut self, cache_dir: PathBuf) {
         let bus = self.event_bus.clone();
 
         // 1. Settings
         self.register(SettingsService::new(self.config.clone()));
 
-        // 2. Queue
-        self.register(QueueService::new());
+        // 2. Notification
+        self.register(NotificationService::new(self.config.clone()).with_database(self.database.clone()));
 
-        // 3. Notification
-        self.register(NotificationService::new(self.config.clone()));
-
-        // 4. Contact
+        // 3. Contact
         self.register(ContactService::new(self.database.clone(), bus.clone()));
 
-        // 5. Chat
+        // 4. Chat
         self.register(ChatService::new(self.database.clone(), bus.clone()));
 
-        // 6. Message
+        // 5. Message
         self.register(MessageService::new(self.database.clone(), bus.clone()));
 
-        // 7. Attachment
+        // 6. Attachment
         self.register(AttachmentService::new(
             self.database.clone(),
             bus.clone(),
             cache_dir.clone(),
         ));
 
-        // 8. Theme
+        // 7. Theme
         self.register(ThemeService::new(self.database.clone(), bus.clone()));
 
-        // 9. FCM
+        // 8. FCM
         self.register(FcmService::new(self.database.clone(), bus.clone()));
 
-        // 10. Handle
+        // 9. Handle
         self.register(HandleService::new(self.database.clone(), bus.clone()));
 
-        // 11. FindMy
+        // 10. FindMy
         self.register(FindMyService::new(bus.clone()));
 
-        // 12. FaceTime
+        // 11. FaceTime
         self.register(FaceTimeService::new(bus.clone()));
 
-        // 13. Backup
-        self.register(BackupService::new(self.config.clone(), bus.clone()));
+        // 12. Backup
+        self.register(BackupService::new(self.config.clone(), bus.clone()).with_database(self.database.clone()));
 
-        // 14. Search
+        // 13. Search
         self.register(SearchService::new(self.database.clone(), bus.clone()));
 
-        // 15. Cache
-        self.register(CacheService::new(bus.clone(), cache_dir));
+        // 14. Cache
+        self.register(CacheService::new(bus.clone(), cache_dir.clone()));
 
-        // 16. ScheduledMessages
+        // 15. ScheduledMessages
         self.register(ScheduledMessageService::new(self.database.clone(), bus.clone()));
 
-        // 17. Sync
+        // 16. Export
+        self.register(ExportService::new(self.database.clone(), bus.clone(), cache_dir.clone()));
+
+        // 17. Stats
+        self.register(StatsService::new(self.database.clone(), bus.clone()));
+
+        // 18. Snapshot
+        if let Some(store) = self.snapshot_store.clone() {
+            self.register(SnapshotService::new(
+                self.config.clone(),
+                self.database.clone(),
+                bus.clone(),
+                store,
+            ));
+        }
+
+        // 19. Sync
         self.register(SyncService::new(
             self.config.clone(),
             self.database.clone(),
             bus.clone(),
         ));
 
-        // 18. ActionHandler
+        // 20. ActionHandler
         self.register(ActionHandler::new(self.database.clone(), bus.clone()));
 
-        // 19. Lifecycle
-        self.register(LifecycleService::new(
-            self.config.clone(),
-            self.database.clone(),
-            bus,
-        ));
+        // 21. Lifecycle (owns the periodic retention run)
+        let retention = Arc::new(RetentionService::new(self.database.clone(), bus.clone(), cache_dir));
+        self.register(
+            LifecycleService::new(self.config.clone(), self.database.clone(), bus)
+                .with_retention(retention),
+        );
 
         info!("registered {} default services", self.services.len());
     }
@@ -212,6 +240,33 @@ impl ServiceRegistry {
         Ok(())
     }
 
+    /// Restore the database from a local snapshot while the app runs.
+    ///
+    /// Every service is shut down first so nothing writes mid-restore, and
+    /// all are started again afterwards, whether or not the restore worked.
+    /// Emits `SnapshotRestored` on success.
+    pub async fn restore_snapshot(&self, id: &str) -> BbResult<SnapshotInfo> {
+        let store = self
+            .snapshot_store
+            .clone()
+            .ok_or_else(|| BbError::ServiceNotInitialized("snapshots are not set up".into()))?;
+        let key = DatabaseKey::from_config(&self.config.read().await.database)?;
+
+        self.shutdown_all().await?;
+        let database = self.database.clone();
+        let snapshot_id = id.to_string();
+        let restored = tokio::task::spawn_blocking(move || store.restore_into(&snapshot_id, &database, key.as_ref()))
+            .await
+            .map_err(|e| BbError::Internal(e.to_string()))
+            .and_then(|r| r);
+        let restarted = self.init_all().await;
+
+        let snapshot = restored?;
+        restarted?;
+        self.event_bus.emit(AppEvent::SnapshotRestored { id: snapshot.id.clone() });
+        Ok(snapshot)
+    }
+
     /// Set the API client (after server configuration is available).
     pub async fn set_api_client(&self, client: ApiClient) {
         let mut api = self.api_client.write().await;
@@ -241,6 +296,11 @@ impl ServiceRegistry {
         results
     }
 
+    /// Where local snapshots are kept, if snapshots are set up.
+    pub fn snapshot_store(&self) -> Option<&SnapshotStore> {
+        self.snapshot_store.as_ref()
+    }
+
     /// Get the number of registered services.
     pub fn service_count(&self) -> usize {
         self.services.len()
@@ -269,7 +329,39 @@ mod tests {
         let mut registry = ServiceRegistry::new(config, db, dispatcher);
         registry.register_all(dir.path().join("cache"));
 
-        assert_eq!(registry.service_count(), 19);
+        assert_eq!(registry.service_count(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot_restarts_services() {
+        let config = ConfigHandle::new(bb_core::config::AppConfig::default());
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+        let snapshot = store.create(&db, None).unwrap();
+
+        let mut registry = ServiceRegistry::new(config, db.clone(), EventDispatcher::new(64))
+            .with_snapshot_store(store);
+        registry.register_all(dir.path().join("cache"));
+        assert_eq!(registry.service_count(), 21);
+        registry.init_all().await.unwrap();
+        let mut events = registry.event_bus().subscribe();
+
+        db.conn().unwrap().execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+        registry.restore_snapshot(&snapshot.id).await.unwrap();
+        assert_eq!(db.stats().unwrap().chats, 0);
+        match events.recv().await.unwrap() {
+            AppEvent::SnapshotRestored { id } => assert_eq!(id, snapshot.id),
+            other => panic!("unexpected event: {other:?}"),
+        }
+        for (name, state, healthy) in registry.health_check().await {
+            assert!(healthy, "service {name} is not healthy after restore (state: {state})");
+        }
+
+        // A failed restore still leaves the services running
+        assert!(registry.restore_snapshot("nope").await.is_err());
+        assert!(registry.health_check().await.iter().all(|(_, _, healthy)| *healthy));
+        registry.shutdown_all().await.unwrap();
     }
 
     #[tokio::test]
@@ -0,0 +1,455 @@
+//! Retention service for enforcing data retention policies.
+//!
+//! Applies the global and per-chat `RetentionPolicy` rows stored in the
+//! database: messages past their age limit are deleted together with their
+//! reactions and attachments, and attachments past their own limit lose
+//! their cached file but keep the row. Bookmarked messages are never
+//! touched. Every run can be done as a dry run that only reports.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::Serialize;
+use tracing::{info, warn, debug};
+
+use bb_core::error::{BbError, BbResult};
+use bb_models::{Attachment, Database, Message, RetentionPolicy};
+use bb_models::queries;
+
+use crate::attachment::cache_file_name;
+use crate::cache::CacheService;
+use crate::event_bus::{AppEvent, EventBus};
+use crate::util::{strip_part_prefix, timestamp_ms};
+use crate::service::{Service, ServiceState};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// What a retention run did (or would do) to a single chat.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChatRetention {
+    pub chat_guid: String,
+    pub messages_deleted: usize,
+    pub reactions_deleted: usize,
+    pub attachments_deleted: usize,
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+}
+
+impl ChatRetention {
+    fn is_empty(&self) -> bool {
+        self.messages_deleted == 0 && self.reactions_deleted == 0 && self.files_removed == 0
+    }
+}
+
+/// Outcome of a retention run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionReport {
+    /// Whether this was a dry run (nothing was deleted).
+    pub dry_run: bool,
+    /// Chats with at least one deletion.
+    pub chats: Vec<ChatRetention>,
+    pub messages_deleted: usize,
+    pub reactions_deleted: usize,
+    pub attachments_deleted: usize,
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+    /// Messages past a limit that were kept because they are bookmarked.
+    pub bookmarked_kept: usize,
+    /// Chats skipped because they are pinned.
+    pub pinned_chats_skipped: usize,
+}
+
+/// Service that applies retention policies to the local database and cache.
+pub struct RetentionService {
+    state: ServiceState,
+    database: Database,
+    event_bus: EventBus,
+    cache: CacheService,
+}
+
+impl RetentionService {
+    /// Create a new RetentionService cleaning files under `cache_dir`.
+    pub fn new(database: Database, event_bus: EventBus, cache_dir: PathBuf) -> Self {
+        Self {
+            state: ServiceState::Created,
+            cache: CacheService::new(event_bus.clone(), cache_dir),
+            database,
+            event_bus,
+        }
+    }
+
+    /// All stored policies, global first.
+    pub fn policies(&self) -> BbResult<Vec<RetentionPolicy>> {
+        let conn = self.database.conn()?;
+        RetentionPolicy::list(&conn)
+    }
+
+    /// Store a policy, replacing any existing policy for the same scope.
+    pub fn set_policy(&self, policy: &mut RetentionPolicy) -> BbResult<i64> {
+        let conn = self.database.conn()?;
+        policy.save(&conn)
+    }
+
+    /// Remove the policy for a scope (`None` for the global policy).
+    pub fn remove_policy(&self, chat_id: Option<i64>) -> BbResult<bool> {
+        let conn = self.database.conn()?;
+        RetentionPolicy::delete_for_scope(&conn, chat_id)
+    }
+
+    /// Apply all policies as of now.
+    pub fn run(&self, dry_run: bool) -> BbResult<RetentionReport> {
+        self.run_at(Utc::now().timestamp_millis(), dry_run)
+    }
+
+    /// Apply all policies as of `now_ms` (Unix ms).
+    pub fn run_at(&self, now_ms: i64, dry_run: bool) -> BbResult<RetentionReport> {
+        let mut report = RetentionReport {
+            dry_run,
+            ..Default::default()
+        };
+
+        let mut conn = self.database.conn()?;
+        let global = RetentionPolicy::find_for_scope(&conn, None)?;
+        let chat_policies: HashMap<i64, RetentionPolicy> = RetentionPolicy::list(&conn)?
+            .into_iter()
+            .filter_map(|p| p.chat_id.map(|id| (id, p)))
+            .collect();
+
+        if global.is_none() && chat_policies.is_empty() {
+            debug!("no retention policies configured");
+            return Ok(report);
+        }
+
+        for (chat_id, chat_guid, is_pinned) in load_chats(&conn)? {
+            let Some(policy) = chat_policies.get(&chat_id).or(global.as_ref()) else {
+                continue;
+            };
+            if !policy.enabled {
+                continue;
+            }
+            if policy.exempt_pinned && is_pinned {
+                report.pinned_chats_skipped += 1;
+                continue;
+            }
+
+            let mut result = ChatRetention {
+                chat_guid,
+                ..Default::default()
+            };
+            report.bookmarked_kept += self.apply_to_chat(&mut conn, chat_id, policy, now_ms, dry_run, &mut result)?;
+            if !result.is_empty() {
+                report.messages_deleted += result.messages_deleted;
+                report.reactions_deleted += result.reactions_deleted;
+                report.attachments_deleted += result.attachments_deleted;
+                report.files_removed += result.files_removed;
+                report.bytes_freed += result.bytes_freed;
+                if !dry_run && result.messages_deleted > 0 {
+                    self.event_bus.emit(AppEvent::ChatUpdated {
+                        chat_guid: result.chat_guid.clone(),
+                    });
+                }
+                report.chats.push(result);
+            }
+        }
+
+        info!(
+            "retention {}: {} messages, {} reactions, {} files ({} bytes)",
+            if dry_run { "dry run" } else { "run" },
+            report.messages_deleted,
+            report.reactions_deleted,
+            report.files_removed,
+            report.bytes_freed
+        );
+        Ok(report)
+    }
+
+    /// Apply a policy to one chat, recording deletions in `result`.
+    ///
+    /// Returns the number of messages kept only because they are bookmarked.
+    fn apply_to_chat(
+        &self,
+        conn: &mut Connection,
+        chat_id: i64,
+        policy: &RetentionPolicy,
+        now_ms: i64,
+        dry_run: bool,
+        result: &mut ChatRetention,
+    ) -> BbResult<usize> {
+        let mut bookmarked_kept = 0;
+        let message_cutoff = policy.message_max_age_days.map(|d| now_ms - i64::from(d) * DAY_MS);
+        let attachment_cutoff = policy.attachment_max_age_days.map(|d| now_ms - i64::from(d) * DAY_MS);
+        if message_cutoff.is_none() && attachment_cutoff.is_none() {
+            return Ok(0);
+        }
+
+        let all = queries::all_messages_for_chat(conn, chat_id)?;
+        let mut reactions: HashMap<&str, Vec<i64>> = HashMap::new();
+        for msg in all.iter().filter(|m| m.is_reaction()) {
+            if let (Some(target), Some(id)) = (msg.associated_message_guid.as_deref(), msg.id) {
+                reactions.entry(strip_part_prefix(target)).or_default().push(id);
+            }
+        }
+
+        let mut delete_ids: Vec<i64> = Vec::new();
+        let mut delete_guids: Vec<&str> = Vec::new();
+        let mut delete_files: Vec<Attachment> = Vec::new();
+        let mut strip_files: Vec<Attachment> = Vec::new();
+
+        for msg in all.iter().filter(|m| !m.is_reaction()) {
+            let (Some(id), Some(millis)) = (msg.id, msg.date_created.as_deref().and_then(timestamp_ms)) else {
+                continue;
+            };
+            let past_messages = message_cutoff.is_some_and(|c| millis < c);
+            let past_attachments = attachment_cutoff.is_some_and(|c| millis < c);
+            if !past_messages && !past_attachments {
+                continue;
+            }
+            if msg.is_bookmarked {
+                bookmarked_kept += 1;
+                continue;
+            }
+
+            if past_messages {
+                delete_ids.push(id);
+                result.messages_deleted += 1;
+                if let Some(guid) = msg.guid.as_deref() {
+                    delete_guids.push(guid);
+                    if let Some(ids) = reactions.get(guid) {
+                        delete_ids.extend(ids);
+                        result.reactions_deleted += ids.len();
+                    }
+                }
+                let attachments = attachments_for(conn, msg)?;
+                result.attachments_deleted += attachments.len();
+                delete_files.extend(attachments);
+            } else {
+                strip_files.extend(attachments_for(conn, msg)?);
+            }
+        }
+
+        if !dry_run && !delete_ids.is_empty() {
+            let tx = conn.transaction().map_err(|e| BbError::Database(e.to_string()))?;
+            for id in &delete_ids {
+                tx.execute("DELETE FROM attachments WHERE message_id = ?1", [id])
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+                tx.execute("DELETE FROM messages WHERE id = ?1", [id])
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+            }
+            for guid in &delete_guids {
+                tx.execute("DELETE FROM message_tombstones WHERE message_guid = ?1", [guid])
+                    .map_err(|e| BbError::Database(e.to_string()))?;
+            }
+            tx.commit().map_err(|e| BbError::Database(e.to_string()))?;
+        }
+
+        // Files go only after the rows are gone, so a failed commit leaves both
+        for attachment in delete_files.iter().chain(&strip_files) {
+            let (files, bytes) = remove_cached_files(&self.cache, attachment, dry_run);
+            result.files_removed += files;
+            result.bytes_freed += bytes;
+        }
+
+        Ok(bookmarked_kept)
+    }
+}
+
+/// Remove (or, for a dry run, measure) the cached files of an attachment,
+/// including the paired live photo video. Returns the file count and bytes.
+pub(crate) fn remove_cached_files(cache: &CacheService, attachment: &Attachment, dry_run: bool) -> (usize, u64) {
+    let Some(guid) = attachment.guid.as_deref() else {
+        return (0, 0);
+    };
+    let names = [
+        cache_file_name(guid, attachment.file_extension()),
+        cache_file_name(&format!("{guid}-live"), Some("mov")),
+    ];
+
+    let mut files = 0;
+    let mut bytes = 0;
+    for name in &names {
+        let Ok(meta) = std::fs::metadata(cache.cache_path(name)) else {
+            continue;
+        };
+        if !dry_run {
+            if let Err(e) = cache.remove(name) {
+                warn!("failed to remove cached file {name}: {e}");
+                continue;
+            }
+        }
+        files += 1;
+        bytes += meta.len();
+    }
+    (files, bytes)
+}
+
+fn load_chats(conn: &Connection) -> BbResult<Vec<(i64, String, bool)>> {
+    let mut stmt = conn
+        .prepare("SELECT id, guid, is_pinned FROM chats ORDER BY id ASC")
+        .map_err(|e| BbError::Database(e.to_string()))?;
+    let chats = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get::<_, i32>(2)? != 0))
+        })
+        .map_err(|e| BbError::Database(e.to_string()))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(chats)
+}
+
+fn attachments_for(conn: &Connection, msg: &Message) -> BbResult<Vec<Attachment>> {
+    // Looked up by row rather than trusting `has_attachments`, which isn't
+    // always set, so no cached file outlives its message.
+    match msg.id {
+        Some(id) => queries::load_attachments_for_message(conn, id),
+        None => Ok(Vec::new()),
+    }
+}
+
+impl Service for RetentionService {
+    fn name(&self) -> &str {
+        "retention"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("retention service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("retention service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn setup() -> (RetentionService, TempDir) {
+        let dir = TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        let service = RetentionService::new(db, EventBus::new(16), dir.path().join("cache"));
+        (service, dir)
+    }
+
+    fn insert_message(conn: &Connection, chat_id: i64, guid: &str, days_old: i64) -> i64 {
+        conn.execute(
+            "INSERT INTO messages (guid, chat_id, date_created) VALUES (?1, ?2, ?3)",
+            rusqlite::params![guid, chat_id, (NOW - days_old * DAY_MS).to_string()],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    #[test]
+    fn test_no_policies_is_noop() {
+        let (service, _dir) = setup();
+        let report = service.run_at(NOW, false).unwrap();
+        assert_eq!(report.messages_deleted, 0);
+        assert!(report.chats.is_empty());
+    }
+
+    #[test]
+    fn test_deletes_old_messages_with_reactions_and_keeps_bookmarks() {
+        let (service, _dir) = setup();
+        let conn = service.database.conn().unwrap();
+        conn.execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+        conn.execute("INSERT INTO chats (guid, is_pinned) VALUES ('chat-2', 1)", []).unwrap();
+
+        insert_message(&conn, 1, "old", 800);
+        let bookmarked = insert_message(&conn, 1, "old-bookmarked", 800);
+        conn.execute("UPDATE messages SET is_bookmarked = 1 WHERE id = ?1", [bookmarked]).unwrap();
+        insert_message(&conn, 1, "new", 10);
+        conn.execute(
+            "INSERT INTO messages (guid, chat_id, date_created, associated_message_guid, associated_message_type)
+             VALUES ('tapback', 1, ?1, 'p:0/old', 'love')",
+            [(NOW - 5 * DAY_MS).to_string()],
+        ).unwrap();
+        insert_message(&conn, 2, "pinned-old", 800);
+
+        let mut global = RetentionPolicy::new(None);
+        global.message_max_age_days = Some(730);
+        service.set_policy(&mut global).unwrap();
+
+        let dry = service.run_at(NOW, true).unwrap();
+        assert_eq!(dry.messages_deleted, 1);
+        assert_eq!(dry.reactions_deleted, 1);
+        assert_eq!(dry.bookmarked_kept, 1);
+        assert_eq!(dry.pinned_chats_skipped, 1);
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM messages", [], |r| r.get(0)).unwrap()
+        };
+        assert_eq!(count(&conn), 5);
+
+        let report = service.run_at(NOW, false).unwrap();
+        assert_eq!(report.messages_deleted, 1);
+        assert_eq!(report.chats.len(), 1);
+        assert_eq!(count(&conn), 3);
+        assert!(queries::find_message_by_guid(&conn, "old").unwrap().is_none());
+        assert!(queries::find_message_by_guid(&conn, "tapback").unwrap().is_none());
+        assert!(queries::find_message_by_guid(&conn, "old-bookmarked").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_chat_policy_overrides_global_and_strips_files() {
+        let (service, dir) = setup();
+        let conn = service.database.conn().unwrap();
+        conn.execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+        let msg_id = insert_message(&conn, 1, "with-file", 200);
+        conn.execute("UPDATE messages SET has_attachments = 1 WHERE id = ?1", [msg_id]).unwrap();
+        conn.execute(
+            "INSERT INTO attachments (guid, message_id, transfer_name) VALUES ('att-1', ?1, 'photo.jpeg')",
+            [msg_id],
+        ).unwrap();
+        let cache = dir.path().join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("att-1.jpeg"), b"0123456789").unwrap();
+
+        // The global policy would delete the message; the chat policy only drops files
+        let mut global = RetentionPolicy::new(None);
+        global.message_max_age_days = Some(30);
+        service.set_policy(&mut global).unwrap();
+        let mut chat = RetentionPolicy::new(Some(1));
+        chat.attachment_max_age_days = Some(90);
+        service.set_policy(&mut chat).unwrap();
+
+        let report = service.run_at(NOW, false).unwrap();
+        assert_eq!(report.messages_deleted, 0);
+        assert_eq!(report.files_removed, 1);
+        assert_eq!(report.bytes_freed, 10);
+        assert!(!cache.join("att-1.jpeg").exists());
+        assert!(queries::find_attachment_by_guid(&conn, "att-1").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_deletes_files_of_messages_not_flagged_with_attachments() {
+        let (service, dir) = setup();
+        let conn = service.database.conn().unwrap();
+        conn.execute("INSERT INTO chats (guid) VALUES ('chat-1')", []).unwrap();
+        let msg_id = insert_message(&conn, 1, "unflagged", 200);
+        conn.execute(
+            "INSERT INTO attachments (guid, message_id, transfer_name) VALUES ('att-2', ?1, 'clip.mov')",
+            [msg_id],
+        ).unwrap();
+        let cache = dir.path().join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("att-2.mov"), b"01234").unwrap();
+
+        let mut global = RetentionPolicy::new(None);
+        global.message_max_age_days = Some(30);
+        service.set_policy(&mut global).unwrap();
+
+        let report = service.run_at(NOW, false).unwrap();
+        assert_eq!(report.messages_deleted, 1);
+        assert_eq!(report.files_removed, 1);
+        assert!(!cache.join("att-2.mov").exists());
+    }
+}
@@ -2,20 +2,92 @@
 //!
 //! Provides CRUD operations for scheduled messages, local caching of the
 //! schedule list, and checking for messages that are due for sending.
+//!
+//! For setups without the server's private-API scheduler, schedules can
+//! also be local: stored in `scheduled_messages` under negative IDs and
+//! sent by `start_local_scheduler` through `MessageService`. Schedules that
+//! came due while the client was not running are sent or skipped according
+//! to the configured catch-up policy. Recurring local schedules (cron or
+//! RRULE, see `bb_models::Recurrence`) move on to their next occurrence
+//! after each run; occurrences missed in between are not sent separately.
 
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{info, warn, debug};
 
-use bb_core::error::BbResult;
-use bb_models::{Database, ScheduledMessage};
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_models::{Database, Recurrence, ScheduledMessage};
+use bb_models::models::recurrence::parse_time_zone;
 use bb_models::models::scheduled_message::status as msg_status;
 use bb_api::ApiClient;
 use bb_api::endpoints::messages::ScheduleMessageParams;
+use serde::Serialize;
 
 use crate::event_bus::EventBus;
+use crate::message::MessageService;
+use crate::outbox::SubmitStatus;
+use crate::queue::QueueService;
 use crate::service::{Service, ServiceState};
 
+/// Longest the local scheduler sleeps before checking again, so new
+/// schedules and wall-clock jumps (e.g. after the machine sleeps) are
+/// noticed promptly.
+const LOCAL_SCHEDULER_MAX_SLEEP: Duration = Duration::from_secs(30);
+
+/// How late a schedule may run and still count as on time rather than
+/// missed.
+const ON_TIME_GRACE: chrono::Duration = chrono::Duration::minutes(2);
+
+/// What to do with local schedules that came due while the client was
+/// not running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    /// Send every missed schedule.
+    SendAll,
+    /// Skip every missed schedule.
+    SkipAll,
+    /// Send those missed by at most this long; skip the rest.
+    SendRecent(chrono::Duration),
+}
+
+impl CatchUpPolicy {
+    /// Read the policy from `conversation.scheduled_catch_up`.
+    pub async fn from_config(config: &ConfigHandle) -> Self {
+        let config = config.read().await;
+        match config.conversation.scheduled_catch_up.as_str() {
+            "send" => Self::SendAll,
+            "skip" => Self::SkipAll,
+            _ => Self::SendRecent(chrono::Duration::minutes(
+                config.conversation.scheduled_catch_up_minutes as i64,
+            )),
+        }
+    }
+
+    /// Whether a schedule `lateness` past due should still be sent.
+    fn should_send(&self, lateness: chrono::Duration) -> bool {
+        if lateness <= ON_TIME_GRACE {
+            return true;
+        }
+        match self {
+            Self::SendAll => true,
+            Self::SkipAll => false,
+            Self::SendRecent(window) => lateness <= *window,
+        }
+    }
+}
+
+/// Outcome of one pass of the local scheduler.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LocalRunReport {
+    pub sent: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Due while the server was unreachable; left in the outgoing queue.
+    pub queued: usize,
+}
+
 /// Service for scheduled message management.
 ///
 /// Scheduled messages are primarily managed on the server side. This service
@@ -65,7 +137,8 @@ impl ScheduledMessageService {
 
         info!("fetched {} scheduled messages from server", messages.len());
         let mut cached = self.cached.lock().await;
-        *cached = messages.clone();
+        cached.retain(|m| m.is_local());
+        cached.extend(messages.iter().cloned());
         Ok(messages)
     }
 
@@ -172,6 +245,289 @@ impl ScheduledMessageService {
         Ok(due)
     }
 
+    /// Schedule a message to be sent by the client's own scheduler.
+    pub async fn create_local(
+        &self,
+        chat_guid: &str,
+        message: &str,
+        scheduled_for: chrono::DateTime<chrono::Utc>,
+    ) -> BbResult<ScheduledMessage> {
+        if message.trim().is_empty() {
+            return Err(BbError::InvalidInput("scheduled message text is empty".into()));
+        }
+        let mut msg = ScheduledMessage::new_local(chat_guid, message, scheduled_for);
+        let conn = self.database.conn()?;
+        msg.save_local(&conn)?;
+
+        self.cached.lock().await.push(msg.clone());
+        info!("created local scheduled message {:?} for chat: {chat_guid}", msg.id);
+        Ok(msg)
+    }
+
+    /// Schedule a recurring message for the client's own scheduler.
+    /// `recurrence` is a cron expression or RRULE evaluated in the IANA
+    /// zone `time_zone`; the first send is its first occurrence at or after
+    /// `start`.
+    pub async fn create_local_recurring(
+        &self,
+        chat_guid: &str,
+        message: &str,
+        start: chrono::DateTime<chrono::Utc>,
+        recurrence: &str,
+        time_zone: &str,
+    ) -> BbResult<ScheduledMessage> {
+        let tz = parse_time_zone(time_zone)?;
+        let first = Recurrence::parse(recurrence)?
+            .next_after(start, tz, start - chrono::Duration::seconds(1))
+            .ok_or_else(|| BbError::InvalidInput(format!("\"{recurrence}\" has no occurrences")))?;
+        if message.trim().is_empty() {
+            return Err(BbError::InvalidInput("scheduled message text is empty".into()));
+        }
+        let mut msg = ScheduledMessage::new_local(chat_guid, message, first).with_recurrence(recurrence, tz.name());
+        let conn = self.database.conn()?;
+        msg.save_local(&conn)?;
+
+        self.cached.lock().await.push(msg.clone());
+        info!("created recurring local schedule {:?} ({recurrence}) for chat: {chat_guid}", msg.id);
+        Ok(msg)
+    }
+
+    /// Pending local schedules, soonest first.
+    pub fn list_local(&self) -> BbResult<Vec<ScheduledMessage>> {
+        let conn = self.database.conn()?;
+        ScheduledMessage::load_pending_local(&conn)
+    }
+
+    /// Delete a local schedule. Returns whether it existed.
+    pub async fn delete_local(&self, id: i64) -> BbResult<bool> {
+        if id >= 0 {
+            return Err(BbError::InvalidInput(format!("{id} is not a local schedule")));
+        }
+        let conn = self.database.conn()?;
+        let deleted = ScheduledMessage::delete(&conn, id)?;
+        self.cached.lock().await.retain(|m| m.id != Some(id));
+        Ok(deleted)
+    }
+
+    /// When the next pending local schedule is due.
+    pub fn next_local_due(&self) -> BbResult<Option<chrono::DateTime<chrono::Utc>>> {
+        Ok(self.list_local()?.iter().filter_map(|m| m.scheduled_time()).min())
+    }
+
+    /// Send every local schedule due by `now`, recording the outcome on
+    /// each. Each schedule is claimed first, so the desktop app and
+    /// `scheduled run` never both send it; a claim left behind by a
+    /// scheduler that died mid-send is taken over once it goes stale, and
+    /// one whose outcome can't be saved is released. Missed schedules are
+    /// sent or skipped per `policy`. A schedule that can't reach the server
+    /// goes to `queue` as a message waiting for the connection.
+    pub async fn run_due_local(
+        &self,
+        api: &ApiClient,
+        messages: &MessageService,
+        queue: &QueueService,
+        policy: CatchUpPolicy,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> BbResult<LocalRunReport> {
+        let mut report = LocalRunReport::default();
+        for mut msg in self.list_local()? {
+            let Some(due) = msg.scheduled_time() else {
+                msg.status = msg_status::FAILED.to_string();
+                msg.error = Some(format!("invalid schedule time: {}", msg.scheduled_for));
+                self.record(&mut msg).await?;
+                report.failed += 1;
+                continue;
+            };
+            if due > now {
+                break;
+            }
+            // Another process running the scheduler may have taken it
+            if !self.claim(&msg)? {
+                debug!("local schedule {:?} already claimed", msg.id);
+                continue;
+            }
+
+            if !policy.should_send(now - due) {
+                info!("skipping local schedule {:?}, missed by {}m", msg.id, (now - due).num_minutes());
+                msg.status = msg_status::SKIPPED.to_string();
+                self.reschedule(&mut msg, now);
+                self.record_claimed(&mut msg).await?;
+                report.skipped += 1;
+                continue;
+            }
+
+            match messages
+                .send_text_or_queue(Some(api), queue, &msg.chat_guid, &msg.message, "private-api", None)
+                .await
+            {
+                Ok((_, SubmitStatus::Sent)) => {
+                    msg.status = msg_status::SENT.to_string();
+                    msg.error = None;
+                    msg.sent_at = Some(chrono::Utc::now().to_rfc3339());
+                    report.sent += 1;
+                }
+                Ok((_, SubmitStatus::Queued)) => {
+                    debug!("local schedule {:?} queued until reconnect", msg.id);
+                    msg.status = msg_status::QUEUED.to_string();
+                    report.queued += 1;
+                }
+                Err(e) => {
+                    warn!("local schedule {:?} failed: {e}", msg.id);
+                    msg.status = msg_status::FAILED.to_string();
+                    msg.error = Some(e.to_string());
+                    report.failed += 1;
+                }
+            }
+            self.reschedule(&mut msg, now);
+            self.record_claimed(&mut msg).await?;
+        }
+        Ok(report)
+    }
+
+    /// Move a recurring schedule that just ran on to its next occurrence,
+    /// back to pending. When several occurrences have passed since, only
+    /// the latest is kept, to be sent or skipped on the next pass. The
+    /// run's outcome stays visible in `sent_at` and `error`; non-recurring
+    /// and finished schedules keep their final status.
+    fn reschedule(&self, msg: &mut ScheduledMessage, now: chrono::DateTime<chrono::Utc>) {
+        let mut next = match msg.next_occurrence(msg.scheduled_time().unwrap_or(now)) {
+            Ok(Some(next)) => next,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("local schedule {:?} has an invalid recurrence: {e}", msg.id);
+                msg.error = Some(e.to_string());
+                return;
+            }
+        };
+        while let Ok(Some(later)) = msg.next_occurrence(next) {
+            if later > now {
+                break;
+            }
+            next = later;
+        }
+        debug!("local schedule {:?} recurs at {next}", msg.id);
+        msg.scheduled_for = next.to_rfc3339();
+        msg.status = msg_status::PENDING.to_string();
+    }
+
+    /// Server parameters for a message sent at `start`, repeating per
+    /// `recurrence` (cron or RRULE) in `time_zone` when given. Fails when
+    /// the server can't express the recurrence; such schedules must be
+    /// created with `create_local_recurring` instead.
+    pub fn server_params(
+        chat_guid: &str,
+        message: &str,
+        start: chrono::DateTime<chrono::Utc>,
+        recurrence: Option<(&str, &str)>,
+    ) -> BbResult<ScheduleMessageParams> {
+        let (scheduled_for, schedule) = match recurrence {
+            Some((expr, time_zone)) => {
+                let (first, schedule) = Recurrence::parse(expr)?.server_schedule(start, parse_time_zone(time_zone)?)?;
+                (first, Some(schedule))
+            }
+            None => (start, None),
+        };
+        Ok(ScheduleMessageParams {
+            schedule_type: "send-message".to_string(),
+            payload: serde_json::json!({
+                "chatGuid": chat_guid,
+                "message": message,
+                "tempGuid": format!("temp-{}", uuid::Uuid::new_v4()),
+                "method": "private-api",
+            }),
+            scheduled_for: scheduled_for.timestamp_millis(),
+            schedule,
+        })
+    }
+
+    /// Claim a pending schedule in the database before acting on it.
+    fn claim(&self, msg: &ScheduledMessage) -> BbResult<bool> {
+        let Some(id) = msg.id else { return Ok(false) };
+        let conn = self.database.conn()?;
+        ScheduledMessage::claim(&conn, id)
+    }
+
+    /// Save the outcome of a claimed schedule, releasing the claim if it
+    /// can't be saved so the schedule isn't left `sending`.
+    async fn record_claimed(&self, msg: &mut ScheduledMessage) -> BbResult<()> {
+        let Err(e) = self.record(msg).await else { return Ok(()) };
+        warn!("couldn't record local schedule {:?}, releasing it: {e}", msg.id);
+        if let Some(id) = msg.id {
+            if let Err(release_error) = self.database.conn().and_then(|conn| ScheduledMessage::release(&conn, id)) {
+                warn!("couldn't release local schedule {id}, it is retried once the claim goes stale: {release_error}");
+            }
+        }
+        Err(e)
+    }
+
+    /// Save a schedule's new state to the database and cache.
+    async fn record(&self, msg: &mut ScheduledMessage) -> BbResult<()> {
+        let conn = self.database.conn()?;
+        msg.save(&conn)?;
+        let mut cached = self.cached.lock().await;
+        if let Some(pos) = cached.iter().position(|m| m.id == msg.id) {
+            cached[pos] = msg.clone();
+        }
+        Ok(())
+    }
+
+    /// Run local schedules: sleep until the next one is due (checking at
+    /// least every 30 seconds), then send what is due. The catch-up policy
+    /// is re-read from `config` each pass, and the API client from `api`
+    /// since it can be replaced.
+    pub fn start_local_scheduler(
+        service: Arc<Self>,
+        messages: Arc<MessageService>,
+        queue: Arc<QueueService>,
+        config: ConfigHandle,
+        api: Arc<RwLock<Option<ApiClient>>>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            // Without a client, or after a failed pass, due schedules are
+            // still due; wait a full interval instead of retrying at once
+            let mut back_off = false;
+            loop {
+                let wait = match service.next_local_due() {
+                    Ok(Some(_)) if back_off => LOCAL_SCHEDULER_MAX_SLEEP,
+                    Ok(Some(due)) => (due - chrono::Utc::now())
+                        .to_std()
+                        .unwrap_or(Duration::ZERO)
+                        .min(LOCAL_SCHEDULER_MAX_SLEEP),
+                    Ok(None) => LOCAL_SCHEDULER_MAX_SLEEP,
+                    Err(e) => {
+                        warn!("failed to read local schedules: {e}");
+                        LOCAL_SCHEDULER_MAX_SLEEP
+                    }
+                };
+                tokio::time::sleep(wait).await;
+
+                let Some(client) = api.read().await.clone() else {
+                    back_off = true;
+                    continue;
+                };
+                let policy = CatchUpPolicy::from_config(&config).await;
+                match service
+                    .run_due_local(&client, &messages, &queue, policy, chrono::Utc::now())
+                    .await
+                {
+                    Ok(report) => {
+                        back_off = false;
+                        if report.sent + report.failed + report.skipped + report.queued > 0 {
+                            info!(
+                                "local scheduler: {} sent, {} queued, {} failed, {} skipped",
+                                report.sent, report.queued, report.failed, report.skipped
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        back_off = true;
+                        warn!("local scheduler pass failed: {e}");
+                    }
+                }
+            }
+        })
+    }
+
     /// Get the count of pending scheduled messages.
     pub async fn pending_count(&self) -> usize {
         let cached = self.cached.lock().await;
@@ -269,4 +625,174 @@ mod tests {
         let list = svc.list_from_db().unwrap();
         assert!(list.is_empty());
     }
+
+    #[test]
+    fn test_catch_up_policy() {
+        let late = chrono::Duration::minutes(90);
+        let on_time = chrono::Duration::seconds(30);
+        assert!(CatchUpPolicy::SendAll.should_send(late));
+        assert!(!CatchUpPolicy::SkipAll.should_send(late));
+        assert!(CatchUpPolicy::SkipAll.should_send(on_time));
+        let recent = CatchUpPolicy::SendRecent(chrono::Duration::hours(1));
+        assert!(recent.should_send(chrono::Duration::minutes(45)));
+        assert!(!recent.should_send(late));
+    }
+
+    #[tokio::test]
+    async fn test_run_due_local_records_outcomes() {
+        let db = create_test_db();
+        let bus = EventBus::new(16);
+        let svc = ScheduledMessageService::new(db.clone(), bus.clone());
+        let messages = MessageService::new(db, bus);
+        let now = chrono::Utc::now();
+
+        let missed = svc.create_local("chat-1", "missed", now - chrono::Duration::hours(3)).await.unwrap();
+        let due = svc.create_local("chat-1", "due", now - chrono::Duration::seconds(5)).await.unwrap();
+        let future = svc.create_local("chat-1", "future", now + chrono::Duration::hours(1)).await.unwrap();
+        assert!(svc.create_local("chat-1", "  ", now).await.is_err());
+
+        let api = ApiClient::new(&bb_core::config::ServerConfig {
+            address: "http://127.0.0.1:1".into(),
+            ..Default::default()
+        })
+        .unwrap()
+        .with_retry_config(bb_api::RetryConfig { max_retries: 0, ..Default::default() });
+        let policy = CatchUpPolicy::SendRecent(chrono::Duration::hours(1));
+        let queue = QueueService::new();
+        let report = svc.run_due_local(&api, &messages, &queue, policy, now).await.unwrap();
+        assert_eq!((report.sent, report.skipped, report.queued), (0, 1, 1));
+
+        let all = svc.list_from_db().unwrap();
+        let by_id = |id| all.iter().find(|m| m.id == id).unwrap();
+        assert_eq!(by_id(missed.id).status, msg_status::SKIPPED);
+        assert_eq!(by_id(due.id).status, msg_status::QUEUED);
+        assert_eq!(by_id(future.id).status, msg_status::PENDING);
+        assert_eq!(queue.waiting().await[0].text.as_deref(), Some("due"));
+        assert_eq!(svc.next_local_due().unwrap(), future.scheduled_time());
+
+        assert!(svc.delete_local(future.id.unwrap()).await.unwrap());
+        assert!(svc.delete_local(5).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_due_local_skips_claimed_schedules() {
+        let db = create_test_db();
+        let bus = EventBus::new(16);
+        let svc = ScheduledMessageService::new(db.clone(), bus.clone());
+        let messages = MessageService::new(db.clone(), bus);
+        let now = chrono::Utc::now();
+
+        let due = svc.create_local("chat-1", "due", now - chrono::Duration::seconds(5)).await.unwrap();
+        // Another scheduler process is already sending it
+        assert!(ScheduledMessage::claim(&db.conn().unwrap(), due.id.unwrap()).unwrap());
+
+        let api = ApiClient::new(&bb_core::config::ServerConfig {
+            address: "http://127.0.0.1:1".into(),
+            ..Default::default()
+        })
+        .unwrap()
+        .with_retry_config(bb_api::RetryConfig { max_retries: 0, ..Default::default() });
+        let queue = QueueService::new();
+        let report = svc.run_due_local(&api, &messages, &queue, CatchUpPolicy::SendAll, now).await.unwrap();
+        assert_eq!((report.sent, report.skipped, report.queued, report.failed), (0, 0, 0, 0));
+        assert!(queue.waiting().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_send_is_not_left_claimed() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let db = create_test_db();
+        let bus = EventBus::new(16);
+        let svc = ScheduledMessageService::new(db.clone(), bus.clone());
+        let messages = MessageService::new(db, bus);
+        let now = chrono::Utc::now();
+
+        // A server that rejects every send
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0; 8192];
+                let _ = socket.read(&mut buf).await;
+                let body = r#"{"status":400,"message":"Chat does not exist"}"#;
+                let response = format!(
+                    "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        let api = ApiClient::new(&bb_core::config::ServerConfig { address, ..Default::default() })
+            .unwrap()
+            .with_retry_config(bb_api::RetryConfig { max_retries: 0, ..Default::default() });
+
+        let once = svc.create_local("chat-1", "once", now - chrono::Duration::seconds(5)).await.unwrap();
+        let start = now - chrono::Duration::minutes(1);
+        let daily = svc
+            .create_local_recurring("chat-1", "daily", start, "FREQ=DAILY", "UTC")
+            .await
+            .unwrap();
+        let queue = QueueService::new();
+        let report = svc.run_due_local(&api, &messages, &queue, CatchUpPolicy::SendAll, now).await.unwrap();
+        assert_eq!((report.sent, report.failed), (0, 2));
+
+        let all = svc.list_from_db().unwrap();
+        let by_id = |id| all.iter().find(|m| m.id == id).unwrap();
+        assert_eq!(by_id(once.id).status, msg_status::FAILED);
+        assert!(by_id(once.id).error.is_some());
+
+        // The recurring schedule fails this run but is pending for the next
+        let daily = by_id(daily.id);
+        assert_eq!(daily.status, msg_status::PENDING);
+        assert_eq!(daily.scheduled_time(), Some(start + chrono::Duration::days(1)));
+        assert!(daily.error.is_some());
+        assert_eq!(svc.list_local().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recurring_local_schedule_moves_to_next_occurrence() {
+        let db = create_test_db();
+        let bus = EventBus::new(16);
+        let svc = ScheduledMessageService::new(db.clone(), bus.clone());
+        let messages = MessageService::new(db, bus);
+
+        // Every day at 09:00 in New York, starting two days before DST begins
+        let start = chrono::DateTime::parse_from_rfc3339("2025-03-07T12:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        let msg = svc
+            .create_local_recurring("chat-1", "standup", start, "0 9 * * *", "America/New_York")
+            .await
+            .unwrap();
+        assert_eq!(msg.scheduled_for, "2025-03-07T14:00:00+00:00");
+        assert!(svc.create_local_recurring("chat-1", "x", start, "0 9 * * *", "Mars/Olympus").await.is_err());
+
+        // The client was off over the weekend; only the latest run is sent
+        let now = chrono::DateTime::parse_from_rfc3339("2025-03-09T13:00:30Z").unwrap().with_timezone(&chrono::Utc);
+        let api = ApiClient::new(&bb_core::config::ServerConfig {
+            address: "http://127.0.0.1:1".into(),
+            ..Default::default()
+        })
+        .unwrap()
+        .with_retry_config(bb_api::RetryConfig { max_retries: 0, ..Default::default() });
+        let queue = QueueService::new();
+        let report = svc.run_due_local(&api, &messages, &queue, CatchUpPolicy::SkipAll, now).await.unwrap();
+        assert_eq!((report.skipped, report.queued), (1, 0));
+
+        let pending = svc.list_local().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].scheduled_for, "2025-03-09T13:00:00+00:00");
+        let report = svc.run_due_local(&api, &messages, &queue, CatchUpPolicy::SkipAll, now).await.unwrap();
+        assert_eq!(report.queued, 1);
+        assert_eq!(svc.list_local().unwrap()[0].scheduled_for, "2025-03-10T13:00:00+00:00");
+    }
+
+    #[test]
+    fn test_server_params() {
+        let start = chrono::DateTime::parse_from_rfc3339("2025-03-24T08:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        let params = ScheduledMessageService::server_params("c", "hi", start, Some(("FREQ=WEEKLY", "UTC"))).unwrap();
+        assert_eq!(params.scheduled_for, start.timestamp_millis());
+        assert_eq!(params.schedule.unwrap()["intervalType"], "weekly");
+        assert!(ScheduledMessageService::server_params("c", "hi", start, None).unwrap().schedule.is_none());
+        assert!(ScheduledMessageService::server_params("c", "hi", start, Some(("0 9 * * 1-5", "UTC"))).is_err());
+    }
 }
@@ -6,7 +6,7 @@
 use tracing::{info, debug};
 
 use bb_core::error::BbResult;
-use bb_models::{Database, Chat, Message, Contact};
+use bb_models::{Bookmark, BookmarkFilter, Database, Chat, Message, Contact};
 use bb_models::queries;
 
 use crate::event_bus::EventBus;
@@ -31,6 +31,12 @@ pub enum SearchResult {
         contact: Contact,
         score: f64,
     },
+    /// A bookmark whose note or tags match.
+    BookmarkResult {
+        bookmark: Bookmark,
+        message: Message,
+        score: f64,
+    },
 }
 
 impl SearchResult {
@@ -40,6 +46,7 @@ impl SearchResult {
             SearchResult::MessageResult { score, .. } => *score,
             SearchResult::ChatResult { score, .. } => *score,
             SearchResult::ContactResult { score, .. } => *score,
+            SearchResult::BookmarkResult { score, .. } => *score,
         }
     }
 }
@@ -79,6 +86,14 @@ impl SearchService {
 
         // Search messages
         let messages = self.search_messages(&query_lower, limit)?;
+        let found: std::collections::HashSet<Option<String>> = messages
+            .iter()
+            .filter_map(|r| match r {
+                SearchResult::MessageResult { message, .. } => Some(message.guid.clone()),
+                _ => None,
+            })
+            .collect();
+        let message_found = |m: &Message| found.contains(&m.guid);
         results.extend(messages);
 
         // Search chats
@@ -89,6 +104,13 @@ impl SearchService {
         let contacts = self.search_contacts(&query_lower, limit)?;
         results.extend(contacts);
 
+        // Search bookmark notes and tags, skipping messages already found by text
+        let bookmarks = self.search_bookmarks(&query_lower, limit)?;
+        results.extend(bookmarks.into_iter().filter(|r| match r {
+            SearchResult::BookmarkResult { message, .. } => !message_found(message),
+            _ => true,
+        }));
+
         // Sort by score descending
         results.sort_by(|a, b| b.score().partial_cmp(&a.score()).unwrap_or(std::cmp::Ordering::Equal));
 
@@ -144,6 +166,52 @@ impl SearchService {
             .collect())
     }
 
+    /// Search bookmarks by note and tag.
+    pub fn search_bookmarks(&self, query: &str, limit: usize) -> BbResult<Vec<SearchResult>> {
+        let conn = self.database.conn()?;
+        let query = query.to_lowercase();
+        let filter = BookmarkFilter {
+            query: Some(query.trim_start_matches('#').to_string()),
+            limit: limit as i64,
+            ..Default::default()
+        };
+
+        let mut results = Vec::new();
+        for bookmark in Bookmark::list(&conn, &filter)? {
+            let score = Self::score_bookmark_match(&bookmark, &query);
+            if score <= 0.0 {
+                continue;
+            }
+            if let Some(message) = queries::find_message_by_guid(&conn, &bookmark.message_guid)? {
+                results.push(SearchResult::BookmarkResult { bookmark, message, score });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Search messages exchanged with a person across all their handles.
+    pub fn search_person_messages(
+        &self,
+        person_id: i64,
+        query: &str,
+        limit: usize,
+    ) -> BbResult<Vec<SearchResult>> {
+        let conn = self.database.conn()?;
+        let query = query.to_lowercase();
+        let messages = queries::search_person_messages(&conn, person_id, &query, limit as i64)?;
+
+        Ok(messages
+            .into_iter()
+            .map(|msg| {
+                let score = Self::score_message_match(&msg, &query);
+                SearchResult::MessageResult {
+                    message: msg,
+                    score,
+                }
+            })
+            .collect())
+    }
+
     /// Score a message match. Exact matches and shorter messages score higher.
     fn score_message_match(msg: &Message, query: &str) -> f64 {
         let text = msg.text.as_deref().unwrap_or("").to_lowercase();
@@ -180,6 +248,26 @@ impl SearchService {
         score
     }
 
+    /// Score a bookmark match. Exact tags rank highest, then notes.
+    fn score_bookmark_match(bookmark: &Bookmark, query: &str) -> f64 {
+        let mut score = 0.0;
+
+        let tag = query.trim_start_matches('#');
+        if bookmark.tags.iter().any(|t| t == tag) {
+            score += 12.0;
+        } else if bookmark.tags.iter().any(|t| t.contains(tag)) {
+            score += 6.0;
+        }
+
+        if let Some(ref note) = bookmark.note {
+            if note.to_lowercase().contains(query) {
+                score += 4.0;
+            }
+        }
+
+        score
+    }
+
     /// Score a chat match. Display name matches rank highest.
     fn score_chat_match(chat: &Chat, query: &str) -> f64 {
         let mut score = 0.0;
@@ -313,4 +401,29 @@ mod tests {
         };
         assert!((result.score() - 7.5).abs() < f64::EPSILON);
     }
+
+    #[test]
+    fn test_search_includes_bookmarks() {
+        let db = create_test_db();
+        {
+            let conn = db.conn().unwrap();
+            conn.execute_batch(
+                "INSERT INTO messages (guid, text) VALUES ('m1', 'see attached'), ('m2', 'followup later');",
+            )
+            .unwrap();
+        }
+        let messages = crate::message::MessageService::new(db.clone(), EventBus::new(16));
+        messages.bookmark_message("m1", &["followup".into()], Some("send the contract")).unwrap();
+
+        let svc = SearchService::new(db, EventBus::new(16));
+        let results = svc.search_all("#followup", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(&results[0], SearchResult::BookmarkResult { bookmark, .. } if bookmark.message_guid == "m1"));
+
+        // A message matched by its own text is not listed twice
+        messages.bookmark_message("m2", &["followup".into()], None).unwrap();
+        let results = svc.search_all("followup", 10).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(svc.search_all("contract", 10).unwrap().len() == 1);
+    }
 }
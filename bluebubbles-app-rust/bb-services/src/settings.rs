@@ -411,6 +411,17 @@ impl SettingsService {
         config.conversation.move_to_trash = enabled;
     }
 
+    /// Days deleted items stay in the trash before being purged.
+    pub async fn trash_retention_days(&self) -> u32 {
+        self.config.read().await.conversation.trash_retention_days
+    }
+
+    /// Set how many days deleted items stay in the trash.
+    pub async fn set_trash_retention_days(&self, days: u32) {
+        let mut config = self.config.write().await;
+        config.conversation.trash_retention_days = days;
+    }
+
     /// Whether swipe-to-close is enabled for conversations.
     pub async fn swipe_to_close(&self) -> bool {
         self.config.read().await.conversation.swipe_to_close
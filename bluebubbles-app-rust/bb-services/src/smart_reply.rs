@@ -0,0 +1,557 @@
+//! Smart reply service: short reply suggestions for the latest incoming
+//! message, computed offline on the CPU.
+//!
+//! The incoming text is classified into a `ReplyIntent` by simple rules
+//! (thanks, time proposal, greeting, yes/no question, open question, or a
+//! plain statement). Each intent has three reply slots, such as yes / no /
+//! not sure. A slot is filled with the user's own wording when they often
+//! send a matching short reply, otherwise with the built-in template that
+//! their bigram model scores highest, so someone who writes "yeah" gets
+//! "Yeah" rather than "Yes!".
+//!
+//! The model is trained on the user's sent messages in the local database.
+//! Suggestions are off unless the `smartReply` setting is on (falling back
+//! to `conversation.smart_reply` while it is unset), and always off in
+//! incognito mode.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Serialize;
+use tracing::{debug, info};
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_models::models::settings::keys;
+use bb_models::{queries, Chat, Database, Settings};
+
+use crate::service::{Service, ServiceState};
+
+/// Number of recent sent messages the model is trained on.
+const TRAINING_LIMIT: i64 = 2000;
+
+/// Longest sent message, in words, that counts as a reusable reply.
+const MAX_REPLY_WORDS: usize = 6;
+
+/// Times a reply must have been sent before it is suggested verbatim.
+const MIN_REPLY_COUNT: u32 = 2;
+
+/// Weight of the bigram estimate against the unigram estimate.
+const BIGRAM_WEIGHT: f64 = 0.7;
+
+const START: &str = "<s>";
+const END: &str = "</s>";
+
+static THANKS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(thanks|thank you|thank u|thx|ty|tysm|appreciate it|much appreciated)\b").unwrap()
+});
+
+static GREETING_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(hi|hey|hello|yo|hiya|heya|howdy|good (morning|afternoon|evening))\b").unwrap()
+});
+
+static CLOCK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{1,2}(:\d{2})?\s?(am|pm)|\d{1,2}:\d{2}|noon|midnight)\b").unwrap()
+});
+
+static DAY_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(today|tonight|tomorrow|monday|tuesday|wednesday|thursday|friday|saturday|sunday|this weekend|next week)\b")
+        .unwrap()
+});
+
+static PROPOSAL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(how about|what about|let's|lets|want to|wanna|are you free|free|meet|work for you|works for you|down for|can we|should we)\b")
+        .unwrap()
+});
+
+/// Words that start a yes/no question ending in "?".
+const YES_NO_STARTERS: &[&str] = &[
+    "are", "is", "am", "do", "does", "did", "can", "could", "will", "would", "should", "shall", "have", "has",
+    "had", "was", "were", "may", "might", "want", "wanna", "isn't", "aren't", "don't", "doesn't", "didn't",
+    "won't", "can't", "wouldn't", "you", "u", "r",
+];
+
+/// Auxiliaries that, followed by a pronoun, make a yes/no question even
+/// without a "?" ("are you coming", but not "should be fine").
+const AUXILIARIES: &[&str] = &["are", "is", "did", "does", "can", "could", "would", "should", "will", "do"];
+
+const PRONOUNS: &[&str] = &["you", "u", "we", "i", "it", "he", "she", "they", "there", "that", "this"];
+
+const QUESTION_WORDS: &[&str] = &["what", "when", "where", "who", "whom", "whose", "why", "how", "which"];
+
+/// What the incoming message asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyIntent {
+    Thanks,
+    TimeProposal,
+    Greeting,
+    YesNoQuestion,
+    OpenQuestion,
+    Statement,
+}
+
+/// Where a suggestion came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplySource {
+    /// A built-in reply, picked to match the user's style.
+    Template,
+    /// A reply the user has sent before.
+    History,
+}
+
+/// One suggested reply.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SmartReply {
+    pub text: String,
+    pub source: ReplySource,
+}
+
+/// Suggestions for the latest incoming message in a chat.
+#[derive(Debug, Clone, Serialize)]
+pub struct SmartReplies {
+    pub chat_guid: String,
+    pub message_guid: String,
+    pub intent: ReplyIntent,
+    pub suggestions: Vec<SmartReply>,
+}
+
+/// One reply position for an intent: built-in replies, and the prefixes a
+/// sent message must start with to take their place.
+struct Slot {
+    templates: &'static [&'static str],
+    prefixes: &'static [&'static str],
+}
+
+const fn slot(templates: &'static [&'static str], prefixes: &'static [&'static str]) -> Slot {
+    Slot { templates, prefixes }
+}
+
+/// Reply slots for an intent. `{when}` in a template is replaced with the
+/// time the message proposed; such templates are skipped when there is none.
+fn slots(intent: ReplyIntent) -> [Slot; 3] {
+    match intent {
+        ReplyIntent::Thanks => [
+            slot(&["You're welcome!", "Of course!"], &["you're welcome", "youre welcome", "ur welcome", "of course"]),
+            slot(&["No problem!", "No worries!"], &["no problem", "no prob", "no worries", "np"]),
+            slot(&["Anytime!", "Happy to help!"], &["anytime", "any time", "happy to help", "glad to help"]),
+        ],
+        ReplyIntent::TimeProposal => [
+            slot(&["Works for me!", "Sounds good!"], &["works for me", "sounds good", "perfect", "sure"]),
+            slot(&["See you {when}!", "See you then!"], &["see you", "see ya", "cya"]),
+            slot(&["Can we do a bit later?", "Can't make it, sorry"], &["can we do", "can't make it", "cant make it"]),
+        ],
+        ReplyIntent::Greeting => [
+            slot(&["Hey!", "Hi!"], &["hey", "hi", "hello", "yo"]),
+            slot(&["How are you?", "What's up?"], &["how are you", "how's it going", "hows it going", "what's up", "whats up", "sup"]),
+            slot(&["Good to hear from you!"], &["good to hear", "long time"]),
+        ],
+        ReplyIntent::YesNoQuestion => [
+            slot(&["Yes!", "Yeah"], &["yes", "yeah", "yep", "yup", "sure", "definitely"]),
+            slot(&["No", "Nope"], &["nope", "nah", "not really", "no thanks", "no sorry"]),
+            slot(&["Not sure", "Maybe"], &["not sure", "maybe", "idk", "i don't know", "i dont know"]),
+        ],
+        ReplyIntent::OpenQuestion => [
+            slot(&["Let me check", "Let me see"], &["let me check", "let me see", "let me look"]),
+            slot(&["Good question", "Not sure yet"], &["good question", "not sure yet"]),
+            slot(&["I'll let you know", "I'll get back to you"], &["i'll let you know", "ill let you know", "i'll get back", "will let you know"]),
+        ],
+        ReplyIntent::Statement => [
+            slot(&["Ok", "Okay", "Got it"], &["ok", "okay", "got it", "kk"]),
+            slot(&["Sounds good", "Cool"], &["sounds good", "cool", "nice", "awesome", "great"]),
+            slot(&["Haha", "Lol"], &["haha", "lol", "lmao"]),
+        ],
+    }
+}
+
+/// Lowercase `text` and strip punctuation other than apostrophes, so sent
+/// messages and prefixes compare by words.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .replace('\u{2019}', "'")
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '\'' { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether normalized `text` starts with the words of `prefix`.
+fn starts_with_words(text: &str, prefix: &str) -> bool {
+    text == prefix || text.strip_prefix(prefix).is_some_and(|rest| rest.starts_with(' '))
+}
+
+/// The last question in `text`, if it asks one.
+fn last_question(text: &str) -> Option<&str> {
+    let end = text.rfind('?')?;
+    let start = text[..end].rfind(['.', '!', '?', '\n']).map_or(0, |i| i + 1);
+    Some(text[start..end].trim())
+}
+
+/// The time a message proposes, phrased to follow "See you", e.g.
+/// "tomorrow at 5pm" or "on Friday".
+fn proposed_time(lower: &str) -> Option<String> {
+    let day = DAY_RE.find(lower).map(|m| match m.as_str() {
+        day @ ("today" | "tonight" | "tomorrow" | "this weekend" | "next week") => day.to_string(),
+        weekday => {
+            let mut chars = weekday.chars();
+            let first = chars.next().map(|c| c.to_ascii_uppercase()).unwrap_or_default();
+            format!("on {first}{}", chars.as_str())
+        }
+    });
+    let clock = CLOCK_RE.find(lower).map(|m| format!("at {}", m.as_str().replace(' ', "")));
+    match (day, clock) {
+        (Some(day), Some(clock)) => Some(format!("{day} {clock}")),
+        (day, clock) => day.or(clock),
+    }
+}
+
+/// Classify an incoming message. Also returns the time it proposes, if any.
+pub fn classify(text: &str) -> (ReplyIntent, Option<String>) {
+    let lower = text.to_lowercase().replace('\u{2019}', "'");
+    let when = proposed_time(&lower);
+
+    if THANKS_RE.is_match(&lower) {
+        return (ReplyIntent::Thanks, None);
+    }
+    if when.is_some() && (lower.contains('?') || PROPOSAL_RE.is_match(&lower)) {
+        return (ReplyIntent::TimeProposal, when);
+    }
+    let words = normalize(&lower);
+    if GREETING_RE.is_match(&words) && words.split(' ').count() <= 5 {
+        return (ReplyIntent::Greeting, None);
+    }
+
+    let question = last_question(&lower).map(normalize);
+    let question = question.as_deref().or_else(|| {
+        let mut leading = words.split(' ');
+        let asks = match (leading.next(), leading.next()) {
+            (Some(first), _) if QUESTION_WORDS.contains(&first) => true,
+            (Some(first), Some(second)) => AUXILIARIES.contains(&first) && PRONOUNS.contains(&second),
+            _ => false,
+        };
+        asks.then_some(words.as_str())
+    });
+    let Some(question) = question else {
+        return (ReplyIntent::Statement, None);
+    };
+    let first = question.split(' ').next().unwrap_or_default();
+    let intent = if YES_NO_STARTERS.contains(&first) {
+        ReplyIntent::YesNoQuestion
+    } else if question.split(' ').any(|w| QUESTION_WORDS.contains(&w)) {
+        ReplyIntent::OpenQuestion
+    } else {
+        ReplyIntent::YesNoQuestion
+    };
+    (intent, None)
+}
+
+/// Word bigram model over the user's sent messages, plus counts of the
+/// short replies they send.
+#[derive(Debug, Default)]
+pub struct ReplyModel {
+    unigrams: HashMap<String, u32>,
+    bigrams: HashMap<(String, String), u32>,
+    total: u32,
+    /// Normalized short reply -> (wording as most recently sent, count).
+    replies: HashMap<String, (String, u32)>,
+}
+
+impl ReplyModel {
+    /// Train on sent message texts, newest first.
+    pub fn train<I, S>(texts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut model = Self::default();
+        for text in texts {
+            let text = text.as_ref().trim();
+            let normalized = normalize(text);
+            if normalized.is_empty() {
+                continue;
+            }
+
+            let tokens = Self::tokens(&normalized);
+            for pair in tokens.windows(2) {
+                *model.bigrams.entry((pair[0].to_string(), pair[1].to_string())).or_default() += 1;
+            }
+            for token in tokens {
+                *model.unigrams.entry(token.to_string()).or_default() += 1;
+                model.total += 1;
+            }
+
+            if normalized.split(' ').count() <= MAX_REPLY_WORDS && !normalized.contains("http") {
+                model.replies.entry(normalized).or_insert_with(|| (text.to_string(), 0)).1 += 1;
+            }
+        }
+        model
+    }
+
+    fn tokens(normalized: &str) -> Vec<&str> {
+        std::iter::once(START).chain(normalized.split(' ')).chain(std::iter::once(END)).collect()
+    }
+
+    /// Mean log-probability per word of `text` under the model; higher
+    /// means closer to how the user writes.
+    pub fn score(&self, text: &str) -> f64 {
+        let normalized = normalize(text);
+        let tokens = Self::tokens(&normalized);
+        let vocab = (self.unigrams.len() + 1) as f64;
+
+        let total: f64 = tokens
+            .windows(2)
+            .map(|pair| {
+                let unigram = (self.unigrams.get(pair[1]).copied().unwrap_or(0) as f64 + 1.0)
+                    / (self.total as f64 + vocab);
+                let context = self.unigrams.get(pair[0]).copied().unwrap_or(0);
+                let bigram = if context == 0 {
+                    0.0
+                } else {
+                    let count = self.bigrams.get(&(pair[0].to_string(), pair[1].to_string())).copied().unwrap_or(0);
+                    count as f64 / context as f64
+                };
+                (BIGRAM_WEIGHT * bigram + (1.0 - BIGRAM_WEIGHT) * unigram).ln()
+            })
+            .sum();
+        total / (tokens.len() - 1) as f64
+    }
+
+    /// Suggest three replies to `text`.
+    pub fn suggest(&self, text: &str) -> (ReplyIntent, Vec<SmartReply>) {
+        let (intent, when) = classify(text);
+        let mut suggestions: Vec<SmartReply> = Vec::new();
+        for slot in slots(intent) {
+            if let Some(reply) = self.fill(&slot, when.as_deref(), &suggestions) {
+                suggestions.push(reply);
+            }
+        }
+        (intent, suggestions)
+    }
+
+    /// Pick a reply for a slot that is not already suggested.
+    fn fill(&self, slot: &Slot, when: Option<&str>, taken: &[SmartReply]) -> Option<SmartReply> {
+        let is_new = |text: &str| taken.iter().all(|t| normalize(&t.text) != normalize(text));
+
+        let mut own: Vec<(&str, u32)> = self
+            .replies
+            .iter()
+            .filter(|(normalized, (_, count))| {
+                *count >= MIN_REPLY_COUNT && slot.prefixes.iter().any(|p| starts_with_words(normalized, p))
+            })
+            .map(|(_, (text, count))| (text.as_str(), *count))
+            .collect();
+        own.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        if let Some((text, _)) = own.into_iter().find(|(text, _)| is_new(text)) {
+            return Some(SmartReply { text: text.to_string(), source: ReplySource::History });
+        }
+
+        let mut templates: Vec<(String, f64)> = slot
+            .templates
+            .iter()
+            .filter_map(|t| match (t.contains("{when}"), when) {
+                (true, Some(when)) => Some(t.replace("{when}", when)),
+                (true, None) => None,
+                (false, _) => Some(t.to_string()),
+            })
+            .filter(|t| is_new(t))
+            .map(|t| {
+                let score = self.score(&t);
+                (t, score)
+            })
+            .collect();
+        // Stable, so ties keep the template order.
+        templates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        templates
+            .into_iter()
+            .next()
+            .map(|(text, _)| SmartReply { text, source: ReplySource::Template })
+    }
+}
+
+/// Suggests replies from the local message history.
+pub struct SmartReplyService {
+    state: ServiceState,
+    config: ConfigHandle,
+    database: Database,
+}
+
+impl SmartReplyService {
+    /// Create a new SmartReplyService.
+    pub fn new(config: ConfigHandle, database: Database) -> Self {
+        Self {
+            state: ServiceState::Created,
+            config,
+            database,
+        }
+    }
+
+    /// Whether suggestions are on: smart replies are enabled and incognito
+    /// mode is off.
+    pub async fn is_enabled(&self) -> BbResult<bool> {
+        let config = self.config.read().await;
+        if config.privacy.incognito_mode {
+            return Ok(false);
+        }
+        let conn = self.database.conn()?;
+        Ok(Settings::get_bool(&conn, keys::SMART_REPLY)?.unwrap_or(config.conversation.smart_reply))
+    }
+
+    /// Suggest replies to the latest incoming message in a chat. Returns
+    /// `None` when suggestions are off, nothing has been received, or the
+    /// user has already replied.
+    pub async fn suggest_for_chat(&self, chat_guid: &str) -> BbResult<Option<SmartReplies>> {
+        if !self.is_enabled().await? {
+            debug!("smart replies are off");
+            return Ok(None);
+        }
+
+        let conn = self.database.conn()?;
+        let chat = Chat::find_by_guid(&conn, chat_guid)?
+            .ok_or_else(|| BbError::ChatNotFound(chat_guid.to_string()))?;
+        let chat_id = chat.id.unwrap_or(0);
+
+        let Some(message) = queries::latest_incoming_text_for_chat(&conn, chat_id)? else {
+            return Ok(None);
+        };
+        let latest = queries::latest_message_for_chat(&conn, chat_id)?;
+        if latest.is_some_and(|m| m.is_from_me && m.associated_message_guid.is_none()) {
+            return Ok(None);
+        }
+
+        let model = ReplyModel::train(queries::sent_message_texts(&conn, TRAINING_LIMIT)?);
+        let (intent, suggestions) = model.suggest(message.text.as_deref().unwrap_or_default());
+        debug!("{} smart replies for {chat_guid} ({intent:?})", suggestions.len());
+
+        Ok(Some(SmartReplies {
+            chat_guid: chat_guid.to_string(),
+            message_guid: message.guid.unwrap_or_default(),
+            intent,
+            suggestions,
+        }))
+    }
+}
+
+impl Service for SmartReplyService {
+    fn name(&self) -> &str {
+        "smart_reply"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("smart reply service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("smart reply service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(replies: &[SmartReply]) -> Vec<&str> {
+        replies.iter().map(|r| r.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("Thanks so much!").0, ReplyIntent::Thanks);
+        assert_eq!(classify("hey there").0, ReplyIntent::Greeting);
+        assert_eq!(classify("are you coming").0, ReplyIntent::YesNoQuestion);
+        assert_eq!(classify("Should be fine").0, ReplyIntent::Statement);
+        assert_eq!(classify("Got the tickets. Are you coming?").0, ReplyIntent::YesNoQuestion);
+        assert_eq!(classify("you free?").0, ReplyIntent::YesNoQuestion);
+        assert_eq!(classify("Where did you park?").0, ReplyIntent::OpenQuestion);
+        assert_eq!(classify("what time works").0, ReplyIntent::OpenQuestion);
+        assert_eq!(classify("The package arrived").0, ReplyIntent::Statement);
+
+        let (intent, when) = classify("How about tomorrow at 5 PM?");
+        assert_eq!(intent, ReplyIntent::TimeProposal);
+        assert_eq!(when.as_deref(), Some("tomorrow at 5pm"));
+        assert_eq!(classify("let's meet friday").1.as_deref(), Some("on Friday"));
+        assert_eq!(classify("I was born on a friday").0, ReplyIntent::Statement);
+    }
+
+    #[test]
+    fn test_templates_without_history() {
+        let model = ReplyModel::default();
+        let (intent, replies) = model.suggest("Can you pick up milk?");
+        assert_eq!(intent, ReplyIntent::YesNoQuestion);
+        assert_eq!(texts(&replies), vec!["Yes!", "No", "Not sure"]);
+        assert!(replies.iter().all(|r| r.source == ReplySource::Template));
+
+        let (_, replies) = model.suggest("Dinner at 7:30 tonight?");
+        assert_eq!(texts(&replies), vec!["Works for me!", "See you tonight at 7:30!", "Can we do a bit later?"]);
+    }
+
+    #[test]
+    fn test_history_shapes_suggestions() {
+        let sent = ["yeah", "Yeah for sure", "yeah for sure", "nah", "ok see you", "yeah I think so"];
+        let model = ReplyModel::train(sent);
+        let (_, replies) = model.suggest("Want to grab lunch?");
+
+        assert_eq!(replies[0], SmartReply { text: "Yeah for sure".into(), source: ReplySource::History });
+        // "nah" was only sent once, so it is not reused.
+        assert_eq!(replies[1], SmartReply { text: "No".into(), source: ReplySource::Template });
+        assert_eq!(replies.len(), 3);
+
+        // With "yeah" common, the bigram model prefers "Yeah" over "Yes!".
+        let model = ReplyModel::train(["yeah", "yeah ok", "yeah later"]);
+        assert!(model.score("Yeah") > model.score("Yes!"));
+    }
+
+    #[tokio::test]
+    async fn test_suggest_for_chat_respects_settings() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        db.conn()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO handles (id, address, unique_address_service) VALUES (1, 'a@b.c', 'a@b.c/iMessage');
+                 INSERT INTO chats (id, guid, chat_identifier) VALUES (1, 'chat', 'chat');
+                 INSERT INTO messages (guid, chat_id, handle_id, is_from_me, text, date_created) VALUES
+                     ('m1', 1, NULL, 1, 'no worries', '2025-01-01T10:00:00Z'),
+                     ('m2', 1, NULL, 1, 'No worries!', '2025-01-01T10:01:00Z'),
+                     ('m3', 1, 1, 0, 'thank you!!', '2025-01-01T10:02:00Z');",
+            )
+            .unwrap();
+        let config = ConfigHandle::new(bb_core::config::AppConfig::default());
+        let service = SmartReplyService::new(config.clone(), db.clone());
+
+        assert!(service.suggest_for_chat("chat").await.unwrap().is_none());
+        config.write().await.conversation.smart_reply = true;
+
+        let replies = service.suggest_for_chat("chat").await.unwrap().unwrap();
+        assert_eq!(replies.message_guid, "m3");
+        assert_eq!(replies.intent, ReplyIntent::Thanks);
+        assert_eq!(texts(&replies.suggestions), vec!["You're welcome!", "No worries!", "Anytime!"]);
+        assert_eq!(replies.suggestions[1].source, ReplySource::History);
+        assert!(service.suggest_for_chat("missing").await.is_err());
+
+        config.write().await.privacy.incognito_mode = true;
+        assert!(service.suggest_for_chat("chat").await.unwrap().is_none());
+        config.write().await.privacy.incognito_mode = false;
+
+        // The setting the app flips wins over the config
+        Settings::set_bool(&db.conn().unwrap(), keys::SMART_REPLY, false).unwrap();
+        assert!(!service.is_enabled().await.unwrap());
+        config.write().await.conversation.smart_reply = false;
+        Settings::set_bool(&db.conn().unwrap(), keys::SMART_REPLY, true).unwrap();
+        assert!(service.is_enabled().await.unwrap());
+
+        db.conn()
+            .unwrap()
+            .execute("INSERT INTO messages (guid, chat_id, is_from_me, text, date_created) VALUES ('m4', 1, 1, 'np', '2025-01-01T10:03:00Z')", [])
+            .unwrap();
+        assert!(service.suggest_for_chat("chat").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,177 @@
+//! Snapshot service for scheduled local database snapshots.
+//!
+//! Periodically copies the live database into the snapshot directory,
+//! verifies the copy, and prunes old snapshots according to the configured
+//! hourly/daily/weekly retention. Unlike `BackupService`, nothing here
+//! leaves the machine.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn, debug};
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_models::{Database, DatabaseKey, SnapshotInfo, SnapshotStore};
+
+use crate::event_bus::{AppEvent, EventBus};
+use crate::service::{Service, ServiceState};
+
+/// How often the scheduler re-reads the config while snapshots are disabled.
+const DISABLED_POLL: Duration = Duration::from_secs(300);
+
+/// Service that takes and prunes local database snapshots.
+///
+/// Once registered, the scheduler runs while the service is running.
+pub struct SnapshotService {
+    state: ServiceState,
+    config: ConfigHandle,
+    database: Database,
+    event_bus: EventBus,
+    store: SnapshotStore,
+    /// Handle of the scheduler task started by `init`.
+    scheduler: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl SnapshotService {
+    /// Create a new SnapshotService writing into `store`.
+    pub fn new(
+        config: ConfigHandle,
+        database: Database,
+        event_bus: EventBus,
+        store: SnapshotStore,
+    ) -> Self {
+        Self {
+            state: ServiceState::Created,
+            config,
+            database,
+            event_bus,
+            store,
+            scheduler: None,
+        }
+    }
+
+    /// The snapshot store this service writes into.
+    pub fn store(&self) -> &SnapshotStore {
+        &self.store
+    }
+
+    /// Take a snapshot now and prune old ones.
+    pub async fn take_snapshot(&self) -> BbResult<SnapshotInfo> {
+        let (db_config, retention) = {
+            let config = self.config.read().await;
+            (config.database.clone(), config.database.snapshots.clone())
+        };
+        let key = DatabaseKey::from_config(&db_config)?;
+
+        let store = self.store.clone();
+        let database = self.database.clone();
+        let (snapshot, pruned) = tokio::task::spawn_blocking(move || -> BbResult<_> {
+            let snapshot = store.create(&database, key.as_ref())?;
+            let pruned = store.prune(&retention)?;
+            Ok((snapshot, pruned.len()))
+        })
+        .await
+        .map_err(|e| BbError::Internal(e.to_string()))??;
+
+        if pruned > 0 {
+            debug!("pruned {pruned} old snapshot(s)");
+        }
+        self.event_bus.emit(AppEvent::SnapshotCreated {
+            id: snapshot.id.clone(),
+            pruned,
+        });
+        Ok(snapshot)
+    }
+
+    /// List snapshots, newest first.
+    pub fn list(&self) -> BbResult<Vec<SnapshotInfo>> {
+        self.store.list()
+    }
+
+    /// The background scheduler, run from `init` until `shutdown`.
+    ///
+    /// The interval and enabled flag are re-read from config before every
+    /// snapshot, so changes apply without a restart.
+    async fn run_scheduler(service: Arc<SnapshotService>) {
+        loop {
+            let (enabled, interval_minutes) = {
+                let config = service.config.read().await;
+                let snapshots = &config.database.snapshots;
+                (snapshots.enabled, snapshots.interval_minutes)
+            };
+
+            if !enabled || interval_minutes == 0 {
+                tokio::time::sleep(DISABLED_POLL).await;
+                continue;
+            }
+
+            tokio::time::sleep(Duration::from_secs(interval_minutes * 60)).await;
+
+            if let Err(e) = service.take_snapshot().await {
+                warn!("scheduled snapshot failed: {e}");
+            }
+        }
+    }
+}
+
+impl Service for SnapshotService {
+    fn name(&self) -> &str {
+        "snapshot"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let worker = Arc::new(Self::new(
+                self.config.clone(),
+                self.database.clone(),
+                self.event_bus.clone(),
+                self.store.clone(),
+            ));
+            self.scheduler = Some(handle.spawn(Self::run_scheduler(worker)));
+        }
+        self.state = ServiceState::Running;
+        info!("snapshot service initialized ({})", self.store.dir().display());
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        if let Some(task) = self.scheduler.take() {
+            task.abort();
+        }
+        self.state = ServiceState::Stopped;
+        info!("snapshot service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bb_core::config::AppConfig;
+
+    #[tokio::test]
+    async fn test_take_snapshot_emits_event() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let database = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        let bus = EventBus::new(16);
+        let mut rx = bus.subscribe();
+
+        let service = SnapshotService::new(
+            ConfigHandle::new(AppConfig::default()),
+            database,
+            bus,
+            SnapshotStore::new(dir.path().join("snapshots")),
+        );
+
+        let snapshot = service.take_snapshot().await.unwrap();
+        assert_eq!(service.list().unwrap().len(), 1);
+        match rx.recv().await.unwrap() {
+            AppEvent::SnapshotCreated { id, pruned } => {
+                assert_eq!(id, snapshot.id);
+                assert_eq!(pruned, 0);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,524 @@
+//! Stats service for messaging analytics computed from the local database.
+//!
+//! Counting and bucketing are done in SQL by the `queries::stats_*`
+//! functions; this service only resolves names, computes response-time
+//! medians, and assembles the result into `MessagingStats`, whose JSON shape
+//! is versioned by `STATS_SCHEMA_VERSION`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use tracing::{info, debug};
+
+use bb_core::error::{BbError, BbResult};
+use bb_models::{Chat, Database};
+use bb_models::models::person;
+use bb_models::queries::{self, StatsFilter, StatsPeriod, TimelineEntry};
+
+use crate::contact::ContactService;
+use crate::event_bus::EventBus;
+use crate::export::reaction_label;
+use crate::person::PersonService;
+use crate::service::{Service, ServiceState};
+
+/// Version of the `MessagingStats` output schema. Bump on breaking changes.
+pub const STATS_SCHEMA_VERSION: u32 = 1;
+
+/// What to compute statistics over.
+#[derive(Debug, Clone)]
+pub struct StatsOptions {
+    /// Only count messages at or after this time (Unix ms).
+    pub since: Option<i64>,
+    /// Only count messages before this time (Unix ms).
+    pub until: Option<i64>,
+    /// Restrict to one chat.
+    pub chat_guid: Option<String>,
+    /// Number of chats and contacts to include in the ranked lists.
+    pub top: usize,
+    /// Offset from UTC used for day, week and hour buckets.
+    pub utc_offset_secs: i64,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self {
+            since: None,
+            until: None,
+            chat_guid: None,
+            top: 10,
+            utc_offset_secs: 0,
+        }
+    }
+}
+
+/// Messaging statistics. Field names are part of the stable JSON schema.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessagingStats {
+    pub schema_version: u32,
+    pub generated_at: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub utc_offset_secs: i64,
+    pub totals: StatsTotals,
+    /// Busiest chats first.
+    pub chats: Vec<ChatStats>,
+    /// Contacts with the most messages exchanged first.
+    pub top_contacts: Vec<ContactStats>,
+    /// The same counts rolled up per person across all their handles.
+    pub top_people: Vec<PersonStats>,
+    /// Message counts indexed `[weekday][hour]`, weekday 0 = Sunday.
+    pub hour_heatmap: Vec<Vec<i64>>,
+    /// Attachment volume per MIME class, largest first.
+    pub attachments: Vec<MimeClassStats>,
+    /// Reaction usage, most used first.
+    pub reactions: Vec<ReactionStats>,
+}
+
+/// Totals over the whole range.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatsTotals {
+    pub messages: i64,
+    pub sent: i64,
+    pub received: i64,
+    /// Share of messages sent by the user (0.0 - 1.0).
+    pub sent_ratio: f64,
+    pub chats: usize,
+    pub attachments: i64,
+    pub attachment_bytes: i64,
+    pub reactions_sent: i64,
+    pub reactions_received: i64,
+}
+
+/// Per-chat statistics.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStats {
+    pub chat_guid: String,
+    pub title: String,
+    pub messages: i64,
+    pub sent: i64,
+    pub received: i64,
+    pub sent_ratio: f64,
+    pub first_message: Option<String>,
+    pub last_message: Option<String>,
+    pub per_day: Vec<PeriodCount>,
+    pub per_week: Vec<PeriodCount>,
+}
+
+/// Message count for one day (`YYYY-MM-DD`) or week (`YYYY-Www`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodCount {
+    pub period: String,
+    pub count: i64,
+}
+
+/// Per-contact statistics.
+///
+/// `sent` and the response times only cover one-to-one chats, where it is
+/// unambiguous who a message was addressed to.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContactStats {
+    pub handle_id: i64,
+    pub person_id: Option<i64>,
+    pub address: String,
+    pub name: String,
+    pub messages: i64,
+    pub sent: i64,
+    pub received: i64,
+    /// Median time the user took to reply to this contact.
+    pub my_median_response_secs: Option<i64>,
+    /// Median time this contact took to reply to the user.
+    pub their_median_response_secs: Option<i64>,
+}
+
+/// Per-person statistics, summed over the person's handles.
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonStats {
+    pub person_id: i64,
+    pub name: String,
+    /// Number of the person's handles with messages in range.
+    pub handles: usize,
+    pub messages: i64,
+    pub sent: i64,
+    pub received: i64,
+}
+
+/// Attachment volume for one MIME class.
+#[derive(Debug, Clone, Serialize)]
+pub struct MimeClassStats {
+    pub mime_class: String,
+    pub count: i64,
+    pub bytes: i64,
+}
+
+/// Usage of one reaction type.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReactionStats {
+    pub reaction: String,
+    pub sent: i64,
+    pub received: i64,
+}
+
+/// Service computing messaging analytics from the local database.
+pub struct StatsService {
+    state: ServiceState,
+    database: Database,
+    event_bus: EventBus,
+}
+
+impl StatsService {
+    /// Create a new StatsService.
+    pub fn new(database: Database, event_bus: EventBus) -> Self {
+        Self {
+            state: ServiceState::Created,
+            database,
+            event_bus,
+        }
+    }
+
+    /// Compute statistics for the given options.
+    pub fn compute(&self, options: &StatsOptions) -> BbResult<MessagingStats> {
+        let conn = self.database.conn()?;
+
+        let chat_id = match options.chat_guid.as_deref() {
+            Some(guid) => Some(
+                queries::find_chat_by_guid(&conn, guid)?
+                    .and_then(|c| c.id)
+                    .ok_or_else(|| BbError::ChatNotFound(guid.to_string()))?,
+            ),
+            None => None,
+        };
+        let filter = StatsFilter {
+            since: options.since,
+            until: options.until,
+            chat_id,
+            utc_offset_secs: options.utc_offset_secs,
+        };
+
+        let chat_counts = queries::stats_chat_counts(&conn, &filter)?;
+        let mut totals = StatsTotals {
+            chats: chat_counts.len(),
+            ..Default::default()
+        };
+        for counts in &chat_counts {
+            totals.messages += counts.messages;
+            totals.sent += counts.sent;
+            totals.received += counts.received;
+        }
+        totals.sent_ratio = ratio(totals.sent, totals.messages);
+
+        // Per-chat series, only for the chats that make the top list
+        let top_chats: Vec<_> = chat_counts.iter().take(options.top).collect();
+        let mut per_day = group_periods(queries::stats_period_counts(&conn, &filter, StatsPeriod::Day)?);
+        let mut per_week = group_periods(queries::stats_period_counts(&conn, &filter, StatsPeriod::Week)?);
+        let mut chats = Vec::with_capacity(top_chats.len());
+        for counts in top_chats {
+            let Some(mut chat) = Chat::find_by_id(&conn, counts.chat_id)? else {
+                continue;
+            };
+            chat.participants = queries::load_chat_participants(&conn, counts.chat_id)?;
+            chats.push(ChatStats {
+                title: chat.title(),
+                chat_guid: chat.guid,
+                messages: counts.messages,
+                sent: counts.sent,
+                received: counts.received,
+                sent_ratio: ratio(counts.sent, counts.messages),
+                first_message: ms_to_rfc3339(counts.first_ms),
+                last_message: ms_to_rfc3339(counts.last_ms),
+                per_day: per_day.remove(&counts.chat_id).unwrap_or_default(),
+                per_week: per_week.remove(&counts.chat_id).unwrap_or_default(),
+            });
+        }
+
+        let (top_contacts, top_people) = self.contact_stats(&conn, &filter, options.top)?;
+
+        let hour_heatmap = queries::stats_hour_heatmap(&conn, &filter)?
+            .iter()
+            .map(|hours| hours.to_vec())
+            .collect();
+
+        let attachments: Vec<MimeClassStats> = queries::stats_attachment_volume(&conn, &filter)?
+            .into_iter()
+            .map(|(mime_class, count, bytes)| MimeClassStats { mime_class, count, bytes })
+            .collect();
+        totals.attachments = attachments.iter().map(|a| a.count).sum();
+        totals.attachment_bytes = attachments.iter().map(|a| a.bytes).sum();
+
+        let mut reactions: BTreeMap<&'static str, ReactionStats> = BTreeMap::new();
+        for (kind, from_me, count) in queries::stats_reaction_counts(&conn, &filter)? {
+            // Removals ("-love", 3xxx) are not usage
+            let Some((false, label)) = reaction_label(&kind) else {
+                continue;
+            };
+            let entry = reactions.entry(label).or_insert_with(|| ReactionStats {
+                reaction: label.to_string(),
+                sent: 0,
+                received: 0,
+            });
+            if from_me {
+                entry.sent += count;
+                totals.reactions_sent += count;
+            } else {
+                entry.received += count;
+                totals.reactions_received += count;
+            }
+        }
+        let mut reactions: Vec<ReactionStats> = reactions.into_values().collect();
+        reactions.sort_by_key(|r| std::cmp::Reverse(r.sent + r.received));
+
+        debug!("computed stats over {} messages", totals.messages);
+        Ok(MessagingStats {
+            schema_version: STATS_SCHEMA_VERSION,
+            generated_at: chrono::Utc::now().to_rfc3339(),
+            since: options.since.and_then(ms_to_rfc3339),
+            until: options.until.and_then(ms_to_rfc3339),
+            utc_offset_secs: options.utc_offset_secs,
+            totals,
+            chats,
+            top_contacts,
+            top_people,
+            hour_heatmap,
+            attachments,
+            reactions,
+        })
+    }
+
+    fn contact_stats(
+        &self,
+        conn: &rusqlite::Connection,
+        filter: &StatsFilter,
+        top: usize,
+    ) -> BbResult<(Vec<ContactStats>, Vec<PersonStats>)> {
+        let direct = queries::direct_chat_handles(conn)?;
+        let received: HashMap<i64, i64> = queries::stats_received_by_handle(conn, filter)?
+            .into_iter()
+            .collect();
+
+        let timeline = queries::stats_timeline(conn, filter)?;
+        let mut sent: HashMap<i64, i64> = HashMap::new();
+        let mut mine: HashMap<i64, Vec<i64>> = HashMap::new();
+        let mut theirs: HashMap<i64, Vec<i64>> = HashMap::new();
+        let mut prev: Option<&TimelineEntry> = None;
+        for entry in &timeline {
+            let Some(&handle_id) = direct.get(&entry.chat_id) else {
+                prev = None;
+                continue;
+            };
+            if entry.is_from_me {
+                *sent.entry(handle_id).or_default() += 1;
+            }
+            if let Some(p) = prev.filter(|p| p.chat_id == entry.chat_id) {
+                // A reply is the first message after the other side's last one
+                let gap = (entry.ms - p.ms) / 1000;
+                if entry.is_from_me && !p.is_from_me {
+                    mine.entry(handle_id).or_default().push(gap);
+                } else if !entry.is_from_me && p.is_from_me {
+                    theirs.entry(handle_id).or_default().push(gap);
+                }
+            }
+            prev = Some(entry);
+        }
+
+        let mut totals: Vec<(i64, i64)> = received
+            .keys()
+            .chain(sent.keys())
+            .copied()
+            .collect::<std::collections::HashSet<_>>()
+            .into_iter()
+            .map(|id| (id, received.get(&id).copied().unwrap_or(0) + sent.get(&id).copied().unwrap_or(0)))
+            .collect();
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        // Roll handles up into persons before the per-handle list is cut
+        let person_of = person::person_ids_by_handle(conn)?;
+        let mut per_person: HashMap<i64, PersonStats> = HashMap::new();
+        for &(handle_id, messages) in &totals {
+            let Some(&person_id) = person_of.get(&handle_id) else {
+                continue;
+            };
+            let entry = per_person.entry(person_id).or_insert_with(|| PersonStats {
+                person_id,
+                name: String::new(),
+                handles: 0,
+                messages: 0,
+                sent: 0,
+                received: 0,
+            });
+            entry.handles += 1;
+            entry.messages += messages;
+            entry.sent += sent.get(&handle_id).copied().unwrap_or(0);
+            entry.received += received.get(&handle_id).copied().unwrap_or(0);
+        }
+        let mut people: Vec<PersonStats> = per_person.into_values().collect();
+        people.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.person_id.cmp(&b.person_id)));
+        people.truncate(top);
+        let persons = PersonService::new(self.database.clone(), self.event_bus.clone());
+        for p in &mut people {
+            p.name = persons
+                .get_person(p.person_id)?
+                .map(|s| s.display_name)
+                .unwrap_or_default();
+        }
+
+        let contacts = ContactService::new(self.database.clone(), self.event_bus.clone());
+        let mut result = Vec::new();
+        for (handle_id, messages) in totals.into_iter().take(top) {
+            let Some(handle) = queries::find_handle_by_id(conn, handle_id)? else {
+                continue;
+            };
+            result.push(ContactStats {
+                handle_id,
+                person_id: person_of.get(&handle_id).copied(),
+                name: contacts.resolve_handle_name(&handle)?,
+                address: handle.address,
+                messages,
+                sent: sent.get(&handle_id).copied().unwrap_or(0),
+                received: received.get(&handle_id).copied().unwrap_or(0),
+                my_median_response_secs: mine.get_mut(&handle_id).and_then(|v| median(v)),
+                their_median_response_secs: theirs.get_mut(&handle_id).and_then(|v| median(v)),
+            });
+        }
+        Ok((result, people))
+    }
+}
+
+fn group_periods(rows: Vec<(i64, String, i64)>) -> HashMap<i64, Vec<PeriodCount>> {
+    let mut grouped: HashMap<i64, Vec<PeriodCount>> = HashMap::new();
+    for (chat_id, period, count) in rows {
+        grouped.entry(chat_id).or_default().push(PeriodCount { period, count });
+    }
+    grouped
+}
+
+fn ratio(part: i64, whole: i64) -> f64 {
+    if whole == 0 { 0.0 } else { part as f64 / whole as f64 }
+}
+
+fn median(values: &mut [i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    })
+}
+
+fn ms_to_rfc3339(ms: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp_millis(ms).map(|d| d.to_rfc3339())
+}
+
+impl Service for StatsService {
+    fn name(&self) -> &str {
+        "stats"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("stats service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("stats service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [30, 10, 20]), Some(20));
+        assert_eq!(median(&mut [40, 10, 20, 30]), Some(25));
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        {
+            let conn = db.conn().unwrap();
+            conn.execute_batch(
+                "INSERT INTO handles (id, address, service, unique_address_service)
+                     VALUES (1, '+15550001', 'iMessage', '+15550001/iMessage');
+                 INSERT INTO chats (id, guid, display_name) VALUES (1, 'chat-1', 'Alice');
+                 INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1);
+                 INSERT INTO messages (guid, chat_id, handle_id, is_from_me, date_created, has_attachments)
+                     VALUES ('m1', 1, 1, 0, '1700000000000', 1),
+                            ('m2', 1, 0, 1, '1700000060000', 0),
+                            ('m3', 1, 0, 1, '1700000120000', 0),
+                            ('m4', 1, 1, 0, '1700000420000', 0);
+                 INSERT INTO messages (guid, chat_id, handle_id, is_from_me, date_created,
+                                       associated_message_guid, associated_message_type)
+                     VALUES ('r1', 1, 0, 1, '1700000500000', 'p:0/m1', 'love'),
+                            ('r2', 1, 0, 1, '1700000510000', 'p:0/m1', '-love');
+                 INSERT INTO attachments (guid, message_id, mime_type, total_bytes)
+                     VALUES ('a1', 1, 'image/png', 2048);",
+            )
+            .unwrap();
+        }
+
+        let service = StatsService::new(db, EventBus::new(16));
+        let stats = service.compute(&StatsOptions::default()).unwrap();
+
+        assert_eq!(stats.schema_version, STATS_SCHEMA_VERSION);
+        assert_eq!(stats.totals.messages, 4);
+        assert_eq!((stats.totals.sent, stats.totals.received), (2, 2));
+        assert_eq!(stats.totals.reactions_sent, 1);
+        assert_eq!(stats.chats[0].title, "Alice");
+        assert_eq!(stats.chats[0].per_day, vec![PeriodCount { period: "2023-11-14".into(), count: 4 }]);
+
+        let alice = &stats.top_contacts[0];
+        assert_eq!(alice.address, "+15550001");
+        assert_eq!((alice.sent, alice.received), (2, 2));
+        assert_eq!(alice.my_median_response_secs, Some(60));
+        assert_eq!(alice.their_median_response_secs, Some(300));
+
+        assert_eq!(stats.attachments[0].mime_class, "image");
+        assert_eq!(stats.attachments[0].bytes, 2048);
+        assert_eq!(stats.reactions[0].reaction, "loved");
+        assert_eq!(stats.hour_heatmap.len(), 7);
+        assert_eq!(stats.hour_heatmap[2][22], 4);
+    }
+
+    #[test]
+    fn test_person_rollup() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        {
+            let conn = db.conn().unwrap();
+            conn.execute_batch(
+                "INSERT INTO handles (id, address, service, unique_address_service, normalized_address)
+                     VALUES (1, '+16502530000', 'iMessage', '+16502530000/iMessage', '+16502530000'),
+                            (2, '+16502530000', 'SMS', '+16502530000/SMS', '+16502530000');
+                 INSERT INTO chats (id, guid) VALUES (1, 'iMessage;-;+16502530000'), (2, 'SMS;-;+16502530000');
+                 INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1), (2, 2);
+                 INSERT INTO messages (guid, chat_id, handle_id, is_from_me, date_created)
+                     VALUES ('m1', 1, 1, 0, '1700000000000'),
+                            ('m2', 1, 0, 1, '1700000060000'),
+                            ('m3', 2, 2, 0, '1700000120000');",
+            )
+            .unwrap();
+            person::link_persons(&conn).unwrap();
+        }
+
+        let stats = StatsService::new(db, EventBus::new(16))
+            .compute(&StatsOptions::default())
+            .unwrap();
+        assert_eq!(stats.top_contacts.len(), 2);
+        assert_eq!(stats.top_people.len(), 1);
+        let person = &stats.top_people[0];
+        assert_eq!((person.handles, person.messages), (2, 3));
+        assert_eq!((person.sent, person.received), (1, 2));
+        assert_eq!(stats.top_contacts[0].person_id, Some(person.person_id));
+    }
+}
@@ -0,0 +1,499 @@
+//! Trash service: the Recently Deleted view for chats and messages.
+//!
+//! Soft-deleted chats and messages (those with `date_deleted` set) are
+//! listed with their deletion time, can be restored one by one, and are
+//! purged for good once they have been in the trash longer than the
+//! configured window. Nothing is purged automatically while
+//! `conversation.move_to_trash` is off or the window is zero days; the
+//! trash can still be emptied by hand.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::Serialize;
+use tracing::{info, warn, debug};
+
+use bb_core::config::ConfigHandle;
+use bb_core::error::{BbError, BbResult};
+use bb_models::{Attachment, Chat, Database, Message};
+use bb_models::queries;
+
+use crate::cache::CacheService;
+use crate::event_bus::{AppEvent, EventBus};
+use crate::util::timestamp_ms;
+use crate::retention::remove_cached_files;
+use crate::service::{Service, ServiceState};
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// How often the scheduler purges expired items.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// What kind of item is in the trash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashKind {
+    Chat,
+    Message,
+}
+
+/// One deleted chat or message.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashItem {
+    pub kind: TrashKind,
+    /// GUID of the chat or message.
+    pub guid: String,
+    /// Chat the item belongs to (the chat itself for chats).
+    pub chat_guid: String,
+    /// Chat title.
+    pub title: String,
+    /// Message text, for messages.
+    pub preview: Option<String>,
+    pub date_deleted: String,
+    /// When the item will be purged, if its deletion time is known.
+    pub purge_after: Option<String>,
+}
+
+/// What a purge removed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeReport {
+    pub chats_purged: usize,
+    pub messages_purged: usize,
+    pub attachments_deleted: usize,
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+}
+
+/// Service for listing, restoring and purging soft-deleted items.
+pub struct TrashService {
+    state: ServiceState,
+    config: ConfigHandle,
+    database: Database,
+    event_bus: EventBus,
+    cache: CacheService,
+}
+
+impl TrashService {
+    /// Create a new TrashService removing purged files under `cache_dir`.
+    pub fn new(config: ConfigHandle, database: Database, event_bus: EventBus, cache_dir: std::path::PathBuf) -> Self {
+        Self {
+            state: ServiceState::Created,
+            config,
+            cache: CacheService::new(event_bus.clone(), cache_dir),
+            database,
+            event_bus,
+        }
+    }
+
+    /// Days items stay in the trash before they are purged, or None when
+    /// nothing is purged automatically (`move_to_trash` off, or a zero-day
+    /// window).
+    pub async fn retention_days(&self) -> Option<u32> {
+        let config = self.config.read().await;
+        let days = config.conversation.trash_retention_days;
+        (config.conversation.move_to_trash && days > 0).then_some(days)
+    }
+
+    /// List deleted chats and messages, most recently deleted first.
+    pub async fn list(&self) -> BbResult<Vec<TrashItem>> {
+        let days = self.retention_days().await;
+        self.list_with_window(days)
+    }
+
+    /// List deleted items, computing purge times from a `days` window.
+    /// Without a window, items have no purge time.
+    pub fn list_with_window(&self, days: Option<u32>) -> BbResult<Vec<TrashItem>> {
+        let conn = self.database.conn()?;
+        let purge_after = |deleted: &str| {
+            let days = days?;
+            timestamp_ms(deleted)
+                .and_then(|ms| chrono::DateTime::from_timestamp_millis(ms + i64::from(days) * DAY_MS))
+                .map(|d| d.to_rfc3339())
+        };
+
+        let mut items = Vec::new();
+        for mut chat in queries::deleted_chats(&conn)? {
+            let deleted = chat.date_deleted.clone().unwrap_or_default();
+            if let Some(id) = chat.id {
+                chat.participants = queries::load_chat_participants(&conn, id)?;
+            }
+            items.push(TrashItem {
+                kind: TrashKind::Chat,
+                guid: chat.guid.clone(),
+                title: chat.title(),
+                chat_guid: chat.guid,
+                preview: None,
+                purge_after: purge_after(&deleted),
+                date_deleted: deleted,
+            });
+        }
+
+        let mut chats: HashMap<i64, Chat> = HashMap::new();
+        for msg in queries::deleted_messages(&conn)? {
+            let (Some(guid), Some(chat_id)) = (msg.guid.clone(), msg.chat_id) else {
+                continue;
+            };
+            let chat = match chats.entry(chat_id) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => {
+                    let Some(mut chat) = Chat::find_by_id(&conn, chat_id)? else {
+                        continue;
+                    };
+                    chat.participants = queries::load_chat_participants(&conn, chat_id)?;
+                    e.insert(chat)
+                }
+            };
+            let deleted = msg.date_deleted.clone().unwrap_or_default();
+            items.push(TrashItem {
+                kind: TrashKind::Message,
+                guid,
+                chat_guid: chat.guid.clone(),
+                title: chat.title(),
+                preview: msg.text.clone(),
+                purge_after: purge_after(&deleted),
+                date_deleted: deleted,
+            });
+        }
+
+        items.sort_by_key(|i| std::cmp::Reverse(timestamp_ms(&i.date_deleted).unwrap_or(0)));
+        Ok(items)
+    }
+
+    /// Move a chat to the trash.
+    pub fn trash_chat(&self, guid: &str) -> BbResult<()> {
+        let conn = self.database.conn()?;
+        let chat = Chat::find_by_guid(&conn, guid)?
+            .ok_or_else(|| BbError::ChatNotFound(guid.to_string()))?;
+        Chat::soft_delete(&conn, chat.id.unwrap_or_default(), &Utc::now().to_rfc3339())?;
+        info!("moved chat to trash: {guid}");
+        self.event_bus.emit(AppEvent::ChatDeleted {
+            chat_guid: guid.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Move a message to the trash.
+    pub fn trash_message(&self, guid: &str) -> BbResult<()> {
+        let conn = self.database.conn()?;
+        let msg = Message::find_by_guid(&conn, guid)?
+            .ok_or_else(|| BbError::MessageNotFound(guid.to_string()))?;
+        Message::soft_delete(&conn, guid, &Utc::now().to_rfc3339())?;
+        info!("moved message to trash: {guid}");
+        self.event_bus.emit(AppEvent::MessageUpdated {
+            message_guid: guid.to_string(),
+            chat_guid: chat_guid(&conn, msg.chat_id)?,
+        });
+        Ok(())
+    }
+
+    /// Restore a chat or message from the trash. Returns what was restored.
+    pub fn restore(&self, guid: &str) -> BbResult<TrashKind> {
+        let conn = self.database.conn()?;
+
+        if let Some(chat) = Chat::find_by_guid(&conn, guid)?.filter(|c| c.date_deleted.is_some()) {
+            Chat::undelete(&conn, chat.id.unwrap_or_default())?;
+            info!("restored chat from trash: {guid}");
+            self.event_bus.emit(AppEvent::ChatUpdated {
+                chat_guid: guid.to_string(),
+            });
+            return Ok(TrashKind::Chat);
+        }
+
+        if let Some(msg) = Message::find_by_guid(&conn, guid)? {
+            if Message::restore(&conn, guid)? {
+                info!("restored message from trash: {guid}");
+                self.event_bus.emit(AppEvent::MessageUpdated {
+                    message_guid: guid.to_string(),
+                    chat_guid: chat_guid(&conn, msg.chat_id)?,
+                });
+                return Ok(TrashKind::Message);
+            }
+        }
+
+        Err(BbError::InvalidInput(format!("{guid} is not in the trash")))
+    }
+
+    /// Permanently delete one trashed chat or message.
+    pub fn purge(&self, guid: &str) -> BbResult<PurgeReport> {
+        let item = self
+            .list_with_window(None)?
+            .into_iter()
+            .find(|i| i.guid == guid)
+            .ok_or_else(|| BbError::InvalidInput(format!("{guid} is not in the trash")))?;
+        self.purge_items(&[item])
+    }
+
+    /// Permanently delete everything in the trash.
+    pub fn purge_all(&self) -> BbResult<PurgeReport> {
+        let items = self.list_with_window(None)?;
+        self.purge_items(&items)
+    }
+
+    /// Purge items that have been in the trash longer than the window.
+    /// Does nothing when automatic purging is off.
+    pub async fn purge_expired(&self) -> BbResult<PurgeReport> {
+        match self.retention_days().await {
+            Some(days) => self.purge_expired_at(Utc::now().timestamp_millis(), days),
+            None => {
+                debug!("trash: automatic purge is off");
+                Ok(PurgeReport::default())
+            }
+        }
+    }
+
+    /// Purge items deleted more than `days` before `now_ms` (Unix ms).
+    /// Items with an unreadable deletion time are kept, and a zero-day
+    /// window purges nothing.
+    pub fn purge_expired_at(&self, now_ms: i64, days: u32) -> BbResult<PurgeReport> {
+        if days == 0 {
+            return Ok(PurgeReport::default());
+        }
+        let cutoff = now_ms - i64::from(days) * DAY_MS;
+        let expired: Vec<TrashItem> = self
+            .list_with_window(Some(days))?
+            .into_iter()
+            .filter(|i| timestamp_ms(&i.date_deleted).is_some_and(|ms| ms <= cutoff))
+            .collect();
+        if expired.is_empty() {
+            debug!("trash: nothing to purge");
+            return Ok(PurgeReport::default());
+        }
+        self.purge_items(&expired)
+    }
+
+    /// Periodically purge expired items in the background.
+    ///
+    /// The window is re-read from config before every sweep, so changes
+    /// apply without a restart.
+    pub fn start_scheduler(service: Arc<TrashService>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match service.purge_expired().await {
+                    Ok(report) if report.chats_purged + report.messages_purged > 0 => {
+                        info!(
+                            "trash: purged {} chats and {} messages",
+                            report.chats_purged, report.messages_purged
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("scheduled trash purge failed: {e}"),
+                }
+                tokio::time::sleep(PURGE_INTERVAL).await;
+            }
+        })
+    }
+
+    fn purge_items(&self, items: &[TrashItem]) -> BbResult<PurgeReport> {
+        let mut report = PurgeReport::default();
+        let mut conn = self.database.conn()?;
+        let mut files: Vec<Attachment> = Vec::new();
+
+        let tx = conn.transaction().map_err(|e| BbError::Database(e.to_string()))?;
+        for item in items {
+            match item.kind {
+                TrashKind::Chat => {
+                    let Some(chat_id) = Chat::find_by_guid(&tx, &item.guid)?.and_then(|c| c.id) else {
+                        continue;
+                    };
+                    for msg in queries::all_messages_for_chat(&tx, chat_id)? {
+                        if let Some(id) = msg.id {
+                            files.extend(queries::load_attachments_for_message(&tx, id)?);
+                        }
+                        if !msg.is_reaction() {
+                            report.messages_purged += 1;
+                        }
+                    }
+                    exec(&tx, "DELETE FROM attachments WHERE message_id IN (SELECT id FROM messages WHERE chat_id = ?1)", chat_id)?;
+                    exec(&tx, "DELETE FROM message_tombstones WHERE chat_id = ?1", chat_id)?;
+                    exec(&tx, "DELETE FROM bookmark_tags WHERE message_guid IN (SELECT guid FROM messages WHERE chat_id = ?1)", chat_id)?;
+                    exec(&tx, "DELETE FROM bookmarks WHERE message_guid IN (SELECT guid FROM messages WHERE chat_id = ?1)", chat_id)?;
+                    exec(&tx, "DELETE FROM messages WHERE chat_id = ?1", chat_id)?;
+                    exec(&tx, "DELETE FROM chat_handle_join WHERE chat_id = ?1", chat_id)?;
+                    exec(&tx, "DELETE FROM chat_folder_members WHERE chat_id = ?1", chat_id)?;
+                    exec(&tx, "DELETE FROM chats WHERE id = ?1", chat_id)?;
+                    report.chats_purged += 1;
+                }
+                TrashKind::Message => {
+                    // The message and any reactions targeting it
+                    let ids: Vec<i64> = {
+                        let mut stmt = tx
+                            .prepare(
+                                "SELECT id FROM messages
+                                 WHERE guid = ?1 OR associated_message_guid = ?1
+                                    OR associated_message_guid LIKE '%/' || ?1
+                                    OR associated_message_guid LIKE '%:' || ?1",
+                            )
+                            .map_err(|e| BbError::Database(e.to_string()))?;
+                        let rows = stmt
+                            .query_map([&item.guid], |row| row.get(0))
+                            .map_err(|e| BbError::Database(e.to_string()))?;
+                        rows.filter_map(|r| r.ok()).collect()
+                    };
+                    for id in &ids {
+                        files.extend(queries::load_attachments_for_message(&tx, *id)?);
+                        exec(&tx, "DELETE FROM attachments WHERE message_id = ?1", *id)?;
+                        exec(&tx, "DELETE FROM bookmark_tags WHERE message_guid = (SELECT guid FROM messages WHERE id = ?1)", *id)?;
+                        exec(&tx, "DELETE FROM bookmarks WHERE message_guid = (SELECT guid FROM messages WHERE id = ?1)", *id)?;
+                        exec(&tx, "DELETE FROM messages WHERE id = ?1", *id)?;
+                    }
+                    tx.execute("DELETE FROM message_tombstones WHERE message_guid = ?1", [&item.guid])
+                        .map_err(|e| BbError::Database(e.to_string()))?;
+                    report.messages_purged += 1;
+                }
+            }
+        }
+        tx.commit().map_err(|e| BbError::Database(e.to_string()))?;
+
+        // Files go only after the rows are gone, so a failed commit leaves both
+        report.attachments_deleted = files.len();
+        for attachment in &files {
+            let (count, bytes) = remove_cached_files(&self.cache, attachment, false);
+            report.files_removed += count;
+            report.bytes_freed += bytes;
+        }
+
+        info!(
+            "trash purge: {} chats, {} messages, {} files ({} bytes)",
+            report.chats_purged, report.messages_purged, report.files_removed, report.bytes_freed
+        );
+        Ok(report)
+    }
+}
+
+fn exec(conn: &Connection, sql: &str, id: i64) -> BbResult<()> {
+    conn.execute(sql, [id]).map_err(|e| BbError::Database(e.to_string()))?;
+    Ok(())
+}
+
+fn chat_guid(conn: &Connection, chat_id: Option<i64>) -> BbResult<String> {
+    Ok(match chat_id {
+        Some(id) => Chat::find_by_id(conn, id)?.map(|c| c.guid).unwrap_or_default(),
+        None => String::new(),
+    })
+}
+
+impl Service for TrashService {
+    fn name(&self) -> &str {
+        "trash"
+    }
+    fn state(&self) -> ServiceState {
+        self.state
+    }
+    fn init(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Running;
+        info!("trash service initialized");
+        Ok(())
+    }
+    fn shutdown(&mut self) -> BbResult<()> {
+        self.state = ServiceState::Stopped;
+        info!("trash service stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, TrashService) {
+        let dir = TempDir::new().unwrap();
+        let db = Database::init(&dir.path().join("test.db"), &Default::default()).unwrap();
+        {
+            let conn = db.conn().unwrap();
+            conn.execute_batch(
+                "INSERT INTO chats (id, guid, display_name) VALUES (1, 'chat-1', 'Alice'), (2, 'chat-2', 'Bob');
+                 INSERT INTO messages (id, guid, chat_id, text, date_created)
+                     VALUES (1, 'm1', 1, 'hello', '1700000000000'),
+                            (2, 'm2', 1, 'bye', '1700000060000'),
+                            (3, 'm3', 2, 'hi bob', '1700000000000');
+                 INSERT INTO messages (id, guid, chat_id, date_created, associated_message_guid, associated_message_type)
+                     VALUES (4, 'r1', 1, '1700000070000', 'p:0/m2', 'love');
+                 INSERT INTO attachments (guid, message_id, mime_type) VALUES ('a1', 2, 'image/png');",
+            )
+            .unwrap();
+        }
+        let config = ConfigHandle::new(bb_core::config::AppConfig::default());
+        let svc = TrashService::new(config, db, EventBus::new(16), dir.path().join("cache"));
+        (dir, svc)
+    }
+
+    #[test]
+    fn test_trash_restore_and_purge() {
+        let (_dir, svc) = setup();
+        svc.trash_message("m2").unwrap();
+        svc.trash_chat("chat-2").unwrap();
+
+        let items = svc.list_with_window(Some(30)).unwrap();
+        assert_eq!(items.len(), 2);
+        let msg = items.iter().find(|i| i.kind == TrashKind::Message).unwrap();
+        assert_eq!((msg.guid.as_str(), msg.title.as_str()), ("m2", "Alice"));
+        assert_eq!(msg.preview.as_deref(), Some("bye"));
+        assert!(msg.purge_after.is_some());
+
+        assert_eq!(svc.restore("chat-2").unwrap(), TrashKind::Chat);
+        assert!(svc.restore("chat-2").is_err());
+        assert_eq!(svc.list_with_window(Some(30)).unwrap().len(), 1);
+
+        // Not yet expired
+        let now = Utc::now().timestamp_millis();
+        let report = svc.purge_expired_at(now, 30).unwrap();
+        assert_eq!(report.messages_purged, 0);
+
+        // Past the window, the message goes along with its reaction and attachment
+        let report = svc.purge_expired_at(now + 31 * DAY_MS, 30).unwrap();
+        assert_eq!((report.messages_purged, report.attachments_deleted), (1, 1));
+        let conn = svc.database.conn().unwrap();
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM messages WHERE chat_id = 1", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 1);
+    }
+
+    #[test]
+    fn test_purge_chat() {
+        let (_dir, svc) = setup();
+        svc.trash_chat("chat-1").unwrap();
+        let report = svc.purge("chat-1").unwrap();
+        assert_eq!((report.chats_purged, report.messages_purged), (1, 2));
+        assert!(svc.list_with_window(None).unwrap().is_empty());
+
+        let conn = svc.database.conn().unwrap();
+        assert!(Chat::find_by_guid(&conn, "chat-1").unwrap().is_none());
+        assert!(Chat::find_by_guid(&conn, "chat-2").unwrap().is_some());
+        assert!(svc.purge("chat-2").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_window_follows_move_to_trash() {
+        let (_dir, svc) = setup();
+        assert_eq!(svc.retention_days().await, None);
+        svc.config.write().await.conversation.move_to_trash = true;
+        assert_eq!(svc.retention_days().await, Some(30));
+        svc.config.write().await.conversation.trash_retention_days = 0;
+        assert_eq!(svc.retention_days().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_default_config_purges_nothing() {
+        let (_dir, svc) = setup();
+        svc.trash_message("m2").unwrap();
+        svc.trash_chat("chat-2").unwrap();
+        // Deleted long ago
+        svc.database.conn().unwrap().execute_batch(
+            "UPDATE messages SET date_deleted = '1000000000000' WHERE date_deleted IS NOT NULL;
+             UPDATE chats SET date_deleted = '1000000000000' WHERE date_deleted IS NOT NULL;",
+        ).unwrap();
+
+        let report = svc.purge_expired().await.unwrap();
+        assert_eq!((report.chats_purged, report.messages_purged), (0, 0));
+        let items = svc.list().await.unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|i| i.purge_after.is_none()));
+        assert_eq!(svc.purge_expired_at(Utc::now().timestamp_millis(), 0).unwrap().messages_purged, 0);
+    }
+}
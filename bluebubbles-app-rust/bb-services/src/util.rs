@@ -0,0 +1,44 @@
+//! Helpers for reading stored message fields, shared by the services that
+//! walk message rows (export, retention, trash, OTP detection).
+
+/// Strip the `p:N/` or `bp:` prefix from an associated message GUID.
+pub(crate) fn strip_part_prefix(guid: &str) -> &str {
+    if let Some(rest) = guid.strip_prefix("bp:") {
+        return rest;
+    }
+    if guid.starts_with("p:") {
+        if let Some((_, rest)) = guid.split_once('/') {
+            return rest;
+        }
+    }
+    guid
+}
+
+/// Parse a stored message date (Unix ms or RFC 3339) into Unix ms.
+pub(crate) fn timestamp_ms(value: &str) -> Option<i64> {
+    if let Ok(ms) = value.parse::<i64>() {
+        return Some(ms);
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|d| d.timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_part_prefix() {
+        assert_eq!(strip_part_prefix("p:1/ABC"), "ABC");
+        assert_eq!(strip_part_prefix("bp:ABC"), "ABC");
+        assert_eq!(strip_part_prefix("ABC"), "ABC");
+    }
+
+    #[test]
+    fn test_timestamp_ms() {
+        assert_eq!(timestamp_ms("1700000000000"), Some(1_700_000_000_000));
+        assert_eq!(timestamp_ms("2023-11-14T22:13:20Z"), Some(1_700_000_000_000));
+        assert_eq!(timestamp_ms("yesterday"), None);
+    }
+}
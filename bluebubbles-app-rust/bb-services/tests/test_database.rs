@@ -219,7 +219,7 @@ fn chat_list_with_details_includes_unread_count() {
     common::seed_test_data(&db);
 
     let conn = db.conn().unwrap();
-    let details = queries::list_chats_with_details(&conn, 0, 100, true).unwrap();
+    let details = queries::list_chats_with_details(&conn, 0, 100, true, None).unwrap();
 
     assert!(!details.is_empty(), "should return chats with details");
 
@@ -429,6 +429,40 @@ fn latest_message_for_chat() {
     assert!(latest.is_some(), "chat with messages should have a latest message");
 }
 
+#[test]
+fn latest_incoming_text_for_chat() {
+    let (db, _dir) = common::create_test_db();
+    common::seed_test_data(&db);
+
+    let conn = db.conn().unwrap();
+    let chat_id = queries::find_chat_by_guid(&conn, "iMessage;-;chat-1")
+        .unwrap()
+        .unwrap()
+        .id
+        .unwrap();
+    conn.execute(
+        "INSERT INTO messages (guid, chat_id, handle_id, text, is_from_me, date_created, associated_message_guid, associated_message_type)
+         VALUES ('msg-react', ?1, 1, 'Loved a message', 0, '2024-01-01T23:00:00Z', 'msg-0081', 'love')",
+        [chat_id],
+    )
+    .unwrap();
+
+    let latest = queries::latest_incoming_text_for_chat(&conn, chat_id).unwrap().unwrap();
+    assert_eq!(latest.guid.as_deref(), Some("msg-0081"));
+    assert!(!latest.is_from_me);
+}
+
+#[test]
+fn sent_message_texts() {
+    let (db, _dir) = common::create_test_db();
+    common::seed_test_data(&db);
+
+    let conn = db.conn().unwrap();
+    let texts = queries::sent_message_texts(&conn, 5).unwrap();
+    assert_eq!(texts.len(), 5);
+    assert!(texts.iter().all(|t| t.starts_with("Test message")));
+}
+
 #[test]
 fn message_count_for_chat() {
     let (db, _dir) = common::create_test_db();
@@ -628,6 +662,24 @@ fn delete_all_contacts() {
     assert_eq!(after, 0, "all contacts should be deleted");
 }
 
+#[test]
+fn delete_synced_contacts_keeps_local() {
+    let (db, _dir) = common::create_test_db();
+    common::seed_test_data(&db);
+
+    let conn = db.conn().unwrap();
+    let mut local = bb_models::vcard::parse("BEGIN:VCARD\nFN:Imported\nUID:abc\nEND:VCARD\n")
+        .unwrap()
+        .remove(0);
+    local.save(&conn).unwrap();
+
+    queries::delete_synced_contacts(&conn).unwrap();
+
+    let remaining = queries::list_contacts(&conn).unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert!(remaining[0].is_local());
+}
+
 // ---- Settings key-value store ----
 
 #[test]
@@ -888,3 +940,42 @@ fn messages_around_date_returns_surrounding_messages() {
         "should return messages around the target date"
     );
 }
+
+// ---- Link preview cache ----
+
+#[test]
+fn link_preview_cache_replaces_and_expires() {
+    use bb_models::models::payload_data::UrlPreviewData;
+    use bb_models::CachedLinkPreview;
+
+    let (db, _dir) = common::create_test_db();
+    let conn = db.conn().unwrap();
+
+    let mut cached = CachedLinkPreview {
+        url: "https://example.com/a".into(),
+        preview: None,
+        fetched_at: 1_000,
+    };
+    cached.save(&conn).unwrap();
+    assert_eq!(CachedLinkPreview::find(&conn, &cached.url).unwrap(), Some(cached.clone()));
+
+    cached.preview = Some(UrlPreviewData {
+        url: Some(cached.url.clone()),
+        title: Some("Page A".into()),
+        ..Default::default()
+    });
+    cached.fetched_at = 5_000;
+    cached.save(&conn).unwrap();
+    CachedLinkPreview {
+        url: "https://example.com/b".into(),
+        preview: None,
+        fetched_at: 2_000,
+    }
+    .save(&conn)
+    .unwrap();
+
+    assert_eq!(CachedLinkPreview::delete_older_than(&conn, 3_000).unwrap(), 1);
+    assert!(CachedLinkPreview::find(&conn, "https://example.com/b").unwrap().is_none());
+    let found = CachedLinkPreview::find(&conn, &cached.url).unwrap().unwrap();
+    assert_eq!(found.preview.unwrap().title.as_deref(), Some("Page A"));
+}
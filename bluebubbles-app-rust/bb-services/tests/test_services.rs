@@ -291,6 +291,45 @@ fn message_service_list_and_count() {
     assert_eq!(count, 10);
 }
 
+#[test]
+fn message_service_bookmarks_with_tags_and_notes() {
+    let (db, _dir) = common::create_test_db();
+    common::seed_test_data(&db);
+    let bus = common::create_test_event_bus();
+    let svc = MessageService::new(db.clone(), bus);
+
+    let saved = svc
+        .bookmark_message("msg-0001", &["todo".into()], Some("reply to this later"))
+        .unwrap();
+    assert_eq!(saved.chat_guid, "iMessage;-;chat-1");
+    assert!(saved.message.is_bookmarked);
+    svc.bookmark_message("msg-0002", &["todo".into(), "Work".into()], None).unwrap();
+    svc.add_bookmark_tags("msg-0011", &["work".into()]).unwrap();
+    assert!(svc.bookmark_message("missing", &[], None).is_err());
+
+    let todo = svc
+        .list_bookmarks(&bb_models::BookmarkFilter { tag: Some("todo".into()), ..Default::default() })
+        .unwrap();
+    assert_eq!(todo.len(), 2);
+
+    let conn = db.conn().unwrap();
+    let chat_1 = bb_models::queries::find_chat_by_guid(&conn, "iMessage;-;chat-1").unwrap().unwrap();
+    let in_chat = svc
+        .list_bookmarks(&bb_models::BookmarkFilter { chat_id: chat_1.id, ..Default::default() })
+        .unwrap();
+    assert_eq!(in_chat.len(), 2);
+
+    let updated = svc.remove_bookmark_tags("msg-0002", &["todo".into()]).unwrap();
+    assert_eq!(updated.bookmark.tags, vec!["work"]);
+    let cleared = svc.set_bookmark_note("msg-0001", None).unwrap();
+    assert!(cleared.bookmark.note.is_none());
+    assert_eq!(svc.bookmark_tags().unwrap(), vec![("todo".into(), 1), ("work".into(), 2)]);
+
+    assert!(svc.remove_bookmark("msg-0001").unwrap());
+    assert!(svc.get_bookmark("msg-0001").unwrap().is_none());
+    assert!(!svc.find_message("msg-0001").unwrap().unwrap().is_bookmarked);
+}
+
 // ---- ContactService phone matching ----
 
 #[test]
@@ -394,6 +433,7 @@ async fn queue_enqueue_dequeue_lifecycle() {
         attempts: 0,
         max_attempts: 5,
         last_attempt: None,
+        ..Default::default()
     };
 
     svc.enqueue(msg).await;
@@ -418,6 +458,7 @@ async fn queue_remove_by_id() {
         attempts: 0,
         max_attempts: 3,
         last_attempt: None,
+        ..Default::default()
     })
     .await;
 
@@ -439,6 +480,7 @@ async fn queue_clear() {
             attempts: 0,
             max_attempts: 3,
             last_attempt: None,
+            ..Default::default()
         })
         .await;
     }
@@ -493,6 +535,7 @@ fn queued_message_retry_delay_exponential_backoff() {
                 attempts,
                 max_attempts: 10,
                 last_attempt: None,
+                ..Default::default()
             }
             .retry_delay()
         })
@@ -516,6 +559,7 @@ fn queued_message_should_retry_respects_max_attempts() {
         attempts: 2,
         max_attempts: 5,
         last_attempt: None,
+        ..Default::default()
     };
     assert!(under.should_retry());
 
@@ -538,6 +582,7 @@ async fn queue_stats() {
         attempts: 0,
         max_attempts: 3,
         last_attempt: None,
+        ..Default::default()
     })
     .await;
 
@@ -14,7 +14,7 @@ use bb_models::{Chat, Message, Contact, ThemeStruct, Settings};
 use bb_models::queries;
 
 use crate::state::AppState;
-use crate::otp_detector::{detect_otp, OtpDetection};
+use bb_services::otp::{detect_otp, OtpCode, OtpDetection};
 use crate::mcp_state::McpState;
 
 // ─── Serializable response types for the frontend ───────────────────────────
@@ -42,6 +42,8 @@ pub struct ChatWithPreview {
     pub latest_message_date: Option<String>,
     pub latest_message_is_from_me: bool,
     pub participant_names: Vec<String>,
+    /// Unsent composer draft, if any.
+    pub draft: Option<bb_services::chat::Draft>,
 }
 
 /// Sync result returned from full sync.
@@ -101,6 +103,25 @@ fn extract_local_ips(data: Option<&serde_json::Value>) -> (Vec<String>, Vec<Stri
     (ipv4, ipv6)
 }
 
+/// Remember the iCloud and iMessage addresses the server detected, so
+/// mentions of any of them count as mentions of the user.
+fn save_my_addresses(state: &AppState, data: Option<&serde_json::Value>) {
+    let Some(data) = data else { return };
+    let detected = [
+        data.get("detected_icloud").or_else(|| data.get("detectedIcloud")),
+        data.get("detected_imessage").or_else(|| data.get("detectediMessage")),
+    ];
+    let result = state.database.conn().and_then(|conn| {
+        for address in detected.into_iter().flatten().filter_map(|v| v.as_str()) {
+            queries::save_my_address(&conn, address)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        debug!("failed to save detected addresses (non-fatal): {e}");
+    }
+}
+
 async fn try_localhost_ping(address: &str, auth_key: &str) -> bool {
     let server_config = ServerConfig {
         address: address.to_string(),
@@ -270,6 +291,8 @@ pub async fn connect(
     // Parse server info from response
     let info = parse_server_info(response.data.as_ref(), Some(api_root_str), Some(password));
 
+    save_my_addresses(&state, response.data.as_ref());
+
     // Attempt localhost detection (best-effort)
     let (local_ipv4s, local_ipv6s) = extract_local_ips(response.data.as_ref());
     if let Err(e) = apply_localhost_override(&state, local_ipv4s, local_ipv6s).await {
@@ -355,6 +378,7 @@ pub async fn try_auto_connect(state: State<'_, AppState>) -> Result<Option<Serve
     }
 
     let info = parse_server_info(response.data.as_ref(), Some(api_root_str), Some(password));
+    save_my_addresses(&state, response.data.as_ref());
     let (local_ipv4s, local_ipv6s) = extract_local_ips(response.data.as_ref());
     if let Err(e) = apply_localhost_override(&state, local_ipv4s, local_ipv6s).await {
         debug!("localhost detection skipped/failed: {e}");
@@ -382,12 +406,22 @@ pub async fn get_chats(
     state: State<'_, AppState>,
     page: u32,
     limit: u32,
+    folder_id: Option<i64>,
 ) -> Result<Vec<ChatWithPreview>, String> {
-    debug!("get_chats page={page} limit={limit}");
+    debug!("get_chats page={page} limit={limit} folder={folder_id:?}");
 
     let conn = state.database.conn().map_err(|e| e.to_string())?;
     let offset = (page * limit) as i64;
 
+    let folder = match folder_id {
+        Some(id) => Some(
+            bb_models::ChatFolder::find_by_id(&conn, id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("folder not found: {id}"))?,
+        ),
+        None => None,
+    };
+
     // Link contacts to handles so display names resolve correctly
     let _ = queries::link_contacts_to_handles(&conn);
 
@@ -396,6 +430,7 @@ pub async fn get_chats(
         offset,
         limit as i64,
         false,
+        folder.as_ref(),
     )
     .map_err(|e| e.to_string())?;
 
@@ -409,12 +444,14 @@ pub async fn get_chats(
                 .map(|h| h.display_name())
                 .collect();
 
+            let draft = bb_services::chat::Draft::from_chat(&detail.chat);
             ChatWithPreview {
                 chat: detail.chat,
                 latest_message_text: detail.last_message_text,
                 latest_message_date: detail.last_message_date,
                 latest_message_is_from_me: detail.last_message_is_from_me,
                 participant_names,
+                draft,
             }
         })
         .collect();
@@ -424,6 +461,58 @@ pub async fn get_chats(
 
 /// Refresh chats from the server API, save to local DB, and return the updated list.
 /// This is used for background polling to pick up new messages and unread states.
+/// How far back a refresh looks for messages to hand to the notification
+/// rules. Older ones arrived while the app was closed and don't notify.
+const NEW_MESSAGE_WINDOW_MS: i64 = 10 * 60 * 1000;
+
+/// Most messages fetched per chat when its latest message changes.
+const NEW_MESSAGE_LIMIT: i64 = 50;
+
+/// When `latest` is a message the chat didn't have yet, the date (epoch ms)
+/// of the chat's previous latest message. `None` for known messages and
+/// chats with no stored messages.
+fn previous_latest_if_new(conn: &rusqlite::Connection, chat: &Chat, latest: &Message) -> Option<i64> {
+    let guid = latest.guid.as_deref()?;
+    if Message::find_by_guid(conn, guid).ok()?.is_some() {
+        return None;
+    }
+    let previous = queries::latest_message_for_chat(conn, chat.id?).ok()??;
+    let date = previous.date_created?;
+    date.parse::<i64>()
+        .ok()
+        .or_else(|| chrono::DateTime::parse_from_rfc3339(&date).ok().map(|d| d.timestamp_millis()))
+}
+
+/// Fetch the messages that arrived in each chat since its previous latest
+/// message and run them through the action handler, which saves them and
+/// emits `MessageReceived` for the notification listener.
+async fn receive_new_messages(state: &AppState, api: &ApiClient, arrived: Vec<(String, i64)>) {
+    if arrived.is_empty() {
+        return;
+    }
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let handler = bb_services::ActionHandler::new(state.database.clone(), event_bus);
+    let cutoff = chrono::Utc::now().timestamp_millis() - NEW_MESSAGE_WINDOW_MS;
+    for (chat_guid, since) in arrived {
+        let messages = match api
+            .get_chat_messages(&chat_guid, 0, NEW_MESSAGE_LIMIT, "ASC", &["chat", "handle", "attachment"], None, Some(since.max(cutoff)))
+            .await
+        {
+            Ok(messages) => messages,
+            Err(e) => {
+                debug!("failed to fetch new messages for {chat_guid} (non-fatal): {e}");
+                continue;
+            }
+        };
+        for data in messages {
+            let event = bb_socket::SocketEvent { event_type: bb_socket::SocketEventType::NewMessage, data };
+            if let Err(e) = handler.handle_event(event).await {
+                warn!("failed to handle new message in {chat_guid}: {e}");
+            }
+        }
+    }
+}
+
 #[tauri::command]
 pub async fn refresh_chats(
     state: State<'_, AppState>,
@@ -439,6 +528,8 @@ pub async fn refresh_chats(
     let server_page_size = 500u32;
     let mut server_offset = 0u32;
     let mut total_saved = 0u32;
+    // Chats whose latest message is new, with the previous latest date (ms)
+    let mut arrived: Vec<(String, i64)> = Vec::new();
 
     loop {
         let chat_query = serde_json::json!({
@@ -470,6 +561,9 @@ pub async fn refresh_chats(
                     if let Some(last_msg_json) = chat_json.get("lastMessage") {
                         if !last_msg_json.is_null() {
                             if let Ok(mut msg) = Message::from_server_map(last_msg_json) {
+                                if let Some(since) = previous_latest_if_new(&conn, &chat, &msg) {
+                                    arrived.push((chat.guid.clone(), since));
+                                }
                                 msg.chat_id = chat.id;
                                 let _ = msg.save(&conn);
                             }
@@ -492,11 +586,13 @@ pub async fn refresh_chats(
 
     debug!("refresh_chats: saved {total_saved} chats from server (fetched up to offset {server_offset})");
 
+    receive_new_messages(&state, &api, arrived).await;
+
     // Link contacts to handles so display names resolve correctly
     let _ = queries::link_contacts_to_handles(&conn);
 
     // Now read back from local DB to get consistent ChatWithPreview format
-    let chats = queries::list_chats_with_details(&conn, 0, limit as i64, false)
+    let chats = queries::list_chats_with_details(&conn, 0, limit as i64, false, None)
         .map_err(|e| e.to_string())?;
 
     let previews = chats
@@ -509,12 +605,14 @@ pub async fn refresh_chats(
                 .map(|h| h.display_name())
                 .collect();
 
+            let draft = bb_services::chat::Draft::from_chat(&detail.chat);
             ChatWithPreview {
                 chat: detail.chat,
                 latest_message_text: detail.last_message_text,
                 latest_message_date: detail.last_message_date,
                 latest_message_is_from_me: detail.last_message_is_from_me,
                 participant_names,
+                draft,
             }
         })
         .collect();
@@ -522,32 +620,25 @@ pub async fn refresh_chats(
     Ok(previews)
 }
 
-/// Mark a chat as read both on the server and in the local DB.
+/// Mark a chat as read locally and on the server. Offline, the change is
+/// queued in the outbox and sent when the connection returns.
 #[tauri::command]
 pub async fn mark_chat_read(
     state: State<'_, AppState>,
     chat_guid: String,
 ) -> Result<(), String> {
     debug!("mark_chat_read chat={chat_guid}");
+    submit_chat_mutation(&state, &chat_guid, bb_models::ChatMutation::MarkRead).await
+}
 
-    // Update local DB first
-    let conn = state.database.conn().map_err(|e| e.to_string())?;
-    conn.execute(
-        "UPDATE chats SET has_unread_message = 0 WHERE guid = ?1",
-        [&chat_guid],
-    )
-    .map_err(|e| e.to_string())?;
-
-    // Call the server API to mark as read
-    let api = state.api_client().await.map_err(|e| e.to_string())?;
-    let encoded_guid = percent_encode_path(&chat_guid);
-    let path = format!("/chat/{}/read", encoded_guid);
-    let body = serde_json::json!({});
-    // Fire and forget - don't fail the command if the server call fails
-    if let Err(e) = api.post_json::<serde_json::Value>(&path, &body).await {
-        debug!("mark_chat_read server call failed (non-fatal): {e}");
-    }
-
+/// Record the chat open in the window, or `None` when no chat is open.
+/// Messages arriving in it don't post notifications.
+#[tauri::command]
+pub async fn set_active_chat(
+    state: State<'_, AppState>,
+    chat_guid: Option<String>,
+) -> Result<(), String> {
+    *state.active_chat.write().await = chat_guid;
     Ok(())
 }
 
@@ -603,30 +694,50 @@ pub async fn update_chat(
     Ok(())
 }
 
-/// Mark a chat as unread in the local DB and on the server.
+/// Mark a chat as unread locally and on the server, through the outbox.
 #[tauri::command]
 pub async fn mark_chat_unread(
     state: State<'_, AppState>,
     chat_guid: String,
 ) -> Result<(), String> {
     debug!("mark_chat_unread chat={chat_guid}");
+    submit_chat_mutation(&state, &chat_guid, bb_models::ChatMutation::MarkUnread).await
+}
+
+/// Apply a chat change locally and send it, or queue it when there is no
+/// API client yet.
+async fn submit_chat_mutation(
+    state: &AppState,
+    chat_guid: &str,
+    mutation: bb_models::ChatMutation,
+) -> Result<(), String> {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let outbox = bb_services::OutboxService::new(state.database.clone(), event_bus);
+    match state.api_client().await {
+        Ok(api) => outbox.submit(&api, chat_guid, mutation).await.map(|_| ()),
+        Err(_) => outbox.enqueue(chat_guid, mutation).map(|_| ()),
+    }
+    .map_err(|e| e.to_string())
+}
 
+/// List chat changes waiting to be sent to the server.
+#[tauri::command]
+pub async fn get_outbox(state: State<'_, AppState>) -> Result<Vec<bb_models::OutboxEntry>, String> {
     let conn = state.database.conn().map_err(|e| e.to_string())?;
-    conn.execute(
-        "UPDATE chats SET has_unread_message = 1 WHERE guid = ?1",
-        [&chat_guid],
-    ).map_err(|e| e.to_string())?;
+    bb_models::OutboxEntry::pending(&conn).map_err(|e| e.to_string())
+}
 
-    // Call server API to mark as unread
+/// Send queued chat changes now.
+#[tauri::command]
+pub async fn replay_outbox(
+    state: State<'_, AppState>,
+) -> Result<bb_services::outbox::ReplayReport, String> {
     let api = state.api_client().await.map_err(|e| e.to_string())?;
-    let encoded_guid = percent_encode_path(&chat_guid);
-    let path = format!("/chat/{}/unread", encoded_guid);
-    let body = serde_json::json!({});
-    if let Err(e) = api.post_json::<serde_json::Value>(&path, &body).await {
-        debug!("mark_chat_unread server call failed (non-fatal): {e}");
-    }
-
-    Ok(())
+    let event_bus = state.registry.read().await.event_bus().clone();
+    bb_services::OutboxService::new(state.database.clone(), event_bus)
+        .replay(&api)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 // ─── Message commands ────────────────────────────────────────────────────────
@@ -767,42 +878,66 @@ pub async fn send_message(
 ) -> Result<Message, String> {
     info!("send_message to={chat_guid} text_len={}", text.len());
 
-    let api = state.api_client().await.map_err(|e| e.to_string())?;
-
-    let mut body = serde_json::json!({
-        "chatGuid": chat_guid,
-        "message": text,
-        "method": "private-api",
-    });
-
-    if let Some(eff) = effect {
-        body["effectId"] = serde_json::Value::String(eff);
-    }
-
-    let response = api
-        .post_json::<serde_json::Value>("/message/text", &body)
+    // Without a client, or when the server can't be reached, the message
+    // waits for the connection. The send carries the queued temp GUID, so a
+    // send the server did receive is reconciled instead of sent again.
+    let api = state.api_client().await.ok();
+    let (msg, _) = message_service(&state)
+        .await
+        .send_text_or_queue(api.as_ref(), &state.send_queue, &chat_guid, &text, "private-api", effect)
         .await
         .map_err(|e| format!("send failed: {e}"))?;
+    Ok(msg)
+}
 
-    let data = response
-        .data
-        .as_ref()
-        .ok_or_else(|| "no data in send response".to_string())?;
+// ─── Draft commands ──────────────────────────────────────────────────────────
 
-    let mut msg = Message::from_server_map(data).map_err(|e| e.to_string())?;
+/// Save the composer draft for a chat. Empty text with no attachments clears it.
+#[tauri::command]
+pub async fn save_draft(
+    state: State<'_, AppState>,
+    chat_guid: String,
+    text: Option<String>,
+    attachment_paths: Vec<String>,
+) -> Result<(), String> {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::chat::ChatService::new(state.database.clone(), event_bus);
+    service
+        .save_draft(&chat_guid, text.as_deref(), &attachment_paths)
+        .map_err(|e| e.to_string())
+}
 
-    // Save the sent message to local DB so it persists across view reloads.
-    // Look up the chat's local ID to associate the message correctly.
-    let conn = state.database.conn().map_err(|e| e.to_string())?;
-    if let Some(chat) = Chat::find_by_guid(&conn, &chat_guid).map_err(|e| e.to_string())? {
-        msg.chat_id = chat.id;
-    }
-    // Best-effort save; don't fail the send if DB write fails
-    if let Err(e) = msg.save(&conn) {
-        debug!("failed to save sent message to local DB (non-fatal): {e}");
-    }
+/// Load the composer draft for a chat.
+#[tauri::command]
+pub async fn get_draft(
+    state: State<'_, AppState>,
+    chat_guid: String,
+) -> Result<Option<bb_services::chat::Draft>, String> {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::chat::ChatService::new(state.database.clone(), event_bus);
+    service.load_draft(&chat_guid).map_err(|e| e.to_string())
+}
 
-    Ok(msg)
+/// Clear the composer draft for a chat.
+#[tauri::command]
+pub async fn clear_draft(
+    state: State<'_, AppState>,
+    chat_guid: String,
+) -> Result<(), String> {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::chat::ChatService::new(state.database.clone(), event_bus);
+    service.clear_draft(&chat_guid).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// List all chats that have an unsent draft.
+#[tauri::command]
+pub async fn get_drafts(
+    state: State<'_, AppState>,
+) -> Result<Vec<bb_services::chat::Draft>, String> {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::chat::ChatService::new(state.database.clone(), event_bus);
+    let chats = service.chats_with_drafts().map_err(|e| e.to_string())?;
+    Ok(chats.iter().filter_map(bb_services::chat::Draft::from_chat).collect())
 }
 
 #[tauri::command]
@@ -814,9 +949,23 @@ pub async fn search_messages(
     debug!("search_messages query={query} chat={chat_guid:?}");
 
     let conn = state.database.conn().map_err(|e| e.to_string())?;
-    let messages = queries::search_messages(&conn, &query, 50)
+    let mut messages = queries::search_messages(&conn, &query, 50)
         .map_err(|e| e.to_string())?;
 
+    // Include messages whose bookmark note or tags match
+    let filter = bb_models::BookmarkFilter {
+        query: Some(query.trim_start_matches('#').to_string()),
+        limit: 50,
+        ..Default::default()
+    };
+    for bookmark in bb_models::Bookmark::list(&conn, &filter).map_err(|e| e.to_string())? {
+        if !messages.iter().any(|m| m.guid.as_deref() == Some(bookmark.message_guid.as_str())) {
+            messages.extend(
+                queries::find_message_by_guid(&conn, &bookmark.message_guid).map_err(|e| e.to_string())?,
+            );
+        }
+    }
+
     // If a chat_guid filter is provided, filter results
     if let Some(ref guid) = chat_guid {
         if let Some(chat) = Chat::find_by_guid(&conn, guid).map_err(|e| e.to_string())? {
@@ -832,6 +981,369 @@ pub async fn search_messages(
     Ok(messages)
 }
 
+// ─── Bookmark commands ───────────────────────────────────────────────────────
+
+async fn message_service(state: &AppState) -> bb_services::message::MessageService {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    bb_services::message::MessageService::new(state.database.clone(), event_bus)
+        .with_link_previews(state.link_previews.clone())
+}
+
+/// List bookmarks, optionally filtered by tag, chat and text.
+#[tauri::command]
+pub async fn get_bookmarks(
+    state: State<'_, AppState>,
+    tag: Option<String>,
+    chat_guid: Option<String>,
+    query: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<bb_services::message::BookmarkedMessage>, String> {
+    let chat_id = match chat_guid {
+        Some(guid) => {
+            let conn = state.database.conn().map_err(|e| e.to_string())?;
+            Some(
+                Chat::find_by_guid(&conn, &guid)
+                    .map_err(|e| e.to_string())?
+                    .and_then(|c| c.id)
+                    .ok_or_else(|| format!("chat not found: {guid}"))?,
+            )
+        }
+        None => None,
+    };
+    let filter = bb_models::BookmarkFilter {
+        tag,
+        chat_id,
+        query,
+        limit: limit.unwrap_or(100),
+    };
+    message_service(&state).await.list_bookmarks(&filter).map_err(|e| e.to_string())
+}
+
+/// Bookmark a message, adding tags and optionally setting its note.
+#[tauri::command]
+pub async fn bookmark_message(
+    state: State<'_, AppState>,
+    message_guid: String,
+    tags: Vec<String>,
+    note: Option<String>,
+) -> Result<bb_services::message::BookmarkedMessage, String> {
+    message_service(&state)
+        .await
+        .bookmark_message(&message_guid, &tags, note.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Set or clear the note on a bookmark.
+#[tauri::command]
+pub async fn set_bookmark_note(
+    state: State<'_, AppState>,
+    message_guid: String,
+    note: Option<String>,
+) -> Result<bb_services::message::BookmarkedMessage, String> {
+    message_service(&state)
+        .await
+        .set_bookmark_note(&message_guid, note.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Add tags to a bookmark.
+#[tauri::command]
+pub async fn add_bookmark_tags(
+    state: State<'_, AppState>,
+    message_guid: String,
+    tags: Vec<String>,
+) -> Result<bb_services::message::BookmarkedMessage, String> {
+    message_service(&state)
+        .await
+        .add_bookmark_tags(&message_guid, &tags)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove tags from a bookmark.
+#[tauri::command]
+pub async fn remove_bookmark_tags(
+    state: State<'_, AppState>,
+    message_guid: String,
+    tags: Vec<String>,
+) -> Result<bb_services::message::BookmarkedMessage, String> {
+    message_service(&state)
+        .await
+        .remove_bookmark_tags(&message_guid, &tags)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove a bookmark. Returns whether the message was bookmarked.
+#[tauri::command]
+pub async fn remove_bookmark(state: State<'_, AppState>, message_guid: String) -> Result<bool, String> {
+    message_service(&state)
+        .await
+        .remove_bookmark(&message_guid)
+        .map_err(|e| e.to_string())
+}
+
+/// A bookmark tag and how many bookmarks carry it.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct BookmarkTagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+/// List bookmark tags in use.
+#[tauri::command]
+pub async fn get_bookmark_tags(state: State<'_, AppState>) -> Result<Vec<BookmarkTagCount>, String> {
+    let tags = message_service(&state).await.bookmark_tags().map_err(|e| e.to_string())?;
+    Ok(tags
+        .into_iter()
+        .map(|(tag, count)| BookmarkTagCount { tag, count })
+        .collect())
+}
+
+// ─── Chat folder commands ────────────────────────────────────────────────────
+
+async fn folder_service(state: &AppState) -> bb_services::FolderService {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    bb_services::FolderService::new(state.database.clone(), event_bus)
+}
+
+/// List chat folders with their chat and unread counts.
+#[tauri::command]
+pub async fn get_chat_folders(state: State<'_, AppState>) -> Result<Vec<queries::FolderSummary>, String> {
+    folder_service(&state).await.list_folders().map_err(|e| e.to_string())
+}
+
+/// Create a chat folder. With a rule it is a smart folder.
+#[tauri::command]
+pub async fn create_chat_folder(
+    state: State<'_, AppState>,
+    name: String,
+    color: Option<String>,
+    rule: Option<bb_models::FolderRule>,
+) -> Result<bb_models::ChatFolder, String> {
+    let service = folder_service(&state).await;
+    match rule {
+        Some(rule) => service.create_smart_folder(&name, rule, color.as_deref()),
+        None => service.create_folder(&name, color.as_deref()),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Rename a folder, change its color, or replace its smart rule. Omitted
+/// fields are left alone; an empty color clears it.
+#[tauri::command]
+pub async fn update_chat_folder(
+    state: State<'_, AppState>,
+    folder_id: i64,
+    name: Option<String>,
+    color: Option<String>,
+    rule: Option<bb_models::FolderRule>,
+) -> Result<bb_models::ChatFolder, String> {
+    let service = folder_service(&state).await;
+    if let Some(name) = name {
+        service.rename_folder(folder_id, &name).map_err(|e| e.to_string())?;
+    }
+    if let Some(rule) = rule {
+        service.set_folder_rule(folder_id, rule).map_err(|e| e.to_string())?;
+    }
+    if let Some(color) = color {
+        let color = Some(color.as_str()).filter(|c| !c.is_empty());
+        service.set_folder_color(folder_id, color).map_err(|e| e.to_string())?;
+    }
+    let conn = state.database.conn().map_err(|e| e.to_string())?;
+    bb_models::ChatFolder::find_by_id(&conn, folder_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("folder not found: {folder_id}"))
+}
+
+/// Set the display order of folders.
+#[tauri::command]
+pub async fn reorder_chat_folders(state: State<'_, AppState>, folder_ids: Vec<i64>) -> Result<(), String> {
+    folder_service(&state).await.reorder_folders(&folder_ids).map_err(|e| e.to_string())
+}
+
+/// Delete a folder. Its chats are kept.
+#[tauri::command]
+pub async fn delete_chat_folder(state: State<'_, AppState>, folder_id: i64) -> Result<bool, String> {
+    folder_service(&state).await.delete_folder(folder_id).map_err(|e| e.to_string())
+}
+
+/// Add a chat to a folder.
+#[tauri::command]
+pub async fn add_chat_to_folder(
+    state: State<'_, AppState>,
+    folder_id: i64,
+    chat_guid: String,
+) -> Result<(), String> {
+    folder_service(&state)
+        .await
+        .add_chat(folder_id, &chat_guid)
+        .map_err(|e| e.to_string())
+}
+
+/// Remove a chat from a folder. Returns whether it was in the folder.
+#[tauri::command]
+pub async fn remove_chat_from_folder(
+    state: State<'_, AppState>,
+    folder_id: i64,
+    chat_guid: String,
+) -> Result<bool, String> {
+    folder_service(&state)
+        .await
+        .remove_chat(folder_id, &chat_guid)
+        .map_err(|e| e.to_string())
+}
+
+/// List the folders a chat belongs to, including matching smart folders.
+#[tauri::command]
+pub async fn get_folders_for_chat(
+    state: State<'_, AppState>,
+    chat_guid: String,
+) -> Result<Vec<bb_models::ChatFolder>, String> {
+    folder_service(&state)
+        .await
+        .folders_for_chat(&chat_guid)
+        .map_err(|e| e.to_string())
+}
+
+// ─── Notification rule commands ──────────────────────────────────────────────
+
+fn notification_service(state: &AppState) -> bb_services::notification::NotificationService {
+    bb_services::notification::NotificationService::new(state.config.clone()).with_database(state.database.clone())
+}
+
+/// List notification rules in evaluation order.
+#[tauri::command]
+pub async fn get_notification_rules(state: State<'_, AppState>) -> Result<Vec<bb_models::NotificationRule>, String> {
+    notification_service(&state).list_rules().map_err(|e| e.to_string())
+}
+
+/// Add a notification rule at the end of the order.
+#[tauri::command]
+pub async fn add_notification_rule(
+    state: State<'_, AppState>,
+    name: String,
+    conditions: Vec<bb_models::RuleCondition>,
+    action: String,
+) -> Result<bb_models::NotificationRule, String> {
+    let action = bb_models::RuleAction::parse(&action).map_err(|e| e.to_string())?;
+    notification_service(&state)
+        .add_rule(&name, conditions, action)
+        .map_err(|e| e.to_string())
+}
+
+/// Change a rule's name, conditions, action or enabled flag. Omitted
+/// fields are left alone.
+#[tauri::command]
+pub async fn update_notification_rule(
+    state: State<'_, AppState>,
+    rule_id: i64,
+    name: Option<String>,
+    conditions: Option<Vec<bb_models::RuleCondition>>,
+    action: Option<String>,
+    enabled: Option<bool>,
+) -> Result<bb_models::NotificationRule, String> {
+    let service = notification_service(&state);
+    let mut rule = service
+        .find_rule(&rule_id.to_string())
+        .map_err(|e| e.to_string())?
+        .filter(|r| r.id == Some(rule_id))
+        .ok_or_else(|| format!("notification rule not found: {rule_id}"))?;
+    if let Some(name) = name {
+        rule.name = name.trim().to_string();
+    }
+    if let Some(conditions) = conditions {
+        rule.conditions = conditions;
+    }
+    if let Some(action) = action {
+        rule.action = bb_models::RuleAction::parse(&action).map_err(|e| e.to_string())?;
+    }
+    if let Some(enabled) = enabled {
+        rule.enabled = enabled;
+    }
+    service.save_rule(&mut rule).map_err(|e| e.to_string())?;
+    Ok(rule)
+}
+
+/// Move a rule to `position` (0-based). Returns the new order.
+#[tauri::command]
+pub async fn move_notification_rule(
+    state: State<'_, AppState>,
+    rule_id: i64,
+    position: usize,
+) -> Result<Vec<bb_models::NotificationRule>, String> {
+    notification_service(&state).move_rule(rule_id, position).map_err(|e| e.to_string())
+}
+
+/// Delete a notification rule.
+#[tauri::command]
+pub async fn delete_notification_rule(state: State<'_, AppState>, rule_id: i64) -> Result<bool, String> {
+    notification_service(&state).delete_rule(rule_id).map_err(|e| e.to_string())
+}
+
+/// Get the quiet hours.
+#[tauri::command]
+pub async fn get_quiet_hours(state: State<'_, AppState>) -> Result<bb_models::QuietHours, String> {
+    notification_service(&state).quiet_hours().map_err(|e| e.to_string())
+}
+
+/// Replace the quiet hours.
+#[tauri::command]
+pub async fn set_quiet_hours(state: State<'_, AppState>, quiet_hours: bb_models::QuietHours) -> Result<(), String> {
+    notification_service(&state).set_quiet_hours(&quiet_hours).map_err(|e| e.to_string())
+}
+
+/// Decide how to notify for a received message, holding it for its
+/// chat's digest when the rules say so. Returns null when the message
+/// isn't in the local database.
+#[tauri::command]
+pub async fn evaluate_notification(
+    state: State<'_, AppState>,
+    message_guid: String,
+    chat_guid: String,
+) -> Result<Option<bb_services::notification::NotificationDecision>, String> {
+    let event = bb_services::AppEvent::MessageReceived { message_guid, chat_guid, is_from_me: false };
+    let decided = notification_service(&state).process_event(&event).await.map_err(|e| e.to_string())?;
+    Ok(decided.map(|(_, decision)| decision))
+}
+
+/// Get the digest settings.
+#[tauri::command]
+pub async fn get_digest_settings(state: State<'_, AppState>) -> Result<bb_models::DigestSettings, String> {
+    notification_service(&state).digest_settings().map_err(|e| e.to_string())
+}
+
+/// Replace the digest settings.
+#[tauri::command]
+pub async fn set_digest_settings(
+    state: State<'_, AppState>,
+    settings: bb_models::DigestSettings,
+) -> Result<(), String> {
+    notification_service(&state).set_digest_settings(&settings).map_err(|e| e.to_string())
+}
+
+/// Take every held digest now instead of waiting for the window.
+#[tauri::command]
+pub async fn flush_notification_digests(
+    state: State<'_, AppState>,
+) -> Result<Vec<bb_services::notification::NotificationDigest>, String> {
+    notification_service(&state).flush_all_digests().map_err(|e| e.to_string())
+}
+
+// ─── Smart reply commands ────────────────────────────────────────────────────
+
+/// Suggest replies to the latest incoming message in a chat. Returns null
+/// when smart replies are off (or incognito mode is on), or when the latest
+/// message is your own.
+#[tauri::command]
+pub async fn get_smart_replies(
+    state: State<'_, AppState>,
+    chat_guid: String,
+) -> Result<Option<bb_services::smart_reply::SmartReplies>, String> {
+    bb_services::SmartReplyService::new(state.config.clone(), state.database.clone())
+        .suggest_for_chat(&chat_guid)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 // ─── Contact commands ────────────────────────────────────────────────────────
 
 #[tauri::command]
@@ -1147,6 +1659,9 @@ pub async fn send_attachment_message(
     if let Err(e) = msg.save(&conn) {
         debug!("failed to save attachment message to DB (non-fatal): {e}");
     }
+    if let Err(e) = queries::remove_draft_attachment(&conn, &chat_guid, &file_path) {
+        debug!("failed to update draft after send (non-fatal): {e}");
+    }
 
     info!("attachment sent successfully");
     Ok(msg)
@@ -1248,6 +1763,204 @@ pub async fn download_attachment(
     Ok(data_uri)
 }
 
+/// Parse a contact-card (vCard) attachment for preview.
+#[tauri::command]
+pub async fn get_contact_card(
+    state: State<'_, AppState>,
+    attachment_guid: String,
+) -> Result<Vec<bb_services::contact::ContactCard>, String> {
+    let contacts = load_contact_card(&state, &attachment_guid).await?;
+    Ok(contacts.iter().map(bb_services::contact::ContactCard::from).collect())
+}
+
+/// Save a contact-card (vCard) attachment into local contacts.
+#[tauri::command]
+pub async fn save_contact_card(
+    state: State<'_, AppState>,
+    attachment_guid: String,
+) -> Result<bb_services::contact::ImportSummary, String> {
+    info!("save_contact_card guid={attachment_guid}");
+    let contacts = load_contact_card(&state, &attachment_guid).await?;
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::contact::ContactService::new(state.database.clone(), event_bus);
+    service.import_contacts(contacts).map_err(|e| e.to_string())
+}
+
+async fn load_contact_card(state: &AppState, attachment_guid: &str) -> Result<Vec<Contact>, String> {
+    let api = state.api_client().await.map_err(|e| e.to_string())?;
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::attachment::AttachmentService::new(
+        state.database.clone(),
+        event_bus,
+        state.cache_dir.clone(),
+    );
+    service
+        .load_contact_card(&api, attachment_guid)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ─── Person commands ─────────────────────────────────────────────────────────
+
+async fn person_service(state: &AppState) -> bb_services::PersonService {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    bb_services::PersonService::new(state.database.clone(), event_bus)
+}
+
+/// List people with their linked handles, refreshing automatic links first.
+#[tauri::command]
+pub async fn get_people(
+    state: State<'_, AppState>,
+) -> Result<Vec<bb_services::person::PersonSummary>, String> {
+    let service = person_service(&state).await;
+    service.relink().map_err(|e| e.to_string())?;
+    service.list_people().map_err(|e| e.to_string())
+}
+
+/// Find the person owning an address on any service.
+#[tauri::command]
+pub async fn get_person_for_address(
+    state: State<'_, AppState>,
+    address: String,
+) -> Result<Option<bb_services::person::PersonSummary>, String> {
+    person_service(&state).await.find_by_address(&address).map_err(|e| e.to_string())
+}
+
+/// One-to-one chats with a person across all of their handles.
+#[tauri::command]
+pub async fn get_person_chats(
+    state: State<'_, AppState>,
+    person_id: i64,
+) -> Result<Vec<Chat>, String> {
+    person_service(&state).await.chats(person_id).map_err(|e| e.to_string())
+}
+
+/// Search messages exchanged with a person.
+#[tauri::command]
+pub async fn search_person_messages(
+    state: State<'_, AppState>,
+    person_id: i64,
+    query: String,
+) -> Result<Vec<Message>, String> {
+    person_service(&state)
+        .await
+        .search_messages(person_id, &query, 50)
+        .map_err(|e| e.to_string())
+}
+
+/// Merge one person into another.
+#[tauri::command]
+pub async fn merge_people(
+    state: State<'_, AppState>,
+    target_id: i64,
+    source_id: i64,
+) -> Result<bb_services::person::PersonSummary, String> {
+    info!("merge_people target={target_id} source={source_id}");
+    person_service(&state).await.merge(target_id, source_id).map_err(|e| e.to_string())
+}
+
+/// Move a handle out into a person of its own.
+#[tauri::command]
+pub async fn split_person_handle(
+    state: State<'_, AppState>,
+    handle_id: i64,
+) -> Result<bb_services::person::PersonSummary, String> {
+    info!("split_person_handle handle={handle_id}");
+    person_service(&state).await.split(handle_id).map_err(|e| e.to_string())
+}
+
+/// Set or clear a person's name.
+#[tauri::command]
+pub async fn rename_person(
+    state: State<'_, AppState>,
+    person_id: i64,
+    name: Option<String>,
+) -> Result<bb_services::person::PersonSummary, String> {
+    person_service(&state)
+        .await
+        .rename(person_id, name.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+// ─── Trash commands ──────────────────────────────────────────────────────────
+
+async fn trash_service(state: &AppState) -> bb_services::TrashService {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    bb_services::TrashService::new(
+        state.config.clone(),
+        state.database.clone(),
+        event_bus,
+        state.cache_dir.clone(),
+    )
+}
+
+/// List deleted chats and messages, most recently deleted first.
+#[tauri::command]
+pub async fn get_trash(
+    state: State<'_, AppState>,
+) -> Result<Vec<bb_services::trash::TrashItem>, String> {
+    trash_service(&state).await.list().await.map_err(|e| e.to_string())
+}
+
+/// Move a chat to the trash.
+#[tauri::command]
+pub async fn trash_chat(state: State<'_, AppState>, chat_guid: String) -> Result<(), String> {
+    trash_service(&state).await.trash_chat(&chat_guid).map_err(|e| e.to_string())
+}
+
+/// Move a message to the trash.
+#[tauri::command]
+pub async fn trash_message(state: State<'_, AppState>, message_guid: String) -> Result<(), String> {
+    trash_service(&state).await.trash_message(&message_guid).map_err(|e| e.to_string())
+}
+
+/// Restore a chat or message from the trash. Returns "chat" or "message".
+#[tauri::command]
+pub async fn restore_from_trash(
+    state: State<'_, AppState>,
+    guid: String,
+) -> Result<bb_services::trash::TrashKind, String> {
+    trash_service(&state).await.restore(&guid).map_err(|e| e.to_string())
+}
+
+/// Permanently delete one item, or everything when `guid` is omitted.
+#[tauri::command]
+pub async fn purge_trash(
+    state: State<'_, AppState>,
+    guid: Option<String>,
+) -> Result<bb_services::trash::PurgeReport, String> {
+    let service = trash_service(&state).await;
+    match guid {
+        Some(guid) => service.purge(&guid),
+        None => service.purge_all(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+// ─── Snapshot commands ───────────────────────────────────────────────────────
+
+/// List local database snapshots, newest first.
+#[tauri::command]
+pub async fn list_snapshots(state: State<'_, AppState>) -> Result<Vec<bb_models::SnapshotInfo>, String> {
+    let registry = state.registry.read().await;
+    match registry.snapshot_store() {
+        Some(store) => store.list().map_err(|e| e.to_string()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Restore the database from a local snapshot. Services are stopped for
+/// the restore and started again after it.
+#[tauri::command]
+pub async fn restore_snapshot(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<bb_models::SnapshotInfo, String> {
+    info!("restore_snapshot id={id}");
+    let registry = state.registry.read().await;
+    registry.restore_snapshot(&id).await.map_err(|e| e.to_string())
+}
+
 // ─── Private API commands ────────────────────────────────────────────────────
 
 /// Check the Private API status from the server.
@@ -1423,14 +2136,27 @@ pub async fn unsend_message(
     state: State<'_, AppState>,
     message_guid: String,
     part_index: i32,
-) -> Result<serde_json::Value, String> {
+) -> Result<(), String> {
     info!("unsend_message guid={message_guid}");
     let api = state.api_client().await.map_err(|e| e.to_string())?;
-    api.unsend_message(&message_guid, part_index)
+    message_service(&state)
+        .await
+        .unsend_message(&api, &message_guid, part_index)
         .await
         .map_err(|e| format!("unsend failed: {e}"))
 }
 
+/// Get the edit history and unsent parts of a message.
+#[tauri::command]
+pub async fn get_message_edit_history(
+    state: State<'_, AppState>,
+    message_guid: String,
+) -> Result<bb_services::message::EditHistory, String> {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::message::MessageService::new(state.database.clone(), event_bus);
+    service.edit_history(&message_guid).map_err(|e| e.to_string())
+}
+
 // ─── Settings commands ───────────────────────────────────────────────────────
 
 #[tauri::command]
@@ -1449,6 +2175,11 @@ pub async fn update_setting(
 ) -> Result<(), String> {
     debug!("update_setting key={key}");
     let conn = state.database.conn().map_err(|e| e.to_string())?;
+    if key == bb_models::models::settings::keys::DEFAULT_PHONE_REGION {
+        // Stored numbers are keyed by the region they were normalized in
+        bb_models::queries::set_default_phone_region(&conn, &value).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
     Settings::set(&conn, &key, &value).map_err(|e| e.to_string())?;
 
     if key == bb_models::models::settings::keys::REMEMBER_PASSWORD
@@ -1555,7 +2286,7 @@ pub async fn sync_messages(
     let conn = state.database.conn().map_err(|e| e.to_string())?;
 
     // Get all synced chats from local DB (ordered by latest message)
-    let chats = queries::list_chats_with_details(&conn, 0, 10000, false)
+    let chats = queries::list_chats_with_details(&conn, 0, 10000, false, None)
         .map_err(|e| e.to_string())?;
 
     let total = chats.len() as u32;
@@ -2075,70 +2806,33 @@ pub async fn refresh_findmy_friends(
 
 // ─── OTP Detection commands ──────────────────────────────────────────────────
 
-/// Detect OTP in a message by its GUID.
-/// Checks settings to see if OTP detection is enabled.
-/// Returns the detected OTP if found, or null if disabled/not found.
+async fn otp_service(state: &AppState) -> bb_services::OtpService {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    bb_services::OtpService::new(state.database.clone(), event_bus)
+}
+
+/// Detect OTP in a received message by its GUID.
+/// Honors the detection setting, custom patterns and sender allowlist, and
+/// emits `otp-detected` when a code is found.
+/// Returns the detected code, or null if disabled/not found.
 #[tauri::command]
 pub async fn detect_otp_in_message(
     state: State<'_, AppState>,
     app: tauri::AppHandle,
     message_guid: String,
-) -> Result<Option<OtpDetection>, String> {
+) -> Result<Option<OtpCode>, String> {
     debug!("detect_otp_in_message guid={message_guid}");
+    let otp = otp_service(&state).await.check_message(&message_guid).map_err(|e| e.to_string())?;
 
-    // Check if OTP detection is enabled
-    let conn = state.database.conn().map_err(|e| e.to_string())?;
-    let enabled = Settings::get_bool(&conn, bb_models::models::settings::keys::OTP_DETECTION_ENABLED)
-        .map_err(|e| e.to_string())?
-        .unwrap_or(true); // Default to enabled
-
-    if !enabled {
-        debug!("otp detection is disabled");
-        return Ok(None);
-    }
-
-    // Find the message
-    let message = Message::find_by_guid(&conn, &message_guid)
-        .map_err(|e| e.to_string())?;
-
-    let message = match message {
-        Some(m) => m,
-        None => {
-            debug!("message not found: {message_guid}");
-            return Ok(None);
-        }
-    };
-
-    // Get the message text
-    let text = match message.text {
-        Some(ref t) if !t.is_empty() => t,
-        _ => {
-            debug!("message has no text");
-            return Ok(None);
-        }
-    };
-
-    // Detect OTP
-    let detection = detect_otp(text);
-
-    if let Some(ref otp) = detection {
-        info!("otp detected in message {message_guid}: code={}, pattern={:?}", otp.code, otp.pattern);
-
-        // Emit event to frontend
-        let _ = app.emit("otp-detected", serde_json::json!({
-            "messageGuid": message_guid,
-            "code": otp.code,
-            "pattern": format!("{:?}", otp.pattern),
-            "chatId": message.chat_id,
-        }));
-
+    if let Some(ref otp) = otp {
         // Check if auto-copy is enabled
+        let conn = state.database.conn().map_err(|e| e.to_string())?;
         let auto_copy = Settings::get_bool(&conn, bb_models::models::settings::keys::OTP_AUTO_COPY)
             .map_err(|e| e.to_string())?
             .unwrap_or(false);
 
         if auto_copy {
-            debug!("auto-copy enabled for otp: {}", otp.code);
+            debug!("auto-copy enabled for otp in {message_guid}");
             // Note: Actual clipboard copy would be handled by frontend
             // We just emit an additional event
             let _ = app.emit("otp-auto-copy", serde_json::json!({
@@ -2147,7 +2841,32 @@ pub async fn detect_otp_in_message(
         }
     }
 
-    Ok(detection)
+    Ok(otp)
+}
+
+/// Like `detect_otp_in_message`, for a chat's latest received message.
+#[tauri::command]
+pub async fn detect_chat_otp(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    chat_guid: String,
+) -> Result<Option<OtpCode>, String> {
+    let message_guid = {
+        let conn = state.database.conn().map_err(|e| e.to_string())?;
+        let chat = queries::find_chat_by_guid(&conn, &chat_guid)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("chat not found: {chat_guid}"))?;
+        match chat.id {
+            Some(id) => queries::latest_incoming_text_for_chat(&conn, id)
+                .map_err(|e| e.to_string())?
+                .and_then(|m| m.guid),
+            None => None,
+        }
+    };
+    let Some(message_guid) = message_guid else {
+        return Ok(None);
+    };
+    detect_otp_in_message(state, app, message_guid).await
 }
 
 /// Detect OTP in arbitrary text.
@@ -2159,29 +2878,66 @@ pub async fn detect_otp_in_text(text: String) -> Result<Option<OtpDetection>, St
     Ok(detect_otp(&text))
 }
 
+/// Get the OTP settings (custom patterns, allowed senders, default expiry).
+#[tauri::command]
+pub async fn get_otp_settings(state: State<'_, AppState>) -> Result<bb_models::OtpSettings, String> {
+    otp_service(&state).await.settings().map_err(|e| e.to_string())
+}
+
+/// Save the OTP settings. Fails if a custom pattern is not a valid regex.
+#[tauri::command]
+pub async fn set_otp_settings(
+    state: State<'_, AppState>,
+    settings: bb_models::OtpSettings,
+) -> Result<(), String> {
+    otp_service(&state).await.set_settings(&settings).map_err(|e| e.to_string())
+}
+
 // ─── Scheduled message commands ──────────────────────────────────────────────
 
-/// Create a scheduled message via the BB server API.
+fn scheduled_service(state: &AppState, event_bus: bb_services::EventBus) -> bb_services::ScheduledMessageService {
+    bb_services::ScheduledMessageService::new(state.database.clone(), event_bus)
+}
+
+/// Create a scheduled message via the BB server API, or with `local` set,
+/// one sent by the app's own scheduler. `recurrence` (cron or RRULE) repeats
+/// it in `time_zone`, defaulting to the system time zone.
 #[tauri::command]
 pub async fn create_scheduled_message(
     state: State<'_, AppState>,
     chat_guid: String,
     message: String,
     scheduled_for: i64,
+    local: Option<bool>,
+    recurrence: Option<String>,
+    time_zone: Option<String>,
 ) -> Result<serde_json::Value, String> {
-    info!("create_scheduled_message chat={chat_guid} for={scheduled_for}");
+    info!("create_scheduled_message chat={chat_guid} for={scheduled_for} local={local:?} recurrence={recurrence:?}");
+    let when = chrono::DateTime::from_timestamp_millis(scheduled_for)
+        .ok_or_else(|| format!("invalid schedule time: {scheduled_for}"))?;
+    let tz = match time_zone.as_deref() {
+        Some(name) => bb_models::models::recurrence::parse_time_zone(name).map_err(|e| e.to_string())?,
+        None => bb_models::models::recurrence::system_time_zone(),
+    };
+    if local.unwrap_or(false) {
+        let event_bus = state.registry.read().await.event_bus().clone();
+        let service = scheduled_service(&state, event_bus);
+        let msg = match recurrence.as_deref() {
+            Some(expr) => service.create_local_recurring(&chat_guid, &message, when, expr, tz.name()).await,
+            None => service.create_local(&chat_guid, &message, when).await,
+        }
+        .map_err(|e| e.to_string())?;
+        return serde_json::to_value(msg).map_err(|e| e.to_string());
+    }
     let api = state.api_client().await.map_err(|e| e.to_string())?;
 
-    let params = bb_api::endpoints::messages::ScheduleMessageParams {
-        schedule_type: "send-message".to_string(),
-        payload: serde_json::json!({
-            "chatGuid": chat_guid,
-            "message": message,
-            "method": "private-api",
-        }),
-        scheduled_for,
-        schedule: None,
-    };
+    let params = bb_services::ScheduledMessageService::server_params(
+        &chat_guid,
+        &message,
+        when,
+        recurrence.as_deref().map(|expr| (expr, tz.name())),
+    )
+    .map_err(|e| e.to_string())?;
 
     api.create_scheduled_message(&params)
         .await
@@ -2199,79 +2955,35 @@ pub async fn get_scheduled_messages(
         .map_err(|e| format!("get scheduled messages failed: {e}"))
 }
 
-/// Delete a scheduled message by ID.
+/// Get pending schedules run by the app's own scheduler.
+#[tauri::command]
+pub async fn get_local_scheduled_messages(
+    state: State<'_, AppState>,
+) -> Result<Vec<bb_models::ScheduledMessage>, String> {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    scheduled_service(&state, event_bus).list_local().map_err(|e| e.to_string())
+}
+
+/// Delete a scheduled message by ID. Negative IDs are local schedules.
 #[tauri::command]
 pub async fn delete_scheduled_message(
     state: State<'_, AppState>,
     id: i64,
 ) -> Result<(), String> {
+    if id < 0 {
+        let event_bus = state.registry.read().await.event_bus().clone();
+        return scheduled_service(&state, event_bus)
+            .delete_local(id)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+    }
     let api = state.api_client().await.map_err(|e| e.to_string())?;
     api.delete_scheduled_message(id)
         .await
         .map_err(|e| format!("delete scheduled message failed: {e}"))
 }
 
-// ─── Helper function for message processing ─────────────────────────────────
-
-/// Process a newly received message for OTP detection.
-/// This is called internally when new messages arrive from the server.
-/// Can also be exposed as a command if needed for manual triggering.
-pub async fn process_message_for_otp(
-    state: &AppState,
-    app: &tauri::AppHandle,
-    message: &Message,
-) -> Result<Option<OtpDetection>, String> {
-    // Only process messages that are not from the user
-    if message.is_from_me {
-        return Ok(None);
-    }
-
-    // Check if OTP detection is enabled
-    let conn = state.database.conn().map_err(|e| e.to_string())?;
-    let enabled = Settings::get_bool(&conn, bb_models::models::settings::keys::OTP_DETECTION_ENABLED)
-        .map_err(|e| e.to_string())?
-        .unwrap_or(true);
-
-    if !enabled {
-        return Ok(None);
-    }
-
-    // Get message text
-    let text = match &message.text {
-        Some(t) if !t.is_empty() => t,
-        _ => return Ok(None),
-    };
-
-    // Detect OTP
-    let detection = detect_otp(text);
-
-    if let Some(ref otp) = detection {
-        info!("otp detected in new message: code={}, pattern={:?}", otp.code, otp.pattern);
-
-        // Emit event to frontend
-        let _ = app.emit("otp-detected", serde_json::json!({
-            "messageGuid": message.guid,
-            "code": otp.code,
-            "pattern": format!("{:?}", otp.pattern),
-            "chatId": message.chat_id,
-        }));
-
-        // Check auto-copy setting
-        let auto_copy = Settings::get_bool(&conn, bb_models::models::settings::keys::OTP_AUTO_COPY)
-            .map_err(|e| e.to_string())?
-            .unwrap_or(false);
-
-        if auto_copy {
-            debug!("auto-copy enabled, emitting otp-auto-copy event");
-            let _ = app.emit("otp-auto-copy", serde_json::json!({
-                "code": otp.code,
-            }));
-        }
-    }
-
-    Ok(detection)
-}
-
 // ─── MCP Server commands ─────────────────────────────────────────────────────
 
 /// Status info returned to the frontend for the MCP server.
@@ -2329,6 +3041,10 @@ pub async fn start_mcp_server(
         database,
         socket_manager,
         setup_complete,
+        cache_dir: app_state.cache_dir.clone(),
+        send_queue: app_state.send_queue.clone(),
+        active_chat: app_state.active_chat.clone(),
+        link_previews: app_state.link_previews.clone(),
     });
 
     let auth_clone = auth.clone();
@@ -9,7 +9,6 @@
 mod commands;
 mod state;
 mod menu;
-mod otp_detector;
 mod mcp_auth;
 mod mcp_tools;
 mod mcp_server;
@@ -42,6 +41,7 @@ fn main() {
 
     // Load or create config
     let config = AppConfig::default();
+    bb_models::phone::set_default_region(&config.sync.default_phone_region);
     let config_handle = ConfigHandle::new(config);
 
     // Initialize database
@@ -52,11 +52,20 @@ fn main() {
 
     info!("database initialized at {}", db_path.display());
 
+    if let Err(e) = database
+        .conn()
+        .and_then(|conn| bb_models::queries::apply_saved_phone_region(&conn))
+    {
+        tracing::warn!("failed to load the saved phone region: {e}");
+    }
+
+    let snapshot_store = bb_models::SnapshotStore::from_config(&db_config.snapshots, &data_dir);
+
     // Create event dispatcher for socket events
     let dispatcher = EventDispatcher::new(256);
 
     // Build shared application state
-    let app_state = AppState::new(config_handle, database, dispatcher);
+    let app_state = AppState::new(config_handle, database, dispatcher, snapshot_store);
 
     // Build and run the Tauri application
     tauri::Builder::default()
@@ -73,11 +82,18 @@ fn main() {
             commands::get_chats,
             commands::refresh_chats,
             commands::mark_chat_read,
+            commands::set_active_chat,
             commands::mark_chat_unread,
+            commands::get_outbox,
+            commands::replay_outbox,
             commands::update_chat,
             commands::get_messages,
             commands::send_message,
             commands::search_messages,
+            commands::save_draft,
+            commands::get_draft,
+            commands::clear_draft,
+            commands::get_drafts,
             commands::get_contacts,
             commands::get_contact_avatar,
             commands::get_all_contact_avatars,
@@ -86,6 +102,49 @@ fn main() {
             commands::send_attachment_message,
             commands::send_attachment_data,
             commands::download_attachment,
+            commands::get_contact_card,
+            commands::save_contact_card,
+            commands::get_bookmarks,
+            commands::bookmark_message,
+            commands::set_bookmark_note,
+            commands::add_bookmark_tags,
+            commands::remove_bookmark_tags,
+            commands::remove_bookmark,
+            commands::get_bookmark_tags,
+            commands::get_chat_folders,
+            commands::create_chat_folder,
+            commands::update_chat_folder,
+            commands::reorder_chat_folders,
+            commands::delete_chat_folder,
+            commands::add_chat_to_folder,
+            commands::remove_chat_from_folder,
+            commands::get_folders_for_chat,
+            commands::get_notification_rules,
+            commands::add_notification_rule,
+            commands::update_notification_rule,
+            commands::move_notification_rule,
+            commands::delete_notification_rule,
+            commands::get_quiet_hours,
+            commands::set_quiet_hours,
+            commands::evaluate_notification,
+            commands::get_digest_settings,
+            commands::set_digest_settings,
+            commands::flush_notification_digests,
+            commands::get_smart_replies,
+            commands::get_people,
+            commands::get_person_for_address,
+            commands::get_person_chats,
+            commands::search_person_messages,
+            commands::merge_people,
+            commands::split_person_handle,
+            commands::rename_person,
+            commands::get_trash,
+            commands::trash_chat,
+            commands::trash_message,
+            commands::restore_from_trash,
+            commands::purge_trash,
+            commands::list_snapshots,
+            commands::restore_snapshot,
             commands::get_message_reactions,
             commands::get_settings,
             commands::update_setting,
@@ -101,21 +160,26 @@ fn main() {
             commands::send_reaction,
             commands::edit_message,
             commands::unsend_message,
+            commands::get_message_edit_history,
             commands::get_findmy_devices,
             commands::refresh_findmy_devices,
             commands::get_findmy_friends,
             commands::refresh_findmy_friends,
             commands::detect_otp_in_message,
             commands::detect_otp_in_text,
+            commands::detect_chat_otp,
+            commands::get_otp_settings,
+            commands::set_otp_settings,
             commands::create_scheduled_message,
             commands::get_scheduled_messages,
+            commands::get_local_scheduled_messages,
             commands::delete_scheduled_message,
             commands::start_mcp_server,
             commands::stop_mcp_server,
             commands::get_mcp_status,
             commands::regenerate_mcp_token,
         ])
-        .setup(|app| {
+        .setup(move |app| {
             // Setup system tray
             menu::setup_tray(app.handle())?;
 
@@ -128,6 +192,196 @@ fn main() {
                 }
                 info!("services initialized");
 
+                // Purge trashed items once they pass the retention window
+                let trash = std::sync::Arc::new(bb_services::TrashService::new(
+                    state.config.clone(),
+                    state.database.clone(),
+                    state.registry.read().await.event_bus().clone(),
+                    state.cache_dir.clone(),
+                ));
+                bb_services::TrashService::start_scheduler(trash);
+
+                // Replay queued chat changes whenever the connection returns,
+                // and tell the UI when the server rejects one
+                let event_bus = state.registry.read().await.event_bus().clone();
+                let outbox = std::sync::Arc::new(bb_services::OutboxService::new(
+                    state.database.clone(),
+                    event_bus.clone(),
+                ));
+                let api_client = state.registry.read().await.api_client.clone();
+                bb_services::OutboxService::start_replayer(outbox, api_client.clone());
+
+                // Resume sends left queued by the previous run, after checking
+                // which of them the server already received; messages composed
+                // offline go out when the connection returns
+                if let Err(e) = state.send_queue.reload().await {
+                    tracing::warn!("failed to reload send queue: {e}");
+                }
+                let messages = std::sync::Arc::new(
+                    bb_services::message::MessageService::new(state.database.clone(), event_bus.clone())
+                        .with_link_previews(state.link_previews.clone()),
+                );
+                bb_services::message::MessageService::start_retry_loop(
+                    messages.clone(),
+                    state.send_queue.clone(),
+                    api_client.clone(),
+                );
+
+                // Send local scheduled messages as they come due
+                let scheduled = std::sync::Arc::new(bb_services::ScheduledMessageService::new(
+                    state.database.clone(),
+                    event_bus.clone(),
+                ));
+                bb_services::ScheduledMessageService::start_local_scheduler(
+                    scheduled,
+                    messages.clone(),
+                    state.send_queue.clone(),
+                    state.config.clone(),
+                    api_client,
+                );
+                // Post message notifications with reply, mark as read and
+                // mute actions where the desktop supports them
+                let (action_tx, mut action_rx) = tokio::sync::mpsc::unbounded_channel();
+                let notifications =
+                    bb_services::notification::NotificationService::new(state.config.clone())
+                        .with_database(state.database.clone());
+                #[cfg(all(unix, not(target_os = "macos")))]
+                let notifications = match listen_for_actions(action_tx).await {
+                    Ok(notifier) => notifications.with_dbus_notifier(notifier),
+                    Err(e) => {
+                        tracing::warn!("notification actions unavailable: {e}");
+                        notifications
+                    }
+                };
+                #[cfg(not(all(unix, not(target_os = "macos"))))]
+                drop(action_tx);
+                let notifications = std::sync::Arc::new(notifications);
+
+                let actions_handle = handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    use bb_services::notification_actions::NotificationAction;
+                    use tauri::Emitter;
+                    let state = actions_handle.state::<AppState>();
+                    while let Some(action) = action_rx.recv().await {
+                        if let NotificationAction::Open { chat_guid } = &action {
+                            if let Some(window) = actions_handle.get_webview_window("main") {
+                                let _ = window.show();
+                                let _ = window.set_focus();
+                            }
+                            let _ = actions_handle.emit("open-chat", serde_json::json!({
+                                "chatGuid": chat_guid,
+                            }));
+                            continue;
+                        }
+                        let api = match state.api_client().await {
+                            Ok(api) => api,
+                            Err(e) => {
+                                tracing::warn!("notification action failed: {e}");
+                                continue;
+                            }
+                        };
+                        let chats = bb_services::chat::ChatService::new(
+                            state.database.clone(),
+                            state.registry.read().await.event_bus().clone(),
+                        );
+                        if let Err(e) = action.perform(&api, &state.send_queue, &chats, &messages).await {
+                            tracing::warn!("notification action failed: {e}");
+                        }
+                    }
+                });
+
+                // Summarize held notifications once a chat's digest window
+                // passes; the CLI listener shares the same digests
+                bb_services::notification::NotificationService::start_digest_flusher(
+                    notifications.clone(),
+                    event_bus.clone(),
+                );
+                let mut events = event_bus.subscribe();
+                let outbox_handle = handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    use tauri::Emitter;
+                    while let Ok(event) = events.recv().await {
+                        match event {
+                            bb_services::AppEvent::OutboxMutationFailed { chat_guid, description, error } => {
+                                let _ = outbox_handle.emit("outbox-mutation-failed", serde_json::json!({
+                                    "chatGuid": chat_guid,
+                                    "description": description,
+                                    "error": error,
+                                }));
+                            }
+                            bb_services::AppEvent::MessageStatusChanged { temp_guid, chat_guid, status } => {
+                                let _ = outbox_handle.emit("message-status-changed", serde_json::json!({
+                                    "tempGuid": temp_guid,
+                                    "chatGuid": chat_guid,
+                                    "status": status,
+                                }));
+                            }
+                            // Every received message goes through the notification
+                            // rules; digest messages are held for their chat's summary
+                            bb_services::AppEvent::MessageReceived { .. } => {
+                                match notifications.process_event(&event).await {
+                                    Ok(Some((mut ctx, decision))) if decision.shows() => {
+                                        let _ = outbox_handle.emit("message-notification", serde_json::json!({
+                                            "chatGuid": ctx.chat_guid,
+                                            "messageGuid": ctx.message_guid,
+                                            "title": ctx.title(),
+                                            "body": ctx.body(),
+                                            "priority": decision.action == bb_models::RuleAction::Priority,
+                                        }));
+
+                                        // The user is already looking at the open chat
+                                        let state = outbox_handle.state::<AppState>();
+                                        if state.active_chat.read().await.as_deref() == Some(ctx.chat_guid.as_str()) {
+                                            continue;
+                                        }
+                                        let (enabled, show_preview) = match state.database.conn() {
+                                            Ok(conn) => {
+                                                use bb_models::Settings;
+                                                (
+                                                    Settings::get(&conn, "notificationsEnabled").ok().flatten().as_deref() != Some("false"),
+                                                    Settings::get(&conn, "notifShowPreview").ok().flatten().as_deref() != Some("false"),
+                                                )
+                                            }
+                                            Err(_) => (true, true),
+                                        };
+                                        if !enabled {
+                                            continue;
+                                        }
+                                        if !show_preview {
+                                            ctx.text = "New message received.".into();
+                                            ctx.attachment_mime_types.clear();
+                                            ctx.reaction = None;
+                                        }
+                                        if let Err(e) = notifications.show_message(&ctx, &decision).await {
+                                            tracing::warn!("failed to show notification: {e}");
+                                        }
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => tracing::warn!("notification rules failed: {e}"),
+                                }
+                            }
+                            bb_services::AppEvent::NotificationDigestReady { digest } => {
+                                let _ = outbox_handle.emit("notification-digest", serde_json::json!({
+                                    "chatGuid": digest.chat_guid,
+                                    "summary": digest.summary(),
+                                    "body": digest.body(),
+                                }));
+                            }
+                            bb_services::AppEvent::OtpDetected { message_guid, chat_guid, code, sender, expires_at } => {
+                                let _ = outbox_handle.emit("otp-detected", serde_json::json!({
+                                    "messageGuid": message_guid,
+                                    "chatGuid": chat_guid,
+                                    "code": code,
+                                    "snippet": sender.clone().unwrap_or_default(),
+                                    "sender": sender,
+                                    "expiresAt": expires_at,
+                                }));
+                            }
+                            _ => {}
+                        }
+                    }
+                });
+
                 // Auto-start MCP server if enabled in settings
                 if let Ok(conn) = state.database.conn() {
                     use bb_models::Settings;
@@ -169,6 +423,10 @@ fn main() {
                             database: state.database.clone(),
                             socket_manager: state.socket_manager.clone(),
                             setup_complete: state.setup_complete.clone(),
+                            cache_dir: state.cache_dir.clone(),
+                            send_queue: state.send_queue.clone(),
+                            active_chat: state.active_chat.clone(),
+                            link_previews: state.link_previews.clone(),
                         });
 
                         let auth_clone = auth.clone();
@@ -212,3 +470,21 @@ fn main() {
         .run(tauri::generate_context!())
         .expect("error running BlueBubbles");
 }
+
+/// Connect to the desktop notification service and forward the actions
+/// picked on its notifications.
+#[cfg(all(unix, not(target_os = "macos")))]
+async fn listen_for_actions(
+    tx: tokio::sync::mpsc::UnboundedSender<bb_services::notification_actions::NotificationAction>,
+) -> bb_core::error::BbResult<std::sync::Arc<bb_services::notification_actions::DbusNotifier>> {
+    let notifier = bb_services::notification_actions::DbusNotifier::session().await?;
+    let mut actions = notifier.listen().await?;
+    tokio::spawn(async move {
+        while let Some(action) = actions.next().await {
+            if tx.send(action).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(std::sync::Arc::new(notifier))
+}
@@ -259,6 +259,11 @@ async fn handle_jsonrpc(
                 .cloned()
                 .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
 
+            // Tools over local data work without a server connection
+            if let Some(result) = mcp_tools::execute_local_tool(tool_name, &tool_args, &ctx.app_state).await {
+                return tool_call_response(id, result);
+            }
+
             // Get the API client
             let api = match ctx.app_state.api_client().await {
                 Ok(api) => api,
@@ -274,19 +279,7 @@ async fn handle_jsonrpc(
                 }
             };
 
-            match mcp_tools::execute_tool(tool_name, tool_args, &api).await {
-                Ok(result) => jsonrpc_success_response(id, result),
-                Err(e) => {
-                    let err_result = serde_json::json!({
-                        "content": [{
-                            "type": "text",
-                            "text": format!("Error: {e}")
-                        }],
-                        "isError": true
-                    });
-                    jsonrpc_success_response(id, err_result)
-                }
-            }
+            tool_call_response(id, mcp_tools::execute_tool(tool_name, tool_args, &api).await)
         }
         "ping" => {
             jsonrpc_success_response(id, serde_json::json!({}))
@@ -339,6 +332,26 @@ fn jsonrpc_success_response(
         .unwrap()
 }
 
+/// Wrap a tool result; tool failures are reported in-band with `isError`.
+fn tool_call_response(
+    id: Option<serde_json::Value>,
+    result: Result<serde_json::Value, mcp_tools::McpToolError>,
+) -> Response<BoxBody> {
+    match result {
+        Ok(result) => jsonrpc_success_response(id, result),
+        Err(e) => {
+            let err_result = serde_json::json!({
+                "content": [{
+                    "type": "text",
+                    "text": format!("Error: {e}")
+                }],
+                "isError": true
+            });
+            jsonrpc_success_response(id, err_result)
+        }
+    }
+}
+
 fn jsonrpc_error_response(
     id: Option<serde_json::Value>,
     code: i64,
@@ -1,7 +1,9 @@
 //! MCP tool definitions and dispatch.
 //!
 //! Defines the tool catalog exposed via the MCP protocol and routes
-//! `tools/call` requests to the appropriate `ApiClient` methods.
+//! `tools/call` requests to the appropriate `ApiClient` methods. Tools
+//! over local-only data (bookmarks, smart replies) run against the
+//! database and work without a server connection.
 
 use bb_api::ApiClient;
 use bb_api::endpoints::chats::ChatQuery;
@@ -9,6 +11,8 @@ use bb_api::endpoints::messages::{SendTextParams, SendReactionParams, MessageQue
 use serde_json::json;
 use tracing::{info, debug};
 
+use crate::state::AppState;
+
 /// Errors that can occur during tool execution.
 #[derive(Debug)]
 pub enum McpToolError {
@@ -53,9 +57,31 @@ pub fn tool_definitions() -> Vec<serde_json::Value> {
         tool_get_contacts(),
         tool_download_attachment(),
         tool_get_server_info(),
+        tool_list_bookmarks(),
+        tool_bookmark_message(),
+        tool_suggest_replies(),
+        tool_get_otp_codes(),
     ]
 }
 
+/// Execute a tool that only needs local data. Returns None for tools that
+/// go through the server API.
+pub async fn execute_local_tool(
+    name: &str,
+    args: &serde_json::Value,
+    state: &AppState,
+) -> Option<Result<serde_json::Value, McpToolError>> {
+    let result = match name {
+        "list_bookmarks" => exec_list_bookmarks(args, state).await,
+        "bookmark_message" => exec_bookmark_message(args, state).await,
+        "suggest_replies" => exec_suggest_replies(args, state).await,
+        "get_otp_codes" => exec_get_otp_codes(args, state).await,
+        _ => return None,
+    };
+    debug!("executed local mcp tool: {name}");
+    Some(result)
+}
+
 /// Execute a tool by name with the given arguments.
 pub async fn execute_tool(
     name: &str,
@@ -248,6 +274,99 @@ fn tool_get_server_info() -> serde_json::Value {
     })
 }
 
+fn tool_list_bookmarks() -> serde_json::Value {
+    json!({
+        "name": "list_bookmarks",
+        "description": "List bookmarked messages with their tags and notes. Bookmarks tagged e.g. 'todo' work as a reply-later list.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "tag": {
+                    "type": "string",
+                    "description": "Optional: only bookmarks with this tag"
+                },
+                "chat_guid": {
+                    "type": "string",
+                    "description": "Optional: only bookmarks in this chat"
+                },
+                "query": {
+                    "type": "string",
+                    "description": "Optional: only bookmarks whose note, tags or text contain this"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Max bookmarks to return (default 25, max 100)",
+                    "default": 25
+                }
+            }
+        }
+    })
+}
+
+fn tool_bookmark_message() -> serde_json::Value {
+    json!({
+        "name": "bookmark_message",
+        "description": "Bookmark a message, adding tags and optionally a note. Set remove to true to delete the bookmark.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "message_guid": {
+                    "type": "string",
+                    "description": "GUID of the message to bookmark"
+                },
+                "tags": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Tags to add"
+                },
+                "note": {
+                    "type": "string",
+                    "description": "Note to attach (replaces any existing note)"
+                },
+                "remove": {
+                    "type": "boolean",
+                    "description": "Remove the bookmark instead (default false)",
+                    "default": false
+                }
+            },
+            "required": ["message_guid"]
+        }
+    })
+}
+
+fn tool_suggest_replies() -> serde_json::Value {
+    json!({
+        "name": "suggest_replies",
+        "description": "Suggest three short replies to the latest incoming message in a chat, generated on-device from the user's own sent messages. Unavailable when smart replies are disabled or incognito mode is on.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "chat_guid": {
+                    "type": "string",
+                    "description": "GUID of the chat"
+                }
+            },
+            "required": ["chat_guid"]
+        }
+    })
+}
+
+fn tool_get_otp_codes() -> serde_json::Value {
+    json!({
+        "name": "get_otp_codes",
+        "description": "List one-time verification codes from recently received messages that have not expired yet, newest first. Honors the user's OTP settings (detection toggle, custom patterns, allowed senders).",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "chat_guid": {
+                    "type": "string",
+                    "description": "Only return codes from this chat (optional)"
+                }
+            }
+        }
+    })
+}
+
 // ─── Tool Execution ──────────────────────────────────────────────────────────
 
 fn text_content(text: &str) -> serde_json::Value {
@@ -348,6 +467,7 @@ async fn exec_send_message(
         selected_message_guid: None,
         part_index: None,
         dd_scan: None,
+        payload_data: None,
     };
 
     let result = api.send_text(&params).await
@@ -494,6 +614,130 @@ async fn exec_get_server_info(
     Ok(text_content(&pretty))
 }
 
+async fn message_service(state: &AppState) -> bb_services::message::MessageService {
+    let event_bus = state.registry.read().await.event_bus().clone();
+    bb_services::message::MessageService::new(state.database.clone(), event_bus)
+}
+
+async fn exec_list_bookmarks(
+    args: &serde_json::Value,
+    state: &AppState,
+) -> Result<serde_json::Value, McpToolError> {
+    let limit = args.get("limit").and_then(|v| v.as_i64()).unwrap_or(25).clamp(1, 100);
+    let tag = args.get("tag").and_then(|v| v.as_str()).map(String::from);
+    let query = args.get("query").and_then(|v| v.as_str()).map(String::from);
+
+    let chat_id = match args.get("chat_guid").and_then(|v| v.as_str()) {
+        Some(guid) => {
+            let conn = state.database.conn().map_err(|e| McpToolError::Internal(e.to_string()))?;
+            let chat = bb_models::Chat::find_by_guid(&conn, guid)
+                .map_err(|e| McpToolError::Internal(e.to_string()))?
+                .ok_or_else(|| McpToolError::InvalidParams(format!("chat not found: {guid}")))?;
+            chat.id
+        }
+        None => None,
+    };
+
+    let filter = bb_models::BookmarkFilter { tag, chat_id, query, limit };
+    let bookmarks = message_service(state).await.list_bookmarks(&filter)
+        .map_err(|e| McpToolError::Internal(e.to_string()))?;
+
+    let pretty = serde_json::to_string_pretty(&bookmarks)
+        .map_err(|e| McpToolError::Internal(e.to_string()))?;
+
+    info!("mcp list_bookmarks returned {} bookmarks", bookmarks.len());
+    Ok(text_content(&pretty))
+}
+
+async fn exec_bookmark_message(
+    args: &serde_json::Value,
+    state: &AppState,
+) -> Result<serde_json::Value, McpToolError> {
+    let message_guid = args.get("message_guid")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| McpToolError::InvalidParams("message_guid is required".into()))?;
+    let service = message_service(state).await;
+
+    if args.get("remove").and_then(|v| v.as_bool()).unwrap_or(false) {
+        let removed = service.remove_bookmark(message_guid)
+            .map_err(|e| McpToolError::Internal(e.to_string()))?;
+        info!("mcp bookmark_message removed {message_guid}");
+        let text = if removed { "Bookmark removed." } else { "Message was not bookmarked." };
+        return Ok(text_content(text));
+    }
+
+    let tags: Vec<String> = args.get("tags")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|t| t.as_str().map(String::from)).collect())
+        .unwrap_or_default();
+    let note = args.get("note").and_then(|v| v.as_str());
+
+    let bookmark = service.bookmark_message(message_guid, &tags, note)
+        .map_err(|e| match e {
+            bb_core::error::BbError::MessageNotFound(_) => McpToolError::InvalidParams(e.to_string()),
+            _ => McpToolError::Internal(e.to_string()),
+        })?;
+
+    let pretty = serde_json::to_string_pretty(&bookmark)
+        .map_err(|e| McpToolError::Internal(e.to_string()))?;
+
+    info!("mcp bookmark_message {message_guid}");
+    Ok(text_content(&pretty))
+}
+
+async fn exec_suggest_replies(
+    args: &serde_json::Value,
+    state: &AppState,
+) -> Result<serde_json::Value, McpToolError> {
+    let chat_guid = args.get("chat_guid")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| McpToolError::InvalidParams("chat_guid is required".into()))?;
+    let service = bb_services::SmartReplyService::new(state.config.clone(), state.database.clone());
+
+    if !service.is_enabled().await.map_err(|e| McpToolError::Internal(e.to_string()))? {
+        return Ok(text_content("Smart replies are disabled (or incognito mode is on)."));
+    }
+    let replies = service.suggest_for_chat(chat_guid).await
+        .map_err(|e| match e {
+            bb_core::error::BbError::ChatNotFound(_) => McpToolError::InvalidParams(e.to_string()),
+            _ => McpToolError::Internal(e.to_string()),
+        })?;
+    let Some(replies) = replies else {
+        return Ok(text_content("No incoming message to reply to."));
+    };
+
+    let pretty = serde_json::to_string_pretty(&replies)
+        .map_err(|e| McpToolError::Internal(e.to_string()))?;
+
+    info!("mcp suggest_replies for {chat_guid}");
+    Ok(text_content(&pretty))
+}
+
+async fn exec_get_otp_codes(
+    args: &serde_json::Value,
+    state: &AppState,
+) -> Result<serde_json::Value, McpToolError> {
+    let chat_guid = args.get("chat_guid").and_then(|v| v.as_str());
+    let event_bus = state.registry.read().await.event_bus().clone();
+    let service = bb_services::OtpService::new(state.database.clone(), event_bus);
+
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let codes = service.recent_codes(chat_guid, now_ms)
+        .map_err(|e| match e {
+            bb_core::error::BbError::ChatNotFound(_) => McpToolError::InvalidParams(e.to_string()),
+            _ => McpToolError::Internal(e.to_string()),
+        })?;
+    if codes.is_empty() {
+        return Ok(text_content("No unexpired one-time codes."));
+    }
+
+    let pretty = serde_json::to_string_pretty(&codes)
+        .map_err(|e| McpToolError::Internal(e.to_string()))?;
+
+    info!("mcp get_otp_codes returned {} codes", codes.len());
+    Ok(text_content(&pretty))
+}
+
 /// Simple percent-encoding for URL path segments.
 fn percent_encode_path(s: &str) -> String {
     let mut out = String::with_capacity(s.len() * 3);
@@ -8,10 +8,11 @@ use tokio::sync::RwLock;
 
 use bb_core::config::ConfigHandle;
 use bb_core::error::BbResult;
-use bb_models::Database;
+use bb_models::{Database, SnapshotStore};
 use bb_api::ApiClient;
 use bb_socket::{SocketManager, EventDispatcher};
-use bb_services::ServiceRegistry;
+use bb_services::{LinkPreviewService, ServiceRegistry};
+use bb_services::queue::QueueService;
 
 /// Shared application state managed by Tauri.
 pub struct AppState {
@@ -25,34 +26,51 @@ pub struct AppState {
     pub socket_manager: Arc<RwLock<Option<SocketManager>>>,
     /// Whether the initial setup has been completed.
     pub setup_complete: Arc<RwLock<bool>>,
+    /// Directory where downloaded attachments are cached.
+    pub cache_dir: std::path::PathBuf,
+    /// Persistent queue of sends awaiting retry; the only instance over the
+    /// `send_queue` table, so the registry does not register one.
+    pub send_queue: Arc<QueueService>,
+    /// The chat open in the window, which gets no message notifications.
+    pub active_chat: Arc<RwLock<Option<String>>>,
+    /// Link previews for outgoing messages, shared by every send so they
+    /// reuse one HTTP client.
+    pub link_previews: Arc<LinkPreviewService>,
 }
 
 impl AppState {
-    /// Create a new AppState with the given infrastructure.
+    /// Create a new AppState with the given infrastructure. Scheduled
+    /// snapshots go to `snapshot_store`.
     pub fn new(
         config: ConfigHandle,
         database: Database,
         dispatcher: EventDispatcher,
+        snapshot_store: SnapshotStore,
     ) -> Self {
         let mut registry = ServiceRegistry::new(
             config.clone(),
             database.clone(),
             dispatcher,
-        );
+        )
+        .with_snapshot_store(snapshot_store);
 
         let cache_dir = dirs::cache_dir()
             .unwrap_or_else(|| std::path::PathBuf::from("."))
             .join("bluebubbles")
             .join("cache");
 
-        registry.register_all(cache_dir);
+        registry.register_all(cache_dir.clone());
 
         Self {
             registry: Arc::new(RwLock::new(registry)),
             config,
-            database,
+            database: database.clone(),
             socket_manager: Arc::new(RwLock::new(None)),
             setup_complete: Arc::new(RwLock::new(false)),
+            cache_dir,
+            send_queue: Arc::new(QueueService::new().with_database(database.clone())),
+            active_chat: Arc::new(RwLock::new(None)),
+            link_previews: Arc::new(LinkPreviewService::new(database.clone())),
         }
     }
 
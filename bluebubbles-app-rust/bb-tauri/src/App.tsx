@@ -23,6 +23,8 @@ import { LoadingSpinner } from "@/components/LoadingSpinner";
 import { OtpToastProvider } from "@/contexts/OtpToastContext";
 import { OtpToast } from "@/components/OtpToast";
 import { useOtpDetection } from "@/hooks/useOtpDetection";
+import { useNotificationDigests } from "@/hooks/useNotificationDigests";
+import { useMessageNotifications } from "@/hooks/useMessageNotifications";
 import { useOtpToast } from "@/contexts/OtpToastContext";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { Image } from "@tauri-apps/api/image";
@@ -35,6 +37,12 @@ function AppContent() {
   // Initialize OTP detection
   useOtpDetection();
 
+  // Play the sound for received messages the notification rules let through
+  useMessageNotifications();
+
+  // Show notification digests for busy chats
+  useNotificationDigests();
+
   // Ensure window icon is set (avoids stale/default icon in dev)
   useEffect(() => {
     if (!(window as unknown as { __TAURI__?: unknown }).__TAURI__) return;
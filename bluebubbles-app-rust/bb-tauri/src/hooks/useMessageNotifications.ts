@@ -0,0 +1,50 @@
+/**
+ * Message Notification Hook
+ *
+ * Plays the notification sound for the messages the backend lets through
+ * its notification rules. The backend posts the notifications themselves,
+ * with reply, mark as read and mute actions.
+ */
+import { useEffect } from "react";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { MessageNotification } from "@/hooks/useTauri";
+import { useChatStore } from "@/store/chatStore";
+import { useSettingsStore } from "@/store/settingsStore";
+import { playNotificationSound } from "@/utils/notificationSound";
+
+/**
+ * Hook that plays the notification sound for each message the rules let
+ * through, except in the open chat.
+ */
+export function useMessageNotifications() {
+  const { settings } = useSettingsStore();
+  const notificationsEnabled = settings["notificationsEnabled"] !== "false";
+  const soundOn = settings["soundEnabled"] !== "false";
+  const notifSound = settings["notifSound"] || "default";
+
+  useEffect(() => {
+    if (!notificationsEnabled || !soundOn || notifSound === "none") {
+      return;
+    }
+
+    let unlisten: UnlistenFn | undefined;
+
+    listen<MessageNotification>("message-notification", (event) => {
+      // The user is already looking at it
+      if (event.payload.chatGuid === useChatStore.getState().selectedChatGuid) {
+        return;
+      }
+      playNotificationSound(notifSound);
+    }).then((fn) => {
+      unlisten = fn;
+    }).catch((err) => {
+      console.error("failed to listen for message notifications:", err);
+    });
+
+    return () => {
+      if (unlisten) {
+        unlisten();
+      }
+    };
+  }, [notificationsEnabled, soundOn, notifSound]);
+}
@@ -0,0 +1,42 @@
+/**
+ * Notification Digest Hook
+ *
+ * Shows the summaries the backend emits when a chat's held notifications
+ * reach the end of their digest window.
+ */
+import { useEffect } from "react";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { tauriSendNotification, type NotificationDigestReady } from "@/hooks/useTauri";
+import { useSettingsStore } from "@/store/settingsStore";
+
+/**
+ * Hook that shows a desktop notification for each digest, if
+ * notifications are enabled in settings.
+ */
+export function useNotificationDigests() {
+  const { settings } = useSettingsStore();
+  const notificationsEnabled = settings["notificationsEnabled"] !== "false";
+
+  useEffect(() => {
+    if (!notificationsEnabled) {
+      return;
+    }
+
+    let unlisten: UnlistenFn | undefined;
+
+    listen<NotificationDigestReady>("notification-digest", (event) => {
+      const { summary, body } = event.payload;
+      tauriSendNotification(summary, body).catch(() => {});
+    }).then((fn) => {
+      unlisten = fn;
+    }).catch((err) => {
+      console.error("failed to listen for notification digests:", err);
+    });
+
+    return () => {
+      if (unlisten) {
+        unlisten();
+      }
+    };
+  }, [notificationsEnabled]);
+}
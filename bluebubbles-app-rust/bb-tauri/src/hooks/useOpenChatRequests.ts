@@ -0,0 +1,35 @@
+/**
+ * Open Chat Request Hook
+ *
+ * Shows the chat of a message notification the user clicked.
+ */
+import { useEffect } from "react";
+import { useNavigate } from "react-router-dom";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { OpenChatRequest } from "@/hooks/useTauri";
+
+/**
+ * Hook that navigates to the chat named by each `open-chat` event. Must be
+ * used inside the router.
+ */
+export function useOpenChatRequests() {
+  const navigate = useNavigate();
+
+  useEffect(() => {
+    let unlisten: UnlistenFn | undefined;
+
+    listen<OpenChatRequest>("open-chat", (event) => {
+      navigate(`/chat/${encodeURIComponent(event.payload.chatGuid)}`);
+    }).then((fn) => {
+      unlisten = fn;
+    }).catch((err) => {
+      console.error("failed to listen for open chat requests:", err);
+    });
+
+    return () => {
+      if (unlisten) {
+        unlisten();
+      }
+    };
+  }, [navigate]);
+}
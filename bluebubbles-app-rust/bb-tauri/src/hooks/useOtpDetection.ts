@@ -13,10 +13,15 @@ import { useSettingsStore } from "@/store/settingsStore";
  * Payload structure for OTP detection events emitted from Rust.
  */
 interface OtpDetectionPayload {
+  messageGuid: string;
+  chatGuid: string;
   /** The detected OTP code (e.g., "123456") */
   code: string;
-  /** Text snippet from the message containing the OTP */
+  /** Who sent the code (contact name or address) */
   snippet: string;
+  sender: string | null;
+  /** When the code expires, in epoch milliseconds */
+  expiresAt: number;
 }
 
 /**
@@ -38,10 +43,10 @@ export function useOtpDetection() {
 
     // Listen for OTP detection events from Tauri backend
     listen<OtpDetectionPayload>("otp-detected", (event) => {
-      const { code, snippet } = event.payload;
+      const { code, snippet, expiresAt } = event.payload;
 
       // Show OTP in toast (which will auto-copy if enabled)
-      if (code) {
+      if (code && expiresAt > Date.now()) {
         showOtp(code, snippet || "");
       }
     }).then((fn) => {
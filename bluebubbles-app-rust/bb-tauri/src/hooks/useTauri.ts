@@ -53,6 +53,14 @@ export interface ChatWithPreview {
   latest_message_date: string | null;
   latest_message_is_from_me: boolean;
   participant_names: string[];
+  draft: Draft | null;
+}
+
+/** Unsent composer draft for a chat. */
+export interface Draft {
+  chat_guid: string;
+  text: string | null;
+  attachment_paths: string[];
 }
 
 /** Chat model matching the Rust Chat struct. */
@@ -80,6 +88,7 @@ export interface Handle {
   formatted_address: string | null;
   color: string | null;
   contact_id: number | null;
+  normalized_address: string | null;
 }
 
 /** Message model matching the Rust Message struct. */
@@ -106,6 +115,7 @@ export interface Message {
   thread_originator_guid: string | null;
   big_emoji: boolean | null;
   date_edited: string | null;
+  is_bookmarked: boolean;
   attachments: Attachment[];
   associated_messages: Message[];
 }
@@ -189,9 +199,14 @@ export async function tauriDetectLocalhost(): Promise<string | null> {
 
 export async function tauriGetChats(
   page: number,
-  limit: number
+  limit: number,
+  folderId?: number
 ): Promise<ChatWithPreview[]> {
-  return invoke<ChatWithPreview[]>("get_chats", { page, limit });
+  return invoke<ChatWithPreview[]>("get_chats", {
+    page,
+    limit,
+    folderId: folderId ?? null,
+  });
 }
 
 export async function tauriRefreshChats(
@@ -206,12 +221,70 @@ export async function tauriMarkChatRead(
   return invoke<void>("mark_chat_read", { chatGuid });
 }
 
+export async function tauriSetActiveChat(
+  chatGuid: string | null
+): Promise<void> {
+  return invoke<void>("set_active_chat", { chatGuid });
+}
+
 export async function tauriMarkChatUnread(
   chatGuid: string
 ): Promise<void> {
   return invoke<void>("mark_chat_unread", { chatGuid });
 }
 
+/** A chat change the server has not confirmed yet. */
+export type ChatMutation =
+  | { type: "mark_read" }
+  | { type: "mark_unread" }
+  | { type: "rename"; name: string }
+  | { type: "add_participant"; address: string }
+  | { type: "remove_participant"; address: string }
+  | { type: "delete" };
+
+/** A queued chat change, applied locally and waiting for the server. */
+export interface OutboxEntry {
+  id: number | null;
+  chat_guid: string;
+  mutation: ChatMutation;
+  previous: string | null;
+  attempts: number;
+  last_error: string | null;
+  date_created: string;
+}
+
+/** Result of sending queued chat changes. */
+export interface ReplayReport {
+  applied: number;
+  rolled_back: number;
+  remaining: number;
+}
+
+/** Payload of the `outbox-mutation-failed` event. */
+export interface OutboxMutationFailed {
+  chatGuid: string;
+  description: string;
+  error: string;
+}
+
+/** Where an outgoing message is on its way to the server. */
+export type OutgoingStatus = "waiting_for_connection" | "sending" | "sent" | "failed";
+
+/** Payload of the `message-status-changed` event. */
+export interface MessageStatusChanged {
+  tempGuid: string;
+  chatGuid: string;
+  status: OutgoingStatus;
+}
+
+export async function tauriGetOutbox(): Promise<OutboxEntry[]> {
+  return invoke<OutboxEntry[]>("get_outbox");
+}
+
+export async function tauriReplayOutbox(): Promise<ReplayReport> {
+  return invoke<ReplayReport>("replay_outbox");
+}
+
 export async function tauriUpdateChat(
   chatGuid: string,
   updates: Record<string, unknown>
@@ -254,6 +327,27 @@ export async function tauriDownloadAttachment(guid: string): Promise<string> {
   return invoke<string>("download_attachment", { guid });
 }
 
+/** Structured contents of a contact-card (vCard) attachment. */
+export interface ContactCard {
+  display_name: string;
+  structured_name: Record<string, string | null> | null;
+  phones: string[];
+  emails: string[];
+  avatar_base64: string | null;
+}
+
+/** Parse a contact-card attachment for preview. */
+export async function tauriGetContactCard(attachmentGuid: string): Promise<ContactCard[]> {
+  return invoke<ContactCard[]>("get_contact_card", { attachmentGuid });
+}
+
+/** Save a contact-card attachment into local contacts. */
+export async function tauriSaveContactCard(
+  attachmentGuid: string
+): Promise<{ added: number; updated: number }> {
+  return invoke<{ added: number; updated: number }>("save_contact_card", { attachmentGuid });
+}
+
 /** Send an attachment from raw bytes (base64). Used for pasted/dropped files. */
 export async function tauriSendAttachmentData(
   chatGuid: string,
@@ -329,6 +423,267 @@ export async function tauriCheckMessagesSynced(): Promise<boolean> {
   return invoke<boolean>("check_messages_synced");
 }
 
+// ─── Bookmark command wrappers ───────────────────────────────────────────────
+
+/** Tags and a note attached to a bookmarked message. */
+export interface Bookmark {
+  message_guid: string;
+  note: string | null;
+  tags: string[];
+  date_created: string;
+  date_modified: string;
+}
+
+/** A bookmark with the message it annotates. */
+export interface BookmarkedMessage {
+  bookmark: Bookmark;
+  message: Message;
+  chat_guid: string;
+  chat_title: string;
+}
+
+/** Filters for listing bookmarks; omitted fields match everything. */
+export interface BookmarkQuery {
+  tag?: string;
+  chatGuid?: string;
+  query?: string;
+  limit?: number;
+}
+
+export async function tauriGetBookmarks(filter: BookmarkQuery = {}): Promise<BookmarkedMessage[]> {
+  return invoke<BookmarkedMessage[]>("get_bookmarks", {
+    tag: filter.tag ?? null,
+    chatGuid: filter.chatGuid ?? null,
+    query: filter.query ?? null,
+    limit: filter.limit ?? null,
+  });
+}
+
+export async function tauriBookmarkMessage(
+  messageGuid: string,
+  tags: string[] = [],
+  note: string | null = null
+): Promise<BookmarkedMessage> {
+  return invoke<BookmarkedMessage>("bookmark_message", { messageGuid, tags, note });
+}
+
+export async function tauriSetBookmarkNote(messageGuid: string, note: string | null): Promise<BookmarkedMessage> {
+  return invoke<BookmarkedMessage>("set_bookmark_note", { messageGuid, note });
+}
+
+export async function tauriAddBookmarkTags(messageGuid: string, tags: string[]): Promise<BookmarkedMessage> {
+  return invoke<BookmarkedMessage>("add_bookmark_tags", { messageGuid, tags });
+}
+
+export async function tauriRemoveBookmarkTags(messageGuid: string, tags: string[]): Promise<BookmarkedMessage> {
+  return invoke<BookmarkedMessage>("remove_bookmark_tags", { messageGuid, tags });
+}
+
+export async function tauriRemoveBookmark(messageGuid: string): Promise<boolean> {
+  return invoke<boolean>("remove_bookmark", { messageGuid });
+}
+
+export async function tauriGetBookmarkTags(): Promise<{ tag: string; count: number }[]> {
+  return invoke<{ tag: string; count: number }[]>("get_bookmark_tags");
+}
+
+// ─── Chat folder command wrappers ────────────────────────────────────────────
+
+/** Rule selecting the chats of a smart folder. */
+export type FolderRule =
+  | { type: "group" }
+  | { type: "direct" }
+  | { type: "min_participants"; count: number }
+  | { type: "unknown_senders" }
+  | { type: "unread" }
+  | { type: "pinned" }
+  | { type: "muted" }
+  | { type: "service"; name: string }
+  | { type: "name_contains"; text: string }
+  | { type: "all"; rules: FolderRule[] }
+  | { type: "any"; rules: FolderRule[] }
+  | { type: "not"; rule: FolderRule };
+
+/** A manual folder, or a smart folder when `rule` is set. */
+export interface ChatFolder {
+  id: number | null;
+  name: string;
+  color: string | null;
+  rule: FolderRule | null;
+  sort_index: number;
+  date_created: string;
+}
+
+/** A folder with its chat and unread counts. */
+export interface FolderSummary {
+  folder: ChatFolder;
+  chat_count: number;
+  unread_chats: number;
+  unread_count: number;
+}
+
+export async function tauriGetChatFolders(): Promise<FolderSummary[]> {
+  return invoke<FolderSummary[]>("get_chat_folders");
+}
+
+export async function tauriCreateChatFolder(
+  name: string,
+  color?: string,
+  rule?: FolderRule
+): Promise<ChatFolder> {
+  return invoke<ChatFolder>("create_chat_folder", {
+    name,
+    color: color ?? null,
+    rule: rule ?? null,
+  });
+}
+
+/** Update a folder; omitted fields are unchanged, an empty color clears it. */
+export async function tauriUpdateChatFolder(
+  folderId: number,
+  changes: { name?: string; color?: string; rule?: FolderRule }
+): Promise<ChatFolder> {
+  return invoke<ChatFolder>("update_chat_folder", {
+    folderId,
+    name: changes.name ?? null,
+    color: changes.color ?? null,
+    rule: changes.rule ?? null,
+  });
+}
+
+export async function tauriReorderChatFolders(folderIds: number[]): Promise<void> {
+  return invoke<void>("reorder_chat_folders", { folderIds });
+}
+
+export async function tauriDeleteChatFolder(folderId: number): Promise<boolean> {
+  return invoke<boolean>("delete_chat_folder", { folderId });
+}
+
+export async function tauriAddChatToFolder(
+  folderId: number,
+  chatGuid: string
+): Promise<void> {
+  return invoke<void>("add_chat_to_folder", { folderId, chatGuid });
+}
+
+export async function tauriRemoveChatFromFolder(
+  folderId: number,
+  chatGuid: string
+): Promise<boolean> {
+  return invoke<boolean>("remove_chat_from_folder", { folderId, chatGuid });
+}
+
+export async function tauriGetFoldersForChat(chatGuid: string): Promise<ChatFolder[]> {
+  return invoke<ChatFolder[]>("get_folders_for_chat", { chatGuid });
+}
+
+// ─── Person command wrappers ────────────────────────────────────────────────
+
+/** One person across all of their handles. */
+export interface PersonSummary {
+  id: number;
+  display_name: string;
+  name: string | null;
+  contact_id: number | null;
+  handles: Handle[];
+}
+
+/** List people, refreshing automatic handle links first. */
+export async function tauriGetPeople(): Promise<PersonSummary[]> {
+  return invoke<PersonSummary[]>("get_people");
+}
+
+/** Find the person owning an address on any service. */
+export async function tauriGetPersonForAddress(address: string): Promise<PersonSummary | null> {
+  return invoke<PersonSummary | null>("get_person_for_address", { address });
+}
+
+/** One-to-one chats with a person across all of their handles. */
+export async function tauriGetPersonChats(personId: number): Promise<Chat[]> {
+  return invoke<Chat[]>("get_person_chats", { personId });
+}
+
+/** Search messages exchanged with a person. */
+export async function tauriSearchPersonMessages(personId: number, query: string): Promise<Message[]> {
+  return invoke<Message[]>("search_person_messages", { personId, query });
+}
+
+/** Merge the source person into the target person. */
+export async function tauriMergePeople(targetId: number, sourceId: number): Promise<PersonSummary> {
+  return invoke<PersonSummary>("merge_people", { targetId, sourceId });
+}
+
+/** Move a handle out into a person of its own. */
+export async function tauriSplitPersonHandle(handleId: number): Promise<PersonSummary> {
+  return invoke<PersonSummary>("split_person_handle", { handleId });
+}
+
+/** Set or clear a person's name. */
+export async function tauriRenamePerson(personId: number, name: string | null): Promise<PersonSummary> {
+  return invoke<PersonSummary>("rename_person", { personId, name });
+}
+
+// ─── Trash command wrappers ─────────────────────────────────────────────────
+
+/** A deleted chat or message awaiting restore or purge. */
+export interface TrashItem {
+  kind: "chat" | "message";
+  guid: string;
+  chat_guid: string;
+  title: string;
+  preview: string | null;
+  date_deleted: string;
+  purge_after: string | null;
+}
+
+/** What a trash purge removed. */
+export interface PurgeReport {
+  chats_purged: number;
+  messages_purged: number;
+  attachments_deleted: number;
+  files_removed: number;
+  bytes_freed: number;
+}
+
+export async function tauriGetTrash(): Promise<TrashItem[]> {
+  return invoke<TrashItem[]>("get_trash");
+}
+
+export async function tauriTrashChat(chatGuid: string): Promise<void> {
+  return invoke<void>("trash_chat", { chatGuid });
+}
+
+export async function tauriTrashMessage(messageGuid: string): Promise<void> {
+  return invoke<void>("trash_message", { messageGuid });
+}
+
+export async function tauriRestoreFromTrash(guid: string): Promise<"chat" | "message"> {
+  return invoke<"chat" | "message">("restore_from_trash", { guid });
+}
+
+export async function tauriPurgeTrash(guid?: string): Promise<PurgeReport> {
+  return invoke<PurgeReport>("purge_trash", { guid: guid ?? null });
+}
+
+// ─── Snapshot command wrappers ───────────────────────────────────────────────
+
+/** A local database snapshot. */
+export interface SnapshotInfo {
+  id: string;
+  path: string;
+  created_at: string;
+  size_bytes: number;
+}
+
+export async function tauriListSnapshots(): Promise<SnapshotInfo[]> {
+  return invoke<SnapshotInfo[]>("list_snapshots");
+}
+
+/** Restore the database from a snapshot; services restart around it. */
+export async function tauriRestoreSnapshot(id: string): Promise<SnapshotInfo> {
+  return invoke<SnapshotInfo>("restore_snapshot", { id });
+}
+
 // ─── Private API command wrappers ────────────────────────────────────────────
 
 /** Private API status returned from check_private_api_status. */
@@ -388,8 +743,8 @@ export async function tauriEditMessage(
 export async function tauriUnsendMessage(
   messageGuid: string,
   partIndex: number
-): Promise<unknown> {
-  return invoke<unknown>("unsend_message", { messageGuid, partIndex });
+): Promise<void> {
+  return invoke("unsend_message", { messageGuid, partIndex });
 }
 
 /** Sync progress event payload. */
@@ -410,18 +765,72 @@ export async function onSyncComplete(callback: (totalMessages: number) => void):
   return listen<number>("sync-complete", (event) => callback(event.payload));
 }
 
+// ─── Draft command wrappers ──────────────────────────────────────────────────
+
+/** Save the composer draft for a chat. Empty text and no attachments clears it. */
+export async function tauriSaveDraft(
+  chatGuid: string,
+  text: string | null,
+  attachmentPaths: string[] = []
+): Promise<void> {
+  return invoke<void>("save_draft", { chatGuid, text, attachmentPaths });
+}
+
+/** Load the composer draft for a chat. */
+export async function tauriGetDraft(chatGuid: string): Promise<Draft | null> {
+  return invoke<Draft | null>("get_draft", { chatGuid });
+}
+
+/** Clear the composer draft for a chat. */
+export async function tauriClearDraft(chatGuid: string): Promise<void> {
+  return invoke<void>("clear_draft", { chatGuid });
+}
+
+/** List all chats that have an unsent draft. */
+export async function tauriGetDrafts(): Promise<Draft[]> {
+  return invoke<Draft[]>("get_drafts");
+}
+
 // ─── Scheduled message command wrappers ─────────────────────────────────────
 
-/** Create a scheduled message. */
+/** A schedule sent by the app's own scheduler (negative `id`). */
+export interface LocalScheduledMessage {
+  id: number;
+  schedule_type: string;
+  chat_guid: string;
+  message: string;
+  scheduled_for: string;
+  /** Cron expression or RRULE, for recurring schedules. */
+  recurrence: string | null;
+  /** IANA time zone the recurrence is evaluated in. */
+  time_zone: string | null;
+  recurrence_start: string | null;
+  status: "pending" | "sent" | "failed" | "cancelled" | "skipped" | "queued";
+  error: string | null;
+  sent_at: string | null;
+  created_at: string;
+}
+
+/**
+ * Create a scheduled message; `local` schedules are sent by the app.
+ * `recurrence` is a cron expression or RRULE evaluated in `timeZone`
+ * (default: the system time zone).
+ */
 export async function tauriCreateScheduledMessage(
   chatGuid: string,
   message: string,
-  scheduledFor: number
+  scheduledFor: number,
+  local?: boolean,
+  recurrence?: string,
+  timeZone?: string
 ): Promise<unknown> {
   return invoke<unknown>("create_scheduled_message", {
     chatGuid,
     message,
     scheduledFor,
+    local,
+    recurrence,
+    timeZone,
   });
 }
 
@@ -430,6 +839,11 @@ export async function tauriGetScheduledMessages(): Promise<unknown[]> {
   return invoke<unknown[]>("get_scheduled_messages");
 }
 
+/** Get pending local scheduled messages. */
+export async function tauriGetLocalScheduledMessages(): Promise<LocalScheduledMessage[]> {
+  return invoke<LocalScheduledMessage[]>("get_local_scheduled_messages");
+}
+
 /** Delete a scheduled message by ID. */
 export async function tauriDeleteScheduledMessage(id: number): Promise<void> {
   return invoke<void>("delete_scheduled_message", { id });
@@ -466,6 +880,216 @@ export async function tauriRegenerateMcpToken(): Promise<string> {
   return invoke<string>("regenerate_mcp_token");
 }
 
+// ─── Notification rule command wrappers ─────────────────────────────────────
+
+/** A condition on an incoming message; a rule needs all of its conditions. */
+export type RuleCondition =
+  | { type: "chat"; guid: string }
+  | { type: "sender"; address: string }
+  | { type: "person"; id: number }
+  | { type: "text_matches"; pattern: string }
+  | { type: "attachment"; mime: string }
+  | { type: "mentions_me" };
+
+export type RuleAction = "notify" | "silence" | "priority" | "digest";
+
+/** A notification rule; the first enabled match in order decides. */
+export interface NotificationRule {
+  id: number | null;
+  name: string;
+  conditions: RuleCondition[];
+  action: RuleAction;
+  enabled: boolean;
+  sort_index: number;
+  date_created: string;
+}
+
+/** Local-time window (HH:MM) when only priority and breakthrough senders notify. */
+export interface QuietHours {
+  enabled: boolean;
+  start: string;
+  end: string;
+  breakthrough: string[];
+}
+
+export interface NotificationDecision {
+  action: RuleAction;
+  rule_id: number | null;
+  rule_name: string | null;
+  reason: string;
+}
+
+export async function tauriGetNotificationRules(): Promise<NotificationRule[]> {
+  return invoke<NotificationRule[]>("get_notification_rules");
+}
+
+export async function tauriAddNotificationRule(
+  name: string,
+  conditions: RuleCondition[],
+  action: RuleAction
+): Promise<NotificationRule> {
+  return invoke<NotificationRule>("add_notification_rule", { name, conditions, action });
+}
+
+/** Update a rule; omitted fields are unchanged. */
+export async function tauriUpdateNotificationRule(
+  ruleId: number,
+  changes: { name?: string; conditions?: RuleCondition[]; action?: RuleAction; enabled?: boolean }
+): Promise<NotificationRule> {
+  return invoke<NotificationRule>("update_notification_rule", {
+    ruleId,
+    name: changes.name ?? null,
+    conditions: changes.conditions ?? null,
+    action: changes.action ?? null,
+    enabled: changes.enabled ?? null,
+  });
+}
+
+/** Move a rule to a 0-based position; returns the new order. */
+export async function tauriMoveNotificationRule(ruleId: number, position: number): Promise<NotificationRule[]> {
+  return invoke<NotificationRule[]>("move_notification_rule", { ruleId, position });
+}
+
+export async function tauriDeleteNotificationRule(ruleId: number): Promise<boolean> {
+  return invoke<boolean>("delete_notification_rule", { ruleId });
+}
+
+export async function tauriGetQuietHours(): Promise<QuietHours> {
+  return invoke<QuietHours>("get_quiet_hours");
+}
+
+export async function tauriSetQuietHours(quietHours: QuietHours): Promise<void> {
+  return invoke<void>("set_quiet_hours", { quietHours });
+}
+
+/**
+ * How a received message should notify, or null if it isn't stored yet.
+ * Digest messages are held for their chat's summary.
+ */
+export async function tauriEvaluateNotification(
+  messageGuid: string,
+  chatGuid: string
+): Promise<NotificationDecision | null> {
+  return invoke<NotificationDecision | null>("evaluate_notification", { messageGuid, chatGuid });
+}
+
+/** How held digest messages are collected and summarized. */
+export interface DigestSettings {
+  window_secs: number;
+  preview_lines: number;
+  group_chats: boolean;
+}
+
+/** One chat's summary of held messages. */
+export interface NotificationDigest {
+  chat_guid: string;
+  chat_title: string;
+  message_count: number;
+  sender_count: number;
+  reactions: Record<string, number>;
+  lines: string[];
+  message_guids: string[];
+}
+
+/** Payload of the `message-notification` event. */
+export interface MessageNotification {
+  chatGuid: string;
+  messageGuid: string;
+  title: string;
+  body: string;
+  priority: boolean;
+}
+
+/** Payload of the `open-chat` event, sent when a notification is clicked. */
+export interface OpenChatRequest {
+  chatGuid: string;
+}
+
+/** Payload of the `notification-digest` event. */
+export interface NotificationDigestReady {
+  chatGuid: string;
+  summary: string;
+  body: string;
+}
+
+export async function tauriGetDigestSettings(): Promise<DigestSettings> {
+  return invoke<DigestSettings>("get_digest_settings");
+}
+
+export async function tauriSetDigestSettings(settings: DigestSettings): Promise<void> {
+  return invoke<void>("set_digest_settings", { settings });
+}
+
+/** Take every held digest now; they are returned, not shown. */
+export async function tauriFlushNotificationDigests(): Promise<NotificationDigest[]> {
+  return invoke<NotificationDigest[]>("flush_notification_digests");
+}
+
+// ─── Smart replies ───────────────────────────────────────────────────────────
+
+export type ReplyIntent =
+  | "thanks"
+  | "time_proposal"
+  | "greeting"
+  | "yes_no_question"
+  | "open_question"
+  | "statement";
+
+/** A suggested reply; "history" ones are things you have sent before. */
+export interface SmartReply {
+  text: string;
+  source: "template" | "history";
+}
+
+/** Suggestions for a chat's latest incoming message. */
+export interface SmartReplies {
+  chat_guid: string;
+  message_guid: string;
+  intent: ReplyIntent;
+  suggestions: SmartReply[];
+}
+
+/** Suggest replies for a chat. Null when smart replies are off or in incognito mode. */
+export async function tauriGetSmartReplies(chatGuid: string): Promise<SmartReplies | null> {
+  return invoke<SmartReplies | null>("get_smart_replies", { chatGuid });
+}
+
+// ─── One-time codes ──────────────────────────────────────────────────────────
+
+/** How one-time codes are recognized. */
+export interface OtpSettings {
+  /** Extra regexes tried before the built-in ones; group 1 is the code. */
+  custom_patterns: string[];
+  /** Only detect codes from these senders. Empty accepts everyone. */
+  allowed_senders: string[];
+  default_expiry_secs: number;
+}
+
+/** A code found in a received message. */
+export interface OtpCode {
+  message_guid: string;
+  chat_guid: string;
+  code: string;
+  pattern: string;
+  sender: string | null;
+  /** Epoch milliseconds. */
+  expires_at: number;
+}
+
+/** Check a chat's latest received message for a code. Emits "otp-detected" when found. */
+export async function tauriDetectChatOtp(chatGuid: string): Promise<OtpCode | null> {
+  return invoke<OtpCode | null>("detect_chat_otp", { chatGuid });
+}
+
+export async function tauriGetOtpSettings(): Promise<OtpSettings> {
+  return invoke<OtpSettings>("get_otp_settings");
+}
+
+/** Save OTP settings. Rejects invalid regex patterns. */
+export async function tauriSetOtpSettings(settings: OtpSettings): Promise<void> {
+  return invoke("set_otp_settings", { settings });
+}
+
 // ─── Notification helpers ────────────────────────────────────────────────────
 
 /** Send a native desktop notification via Tauri plugin. */
@@ -8,8 +8,12 @@ import { Outlet } from "react-router-dom";
 import { TitleBar } from "./TitleBar";
 import { Sidebar } from "./Sidebar";
 import { ConversationList } from "@/pages/ConversationList";
+import { useOpenChatRequests } from "@/hooks/useOpenChatRequests";
 
 export function AppLayout() {
+  // Show the chat of a clicked message notification
+  useOpenChatRequests();
+
   const sidebarWidth = 315;
   const containerStyle: CSSProperties = {
     display: "flex",
@@ -517,6 +517,8 @@ function GeneralPanel() {
   const { sendWithReturn, tabletMode, updateSetting, settings } = useSettingsStore();
   const autoOpenKeyboard = settings["autoOpenKeyboard"] !== "false";
   const generateLinkPreviews = settings["generateLinkPreviews"] !== "false";
+  const smartReply = settings["smartReply"] === "true";
+  const defaultPhoneRegion = settings["defaultPhoneRegion"] ?? "";
 
   return (
     <>
@@ -533,6 +535,12 @@ function GeneralPanel() {
           value={autoOpenKeyboard}
           onChange={(v) => updateSetting("autoOpenKeyboard", String(v))}
         />
+        <SettingsSwitch
+          label="Smart Replies"
+          subtitle="Suggest short replies learned on this device from messages you've sent. Always off in incognito mode."
+          value={smartReply}
+          onChange={(v) => updateSetting("smartReply", String(v))}
+        />
       </SettingsSection>
 
       <SettingsSection title="Layout">
@@ -544,10 +552,36 @@ function GeneralPanel() {
         />
       </SettingsSection>
 
+      <SettingsSection title="Contacts">
+        <SettingsDropdown
+          label="Default Phone Region"
+          subtitle="Country assumed for phone numbers saved without a country code"
+          value={defaultPhoneRegion}
+          options={[
+            { label: "System Default", value: "" },
+            { label: "United States", value: "US" },
+            { label: "Canada", value: "CA" },
+            { label: "United Kingdom", value: "GB" },
+            { label: "Ireland", value: "IE" },
+            { label: "Australia", value: "AU" },
+            { label: "New Zealand", value: "NZ" },
+            { label: "Germany", value: "DE" },
+            { label: "France", value: "FR" },
+            { label: "Spain", value: "ES" },
+            { label: "Italy", value: "IT" },
+            { label: "Netherlands", value: "NL" },
+            { label: "India", value: "IN" },
+            { label: "Mexico", value: "MX" },
+            { label: "Brazil", value: "BR" },
+          ]}
+          onChange={(v) => updateSetting("defaultPhoneRegion", v)}
+        />
+      </SettingsSection>
+
       <SettingsSection title="Links">
         <SettingsSwitch
           label="Generate Link Previews"
-          subtitle="Show rich previews for URLs shared in messages"
+          subtitle="Fetch titles and images for links you send so they arrive as rich previews. Turn off to never contact linked sites."
           value={generateLinkPreviews}
           onChange={(v) => updateSetting("generateLinkPreviews", String(v))}
         />
@@ -2304,6 +2338,32 @@ function AboutPanel() {
         />
       </SettingsSection>
 
+      <SettingsSection title="Contacts">
+        <SettingsDropdown
+          label="Default Phone Region"
+          subtitle="Country assumed for phone numbers saved without a country code"
+          value={defaultPhoneRegion}
+          options={[
+            { label: "System Default", value: "" },
+            { label: "United States", value: "US" },
+            { label: "Canada", value: "CA" },
+            { label: "United Kingdom", value: "GB" },
+            { label: "Ireland", value: "IE" },
+            { label: "Australia", value: "AU" },
+            { label: "New Zealand", value: "NZ" },
+            { label: "Germany", value: "DE" },
+            { label: "France", value: "FR" },
+            { label: "Spain", value: "ES" },
+            { label: "Italy", value: "IT" },
+            { label: "Netherlands", value: "NL" },
+            { label: "India", value: "IN" },
+            { label: "Mexico", value: "MX" },
+            { label: "Brazil", value: "BR" },
+          ]}
+          onChange={(v) => updateSetting("defaultPhoneRegion", v)}
+        />
+      </SettingsSection>
+
       <SettingsSection title="Links">
         <SettingsTile
           label="GitHub Repository"
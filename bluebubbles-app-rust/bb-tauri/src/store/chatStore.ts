@@ -3,9 +3,7 @@
  */
 import { create } from "zustand";
 import type { ChatWithPreview } from "@/hooks/useTauri";
-import { tauriGetChats, tauriRefreshChats, tauriMarkChatRead, tauriMarkChatUnread, tauriUpdateChat, tauriSendNotification } from "@/hooks/useTauri";
-import { playNotificationSound } from "@/utils/notificationSound";
-import { useSettingsStore } from "./settingsStore";
+import { tauriGetChats, tauriRefreshChats, tauriMarkChatRead, tauriMarkChatUnread, tauriSetActiveChat, tauriUpdateChat, tauriDetectChatOtp } from "@/hooks/useTauri";
 
 interface ChatState {
   chats: ChatWithPreview[];
@@ -85,53 +83,22 @@ export const useChatStore = create<ChatState>((set, get) => ({
       const freshChats = await tauriRefreshChats(PAGE_SIZE);
       const { chats: currentChats, selectedChatGuid } = get();
 
-      // Detect new incoming messages by comparing latest_message_date
+      // Detect new incoming messages by comparing latest_message_date.
+      // Notifications come from the backend, which runs every received
+      // message through the notification rules.
       const oldChatMap = new Map(currentChats.map((c) => [c.chat.guid, c]));
-      let newIncomingCount = 0;
-      let lastIncomingText: string | null = null;
 
       for (const fresh of freshChats) {
         const old = oldChatMap.get(fresh.chat.guid);
         if (!old) continue;
 
-        // Skip if this is the currently selected chat (user is already looking at it)
-        if (fresh.chat.guid === selectedChatGuid) continue;
-
-        // Skip muted chats
-        if (fresh.chat.mute_type != null) continue;
-
-        // Check if there's a newer message that's NOT from me
+        // Verification codes are surfaced even for the open chat
         if (
-          fresh.latest_message_date &&
           fresh.latest_message_is_from_me === false &&
+          fresh.latest_message_date &&
           (!old.latest_message_date || fresh.latest_message_date > old.latest_message_date)
         ) {
-          newIncomingCount++;
-          lastIncomingText = fresh.latest_message_text;
-        }
-      }
-
-      // Fire notification sound + desktop notification for new incoming messages
-      if (newIncomingCount > 0) {
-        const s = useSettingsStore.getState().settings;
-        const soundOn = s["soundEnabled"] !== "false";
-        const notifSound = s["notifSound"] || "default";
-        const notifEnabled = s["notificationsEnabled"] !== "false";
-
-        if (soundOn && notifSound !== "none") {
-          playNotificationSound(notifSound);
-        }
-
-        if (notifEnabled) {
-          const showPreview = s["notifShowPreview"] !== "false";
-          const title = newIncomingCount === 1 ? "New Message" : `${newIncomingCount} New Messages`;
-          const body =
-            showPreview && lastIncomingText
-              ? lastIncomingText
-              : newIncomingCount === 1
-                ? "New message received."
-                : `${newIncomingCount} new messages received.`;
-          tauriSendNotification(title, body).catch(() => {});
+          tauriDetectChatOtp(fresh.chat.guid).catch(() => null);
         }
       }
 
@@ -180,7 +147,11 @@ export const useChatStore = create<ChatState>((set, get) => ({
     }
   },
 
-  selectChat: (guid) => set({ selectedChatGuid: guid }),
+  selectChat: (guid) => {
+    set({ selectedChatGuid: guid });
+    // The backend skips notifications for the open chat
+    tauriSetActiveChat(guid).catch(() => {});
+  },
 
   setSearchQuery: (query) => set({ searchQuery: query }),
 
@@ -3,10 +3,10 @@
  */
 import { create } from "zustand";
 import type { Message } from "@/hooks/useTauri";
-import { tauriGetMessages, tauriSendMessage, tauriSendAttachmentData, tauriSendAttachmentMessage, tauriSendNotification } from "@/hooks/useTauri";
+import { tauriGetMessages, tauriSendMessage, tauriSendAttachmentData, tauriSendAttachmentMessage } from "@/hooks/useTauri";
 import { useChatStore } from "./chatStore";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
-import { playSentSound, playEffectSound, playNotificationSound, playReactionSound } from "@/utils/notificationSound";
+import { playSentSound, playEffectSound } from "@/utils/notificationSound";
 import { useSettingsStore } from "./settingsStore";
 
 interface MessageState {
@@ -327,34 +327,6 @@ export const useMessageStore = create<MessageState>((set, get) => ({
       date_created: message.date_created,
       is_from_me: message.is_from_me,
     });
-
-    // Play notification sound + send desktop notification for incoming messages
-    if (!message.is_from_me) {
-      const s = useSettingsStore.getState().settings;
-      const notifEnabled = s["notificationsEnabled"] !== "false";
-      const soundOn = s["soundEnabled"] !== "false";
-      const notifSound = s["notifSound"] || "default";
-
-      // Check if it's a reaction (associated_message_type set)
-      const isReaction = message.associated_message_type != null && message.associated_message_type !== "";
-
-      if (isReaction) {
-        if (soundOn && s["notifyReactions"] !== "false") {
-          playReactionSound(false);
-        }
-      } else {
-        if (soundOn && notifSound !== "none") {
-          playNotificationSound(notifSound);
-        }
-        if (notifEnabled) {
-          const showSender = s["notifShowSender"] !== "false";
-          const showPreview = s["notifShowPreview"] !== "false";
-          const title = showSender ? "New Message" : "BlueBubbles";
-          const body = showPreview && message.text ? message.text : "New message received.";
-          tauriSendNotification(title, body).catch(() => {});
-        }
-      }
-    }
   },
 
   addOptimisticReaction: (messageGuid: string, reaction: string) => {